@@ -0,0 +1,34 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Decoded } from "./transactions";
+
+export type AccountResponse = { address: string, owner: string, owner_name?: string | null, lamports: number | string, executable: boolean, rent_epoch: number, space: number, data: string, decoded?: Decoded | null, };
+
+export type ConsistencyQuery = { consistent: boolean, };
+
+export type IdlSummary = { program_id: string, name: string, instructions: Array<string>, accounts: Array<string>, events: Array<string>, };
+
+export type IdlUploadQuery = { program_id?: string | null, };
+
+export type IndexStatus = { program: string, owner_offset: number, accounts: number, owners: number, reconciled_at_ms?: number | null, live: boolean, };
+
+export type IndexStatusResponse = { programs: Array<IndexStatus>, };
+
+export type IndexedAccount = { address: string, lamports: number | string, slot: number, };
+
+export type IndexedAccountsQuery = { owner: string, };
+
+export type IndexedAccountsResponse = { program: string, owner: string, accounts: Array<IndexedAccount>, reconciled_at_ms: number, live: boolean, };
+
+export type KnownProgram = { program_id: string, name: string, source: ProgramSource, };
+
+export type ProgramAccountsFormat = "json" | "ndjson" | "json_array";
+
+export type ProgramAccountsQuery = { limit?: number | null, format: ProgramAccountsFormat, };
+
+export type ProgramAccountsResponse = { program: string, program_name?: string | null, total: number, accounts: Array<AccountResponse>, };
+
+export type ProgramSource = "builtin" | "custom";
+
+export type ProgramsResponse = { programs: Array<KnownProgram>, };
+
+export type SetProgramNameRequest = { name: string, };
@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type Actor = { source: string, ip?: string | null, forwarded_for?: string | null, subject?: string | null, org?: string | null, };
+
+export type AllowlistAddRequest = { wallets: Array<string>, };
+
+export type AllowlistAddResponse = { added: Array<string>, total: number, };
+
+export type AllowlistResponse = { enforced: boolean, wallets: Array<string>, };
+
+export type AuditQuery = { action?: string | null, org?: string | null, since_ms?: number | null, limit?: number | null, };
+
+export type AuditQueryResponse = { records: Array<AuditRecord>, };
+
+export type AuditRecord = { timestamp_ms: number, action: string, actor: Actor, params: JsonValue, outcome: string, error?: string | null, signature?: string | null, receipt?: string | null, };
+
+export type ExportReport = { trigger: string, started_at_ms: number, finished_at_ms: number, objects: Array<ExportedObject>, error?: string | null, };
+
+export type ExportedObject = { key: string, rows: number, bytes: number, };
@@ -0,0 +1,65 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Actor } from "./admin";
+import type { ExplorerUrls } from "./transactions";
+
+export type AirdropReply = AirdropResponse | PendingAirdropResponse | DryRunAirdropResponse;
+
+export type AirdropRequest = { address: string, amount_sol?: number | null, lamports?: number | string | null, callback_url?: string | null, dry_run: boolean, wait?: boolean | null, };
+
+export type AirdropResponse = { success: boolean, message: string, wallet: string, airdrop_amount_sol: number, airdrop_amount_lamports: number | string, transaction_signature: string, explorer_url: string, explorer_urls?: ExplorerUrls | null, receipt_id?: string | null, commitment?: string | null, confirmed_balance_lamports?: number | string | null, projected_balance_lamports?: number | string | null, status_url?: string | null, };
+
+export type Approval = { id: string, wallet: string, lamports: number | string, status: ApprovalStatus, requested_by: Actor, requested_at_ms: number, callback_url?: string | null, decided_by?: string | null, decided_at_ms?: number | null, reason?: string | null, signature?: string | null, error?: string | null, };
+
+export type ApprovalStatus = "pending" | "approved" | "rejected" | "failed";
+
+export type ApprovalsQuery = { status?: ApprovalStatus | null, };
+
+export type ApprovalsResponse = { total: number, approvals: Array<Approval>, };
+
+export type AuxBalance = { address: string, balance_lamports?: number | string | null, error?: string | null, };
+
+export type BalanceRow = { address: string, lamports?: number | string | null, sol_decimal?: string | null, exists?: boolean | null, error?: string | null, };
+
+export type BalancesFormat = "ndjson" | "csv";
+
+export type BulkBalancesQuery = { format: BalancesFormat, };
+
+export type DryRunAirdropResponse = { success: boolean, dry_run: boolean, message: string, wallet: string, airdrop_amount_lamports: number | string, source: string, needs_approval: boolean, wallet_balance_lamports: number | string, resulting_wallet_balance_lamports: number | string, estimated_fee_lamports?: number | string | null, treasury_balance_lamports?: number | string | null, resulting_treasury_balance_lamports?: number | string | null, simulation?: SimulationResult | null, };
+
+export type Movement = { address: string, balance_lamports: number | string, moved_lamports: number | string, signature?: string | null, explorer_urls?: ExplorerUrls | null, error?: string | null, };
+
+export type Outflow = { signature: string, slot: number, block_time?: number | null, lamports: number | string, explorer_urls: ExplorerUrls, };
+
+export type PendingAirdropResponse = { success: boolean, message: string, wallet: string, airdrop_amount_lamports: number | string, approval_id: string, status: ApprovalStatus, status_url: string, };
+
+export type QuotaReport = { daily_airdrop_sol?: number | null, used_today_sol: number, used_today_lamports: number | string, remaining_today_sol?: number | null, remaining_today_lamports?: number | string | null, resets_at_ms: number, };
+
+export type RebalanceRequest = { target_lamports: number | string, };
+
+export type RebalanceResponse = { target_lamports: number | string, movements: Array<Movement>, };
+
+export type RejectRequest = { reason?: string | null, };
+
+export type RouteRpcReport = { route: string, requests: number, rpc_calls: number, rpc_calls_per_request: number, };
+
+export type RowError = { line: number, error: string, };
+
+export type RowResult = { line: number, wallet: string, lamports: number | string, signature?: string | null, receipt_id?: string | null, error?: string | null, };
+
+export type RpcBudgetReport = { daily_calls?: number | null, used_today: number, remaining_today?: number | null, };
+
+export type SimulationResult = { error?: string | null, units_consumed?: number | null, logs: Array<string>, };
+
+export type Topup = { wallet: string, min_lamports: number | string, target_lamports: number | string, registered_by?: string | null, registered_at_ms: number, last_run?: TopupRun | null, };
+
+export type TopupOutcome = "skipped" | "topped_up" | "failed";
+
+export type TopupRequest = { wallet: string, min_sol: number, target_sol?: number | null, };
+
+export type TopupRun = { checked_at_ms: number, outcome: TopupOutcome, balance_lamports?: number | string | null, amount_lamports?: number | string | null, source?: string | null, signature?: string | null, error?: string | null, };
+
+export type TreasuryQuery = { limit?: number | null, };
+
+export type TreasuryResponse = { address: string, balance_lamports: number | string, balance_sol_decimal: string, low_watermark_sol?: number | null, paused: boolean, auxiliary: Array<AuxBalance>, recent_outflows: Array<Outflow>, };
+
+export type UsageReport = { subject: string, requests: number, errors: number, error_rate: number, airdrops: number, airdropped_sol: number, airdropped_lamports: number | string, last_seen_ms: number, quota: QuotaReport, rpc_calls: number, rpc_budget: RpcBudgetReport, };
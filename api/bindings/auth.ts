@@ -0,0 +1,36 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { UsageReport } from "./airdrop";
+
+export type CreateKeyRequest = { role: Role, org?: string | null, label?: string | null, };
+
+export type CreateOrgRequest = { id: string, name: string, airdrop_daily_quota_sol?: number | null, webhooks: Array<string>, };
+
+export type IssueKeyRequest = { role: Role, label?: string | null, };
+
+export type IssuedKeyResponse = { api_key: string, id: number, role: Role, org?: string | null, label?: string | null, created_at_ms: number, rotated_at_ms?: number | null, };
+
+export type KeyInfo = { id: number, role: Role, org?: string | null, label?: string | null, created_at_ms: number, rotated_at_ms?: number | null, };
+
+export type KeysQuery = { org?: string | null, };
+
+export type KeysResponse = { total: number, keys: Array<KeyInfo>, };
+
+export type Org = { id: string, name: string, airdrop_daily_quota_sol?: number | null, webhooks: Array<string>, created_at_ms: number, };
+
+export type OrgReport = { id: string, name: string, airdrop_daily_quota_sol?: number | null, webhooks: Array<string>, created_at_ms: number, keys: Array<KeyInfo>, watchlists: number, usage: UsageReport, };
+
+export type OrgSettings = { name: string, airdrop_daily_quota_sol?: number | null, webhooks: Array<string>, };
+
+export type OrgsResponse = { total: number, orgs: Array<OrgReport>, };
+
+export type Priority = "low" | "normal" | "high";
+
+export type Role = "reader" | "funder" | "admin";
+
+export type SiwsChallengeRequest = { wallet: string, };
+
+export type SiwsChallengeResponse = { wallet: string, nonce: string, message: string, expires_at_ms: number, };
+
+export type SiwsSessionResponse = { token: string, wallet: string, expires_at_ms: number, };
+
+export type SiwsVerifyRequest = { wallet: string, nonce: string, signature: string, };
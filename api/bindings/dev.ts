@@ -0,0 +1,46 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+import type { ExplorerUrls } from "./transactions";
+import type { SolValue } from "./wallets";
+
+export type BufferInfo = { address: string, lamports: number | string, sol: SolValue, sol_decimal: string, display?: string | null, };
+
+export type ChaosRule = { route: string, method?: string | null, latency_ms: number, latency_jitter_ms: number, latency_rate: number, error_rate: number, rpc_error_rate: number, rpc_error_message?: string | null, };
+
+export type ChaosStatus = { active: boolean, rules: Array<ChaosRule>, injected: InjectedCounts, };
+
+export type CloseBuffersRequest = { buffers?: Array<string> | null, };
+
+export type CloseBuffersResponse = { recipient: string, closed: Array<ClosedBuffer>, failed: Array<FailedBuffer>, reclaimed_lamports: number | string, };
+
+export type ClosedBuffer = { address: string, lamports: number | string, transaction_signature: string, explorer_urls: ExplorerUrls, };
+
+export type FailedBuffer = { address: string, error: string, };
+
+export type Faults = { methods: Array<string>, error_rate: number, fail_next: number, message?: string | null, };
+
+export type Fixture = { pubkey: string, account: JsonValue, };
+
+export type FixtureSummary = { pubkey: string, owner: string, lamports: number | string, data_len: number, executable: boolean, };
+
+export type FixturesResponse = { fixtures: Array<FixtureSummary>, validator: ValidatorStatus, };
+
+export type InjectedCounts = { latency: number, error: number, rpc_error: number, };
+
+export type Job = { id: number, kind: string, status: JobStatus, progress?: JobProgress | null, result?: JsonValue | null, error?: string | null, };
+
+export type JobAcceptedResponse = { job_id: number, status_url: string, };
+
+export type JobProgress = { step: string, completed: number, total: number, };
+
+export type JobStatus = "pending" | "running" | "succeeded" | "failed";
+
+export type ListBuffersResponse = { authority: string, buffers: Array<BufferInfo>, total_lamports: number | string, };
+
+export type LoadFixturesRequest = { fixtures: Array<Fixture>, reset: boolean, };
+
+export type MockBalanceRequest = { lamports: number | string, };
+
+export type MockStatus = { slot: number, accounts: { [key in string]: number }, faults: Faults, };
+
+export type ValidatorStatus = { running: boolean, pid?: number | null, rpc_url?: string | null, ledger_dir?: string | null, started_at_ms?: number | null, };
@@ -0,0 +1,58 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Priority } from "./auth";
+
+export type ConfirmationReport = { samples: number, p50_ms?: number | null, p90_ms?: number | null, };
+
+export type CpuProfile = { window_ms: number, cpu_ms: number, threads: Array<ThreadCpu>, };
+
+export type Dependency = { name: string, kind: string, target: string, required: boolean, status: DependencyStatus, latency_ms: number, checked_at_ms: number, last_success_at_ms?: number | null, last_failure_at_ms?: number | null, detail?: string | null, error?: string | null, };
+
+export type DependencyReport = { status: string, checked_at_ms: number, dependencies: Array<Dependency>, };
+
+export type DependencyStatus = "up" | "down";
+
+export type Failures = { calls: number, failed: number, timed_out: number, errors: { [key in string]: number }, };
+
+export type HealthDetailsResponse = { status: string, rpc_url: string, rpc?: RpcStatus | null, slots?: SlotStatus | null, window_secs: number, routes: Array<RouteReport>, load: LoadReport, queue: QueueReport, rpc_pool: PoolReport, tasks: Array<TaskReport>, panics: PanicReport, confirmations: ConfirmationReport, };
+
+export type HealthResponse = { status: string, rpc_url: string, rpc?: RpcStatus | null, };
+
+export type LagSource = "reference" | "health" | "retransmit";
+
+export type LoadReport = { in_flight: number, rpc_p95_latency_ms?: number | null, rpc_calls: number, shedding?: string | null, shed_requests: number, };
+
+export type PanicReport = { total: number, last_at_ms?: number | null, last_message?: string | null, };
+
+export type PoolReport = { in_flight: number, peak_in_flight: number, waiting: number, upstreams: Array<UpstreamReport>, max_connections: number, idle_per_host: number, idle_timeout_secs: number, http2: boolean, };
+
+export type PoolSettings = { max_connections: number, idle_per_host: number, idle_timeout_secs: number, http2: boolean, };
+
+export type PprofFormat = "svg" | "folded" | "pprof";
+
+export type PprofQuery = { seconds?: number | null, format: PprofFormat, };
+
+export type ProfileQuery = { seconds?: number | null, };
+
+export type QueueReport = { running: number, limit?: number | null, tiers: Array<TierReport>, };
+
+export type ReloadReport = { applied: Array<string>, restart_required: Array<string>, };
+
+export type RouteReport = { route: string, requests: number, success_rate: number, p95_latency_ms: number, slo_met: boolean, };
+
+export type RpcStatus = { version?: string | null, feature_set?: number | null, slot_lag?: number | null, problems: Array<string>, error?: string | null, checked_at_ms: number, };
+
+export type RuntimeReport = { window_ms: number, workers: number, alive_tasks: number, global_queue_depth: number, blocked_workers: number, worker_stats: Array<WorkerReport>, };
+
+export type SlotStatus = { slot: number, lag_slots?: number | null, lag_ms_estimate?: number | null, lag_source?: LagSource | null, checked_at_ms: number, error?: string | null, };
+
+export type TaskReport = { name: string, state: TaskState, restarts: number, started_at_ms: number, last_success_ms?: number | null, last_panic?: string | null, last_panic_at_ms?: number | null, };
+
+export type TaskState = "running" | "restarting" | "finished";
+
+export type ThreadCpu = { tid: number, name: string, cpu_ms: number, cpu_percent: number, };
+
+export type TierReport = { priority: Priority, waiting: number, queued: number, rejected: number, };
+
+export type UpstreamReport = { upstream: string, requests: number, errors: number, avg_latency_ms: number, rate_limited_ms: number, };
+
+export type WorkerReport = { worker: number, busy_ms: number, busy_percent: number, parks: number, blocked: boolean, };
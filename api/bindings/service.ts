@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Role } from "./auth";
+
+export type ApiIndex = { service: string, version: string, require_auth: boolean, endpoints: Array<Endpoint>, };
+
+export type BuildInfo = { service: string, version: string, git_commit: string, git_dirty: boolean, built_at: string, features: Array<string>, solana_sdk: string, rustc: string, };
+
+export type Endpoint = { path: string, methods: Array<string>, role?: Role | null, state: FeatureState, disabled_by?: string | null, };
+
+export type Faucet = { enabled: boolean, min_airdrop_lamports: number | string, max_airdrop_sol: number, default_airdrop_sol: number, daily_quota_sol?: number | null, approval_threshold_lamports?: number | string | null, receipts: boolean, };
+
+export type FeatureInfo = { name: string, state: FeatureState, configured?: FeatureState | null, override?: FeatureState | null, routes: Array<string>, };
+
+export type FeatureState = "enabled" | "disabled" | "hidden";
+
+export type FrontendConfig = { title: string, cluster: string, theme: Theme, faucet: Faucet, features: FrontendFeatures, };
+
+export type FrontendFeatures = { balance: boolean, sign_in: boolean, require_auth: boolean, sign_in_required: boolean, };
+
+export type FrontendTheme = "dark" | "light";
+
+export type Theme = { mode: FrontendTheme, accent_color: string, logo_url?: string | null, };
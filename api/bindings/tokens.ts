@@ -0,0 +1,47 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+import type { ExplorerUrls } from "./transactions";
+
+export type AssetProofResponse = { asset_id: string, tree: string, root: string, leaf: string, node_index: number, proof: Array<string>, };
+
+export type CnftTransferRequest = { new_owner: string, submit: boolean, };
+
+export type CnftTransferResponse = { asset_id: string, owner: string, new_owner: string, tree: string, leaf_index: number, proof_nodes: number, canopy_depth: number, transaction?: string | null, transaction_signature?: string | null, explorer_url?: string | null, explorer_urls?: ExplorerUrls | null, };
+
+export type FeeResponse = { numerator: number, denominator: number, };
+
+export type MintNftRequest = { wallet: string, name: string, symbol: string, uri: string, };
+
+export type MintNftResponse = { wallet: string, mint: string, token_account: string, metadata: string, master_edition: string, transaction_signature: string, explorer_url: string, explorer_urls: ExplorerUrls, };
+
+export type Proposal = { address: string, governance: string, governing_token_mint: string, token_owner_record: string, name: string, description_link: string, state: string, vote_type: string, votes: VoteCounts, timeline: Timeline, signatories_count: number, signatories_signed_off_count: number, vote_threshold?: string | null, max_voting_time_secs?: number | null, options: Array<ProposalOption>, };
+
+export type ProposalOption = { label: string, vote_weight: number, vote_result: string, transactions_count: number, transactions_executed_count: number, };
+
+export type ProposalsQuery = { state?: string | null, limit?: number | null, };
+
+export type ProposalsResponse = { realm: string, total: number, proposals: Array<Proposal>, };
+
+export type QuoteQuery = { input_mint: string, output_mint: string, amount: number, slippage_bps?: number | null, };
+
+export type StakePoolDepositRequest = { wallet: string, lamports: number | string, submit: boolean, };
+
+export type StakePoolFees = { epoch: FeeResponse, sol_deposit: FeeResponse, sol_withdrawal: FeeResponse, stake_deposit: FeeResponse, stake_withdrawal: FeeResponse, };
+
+export type StakePoolResponse = { pool: string, program_id: string, manager: string, staker: string, pool_mint: string, token_program_id: string, reserve_stake: string, validator_list: string, manager_fee_account: string, total_lamports: number | string, total_sol_decimal: string, pool_token_supply: number, exchange_rate: number, last_update_epoch: number, current_epoch: number, needs_update: boolean, fees: StakePoolFees, sol_deposit_authority?: string | null, sol_withdraw_authority?: string | null, };
+
+export type StakePoolTransactionResponse = { pool: string, wallet: string, transaction?: string | null, transaction_signature?: string | null, explorer_url?: string | null, explorer_urls?: ExplorerUrls | null, };
+
+export type StakePoolWithdrawRequest = { wallet: string, pool_tokens: number, submit: boolean, };
+
+export type SwapBuildRequest = { quote: JsonValue, user: string, submit: boolean, };
+
+export type SwapBuildResponse = { user: string, transaction?: string | null, last_valid_block_height: number, transaction_signature?: string | null, explorer_url?: string | null, explorer_urls?: ExplorerUrls | null, };
+
+export type Timeline = { draft_at: number, signing_off_at?: number | null, voting_at?: number | null, voting_at_slot?: number | null, voting_completed_at?: number | null, executing_at?: number | null, closed_at?: number | null, };
+
+export type ValidatorListResponse = { pool: string, max_validators: number, total_active_stake_lamports: number | string, validators: Array<ValidatorStakeResponse>, };
+
+export type ValidatorStakeResponse = { vote_account: string, active_stake_lamports: number | string, transient_stake_lamports: number | string, last_update_epoch: number, status: string, };
+
+export type VoteCounts = { yes?: number | null, no?: number | null, abstain?: number | null, veto: number, max_vote_weight?: number | null, };
@@ -0,0 +1,45 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+import type { SolValue } from "./wallets";
+
+export type DecodeRequest = { data: string, offset: number, schema: Array<JsonValue>, };
+
+export type DecodeResponse = { decoded: JsonValue, bytes_read: number, trailing_bytes: number, };
+
+export type Decoded = { decoder: string, parsed?: JsonValue | null, error?: string | null, };
+
+export type ExplorerUrls = { solana_explorer: string, solscan: string, solana_fm?: string | null, };
+
+export type IndexedTx = { wallet: string, signature: string, slot: number, block_time?: number | null, succeeded: boolean, type: TxKind, change_lamports: number | string, fee_lamports: number | string, counterparty?: string | null, mints: Array<string>, memos: Array<string>, };
+
+export type InstructionResponse = { program_id: string, program_name?: string | null, accounts: Array<string>, data: string, decoded?: Decoded | null, inner_instructions: Array<InstructionResponse>, };
+
+export type Linked<T> = { explorer_urls: ExplorerUrls, } & T;
+
+export type MemoMatch = { signature: string, wallets: Array<string>, slot: number, block_time?: number | null, memos: Array<string>, };
+
+export type MemoSearchQuery = { q: string, limit?: number | null, };
+
+export type MemoSearchResponse = { query: string, matches: Array<Linked<MemoMatch>>, };
+
+export type ProgramEventResponse = { program_id: string, program_name?: string | null, data: string, decoded?: Decoded | null, };
+
+export type Receipt = { receipt_id: string, verified: boolean, signature: string, explorer_urls: ExplorerUrls, treasury: string, slot: number, block_time?: number | null, checks: ReceiptChecks, record: ReceiptRecord, };
+
+export type ReceiptChecks = { transaction_succeeded: boolean, paid_by_treasury: boolean, memo_matches: boolean, transfer_matches: boolean, };
+
+export type ReceiptRecord = { timestamp_ms: number, action: string, params: JsonValue, };
+
+export type SignerCostResponse = { address: string, fee_payer: boolean, signature_fee_lamports: number | string, paid_lamports: number | string, };
+
+export type TransactionCostResponse = { signature: string, explorer_urls: ExplorerUrls, slot: number, block_time?: number | null, status: string, fee_lamports: number | string, fee_sol: SolValue, fee_sol_decimal: string, base_fee_lamports: number | string, priority_fee_lamports: number | string, compute_unit_price_micro_lamports: number | string, compute_units_requested: number, compute_unit_limit_source: string, compute_units_consumed?: number | null, signers: Array<SignerCostResponse>, };
+
+export type TransactionResponse = { signature: string, explorer_urls: ExplorerUrls, slot: number, block_time?: number | null, status: string, fee_lamports: number | string, instructions: Array<InstructionResponse>, events: Array<ProgramEventResponse>, };
+
+export type TransactionStatusEvent = { signature: string, explorer_urls: ExplorerUrls, commitment: string, slot?: number | null, error?: string | null, };
+
+export type TxKind = "transfer" | "token" | "other";
+
+export type WalletTransactionsQuery = { type?: string | null, min_amount?: string | null, since?: number | null, until?: number | null, before?: string | null, limit?: number | null, };
+
+export type WalletTransactionsResponse = { wallet: string, transactions: Array<Linked<IndexedTx>>, indexed: number, backfill_complete: boolean, next_before?: string | null, };
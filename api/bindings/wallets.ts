@@ -0,0 +1,98 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Failures } from "./health";
+import type { JsonValue } from "./serde_json/JsonValue";
+import type { ExplorerUrls } from "./transactions";
+
+export type Activity = { wallet: string, signature: string, slot: number, block_time?: number | null, succeeded: boolean, memo?: string | null, explorer_urls: ExplorerUrls, };
+
+export type AlertFormat = "chat" | "json";
+
+export type AmountFormat = "raw" | "display";
+
+export type AmountOptions = { sol_as_string?: boolean | null, format?: AmountFormat | null, };
+
+export type AnchorEvent = { signature: string, slot: number, program_id: string, name: string, data: JsonValue, };
+
+export type AnchorEventQuery = { events?: string | null, };
+
+export type BalanceAlert = { id: number, name?: string | null, wallet: string, below_lamports: number | string, hysteresis_lamports: number | string, target: string, format: AlertFormat, created_at_ms: number, triggered: boolean, last_balance_lamports?: number | string | null, changed_at_ms?: number | null, };
+
+export type BalanceAlertRequest = { name?: string | null, wallet?: string | null, below_sol: number, hysteresis_sol?: number | null, target: string, format: AlertFormat, };
+
+export type BalanceAlertsResponse = { alerts: Array<BalanceAlert>, };
+
+export type BalanceAtQuery = { slot?: number | null, block_time?: number | null, };
+
+export type BalanceAtResponse = { wallet: string, slot?: number | null, block_time?: number | null, balance_lamports: number | string, balance_sol: SolValue, balance_sol_decimal: string, balance_display?: string | null, source: string, source_signature?: string | null, source_slot?: number | null, rpc: string, };
+
+export type CounterpartySummary = { address: string, transactions: number, sent_to_lamports: number | string, received_from_lamports: number | string, };
+
+export type DiffSide = { wallet: string, slot?: number | null, balance_lamports: number | string, balance_sol_decimal: string, };
+
+export type EcLevel = "L" | "M" | "Q" | "H";
+
+export type Event = { id: number, kind: EventKind, timestamp_ms: number, data: JsonValue, };
+
+export type EventKind = "balance_changed" | "program_transaction" | "faucet_confirmed";
+
+export type EventStreamQuery = { kinds?: string | null, };
+
+export type GetBalance = { address: string, };
+
+export type GetBalanceResponse = { wallet: string, balance_lamports: number | string, balance_sol: SolValue, balance_sol_decimal: string, balance_display?: string | null, };
+
+export type Label = { address: string, label: string, tags: Array<string>, updated_at_ms: number, };
+
+export type LabelQuery = { q?: string | null, tag?: string | null, limit?: number | null, };
+
+export type LabelsResponse = { labels: Array<Label>, };
+
+export type Member = { wallet: string, balance_lamports?: number | string | null, error?: string | null, };
+
+export type MessageEncoding = "utf8" | "base64" | "base58";
+
+export type Overview = { id: number, name: string, total_lamports: number | string, total_sol_decimal: string, tokens: Array<TokenTotal>, recent_activity: Array<Activity>, members: Array<Member>, failures: Failures, };
+
+export type ParsedOffchainMessage = { version: number, format: string, message: string, length: number, };
+
+export type QrFormat = "svg" | "png";
+
+export type QrQuery = { data?: string | null, recipient?: string | null, amount?: string | null, spl_token?: string | null, reference?: string | null, label?: string | null, message?: string | null, memo?: string | null, format: QrFormat, size?: number | null, margin?: number | null, ec: EcLevel, };
+
+export type SetLabelRequest = { label: string, tags: Array<string>, };
+
+export type SignMessageRequest = { message: string, encoding: MessageEncoding, format: SignedFormat, key?: string | null, };
+
+export type SignMessageResponse = { key: string, pubkey: string, signature: string, format: string, signed_message: string, };
+
+export type SignedFormat = "auto" | "raw" | "offchain";
+
+export type SolValue = number | string;
+
+export type SummaryQuery = { days?: number | null, };
+
+export type TokenActivitySummary = { mint: string, decimals: number, received_amount: string, sent_amount: string, transactions: number, };
+
+export type TokenDiffResponse = { mint: string, decimals: number, from_amount?: string | null, to_amount?: string | null, delta: string, };
+
+export type TokenTotal = { mint: string, decimals: number, amount: string, holders: number, };
+
+export type VerifySignatureRequest = { pubkey: string, signature: string, message: string, encoding: MessageEncoding, format: SignedFormat, };
+
+export type VerifySignatureResponse = { valid: boolean, pubkey: string, format: string, offchain_message?: ParsedOffchainMessage | null, };
+
+export type WalletBalanceQuery = { wait_for_change?: string | null, previous_lamports?: number | string | null, };
+
+export type WalletBalanceResponse = { wallet: string, balance_lamports: number | string, balance_sol: SolValue, balance_sol_decimal: string, balance_display?: string | null, previous_lamports: number | string, changed: boolean, waited_ms: number, };
+
+export type WalletDiffRequest = { wallets?: Array<string> | null, wallet?: string | null, from_slot?: number | null, to_slot?: number | null, };
+
+export type WalletDiffResponse = { mode: string, from: DiffSide, to: DiffSide, lamports_delta: number, sol_delta: SolValue, sol_delta_decimal: string, tokens: Array<TokenDiffResponse>, };
+
+export type WalletSummaryResponse = { wallet: string, days: number, transaction_count: number, failed_transaction_count: number, received_lamports: number | string, received_sol: SolValue, received_sol_decimal: string, sent_lamports: number | string, sent_sol: SolValue, sent_sol_decimal: string, fees_lamports: number | string, top_counterparties: Array<CounterpartySummary>, token_activity: Array<TokenActivitySummary>, truncated: boolean, cached: boolean, computed_at_ms: number, };
+
+export type Watchlist = { id: number, name: string, wallets: Array<string>, owner: string, created_at_ms: number, };
+
+export type WatchlistRequest = { name: string, wallets: Array<string>, };
+
+export type WatchlistsResponse = { total: number, watchlists: Array<Watchlist>, };
@@ -0,0 +1,158 @@
+// Account reads, program accounts and the account index.
+use crate::{lamports, Decoded};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts", optional_fields = nullable)]
+pub struct AccountResponse {
+    pub address: String,
+    pub owner: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_name: Option<String>,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub lamports: u64,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub space: usize,
+    // Base64.
+    pub data: String,
+    // Present when a decoder is registered for the owner program.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<Decoded>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts")]
+pub struct ConsistencyQuery {
+    // Pin every sub-read to the same minimum context slot, so a view over
+    // several accounts can't mix older and newer state.
+    #[serde(default)]
+    pub consistent: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts")]
+pub struct IndexStatusResponse {
+    pub programs: Vec<IndexStatus>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts")]
+pub struct IndexedAccountsQuery {
+    pub owner: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts")]
+pub struct IndexedAccountsResponse {
+    pub program: String,
+    pub owner: String,
+    pub accounts: Vec<IndexedAccount>,
+    // When the index was last rebuilt from getProgramAccounts.
+    pub reconciled_at_ms: u64,
+    // Whether a subscription has been keeping it current since.
+    pub live: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramAccountsFormat {
+    // Up to 1,000 accounts at once, in the usual envelope.
+    #[default]
+    Json,
+    // Streamed, one account per line.
+    Ndjson,
+    // Streamed as a bare JSON array of accounts.
+    JsonArray,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts", optional_fields = nullable)]
+pub struct ProgramAccountsQuery {
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub format: ProgramAccountsFormat,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts", optional_fields = nullable)]
+pub struct ProgramAccountsResponse {
+    pub program: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program_name: Option<String>,
+    // Accounts owned by the program before `limit` was applied.
+    pub total: usize,
+    pub accounts: Vec<AccountResponse>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts")]
+pub struct ProgramsResponse {
+    pub programs: Vec<KnownProgram>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts")]
+pub struct SetProgramNameRequest {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts", optional_fields = nullable)]
+pub struct IdlUploadQuery {
+    // For IDLs that don't record their program's address.
+    pub program_id: Option<String>,
+}
+
+// What an uploaded IDL lets the server decode for its program.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts")]
+pub struct IdlSummary {
+    pub program_id: String,
+    pub name: String,
+    pub instructions: Vec<String>,
+    pub accounts: Vec<String>,
+    pub events: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts", optional_fields = nullable)]
+pub struct IndexStatus {
+    pub program: String,
+    pub owner_offset: usize,
+    pub accounts: usize,
+    pub owners: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconciled_at_ms: Option<u64>,
+    pub live: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts")]
+pub struct IndexedAccount {
+    pub address: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub lamports: u64,
+    pub slot: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts")]
+pub struct KnownProgram {
+    pub program_id: String,
+    pub name: String,
+    pub source: ProgramSource,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "accounts.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramSource {
+    Builtin,
+    // Added through /admin/programs; replaces a builtin name for the same id.
+    Custom,
+}
@@ -0,0 +1,118 @@
+// The audit log, the allowlist and exports.
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "admin.ts", optional_fields = nullable)]
+pub struct Actor {
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forwarded_for: Option<String>,
+    // Authenticated caller (JWT `sub` or "admin_token"), when there is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    // Org of the API key used, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+}
+
+impl Actor {
+    pub fn system(source: &str) -> Self {
+        Actor {
+            source: source.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "admin.ts", optional_fields = nullable)]
+pub struct AuditRecord {
+    pub timestamp_ms: u64,
+    pub action: String,
+    pub actor: Actor,
+    pub params: serde_json::Value,
+    pub outcome: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    // Id of the receipt memo the transaction carries (see receipts.rs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn signature(mut self, signature: impl ToString) -> Self {
+        self.signature = Some(signature.to_string());
+        self
+    }
+
+    pub fn receipt(mut self, receipt: Option<String>) -> Self {
+        self.receipt = receipt;
+        self
+    }
+
+    pub fn failed(mut self, error: impl ToString) -> Self {
+        self.outcome = "failure".to_string();
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "admin.ts", optional_fields = nullable)]
+pub struct ExportReport {
+    // "scheduled" or "manual".
+    pub trigger: String,
+    pub started_at_ms: u64,
+    pub finished_at_ms: u64,
+    pub objects: Vec<ExportedObject>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "admin.ts")]
+pub struct ExportedObject {
+    pub key: String,
+    pub rows: usize,
+    pub bytes: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "admin.ts")]
+pub struct AllowlistAddRequest {
+    pub wallets: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "admin.ts")]
+pub struct AllowlistAddResponse {
+    pub added: Vec<String>,
+    pub total: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "admin.ts")]
+pub struct AllowlistResponse {
+    pub enforced: bool,
+    pub wallets: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "admin.ts", optional_fields = nullable)]
+pub struct AuditQuery {
+    pub action: Option<String>,
+    pub org: Option<String>,
+    pub since_ms: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "admin.ts")]
+pub struct AuditQueryResponse {
+    pub records: Vec<AuditRecord>,
+}
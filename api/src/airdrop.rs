@@ -0,0 +1,516 @@
+// Airdrops, approvals, scheduled top-ups, the treasury and quotas.
+use crate::{lamports, Actor, ExplorerUrls};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use ts_rs::TS;
+
+// An airdrop above `airdrop_approval_threshold_lamports`, held until an
+// admin decides on it.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct Approval {
+    // Random, so only the requester, who was given it, can poll it.
+    pub id: String,
+    pub wallet: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub lamports: u64,
+    pub status: ApprovalStatus,
+    pub requested_by: Actor,
+    pub requested_at_ms: u64,
+    // POSTed the approval once it is decided.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_at_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct BalanceRow {
+    pub address: String,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub lamports: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sol_decimal: Option<String>,
+    // False for addresses with no account (and so no lamports).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exists: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum BalancesFormat {
+    #[default]
+    Ndjson,
+    Csv,
+}
+
+impl BalancesFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            BalancesFormat::Ndjson => "application/x-ndjson",
+            BalancesFormat::Csv => "text/csv",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+pub struct RowError {
+    pub line: usize,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct RowResult {
+    pub line: usize,
+    pub wallet: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub lamports: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct ApprovalsQuery {
+    pub status: Option<ApprovalStatus>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+pub struct ApprovalsResponse {
+    pub total: usize,
+    pub approvals: Vec<Approval>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+pub struct BulkBalancesQuery {
+    #[serde(default)]
+    pub format: BalancesFormat,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+pub struct RebalanceRequest {
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub target_lamports: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+pub struct RebalanceResponse {
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub target_lamports: u64,
+    pub movements: Vec<Movement>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct RejectRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct TopupRequest {
+    pub wallet: String,
+    pub min_sol: f64,
+    // Balance to top up to; defaults to twice the minimum.
+    pub target_sol: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct TreasuryQuery {
+    // How many recent transactions to look through for outflows.
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct TreasuryResponse {
+    pub address: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub balance_lamports: u64,
+    pub balance_sol_decimal: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub low_watermark_sol: Option<f64>,
+    // Non-admin writes are refused while this is set.
+    pub paused: bool,
+    pub auxiliary: Vec<AuxBalance>,
+    pub recent_outflows: Vec<Outflow>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct Topup {
+    pub wallet: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub min_lamports: u64,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub target_lamports: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registered_by: Option<String>,
+    pub registered_at_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<TopupRun>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum TopupOutcome {
+    // Balance was at or above the minimum.
+    Skipped,
+    ToppedUp,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct TopupRun {
+    pub checked_at_ms: u64,
+    pub outcome: TopupOutcome,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub balance_lamports: Option<u64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub amount_lamports: Option<u64>,
+    // "treasury" or "airdrop".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct AuxBalance {
+    pub address: String,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub balance_lamports: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct Movement {
+    pub address: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub balance_lamports: u64,
+    // Positive when the treasury paid the keypair, negative when the
+    // keypair's surplus went back to the treasury.
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub moved_lamports: i128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_urls: Option<ExplorerUrls>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct Outflow {
+    pub signature: String,
+    pub slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<i64>,
+    // What the treasury's balance dropped by, fees included.
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub lamports: u64,
+    pub explorer_urls: ExplorerUrls,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct QuotaReport {
+    pub daily_airdrop_sol: Option<u64>,
+    // Whole SOL, rounded down; the lamport fields are exact.
+    pub used_today_sol: u64,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub used_today_lamports: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_today_sol: Option<u64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub remaining_today_lamports: Option<u64>,
+    pub resets_at_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+pub struct RouteRpcReport {
+    pub route: String,
+    pub requests: u64,
+    pub rpc_calls: u64,
+    pub rpc_calls_per_request: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct RpcBudgetReport {
+    pub daily_calls: Option<u64>,
+    pub used_today: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_today: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+pub struct UsageReport {
+    pub subject: String,
+    pub requests: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub airdrops: u64,
+    pub airdropped_sol: u64,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub airdropped_lamports: u64,
+    pub last_seen_ms: u64,
+    pub quota: QuotaReport,
+    // Upstream RPC calls this credential's requests caused.
+    pub rpc_calls: u64,
+    pub rpc_budget: RpcBudgetReport,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    Pending,
+    // Approved and sent; `signature` is set once the RPC node accepted it.
+    Approved,
+    Rejected,
+    // Approved, but the airdrop itself failed; see `error`.
+    Failed,
+}
+
+impl fmt::Display for ApprovalStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ApprovalStatus::Pending => "pending",
+            ApprovalStatus::Approved => "approved",
+            ApprovalStatus::Rejected => "rejected",
+            ApprovalStatus::Failed => "failed",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct AirdropRequest {
+    // Were `wallet` and `sol`; see `deprecations`.
+    #[serde(alias = "wallet")]
+    pub address: String,
+    // Exactly one of the two.
+    #[serde(alias = "sol", skip_serializing_if = "Option::is_none")]
+    pub amount_sol: Option<u64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub lamports: Option<u64>,
+    // For airdrops that need approval: POSTed the decided approval.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    // Runs every check (and simulates the transfer) without sending
+    // anything or using up quota; the reply is a `DryRunAirdropResponse`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    // `false` replies as soon as the airdrop is sent, without waiting for
+    // a treasury transfer to confirm or fetching the balance: the reply has
+    // no commitment or balances. Follow it at `status_url`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait: Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+#[serde(untagged)]
+pub enum AirdropReply {
+    Sent(AirdropResponse),
+    Pending(PendingAirdropResponse),
+    DryRun(DryRunAirdropResponse),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct AirdropResponse {
+    pub success: bool,
+    pub message: String,
+    pub wallet: String,
+    pub airdrop_amount_sol: f64,
+    // The exact amount requested from the RPC node.
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub airdrop_amount_lamports: u64,
+    pub transaction_signature: String,
+    // `explorer_urls.solana_explorer`.
+    pub explorer_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explorer_urls: Option<ExplorerUrls>,
+    // Set when the transfer carries a receipt memo; check it at
+    // /receipts/{id}.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt_id: Option<String>,
+    // The commitment the transaction had reached when the response was
+    // sent: "pending", "processed", "confirmed" or "finalized".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commitment: Option<String>,
+    // The wallet's balance at "confirmed" commitment, which may not include
+    // the airdrop yet.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub confirmed_balance_lamports: Option<u64>,
+    // The balance once the airdrop lands. The three are left out when the
+    // node couldn't be asked.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub projected_balance_lamports: Option<u64>,
+    // Server-sent events as the transaction is confirmed and finalized.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_url: Option<String>,
+}
+
+// What an airdrop would do, from a request with `dry_run` set.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct DryRunAirdropResponse {
+    pub success: bool,
+    pub dry_run: bool,
+    pub message: String,
+    pub wallet: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub airdrop_amount_lamports: u64,
+    // "treasury" (a transfer from the server keypair) or "airdrop" (the
+    // RPC node's faucet).
+    pub source: String,
+    // The airdrop would wait for an admin's approval before it is sent.
+    pub needs_approval: bool,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub wallet_balance_lamports: u64,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub resulting_wallet_balance_lamports: u64,
+    // Treasury transfers only: the faucet's fee isn't ours to pay.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub estimated_fee_lamports: Option<u64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub treasury_balance_lamports: Option<u64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub resulting_treasury_balance_lamports: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub simulation: Option<SimulationResult>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts", optional_fields = nullable)]
+pub struct SimulationResult {
+    // Set when the simulated transaction failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub units_consumed: Option<u64>,
+    #[serde(default)]
+    pub logs: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "airdrop.ts")]
+pub struct PendingAirdropResponse {
+    pub success: bool,
+    pub message: String,
+    pub wallet: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub airdrop_amount_lamports: u64,
+    pub approval_id: String,
+    pub status: ApprovalStatus,
+    // Poll this until the status is no longer "pending".
+    pub status_url: String,
+}
@@ -0,0 +1,205 @@
+// Roles, API keys, orgs and Sign In With Solana.
+use crate::UsageReport;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use ts_rs::TS;
+
+// Scheduling tier of a credential under load: higher tiers are exempt from
+// shedding and served first from the request queue. Anonymous callers are
+// Low, authenticated ones Normal unless priority_tiers says otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            "high" => Ok(Priority::High),
+            _ => Err(format!("unknown priority '{}'", s)),
+        }
+    }
+}
+
+// Ordered so that a higher role includes everything below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Reader,
+    Funder,
+    Admin,
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reader" => Ok(Role::Reader),
+            "funder" => Ok(Role::Funder),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("unknown role '{}'", s)),
+        }
+    }
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Reader => "reader",
+            Role::Funder => "funder",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts", optional_fields = nullable)]
+pub struct CreateKeyRequest {
+    pub role: Role,
+    // Makes it a key of this org.
+    pub org: Option<String>,
+    pub label: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts")]
+pub struct CreateOrgRequest {
+    pub id: String,
+    #[serde(flatten)]
+    pub settings: OrgSettings,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts", optional_fields = nullable)]
+pub struct IssueKeyRequest {
+    pub role: Role,
+    pub label: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts")]
+pub struct IssuedKeyResponse {
+    #[serde(flatten)]
+    pub key: KeyInfo,
+    // Shown only in this response; only a salted hash of it is kept.
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts", optional_fields = nullable)]
+pub struct KeysQuery {
+    pub org: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts")]
+pub struct KeysResponse {
+    pub total: usize,
+    pub keys: Vec<KeyInfo>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts", optional_fields = nullable)]
+pub struct OrgReport {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub airdrop_daily_quota_sol: Option<u64>,
+    pub webhooks: Vec<String>,
+    pub created_at_ms: u64,
+    pub keys: Vec<KeyInfo>,
+    pub watchlists: usize,
+    // Everything the org's keys did, together.
+    pub usage: UsageReport,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts")]
+pub struct OrgsResponse {
+    pub total: usize,
+    pub orgs: Vec<OrgReport>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts")]
+pub struct SiwsChallengeRequest {
+    pub wallet: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts")]
+pub struct SiwsChallengeResponse {
+    pub wallet: String,
+    pub nonce: String,
+    // Sign these exact bytes (UTF-8) with the wallet.
+    pub message: String,
+    pub expires_at_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts")]
+pub struct SiwsSessionResponse {
+    pub token: String,
+    pub wallet: String,
+    pub expires_at_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts")]
+pub struct SiwsVerifyRequest {
+    pub wallet: String,
+    pub nonce: String,
+    // Base58 ed25519 signature over the challenge message.
+    pub signature: String,
+}
+
+// What an admin sees of a key.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts", optional_fields = nullable)]
+pub struct KeyInfo {
+    pub id: u64,
+    pub role: Role,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub created_at_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotated_at_ms: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts", optional_fields = nullable)]
+pub struct Org {
+    pub id: String,
+    pub name: String,
+    // Whole SOL the org's keys may airdrop per UTC day, together. Each key
+    // is still held to airdrop_daily_quota_sol on its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub airdrop_daily_quota_sol: Option<u64>,
+    // Slack / Discord incoming webhooks told about the org's airdrops.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<String>,
+    pub created_at_ms: u64,
+}
+
+// The parts of an org an admin sets when creating or updating it.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "auth.ts", optional_fields = nullable)]
+pub struct OrgSettings {
+    pub name: String,
+    #[serde(default)]
+    pub airdrop_daily_quota_sol: Option<u64>,
+    #[serde(default)]
+    pub webhooks: Vec<String>,
+}
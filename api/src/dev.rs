@@ -0,0 +1,237 @@
+// Program deploys and buffers, fixtures, the local validator, chaos and
+// the mock backend.
+use crate::{lamports, ExplorerUrls, SolValue};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use ts_rs::TS;
+
+fn always() -> f64 {
+    1.0
+}
+
+// One route's injected faults. Latency is rolled on its own and applied
+// first; then at most one of the two failures replaces the response.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts", optional_fields = nullable)]
+#[serde(deny_unknown_fields)]
+pub struct ChaosRule {
+    // Route pattern as registered, e.g. "/wallet/{pubkey}/summary", or "*"
+    // for every route.
+    pub route: String,
+    // HTTP method; every method when unset.
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub latency_ms: u64,
+    // Extra random delay of up to this much on top of latency_ms.
+    #[serde(default)]
+    pub latency_jitter_ms: u64,
+    #[serde(default = "always")]
+    pub latency_rate: f64,
+    // Share of requests answered with a plain 500.
+    #[serde(default)]
+    pub error_rate: f64,
+    // Share of requests answered as if the RPC node had failed.
+    #[serde(default)]
+    pub rpc_error_rate: f64,
+    #[serde(default)]
+    pub rpc_error_message: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct ChaosStatus {
+    // Rules only take effect in dev mode.
+    pub active: bool,
+    pub rules: Vec<ChaosRule>,
+    pub injected: InjectedCounts,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct InjectedCounts {
+    pub latency: u64,
+    pub error: u64,
+    pub rpc_error: u64,
+}
+
+// Same shape as `solana account <address> --output json`, which is also
+// what solana-test-validator's --account / --account-dir read.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct Fixture {
+    pub pubkey: String,
+    // The account as `solana account` prints it: lamports, data as
+    // [base64, "base64"], owner, executable, rentEpoch and space.
+    pub account: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct FixtureSummary {
+    pub pubkey: String,
+    pub owner: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub lamports: u64,
+    pub data_len: usize,
+    pub executable: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts", optional_fields = nullable)]
+pub struct BufferInfo {
+    pub address: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub lamports: u64,
+    pub sol: SolValue,
+    pub sol_decimal: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts", optional_fields = nullable)]
+pub struct CloseBuffersRequest {
+    // Closes every server-owned buffer when omitted.
+    pub buffers: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct CloseBuffersResponse {
+    pub recipient: String,
+    pub closed: Vec<ClosedBuffer>,
+    pub failed: Vec<FailedBuffer>,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub reclaimed_lamports: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct ClosedBuffer {
+    pub address: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub lamports: u64,
+    pub transaction_signature: String,
+    pub explorer_urls: ExplorerUrls,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct FailedBuffer {
+    pub address: String,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct FixturesResponse {
+    pub fixtures: Vec<FixtureSummary>,
+    pub validator: ValidatorStatus,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct JobAcceptedResponse {
+    pub job_id: u64,
+    pub status_url: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct ListBuffersResponse {
+    pub authority: String,
+    pub buffers: Vec<BufferInfo>,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub total_lamports: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct LoadFixturesRequest {
+    pub fixtures: Vec<Fixture>,
+    // Restart a running validator with a fresh ledger so the fixtures are
+    // loaded now rather than on its next reset.
+    #[serde(default)]
+    pub reset: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct MockBalanceRequest {
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub lamports: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts", optional_fields = nullable)]
+pub struct Job {
+    pub id: u64,
+    pub kind: String,
+    pub status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<JobProgress>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct JobProgress {
+    pub step: String,
+    pub completed: u64,
+    pub total: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+// Injected failures. `fail_next` fails that many matching calls outright;
+// `error_rate` then fails a random share of them.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts", optional_fields = nullable)]
+#[serde(default, deny_unknown_fields)]
+pub struct Faults {
+    // RPC method names, e.g. "requestAirdrop"; empty matches every method.
+    pub methods: Vec<String>,
+    pub error_rate: f64,
+    pub fail_next: u32,
+    pub message: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts")]
+pub struct MockStatus {
+    pub slot: u64,
+    pub accounts: BTreeMap<String, u64>,
+    pub faults: Faults,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "dev.ts", optional_fields = nullable)]
+pub struct ValidatorStatus {
+    pub running: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ledger_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at_ms: Option<u64>,
+}
@@ -0,0 +1,363 @@
+// Health, dependencies and the runtime reports behind /health/details
+// and /admin.
+use crate::Priority;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+use ts_rs::TS;
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct RpcStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature_set: Option<u32>,
+    // How far behind the cluster the node is, from the slot tracker.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot_lag: Option<u64>,
+    // Why writes are warned about or refused; empty when the node is fine.
+    // Worked out against the current config and slot lag whenever the
+    // status is read.
+    pub problems: Vec<String>,
+    // Set when the node couldn't be asked; the last answer is kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub checked_at_ms: u64,
+}
+
+impl RpcStatus {
+    pub fn compatible(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+// Observed confirmation latency, in /health/details.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct ConfirmationReport {
+    pub samples: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p50_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p90_ms: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct Dependency {
+    pub name: String,
+    // What it is to this server: "rpc", "das", "price", "store", ...
+    pub kind: String,
+    // Scheme and host, or the store backend; never a full URL, since
+    // provider URLs often carry a key.
+    pub target: String,
+    // Whether the server is down without it, rather than missing a feature.
+    pub required: bool,
+    pub status: DependencyStatus,
+    pub latency_ms: u64,
+    pub checked_at_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success_at_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_failure_at_ms: Option<u64>,
+    // e.g. the slot an RPC node is at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct DependencyReport {
+    // "ok", "degraded" with an optional dependency down, or "down" with a
+    // required one down.
+    pub status: String,
+    pub checked_at_ms: u64,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyStatus {
+    Up,
+    Down,
+}
+
+// What went wrong across a fan-out, for responses and logs: each distinct
+// error once, with how many calls it failed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct Failures {
+    pub calls: usize,
+    pub failed: usize,
+    pub timed_out: usize,
+    pub errors: BTreeMap<String, usize>,
+}
+
+impl Failures {
+    pub fn is_empty(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+impl fmt::Display for Failures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} calls failed", self.failed, self.calls)?;
+        for (i, (error, count)) in self.errors.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { "; " };
+            write!(f, "{}{} (x{})", sep, error, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum LagSource {
+    // The slot of `rpc_reference_url`, another node assumed to be current.
+    Reference,
+    // The node's own getHealth, which says how far behind it is once it
+    // considers itself unhealthy.
+    Health,
+    // getMaxRetransmitSlot, the newest slot the node has seen from the
+    // cluster.
+    Retransmit,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct SlotStatus {
+    // The node's processed slot at the last check.
+    pub slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lag_slots: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lag_ms_estimate: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lag_source: Option<LagSource>,
+    pub checked_at_ms: u64,
+    // Set when the last check failed; the values are from the one before.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct HealthDetailsResponse {
+    pub status: String,
+    pub rpc_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc: Option<RpcStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slots: Option<SlotStatus>,
+    pub window_secs: u64,
+    pub routes: Vec<RouteReport>,
+    pub load: LoadReport,
+    pub queue: QueueReport,
+    pub rpc_pool: PoolReport,
+    pub tasks: Vec<TaskReport>,
+    pub panics: PanicReport,
+    pub confirmations: ConfirmationReport,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct HealthResponse {
+    pub status: String,
+    pub rpc_url: String,
+    // The node's version and how far behind it is, once it has been checked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc: Option<RpcStatus>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct PprofQuery {
+    pub seconds: Option<u64>,
+    #[serde(default)]
+    pub format: PprofFormat,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct ProfileQuery {
+    // How long to sample for.
+    pub seconds: Option<u64>,
+}
+
+// Handler panics since startup, in /health/details.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct PanicReport {
+    pub total: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_at_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_message: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct PoolReport {
+    #[serde(flatten)]
+    pub settings: PoolSettings,
+    // Upstream requests being sent, and those waiting for a free
+    // connection slot.
+    pub in_flight: usize,
+    pub peak_in_flight: usize,
+    pub waiting: usize,
+    pub upstreams: Vec<UpstreamReport>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct PoolSettings {
+    pub max_connections: usize,
+    pub idle_per_host: usize,
+    pub idle_timeout_secs: u64,
+    pub http2: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct UpstreamReport {
+    // Scheme and host only; paid RPC URLs often carry a key in the path.
+    pub upstream: String,
+    pub requests: usize,
+    pub errors: u64,
+    pub avg_latency_ms: u64,
+    pub rate_limited_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum PprofFormat {
+    #[default]
+    Svg,
+    Folded,
+    Pprof,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct CpuProfile {
+    pub window_ms: u64,
+    pub cpu_ms: u64,
+    // Busiest first; threads idle over the window are left out.
+    pub threads: Vec<ThreadCpu>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct RuntimeReport {
+    pub window_ms: u64,
+    pub workers: usize,
+    pub alive_tasks: usize,
+    // Tasks spawned from outside the runtime, waiting for a worker.
+    pub global_queue_depth: usize,
+    pub blocked_workers: usize,
+    pub worker_stats: Vec<WorkerReport>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct ThreadCpu {
+    pub tid: u32,
+    pub name: String,
+    pub cpu_ms: u64,
+    // Of one core, over the window.
+    pub cpu_percent: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct WorkerReport {
+    pub worker: usize,
+    pub busy_ms: u64,
+    pub busy_percent: f64,
+    // Times the worker ran out of work and went to sleep.
+    pub parks: u64,
+    pub blocked: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct QueueReport {
+    pub running: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    pub tiers: Vec<TierReport>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct TierReport {
+    pub priority: Priority,
+    pub waiting: usize,
+    // Requests that had to wait for a slot, and those that gave up.
+    pub queued: u64,
+    pub rejected: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct LoadReport {
+    pub in_flight: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_p95_latency_ms: Option<u64>,
+    pub rpc_calls: usize,
+    // Why low-priority requests are being rejected right now, if they are.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shedding: Option<String>,
+    pub shed_requests: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct RouteReport {
+    pub route: String,
+    pub requests: usize,
+    pub success_rate: f64,
+    // 0 for routes that wait for a transaction to confirm, whose latency
+    // isn't judged.
+    pub p95_latency_ms: u64,
+    pub slo_met: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+pub struct ReloadReport {
+    pub applied: Vec<String>,
+    pub restart_required: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts", optional_fields = nullable)]
+pub struct TaskReport {
+    pub name: String,
+    pub state: TaskState,
+    pub restarts: u64,
+    pub started_at_ms: u64,
+    // When the task last finished a round of work.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_panic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_panic_at_ms: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "health.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    // Panicked and waiting out its backoff before the next start.
+    Restarting,
+    // Returned on its own, e.g. a watcher with nothing to watch.
+    Finished,
+}
@@ -0,0 +1,284 @@
+// Request and response bodies shared by the server and its typed client.
+// Only serde, ts-rs and std here, and nothing from the Solana crates, so
+// this builds for wasm32 too. Each type's TypeScript goes to the file of
+// its module in api/bindings.
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+mod accounts;
+mod admin;
+mod airdrop;
+mod auth;
+mod dev;
+mod health;
+mod service;
+mod tokens;
+mod transactions;
+mod wallets;
+
+pub use accounts::*;
+pub use admin::*;
+pub use airdrop::*;
+pub use auth::*;
+pub use dev::*;
+pub use health::*;
+pub use service::*;
+pub use tokens::*;
+pub use transactions::*;
+pub use wallets::*;
+
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+// f64 cannot represent every lamport amount exactly, so clients that care
+// can ask for the SOL value as a decimal string instead.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+#[serde(untagged)]
+pub enum SolValue {
+    Number(f64),
+    Decimal(String),
+}
+
+impl SolValue {
+    pub fn new(lamports: u64, as_string: bool) -> Self {
+        if as_string {
+            SolValue::Decimal(lamports_to_sol_decimal(lamports))
+        } else {
+            SolValue::Number(lamports as f64 / LAMPORTS_PER_SOL as f64)
+        }
+    }
+
+    pub fn signed(lamports: i128, as_string: bool) -> Self {
+        if as_string {
+            SolValue::Decimal(signed_lamports_to_sol_decimal(lamports))
+        } else {
+            SolValue::Number(lamports as f64 / LAMPORTS_PER_SOL as f64)
+        }
+    }
+}
+
+// Exact decimal rendering, e.g. 1_500_000_001 -> "1.500000001", 2_000_000_000 -> "2".
+pub fn lamports_to_sol_decimal(lamports: u64) -> String {
+    let whole = lamports / LAMPORTS_PER_SOL;
+    let frac = lamports % LAMPORTS_PER_SOL;
+    if frac == 0 {
+        return whole.to_string();
+    }
+
+    let frac = format!("{:09}", frac);
+    format!("{}.{}", whole, frac.trim_end_matches('0'))
+}
+
+// Same as lamports_to_sol_decimal with a leading '-' for negative deltas.
+pub fn signed_lamports_to_sol_decimal(lamports: i128) -> String {
+    let magnitude = lamports_to_sol_decimal(lamports.unsigned_abs() as u64);
+    if lamports < 0 {
+        format!("-{}", magnitude)
+    } else {
+        magnitude
+    }
+}
+
+// Lamport amounts are numbers, or strings from servers (and requests)
+// using `lamports_as_string`: u64 amounts past 2^53 don't survive
+// JavaScript numbers. Every lamports field here is `with = "lamports"`
+// (or `lamports::optional`), takes both forms, and is written as a string
+// inside `as_strings`.
+pub mod lamports {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+    use std::{cell::Cell, fmt::Display, str::FromStr};
+
+    thread_local! {
+        static AS_STRINGS: Cell<bool> = const { Cell::new(false) };
+    }
+
+    // Runs `f`, which serializes a response, with lamport amounts written
+    // as strings when `enabled`. Binary formats keep native integers.
+    pub fn as_strings<R>(enabled: bool, f: impl FnOnce() -> R) -> R {
+        let previous = AS_STRINGS.replace(enabled);
+        let result = f();
+        AS_STRINGS.set(previous);
+        result
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Amount<T> {
+        Number(T),
+        String(String),
+    }
+
+    fn parse<T: FromStr, E: Error>(amount: Amount<T>) -> Result<T, E> {
+        match amount {
+            Amount::Number(lamports) => Ok(lamports),
+            Amount::String(text) => text
+                .parse()
+                .map_err(|_| E::custom(format!("invalid lamport amount '{}'", text))),
+        }
+    }
+
+    // Also signed amounts (balance changes) of any integer type.
+    pub fn serialize<S, T>(lamports: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize + Display,
+    {
+        if AS_STRINGS.get() && serializer.is_human_readable() {
+            serializer.collect_str(lamports)
+        } else {
+            lamports.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de> + FromStr,
+    {
+        parse(Amount::deserialize(deserializer)?)
+    }
+
+    pub mod optional {
+        use super::*;
+
+        pub fn serialize<S, T>(lamports: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+            T: Serialize + Display,
+        {
+            match lamports {
+                Some(lamports) => super::serialize(lamports, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: Deserialize<'de> + FromStr,
+        {
+            Option::<Amount<T>>::deserialize(deserializer)?
+                .map(parse)
+                .transpose()
+        }
+    }
+}
+
+// List parameters: a JSON array in bodies, or comma-separated in query
+// strings, which can't carry sequences.
+pub mod lists {
+    use serde::{Deserialize, Deserializer};
+
+    pub fn optional<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum List {
+            Items(Vec<String>),
+            Joined(String),
+        }
+        Ok(
+            Option::<List>::deserialize(deserializer)?.map(|list| match list {
+                List::Items(items) => items,
+                List::Joined(joined) => joined
+                    .split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect(),
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ts_rs::TS;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Amounts {
+        #[serde(with = "lamports")]
+        lamports: u64,
+        #[serde(default, with = "lamports::optional")]
+        fee_lamports: Option<u64>,
+        #[serde(with = "lamports")]
+        change_lamports: i64,
+    }
+
+    #[test]
+    fn lamports_take_numbers_and_strings() {
+        let a: Amounts = serde_json::from_str(
+            r#"{"lamports": "18446744073709551615", "fee_lamports": 5000, "change_lamports": "-5000"}"#,
+        )
+        .unwrap();
+        assert_eq!(a.lamports, u64::MAX);
+        assert_eq!(a.fee_lamports, Some(5_000));
+        assert_eq!(a.change_lamports, -5_000);
+
+        let a: Amounts = serde_json::from_str(r#"{"lamports": 1, "change_lamports": 2}"#).unwrap();
+        assert_eq!(a.fee_lamports, None);
+        assert!(
+            serde_json::from_str::<Amounts>(r#"{"lamports": "1 SOL", "change_lamports": 0}"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn lamports_are_written_as_strings_only_when_asked() {
+        let a = Amounts {
+            lamports: u64::MAX,
+            fee_lamports: Some(5_000),
+            change_lamports: -5_000,
+        };
+        let numbers =
+            r#"{"lamports":18446744073709551615,"fee_lamports":5000,"change_lamports":-5000}"#;
+        let strings = r#"{"lamports":"18446744073709551615","fee_lamports":"5000","change_lamports":"-5000"}"#;
+        assert_eq!(serde_json::to_string(&a).unwrap(), numbers);
+        let written = lamports::as_strings(true, || serde_json::to_string(&a).unwrap());
+        assert_eq!(written, strings);
+        assert_eq!(serde_json::from_str::<Amounts>(&written).unwrap(), a);
+        // Scoped to the closure.
+        assert_eq!(serde_json::to_string(&a).unwrap(), numbers);
+
+        let none = Amounts {
+            fee_lamports: None,
+            ..a
+        };
+        assert_eq!(
+            lamports::as_strings(true, || serde_json::to_value(&none).unwrap())["fee_lamports"],
+            serde_json::Value::Null
+        );
+    }
+
+    #[test]
+    fn lists_take_arrays_and_comma_separated_strings() {
+        let diff: WalletDiffRequest = serde_json::from_str(r#"{"wallets": "a, b,,c"}"#).unwrap();
+        assert_eq!(diff.wallets.unwrap(), ["a", "b", "c"]);
+        let diff: WalletDiffRequest = serde_json::from_str(r#"{"wallets": ["a", "b"]}"#).unwrap();
+        assert_eq!(diff.wallets.unwrap(), ["a", "b"]);
+    }
+
+    #[test]
+    fn sol_decimals_are_exact() {
+        assert_eq!(lamports_to_sol_decimal(1_500_000_001), "1.500000001");
+        assert_eq!(lamports_to_sol_decimal(2_000_000_000), "2");
+        assert_eq!(signed_lamports_to_sol_decimal(-1_000), "-0.000001");
+    }
+
+    #[test]
+    fn typescript_takes_lamports_as_numbers_or_strings() {
+        let config = ts_rs::Config::default().with_large_int("number");
+        assert_eq!(
+            GetBalanceResponse::decl(&config),
+            "type GetBalanceResponse = { wallet: string, balance_lamports: number | string, \
+             balance_sol: SolValue, balance_sol_decimal: string, \
+             balance_display?: string | null, };"
+        );
+        assert_eq!(
+            ApprovalStatus::decl(&config),
+            r#"type ApprovalStatus = "pending" | "approved" | "rejected" | "failed";"#
+        );
+    }
+}
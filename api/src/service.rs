@@ -0,0 +1,164 @@
+// What the server is: its routes, features, frontend config and build.
+use crate::{lamports, Role};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use ts_rs::TS;
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "service.ts")]
+pub struct ApiIndex {
+    pub service: String,
+    pub version: String,
+    // Without `require_auth`, anonymous callers have the funder role.
+    pub require_auth: bool,
+    pub endpoints: Vec<Endpoint>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "service.ts", optional_fields = nullable)]
+pub struct Endpoint {
+    pub path: String,
+    pub methods: Vec<String>,
+    // The role a caller needs; public routes have none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    // Enabled, or disabled by a feature flag or a setting (answered with
+    // 403 or 404). Hidden routes aren't listed.
+    pub state: FeatureState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled_by: Option<String>,
+}
+
+// Color scheme of the served frontend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "service.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum FrontendTheme {
+    Dark,
+    Light,
+}
+
+impl FromStr for FrontendTheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dark" => Ok(FrontendTheme::Dark),
+            "light" => Ok(FrontendTheme::Light),
+            _ => Err(format!("unknown frontend theme '{}'", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "service.ts", optional_fields = nullable)]
+pub struct FeatureInfo {
+    // A group name, or a route pattern for a flag on a single route.
+    pub name: String,
+    pub state: FeatureState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configured: Option<FeatureState>,
+    #[serde(rename = "override", skip_serializing_if = "Option::is_none")]
+    pub runtime_override: Option<FeatureState>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "service.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureState {
+    Enabled,
+    // Answered with 403 feature_disabled.
+    Disabled,
+    // Answered with a bare 404, as if the route didn't exist.
+    Hidden,
+}
+
+impl FromStr for FeatureState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "enabled" => Ok(FeatureState::Enabled),
+            "disabled" => Ok(FeatureState::Disabled),
+            "hidden" => Ok(FeatureState::Hidden),
+            other => Err(format!(
+                "unknown feature state '{}', expected enabled, disabled or hidden",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "service.ts", optional_fields = nullable)]
+pub struct Faucet {
+    pub enabled: bool,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub min_airdrop_lamports: u64,
+    pub max_airdrop_sol: u64,
+    // What the airdrop button asks for: 1 SOL, or less when that's over
+    // the limit.
+    pub default_airdrop_sol: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daily_quota_sol: Option<u64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub approval_threshold_lamports: Option<u64>,
+    // Airdrops come with a receipt at /receipts/{id}.
+    pub receipts: bool,
+}
+
+// What the served frontend needs to brand itself and show only what this
+// deployment offers, at /config.json.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "service.ts")]
+pub struct FrontendConfig {
+    pub title: String,
+    pub cluster: String,
+    pub theme: Theme,
+    pub faucet: Faucet,
+    pub features: FrontendFeatures,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "service.ts")]
+pub struct FrontendFeatures {
+    pub balance: bool,
+    pub sign_in: bool,
+    // Anonymous callers can't check balances or request airdrops.
+    pub require_auth: bool,
+    // Airdrops need a sign-in session for the receiving wallet.
+    pub sign_in_required: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "service.ts", optional_fields = nullable)]
+pub struct Theme {
+    pub mode: FrontendTheme,
+    pub accent_color: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo_url: Option<String>,
+}
+
+// What is deployed, as embedded by build.rs, at /version.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "service.ts")]
+pub struct BuildInfo {
+    pub service: String,
+    pub version: String,
+    pub git_commit: String,
+    // Built from a tree with uncommitted changes.
+    pub git_dirty: bool,
+    pub built_at: String,
+    // Cargo features compiled in.
+    pub features: Vec<String>,
+    pub solana_sdk: String,
+    pub rustc: String,
+}
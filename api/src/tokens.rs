@@ -0,0 +1,304 @@
+// NFTs, stake pools, governance and swaps.
+use crate::{lamports, ExplorerUrls};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts", optional_fields = nullable)]
+pub struct Proposal {
+    pub address: String,
+    pub governance: String,
+    pub governing_token_mint: String,
+    pub token_owner_record: String,
+    pub name: String,
+    pub description_link: String,
+    pub state: String,
+    pub vote_type: String,
+    pub votes: VoteCounts,
+    pub timeline: Timeline,
+    pub signatories_count: u8,
+    pub signatories_signed_off_count: u8,
+    // e.g. "yes_vote_percentage:60"; unset until voting starts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vote_threshold: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_voting_time_secs: Option<u32>,
+    pub options: Vec<ProposalOption>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct ProposalOption {
+    pub label: String,
+    pub vote_weight: u64,
+    pub vote_result: String,
+    pub transactions_count: u16,
+    pub transactions_executed_count: u16,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts", optional_fields = nullable)]
+pub struct Timeline {
+    pub draft_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing_off_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voting_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voting_at_slot: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voting_completed_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub executing_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<i64>,
+}
+
+// Raw governing token amounts. `yes` is only set for single choice
+// proposals; multiple choice weights are per option.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts", optional_fields = nullable)]
+pub struct VoteCounts {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub yes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abstain: Option<u64>,
+    pub veto: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_vote_weight: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct AssetProofResponse {
+    pub asset_id: String,
+    pub tree: String,
+    pub root: String,
+    pub leaf: String,
+    pub node_index: u64,
+    // Leaf to root; trim the tree's canopy before using it in an instruction.
+    pub proof: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct CnftTransferRequest {
+    pub new_owner: String,
+    // Dev mode and admins only: sign and send as the server keypair, which
+    // must own the asset.
+    #[serde(default)]
+    pub submit: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts", optional_fields = nullable)]
+pub struct CnftTransferResponse {
+    pub asset_id: String,
+    pub owner: String,
+    pub new_owner: String,
+    pub tree: String,
+    pub leaf_index: u32,
+    pub proof_nodes: usize,
+    pub canopy_depth: usize,
+    // Base64 transaction for the owner to sign, when not submitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_urls: Option<ExplorerUrls>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct FeeResponse {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct MintNftRequest {
+    pub wallet: String,
+    pub name: String,
+    #[serde(default)]
+    pub symbol: String,
+    #[serde(default)]
+    pub uri: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct MintNftResponse {
+    pub wallet: String,
+    pub mint: String,
+    pub token_account: String,
+    pub metadata: String,
+    pub master_edition: String,
+    pub transaction_signature: String,
+    pub explorer_url: String,
+    pub explorer_urls: ExplorerUrls,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts", optional_fields = nullable)]
+pub struct ProposalsQuery {
+    // e.g. "voting"; every state when unset.
+    pub state: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct ProposalsResponse {
+    pub realm: String,
+    // Matching proposals before `limit` was applied.
+    pub total: usize,
+    pub proposals: Vec<Proposal>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct StakePoolDepositRequest {
+    pub wallet: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub lamports: u64,
+    // Sign and send as the server keypair, which must be `wallet`.
+    #[serde(default)]
+    pub submit: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct StakePoolFees {
+    pub epoch: FeeResponse,
+    pub sol_deposit: FeeResponse,
+    pub sol_withdrawal: FeeResponse,
+    pub stake_deposit: FeeResponse,
+    pub stake_withdrawal: FeeResponse,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts", optional_fields = nullable)]
+pub struct StakePoolResponse {
+    pub pool: String,
+    pub program_id: String,
+    pub manager: String,
+    pub staker: String,
+    pub pool_mint: String,
+    pub token_program_id: String,
+    pub reserve_stake: String,
+    pub validator_list: String,
+    pub manager_fee_account: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub total_lamports: u64,
+    pub total_sol_decimal: String,
+    pub pool_token_supply: u64,
+    // SOL per pool token, as of the pool's last update.
+    pub exchange_rate: f64,
+    pub last_update_epoch: u64,
+    pub current_epoch: u64,
+    // SOL deposits and withdrawals fail until the pool is updated for the
+    // current epoch.
+    pub needs_update: bool,
+    pub fees: StakePoolFees,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sol_deposit_authority: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sol_withdraw_authority: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts", optional_fields = nullable)]
+pub struct StakePoolTransactionResponse {
+    pub pool: String,
+    pub wallet: String,
+    // Base64 transaction for `wallet` to sign, when not submitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_urls: Option<ExplorerUrls>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct StakePoolWithdrawRequest {
+    pub wallet: String,
+    // Raw pool token units.
+    pub pool_tokens: u64,
+    #[serde(default)]
+    pub submit: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct SwapBuildRequest {
+    // A /swap/quote response, unmodified.
+    pub quote: serde_json::Value,
+    pub user: String,
+    // Dev mode and admins only: sign and send as the server keypair, which
+    // must be `user`.
+    #[serde(default)]
+    pub submit: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts", optional_fields = nullable)]
+pub struct SwapBuildResponse {
+    pub user: String,
+    // Base64 versioned transaction for `user` to sign, when not submitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<String>,
+    pub last_valid_block_height: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_urls: Option<ExplorerUrls>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct ValidatorListResponse {
+    pub pool: String,
+    pub max_validators: u32,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub total_active_stake_lamports: u64,
+    pub validators: Vec<ValidatorStakeResponse>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts")]
+pub struct ValidatorStakeResponse {
+    pub vote_account: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub active_stake_lamports: u64,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub transient_stake_lamports: u64,
+    pub last_update_epoch: u64,
+    pub status: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "tokens.ts", optional_fields = nullable)]
+pub struct QuoteQuery {
+    pub input_mint: String,
+    pub output_mint: String,
+    // In the input mint's base units.
+    pub amount: u64,
+    pub slippage_bps: Option<u16>,
+}
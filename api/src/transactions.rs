@@ -0,0 +1,301 @@
+// Transactions, their history, decoding and receipts.
+use crate::{lamports, SolValue};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use ts_rs::TS;
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct Decoded {
+    pub decoder: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parsed: Option<serde_json::Value>,
+    // Set instead of `parsed` when the decoder rejected the data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts")]
+pub struct DecodeRequest {
+    // Base64.
+    pub data: String,
+    // Bytes to skip first, e.g. 8 for an Anchor discriminator.
+    #[serde(default)]
+    pub offset: usize,
+    // [{"name": "amount", "type": "u64"}, {"name": "owners", "type": {"vec": "pubkey"}}]
+    pub schema: Vec<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts")]
+pub struct DecodeResponse {
+    pub decoded: serde_json::Value,
+    pub bytes_read: usize,
+    // Data left over after the last field.
+    pub trailing_bytes: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct InstructionResponse {
+    pub program_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program_name: Option<String>,
+    pub accounts: Vec<String>,
+    // Base64.
+    pub data: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<Decoded>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inner_instructions: Vec<InstructionResponse>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct MemoSearchQuery {
+    pub q: String,
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts")]
+pub struct MemoSearchResponse {
+    pub query: String,
+    pub matches: Vec<Linked<MemoMatch>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts")]
+pub struct SignerCostResponse {
+    pub address: String,
+    pub fee_payer: bool,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub signature_fee_lamports: u64,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub paid_lamports: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct TransactionCostResponse {
+    pub signature: String,
+    pub explorer_urls: ExplorerUrls,
+    pub slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<i64>,
+    pub status: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub fee_lamports: u64,
+    pub fee_sol: SolValue,
+    pub fee_sol_decimal: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub base_fee_lamports: u64,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub priority_fee_lamports: u64,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub compute_unit_price_micro_lamports: u64,
+    pub compute_units_requested: u64,
+    // "instruction" when SetComputeUnitLimit was used, otherwise "default".
+    pub compute_unit_limit_source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compute_units_consumed: Option<u64>,
+    pub signers: Vec<SignerCostResponse>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct TransactionResponse {
+    pub signature: String,
+    pub explorer_urls: ExplorerUrls,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub status: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub fee_lamports: u64,
+    pub instructions: Vec<InstructionResponse>,
+    // Data programs logged ("Program data:"), e.g. Anchor events.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<ProgramEventResponse>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct ProgramEventResponse {
+    pub program_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program_name: Option<String>,
+    // Base64.
+    pub data: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<Decoded>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct TransactionStatusEvent {
+    pub signature: String,
+    pub explorer_urls: ExplorerUrls,
+    // "pending", "processed", "confirmed" or "finalized".
+    pub commitment: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct WalletTransactionsQuery {
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    // SOL, as a decimal string.
+    pub min_amount: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub before: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct WalletTransactionsResponse {
+    pub wallet: String,
+    pub transactions: Vec<Linked<IndexedTx>>,
+    // Transactions indexed for the wallet so far.
+    pub indexed: usize,
+    pub backfill_complete: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_before: Option<String>,
+}
+
+// A stored record naming a transaction, with the links added as it is
+// served: they depend on the cluster the server is on now, not the one it
+// was on when the record was written.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts")]
+pub struct Linked<T> {
+    #[serde(flatten)]
+    pub item: T,
+    pub explorer_urls: ExplorerUrls,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct Receipt {
+    pub receipt_id: String,
+    // Every check passed.
+    pub verified: bool,
+    pub signature: String,
+    pub explorer_urls: ExplorerUrls,
+    pub treasury: String,
+    pub slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<i64>,
+    pub checks: ReceiptChecks,
+    pub record: ReceiptRecord,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts")]
+pub struct ReceiptChecks {
+    pub transaction_succeeded: bool,
+    // The fee payer is this server's keypair.
+    pub paid_by_treasury: bool,
+    pub memo_matches: bool,
+    // A system transfer of the recorded amount to the recorded wallet.
+    pub transfer_matches: bool,
+}
+
+// The audit record behind a receipt, without who asked for the airdrop.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts")]
+pub struct ReceiptRecord {
+    pub timestamp_ms: u64,
+    pub action: String,
+    pub params: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct IndexedTx {
+    pub wallet: String,
+    pub signature: String,
+    pub slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<i64>,
+    pub succeeded: bool,
+    #[serde(rename = "type")]
+    pub kind: TxKind,
+    // The wallet's SOL change without the fee; negative when it sent.
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub change_lamports: i64,
+    // Paid by the wallet, when it was the fee payer.
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub fee_lamports: u64,
+    // The account that moved the most SOL the other way.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counterparty: Option<String>,
+    // Mints whose balance the wallet's token accounts changed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mints: Vec<String>,
+    // The text of its memo instructions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memos: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct MemoMatch {
+    pub signature: String,
+    // The watched wallets the transaction involves.
+    pub wallets: Vec<String>,
+    pub slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<i64>,
+    pub memos: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum TxKind {
+    // The wallet's SOL balance moved, beyond the fee.
+    Transfer,
+    // One of the wallet's token balances moved.
+    Token,
+    Other,
+}
+
+impl FromStr for TxKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transfer" => Ok(TxKind::Transfer),
+            "token" => Ok(TxKind::Token),
+            "other" => Ok(TxKind::Other),
+            _ => Err(format!("unknown transaction type '{}'", s)),
+        }
+    }
+}
+
+// Links to a transaction on the public explorers, on the server's cluster.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "transactions.ts", optional_fields = nullable)]
+pub struct ExplorerUrls {
+    pub solana_explorer: String,
+    pub solscan: String,
+    // Left out on clusters solana.fm can't show.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solana_fm: Option<String>,
+}
@@ -0,0 +1,611 @@
+// Balances, wallet summaries, signing, labels, alerts, watchlists and
+// events.
+use crate::{lamports, lists, ExplorerUrls, Failures, SolValue};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use ts_rs::TS;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum AlertFormat {
+    // A Slack (or Discord) incoming webhook message.
+    #[default]
+    Chat,
+    // The alert and the balance as JSON, for anything else.
+    Json,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct BalanceAlert {
+    pub id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // An address, or "treasury" for the server keypair.
+    pub wallet: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub below_lamports: u64,
+    // Once triggered, the alert only clears when the balance is back to
+    // `below_lamports + hysteresis_lamports`, so a balance hovering around
+    // the threshold doesn't alert on every check.
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub hysteresis_lamports: u64,
+    pub target: String,
+    #[serde(default)]
+    pub format: AlertFormat,
+    pub created_at_ms: u64,
+    #[serde(default)]
+    pub triggered: bool,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub last_balance_lamports: Option<u64>,
+    // When it last triggered or cleared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_at_ms: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum AmountFormat {
+    Raw,
+    Display,
+}
+
+// Per-request overrides for how amounts are rendered, taken from the query string.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct AmountOptions {
+    pub sol_as_string: Option<bool>,
+    pub format: Option<AmountFormat>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct Event {
+    // Increases by one per event since the server started.
+    pub id: u64,
+    pub kind: EventKind,
+    pub timestamp_ms: u64,
+    pub data: serde_json::Value,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    // A wallet the server keeps an eye on (the treasury, top-up wallets)
+    // changed balance between two checks.
+    BalanceChanged,
+    // A transaction of one of `event_programs`, with its logs.
+    ProgramTransaction,
+    // An airdrop or treasury transfer sent by the faucet was confirmed.
+    FaucetConfirmed,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::BalanceChanged => "balance_changed",
+            EventKind::ProgramTransaction => "program_transaction",
+            EventKind::FaucetConfirmed => "faucet_confirmed",
+        }
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_string()))
+            .map_err(|_| format!("unknown event kind '{}'", s))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct BalanceAlertRequest {
+    pub name: Option<String>,
+    // An address; the server keypair when omitted.
+    pub wallet: Option<String>,
+    pub below_sol: f64,
+    // Defaults to a tenth of `below_sol`.
+    pub hysteresis_sol: Option<f64>,
+    // A Slack or Discord incoming webhook, or any URL with format "json".
+    pub target: String,
+    #[serde(default)]
+    pub format: AlertFormat,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct BalanceAlertsResponse {
+    pub alerts: Vec<BalanceAlert>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct CounterpartySummary {
+    pub address: String,
+    pub transactions: u32,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub sent_to_lamports: u64,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub received_from_lamports: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct DiffSide {
+    pub wallet: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub balance_lamports: u64,
+    pub balance_sol_decimal: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct EventStreamQuery {
+    // Comma-separated event kinds; all of them when absent.
+    pub kinds: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct AnchorEventQuery {
+    // Comma-separated event names from the program's IDL; all of them
+    // when absent.
+    pub events: Option<String>,
+}
+
+// An Anchor event a program logged, as sent on /ws/events/{program_id}.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct AnchorEvent {
+    pub signature: String,
+    pub slot: u64,
+    pub program_id: String,
+    pub name: String,
+    // The event's fields, decoded with the IDL.
+    pub data: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct LabelQuery {
+    pub q: Option<String>,
+    pub tag: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct LabelsResponse {
+    pub labels: Vec<Label>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct QrQuery {
+    // Text to encode as is.
+    pub data: Option<String>,
+    // Or a Solana Pay transfer request to this address.
+    pub recipient: Option<String>,
+    pub amount: Option<String>,
+    pub spl_token: Option<String>,
+    // Comma separated.
+    pub reference: Option<String>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    pub memo: Option<String>,
+    #[serde(default)]
+    pub format: QrFormat,
+    // Width in pixels.
+    pub size: Option<u32>,
+    // Light modules around the code.
+    pub margin: Option<u32>,
+    #[serde(default)]
+    pub ec: EcLevel,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct SetLabelRequest {
+    pub label: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct SignMessageRequest {
+    pub message: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+    // "raw", or "offchain" (the default) for the offchain message envelope.
+    #[serde(default)]
+    pub format: SignedFormat,
+    // A dev key, generated on first use; "dev" when not given. "server"
+    // selects the configured keypair, which only signs offchain messages.
+    pub key: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct SignMessageResponse {
+    pub key: String,
+    pub pubkey: String,
+    pub signature: String,
+    pub format: String,
+    // Base64 of the exact bytes signed.
+    pub signed_message: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct SummaryQuery {
+    pub days: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct TokenActivitySummary {
+    pub mint: String,
+    pub decimals: u8,
+    pub received_amount: String,
+    pub sent_amount: String,
+    pub transactions: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct TokenDiffResponse {
+    pub mint: String,
+    pub decimals: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_amount: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_amount: Option<String>,
+    pub delta: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct VerifySignatureRequest {
+    pub pubkey: String,
+    pub signature: String,
+    pub message: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+    #[serde(default)]
+    pub format: SignedFormat,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct VerifySignatureResponse {
+    pub valid: bool,
+    pub pubkey: String,
+    pub format: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offchain_message: Option<ParsedOffchainMessage>,
+}
+
+// Either {"wallets": [from, to]} or {"wallet", "from_slot", "to_slot"}.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct WalletDiffRequest {
+    #[serde(default, deserialize_with = "lists::optional")]
+    pub wallets: Option<Vec<String>>,
+    pub wallet: Option<String>,
+    pub from_slot: Option<u64>,
+    pub to_slot: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct WalletDiffResponse {
+    pub mode: String,
+    pub from: DiffSide,
+    pub to: DiffSide,
+    pub lamports_delta: i128,
+    pub sol_delta: SolValue,
+    pub sol_delta_decimal: String,
+    pub tokens: Vec<TokenDiffResponse>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct WalletSummaryResponse {
+    pub wallet: String,
+    pub days: u32,
+    pub transaction_count: u32,
+    pub failed_transaction_count: u32,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub received_lamports: u64,
+    pub received_sol: SolValue,
+    pub received_sol_decimal: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub sent_lamports: u64,
+    pub sent_sol: SolValue,
+    pub sent_sol_decimal: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub fees_lamports: u64,
+    pub top_counterparties: Vec<CounterpartySummary>,
+    pub token_activity: Vec<TokenActivitySummary>,
+    pub truncated: bool,
+    pub cached: bool,
+    pub computed_at_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct WatchlistRequest {
+    pub name: String,
+    pub wallets: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct WatchlistsResponse {
+    pub total: usize,
+    pub watchlists: Vec<Watchlist>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct Label {
+    pub address: String,
+    pub label: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub updated_at_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub enum EcLevel {
+    // Recovers about 7% of the code.
+    #[serde(alias = "l")]
+    L,
+    // About 15%.
+    #[default]
+    #[serde(alias = "m")]
+    M,
+    // About 25%.
+    #[serde(alias = "q")]
+    Q,
+    // About 30%.
+    #[serde(alias = "h")]
+    H,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+impl QrFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            QrFormat::Svg => "image/svg+xml",
+            QrFormat::Png => "image/png",
+        }
+    }
+}
+
+// How the message travels in JSON.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum MessageEncoding {
+    #[default]
+    Utf8,
+    Base64,
+    Base58,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct ParsedOffchainMessage {
+    pub version: u8,
+    pub format: String,
+    // UTF-8 text, or base64 when the body isn't valid UTF-8.
+    pub message: String,
+    pub length: usize,
+}
+
+// What was signed: the message bytes as given, or the message wrapped in the
+// offchain message envelope (`solana sign-offchain-message`). With `auto`, a
+// message that is already an envelope is verified as one, and anything else
+// is tried both ways.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum SignedFormat {
+    #[default]
+    Auto,
+    Raw,
+    Offchain,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct Activity {
+    pub wallet: String,
+    pub signature: String,
+    pub slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<i64>,
+    pub succeeded: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    pub explorer_urls: ExplorerUrls,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct Member {
+    pub wallet: String,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub balance_lamports: Option<u64>,
+    // Set when the wallet couldn't be loaded; it is left out of the totals.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct Overview {
+    pub id: u64,
+    pub name: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub total_lamports: u64,
+    pub total_sol_decimal: String,
+    pub tokens: Vec<TokenTotal>,
+    // Newest first, across all members.
+    pub recent_activity: Vec<Activity>,
+    pub members: Vec<Member>,
+    // Members that couldn't be loaded, by error.
+    #[serde(skip_serializing_if = "Failures::is_empty")]
+    pub failures: Failures,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct TokenTotal {
+    pub mint: String,
+    pub decimals: u8,
+    // Raw amount summed over every member, as a string since it can
+    // exceed what JSON numbers hold exactly.
+    pub amount: String,
+    pub holders: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct Watchlist {
+    pub id: u64,
+    pub name: String,
+    pub wallets: Vec<String>,
+    // Subject of the credential that created it; only it can see the list.
+    pub owner: String,
+    pub created_at_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts")]
+pub struct GetBalance {
+    // Was `wallet`; see `deprecations`.
+    #[serde(alias = "wallet")]
+    pub address: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct GetBalanceResponse {
+    pub wallet: String,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub balance_lamports: u64,
+    pub balance_sol: SolValue,
+    pub balance_sol_decimal: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_display: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct WalletBalanceQuery {
+    // Holds the request until the balance differs from `previous_lamports`
+    // or this long passes ("30s"); answers at once when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait_for_change: Option<String>,
+    // The balance the caller last saw; the balance when the request
+    // arrived if unset.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "lamports::optional"
+    )]
+    #[ts(type = "number | string | null")]
+    pub previous_lamports: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct WalletBalanceResponse {
+    pub wallet: String,
+    // At "confirmed" commitment.
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub balance_lamports: u64,
+    pub balance_sol: SolValue,
+    pub balance_sol_decimal: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_display: Option<String>,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub previous_lamports: u64,
+    // False when the wait timed out.
+    pub changed: bool,
+    pub waited_ms: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct BalanceAtQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "wallets.ts", optional_fields = nullable)]
+pub struct BalanceAtResponse {
+    pub wallet: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<i64>,
+    #[serde(with = "lamports")]
+    #[ts(type = "number | string")]
+    pub balance_lamports: u64,
+    pub balance_sol: SolValue,
+    pub balance_sol_decimal: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_display: Option<String>,
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_slot: Option<u64>,
+    pub rpc: String,
+}
@@ -0,0 +1,231 @@
+use axum::{
+    extract::{FromRequestParts, Path},
+    http::request::Parts,
+};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use solana_sdk::{hash::hashv, pubkey, pubkey::Pubkey};
+use std::{fmt, str::FromStr};
+
+use crate::{
+    handlers::{service_error, ApiError},
+    rpc,
+    service::{rpc_error, ServiceError},
+    state::AppState,
+};
+
+// SPL Name Service, which owns .sol domains.
+const NAME_PROGRAM_ID: Pubkey = pubkey!("namesLPneVptA9Z5rqUDD9tMTWEJwofgaYwp8cawRkX");
+// The .sol top-level domain every name is registered under.
+const SOL_TLD: Pubkey = pubkey!("58PwtjSDuFHuUkYjH9BYnnQKHfwo9reZhC2zMJv9JPkx");
+const HASH_PREFIX: &str = "SPL Name Service";
+// Name record header: parent, owner, class.
+const OWNER_OFFSET: usize = 32;
+
+// A base58 public key, validated wherever it is parsed: in request types,
+// path parameters (see `AddressPath`) or from strings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WalletAddress(pub Pubkey);
+
+impl FromStr for WalletAddress {
+    type Err = ServiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Pubkey::from_str(s)
+            .map(WalletAddress)
+            .map_err(|_| ServiceError::InvalidWallet)
+    }
+}
+
+impl fmt::Display for WalletAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<'de> Deserialize<'de> for WalletAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|_| serde::de::Error::custom(format!("invalid wallet address '{}'", s)))
+    }
+}
+
+impl Serialize for WalletAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl From<WalletAddress> for Pubkey {
+    fn from(address: WalletAddress) -> Self {
+        address.0
+    }
+}
+
+// The name record of `name` (without ".sol") under the .sol TLD.
+fn domain_account(name: &str) -> Pubkey {
+    let hashed = hashv(&[HASH_PREFIX.as_bytes(), name.as_bytes()]);
+    let (account, _) = Pubkey::find_program_address(
+        &[
+            hashed.as_ref(),
+            Pubkey::default().as_ref(),
+            SOL_TLD.as_ref(),
+        ],
+        &NAME_PROGRAM_ID,
+    );
+    account
+}
+
+// Blocking: the owner of a .sol domain such as "bonfida.sol". Subdomains
+// aren't supported.
+pub fn resolve_sol_domain(rpc_url: &str, domain: &str) -> Result<Pubkey, ServiceError> {
+    let name = domain
+        .strip_suffix(".sol")
+        .filter(|name| !name.is_empty() && !name.contains('.'))
+        .ok_or_else(|| ServiceError::Invalid(format!("'{}' is not a .sol domain", domain)))?;
+    let account = domain_account(name);
+    let client = rpc::client(rpc_url);
+    let _span = tracing::info_span!("rpc.resolve_sol_domain", %rpc_url, domain).entered();
+    let data = client
+        .get_account_data(&account)
+        .map_err(rpc_error("Failed to resolve domain"))?;
+    data.get(OWNER_OFFSET..OWNER_OFFSET + 32)
+        .and_then(|owner| Pubkey::try_from(owner).ok())
+        .ok_or_else(|| ServiceError::Invalid(format!("'{}' has no owner record", domain)))
+}
+
+// Path parameters that are addresses: a single `WalletAddress` or a tuple
+// of them. Invalid ones are rejected as "Invalid wallet address" like any
+// other; with `resolve_sol_domains` on, .sol domains are accepted and
+// resolved to their owner first.
+pub struct AddressPath<T>(pub T);
+
+impl<T> FromRequestParts<AppState> for AddressPath<T>
+where
+    T: DeserializeOwned + Send,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Path(params) = Path::<Vec<(String, String)>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| service_error(ServiceError::InvalidWallet))?;
+        let config = state.config();
+        let mut values = Vec::with_capacity(params.len());
+        for (_, value) in params {
+            if config.resolve_sol_domains && value.ends_with(".sol") {
+                let rpc_url = config.rpc_url.clone();
+                let owner = rpc::spawn_blocking(move || resolve_sol_domain(&rpc_url, &value))
+                    .await
+                    .map_err(|e| service_error(ServiceError::Invalid(e.to_string())))?
+                    .map_err(service_error)?;
+                values.push(serde_json::Value::String(owner.to_string()));
+            } else {
+                values.push(serde_json::Value::String(value));
+            }
+        }
+        let value = match values.len() {
+            1 => values.pop().unwrap_or_default(),
+            _ => serde_json::Value::Array(values),
+        };
+        serde_json::from_value(value)
+            .map(AddressPath)
+            .map_err(|_| service_error(ServiceError::InvalidWallet))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn invalid<T>(result: Result<T, ServiceError>) -> String {
+        match result {
+            Err(ServiceError::Invalid(message)) => message,
+            _ => panic!("expected an invalid request error"),
+        }
+    }
+
+    #[test]
+    fn addresses_parse_and_serialize() {
+        let key = Pubkey::new_unique();
+        let address: WalletAddress = key.to_string().parse().unwrap();
+        assert_eq!(Pubkey::from(address), key);
+        assert_eq!(
+            serde_json::to_string(&address).unwrap(),
+            format!("\"{}\"", key)
+        );
+        assert!(matches!(
+            "not-a-key".parse::<WalletAddress>(),
+            Err(ServiceError::InvalidWallet)
+        ));
+        let err = serde_json::from_str::<WalletAddress>("\"0OIl\"").unwrap_err();
+        assert_eq!(err.to_string(), "invalid wallet address '0OIl'");
+    }
+
+    #[test]
+    fn domain_account_known_answer() {
+        // As derived by Bonfida's SDK (getDomainKeySync("bonfida")).
+        assert_eq!(
+            domain_account("bonfida").to_string(),
+            "Crf8hzfthWGbGbLTVCiqRqV5MVnbpHB1L9KQMd6gsinb"
+        );
+    }
+
+    #[test]
+    fn only_top_level_sol_domains_resolve() {
+        let chain = rpc::mock_for_tests();
+        for domain in ["bonfida", ".sol", "sub.bonfida.sol", "bonfida.eth"] {
+            assert_eq!(
+                invalid(resolve_sol_domain("http://mock", domain)),
+                format!("'{}' is not a .sol domain", domain)
+            );
+        }
+        // The mock's accounts carry no data, so no owner record either.
+        chain.set_balance(domain_account("empty"), 1);
+        assert_eq!(
+            invalid(resolve_sol_domain("http://mock", "empty.sol")),
+            "'empty.sol' has no owner record"
+        );
+    }
+
+    #[tokio::test]
+    async fn path_addresses_are_validated() {
+        use axum::{routing::get, Router};
+
+        let state = AppState::for_tests(Config::default());
+        let app = Router::new()
+            .route(
+                "/one/{a}",
+                get(|AddressPath(a): AddressPath<WalletAddress>| async move { a.to_string() }),
+            )
+            .route(
+                "/two/{a}/{b}",
+                get(
+                    |AddressPath((a, b)): AddressPath<(WalletAddress, WalletAddress)>| async move {
+                        format!("{} {}", a, b)
+                    },
+                ),
+            )
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let get = |path: String| reqwest::get(format!("{}{}", url, path));
+        let res = get(format!("/one/{}", a)).await.unwrap();
+        assert_eq!(res.text().await.unwrap(), a.to_string());
+        let res = get(format!("/two/{}/{}", a, b)).await.unwrap();
+        assert_eq!(res.text().await.unwrap(), format!("{} {}", a, b));
+        let res = get(format!("/two/{}/nope", a)).await.unwrap();
+        assert_eq!(res.status(), 400);
+        assert!(res.text().await.unwrap().contains("Invalid wallet address"));
+        // Domains are only resolved when `resolve_sol_domains` is on.
+        let res = get("/one/bonfida.sol".to_string()).await.unwrap();
+        assert_eq!(res.status(), 400);
+    }
+}
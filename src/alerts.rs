@@ -0,0 +1,450 @@
+use serde::Serialize;
+use solana_sdk::{
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+    signature::Signer,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{
+    amount,
+    clock::now_ms,
+    fanout, logging,
+    service::{self, ServiceError},
+    state::AppState,
+    store::Store,
+    tasks,
+};
+
+pub use crate::api::{AlertFormat, BalanceAlert};
+
+const STORE_NAME: &str = "balance_alerts";
+const MAX_ALERTS: usize = 100;
+const MAX_NAME_LENGTH: usize = 64;
+// The wallet of alerts on the server keypair, whichever it is.
+pub const TREASURY: &str = "treasury";
+// Hysteresis of alerts that don't give one: a tenth of the threshold.
+const DEFAULT_HYSTERESIS_DIVISOR: u64 = 10;
+
+pub struct NewAlert {
+    pub name: Option<String>,
+    pub wallet: String,
+    pub below_sol: f64,
+    pub hysteresis_sol: Option<f64>,
+    pub target: String,
+    pub format: AlertFormat,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Transition {
+    Triggered,
+    Cleared,
+}
+
+// Balance thresholds on the treasury and other wallets, each alerting its
+// own webhook when the balance drops below it and again when it recovers.
+// Saved as JSON under "balance_alerts" in the store (`balance_alerts_path`
+// for files), with whether each is triggered, so a restart doesn't repeat
+// alerts.
+pub struct AlertStore {
+    store: Arc<dyn Store>,
+    next_id: AtomicU64,
+    entries: Mutex<BTreeMap<u64, BalanceAlert>>,
+}
+
+fn invalid(message: impl Into<String>) -> ServiceError {
+    ServiceError::Invalid(message.into())
+}
+
+impl AlertStore {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let mut entries = BTreeMap::new();
+        if let Some(text) = store.read(STORE_NAME)? {
+            let alerts: Vec<BalanceAlert> = serde_json::from_str(&text).map_err(|e| {
+                format!(
+                    "invalid balance alerts in {}: {}",
+                    store.location(STORE_NAME),
+                    e
+                )
+            })?;
+            for alert in alerts {
+                entries.insert(alert.id, alert);
+            }
+        }
+        Ok(AlertStore {
+            store,
+            next_id: AtomicU64::new(entries.keys().next_back().copied().unwrap_or_default()),
+            entries: Mutex::new(entries),
+        })
+    }
+
+    pub fn create(&self, new: NewAlert) -> Result<BalanceAlert, ServiceError> {
+        let wallet = if new.wallet == TREASURY {
+            new.wallet
+        } else {
+            service::parse_wallet(&new.wallet)?.to_string()
+        };
+        if !new.below_sol.is_finite() || new.below_sol <= 0.0 {
+            return Err(invalid("'below_sol' must be positive"));
+        }
+        let below_lamports = sol_to_lamports(new.below_sol);
+        let hysteresis_lamports = match new.hysteresis_sol {
+            Some(sol) if !sol.is_finite() || sol < 0.0 => {
+                return Err(invalid("'hysteresis_sol' must be 0 or more"))
+            }
+            Some(sol) => sol_to_lamports(sol),
+            None => below_lamports / DEFAULT_HYSTERESIS_DIVISOR,
+        };
+        if !new.target.starts_with("https://") && !new.target.starts_with("http://") {
+            return Err(invalid("'target' must be an http(s) URL"));
+        }
+        let name = new.name.map(|n| n.trim().to_string());
+        if name
+            .as_ref()
+            .is_some_and(|n| n.is_empty() || n.chars().count() > MAX_NAME_LENGTH)
+        {
+            return Err(invalid(format!(
+                "Alert names must be 1-{} characters",
+                MAX_NAME_LENGTH
+            )));
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_ALERTS {
+            return Err(invalid(format!("At most {} balance alerts", MAX_ALERTS)));
+        }
+        let alert = BalanceAlert {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+            name,
+            wallet,
+            below_lamports,
+            hysteresis_lamports,
+            target: new.target,
+            format: new.format,
+            created_at_ms: now_ms(),
+            triggered: false,
+            last_balance_lamports: None,
+            changed_at_ms: None,
+        };
+        entries.insert(alert.id, alert.clone());
+        if let Err(e) = self.save(&entries) {
+            entries.remove(&alert.id);
+            return Err(ServiceError::Rpc {
+                action: "Failed to save balance alert",
+                message: e,
+            });
+        }
+        Ok(alert)
+    }
+
+    pub fn list(&self) -> Vec<BalanceAlert> {
+        self.entries.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn remove(&self, id: u64) -> Result<bool, String> {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(previous) = entries.remove(&id) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.save(&entries) {
+            entries.insert(id, previous);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    // Records the balances (by alert wallet) and returns the alerts that
+    // triggered or cleared on them.
+    fn observe(&self, balances: &HashMap<String, u64>) -> Vec<(BalanceAlert, Transition)> {
+        let mut entries = self.entries.lock().unwrap();
+        let mut changed = Vec::new();
+        for alert in entries.values_mut() {
+            let Some(&balance) = balances.get(&alert.wallet) else {
+                continue;
+            };
+            alert.last_balance_lamports = Some(balance);
+            let transition = if !alert.triggered && balance < alert.below_lamports {
+                Transition::Triggered
+            } else if alert.triggered
+                && balance
+                    >= alert
+                        .below_lamports
+                        .saturating_add(alert.hysteresis_lamports)
+            {
+                Transition::Cleared
+            } else {
+                continue;
+            };
+            alert.triggered = matches!(transition, Transition::Triggered);
+            alert.changed_at_ms = Some(now_ms());
+            changed.push((alert.clone(), transition));
+        }
+        // The last balances alone aren't worth a write.
+        if !changed.is_empty() {
+            if let Err(e) = self.save(&entries) {
+                logging::warn(format_args!("Failed to save balance alerts: {}", e));
+            }
+        }
+        changed
+    }
+
+    fn save(&self, entries: &BTreeMap<u64, BalanceAlert>) -> Result<(), String> {
+        if !self.store.persists(STORE_NAME) {
+            return Ok(());
+        }
+        let alerts: Vec<&BalanceAlert> = entries.values().collect();
+        let text = serde_json::to_string_pretty(&alerts).map_err(|e| e.to_string())?;
+        self.store.write(STORE_NAME, &text)
+    }
+}
+
+fn message(alert: &BalanceAlert, address: &Pubkey, transition: Transition, balance: u64) -> String {
+    let subject = match &alert.name {
+        Some(name) => format!("{} ({})", name, address),
+        None if alert.wallet == TREASURY => format!("Treasury {}", address),
+        None => format!("Wallet {}", address),
+    };
+    let balance = amount::lamports_to_sol_decimal(balance);
+    match transition {
+        Transition::Triggered => format!(
+            "{} is down to {} SOL, below {} SOL",
+            subject,
+            balance,
+            lamports_to_sol(alert.below_lamports)
+        ),
+        Transition::Cleared => format!(
+            "{} is back to {} SOL, above {} SOL",
+            subject,
+            balance,
+            lamports_to_sol(
+                alert
+                    .below_lamports
+                    .saturating_add(alert.hysteresis_lamports)
+            )
+        ),
+    }
+}
+
+// Checks every alerted wallet every `balance_alert_interval_secs` and
+// posts the alerts that trigger or clear to their targets.
+pub async fn watch(state: AppState) {
+    loop {
+        let config = state.config();
+        let treasury = state.keypair.as_ref().map(|k| k.pubkey());
+        let mut addresses: HashMap<String, Pubkey> = HashMap::new();
+        for alert in state.alerts.list() {
+            let address = match alert.wallet.as_str() {
+                TREASURY => treasury,
+                wallet => service::parse_wallet(wallet).ok(),
+            };
+            if let Some(address) = address {
+                addresses.insert(alert.wallet, address);
+            }
+        }
+
+        let rpc_url = config.rpc_url.clone();
+        let lookups: Vec<Pubkey> = addresses.values().copied().collect();
+        let results = fanout::run(fanout::Limits::new(&config), lookups, move |address| {
+            service::get_balance(&rpc_url, &address.to_string())
+        })
+        .await;
+        let failures = fanout::failures(&results);
+        if !failures.is_empty() {
+            logging::warn(format_args!(
+                "Failed to check some balance alerts: {}",
+                failures
+            ));
+        }
+        let balances: HashMap<String, u64> = addresses
+            .keys()
+            .zip(results)
+            .filter_map(|(wallet, result)| Some((wallet.clone(), result.ok()?)))
+            .collect();
+
+        for (alert, transition) in state.alerts.observe(&balances) {
+            let (Some(address), Some(&balance)) =
+                (addresses.get(&alert.wallet), balances.get(&alert.wallet))
+            else {
+                continue;
+            };
+            let text = message(&alert, address, transition, balance);
+            match alert.format {
+                AlertFormat::Chat => state.notifier.send(&alert.target, &text).await,
+                AlertFormat::Json => {
+                    let body = serde_json::json!({
+                        "alert_id": alert.id,
+                        "name": alert.name,
+                        "wallet": address.to_string(),
+                        "state": transition,
+                        "balance_lamports": balance,
+                        "below_lamports": alert.below_lamports,
+                        "hysteresis_lamports": alert.hysteresis_lamports,
+                        "message": text,
+                    });
+                    state.notifier.send_json(&alert.target, &body).await
+                }
+            }
+        }
+        tasks::succeeded();
+        tokio::time::sleep(Duration::from_secs(config.balance_alert_interval_secs)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::FlakyStore;
+
+    fn new_alert(wallet: &str, below_sol: f64, hysteresis_sol: Option<f64>) -> NewAlert {
+        NewAlert {
+            name: None,
+            wallet: wallet.to_string(),
+            below_sol,
+            hysteresis_sol,
+            target: "https://hooks.example/alerts".to_string(),
+            format: AlertFormat::Json,
+        }
+    }
+
+    fn invalid<T>(result: Result<T, ServiceError>) -> String {
+        match result {
+            Err(ServiceError::Invalid(message)) => message,
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    fn transitions(alerts: &AlertStore, wallet: &str, lamports: u64) -> Vec<&'static str> {
+        alerts
+            .observe(&HashMap::from([(wallet.to_string(), lamports)]))
+            .into_iter()
+            .map(|(_, t)| match t {
+                Transition::Triggered => "triggered",
+                Transition::Cleared => "cleared",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn alerts_are_validated() {
+        let alerts = AlertStore::load(Arc::new(FlakyStore::default())).unwrap();
+        let alert = alerts.create(new_alert(TREASURY, 2.0, None)).unwrap();
+        assert_eq!((alert.id, alert.below_lamports), (1, 2_000_000_000));
+        assert_eq!(alert.hysteresis_lamports, 200_000_000);
+
+        for below in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                invalid(alerts.create(new_alert(TREASURY, below, None))),
+                "'below_sol' must be positive"
+            );
+        }
+        assert_eq!(
+            invalid(alerts.create(new_alert(TREASURY, 1.0, Some(-0.1)))),
+            "'hysteresis_sol' must be 0 or more"
+        );
+        assert!(matches!(
+            alerts.create(new_alert("nope", 1.0, None)),
+            Err(ServiceError::InvalidWallet)
+        ));
+        let ftp = NewAlert {
+            target: "ftp://example".to_string(),
+            ..new_alert(TREASURY, 1.0, None)
+        };
+        assert_eq!(
+            invalid(alerts.create(ftp)),
+            "'target' must be an http(s) URL"
+        );
+        let unnamed = NewAlert {
+            name: Some("  ".to_string()),
+            ..new_alert(TREASURY, 1.0, None)
+        };
+        assert_eq!(
+            invalid(alerts.create(unnamed)),
+            "Alert names must be 1-64 characters"
+        );
+
+        while alerts.list().len() < MAX_ALERTS {
+            alerts.create(new_alert(TREASURY, 1.0, None)).unwrap();
+        }
+        assert_eq!(
+            invalid(alerts.create(new_alert(TREASURY, 1.0, None))),
+            "At most 100 balance alerts"
+        );
+    }
+
+    #[test]
+    fn thresholds_trigger_and_clear_with_hysteresis() {
+        let store = Arc::new(FlakyStore::default());
+        let alerts = AlertStore::load(store.clone()).unwrap();
+        let wallet = Pubkey::new_unique().to_string();
+        alerts.create(new_alert(&wallet, 1.0, None)).unwrap();
+
+        assert!(transitions(&alerts, &wallet, 2_000_000_000).is_empty());
+        assert_eq!(transitions(&alerts, &wallet, 900_000_000), ["triggered"]);
+        assert!(transitions(&alerts, &wallet, 800_000_000).is_empty());
+        // Back above the threshold, but not past the hysteresis.
+        assert!(transitions(&alerts, &wallet, 1_050_000_000).is_empty());
+        assert_eq!(transitions(&alerts, &wallet, 1_100_000_000), ["cleared"]);
+        assert_eq!(transitions(&alerts, &wallet, 500_000_000), ["triggered"]);
+        assert!(transitions(&alerts, "treasury", 0).is_empty());
+
+        // A restart remembers the alert is triggered, and keeps numbering.
+        let reloaded = AlertStore::load(store).unwrap();
+        assert!(transitions(&reloaded, &wallet, 400_000_000).is_empty());
+        let alert = &reloaded.list()[0];
+        assert!(alert.triggered);
+        assert_eq!(alert.last_balance_lamports, Some(400_000_000));
+        assert_eq!(
+            reloaded.create(new_alert(TREASURY, 1.0, None)).unwrap().id,
+            2
+        );
+    }
+
+    #[test]
+    fn unsaved_changes_roll_back() {
+        let store = Arc::new(FlakyStore::default());
+        let alerts = AlertStore::load(store.clone()).unwrap();
+        let alert = alerts.create(new_alert(TREASURY, 1.0, None)).unwrap();
+        store.fail(true);
+        assert!(alerts.create(new_alert(TREASURY, 1.0, None)).is_err());
+        assert!(alerts.remove(alert.id).is_err());
+        assert_eq!(alerts.list().len(), 1);
+        store.fail(false);
+        assert!(alerts.remove(alert.id).unwrap());
+        assert!(!alerts.remove(alert.id).unwrap());
+    }
+
+    #[test]
+    fn messages() {
+        let alerts = AlertStore::load(Arc::new(FlakyStore::default())).unwrap();
+        let address = Pubkey::new_unique();
+        let treasury = alerts.create(new_alert(TREASURY, 2.0, Some(0.5))).unwrap();
+        assert_eq!(
+            message(&treasury, &address, Transition::Triggered, 1_250_000_000),
+            format!("Treasury {} is down to 1.25 SOL, below 2 SOL", address)
+        );
+        assert_eq!(
+            message(&treasury, &address, Transition::Cleared, 3_000_000_000),
+            format!("Treasury {} is back to 3 SOL, above 2.5 SOL", address)
+        );
+        let named = NewAlert {
+            name: Some(" CI wallet ".to_string()),
+            ..new_alert(&address.to_string(), 1.0, None)
+        };
+        let named = alerts.create(named).unwrap();
+        assert_eq!(
+            message(&named, &address, Transition::Triggered, 1),
+            format!(
+                "CI wallet ({}) is down to 0.000000001 SOL, below 1 SOL",
+                address
+            )
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    denylist,
+    service::{self, ServiceError},
+    store::Store,
+};
+
+const STORE_NAME: &str = "allowlist";
+
+// Wallets registered through the admin API. Only consulted when
+// `allowlist_only` is on; saved one per line under "allowlist" in the store
+// (`allowlist_path` for files) so the list survives restarts.
+pub struct Allowlist {
+    store: Arc<dyn Store>,
+    addresses: RwLock<BTreeSet<Pubkey>>,
+}
+
+impl Allowlist {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let addresses = match store.read(STORE_NAME)? {
+            Some(text) => denylist::parse_addresses(&text)
+                .map_err(|e| format!("{}: {}", store.location(STORE_NAME), e))?
+                .into_iter()
+                .collect(),
+            None => BTreeSet::new(),
+        };
+        Ok(Allowlist {
+            store,
+            addresses: RwLock::new(addresses),
+        })
+    }
+
+    pub fn check(&self, wallet: &str, enforced: bool) -> Result<(), ServiceError> {
+        let pubkey = service::parse_wallet(wallet)?;
+        if enforced && !self.addresses.read().unwrap().contains(&pubkey) {
+            return Err(ServiceError::NotAllowlisted(pubkey));
+        }
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<Pubkey> {
+        self.addresses.read().unwrap().iter().copied().collect()
+    }
+
+    // Returns the addresses that were not already present.
+    pub fn add(&self, wallets: &[Pubkey]) -> Result<Vec<Pubkey>, String> {
+        let mut addresses = self.addresses.write().unwrap();
+        let added: Vec<Pubkey> = wallets
+            .iter()
+            .copied()
+            .filter(|w| addresses.insert(*w))
+            .collect();
+        if !added.is_empty() {
+            if let Err(e) = self.save(&addresses) {
+                for wallet in &added {
+                    addresses.remove(wallet);
+                }
+                return Err(e);
+            }
+        }
+        Ok(added)
+    }
+
+    pub fn remove(&self, wallet: &Pubkey) -> Result<bool, String> {
+        let mut addresses = self.addresses.write().unwrap();
+        if !addresses.remove(wallet) {
+            return Ok(false);
+        }
+        if let Err(e) = self.save(&addresses) {
+            addresses.insert(*wallet);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn save(&self, addresses: &BTreeSet<Pubkey>) -> Result<(), String> {
+        if !self.store.persists(STORE_NAME) {
+            return Ok(());
+        }
+        let mut text = String::new();
+        for address in addresses {
+            text.push_str(&address.to_string());
+            text.push('\n');
+        }
+        self.store.write(STORE_NAME, &text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{FlakyStore, MemoryStore};
+
+    #[test]
+    fn registered_wallets_survive_a_reload() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        let allowlist = Allowlist::load(store.clone()).unwrap();
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(matches!(
+            allowlist.check(&a.to_string(), true),
+            Err(ServiceError::NotAllowlisted(wallet)) if wallet == a
+        ));
+        assert!(allowlist.check(&a.to_string(), false).is_ok());
+
+        assert_eq!(allowlist.add(&[a, b]).unwrap(), [a, b]);
+        assert_eq!(allowlist.add(&[a]).unwrap(), []);
+        assert_eq!(allowlist.remove(&b), Ok(true));
+        assert_eq!(allowlist.remove(&b), Ok(false));
+
+        let reloaded = Allowlist::load(store).unwrap();
+        assert_eq!(reloaded.list(), [a]);
+        assert!(reloaded.check(&a.to_string(), true).is_ok());
+    }
+
+    #[test]
+    fn failed_saves_are_rolled_back() {
+        let store = Arc::new(FlakyStore::default());
+        let allowlist = Allowlist::load(store.clone()).unwrap();
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        allowlist.add(&[a]).unwrap();
+
+        store.fail(true);
+        assert_eq!(allowlist.add(&[a, b]), Err("disk full".to_string()));
+        assert_eq!(allowlist.remove(&a), Err("disk full".to_string()));
+        assert_eq!(allowlist.list(), [a]);
+    }
+
+    #[test]
+    fn corrupt_lists_name_the_store() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        store.write(STORE_NAME, "oops\n").unwrap();
+        assert_eq!(
+            Allowlist::load(store).err().unwrap(),
+            "memory store 'allowlist': line 1: invalid address 'oops'"
+        );
+    }
+}
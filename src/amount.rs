@@ -0,0 +1,122 @@
+use crate::config::Config;
+
+pub use crate::api::{
+    lamports_to_sol_decimal, signed_lamports_to_sol_decimal, AmountFormat, AmountOptions, SolValue,
+};
+
+// Fraction digits shown in human-readable amounts.
+const DISPLAY_DECIMALS: u32 = 4;
+
+pub fn sol_as_string(options: &AmountOptions, config: &Config) -> bool {
+    options.sol_as_string.unwrap_or(config.sol_as_string)
+}
+
+// Some("1,234.5678 SOL") when the client asked for format=display.
+pub fn display(options: &AmountOptions, lamports: u64, config: &Config) -> Option<String> {
+    (options.format == Some(AmountFormat::Display))
+        .then(|| format_sol_display(lamports, &config.display_locale))
+}
+
+// (group separator, decimal separator) keyed by language, falling back to en.
+fn locale_separators(locale: &str) -> (&'static str, &'static str) {
+    if locale.eq_ignore_ascii_case("de-CH") {
+        return ("\u{2019}", ".");
+    }
+    let language = locale.split(['-', '_']).next().unwrap_or_default();
+    match language.to_ascii_lowercase().as_str() {
+        "de" | "es" | "it" | "pt" | "nl" | "id" | "tr" | "da" => (".", ","),
+        "fr" | "ru" | "pl" | "cs" | "sv" | "nb" | "fi" | "uk" => ("\u{202f}", ","),
+        _ => (",", "."),
+    }
+}
+
+pub fn is_supported_locale(locale: &str) -> bool {
+    let mut parts = locale.split(['-', '_']);
+    let language = parts.next().unwrap_or_default();
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && parts.all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+// Rounds half-up to DISPLAY_DECIMALS and groups thousands per locale,
+// e.g. 1_234_567_800_000 lamports in en-US -> "1,234.5678 SOL".
+pub fn format_sol_display(lamports: u64, locale: &str) -> String {
+    let (group, decimal) = locale_separators(locale);
+
+    let step = 10u64.pow(9 - DISPLAY_DECIMALS);
+    let units = lamports / step + u64::from(lamports % step >= step / 2);
+    let scale = 10u64.pow(DISPLAY_DECIMALS);
+    let (whole, frac) = (units / scale, units % scale);
+
+    let digits = whole.to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push_str(group);
+        }
+        grouped.push(c);
+    }
+
+    if frac == 0 {
+        return format!("{} SOL", grouped);
+    }
+    let frac = format!("{:0width$}", frac, width = DISPLAY_DECIMALS as usize);
+    format!("{}{}{} SOL", grouped, decimal, frac.trim_end_matches('0'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_amounts_round_half_up_and_group_by_locale() {
+        assert_eq!(
+            format_sol_display(1_234_567_800_000, "en-US"),
+            "1,234.5678 SOL"
+        );
+        assert_eq!(
+            format_sol_display(1_234_567_800_000, "de-DE"),
+            "1.234,5678 SOL"
+        );
+        assert_eq!(
+            format_sol_display(1_234_567_800_000, "de-CH"),
+            "1\u{2019}234.5678 SOL"
+        );
+        assert_eq!(
+            format_sol_display(1_234_567_800_000, "fr"),
+            "1\u{202f}234,5678 SOL"
+        );
+        assert_eq!(format_sol_display(1_500_000_000, "xx"), "1.5 SOL");
+        assert_eq!(format_sol_display(0, "en-US"), "0 SOL");
+        // 0.00004999 rounds down, 0.00005 up to the fourth decimal.
+        assert_eq!(format_sol_display(49_999, "en-US"), "0 SOL");
+        assert_eq!(format_sol_display(50_000, "en-US"), "0.0001 SOL");
+        assert_eq!(format_sol_display(999_950_000, "en-US"), "1 SOL");
+        assert_eq!(
+            format_sol_display(u64::MAX, "en-US"),
+            "18,446,744,073.7096 SOL"
+        );
+    }
+
+    #[test]
+    fn locales_are_language_tags() {
+        for locale in ["en", "en-US", "pt_BR", "zh-Hant-TW", "fil"] {
+            assert!(is_supported_locale(locale), "{}", locale);
+        }
+        for locale in ["", "e", "english", "en-", "en-U S", "1a"] {
+            assert!(!is_supported_locale(locale), "{}", locale);
+        }
+    }
+
+    #[test]
+    fn display_is_opt_in() {
+        let config = Config::default();
+        let mut options = AmountOptions::default();
+        assert_eq!(display(&options, 1, &config), None);
+        options.format = Some(AmountFormat::Display);
+        assert_eq!(
+            display(&options, 1_000_000_000, &config).as_deref(),
+            Some("1 SOL")
+        );
+    }
+}
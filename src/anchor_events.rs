@@ -0,0 +1,328 @@
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket};
+use futures_util::StreamExt;
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient,
+    rpc_config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter},
+    rpc_response::RpcLogsResponse,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::{config::Config, decoders::Registry, index, inspect, logging};
+
+pub use crate::api::AnchorEvent;
+
+static STREAMS: AtomicUsize = AtomicUsize::new(0);
+
+// Counts a connected /ws/events client until it is dropped.
+pub struct Slot(());
+
+impl Slot {
+    // None when `max_event_streams` clients are connected already.
+    pub fn take(config: &Config) -> Option<Slot> {
+        STREAMS
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |streams| {
+                (streams < config.max_event_streams).then_some(streams + 1)
+            })
+            .ok()
+            .map(|_| Slot(()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        STREAMS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// The events `program_id` logged in a transaction, decoded with its IDL.
+// Failed transactions are skipped, their events having been rolled back,
+// as is logged data no event in the IDL matches. `names` keeps only those
+// events. Events emitted with emit_cpi! are inner instructions, not log
+// lines, so they aren't seen here.
+pub fn decode(
+    decoders: &Registry,
+    program_id: &Pubkey,
+    names: Option<&[String]>,
+    slot: u64,
+    logs: &RpcLogsResponse,
+) -> Vec<AnchorEvent> {
+    if logs.err.is_some() {
+        return Vec::new();
+    }
+    inspect::events(decoders, &logs.logs)
+        .into_iter()
+        .filter(|event| event.program_id == *program_id)
+        .filter_map(|event| {
+            let mut parsed = event.decoded?.parsed?;
+            let name = parsed.get("type")?.as_str()?.to_string();
+            if names.is_some_and(|names| !names.contains(&name)) {
+                return None;
+            }
+            Some(AnchorEvent {
+                signature: logs.signature.clone(),
+                slot,
+                program_id: program_id.to_string(),
+                name,
+                data: parsed.get_mut("info").map(serde_json::Value::take)?,
+            })
+        })
+        .collect()
+}
+
+// Sends the program's events to the client as JSON text messages until it
+// hangs up. The socket is closed with an error when the node's logs
+// subscription can't be made or drops; clients reconnect to resume.
+pub async fn forward(
+    mut socket: WebSocket,
+    _slot: Slot,
+    config: Arc<Config>,
+    decoders: Arc<Registry>,
+    program_id: Pubkey,
+    names: Option<Vec<String>>,
+) {
+    let url = config
+        .index_ws_url
+        .clone()
+        .unwrap_or_else(|| index::ws_url(&config.rpc_url));
+    let Err(e) = follow(&mut socket, &url, &decoders, &program_id, names.as_deref()).await else {
+        return;
+    };
+    logging::warn(format_args!(
+        "Event stream for {} on {} failed: {}",
+        program_id, url, e
+    ));
+    let _ = socket
+        .send(Message::Close(Some(CloseFrame {
+            code: close_code::ERROR,
+            reason: "The RPC node's logs subscription failed".into(),
+        })))
+        .await;
+}
+
+// Ok once the client goes away.
+async fn follow(
+    socket: &mut WebSocket,
+    url: &str,
+    decoders: &Registry,
+    program_id: &Pubkey,
+    names: Option<&[String]>,
+) -> Result<(), String> {
+    let client = PubsubClient::new(url).await.map_err(|e| e.to_string())?;
+    let (mut logs, _unsubscribe) = client
+        .logs_subscribe(
+            RpcTransactionLogsFilter::Mentions(vec![program_id.to_string()]),
+            RpcTransactionLogsConfig {
+                commitment: Some(CommitmentConfig::confirmed()),
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    loop {
+        tokio::select! {
+            update = logs.next() => {
+                let Some(update) = update else {
+                    return Err("the subscription closed".to_string());
+                };
+                for event in decode(decoders, program_id, names, update.context.slot, &update.value) {
+                    let text = serde_json::to_string(&event).map_err(|e| e.to_string())?;
+                    if socket.send(Message::Text(text.into())).await.is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return Ok(()),
+                // Pings are answered by axum; nothing else is expected.
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, idl::Idl, state::AppState};
+    use axum::{extract::ws::WebSocketUpgrade, routing::get, Router};
+    use base64::Engine;
+    use serde_json::{json, Value};
+    use tokio_tungstenite::tungstenite;
+
+    const DEPOSITED: [u8; 8] = [1; 8];
+    const WITHDRAWN: [u8; 8] = [2; 8];
+
+    fn idl(program_id: &Pubkey) -> Idl {
+        let amount = json!({ "kind": "struct", "fields": [{ "name": "amount", "type": "u64" }] });
+        Idl::parse(&json!({
+            "address": program_id.to_string(),
+            "metadata": { "name": "vault" },
+            "instructions": [],
+            "events": [
+                { "name": "Deposited", "discriminator": DEPOSITED },
+                { "name": "Withdrawn", "discriminator": WITHDRAWN },
+            ],
+            "types": [
+                { "name": "Deposited", "type": amount },
+                { "name": "Withdrawn", "type": amount },
+            ],
+        }))
+        .unwrap()
+        .1
+    }
+
+    fn data_line(discriminator: [u8; 8], amount: u64) -> String {
+        let mut data = discriminator.to_vec();
+        data.extend(amount.to_le_bytes());
+        format!(
+            "Program data: {}",
+            base64::engine::general_purpose::STANDARD.encode(data)
+        )
+    }
+
+    // A vault deposit and withdrawal, with data logged by another program
+    // the vault calls in between.
+    fn logs(program_id: &Pubkey, other: &Pubkey) -> Vec<String> {
+        vec![
+            format!("Program {} invoke [1]", program_id),
+            data_line(DEPOSITED, 5),
+            format!("Program {} invoke [2]", other),
+            data_line(DEPOSITED, 6),
+            format!("Program {} success", other),
+            data_line(WITHDRAWN, 7),
+            "Program data: bm90IGFuIGV2ZW50".to_string(),
+            format!("Program {} success", program_id),
+        ]
+    }
+
+    fn registry(program_id: &Pubkey) -> Registry {
+        let decoders = Registry::default();
+        decoders.register(*program_id, Arc::new(idl(program_id)));
+        decoders
+    }
+
+    #[test]
+    fn decodes_the_programs_own_events() {
+        let program_id = Pubkey::new_unique();
+        let decoders = registry(&program_id);
+        let mut response = RpcLogsResponse {
+            signature: "sig".to_string(),
+            err: None,
+            logs: logs(&program_id, &Pubkey::new_unique()),
+        };
+        let events = decode(&decoders, &program_id, None, 9, &response);
+        let summary: Vec<(&str, &Value)> =
+            events.iter().map(|e| (e.name.as_str(), &e.data)).collect();
+        assert_eq!(
+            summary,
+            [
+                ("Deposited", &json!({ "amount": 5 })),
+                ("Withdrawn", &json!({ "amount": 7 })),
+            ]
+        );
+        assert_eq!((events[0].signature.as_str(), events[0].slot), ("sig", 9));
+        assert_eq!(events[0].program_id, program_id.to_string());
+
+        let only = ["Withdrawn".to_string()];
+        let events = decode(&decoders, &program_id, Some(&only), 9, &response);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name, "Withdrawn");
+
+        response.err = Some(solana_sdk::transaction::TransactionError::AccountInUse);
+        assert!(decode(&decoders, &program_id, None, 9, &response).is_empty());
+    }
+
+    // Answers one logsSubscribe and then sends `logs` as a notification.
+    async fn fake_node(logs: Vec<String>) -> String {
+        let app = Router::new().route(
+            "/",
+            get(|upgrade: WebSocketUpgrade| async move {
+                upgrade.on_upgrade(move |mut socket| async move {
+                    let Some(Ok(Message::Text(request))) = socket.recv().await else {
+                        return;
+                    };
+                    let request: Value = serde_json::from_str(&request).unwrap();
+                    assert_eq!(request["method"], "logsSubscribe");
+                    let replies = [
+                        json!({ "jsonrpc": "2.0", "result": 7, "id": request["id"] }),
+                        json!({
+                            "jsonrpc": "2.0",
+                            "method": "logsNotification",
+                            "params": {
+                                "subscription": 7,
+                                "result": {
+                                    "context": { "slot": 42 },
+                                    "value": { "signature": "sig", "err": null, "logs": logs },
+                                },
+                            },
+                        }),
+                    ];
+                    for reply in replies {
+                        let _ = socket.send(Message::Text(reply.to_string().into())).await;
+                    }
+                    // Held open until the server hangs up.
+                    while let Some(Ok(_)) = socket.recv().await {}
+                })
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("ws://{}/", addr)
+    }
+
+    #[tokio::test]
+    async fn streams_decoded_events_over_a_websocket() {
+        let program_id = Pubkey::new_unique();
+        let node = fake_node(logs(&program_id, &Pubkey::new_unique())).await;
+        let state = AppState::for_tests(Config {
+            index_ws_url: Some(node),
+            ..Config::default()
+        });
+        state
+            .decoders
+            .register(program_id, Arc::new(idl(&program_id)));
+        let app = Router::new()
+            .route(
+                "/ws/events/{program_id}",
+                get(crate::handlers::stream_anchor_events),
+            )
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let url =
+            |program: &Pubkey, query: &str| format!("ws://{}/ws/events/{}{}", addr, program, query);
+
+        let (mut socket, _) =
+            tokio_tungstenite::connect_async(url(&program_id, "?events=Deposited"))
+                .await
+                .unwrap();
+        let tungstenite::Message::Text(text) = socket.next().await.unwrap().unwrap() else {
+            panic!("expected a text message");
+        };
+        let event: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(
+            event,
+            json!({
+                "signature": "sig",
+                "slot": 42,
+                "program_id": program_id.to_string(),
+                "name": "Deposited",
+                "data": { "amount": 5 },
+            })
+        );
+
+        let status = |result: Result<_, tungstenite::Error>| match result {
+            Err(tungstenite::Error::Http(response)) => response.status().as_u16(),
+            other => panic!("expected an HTTP error, got {:?}", other.map(|_| ())),
+        };
+        let unknown = tokio_tungstenite::connect_async(url(&Pubkey::new_unique(), "")).await;
+        assert_eq!(status(unknown), 404);
+        let misnamed = tokio_tungstenite::connect_async(url(&program_id, "?events=Nope")).await;
+        assert_eq!(status(misnamed), 400);
+    }
+}
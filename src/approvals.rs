@@ -0,0 +1,556 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{audit::Actor, clock::now_ms, logging, service::ServiceError, store::Store, telemetry};
+
+pub use crate::api::{Approval, ApprovalStatus};
+
+const STORE_NAME: &str = "approvals";
+// Decided approvals are kept for polling until the oldest are dropped.
+const MAX_APPROVALS: usize = 1_000;
+const MAX_PENDING: usize = 200;
+// So one caller can't fill the queue for everyone else.
+const MAX_PENDING_PER_CALLER: usize = 10;
+const CALLBACK_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum DecisionError {
+    NotFound,
+    AlreadyDecided(ApprovalStatus),
+    Save(String),
+}
+
+impl fmt::Display for DecisionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecisionError::NotFound => write!(f, "Approval not found"),
+            DecisionError::AlreadyDecided(status) => write!(f, "Approval is already {}", status),
+            DecisionError::Save(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+// Saved as JSON under "approvals" in the store (`approvals_path` for
+// files), so pending requests survive a restart.
+pub struct ApprovalStore {
+    store: Arc<dyn Store>,
+    client: reqwest::Client,
+    entries: Mutex<BTreeMap<String, Approval>>,
+}
+
+impl ApprovalStore {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let mut entries = BTreeMap::new();
+        if let Some(text) = store.read(STORE_NAME)? {
+            let approvals: Vec<Approval> = serde_json::from_str(&text).map_err(|e| {
+                format!("invalid approvals in {}: {}", store.location(STORE_NAME), e)
+            })?;
+            for approval in approvals {
+                entries.insert(approval.id.clone(), approval);
+            }
+        }
+        // Redirects aren't followed: an allowed host could otherwise send
+        // the callback on to one that isn't.
+        let client = reqwest::Client::builder()
+            .connect_timeout(CALLBACK_CONNECT_TIMEOUT)
+            .timeout(CALLBACK_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| format!("Failed to build the approval callback client: {}", e))?;
+        Ok(ApprovalStore {
+            store,
+            client,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    pub fn create(
+        &self,
+        wallet: &str,
+        lamports: u64,
+        requested_by: Actor,
+        callback_url: Option<String>,
+        callback_hosts: &[String],
+    ) -> Result<Approval, ServiceError> {
+        if let Some(url) = &callback_url {
+            check_callback(url, callback_hosts).map_err(ServiceError::Invalid)?;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let pending: Vec<&Approval> = entries
+            .values()
+            .filter(|a| a.status == ApprovalStatus::Pending)
+            .collect();
+        if pending.len() >= MAX_PENDING {
+            return Err(ServiceError::Invalid(format!(
+                "Too many airdrops are awaiting approval (max {})",
+                MAX_PENDING
+            )));
+        }
+        if let Some(caller) = requester(&requested_by) {
+            let own = pending
+                .iter()
+                .filter(|a| requester(&a.requested_by) == Some(caller))
+                .count();
+            if own >= MAX_PENDING_PER_CALLER {
+                return Err(ServiceError::Invalid(format!(
+                    "You have too many airdrops awaiting approval (max {})",
+                    MAX_PENDING_PER_CALLER
+                )));
+            }
+        }
+        if entries.len() >= MAX_APPROVALS {
+            let oldest = entries
+                .values()
+                .filter(|a| a.status != ApprovalStatus::Pending)
+                .min_by_key(|a| a.requested_at_ms)
+                .map(|a| a.id.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        let approval = Approval {
+            id: format!("{:032x}", rand::random::<u128>()),
+            wallet: wallet.to_string(),
+            lamports,
+            status: ApprovalStatus::Pending,
+            requested_by,
+            requested_at_ms: now_ms(),
+            callback_url,
+            decided_by: None,
+            decided_at_ms: None,
+            reason: None,
+            signature: None,
+            error: None,
+        };
+        entries.insert(approval.id.clone(), approval.clone());
+        if let Err(e) = self.save(&entries) {
+            entries.remove(&approval.id);
+            return Err(ServiceError::Rpc {
+                action: "Failed to save approval",
+                message: e,
+            });
+        }
+        Ok(approval)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Approval> {
+        self.entries.lock().unwrap().get(id).cloned()
+    }
+
+    // Newest first.
+    pub fn list(&self, status: Option<ApprovalStatus>) -> Vec<Approval> {
+        let mut approvals: Vec<Approval> = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|a| status.is_none_or(|s| a.status == s))
+            .cloned()
+            .collect();
+        approvals.sort_by_key(|a| std::cmp::Reverse(a.requested_at_ms));
+        approvals
+    }
+
+    // Moves a pending approval to `status`. Approving claims it, so two
+    // admins approving at once can't send the airdrop twice; `finish`
+    // records how the airdrop went.
+    pub fn decide(
+        &self,
+        id: &str,
+        status: ApprovalStatus,
+        decided_by: Option<String>,
+        reason: Option<String>,
+    ) -> Result<Approval, DecisionError> {
+        self.update(id, |approval| {
+            if approval.status != ApprovalStatus::Pending {
+                return Err(DecisionError::AlreadyDecided(approval.status));
+            }
+            approval.status = status;
+            approval.decided_by = decided_by;
+            approval.decided_at_ms = Some(now_ms());
+            approval.reason = reason;
+            Ok(())
+        })
+    }
+
+    pub fn finish(
+        &self,
+        id: &str,
+        result: Result<String, String>,
+    ) -> Result<Approval, DecisionError> {
+        self.update(id, |approval| {
+            match result {
+                Ok(signature) => approval.signature = Some(signature),
+                Err(error) => {
+                    approval.status = ApprovalStatus::Failed;
+                    approval.error = Some(error);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn update(
+        &self,
+        id: &str,
+        f: impl FnOnce(&mut Approval) -> Result<(), DecisionError>,
+    ) -> Result<Approval, DecisionError> {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(approval) = entries.get_mut(id) else {
+            return Err(DecisionError::NotFound);
+        };
+        let previous = approval.clone();
+        f(approval)?;
+        let updated = approval.clone();
+        if let Err(e) = self.save(&entries) {
+            entries.insert(id.to_string(), previous);
+            return Err(DecisionError::Save(e));
+        }
+        Ok(updated)
+    }
+
+    // Tells the requester about a decided approval, when it left a
+    // callback_url. Failures are only logged; the requester can still poll.
+    // The host is checked again, as it may have been dropped from
+    // `callback_hosts` while the approval was pending.
+    pub async fn send_callback(&self, approval: &Approval, callback_hosts: &[String]) {
+        let Some(url) = &approval.callback_url else {
+            return;
+        };
+        if let Err(e) = check_callback(url, callback_hosts) {
+            logging::warn(format_args!("Not sending approval callback: {}", e));
+            return;
+        }
+        let result = telemetry::traced(self.client.post(url))
+            .json(approval)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            logging::warn(format_args!("Failed to send approval callback: {}", e));
+        }
+    }
+
+    fn save(&self, entries: &BTreeMap<String, Approval>) -> Result<(), String> {
+        if !self.store.persists(STORE_NAME) {
+            return Ok(());
+        }
+        let approvals: Vec<&Approval> = entries.values().collect();
+        let text = serde_json::to_string_pretty(&approvals).map_err(|e| e.to_string())?;
+        self.store.write(STORE_NAME, &text)
+    }
+}
+
+// Who an approval counts against for MAX_PENDING_PER_CALLER: the
+// authenticated subject, else the caller's IP, as for rate limits.
+fn requester(actor: &Actor) -> Option<&str> {
+    actor.subject.as_deref().or(actor.ip.as_deref())
+}
+
+// A callback_url must be http(s) and name one of the configured hosts.
+fn check_callback(url: &str, callback_hosts: &[String]) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url)
+        .ok()
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .ok_or_else(|| "callback_url must be an http(s) URL".to_string())?;
+    let host = parsed.host_str().unwrap_or_default();
+    if !callback_hosts.iter().any(|h| h.eq_ignore_ascii_case(host)) {
+        return Err(if callback_hosts.is_empty() {
+            "callback_url isn't accepted: no callback hosts are configured".to_string()
+        } else {
+            format!(
+                "callback_url host '{}' isn't an allowed callback host",
+                host
+            )
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::FlakyStore;
+    use axum::{routing::post, Json, Router};
+    use tokio::sync::mpsc;
+
+    fn store() -> (Arc<FlakyStore>, ApprovalStore) {
+        let backing = Arc::new(FlakyStore::default());
+        let approvals = ApprovalStore::load(backing.clone()).unwrap();
+        (backing, approvals)
+    }
+
+    fn request(approvals: &ApprovalStore, lamports: u64) -> Approval {
+        approvals
+            .create("wallet", lamports, Actor::system("api"), None, &[])
+            .unwrap()
+    }
+
+    #[test]
+    fn approvals_are_decided_once() {
+        let (backing, approvals) = store();
+        let approval = request(&approvals, 5_000_000_000);
+        assert_eq!(approval.status, ApprovalStatus::Pending);
+        assert_eq!(approval.id.len(), 32);
+
+        let approved = approvals
+            .decide(
+                &approval.id,
+                ApprovalStatus::Approved,
+                Some("ops".into()),
+                None,
+            )
+            .unwrap();
+        assert_eq!(approved.decided_by.as_deref(), Some("ops"));
+        assert!(approved.decided_at_ms.is_some());
+        assert_eq!(
+            approvals
+                .decide(&approval.id, ApprovalStatus::Rejected, None, None)
+                .err()
+                .unwrap()
+                .to_string(),
+            "Approval is already approved"
+        );
+        assert!(matches!(
+            approvals.decide("missing", ApprovalStatus::Approved, None, None),
+            Err(DecisionError::NotFound)
+        ));
+
+        let finished = approvals.finish(&approval.id, Ok("sig".into())).unwrap();
+        assert_eq!(
+            (finished.status, finished.signature.as_deref()),
+            (ApprovalStatus::Approved, Some("sig"))
+        );
+
+        let other = request(&approvals, 1);
+        approvals
+            .decide(&other.id, ApprovalStatus::Approved, None, None)
+            .unwrap();
+        let failed = approvals
+            .finish(&other.id, Err("node down".into()))
+            .unwrap();
+        assert_eq!(
+            (failed.status, failed.error.as_deref()),
+            (ApprovalStatus::Failed, Some("node down"))
+        );
+
+        let reloaded = ApprovalStore::load(backing).unwrap();
+        assert_eq!(reloaded.list(None).len(), 2);
+        assert_eq!(reloaded.list(Some(ApprovalStatus::Failed))[0].id, other.id);
+    }
+
+    #[test]
+    fn failed_saves_roll_back() {
+        let (backing, approvals) = store();
+        let approval = request(&approvals, 1);
+        backing.fail(true);
+        assert!(matches!(
+            approvals.create("wallet", 1, Actor::system("api"), None, &[]),
+            Err(ServiceError::Rpc {
+                action: "Failed to save approval",
+                ..
+            })
+        ));
+        assert!(matches!(
+            approvals.decide(&approval.id, ApprovalStatus::Approved, None, None),
+            Err(DecisionError::Save(message)) if message == "disk full"
+        ));
+        backing.fail(false);
+        assert_eq!(approvals.list(None).len(), 1);
+        assert_eq!(
+            approvals.get(&approval.id).unwrap().status,
+            ApprovalStatus::Pending
+        );
+    }
+
+    fn request_template() -> Approval {
+        Approval {
+            id: String::new(),
+            wallet: "wallet".into(),
+            lamports: 1,
+            status: ApprovalStatus::Pending,
+            requested_by: Actor::system("api"),
+            requested_at_ms: 0,
+            callback_url: None,
+            decided_by: None,
+            decided_at_ms: None,
+            reason: None,
+            signature: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn limits_on_pending_and_kept_approvals() {
+        let (_, approvals) = store();
+        for _ in 0..MAX_PENDING {
+            request(&approvals, 1);
+        }
+        assert!(matches!(
+            approvals.create("wallet", 1, Actor::system("api"), None, &[]),
+            Err(ServiceError::Invalid(message))
+                if message == "Too many airdrops are awaiting approval (max 200)"
+        ));
+
+        // Fill up with decided approvals; a new request drops the oldest.
+        let mut entries = approvals.entries.lock().unwrap();
+        entries.clear();
+        for i in 0..MAX_APPROVALS {
+            let mut approval = Approval {
+                id: format!("old-{}", i),
+                requested_at_ms: i as u64,
+                ..request_template()
+            };
+            approval.status = ApprovalStatus::Rejected;
+            entries.insert(approval.id.clone(), approval);
+        }
+        drop(entries);
+        request(&approvals, 1);
+        assert_eq!(approvals.list(None).len(), MAX_APPROVALS);
+        assert!(approvals.get("old-0").is_none());
+        assert!(approvals.get("old-1").is_some());
+    }
+
+    #[test]
+    fn pending_approvals_are_capped_per_caller() {
+        let (_, approvals) = store();
+        let caller = |subject: Option<&str>, ip: &str| Actor {
+            subject: subject.map(str::to_string),
+            ip: Some(ip.to_string()),
+            ..Actor::system("http")
+        };
+        for _ in 0..MAX_PENDING_PER_CALLER {
+            approvals
+                .create("wallet", 1, caller(None, "10.0.0.1"), None, &[])
+                .unwrap();
+        }
+        assert!(matches!(
+            approvals.create("wallet", 1, caller(None, "10.0.0.1"), None, &[]),
+            Err(ServiceError::Invalid(message))
+                if message == "You have too many airdrops awaiting approval (max 10)"
+        ));
+        // Other callers, and the same IP signed in, still get through.
+        approvals
+            .create("wallet", 1, caller(None, "10.0.0.2"), None, &[])
+            .unwrap();
+        approvals
+            .create("wallet", 1, caller(Some("alice"), "10.0.0.1"), None, &[])
+            .unwrap();
+
+        // Deciding one frees a slot.
+        let first = approvals
+            .list(None)
+            .into_iter()
+            .find(|a| {
+                a.requested_by.subject.is_none() && a.requested_by.ip.as_deref() == Some("10.0.0.1")
+            })
+            .unwrap();
+        approvals
+            .decide(&first.id, ApprovalStatus::Rejected, None, None)
+            .unwrap();
+        approvals
+            .create("wallet", 1, caller(None, "10.0.0.1"), None, &[])
+            .unwrap();
+    }
+
+    #[test]
+    fn callbacks_only_go_to_configured_hosts() {
+        let hosts = vec!["hooks.example.com".to_string()];
+        assert_eq!(
+            check_callback("https://hooks.example.com/a", &hosts),
+            Ok(())
+        );
+        assert_eq!(
+            check_callback("http://HOOKS.example.com:8080/a", &hosts),
+            Ok(())
+        );
+        for (url, error) in [
+            (
+                "ftp://hooks.example.com/a",
+                "callback_url must be an http(s) URL",
+            ),
+            ("not a url", "callback_url must be an http(s) URL"),
+            (
+                "http://169.254.169.254/latest/meta-data",
+                "callback_url host '169.254.169.254' isn't an allowed callback host",
+            ),
+            (
+                "http://hooks.example.com.evil.test/a",
+                "callback_url host 'hooks.example.com.evil.test' isn't an allowed callback host",
+            ),
+        ] {
+            assert_eq!(
+                check_callback(url, &hosts),
+                Err(error.to_string()),
+                "{}",
+                url
+            );
+        }
+        assert_eq!(
+            check_callback("https://hooks.example.com/a", &[]),
+            Err("callback_url isn't accepted: no callback hosts are configured".to_string())
+        );
+
+        let (_, approvals) = store();
+        assert!(matches!(
+            approvals.create(
+                "wallet",
+                1,
+                Actor::system("api"),
+                Some("http://localhost:8899/".into()),
+                &hosts,
+            ),
+            Err(ServiceError::Invalid(message))
+                if message == "callback_url host 'localhost' isn't an allowed callback host"
+        ));
+        assert!(approvals.list(None).is_empty());
+    }
+
+    #[tokio::test]
+    async fn callbacks_post_the_decision() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(approval): Json<Approval>| async move {
+                tx.send(approval).unwrap();
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (_, approvals) = store();
+        let hosts = vec!["127.0.0.1".to_string()];
+        let approval = approvals
+            .create(
+                "wallet",
+                1,
+                Actor::system("api"),
+                Some(format!("http://{}/hook", addr)),
+                &hosts,
+            )
+            .unwrap();
+        let rejected = approvals
+            .decide(
+                &approval.id,
+                ApprovalStatus::Rejected,
+                None,
+                Some("too much".into()),
+            )
+            .unwrap();
+        // Not sent once the host is no longer allowed.
+        approvals.send_callback(&rejected, &[]).await;
+        approvals.send_callback(&rejected, &hosts).await;
+
+        let received = rx.recv().await.unwrap();
+        assert_eq!(received.id, approval.id);
+        assert_eq!(received.status, ApprovalStatus::Rejected);
+        assert_eq!(received.reason.as_deref(), Some("too much"));
+        assert!(rx.try_recv().is_err());
+    }
+}
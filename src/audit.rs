@@ -0,0 +1,206 @@
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use crate::{auth::Principal, clock, logging, store::Store};
+
+pub use crate::api::{Actor, AuditRecord};
+
+// Who made an HTTP request, for handlers to audit.
+pub struct Caller(pub Actor);
+
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+        let forwarded_for = parts
+            .headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let principal = parts.extensions.get::<Principal>();
+        let subject = principal.and_then(|p| p.subject.clone());
+        let org = principal.and_then(|p| p.org.clone());
+
+        Ok(Caller(Actor {
+            source: "http".to_string(),
+            ip,
+            forwarded_for,
+            subject,
+            org,
+        }))
+    }
+}
+
+// A successful `action`, as of now; see AuditRecord's builder methods for
+// the rest.
+pub fn record(action: &str, actor: Actor, params: serde_json::Value) -> AuditRecord {
+    AuditRecord {
+        timestamp_ms: clock::now_ms(),
+        action: action.to_string(),
+        actor,
+        params,
+        outcome: "success".to_string(),
+        error: None,
+        signature: None,
+        receipt: None,
+    }
+}
+
+const STORE_NAME: &str = "audit_log";
+
+// Append-only JSON lines, kept by the store under "audit_log". Disabled when
+// the store doesn't keep it.
+pub struct AuditLog {
+    store: Arc<dyn Store>,
+}
+
+impl AuditLog {
+    pub fn new(store: Arc<dyn Store>) -> Self {
+        AuditLog { store }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.store.persists(STORE_NAME)
+    }
+
+    pub fn record(&self, record: AuditRecord) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let result = serde_json::to_string(&record)
+            .map_err(|e| e.to_string())
+            .and_then(|line| self.store.append(STORE_NAME, &line));
+
+        if let Err(e) = result {
+            logging::error(format_args!("Failed to write audit record: {}", e));
+        }
+    }
+
+    pub fn find_receipt(&self, id: &str) -> Result<Option<AuditRecord>, String> {
+        let contents = self
+            .store
+            .read(STORE_NAME)
+            .map_err(|e| format!("failed to read audit log: {}", e))?
+            .unwrap_or_default();
+
+        Ok(contents
+            .lines()
+            .filter(|line| line.contains(id))
+            .filter_map(|line| serde_json::from_str::<AuditRecord>(line).ok())
+            .find(|r| r.receipt.as_deref() == Some(id)))
+    }
+
+    // Newest records first.
+    pub fn query(
+        &self,
+        action: Option<&str>,
+        org: Option<&str>,
+        since_ms: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<AuditRecord>, String> {
+        let contents = self
+            .store
+            .read(STORE_NAME)
+            .map_err(|e| format!("failed to read audit log: {}", e))?
+            .unwrap_or_default();
+
+        Ok(contents
+            .lines()
+            .rev()
+            .filter_map(|line| serde_json::from_str::<AuditRecord>(line).ok())
+            .filter(|r| action.is_none_or(|a| r.action == a))
+            .filter(|r| org.is_none_or(|o| r.actor.org.as_deref() == Some(o)))
+            .filter(|r| since_ms.is_none_or(|since| r.timestamp_ms >= since))
+            .take(limit)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth::Priority, store::MemoryStore};
+    use axum::http::Request;
+    use serde_json::json;
+
+    fn actor(org: Option<&str>) -> Actor {
+        Actor {
+            source: "cli".into(),
+            org: org.map(str::to_string),
+            ..Actor::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn callers_come_from_the_connection_and_principal() {
+        let (mut parts, ()) = Request::builder()
+            .header("x-forwarded-for", "203.0.113.7")
+            .extension(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4000))))
+            .extension(Principal {
+                subject: Some("ci".into()),
+                role: None,
+                wallet: None,
+                org: Some("acme".into()),
+                priority: Priority::Normal,
+            })
+            .body(())
+            .unwrap()
+            .into_parts();
+        let Ok(Caller(actor)) = Caller::from_request_parts(&mut parts, &()).await;
+        assert_eq!(actor.source, "http");
+        assert_eq!(actor.ip.as_deref(), Some("10.0.0.1"));
+        assert_eq!(actor.forwarded_for.as_deref(), Some("203.0.113.7"));
+        assert_eq!(actor.subject.as_deref(), Some("ci"));
+        assert_eq!(actor.org.as_deref(), Some("acme"));
+    }
+
+    #[test]
+    fn queries_filter_newest_first() {
+        let log = AuditLog::new(Arc::new(MemoryStore::default()));
+        assert!(log.is_enabled());
+        log.record(record(
+            "airdrop",
+            actor(Some("acme")),
+            json!({ "lamports": 1 }),
+        ));
+        log.record(record("airdrop", actor(None), json!({ "lamports": 2 })).failed("denied"));
+        log.record(record("transfer", actor(Some("acme")), json!({})).signature("sig"));
+
+        let all = log.query(None, None, None, 10).unwrap();
+        let actions: Vec<_> = all.iter().map(|r| r.action.as_str()).collect();
+        assert_eq!(actions, ["transfer", "airdrop", "airdrop"]);
+        assert_eq!(all[1].outcome, "failure");
+        assert_eq!(all[1].error.as_deref(), Some("denied"));
+
+        let acme_airdrops = log.query(Some("airdrop"), Some("acme"), None, 10).unwrap();
+        assert_eq!(acme_airdrops.len(), 1);
+        assert_eq!(acme_airdrops[0].params, json!({ "lamports": 1 }));
+        assert_eq!(log.query(None, None, None, 1).unwrap().len(), 1);
+        assert!(log
+            .query(None, None, Some(clock::now_ms() + 60_000), 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn receipts_are_found_by_id() {
+        let log = AuditLog::new(Arc::new(MemoryStore::default()));
+        // Mentions the id without being its receipt.
+        log.record(
+            record("airdrop", actor(None), json!({ "note": "rcpt-1" }))
+                .receipt(Some("rcpt-2".into())),
+        );
+        log.record(record("airdrop", actor(None), json!({})).receipt(Some("rcpt-1".into())));
+        let found = log.find_receipt("rcpt-1").unwrap().unwrap();
+        assert_eq!(found.receipt.as_deref(), Some("rcpt-1"));
+        assert!(log.find_receipt("rcpt-3").unwrap().is_none());
+    }
+}
@@ -0,0 +1,547 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    config::Config,
+    handlers::{error_response, ApiError},
+    keys::{constant_time_eq, API_KEY_PREFIX},
+    siws::SESSION_TOKEN_PREFIX,
+    state::AppState,
+};
+
+pub use crate::api::{Priority, Role};
+
+const JWKS_MAX_AGE: Duration = Duration::from_secs(300);
+// Unknown `kid`s trigger a refetch, but not more often than this.
+const JWKS_MIN_REFETCH: Duration = Duration::from_secs(30);
+
+// Who made the request, as established by the route group's guard. Stored
+// in the request extensions so later extractors (e.g. Caller) can see it.
+#[derive(Clone, Debug)]
+pub struct Principal {
+    pub subject: Option<String>,
+    pub role: Option<Role>,
+    // Set for SIWS sessions: the wallet whose ownership was proven.
+    pub wallet: Option<Pubkey>,
+    // Set for org API keys: the tenant the key belongs to.
+    pub org: Option<String>,
+    pub priority: Priority,
+}
+
+impl Principal {
+    fn new(subject: Option<String>, role: Option<Role>, config: &Config) -> Self {
+        let priority = subject
+            .as_ref()
+            .and_then(|s| config.priority_tiers.get(s))
+            .copied()
+            .unwrap_or(Priority::Normal);
+        Principal {
+            subject,
+            role,
+            wallet: None,
+            org: None,
+            priority,
+        }
+    }
+}
+
+// Priority of a request that has passed its route group's guard.
+pub fn request_priority(req: &axum::extract::Request) -> Priority {
+    req.extensions()
+        .get::<Principal>()
+        .map_or(Priority::Low, |p| p.priority)
+}
+
+#[derive(Default)]
+pub struct JwksCache {
+    cached: RwLock<Option<CachedJwks>>,
+}
+
+struct CachedJwks {
+    url: String,
+    fetched_at: Instant,
+    keys: JwkSet,
+}
+
+impl JwksCache {
+    async fn key(&self, url: &str, kid: Option<&str>) -> Result<DecodingKey, String> {
+        {
+            let cached = self.cached.read().await;
+            if let Some(cached) = cached.as_ref().filter(|c| c.url == url) {
+                let age = cached.fetched_at.elapsed();
+                match find_key(&cached.keys, kid) {
+                    Some(key) if age < JWKS_MAX_AGE => return key,
+                    None if age < JWKS_MIN_REFETCH => return Err("Unknown signing key".to_string()),
+                    _ => {}
+                }
+            }
+        }
+
+        let keys: JwkSet = reqwest::get(url)
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("failed to fetch JWKS: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("invalid JWKS: {}", e))?;
+        let key = find_key(&keys, kid);
+        *self.cached.write().await = Some(CachedJwks {
+            url: url.to_string(),
+            fetched_at: Instant::now(),
+            keys,
+        });
+        key.unwrap_or_else(|| Err("Unknown signing key".to_string()))
+    }
+}
+
+fn find_key(keys: &JwkSet, kid: Option<&str>) -> Option<Result<DecodingKey, String>> {
+    let jwk = match kid {
+        Some(kid) => keys.find(kid),
+        None if keys.keys.len() == 1 => keys.keys.first(),
+        None => None,
+    }?;
+    Some(DecodingKey::from_jwk(jwk).map_err(|e| e.to_string()))
+}
+
+// Accepts a single role name, a space-separated list (OAuth `scope` style)
+// or an array of names; the highest mapped role wins.
+fn role_from_claims(claims: &serde_json::Value, config: &Config) -> Option<Role> {
+    let names: Vec<&str> = match claims.get(&config.jwt_roles_claim) {
+        Some(serde_json::Value::String(s)) => s.split_whitespace().collect(),
+        Some(serde_json::Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    names
+        .into_iter()
+        .filter_map(|name| {
+            config
+                .jwt_role_mapping
+                .get(name)
+                .copied()
+                .or_else(|| name.parse().ok())
+        })
+        .max()
+}
+
+async fn verify_jwt(state: &AppState, config: &Config, token: &str) -> Result<Principal, String> {
+    let header = decode_header(token).map_err(|e| e.to_string())?;
+    let key = match (header.alg, &config.jwt_hs256_secret, &config.jwt_jwks_url) {
+        (Algorithm::HS256, Some(secret), _) => DecodingKey::from_secret(secret.as_bytes()),
+        (Algorithm::RS256, _, Some(url)) => state.jwks.key(url, header.kid.as_deref()).await?,
+        (alg, _, _) => return Err(format!("{:?} tokens are not accepted", alg)),
+    };
+
+    let mut validation = Validation::new(header.alg);
+    match &config.jwt_issuer {
+        Some(issuer) => validation.set_issuer(&[issuer]),
+        None => validation.iss = None,
+    }
+    match &config.jwt_audience {
+        Some(audience) => validation.set_audience(&[audience]),
+        None => validation.validate_aud = false,
+    }
+
+    let claims = decode::<serde_json::Value>(token, &key, &validation)
+        .map_err(|e| e.to_string())?
+        .claims;
+    Ok(Principal::new(
+        claims
+            .get("sub")
+            .and_then(|s| s.as_str())
+            .map(|s| s.to_string()),
+        role_from_claims(&claims, config),
+        config,
+    ))
+}
+
+// Ok(None) for requests without a bearer token.
+async fn authenticate(parts: &Parts, state: &AppState) -> Result<Option<Principal>, ApiError> {
+    let config = state.config();
+    let Some(token) = parts
+        .headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    else {
+        return Ok(None);
+    };
+
+    if config
+        .admin_token
+        .as_deref()
+        .is_some_and(|admin| constant_time_eq(admin, token))
+    {
+        return Ok(Some(Principal::new(
+            Some("admin_token".to_string()),
+            Some(Role::Admin),
+            &config,
+        )));
+    }
+    if token.starts_with(SESSION_TOKEN_PREFIX) {
+        if !config.siws_enabled {
+            return Err(error_response(
+                StatusCode::UNAUTHORIZED,
+                "Sign-in with Solana is disabled on this server",
+            ));
+        }
+        let wallet = state.sessions.wallet(token).ok_or_else(|| {
+            error_response(
+                StatusCode::UNAUTHORIZED,
+                "Sign-in session is invalid or expired",
+            )
+        })?;
+        // Anyone can sign in with a wallet of their own, so a session only
+        // proves which wallet airdrops may go to. It reads, and funds only
+        // where anonymous callers may; with no subject it is rate limited
+        // and metered as the anonymous caller it is.
+        return Ok(Some(Principal {
+            subject: None,
+            role: Some(Role::Reader),
+            wallet: Some(wallet),
+            org: None,
+            priority: Priority::Low,
+        }));
+    }
+    if token.starts_with(API_KEY_PREFIX) {
+        let key = state.keys.authenticate(token).ok_or_else(|| {
+            error_response(StatusCode::UNAUTHORIZED, "API key is invalid or revoked")
+        })?;
+        return Ok(Some(Principal {
+            org: key.org,
+            ..Principal::new(Some(key.subject), Some(key.role), &config)
+        }));
+    }
+    if config.jwt_enabled() {
+        return verify_jwt(state, &config, token)
+            .await
+            .map(Some)
+            .map_err(|e| {
+                error_response(
+                    StatusCode::UNAUTHORIZED,
+                    format!("Invalid bearer token: {}", e),
+                )
+            });
+    }
+    Err(error_response(
+        StatusCode::UNAUTHORIZED,
+        "Invalid bearer token",
+    ))
+}
+
+async fn authorize(parts: &mut Parts, state: &AppState, required: Role) -> Result<(), ApiError> {
+    let config = state.config();
+    if required == Role::Admin
+        && config.admin_token.is_none()
+        && !config.jwt_enabled()
+        && !state.keys.has_admin()
+    {
+        return Err(error_response(
+            StatusCode::FORBIDDEN,
+            "Admin API is disabled",
+        ));
+    }
+
+    let principal = authenticate(parts, state).await?;
+    // Without require_auth, reading and funding stay open to anonymous
+    // callers as they were before authentication existed.
+    let open = (!config.require_auth).then_some(Role::Funder);
+    let role = principal.as_ref().and_then(|p| p.role).max(open);
+
+    match role {
+        Some(role) if role >= required => {
+            if let Some(principal) = principal {
+                parts.extensions.insert(principal);
+            }
+            Ok(())
+        }
+        _ if principal.is_none() => Err(error_response(
+            StatusCode::UNAUTHORIZED,
+            "Authentication required",
+        )),
+        _ => Err(error_response(
+            StatusCode::FORBIDDEN,
+            format!("This route requires the '{}' role", required.as_str()),
+        )),
+    }
+}
+
+pub trait RequiredRole {
+    const ROLE: Role;
+}
+
+pub struct Reader;
+pub struct Funder;
+pub struct Admin;
+
+impl RequiredRole for Reader {
+    const ROLE: Role = Role::Reader;
+}
+impl RequiredRole for Funder {
+    const ROLE: Role = Role::Funder;
+}
+impl RequiredRole for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+// Guard for a route group, applied with `middleware::from_extractor_with_state`.
+pub struct Require<R>(PhantomData<R>);
+
+impl<R: RequiredRole> FromRequestParts<AppState> for Require<R> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        authorize(parts, state, R::ROLE).await?;
+        Ok(Require(PhantomData))
+    }
+}
+
+// For an option of a lower-role route that spends the server keypair's
+// funds, e.g. `submit` on /swap/build: the route's guard lets funders in,
+// anonymous ones too without require_auth, so the option checks for an
+// admin itself.
+pub fn require_admin(principal: Option<&Principal>, what: &str) -> Result<(), ApiError> {
+    match principal.and_then(|p| p.role) {
+        Some(Role::Admin) => Ok(()),
+        _ => Err(error_response(
+            StatusCode::FORBIDDEN,
+            format!("{} requires the 'admin' role", what),
+        )),
+    }
+}
+
+// Dev routes answer 404 unless dev mode is on, so production deployments
+// don't advertise them. With dev mode on they need an admin.
+pub struct DevOnly;
+
+impl FromRequestParts<AppState> for DevOnly {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if !state.config().dev_mode {
+            return Err(error_response(StatusCode::NOT_FOUND, "Not found"));
+        }
+        authorize(parts, state, Role::Admin).await?;
+        Ok(DevOnly)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    const SECRET: &str = "test-secret";
+
+    fn parts(token: Option<&str>) -> Parts {
+        let mut req = Request::builder();
+        if let Some(token) = token {
+            req = req.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        req.body(()).unwrap().into_parts().0
+    }
+
+    fn jwt(claims: serde_json::Value) -> String {
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn expiry() -> u64 {
+        crate::clock::now_secs() + 600
+    }
+
+    async fn status(state: &AppState, token: Option<&str>, required: Role) -> StatusCode {
+        match authorize(&mut parts(token), state, required).await {
+            Ok(()) => StatusCode::OK,
+            Err((status, _)) => status,
+        }
+    }
+
+    #[test]
+    fn the_highest_mapped_role_wins() {
+        let mut config = Config::default();
+        config.jwt_role_mapping.insert("ops".into(), Role::Admin);
+        let role = |claims| role_from_claims(&claims, &config);
+        assert_eq!(
+            role(json!({ "roles": "reader funder" })),
+            Some(Role::Funder)
+        );
+        assert_eq!(
+            role(json!({ "roles": ["reader", "ops"] })),
+            Some(Role::Admin)
+        );
+        assert_eq!(role(json!({ "roles": ["viewer"] })), None);
+        assert_eq!(role(json!({ "scope": "admin" })), None);
+
+        config.jwt_roles_claim = "scope".into();
+        assert_eq!(
+            role_from_claims(&json!({ "scope": "admin" }), &config),
+            Some(Role::Admin)
+        );
+    }
+
+    #[tokio::test]
+    async fn admin_routes_need_a_credential_that_can_be_configured() {
+        let state = AppState::for_tests(Config::default());
+        assert_eq!(
+            status(&state, None, Role::Admin).await,
+            StatusCode::FORBIDDEN
+        );
+        // Anonymous callers may still read and fund.
+        assert_eq!(status(&state, None, Role::Funder).await, StatusCode::OK);
+
+        let state = AppState::for_tests(Config {
+            admin_token: Some("letmein".into()),
+            require_auth: true,
+            ..Config::default()
+        });
+        assert_eq!(
+            status(&state, None, Role::Reader).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(&state, Some("wrong"), Role::Reader).await,
+            StatusCode::UNAUTHORIZED
+        );
+        let mut parts = parts(Some("letmein"));
+        authorize(&mut parts, &state, Role::Admin).await.unwrap();
+        let principal = parts.extensions.get::<Principal>().unwrap();
+        assert_eq!(principal.subject.as_deref(), Some("admin_token"));
+    }
+
+    #[tokio::test]
+    async fn jwts_are_verified_and_mapped_to_roles() {
+        let mut config = Config {
+            jwt_hs256_secret: Some(SECRET.into()),
+            jwt_issuer: Some("https://issuer.example".into()),
+            require_auth: true,
+            ..Config::default()
+        };
+        config.priority_tiers.insert("ci".into(), Priority::High);
+        let state = AppState::for_tests(config);
+
+        let reader = jwt(json!({
+            "sub": "ci",
+            "roles": "reader",
+            "iss": "https://issuer.example",
+            "exp": expiry(),
+        }));
+        assert_eq!(
+            status(&state, Some(&reader), Role::Reader).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&state, Some(&reader), Role::Funder).await,
+            StatusCode::FORBIDDEN
+        );
+        let mut parts = parts(Some(&reader));
+        authorize(&mut parts, &state, Role::Reader).await.unwrap();
+        assert_eq!(
+            parts.extensions.get::<Principal>().unwrap().priority,
+            Priority::High
+        );
+
+        let other_issuer = jwt(json!({
+            "sub": "ci",
+            "roles": "admin",
+            "iss": "https://elsewhere.example",
+            "exp": expiry(),
+        }));
+        assert_eq!(
+            status(&state, Some(&other_issuer), Role::Reader).await,
+            StatusCode::UNAUTHORIZED
+        );
+        let forged = encode(
+            &Header::default(),
+            &json!({ "sub": "ci", "roles": "admin", "iss": "https://issuer.example", "exp": expiry() }),
+            &EncodingKey::from_secret(b"guessed"),
+        )
+        .unwrap();
+        assert_eq!(
+            status(&state, Some(&forged), Role::Reader).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    fn sign_in(state: &AppState) -> (Pubkey, String) {
+        use solana_sdk::signature::{Keypair, Signer};
+
+        let keypair = Keypair::new();
+        let challenge = state
+            .sessions
+            .challenge("faucet.example", keypair.pubkey())
+            .unwrap();
+        let signature = keypair.sign_message(challenge.message.as_bytes());
+        let session = state
+            .sessions
+            .verify(
+                keypair.pubkey(),
+                &challenge.nonce,
+                &signature.to_string(),
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        (keypair.pubkey(), session.token)
+    }
+
+    #[tokio::test]
+    async fn siws_sessions_only_read_under_require_auth() {
+        let state = AppState::for_tests(Config {
+            admin_token: Some("letmein".into()),
+            require_auth: true,
+            siws_enabled: true,
+            ..Config::default()
+        });
+        let (_, token) = sign_in(&state);
+        assert_eq!(
+            status(&state, Some(&token), Role::Reader).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&state, Some(&token), Role::Funder).await,
+            StatusCode::FORBIDDEN
+        );
+
+        // Without require_auth the session funds as anonymous callers do,
+        // with no subject of its own to mint rate limit buckets or quotas.
+        let state = AppState::for_tests(Config {
+            siws_enabled: true,
+            ..Config::default()
+        });
+        let (wallet, token) = sign_in(&state);
+        let mut parts = parts(Some(&token));
+        authorize(&mut parts, &state, Role::Funder).await.unwrap();
+        let principal = parts.extensions.get::<Principal>().unwrap();
+        assert_eq!(principal.wallet, Some(wallet));
+        assert_eq!(principal.subject, None);
+        assert_eq!(principal.role, Some(Role::Reader));
+
+        // Switched off, sessions are refused outright.
+        let state = AppState::for_tests(Config::default());
+        let (_, token) = sign_in(&state);
+        assert_eq!(
+            status(&state, Some(&token), Role::Reader).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use futures_util::StreamExt;
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+use crate::{
+    config::Config,
+    index, logging, rpc,
+    service::{rpc_error, ServiceError},
+};
+
+// How often a wait re-reads the balance when it can't subscribe: on the
+// mock backend, or when the node's websocket is down.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+static WAITING: AtomicUsize = AtomicUsize::new(0);
+
+// Counts a waiting request until it is dropped, which is also when its
+// client goes away.
+pub struct Waiter(());
+
+impl Waiter {
+    // None when `balance_wait_max_waiters` requests are waiting already.
+    pub fn enter(config: &Config) -> Option<Waiter> {
+        WAITING
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |waiting| {
+                (waiting < config.balance_wait_max_waiters).then_some(waiting + 1)
+            })
+            .ok()
+            .map(|_| Waiter(()))
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        WAITING.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// A `wait_for_change` value: seconds, or a number with an `s` or `m`
+// suffix ("30", "30s", "2m").
+pub fn parse_wait(text: &str) -> Result<Duration, ServiceError> {
+    let text = text.trim();
+    let (number, unit) = match text.strip_suffix('m') {
+        Some(minutes) => (minutes, 60),
+        None => (text.strip_suffix('s').unwrap_or(text), 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .map(Duration::from_secs)
+        .ok_or_else(|| {
+            ServiceError::Invalid(format!(
+                "Invalid wait_for_change '{}': use seconds like '30s'",
+                text
+            ))
+        })
+}
+
+pub struct Wait {
+    pub previous: u64,
+    pub balance: u64,
+}
+
+impl Wait {
+    pub fn changed(&self) -> bool {
+        self.balance != self.previous
+    }
+}
+
+pub async fn confirmed_balance(rpc_url: &str, wallet: Pubkey) -> Result<u64, ServiceError> {
+    let rpc_url = rpc_url.to_string();
+    rpc::spawn_blocking(move || {
+        rpc::client(&rpc_url)
+            .get_balance_with_commitment(&wallet, CommitmentConfig::confirmed())
+            .map(|response| response.value)
+            .map_err(rpc_error("Failed to get balance"))
+    })
+    .await
+    .map_err(|e| ServiceError::Rpc {
+        action: "Failed to get balance",
+        message: e.to_string(),
+    })?
+}
+
+// Holds until the wallet's confirmed balance differs from `previous` (the
+// balance when called, if not given) or `timeout` passes, following an
+// account subscription and falling back to polling without one.
+pub async fn wait_for_change(
+    config: &Config,
+    wallet: Pubkey,
+    previous: Option<u64>,
+    timeout: Duration,
+) -> Result<Wait, ServiceError> {
+    let deadline = Instant::now() + timeout;
+    let previous = match previous {
+        Some(previous) => previous,
+        None => confirmed_balance(&config.rpc_url, wallet).await?,
+    };
+    if rpc::mock().is_none() {
+        let url = config
+            .index_ws_url
+            .clone()
+            .unwrap_or_else(|| index::ws_url(&config.rpc_url));
+        match follow(&url, &config.rpc_url, wallet, previous, deadline).await {
+            Ok(balance) => return Ok(Wait { previous, balance }),
+            Err(e) => logging::warn(format_args!(
+                "Balance subscription for {} on {} failed, polling: {}",
+                wallet, url, e
+            )),
+        }
+    }
+    loop {
+        let balance = confirmed_balance(&config.rpc_url, wallet).await?;
+        if balance != previous || Instant::now() >= deadline {
+            return Ok(Wait { previous, balance });
+        }
+        tokio::time::sleep_until((Instant::now() + POLL_INTERVAL).min(deadline)).await;
+    }
+}
+
+// The balance once it changes or at the deadline. Reads it after
+// subscribing, so a change in between isn't missed.
+async fn follow(
+    url: &str,
+    rpc_url: &str,
+    wallet: Pubkey,
+    previous: u64,
+    deadline: Instant,
+) -> Result<u64, String> {
+    let client = PubsubClient::new(url).await.map_err(|e| e.to_string())?;
+    let config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        // Only the lamports are wanted.
+        data_slice: Some(UiDataSliceConfig {
+            offset: 0,
+            length: 0,
+        }),
+        commitment: Some(CommitmentConfig::confirmed()),
+        min_context_slot: None,
+    };
+    let (mut updates, _unsubscribe) = client
+        .account_subscribe(&wallet, Some(config))
+        .await
+        .map_err(|e| e.to_string())?;
+    let balance = confirmed_balance(rpc_url, wallet)
+        .await
+        .map_err(|e| e.to_string())?;
+    if balance != previous {
+        return Ok(balance);
+    }
+    loop {
+        match tokio::time::timeout_at(deadline, updates.next()).await {
+            Ok(Some(update)) if update.value.lamports != previous => {
+                return Ok(update.value.lamports)
+            }
+            Ok(Some(_)) => {}
+            Ok(None) => return Err("the subscription closed".to_string()),
+            Err(_) => return Ok(previous),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_config() -> Config {
+        Config {
+            rpc_url: "http://mock".to_string(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn wait_durations() {
+        let secs = |text| parse_wait(text).ok().map(|d| d.as_secs());
+        assert_eq!(secs("30"), Some(30));
+        assert_eq!(secs(" 30s "), Some(30));
+        assert_eq!(secs("2m"), Some(120));
+        assert_eq!(secs("0"), Some(0));
+        for bad in ["", "s", "1h", "-5", "1.5s", "2ms"] {
+            match parse_wait(bad) {
+                Err(ServiceError::Invalid(message)) => assert_eq!(
+                    message,
+                    format!("Invalid wait_for_change '{}': use seconds like '30s'", bad)
+                ),
+                _ => panic!("'{}' should be rejected", bad),
+            }
+        }
+    }
+
+    #[test]
+    fn waiters_are_limited() {
+        let config = Config {
+            balance_wait_max_waiters: 2,
+            ..Config::default()
+        };
+        let first = Waiter::enter(&config).unwrap();
+        let _second = Waiter::enter(&config).unwrap();
+        assert!(Waiter::enter(&config).is_none());
+        drop(first);
+        assert!(Waiter::enter(&config).is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn waits_end_on_change_or_timeout() {
+        let chain = rpc::mock_for_tests();
+        let wallet = Pubkey::new_unique();
+        chain.set_balance(wallet, 100);
+        let config = mock_config();
+
+        // A stale `previous` returns at once.
+        let wait = wait_for_change(&config, wallet, Some(50), Duration::from_secs(30))
+            .await
+            .unwrap();
+        assert!(wait.changed());
+        assert_eq!((wait.previous, wait.balance), (50, 100));
+
+        let started = Instant::now();
+        let wait = wait_for_change(&config, wallet, None, Duration::from_millis(200))
+            .await
+            .unwrap();
+        assert!(!wait.changed());
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        let funder = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            chain.set_balance(wallet, 250);
+        });
+        let wait = wait_for_change(&config, wallet, None, Duration::from_secs(30))
+            .await
+            .unwrap();
+        funder.await.unwrap();
+        assert_eq!((wait.previous, wait.balance), (100, 250));
+    }
+}
@@ -0,0 +1,576 @@
+use futures_util::{stream, Stream, StreamExt};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, signature::Keypair};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt::Write,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::Semaphore;
+
+use crate::{
+    amount::lamports_to_sol_decimal,
+    audit::{self, Actor},
+    events::EventKind,
+    fanout::{self, Limits},
+    receipts, rpc,
+    service::{rpc_error, ServiceError},
+    state::AppState,
+};
+
+pub use crate::api::{BalanceRow, BalancesFormat, RowError, RowResult};
+
+pub const JOB_KIND: &str = "airdrop_bulk";
+
+// One `wallet,amount` line of an upload; amounts are in SOL.
+pub struct Row {
+    pub line: usize,
+    pub wallet: Pubkey,
+    pub lamports: u64,
+}
+
+// Parses an amount like "1", "0.25" or ".5" without going through f64.
+pub fn parse_sol(amount: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid amount '{}'", amount);
+    let (whole, frac) = amount.split_once('.').unwrap_or((amount, ""));
+    let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if (whole.is_empty() && frac.is_empty()) || !digits(whole) || !digits(frac) {
+        return Err(invalid());
+    }
+    if frac.len() > 9 {
+        return Err(format!("amount '{}' is finer than a lamport", amount));
+    }
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| invalid())?
+    };
+    let frac: u64 = format!("{:0<9}", frac).parse().map_err(|_| invalid())?;
+    whole
+        .checked_mul(LAMPORTS_PER_SOL)
+        .and_then(|l| l.checked_add(frac))
+        .ok_or_else(|| format!("amount '{}' is out of range", amount))
+}
+
+fn parse_row(line: &str) -> Result<(Pubkey, u64), String> {
+    let fields: Vec<&str> = line
+        .split(',')
+        .map(|f| f.trim().trim_matches('"'))
+        .collect();
+    let [wallet, amount] = fields[..] else {
+        return Err(format!(
+            "expected wallet,amount, got {} fields",
+            fields.len()
+        ));
+    };
+    let wallet = Pubkey::from_str(wallet).map_err(|_| format!("invalid wallet '{}'", wallet))?;
+    let lamports = parse_sol(amount)?;
+    if lamports == 0 {
+        return Err("amount must be greater than 0".to_string());
+    }
+    Ok((wallet, lamports))
+}
+
+// Validates every row before anything is sent, so a bad upload pays no one.
+// Blank lines and a leading `wallet,amount` header are skipped; a wallet
+// may appear only once.
+pub fn parse(csv: &str, max_rows: usize) -> Result<Vec<Row>, Vec<RowError>> {
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen: HashMap<Pubkey, usize> = HashMap::new();
+    for (i, text) in csv.lines().enumerate() {
+        let line = i + 1;
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        if rows.is_empty() && errors.is_empty() && text.to_ascii_lowercase().starts_with("wallet") {
+            continue;
+        }
+        match parse_row(text) {
+            Ok((wallet, lamports)) => match seen.insert(wallet, line) {
+                Some(first) => errors.push(RowError {
+                    line,
+                    error: format!("wallet already listed on line {}", first),
+                }),
+                None => rows.push(Row {
+                    line,
+                    wallet,
+                    lamports,
+                }),
+            },
+            Err(error) => errors.push(RowError { line, error }),
+        }
+    }
+
+    if rows.is_empty() && errors.is_empty() {
+        errors.push(RowError {
+            line: 0,
+            error: "the upload has no rows".to_string(),
+        });
+    }
+    if rows.len() + errors.len() > max_rows {
+        errors.push(RowError {
+            line: 0,
+            error: format!("the upload has more than {} rows", max_rows),
+        });
+    }
+    if errors.is_empty() {
+        Ok(rows)
+    } else {
+        Err(errors)
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// The downloadable form of a finished job's results.
+pub fn results_csv(results: &[RowResult]) -> String {
+    let mut csv = String::from("line,wallet,lamports,status,signature,receipt_id,error\n");
+    for r in results {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{}",
+            r.line,
+            r.wallet,
+            r.lamports,
+            if r.error.is_none() { "sent" } else { "failed" },
+            r.signature.as_deref().unwrap_or_default(),
+            r.receipt_id.as_deref().unwrap_or_default(),
+            csv_field(r.error.as_deref().unwrap_or_default()),
+        );
+    }
+    csv
+}
+
+// Pays every row from the treasury, `bulk_airdrop_concurrency` at a time,
+// and finishes the job with the per-row results. A failed row doesn't stop
+// the others.
+pub async fn run(state: AppState, job_id: u64, payer: Arc<Keypair>, actor: Actor, rows: Vec<Row>) {
+    let config = state.config();
+    let total = rows.len() as u64;
+    state.jobs.progress(job_id, "sending", 0, total);
+
+    let limit = Arc::new(Semaphore::new(config.bulk_airdrop_concurrency));
+    let sent = Arc::new(AtomicU64::new(0));
+    let mut tasks = Vec::new();
+    for row in &rows {
+        let permit = limit
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        let config = config.clone();
+        let payer = payer.clone();
+        let (jobs, sent) = (state.jobs.clone(), sent.clone());
+        let (wallet, lamports) = (row.wallet, row.lamports);
+        tasks.push(rpc::spawn_blocking(move || {
+            let _permit = permit;
+            let result = receipts::pay(&config, &payer, &wallet, lamports, true);
+            let completed = sent.fetch_add(1, Ordering::Relaxed) + 1;
+            jobs.progress(job_id, "sending", completed, total);
+            result
+        }));
+    }
+
+    let mut results = Vec::with_capacity(rows.len());
+    let mut failed = 0;
+    for (row, task) in rows.into_iter().zip(tasks) {
+        let result = task.await.unwrap_or_else(|e| {
+            Err(ServiceError::Rpc {
+                action: "Transfer failed",
+                message: e.to_string(),
+            })
+        });
+        let wallet = row.wallet.to_string();
+        let record = audit::record(
+            JOB_KIND,
+            actor.clone(),
+            serde_json::json!({
+                "wallet": wallet,
+                "lamports": row.lamports,
+                "job_id": job_id,
+                "line": row.line,
+            }),
+        );
+        let result = match result {
+            Ok((signature, receipt)) => {
+                state
+                    .audit
+                    .record(record.signature(signature).receipt(receipt.clone()));
+                state.events.publish(
+                    EventKind::FaucetConfirmed,
+                    serde_json::json!({
+                        "wallet": wallet,
+                        "lamports": row.lamports,
+                        "source": "treasury",
+                        "signature": signature.to_string(),
+                    }),
+                );
+                RowResult {
+                    line: row.line,
+                    wallet,
+                    lamports: row.lamports,
+                    signature: Some(signature.to_string()),
+                    receipt_id: receipt,
+                    error: None,
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                state.audit.record(record.failed(&e));
+                RowResult {
+                    line: row.line,
+                    wallet,
+                    lamports: row.lamports,
+                    signature: None,
+                    receipt_id: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+        results.push(result);
+    }
+
+    state.jobs.succeed(
+        job_id,
+        serde_json::json!({
+            "sent": total - failed,
+            "failed": failed,
+            "results_url": format!("/admin/airdrop_bulk/{}/results.csv", job_id),
+            "rows": results,
+        }),
+    );
+}
+
+// An address list for POST /balances_bulk: one address per line, or a CSV
+// whose first column is the address. A leading address/wallet/pubkey
+// header is skipped.
+pub fn parse_addresses(text: &str, max: usize) -> Result<Vec<Pubkey>, Vec<RowError>> {
+    let mut addresses = Vec::new();
+    let mut errors = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let field = line.split(',').next().unwrap_or_default();
+        let field = field.trim().trim_matches('"');
+        if field.is_empty() {
+            continue;
+        }
+        let first = addresses.is_empty() && errors.is_empty();
+        if first && ["address", "wallet", "pubkey"].contains(&field.to_ascii_lowercase().as_str()) {
+            continue;
+        }
+        match Pubkey::from_str(field) {
+            Ok(address) => addresses.push(address),
+            Err(_) => errors.push(RowError {
+                line: i + 1,
+                error: format!("invalid address '{}'", field),
+            }),
+        }
+    }
+
+    if addresses.is_empty() && errors.is_empty() {
+        errors.push(RowError {
+            line: 0,
+            error: "the upload has no addresses".to_string(),
+        });
+    }
+    if addresses.len() + errors.len() > max {
+        errors.push(RowError {
+            line: 0,
+            error: format!("the upload has more than {} addresses", max),
+        });
+    }
+    if errors.is_empty() {
+        Ok(addresses)
+    } else {
+        Err(errors)
+    }
+}
+
+fn render(row: &BalanceRow, format: BalancesFormat) -> String {
+    match format {
+        BalancesFormat::Ndjson => {
+            let mut line = serde_json::to_string(row).unwrap_or_default();
+            line.push('\n');
+            line
+        }
+        BalancesFormat::Csv => format!(
+            "{},{},{},{},{}\n",
+            row.address,
+            row.lamports.map(|l| l.to_string()).unwrap_or_default(),
+            row.sol_decimal.as_deref().unwrap_or_default(),
+            row.exists.map(|e| e.to_string()).unwrap_or_default(),
+            csv_field(row.error.as_deref().unwrap_or_default()),
+        ),
+    }
+}
+
+// Blocking: one getMultipleAccounts call for a chunk of addresses.
+fn load_chunk(rpc_url: &str, chunk: &[Pubkey]) -> Result<Vec<BalanceRow>, ServiceError> {
+    let client = rpc::client(rpc_url);
+    let _span = tracing::info_span!("rpc.bulk_balances", %rpc_url, count = chunk.len()).entered();
+    let accounts = client
+        .get_multiple_accounts(chunk)
+        .map_err(rpc_error("Failed to get accounts"))?;
+    Ok(chunk
+        .iter()
+        .zip(accounts)
+        .map(|(address, account)| {
+            let lamports = account.as_ref().map(|a| a.lamports).unwrap_or_default();
+            BalanceRow {
+                address: address.to_string(),
+                lamports: Some(lamports),
+                sol_decimal: Some(lamports_to_sol_decimal(lamports)),
+                exists: Some(account.is_some()),
+                error: None,
+            }
+        })
+        .collect())
+}
+
+// Balances in upload order, one row per address, sent as each chunk of
+// MAX_ACCOUNTS_PER_CALL comes back; chunks are fanned out within `limits`.
+// A chunk that fails gets an error row per address instead of ending the
+// stream.
+pub fn balances(
+    rpc_url: String,
+    limits: Limits,
+    addresses: Vec<Pubkey>,
+    format: BalancesFormat,
+) -> impl Stream<Item = Result<String, Infallible>> {
+    let header = match format {
+        BalancesFormat::Ndjson => None,
+        BalancesFormat::Csv => Some("address,lamports,sol_decimal,exists,error\n".to_string()),
+    };
+    let chunks: Vec<Vec<Pubkey>> = addresses
+        .chunks(rpc::MAX_ACCOUNTS_PER_CALL)
+        .map(<[Pubkey]>::to_vec)
+        .collect();
+    let loaded = fanout::stream(limits, chunks.clone(), move |chunk: Vec<Pubkey>| {
+        load_chunk(&rpc_url, &chunk)
+    });
+    let rows = loaded
+        .zip(stream::iter(chunks))
+        .map(move |(result, chunk)| {
+            let rows = result.unwrap_or_else(|error| {
+                chunk
+                    .iter()
+                    .map(|address| BalanceRow {
+                        address: address.to_string(),
+                        lamports: None,
+                        sol_decimal: None,
+                        exists: None,
+                        error: Some(error.to_string()),
+                    })
+                    .collect()
+            });
+            Ok(rows
+                .iter()
+                .map(|row| render(row, format))
+                .collect::<String>())
+        });
+    stream::iter(header.map(Ok)).chain(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, jobs::JobStatus};
+    use serde_json::json;
+    use solana_sdk::signature::Signer;
+    use std::time::Duration;
+
+    #[test]
+    fn sol_amounts_parse_exactly() {
+        assert_eq!(parse_sol("1"), Ok(1_000_000_000));
+        assert_eq!(parse_sol("0.25"), Ok(250_000_000));
+        assert_eq!(parse_sol(".5"), Ok(500_000_000));
+        assert_eq!(parse_sol("2."), Ok(2_000_000_000));
+        assert_eq!(parse_sol("0.000000001"), Ok(1));
+        assert_eq!(parse_sol("18446744073.709551615"), Ok(u64::MAX));
+        assert_eq!(
+            parse_sol("18446744073.709551616").unwrap_err(),
+            "amount '18446744073.709551616' is out of range"
+        );
+        assert_eq!(
+            parse_sol("0.0000000001").unwrap_err(),
+            "amount '0.0000000001' is finer than a lamport"
+        );
+        for bad in ["", ".", "-1", "1e3", "1.2.3", " 1"] {
+            assert_eq!(
+                parse_sol(bad).unwrap_err(),
+                format!("invalid amount '{}'", bad)
+            );
+        }
+    }
+
+    #[test]
+    fn uploads_are_checked_whole() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let csv = format!("Wallet,Amount\n\n{},1.5\n\"{}\", \"0.1\"\n", a, b);
+        let rows = parse(&csv, 10).unwrap();
+        let parsed: Vec<_> = rows
+            .iter()
+            .map(|r| (r.line, r.wallet, r.lamports))
+            .collect();
+        assert_eq!(parsed, [(3, a, 1_500_000_000), (4, b, 100_000_000)]);
+
+        let csv = format!("{},1\n{},2\nnope,1\n{},0\n{}\n", a, a, b, b);
+        let errors: Vec<_> = parse(&csv, 10)
+            .err()
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.line, e.error))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (2, "wallet already listed on line 1".to_string()),
+                (3, "invalid wallet 'nope'".to_string()),
+                (4, "amount must be greater than 0".to_string()),
+                (5, "expected wallet,amount, got 1 fields".to_string()),
+            ]
+        );
+
+        let errors = parse("wallet,amount\n", 10).err().unwrap();
+        assert_eq!(errors[0].error, "the upload has no rows");
+        let csv = format!("{},1\n{},1\n", a, b);
+        let errors = parse(&csv, 1).err().unwrap();
+        assert_eq!(errors[0].error, "the upload has more than 1 rows");
+    }
+
+    #[test]
+    fn results_download_quotes_errors() {
+        let results = [
+            RowResult {
+                line: 1,
+                wallet: "w1".into(),
+                lamports: 5,
+                signature: Some("sig".into()),
+                receipt_id: Some("r".into()),
+                error: None,
+            },
+            RowResult {
+                line: 2,
+                wallet: "w2".into(),
+                lamports: 6,
+                signature: None,
+                receipt_id: None,
+                error: Some("failed: \"insufficient\", funds".into()),
+            },
+        ];
+        assert_eq!(
+            results_csv(&results),
+            "line,wallet,lamports,status,signature,receipt_id,error\n\
+             1,w1,5,sent,sig,r,\n\
+             2,w2,6,failed,,,\"failed: \"\"insufficient\"\", funds\"\n"
+        );
+    }
+
+    #[test]
+    fn address_lists() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let text = format!("pubkey,label\n{},first\n\n\"{}\"\n", a, b);
+        assert_eq!(parse_addresses(&text, 2).unwrap(), [a, b]);
+        let errors = parse_addresses(&format!("{}\nbad\n", a), 5).err().unwrap();
+        assert_eq!(
+            (errors[0].line, errors[0].error.as_str()),
+            (2, "invalid address 'bad'")
+        );
+        let errors = parse_addresses("address\n", 5).err().unwrap();
+        assert_eq!(errors[0].error, "the upload has no addresses");
+        let errors = parse_addresses(&text, 1).err().unwrap();
+        assert_eq!(errors[0].error, "the upload has more than 1 addresses");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn balances_stream_in_upload_order() {
+        let chain = rpc::mock_for_tests();
+        let addresses: Vec<Pubkey> = (0..rpc::MAX_ACCOUNTS_PER_CALL + 1)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        chain.set_balance(addresses[0], 1_500_000_000);
+        chain.set_balance(addresses[rpc::MAX_ACCOUNTS_PER_CALL], 7);
+        let limits = Limits {
+            concurrency: 2,
+            timeout: Duration::from_secs(10),
+        };
+
+        let chunks: Vec<String> = balances(
+            "http://mock".to_string(),
+            limits,
+            addresses.clone(),
+            BalancesFormat::Csv,
+        )
+        .map(|chunk| chunk.unwrap())
+        .collect()
+        .await;
+        let csv = chunks.concat();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), addresses.len() + 1);
+        assert_eq!(lines[0], "address,lamports,sol_decimal,exists,error");
+        assert_eq!(lines[1], format!("{},1500000000,1.5,true,", addresses[0]));
+        assert_eq!(lines[2], format!("{},0,0,false,", addresses[1]));
+        assert_eq!(
+            lines[101],
+            format!("{},7,0.000000007,true,", addresses[100])
+        );
+
+        let ndjson: Vec<String> = balances(
+            "http://mock".to_string(),
+            limits,
+            addresses[..1].to_vec(),
+            BalancesFormat::Ndjson,
+        )
+        .map(|chunk| chunk.unwrap())
+        .collect()
+        .await;
+        let row: serde_json::Value = serde_json::from_str(ndjson[0].trim_end()).unwrap();
+        assert_eq!(row["address"], addresses[0].to_string());
+        assert_eq!(row["exists"], true);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn bulk_jobs_pay_every_row_they_can() {
+        let chain = rpc::mock_for_tests();
+        let state = AppState::for_tests(Config {
+            rpc_url: "http://mock".to_string(),
+            ..Config::default()
+        });
+        let payer = Arc::new(Keypair::new());
+        chain.set_balance(payer.pubkey(), LAMPORTS_PER_SOL);
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let rows = parse(&format!("{},0.5\n{},2\n", a, b), 10).unwrap();
+        let actor: Actor = serde_json::from_value(json!({ "source": "test" })).unwrap();
+
+        let job = state.jobs.create(JOB_KIND);
+        run(state.clone(), job, payer, actor, rows).await;
+
+        let job = state.jobs.get(job).unwrap();
+        assert!(matches!(job.status, JobStatus::Succeeded));
+        let result = job.result.unwrap();
+        assert_eq!(
+            (result["sent"].clone(), result["failed"].clone()),
+            (json!(1), json!(1))
+        );
+        assert_eq!(result["rows"][0]["wallet"], a.to_string());
+        assert!(result["rows"][0]["signature"].is_string());
+        assert!(result["rows"][1]["error"].is_string());
+        let progress = job.progress.unwrap();
+        assert_eq!((progress.completed, progress.total), (2, 2));
+        assert_eq!(
+            rpc::client("http://mock").get_balance(&a).unwrap(),
+            LAMPORTS_PER_SOL / 2
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{
+    auth::Role,
+    config::Config,
+    features::{FeatureState, Features},
+    rpc,
+};
+
+pub use crate::api::{ApiIndex, Endpoint};
+
+// Every route the server registers, by the role its router requires.
+// Keep in step with the routers in main.rs.
+const PUBLIC: &[(&[&str], &str)] = &[
+    (&["GET"], "/"),
+    (&["GET"], "/api"),
+    (&["GET"], "/config.json"),
+    (&["GET"], "/health"),
+    (&["GET"], "/health/details"),
+    (&["GET"], "/health/dependencies"),
+    (&["GET"], "/openapi.json"),
+    (&["GET"], "/qr"),
+    (&["GET"], "/version"),
+    (&["GET"], "/receipts/{id}"),
+    (&["POST"], "/auth/siws/challenge"),
+    (&["POST"], "/auth/siws/verify"),
+];
+
+const READ: &[(&[&str], &str)] = &[
+    (&["GET", "POST"], "/get_balance"),
+    (&["GET"], "/wallet/{pubkey}/balance"),
+    (&["GET"], "/wallet/{pubkey}/balance_at"),
+    (&["GET"], "/wallet/{pubkey}/summary"),
+    (&["GET"], "/wallet/{pubkey}/transactions"),
+    (&["GET", "POST"], "/wallet/diff"),
+    (&["POST"], "/balances_bulk"),
+    (&["GET"], "/search/memos"),
+    (&["GET"], "/labels"),
+    (&["GET"], "/programs"),
+    (&["GET"], "/index"),
+    (&["GET"], "/index/{program}/accounts"),
+    (&["GET"], "/accounts/{address}"),
+    (&["GET"], "/programs/{program}/accounts"),
+    (&["GET"], "/tx/{signature}"),
+    (&["GET"], "/explorer/tx/{signature}"),
+    (&["GET"], "/explorer/address/{address}"),
+    (&["GET"], "/tx/{signature}/cost"),
+    (&["GET"], "/tx/{signature}/stream"),
+    (&["POST"], "/decode"),
+    (&["GET", "POST"], "/verify_signature"),
+    (&["GET"], "/topups/{wallet}"),
+    (&["GET"], "/cnft/{asset_id}/proof"),
+    (&["GET"], "/swap/quote"),
+    (&["GET"], "/stake-pools/{pool}"),
+    (&["GET"], "/stake-pools/{pool}/validators"),
+    (&["GET"], "/governance/{realm}/proposals"),
+    (&["GET"], "/governance/{realm}/proposals/{proposal}"),
+    (&["GET", "POST"], "/watchlists"),
+    (&["GET", "DELETE"], "/watchlists/{id}"),
+    (&["GET"], "/watchlists/{id}/overview"),
+    (&["GET"], "/me/usage"),
+    (&["GET"], "/stream/events"),
+    (&["GET"], "/ws/events/{program_id}"),
+];
+
+const FUND: &[(&[&str], &str)] = &[
+    (&["POST"], "/get_airdrop"),
+    (&["GET"], "/approvals/{id}"),
+    (&["POST"], "/topups"),
+    (&["DELETE"], "/topups/{wallet}"),
+    (&["POST"], "/cnft/{asset_id}/transfer"),
+    (&["POST"], "/swap/build"),
+];
+
+const ADMIN: &[(&[&str], &str)] = &[
+    (&["POST"], "/admin/config/reload"),
+    (&["GET"], "/admin/audit"),
+    (&["GET", "POST"], "/admin/allowlist"),
+    (&["DELETE"], "/admin/allowlist/{wallet}"),
+    (&["PUT", "DELETE"], "/admin/programs/{program}"),
+    (&["POST"], "/idl"),
+    (&["PUT", "DELETE"], "/admin/labels/{address}"),
+    (&["GET"], "/admin/usage"),
+    (&["GET"], "/admin/usage/routes"),
+    (&["GET"], "/admin/treasury"),
+    (&["POST"], "/admin/treasury/sweep"),
+    (&["POST"], "/admin/treasury/rebalance"),
+    (&["GET", "POST"], "/admin/alerts"),
+    (&["DELETE"], "/admin/alerts/{id}"),
+    (&["GET", "POST"], "/admin/export"),
+    (&["POST"], "/admin/airdrop_bulk"),
+    (&["GET"], "/admin/airdrop_bulk/{id}"),
+    (&["GET"], "/admin/airdrop_bulk/{id}/results.csv"),
+    (&["GET"], "/admin/approvals"),
+    (&["POST"], "/admin/approvals/{id}/approve"),
+    (&["POST"], "/admin/approvals/{id}/reject"),
+    (&["GET", "POST"], "/admin/keys"),
+    (&["DELETE"], "/admin/keys/{key}"),
+    (&["POST"], "/admin/keys/{key}/rotate"),
+    (&["GET", "POST"], "/admin/orgs"),
+    (&["GET", "PUT", "DELETE"], "/admin/orgs/{org}"),
+    (&["POST"], "/admin/orgs/{org}/keys"),
+    (&["DELETE"], "/admin/orgs/{org}/keys/{key}"),
+    (&["POST"], "/admin/orgs/{org}/keys/{key}/rotate"),
+    (&["GET"], "/admin/orgs/{org}/audit"),
+    (&["GET"], "/admin/mock"),
+    (&["PUT"], "/admin/mock/faults"),
+    (&["PUT"], "/admin/mock/accounts/{wallet}"),
+    (&["POST"], "/admin/mock/reset"),
+    (&["GET", "PUT"], "/admin/features"),
+    (&["GET", "PUT", "DELETE"], "/admin/chaos"),
+];
+
+// Admin routes too, in builds with the `profiling` feature.
+#[cfg(feature = "profiling")]
+const DEBUG: &[(&[&str], &str)] = &[
+    (&["GET"], "/debug/pprof"),
+    (&["GET"], "/debug/cpu"),
+    (&["GET"], "/debug/tokio"),
+];
+
+// Admin routes too, but only there with `dev_mode` on.
+const DEV: &[(&[&str], &str)] = &[
+    (&["POST"], "/dev/deploy"),
+    (&["GET"], "/dev/jobs/{id}"),
+    (&["POST"], "/dev/nft/mint"),
+    (&["POST"], "/dev/sign_message"),
+    (&["POST"], "/dev/stake-pools/{pool}/deposit"),
+    (&["POST"], "/dev/stake-pools/{pool}/withdraw"),
+    (&["GET"], "/dev/buffers"),
+    (&["POST"], "/dev/buffers/close"),
+    (&["GET"], "/dev/validator"),
+    (&["POST"], "/dev/validator/start"),
+    (&["POST"], "/dev/validator/stop"),
+    (&["POST"], "/dev/validator/reset"),
+    (&["GET", "POST"], "/dev/fixtures"),
+];
+
+// Routes that answer only with a setting on.
+const TX_HISTORY_ROUTES: &[&str] = &["/wallet/{pubkey}/transactions", "/search/memos"];
+const MOCK_PREFIX: &str = "/admin/mock";
+
+// The routes of this deployment as a caller would find them: routes that
+// 404 here (hidden by a flag, dev routes without dev mode, the mock's
+// controls on a real node) are left out.
+pub fn index(config: &Config, features: &Features) -> ApiIndex {
+    let mut groups = vec![
+        (None, PUBLIC),
+        (Some(Role::Reader), READ),
+        (Some(Role::Funder), FUND),
+        (Some(Role::Admin), ADMIN),
+    ];
+    #[cfg(feature = "profiling")]
+    groups.push((Some(Role::Admin), DEBUG));
+    if config.dev_mode {
+        groups.push((Some(Role::Admin), DEV));
+    }
+    let mock = rpc::mock().is_some();
+    let endpoints = groups
+        .into_iter()
+        .flat_map(|(role, routes)| routes.iter().map(move |route| (role, route)))
+        .filter(|(_, (_, path))| mock || !path.starts_with(MOCK_PREFIX))
+        .filter_map(|(role, &(methods, path))| {
+            let (state, disabled_by) = match features.state_for(config, path) {
+                FeatureState::Hidden => return None,
+                FeatureState::Disabled => (FeatureState::Disabled, Some("feature flag")),
+                FeatureState::Enabled
+                    if !config.tx_history && TX_HISTORY_ROUTES.contains(&path) =>
+                {
+                    (FeatureState::Disabled, Some("tx_history"))
+                }
+                FeatureState::Enabled => (FeatureState::Enabled, None),
+            };
+            Some(Endpoint {
+                path: path.to_string(),
+                methods: methods.iter().map(|m| m.to_string()).collect(),
+                role,
+                state,
+                disabled_by: disabled_by.map(str::to_string),
+            })
+        })
+        .collect();
+    ApiIndex {
+        service: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        require_auth: config.require_auth,
+        endpoints,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeSet, HashMap};
+
+    fn endpoint<'a>(index: &'a ApiIndex, path: &str) -> Option<&'a Endpoint> {
+        index.endpoints.iter().find(|e| e.path == path)
+    }
+
+    // The catalog lists what the routers register, no more and no less.
+    #[test]
+    fn catalog_matches_the_routers() {
+        let main = include_str!("main.rs");
+        let registered: BTreeSet<&str> = main
+            .split(".route(")
+            .skip(1)
+            .filter_map(|call| call.trim_start().strip_prefix('"')?.split('"').next())
+            .collect();
+        let groups = [
+            PUBLIC,
+            READ,
+            FUND,
+            ADMIN,
+            DEV,
+            #[cfg(feature = "profiling")]
+            DEBUG,
+        ];
+        // A path may be in two groups, under different methods.
+        let methods: Vec<(&str, &str)> = groups
+            .iter()
+            .flat_map(|routes| routes.iter())
+            .flat_map(|(methods, path)| methods.iter().map(move |m| (*m, *path)))
+            .collect();
+        assert_eq!(methods.iter().collect::<BTreeSet<_>>().len(), methods.len());
+        let listed: BTreeSet<&str> = methods.into_iter().map(|(_, path)| path).collect();
+        #[cfg(not(feature = "profiling"))]
+        let registered: BTreeSet<&str> = registered
+            .into_iter()
+            .filter(|path| !path.starts_with("/debug/"))
+            .collect();
+        assert_eq!(registered, listed);
+    }
+
+    #[test]
+    fn index_leaves_out_what_would_404() {
+        rpc::mock_for_tests();
+        let features = Features::default();
+        let index = super::index(&Config::default(), &features);
+        assert!(endpoint(&index, "/dev/deploy").is_none());
+        let mock = endpoint(&index, "/admin/mock").unwrap();
+        assert!(matches!(mock.role, Some(Role::Admin)));
+        let idl = endpoint(&index, "/idl").unwrap();
+        assert_eq!(idl.methods, ["POST"]);
+        assert!(matches!(idl.role, Some(Role::Admin)));
+        let balance = endpoint(&index, "/get_balance").unwrap();
+        assert_eq!(balance.methods, ["GET", "POST"]);
+        assert!(matches!(balance.role, Some(Role::Reader)));
+        assert!(endpoint(&index, "/health").unwrap().role.is_none());
+        let transactions = endpoint(&index, "/wallet/{pubkey}/transactions").unwrap();
+        assert_eq!(transactions.state, FeatureState::Disabled);
+        assert_eq!(transactions.disabled_by.as_deref(), Some("tx_history"));
+
+        let config = Config {
+            dev_mode: true,
+            tx_history: true,
+            features: HashMap::from([
+                ("swaps".to_string(), FeatureState::Hidden),
+                ("faucet".to_string(), FeatureState::Disabled),
+            ]),
+            ..Config::default()
+        };
+        let index = super::index(&config, &features);
+        assert!(endpoint(&index, "/dev/deploy").is_some());
+        assert!(endpoint(&index, "/swap/quote").is_none());
+        let airdrop = endpoint(&index, "/get_airdrop").unwrap();
+        assert_eq!(airdrop.state, FeatureState::Disabled);
+        assert_eq!(airdrop.disabled_by.as_deref(), Some("feature flag"));
+        let transactions = endpoint(&index, "/wallet/{pubkey}/transactions").unwrap();
+        assert_eq!(transactions.state, FeatureState::Enabled);
+    }
+}
@@ -0,0 +1,330 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{handlers::error_response, service::ServiceError, state::AppState};
+
+pub use crate::api::{ChaosRule, ChaosStatus, InjectedCounts};
+
+const MAX_RULES: usize = 100;
+const MAX_LATENCY_MS: u64 = 60_000;
+// Never subject to injection, so a bad rule can always be cleared.
+const CONTROL_ROUTE: &str = "/admin/chaos";
+// Lets clients (and whoever reads their logs) tell injected failures from
+// real ones.
+const INJECTED_HEADER: &str = "x-chaos-injected";
+
+// Dev-mode fault injection for exercising client retry logic.
+#[derive(Default)]
+pub struct Chaos {
+    rules: Mutex<Vec<ChaosRule>>,
+    latency: AtomicU64,
+    error: AtomicU64,
+    rpc_error: AtomicU64,
+}
+
+fn check_rate(name: &str, rate: f64) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&rate) {
+        return Err(format!("'{}' must be between 0 and 1", name));
+    }
+    Ok(())
+}
+
+fn validate(rule: &ChaosRule) -> Result<(), String> {
+    if rule.route != "*" && !rule.route.starts_with('/') {
+        return Err(format!(
+            "route '{}' must be a route pattern starting with '/' or '*'",
+            rule.route
+        ));
+    }
+    if rule.route == CONTROL_ROUTE {
+        return Err(format!("{} can't be targeted", CONTROL_ROUTE));
+    }
+    if rule.latency_ms.saturating_add(rule.latency_jitter_ms) > MAX_LATENCY_MS {
+        return Err(format!(
+            "latency_ms plus latency_jitter_ms must be at most {}",
+            MAX_LATENCY_MS
+        ));
+    }
+    check_rate("latency_rate", rule.latency_rate)?;
+    check_rate("error_rate", rule.error_rate)?;
+    check_rate("rpc_error_rate", rule.rpc_error_rate)?;
+    if rule.error_rate + rule.rpc_error_rate > 1.0 {
+        return Err("error_rate plus rpc_error_rate must be at most 1".to_string());
+    }
+    Ok(())
+}
+
+fn roll(rate: f64) -> bool {
+    rate > 0.0 && rand::random::<f64>() < rate
+}
+
+fn injected(mut res: Response, kind: &'static str) -> Response {
+    res.headers_mut()
+        .insert(INJECTED_HEADER, HeaderValue::from_static(kind));
+    res
+}
+
+impl Chaos {
+    pub fn status(&self, dev_mode: bool) -> ChaosStatus {
+        ChaosStatus {
+            active: dev_mode,
+            rules: self.rules.lock().unwrap().clone(),
+            injected: InjectedCounts {
+                latency: self.latency.load(Ordering::Relaxed),
+                error: self.error.load(Ordering::Relaxed),
+                rpc_error: self.rpc_error.load(Ordering::Relaxed),
+            },
+        }
+    }
+
+    // Replaces every rule. For a request the first matching rule applies.
+    pub fn set_rules(&self, rules: Vec<ChaosRule>) -> Result<(), String> {
+        if rules.len() > MAX_RULES {
+            return Err(format!("at most {} rules are allowed", MAX_RULES));
+        }
+        rules.iter().try_for_each(validate)?;
+        *self.rules.lock().unwrap() = rules;
+        Ok(())
+    }
+
+    pub fn clear(&self) {
+        self.rules.lock().unwrap().clear();
+    }
+
+    fn rule_for(&self, route: &str, method: &str) -> Option<ChaosRule> {
+        self.rules
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|r| {
+                (r.route == "*" || r.route == route)
+                    && r.method
+                        .as_deref()
+                        .is_none_or(|m| m.eq_ignore_ascii_case(method))
+            })
+            .cloned()
+    }
+}
+
+pub async fn inject(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    if !state.config().dev_mode {
+        return next.run(req).await;
+    }
+    let Some(route) = req.extensions().get::<MatchedPath>().map(|p| p.as_str()) else {
+        return next.run(req).await;
+    };
+    if route == CONTROL_ROUTE {
+        return next.run(req).await;
+    }
+    let Some(rule) = state.chaos.rule_for(route, req.method().as_str()) else {
+        return next.run(req).await;
+    };
+
+    let mut delayed = false;
+    if (rule.latency_ms > 0 || rule.latency_jitter_ms > 0) && roll(rule.latency_rate) {
+        let jitter = match rule.latency_jitter_ms {
+            0 => 0,
+            max => rand::random::<u64>() % (max + 1),
+        };
+        tokio::time::sleep(Duration::from_millis(rule.latency_ms + jitter)).await;
+        state.chaos.latency.fetch_add(1, Ordering::Relaxed);
+        delayed = true;
+    }
+
+    let failure = rand::random::<f64>();
+    if failure < rule.error_rate {
+        state.chaos.error.fetch_add(1, Ordering::Relaxed);
+        let res = error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error");
+        return injected(res.into_response(), "error");
+    }
+    if failure < rule.error_rate + rule.rpc_error_rate {
+        state.chaos.rpc_error.fetch_add(1, Ordering::Relaxed);
+        let err = ServiceError::Rpc {
+            action: "RPC request failed",
+            message: rule
+                .rpc_error_message
+                .unwrap_or_else(|| "connection reset by peer".to_string()),
+        };
+        let res = error_response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string());
+        return injected(res.into_response(), "rpc_error");
+    }
+
+    let res = next.run(req).await;
+    if delayed {
+        injected(res, "latency")
+    } else {
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use axum::{middleware, routing::get, Router};
+
+    fn rule(route: &str) -> ChaosRule {
+        ChaosRule {
+            route: route.to_string(),
+            method: None,
+            latency_ms: 0,
+            latency_jitter_ms: 0,
+            latency_rate: 1.0,
+            error_rate: 0.0,
+            rpc_error_rate: 0.0,
+            rpc_error_message: None,
+        }
+    }
+
+    #[test]
+    fn rules_are_validated() {
+        let chaos = Chaos::default();
+        let err = |r: ChaosRule| chaos.set_rules(vec![r]).unwrap_err();
+
+        assert!(err(rule("wallet")).starts_with("route 'wallet' must be"));
+        assert_eq!(err(rule("/admin/chaos")), "/admin/chaos can't be targeted");
+        assert_eq!(
+            err(ChaosRule {
+                latency_ms: 50_000,
+                latency_jitter_ms: 10_001,
+                ..rule("*")
+            }),
+            "latency_ms plus latency_jitter_ms must be at most 60000"
+        );
+        assert_eq!(
+            err(ChaosRule {
+                error_rate: 1.5,
+                ..rule("*")
+            }),
+            "'error_rate' must be between 0 and 1"
+        );
+        assert_eq!(
+            err(ChaosRule {
+                error_rate: 0.6,
+                rpc_error_rate: 0.6,
+                ..rule("*")
+            }),
+            "error_rate plus rpc_error_rate must be at most 1"
+        );
+        assert!(chaos
+            .set_rules(vec![rule("*"); MAX_RULES + 1])
+            .unwrap_err()
+            .starts_with("at most 100 rules"));
+        assert!(chaos.status(true).rules.is_empty());
+    }
+
+    #[test]
+    fn first_matching_rule_applies() {
+        let chaos = Chaos::default();
+        chaos
+            .set_rules(vec![
+                ChaosRule {
+                    method: Some("post".into()),
+                    latency_ms: 1,
+                    ..rule("/get_airdrop")
+                },
+                ChaosRule {
+                    latency_ms: 2,
+                    ..rule("*")
+                },
+            ])
+            .unwrap();
+
+        assert_eq!(
+            chaos.rule_for("/get_airdrop", "POST").unwrap().latency_ms,
+            1
+        );
+        assert_eq!(chaos.rule_for("/get_airdrop", "GET").unwrap().latency_ms, 2);
+        assert_eq!(chaos.rule_for("/health", "GET").unwrap().latency_ms, 2);
+        chaos.clear();
+        assert!(chaos.rule_for("/health", "GET").is_none());
+    }
+
+    async fn serve(state: AppState) -> String {
+        let app = Router::new()
+            .route("/ping", get(|| async { "pong" }))
+            .route(CONTROL_ROUTE, get(|| async { "rules" }))
+            .route_layer(middleware::from_fn_with_state(state.clone(), inject))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn injected_failures_are_marked_and_counted() {
+        let state = AppState::for_tests(Config {
+            dev_mode: true,
+            ..Config::default()
+        });
+        state
+            .chaos
+            .set_rules(vec![
+                ChaosRule {
+                    rpc_error_rate: 1.0,
+                    rpc_error_message: Some("node is down".into()),
+                    ..rule("/ping")
+                },
+                ChaosRule {
+                    error_rate: 1.0,
+                    ..rule("*")
+                },
+            ])
+            .unwrap();
+        let base = serve(state.clone()).await;
+
+        let res = reqwest::get(format!("{}/ping", base)).await.unwrap();
+        assert_eq!(res.status(), 500);
+        assert_eq!(res.headers()[INJECTED_HEADER], "rpc_error");
+        assert!(res.text().await.unwrap().contains("node is down"));
+
+        let res = reqwest::get(format!("{}/admin/chaos", base)).await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert!(res.headers().get(INJECTED_HEADER).is_none());
+
+        state
+            .chaos
+            .set_rules(vec![ChaosRule {
+                latency_ms: 1,
+                ..rule("*")
+            }])
+            .unwrap();
+        let res = reqwest::get(format!("{}/ping", base)).await.unwrap();
+        assert_eq!(res.headers()[INJECTED_HEADER], "latency");
+        assert_eq!(res.text().await.unwrap(), "pong");
+
+        let counts = state.chaos.status(true).injected;
+        assert_eq!((counts.latency, counts.error, counts.rpc_error), (1, 0, 1));
+    }
+
+    #[tokio::test]
+    async fn nothing_is_injected_outside_dev_mode() {
+        let state = AppState::for_tests(Config::default());
+        state
+            .chaos
+            .set_rules(vec![ChaosRule {
+                error_rate: 1.0,
+                ..rule("*")
+            }])
+            .unwrap();
+        let base = serve(state.clone()).await;
+        let res = reqwest::get(format!("{}/ping", base)).await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert!(res.headers().get(INJECTED_HEADER).is_none());
+        assert_eq!(res.text().await.unwrap(), "pong");
+        let counts = state.chaos.status(false).injected;
+        assert_eq!((counts.latency, counts.error, counts.rpc_error), (0, 0, 0));
+    }
+}
@@ -0,0 +1,1219 @@
+use reqwest::{
+    multipart::{Form, Part},
+    Client, RequestBuilder, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+use crate::api::{
+    AccountResponse, AirdropReply, AirdropRequest, AllowlistAddRequest, AllowlistAddResponse,
+    AllowlistResponse, ApiIndex, Approval, ApprovalsQuery, ApprovalsResponse, AssetProofResponse,
+    AuditQuery, AuditQueryResponse, BalanceAlert, BalanceAlertRequest, BalanceAlertsResponse,
+    BalanceAtQuery, BalanceAtResponse, BalanceRow, BalancesFormat, BuildInfo, BulkBalancesQuery,
+    ChaosRule, ChaosStatus, CloseBuffersRequest, CloseBuffersResponse, CnftTransferRequest,
+    CnftTransferResponse, ConsistencyQuery, CpuProfile, CreateKeyRequest, CreateOrgRequest,
+    DecodeRequest, DecodeResponse, DependencyReport, Event, EventStreamQuery, ExportReport, Faults,
+    FeatureInfo, FeatureState, FixturesResponse, FrontendConfig, GetBalance, GetBalanceResponse,
+    HealthDetailsResponse, HealthResponse, IdlSummary, IdlUploadQuery, IndexStatusResponse,
+    IndexedAccountsQuery, IndexedAccountsResponse, IssueKeyRequest, IssuedKeyResponse, Job,
+    JobAcceptedResponse, KeysQuery, KeysResponse, KnownProgram, Label, LabelQuery, LabelsResponse,
+    ListBuffersResponse, LoadFixturesRequest, MemoSearchQuery, MemoSearchResponse, MintNftRequest,
+    MintNftResponse, MockBalanceRequest, MockStatus, OrgReport, OrgSettings, OrgsResponse,
+    Overview, PprofQuery, ProfileQuery, ProgramAccountsFormat, ProgramAccountsQuery,
+    ProgramAccountsResponse, ProgramsResponse, Proposal, ProposalsQuery, ProposalsResponse,
+    QrQuery, QuoteQuery, RebalanceRequest, RebalanceResponse, Receipt, RejectRequest, ReloadReport,
+    RouteRpcReport, RuntimeReport, SetLabelRequest, SetProgramNameRequest, SignMessageRequest,
+    SignMessageResponse, SiwsChallengeRequest, SiwsChallengeResponse, SiwsSessionResponse,
+    SiwsVerifyRequest, StakePoolDepositRequest, StakePoolResponse, StakePoolTransactionResponse,
+    StakePoolWithdrawRequest, SummaryQuery, SwapBuildRequest, SwapBuildResponse, Topup,
+    TopupRequest, TransactionCostResponse, TransactionResponse, TransactionStatusEvent,
+    TreasuryQuery, TreasuryResponse, UsageReport, ValidatorListResponse, ValidatorStatus,
+    VerifySignatureRequest, VerifySignatureResponse, WalletBalanceQuery, WalletBalanceResponse,
+    WalletDiffRequest, WalletDiffResponse, WalletSummaryResponse, WalletTransactionsQuery,
+    WalletTransactionsResponse, Watchlist, WatchlistRequest, WatchlistsResponse,
+};
+
+#[derive(Debug)]
+pub enum ClientError {
+    // The server couldn't be reached, or its answer couldn't be read.
+    Http(reqwest::Error),
+    // The server answered with an error body.
+    Api {
+        status: u16,
+        code: String,
+        message: String,
+        request_id: Option<String>,
+    },
+    // An event or NDJSON row that isn't what the endpoint sends.
+    Decode(serde_json::Error),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http(e) => write!(f, "{}", e),
+            ClientError::Api {
+                status,
+                code,
+                message,
+                ..
+            } => write!(f, "{} {}: {}", status, code, message),
+            ClientError::Decode(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(e: serde_json::Error) -> Self {
+        ClientError::Decode(e)
+    }
+}
+
+// `{"success":true,"data":...}`, or the bare value from servers running
+// with `legacy_responses`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Body<T> {
+    Envelope { data: T },
+    Bare(T),
+}
+
+#[derive(Deserialize)]
+struct Failure {
+    error: ErrorBody,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ErrorBody {
+    Detailed {
+        code: String,
+        message: String,
+        request_id: Option<String>,
+    },
+    // `{"error":"...","code":"..."}` from legacy servers.
+    Message(String),
+}
+
+// An update from `/tx/{signature}/stream`.
+#[derive(Clone, Debug)]
+pub enum TransactionUpdate {
+    Status(TransactionStatusEvent),
+    // The server stopped following the transaction before it finalized;
+    // the last commitment it saw, if any.
+    Timeout { commitment: Option<String> },
+}
+
+#[derive(Deserialize)]
+struct Timeout {
+    commitment: Option<String>,
+}
+
+fn transaction_update(event: &str, data: &str) -> serde_json::Result<TransactionUpdate> {
+    match event {
+        "timeout" => serde_json::from_str::<Timeout>(data).map(|t| TransactionUpdate::Timeout {
+            commitment: t.commitment,
+        }),
+        _ => serde_json::from_str(data).map(TransactionUpdate::Status),
+    }
+}
+
+// The events of a server-sent event stream, read as they arrive.
+// Keep-alives and comments are skipped.
+pub struct EventStream<T> {
+    res: Response,
+    buf: Vec<u8>,
+    parse: fn(&str, &str) -> serde_json::Result<T>,
+}
+
+impl<T> EventStream<T> {
+    // The next event, or None once the server ends the stream.
+    pub async fn next(&mut self) -> Result<Option<T>, ClientError> {
+        loop {
+            if let Some(end) = self.buf.windows(2).position(|w| w == b"\n\n") {
+                let frame: Vec<u8> = self.buf.drain(..end + 2).collect();
+                let (event, data) = sse_frame(&String::from_utf8_lossy(&frame));
+                if data.is_empty() {
+                    continue;
+                }
+                return Ok(Some((self.parse)(&event, &data)?));
+            }
+            match self.res.chunk().await? {
+                Some(chunk) => self.buf.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+// The event name ("message" when unnamed) and data of one frame.
+fn sse_frame(frame: &str) -> (String, String) {
+    let mut event = "message".to_string();
+    let mut data: Vec<&str> = Vec::new();
+    for line in frame.lines() {
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => event = value.to_string(),
+            "data" => data.push(value),
+            _ => {}
+        }
+    }
+    (event, data.join("\n"))
+}
+
+// Percent-encodes one path segment, leaving RFC 3986's unreserved bytes.
+fn segment(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+fn api_error(status: StatusCode, text: String) -> ClientError {
+    let (code, message, request_id) = match serde_json::from_str::<Failure>(&text) {
+        Ok(Failure {
+            error:
+                ErrorBody::Detailed {
+                    code,
+                    message,
+                    request_id,
+                },
+        }) => (code, message, request_id),
+        Ok(Failure {
+            error: ErrorBody::Message(message),
+        }) => (String::new(), message, None),
+        Err(_) => (String::new(), text, None),
+    };
+    ClientError::Api {
+        status: status.as_u16(),
+        code,
+        message,
+        request_id,
+    }
+}
+
+// Typed client for the server's HTTP API, a method per route. Addresses,
+// signatures and ids in paths are taken as strings.
+#[derive(Clone)]
+pub struct SolanaServerClient {
+    http: Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl SolanaServerClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        SolanaServerClient {
+            http: Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+        }
+    }
+
+    // Sent as a bearer token: the admin token, a JWT, an API key or a
+    // sign-in session.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ClientError> {
+        self.send(self.http.get(self.url(path))).await
+    }
+
+    pub async fn get_with<Q, T>(&self, path: &str, query: &Q) -> Result<T, ClientError>
+    where
+        Q: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        self.send(self.http.get(self.url(path)).query(query)).await
+    }
+
+    pub async fn post<B, T>(&self, path: &str, body: &B) -> Result<T, ClientError>
+    where
+        B: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        self.send(self.http.post(self.url(path)).json(body)).await
+    }
+
+    // Service
+
+    // The browser frontend's page.
+    pub async fn index_page(&self) -> Result<String, ClientError> {
+        self.text(self.http.get(self.url("/"))).await
+    }
+
+    pub async fn api_index(&self) -> Result<ApiIndex, ClientError> {
+        self.get("/api").await
+    }
+
+    pub async fn frontend_config(&self) -> Result<FrontendConfig, ClientError> {
+        self.get("/config.json").await
+    }
+
+    // The report even when the server answers 503 with it.
+    pub async fn health(&self) -> Result<HealthResponse, ClientError> {
+        self.report("/health").await
+    }
+
+    pub async fn health_details(&self) -> Result<HealthDetailsResponse, ClientError> {
+        self.report("/health/details").await
+    }
+
+    pub async fn health_dependencies(&self) -> Result<DependencyReport, ClientError> {
+        self.report("/health/dependencies").await
+    }
+
+    pub async fn openapi(&self) -> Result<serde_json::Value, ClientError> {
+        self.get("/openapi.json").await
+    }
+
+    // SVG or PNG, as `query.format` asks.
+    pub async fn qr_code(&self, query: &QrQuery) -> Result<Vec<u8>, ClientError> {
+        self.bytes(self.http.get(self.url("/qr")).query(query))
+            .await
+    }
+
+    pub async fn version(&self) -> Result<BuildInfo, ClientError> {
+        self.get("/version").await
+    }
+
+    pub async fn receipt(&self, id: &str) -> Result<Receipt, ClientError> {
+        self.get(&format!("/receipts/{}", segment(id))).await
+    }
+
+    pub async fn siws_challenge(
+        &self,
+        request: &SiwsChallengeRequest,
+    ) -> Result<SiwsChallengeResponse, ClientError> {
+        self.post("/auth/siws/challenge", request).await
+    }
+
+    pub async fn siws_verify(
+        &self,
+        request: &SiwsVerifyRequest,
+    ) -> Result<SiwsSessionResponse, ClientError> {
+        self.post("/auth/siws/verify", request).await
+    }
+
+    // Wallets
+
+    pub async fn get_balance(&self, address: &str) -> Result<GetBalanceResponse, ClientError> {
+        let query = GetBalance {
+            address: address.to_string(),
+        };
+        self.get_with("/get_balance", &query).await
+    }
+
+    pub async fn balance_at(
+        &self,
+        address: &str,
+        at: &BalanceAtQuery,
+    ) -> Result<BalanceAtResponse, ClientError> {
+        self.get_with(&format!("/wallet/{}/balance_at", segment(address)), at)
+            .await
+    }
+
+    pub async fn wallet_summary(
+        &self,
+        address: &str,
+        query: &SummaryQuery,
+    ) -> Result<WalletSummaryResponse, ClientError> {
+        self.get_with(&format!("/wallet/{}/summary", segment(address)), query)
+            .await
+    }
+
+    pub async fn wallet_transactions(
+        &self,
+        address: &str,
+        query: &WalletTransactionsQuery,
+    ) -> Result<WalletTransactionsResponse, ClientError> {
+        self.get_with(&format!("/wallet/{}/transactions", segment(address)), query)
+            .await
+    }
+
+    // Answers at once unless `query.wait_for_change` is set.
+    pub async fn wallet_balance(
+        &self,
+        address: &str,
+        query: &WalletBalanceQuery,
+    ) -> Result<WalletBalanceResponse, ClientError> {
+        self.get_with(&format!("/wallet/{}/balance", segment(address)), query)
+            .await
+    }
+
+    pub async fn wallet_diff(
+        &self,
+        request: &WalletDiffRequest,
+    ) -> Result<WalletDiffResponse, ClientError> {
+        self.post("/wallet/diff", request).await
+    }
+
+    // A row per address, in the order given.
+    pub async fn bulk_balances(&self, addresses: &[&str]) -> Result<Vec<BalanceRow>, ClientError> {
+        let query = BulkBalancesQuery {
+            format: BalancesFormat::Ndjson,
+        };
+        let request = self
+            .http
+            .post(self.url("/balances_bulk"))
+            .query(&query)
+            .body(addresses.join("\n"));
+        let text = self.text(request).await?;
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(ClientError::from))
+            .collect()
+    }
+
+    pub async fn search_memos(
+        &self,
+        query: &MemoSearchQuery,
+    ) -> Result<MemoSearchResponse, ClientError> {
+        self.get_with("/search/memos", query).await
+    }
+
+    pub async fn verify_signature(
+        &self,
+        request: &VerifySignatureRequest,
+    ) -> Result<VerifySignatureResponse, ClientError> {
+        self.post("/verify_signature", request).await
+    }
+
+    pub async fn labels(&self, query: &LabelQuery) -> Result<LabelsResponse, ClientError> {
+        self.get_with("/labels", query).await
+    }
+
+    pub async fn events(
+        &self,
+        query: &EventStreamQuery,
+    ) -> Result<EventStream<Event>, ClientError> {
+        let request = self.http.get(self.url("/stream/events")).query(query);
+        self.stream(request, |_, data| serde_json::from_str(data))
+            .await
+    }
+
+    pub async fn watchlists(&self) -> Result<WatchlistsResponse, ClientError> {
+        self.get("/watchlists").await
+    }
+
+    pub async fn create_watchlist(
+        &self,
+        request: &WatchlistRequest,
+    ) -> Result<Watchlist, ClientError> {
+        self.post("/watchlists", request).await
+    }
+
+    pub async fn watchlist(&self, id: u64) -> Result<Watchlist, ClientError> {
+        self.get(&format!("/watchlists/{}", id)).await
+    }
+
+    pub async fn delete_watchlist(&self, id: u64) -> Result<(), ClientError> {
+        self.delete(&format!("/watchlists/{}", id)).await
+    }
+
+    pub async fn watchlist_overview(
+        &self,
+        id: u64,
+        query: &ConsistencyQuery,
+    ) -> Result<Overview, ClientError> {
+        self.get_with(&format!("/watchlists/{}/overview", id), query)
+            .await
+    }
+
+    pub async fn my_usage(&self) -> Result<UsageReport, ClientError> {
+        self.get("/me/usage").await
+    }
+
+    // Accounts and programs
+
+    pub async fn account(&self, address: &str) -> Result<AccountResponse, ClientError> {
+        self.get(&format!("/accounts/{}", segment(address))).await
+    }
+
+    // Up to `limit` accounts (100 by default, at most 1000).
+    pub async fn program_accounts(
+        &self,
+        program: &str,
+        limit: Option<usize>,
+    ) -> Result<ProgramAccountsResponse, ClientError> {
+        let query = ProgramAccountsQuery {
+            limit,
+            format: ProgramAccountsFormat::Json,
+        };
+        self.get_with(&format!("/programs/{}/accounts", segment(program)), &query)
+            .await
+    }
+
+    pub async fn programs(&self) -> Result<ProgramsResponse, ClientError> {
+        self.get("/programs").await
+    }
+
+    pub async fn index_status(&self) -> Result<IndexStatusResponse, ClientError> {
+        self.get("/index").await
+    }
+
+    pub async fn indexed_accounts(
+        &self,
+        program: &str,
+        query: &IndexedAccountsQuery,
+    ) -> Result<IndexedAccountsResponse, ClientError> {
+        self.get_with(&format!("/index/{}/accounts", segment(program)), query)
+            .await
+    }
+
+    pub async fn decode(&self, request: &DecodeRequest) -> Result<DecodeResponse, ClientError> {
+        self.post("/decode", request).await
+    }
+
+    // Transactions
+
+    pub async fn transaction(&self, signature: &str) -> Result<TransactionResponse, ClientError> {
+        self.get(&format!("/tx/{}", segment(signature))).await
+    }
+
+    pub async fn transaction_cost(
+        &self,
+        signature: &str,
+    ) -> Result<TransactionCostResponse, ClientError> {
+        self.get(&format!("/tx/{}/cost", segment(signature))).await
+    }
+
+    // A status per commitment level reached; ends once the transaction
+    // finalizes or fails, or with a timeout.
+    pub async fn stream_transaction(
+        &self,
+        signature: &str,
+    ) -> Result<EventStream<TransactionUpdate>, ClientError> {
+        let request = self
+            .http
+            .get(self.url(&format!("/tx/{}/stream", segment(signature))));
+        self.stream(request, transaction_update).await
+    }
+
+    // The explorer's HTML page for a transaction.
+    pub async fn explorer_transaction(&self, signature: &str) -> Result<String, ClientError> {
+        let url = self.url(&format!("/explorer/tx/{}", segment(signature)));
+        self.text(self.http.get(url)).await
+    }
+
+    pub async fn explorer_address(&self, address: &str) -> Result<String, ClientError> {
+        let url = self.url(&format!("/explorer/address/{}", segment(address)));
+        self.text(self.http.get(url)).await
+    }
+
+    // Tokens, NFTs, swaps, stake pools and governance
+
+    pub async fn asset_proof(&self, asset_id: &str) -> Result<AssetProofResponse, ClientError> {
+        self.get(&format!("/cnft/{}/proof", segment(asset_id)))
+            .await
+    }
+
+    pub async fn transfer_cnft(
+        &self,
+        asset_id: &str,
+        request: &CnftTransferRequest,
+    ) -> Result<CnftTransferResponse, ClientError> {
+        self.post(&format!("/cnft/{}/transfer", segment(asset_id)), request)
+            .await
+    }
+
+    // The aggregator's quote, passed through.
+    pub async fn swap_quote(&self, query: &QuoteQuery) -> Result<serde_json::Value, ClientError> {
+        self.get_with("/swap/quote", query).await
+    }
+
+    pub async fn build_swap(
+        &self,
+        request: &SwapBuildRequest,
+    ) -> Result<SwapBuildResponse, ClientError> {
+        self.post("/swap/build", request).await
+    }
+
+    pub async fn stake_pool(&self, pool: &str) -> Result<StakePoolResponse, ClientError> {
+        self.get(&format!("/stake-pools/{}", segment(pool))).await
+    }
+
+    pub async fn stake_pool_validators(
+        &self,
+        pool: &str,
+    ) -> Result<ValidatorListResponse, ClientError> {
+        self.get(&format!("/stake-pools/{}/validators", segment(pool)))
+            .await
+    }
+
+    pub async fn proposals(
+        &self,
+        realm: &str,
+        query: &ProposalsQuery,
+    ) -> Result<ProposalsResponse, ClientError> {
+        self.get_with(&format!("/governance/{}/proposals", segment(realm)), query)
+            .await
+    }
+
+    pub async fn proposal(&self, realm: &str, proposal: &str) -> Result<Proposal, ClientError> {
+        self.get(&format!(
+            "/governance/{}/proposals/{}",
+            segment(realm),
+            segment(proposal)
+        ))
+        .await
+    }
+
+    // Airdrops and top-ups
+
+    // Sent, or held for an admin's approval.
+    pub async fn get_airdrop(&self, request: &AirdropRequest) -> Result<AirdropReply, ClientError> {
+        self.post("/get_airdrop", request).await
+    }
+
+    pub async fn approval(&self, id: &str) -> Result<Approval, ClientError> {
+        self.get(&format!("/approvals/{}", segment(id))).await
+    }
+
+    pub async fn topup(&self, wallet: &str) -> Result<Topup, ClientError> {
+        self.get(&format!("/topups/{}", segment(wallet))).await
+    }
+
+    pub async fn register_topup(&self, request: &TopupRequest) -> Result<Topup, ClientError> {
+        self.post("/topups", request).await
+    }
+
+    pub async fn remove_topup(&self, wallet: &str) -> Result<(), ClientError> {
+        self.delete(&format!("/topups/{}", segment(wallet))).await
+    }
+
+    // Admin
+
+    pub async fn reload_config(&self) -> Result<ReloadReport, ClientError> {
+        self.post_empty("/admin/config/reload").await
+    }
+
+    pub async fn audit_log(&self, query: &AuditQuery) -> Result<AuditQueryResponse, ClientError> {
+        self.get_with("/admin/audit", query).await
+    }
+
+    pub async fn allowlist(&self) -> Result<AllowlistResponse, ClientError> {
+        self.get("/admin/allowlist").await
+    }
+
+    pub async fn add_to_allowlist(
+        &self,
+        request: &AllowlistAddRequest,
+    ) -> Result<AllowlistAddResponse, ClientError> {
+        self.post("/admin/allowlist", request).await
+    }
+
+    pub async fn remove_from_allowlist(&self, wallet: &str) -> Result<(), ClientError> {
+        self.delete(&format!("/admin/allowlist/{}", segment(wallet)))
+            .await
+    }
+
+    pub async fn set_program_name(
+        &self,
+        program: &str,
+        request: &SetProgramNameRequest,
+    ) -> Result<KnownProgram, ClientError> {
+        self.put(&format!("/admin/programs/{}", segment(program)), request)
+            .await
+    }
+
+    // The program's id comes from the IDL unless `query` gives one.
+    pub async fn upload_idl(
+        &self,
+        idl: &serde_json::Value,
+        query: &IdlUploadQuery,
+    ) -> Result<IdlSummary, ClientError> {
+        let request = self.http.post(self.url("/idl")).query(query).json(idl);
+        self.send(request).await
+    }
+
+    pub async fn remove_program_name(&self, program: &str) -> Result<(), ClientError> {
+        self.delete(&format!("/admin/programs/{}", segment(program)))
+            .await
+    }
+
+    pub async fn set_label(
+        &self,
+        address: &str,
+        request: &SetLabelRequest,
+    ) -> Result<Label, ClientError> {
+        self.put(&format!("/admin/labels/{}", segment(address)), request)
+            .await
+    }
+
+    pub async fn remove_label(&self, address: &str) -> Result<(), ClientError> {
+        self.delete(&format!("/admin/labels/{}", segment(address)))
+            .await
+    }
+
+    pub async fn all_usage(&self) -> Result<Vec<UsageReport>, ClientError> {
+        self.get("/admin/usage").await
+    }
+
+    pub async fn route_usage(&self) -> Result<Vec<RouteRpcReport>, ClientError> {
+        self.get("/admin/usage/routes").await
+    }
+
+    pub async fn treasury(&self, query: &TreasuryQuery) -> Result<TreasuryResponse, ClientError> {
+        self.get_with("/admin/treasury", query).await
+    }
+
+    pub async fn sweep_treasury(&self) -> Result<RebalanceResponse, ClientError> {
+        self.post_empty("/admin/treasury/sweep").await
+    }
+
+    pub async fn rebalance_treasury(
+        &self,
+        request: &RebalanceRequest,
+    ) -> Result<RebalanceResponse, ClientError> {
+        self.post("/admin/treasury/rebalance", request).await
+    }
+
+    pub async fn balance_alerts(&self) -> Result<BalanceAlertsResponse, ClientError> {
+        self.get("/admin/alerts").await
+    }
+
+    pub async fn create_balance_alert(
+        &self,
+        request: &BalanceAlertRequest,
+    ) -> Result<BalanceAlert, ClientError> {
+        self.post("/admin/alerts", request).await
+    }
+
+    pub async fn remove_balance_alert(&self, id: u64) -> Result<(), ClientError> {
+        self.delete(&format!("/admin/alerts/{}", id)).await
+    }
+
+    pub async fn last_export(&self) -> Result<ExportReport, ClientError> {
+        self.get("/admin/export").await
+    }
+
+    pub async fn run_export(&self) -> Result<ExportReport, ClientError> {
+        self.post_empty("/admin/export").await
+    }
+
+    // `csv` is the upload itself: a header row, then a row per recipient.
+    pub async fn bulk_airdrop(&self, csv: &str) -> Result<JobAcceptedResponse, ClientError> {
+        let file = Part::text(csv.to_string()).file_name("airdrop.csv");
+        let request = self
+            .http
+            .post(self.url("/admin/airdrop_bulk"))
+            .multipart(Form::new().part("file", file));
+        self.send(request).await
+    }
+
+    pub async fn bulk_airdrop_status(&self, id: u64) -> Result<Job, ClientError> {
+        self.get(&format!("/admin/airdrop_bulk/{}", id)).await
+    }
+
+    // The finished job's results as CSV.
+    pub async fn bulk_airdrop_results(&self, id: u64) -> Result<String, ClientError> {
+        let url = self.url(&format!("/admin/airdrop_bulk/{}/results.csv", id));
+        self.text(self.http.get(url)).await
+    }
+
+    pub async fn approvals(
+        &self,
+        query: &ApprovalsQuery,
+    ) -> Result<ApprovalsResponse, ClientError> {
+        self.get_with("/admin/approvals", query).await
+    }
+
+    pub async fn approve_airdrop(&self, id: &str) -> Result<Approval, ClientError> {
+        self.post_empty(&format!("/admin/approvals/{}/approve", segment(id)))
+            .await
+    }
+
+    pub async fn reject_airdrop(
+        &self,
+        id: &str,
+        request: &RejectRequest,
+    ) -> Result<Approval, ClientError> {
+        self.post(&format!("/admin/approvals/{}/reject", segment(id)), request)
+            .await
+    }
+
+    pub async fn keys(&self, query: &KeysQuery) -> Result<KeysResponse, ClientError> {
+        self.get_with("/admin/keys", query).await
+    }
+
+    pub async fn create_key(
+        &self,
+        request: &CreateKeyRequest,
+    ) -> Result<IssuedKeyResponse, ClientError> {
+        self.post("/admin/keys", request).await
+    }
+
+    pub async fn revoke_key(&self, id: u64) -> Result<(), ClientError> {
+        self.delete(&format!("/admin/keys/{}", id)).await
+    }
+
+    pub async fn rotate_key(&self, id: u64) -> Result<IssuedKeyResponse, ClientError> {
+        self.post_empty(&format!("/admin/keys/{}/rotate", id)).await
+    }
+
+    pub async fn orgs(&self) -> Result<OrgsResponse, ClientError> {
+        self.get("/admin/orgs").await
+    }
+
+    pub async fn create_org(&self, request: &CreateOrgRequest) -> Result<OrgReport, ClientError> {
+        self.post("/admin/orgs", request).await
+    }
+
+    pub async fn org(&self, org: &str) -> Result<OrgReport, ClientError> {
+        self.get(&format!("/admin/orgs/{}", segment(org))).await
+    }
+
+    pub async fn update_org(
+        &self,
+        org: &str,
+        settings: &OrgSettings,
+    ) -> Result<OrgReport, ClientError> {
+        self.put(&format!("/admin/orgs/{}", segment(org)), settings)
+            .await
+    }
+
+    pub async fn delete_org(&self, org: &str) -> Result<(), ClientError> {
+        self.delete(&format!("/admin/orgs/{}", segment(org))).await
+    }
+
+    pub async fn issue_org_key(
+        &self,
+        org: &str,
+        request: &IssueKeyRequest,
+    ) -> Result<IssuedKeyResponse, ClientError> {
+        self.post(&format!("/admin/orgs/{}/keys", segment(org)), request)
+            .await
+    }
+
+    pub async fn revoke_org_key(&self, org: &str, id: u64) -> Result<(), ClientError> {
+        self.delete(&format!("/admin/orgs/{}/keys/{}", segment(org), id))
+            .await
+    }
+
+    pub async fn rotate_org_key(
+        &self,
+        org: &str,
+        id: u64,
+    ) -> Result<IssuedKeyResponse, ClientError> {
+        self.post_empty(&format!("/admin/orgs/{}/keys/{}/rotate", segment(org), id))
+            .await
+    }
+
+    pub async fn org_audit_log(
+        &self,
+        org: &str,
+        query: &AuditQuery,
+    ) -> Result<AuditQueryResponse, ClientError> {
+        self.get_with(&format!("/admin/orgs/{}/audit", segment(org)), query)
+            .await
+    }
+
+    pub async fn mock_status(&self) -> Result<MockStatus, ClientError> {
+        self.get("/admin/mock").await
+    }
+
+    pub async fn set_mock_faults(&self, faults: &Faults) -> Result<MockStatus, ClientError> {
+        self.put("/admin/mock/faults", faults).await
+    }
+
+    pub async fn set_mock_balance(
+        &self,
+        wallet: &str,
+        request: &MockBalanceRequest,
+    ) -> Result<MockStatus, ClientError> {
+        self.put(
+            &format!("/admin/mock/accounts/{}", segment(wallet)),
+            request,
+        )
+        .await
+    }
+
+    pub async fn reset_mock(&self) -> Result<MockStatus, ClientError> {
+        self.post_empty("/admin/mock/reset").await
+    }
+
+    pub async fn features(&self) -> Result<Vec<FeatureInfo>, ClientError> {
+        self.get("/admin/features").await
+    }
+
+    // A None state puts a feature back to its configured default.
+    pub async fn update_features(
+        &self,
+        changes: &BTreeMap<String, Option<FeatureState>>,
+    ) -> Result<Vec<FeatureInfo>, ClientError> {
+        self.put("/admin/features", changes).await
+    }
+
+    pub async fn chaos_status(&self) -> Result<ChaosStatus, ClientError> {
+        self.get("/admin/chaos").await
+    }
+
+    pub async fn set_chaos_rules(&self, rules: &[ChaosRule]) -> Result<ChaosStatus, ClientError> {
+        self.put("/admin/chaos", rules).await
+    }
+
+    pub async fn clear_chaos_rules(&self) -> Result<ChaosStatus, ClientError> {
+        self.send(self.http.delete(self.url("/admin/chaos"))).await
+    }
+
+    // Profiling, on servers built with it
+
+    // A pprof profile, or a flamegraph SVG, as `query.format` asks.
+    pub async fn pprof(&self, query: &PprofQuery) -> Result<Vec<u8>, ClientError> {
+        self.bytes(self.http.get(self.url("/debug/pprof")).query(query))
+            .await
+    }
+
+    pub async fn cpu_profile(&self, query: &ProfileQuery) -> Result<CpuProfile, ClientError> {
+        self.get_with("/debug/cpu", query).await
+    }
+
+    pub async fn tokio_report(&self, query: &ProfileQuery) -> Result<RuntimeReport, ClientError> {
+        self.get_with("/debug/tokio", query).await
+    }
+
+    // Dev
+
+    // Deploys in the background; a fresh program id when `program_id` is None.
+    pub async fn deploy_program(
+        &self,
+        program: &[u8],
+        program_id: Option<&str>,
+    ) -> Result<JobAcceptedResponse, ClientError> {
+        let mut form = Form::new().part(
+            "program",
+            Part::bytes(program.to_vec()).file_name("program.so"),
+        );
+        if let Some(id) = program_id {
+            form = form.text("program_id", id.to_string());
+        }
+        let request = self.http.post(self.url("/dev/deploy")).multipart(form);
+        self.send(request).await
+    }
+
+    pub async fn job(&self, id: u64) -> Result<Job, ClientError> {
+        self.get(&format!("/dev/jobs/{}", id)).await
+    }
+
+    pub async fn mint_nft(&self, request: &MintNftRequest) -> Result<MintNftResponse, ClientError> {
+        self.post("/dev/nft/mint", request).await
+    }
+
+    pub async fn sign_message(
+        &self,
+        request: &SignMessageRequest,
+    ) -> Result<SignMessageResponse, ClientError> {
+        self.post("/dev/sign_message", request).await
+    }
+
+    pub async fn stake_pool_deposit(
+        &self,
+        pool: &str,
+        request: &StakePoolDepositRequest,
+    ) -> Result<StakePoolTransactionResponse, ClientError> {
+        self.post(
+            &format!("/dev/stake-pools/{}/deposit", segment(pool)),
+            request,
+        )
+        .await
+    }
+
+    pub async fn stake_pool_withdraw(
+        &self,
+        pool: &str,
+        request: &StakePoolWithdrawRequest,
+    ) -> Result<StakePoolTransactionResponse, ClientError> {
+        self.post(
+            &format!("/dev/stake-pools/{}/withdraw", segment(pool)),
+            request,
+        )
+        .await
+    }
+
+    pub async fn buffers(&self) -> Result<ListBuffersResponse, ClientError> {
+        self.get("/dev/buffers").await
+    }
+
+    pub async fn close_buffers(
+        &self,
+        request: &CloseBuffersRequest,
+    ) -> Result<CloseBuffersResponse, ClientError> {
+        self.post("/dev/buffers/close", request).await
+    }
+
+    pub async fn validator_status(&self) -> Result<ValidatorStatus, ClientError> {
+        self.get("/dev/validator").await
+    }
+
+    pub async fn start_validator(&self) -> Result<ValidatorStatus, ClientError> {
+        self.post_empty("/dev/validator/start").await
+    }
+
+    pub async fn stop_validator(&self) -> Result<ValidatorStatus, ClientError> {
+        self.post_empty("/dev/validator/stop").await
+    }
+
+    pub async fn reset_validator(&self) -> Result<ValidatorStatus, ClientError> {
+        self.post_empty("/dev/validator/reset").await
+    }
+
+    pub async fn fixtures(&self) -> Result<FixturesResponse, ClientError> {
+        self.get("/dev/fixtures").await
+    }
+
+    pub async fn load_fixtures(
+        &self,
+        request: &LoadFixturesRequest,
+    ) -> Result<FixturesResponse, ClientError> {
+        self.post("/dev/fixtures", request).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    async fn put<B, T>(&self, path: &str, body: &B) -> Result<T, ClientError>
+    where
+        B: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        self.send(self.http.put(self.url(path)).json(body)).await
+    }
+
+    async fn post_empty<T: DeserializeOwned>(&self, path: &str) -> Result<T, ClientError> {
+        self.send(self.http.post(self.url(path))).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), ClientError> {
+        self.respond(self.http.delete(self.url(path))).await?;
+        Ok(())
+    }
+
+    async fn text(&self, request: RequestBuilder) -> Result<String, ClientError> {
+        Ok(self.respond(request).await?.text().await?)
+    }
+
+    async fn bytes(&self, request: RequestBuilder) -> Result<Vec<u8>, ClientError> {
+        Ok(self.respond(request).await?.bytes().await?.to_vec())
+    }
+
+    async fn stream<T>(
+        &self,
+        request: RequestBuilder,
+        parse: fn(&str, &str) -> serde_json::Result<T>,
+    ) -> Result<EventStream<T>, ClientError> {
+        Ok(EventStream {
+            res: self.respond(request).await?,
+            buf: Vec::new(),
+            parse,
+        })
+    }
+
+    // Health reports come with a 503 when something is down.
+    async fn report<T: DeserializeOwned>(&self, path: &str) -> Result<T, ClientError> {
+        let res = self.authorize(self.http.get(self.url(path))).send().await?;
+        let status = res.status();
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            let text = res.text().await?;
+            return match serde_json::from_str::<Body<T>>(&text) {
+                Ok(Body::Envelope { data } | Body::Bare(data)) => Ok(data),
+                Err(_) => Err(api_error(status, text)),
+            };
+        }
+        Self::json(Self::checked(res).await?).await
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, ClientError> {
+        Self::json(self.respond(request).await?).await
+    }
+
+    async fn respond(&self, request: RequestBuilder) -> Result<Response, ClientError> {
+        Self::checked(self.authorize(request).send().await?).await
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn checked(res: Response) -> Result<Response, ClientError> {
+        let status = res.status();
+        if status.is_success() {
+            return Ok(res);
+        }
+        Err(api_error(status, res.text().await?))
+    }
+
+    async fn json<T: DeserializeOwned>(res: Response) -> Result<T, ClientError> {
+        match res.json::<Body<T>>().await? {
+            Body::Envelope { data } | Body::Bare(data) => Ok(data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        http::{header, StatusCode as Status},
+        routing::{delete, get},
+        Router,
+    };
+
+    const STATUS_STREAM: &str = concat!(
+        ": ping\n\n",
+        "event: status\ndata: {\"signature\":\"s\",\"explorer_urls\":",
+        "{\"solana_explorer\":\"e\",\"solscan\":\"s\"},",
+        "\"commitment\":\"confirmed\",\"slot\":9}\n\n",
+        "event: timeout\ndata: {\"signature\":\"s\",\"commitment\":\"confirmed\"}\n\n",
+    );
+
+    // A stand-in server answering a few routes the way the real one does.
+    async fn serve() -> SolanaServerClient {
+        let app = Router::new()
+            .route(
+                "/health",
+                get(|| async {
+                    (
+                        Status::SERVICE_UNAVAILABLE,
+                        r#"{"success":true,"data":{"status":"unhealthy","rpc_url":"http://node"}}"#,
+                    )
+                }),
+            )
+            .route("/openapi.json", get(|| async { r#"{"openapi":"3.1.0"}"# }))
+            .route(
+                "/dev/jobs/{id}",
+                get(|| async {
+                    (
+                        Status::NOT_FOUND,
+                        r#"{"success":false,"error":{"code":"not_found","message":"No such job","request_id":"r1"}}"#,
+                    )
+                }),
+            )
+            .route(
+                "/admin/keys/{id}",
+                delete(|| async { Status::NO_CONTENT }),
+            )
+            .route(
+                "/receipts/{id}",
+                get(|| async { (Status::TOO_MANY_REQUESTS, "slow down") }),
+            )
+            .route(
+                "/tx/{signature}/stream",
+                get(|| async { ([(header::CONTENT_TYPE, "text/event-stream")], STATUS_STREAM) }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        SolanaServerClient::new(format!("http://{}/", addr))
+    }
+
+    #[test]
+    fn segments_are_percent_encoded() {
+        assert_eq!(segment("abc-DEF_1.2~"), "abc-DEF_1.2~");
+        assert_eq!(segment("a b/c?d"), "a%20b%2Fc%3Fd");
+        assert_eq!(segment("é"), "%C3%A9");
+    }
+
+    #[test]
+    fn sse_frames_join_data_lines() {
+        assert_eq!(
+            sse_frame("event: status\nid: 7\ndata: {\"a\":\ndata: 1}\n\n"),
+            ("status".to_string(), "{\"a\":\n1}".to_string())
+        );
+        assert_eq!(
+            sse_frame("data:x\n\n"),
+            ("message".to_string(), "x".to_string())
+        );
+        assert_eq!(
+            sse_frame(": keep-alive\n\n"),
+            ("message".to_string(), String::new())
+        );
+    }
+
+    #[test]
+    fn legacy_errors_keep_their_message() {
+        let body = r#"{"error":"Invalid address","code":"bad_request"}"#;
+        match api_error(StatusCode::BAD_REQUEST, body.to_string()) {
+            ClientError::Api {
+                status,
+                code,
+                message,
+                request_id,
+            } => {
+                assert_eq!(status, 400);
+                assert_eq!(code, "");
+                assert_eq!(message, "Invalid address");
+                assert_eq!(request_id, None);
+            }
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn typed_methods_read_bodies_and_errors() {
+        let client = serve().await;
+        assert_eq!(client.health().await.unwrap().status, "unhealthy");
+        assert_eq!(client.openapi().await.unwrap()["openapi"], "3.1.0");
+        client.revoke_key(4).await.unwrap();
+        match client.job(7).await {
+            Err(ClientError::Api {
+                status,
+                code,
+                message,
+                request_id,
+            }) => {
+                assert_eq!(status, 404);
+                assert_eq!(code, "not_found");
+                assert_eq!(message, "No such job");
+                assert_eq!(request_id.as_deref(), Some("r1"));
+            }
+            other => panic!("{:?}", other),
+        }
+        match client.receipt("a b").await {
+            Err(ClientError::Api {
+                status, message, ..
+            }) => assert_eq!((status, message.as_str()), (429, "slow down")),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn transaction_streams_end_with_a_timeout() {
+        let client = serve().await;
+        let mut updates = client.stream_transaction("s").await.unwrap();
+        match updates.next().await.unwrap() {
+            Some(TransactionUpdate::Status(status)) => {
+                assert_eq!(status.commitment, "confirmed");
+                assert_eq!(status.slot, Some(9));
+            }
+            other => panic!("{:?}", other),
+        }
+        match updates.next().await.unwrap() {
+            Some(TransactionUpdate::Timeout { commitment }) => {
+                assert_eq!(commitment.as_deref(), Some("confirmed"))
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(updates.next().await.unwrap().is_none());
+    }
+}
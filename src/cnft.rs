@@ -0,0 +1,402 @@
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_client::rpc_request::RpcRequest;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_sdk::{
+    hash,
+    instruction::{AccountMeta, Instruction},
+    packet::PACKET_DATA_SIZE,
+    pubkey,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_program,
+    transaction::Transaction,
+};
+use std::str::FromStr;
+
+use crate::{
+    confirm, rpc,
+    service::{rpc_error, ServiceError},
+};
+
+const BUBBLEGUM_PROGRAM_ID: Pubkey = pubkey!("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+const ACCOUNT_COMPRESSION_PROGRAM_ID: Pubkey =
+    pubkey!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+const NOOP_PROGRAM_ID: Pubkey = pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+
+// spl-account-compression tree account layout: account type and header
+// version bytes, then the v1 header (max_buffer_size, max_depth, authority,
+// creation_slot, padding), the tree itself, and the canopy at the end.
+const TREE_HEADER_SIZE: usize = 2 + 54;
+const TREE_MAX_BUFFER_SIZE_OFFSET: usize = 2;
+const TREE_MAX_DEPTH_OFFSET: usize = 6;
+
+// DAS (Metaplex Read API) responses, reduced to the fields used here.
+#[derive(Deserialize)]
+struct DasAsset {
+    compression: DasCompression,
+    ownership: DasOwnership,
+}
+
+#[derive(Deserialize)]
+struct DasCompression {
+    compressed: bool,
+    data_hash: String,
+    creator_hash: String,
+    leaf_id: u64,
+    tree: String,
+}
+
+#[derive(Deserialize)]
+struct DasOwnership {
+    owner: String,
+    delegate: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AssetProof {
+    pub root: String,
+    pub proof: Vec<String>,
+    pub node_index: u64,
+    pub leaf: String,
+    pub tree_id: String,
+}
+
+pub struct TransferPlan {
+    pub owner: Pubkey,
+    pub tree: Pubkey,
+    pub leaf_index: u32,
+    pub proof_len: usize,
+    pub canopy_depth: usize,
+    pub instruction: Instruction,
+}
+
+fn das_call<T: serde::de::DeserializeOwned>(
+    client: &RpcClient,
+    method: &'static str,
+    asset_id: &str,
+) -> Result<T, ServiceError> {
+    // Positional; RpcClient only sends array params, which DAS servers
+    // accept the same as {"id": ...}.
+    let value: Value = client
+        .send(RpcRequest::Custom { method }, json!([asset_id]))
+        .map_err(rpc_error("DAS request failed"))?;
+    serde_json::from_value(value).map_err(|e| ServiceError::Rpc {
+        action: "Unexpected DAS response",
+        message: format!("{}: {}", method, e),
+    })
+}
+
+fn parse_key(name: &str, value: &str) -> Result<Pubkey, ServiceError> {
+    Pubkey::from_str(value).map_err(|_| ServiceError::Rpc {
+        action: "Unexpected DAS response",
+        message: format!("'{}' is not a valid address: {}", name, value),
+    })
+}
+
+// DAS returns hashes base58 encoded, the same as addresses.
+fn parse_hash(name: &str, value: &str) -> Result<[u8; 32], ServiceError> {
+    parse_key(name, value).map(|key| key.to_bytes())
+}
+
+fn parse_asset_id(asset_id: &str) -> Result<(), ServiceError> {
+    Pubkey::from_str(asset_id)
+        .map(|_| ())
+        .map_err(|_| ServiceError::Invalid(format!("Invalid asset id '{}'", asset_id)))
+}
+
+pub fn asset_proof(das_url: &str, asset_id: &str) -> Result<AssetProof, ServiceError> {
+    parse_asset_id(asset_id)?;
+    let client = rpc::client(das_url);
+
+    let _span = tracing::info_span!("das.get_asset_proof", asset_id).entered();
+    das_call(&client, "getAssetProof", asset_id)
+}
+
+// Proof nodes the tree keeps on chain (its canopy) must be left out of the
+// instruction, or the transaction quickly outgrows the packet limit.
+fn canopy_depth(client: &RpcClient, tree: &Pubkey) -> Result<usize, ServiceError> {
+    let data = client
+        .get_account_data(tree)
+        .map_err(rpc_error("Failed to load merkle tree"))?;
+    let read_u32 = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+    };
+    let (Some(max_buffer_size), Some(max_depth)) = (
+        read_u32(TREE_MAX_BUFFER_SIZE_OFFSET),
+        read_u32(TREE_MAX_DEPTH_OFFSET),
+    ) else {
+        return Err(ServiceError::Invalid(format!(
+            "{} is not a merkle tree account",
+            tree
+        )));
+    };
+    // sequence_number, active_index and buffer_size, then the changelog
+    // buffer and rightmost proof, each entry 32 * depth + 40 bytes.
+    let entry = 32 * max_depth + 40;
+    let tree_size = 24 + max_buffer_size * entry + entry;
+    let canopy_nodes = data.len().saturating_sub(TREE_HEADER_SIZE + tree_size) / 32;
+    Ok((canopy_nodes + 2).ilog2() as usize - 1)
+}
+
+// Bubblegum `transfer`, signed by the current owner.
+pub fn plan_transfer(
+    das_url: &str,
+    rpc_url: &str,
+    asset_id: &str,
+    new_owner: &Pubkey,
+) -> Result<TransferPlan, ServiceError> {
+    parse_asset_id(asset_id)?;
+    let das = rpc::client(das_url);
+
+    let _span = tracing::info_span!("das.plan_transfer", asset_id).entered();
+    let asset: DasAsset = das_call(&das, "getAsset", asset_id)?;
+    if !asset.compression.compressed {
+        return Err(ServiceError::Invalid(format!(
+            "Asset {} is not a compressed NFT",
+            asset_id
+        )));
+    }
+    let proof: AssetProof = das_call(&das, "getAssetProof", asset_id)?;
+    if proof.tree_id != asset.compression.tree {
+        return Err(ServiceError::Rpc {
+            action: "Unexpected DAS response",
+            message: format!(
+                "proof is for tree {}, asset is in {}",
+                proof.tree_id, asset.compression.tree
+            ),
+        });
+    }
+
+    let owner = parse_key("owner", &asset.ownership.owner)?;
+    let delegate = match &asset.ownership.delegate {
+        Some(delegate) => parse_key("delegate", delegate)?,
+        None => owner,
+    };
+    let tree = parse_key("tree", &asset.compression.tree)?;
+    let leaf_index = u32::try_from(asset.compression.leaf_id).map_err(|_| ServiceError::Rpc {
+        action: "Unexpected DAS response",
+        message: format!("leaf_id {} is out of range", asset.compression.leaf_id),
+    })?;
+
+    let canopy_depth = canopy_depth(&rpc::client(rpc_url), &tree)?;
+    let proof_len = proof.proof.len().saturating_sub(canopy_depth);
+    let proof_accounts = proof.proof[..proof_len]
+        .iter()
+        .map(|node| parse_key("proof", node).map(|key| AccountMeta::new_readonly(key, false)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (tree_config, _) = Pubkey::find_program_address(&[tree.as_ref()], &BUBBLEGUM_PROGRAM_ID);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(tree_config, false),
+        AccountMeta::new_readonly(owner, true),
+        AccountMeta::new_readonly(delegate, false),
+        AccountMeta::new_readonly(*new_owner, false),
+        AccountMeta::new(tree, false),
+        AccountMeta::new_readonly(NOOP_PROGRAM_ID, false),
+        AccountMeta::new_readonly(ACCOUNT_COMPRESSION_PROGRAM_ID, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(proof_accounts);
+
+    // Anchor discriminator, then root, data_hash, creator_hash, nonce and
+    // index. Bubblegum uses the leaf index as the nonce.
+    let mut data = hash::hash(b"global:transfer").to_bytes()[..8].to_vec();
+    data.extend_from_slice(&parse_hash("root", &proof.root)?);
+    data.extend_from_slice(&parse_hash("data_hash", &asset.compression.data_hash)?);
+    data.extend_from_slice(&parse_hash(
+        "creator_hash",
+        &asset.compression.creator_hash,
+    )?);
+    data.extend_from_slice(&asset.compression.leaf_id.to_le_bytes());
+    data.extend_from_slice(&leaf_index.to_le_bytes());
+
+    Ok(TransferPlan {
+        owner,
+        tree,
+        leaf_index,
+        proof_len,
+        canopy_depth,
+        instruction: Instruction {
+            program_id: BUBBLEGUM_PROGRAM_ID,
+            accounts,
+            data,
+        },
+    })
+}
+
+fn check_size(tx: &Transaction) -> Result<(), ServiceError> {
+    let size = bincode::serialized_size(tx).unwrap_or(u64::MAX);
+    if size > PACKET_DATA_SIZE as u64 {
+        return Err(ServiceError::Invalid(format!(
+            "Transfer transaction is {} bytes, over the {} byte limit; the tree's canopy is too small for a plain transaction",
+            size, PACKET_DATA_SIZE
+        )));
+    }
+    Ok(())
+}
+
+// Unsigned, with the owner as fee payer, base64 encoded for wallets'
+// signTransaction.
+pub fn unsigned_transfer(rpc_url: &str, plan: &TransferPlan) -> Result<String, ServiceError> {
+    let client = rpc::client(rpc_url);
+    let blockhash = client
+        .get_latest_blockhash()
+        .map_err(rpc_error("Failed to build transfer"))?;
+    let mut tx =
+        Transaction::new_with_payer(std::slice::from_ref(&plan.instruction), Some(&plan.owner));
+    tx.message.recent_blockhash = blockhash;
+    check_size(&tx)?;
+    let bytes = bincode::serialize(&tx).map_err(|e| ServiceError::Invalid(e.to_string()))?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+pub fn submit_transfer(
+    rpc_url: &str,
+    owner: &Keypair,
+    plan: &TransferPlan,
+) -> Result<Signature, ServiceError> {
+    let client = rpc::client(rpc_url);
+    let blockhash = client
+        .get_latest_blockhash()
+        .map_err(rpc_error("Transfer failed"))?;
+    let tx = Transaction::new_signed_with_payer(
+        std::slice::from_ref(&plan.instruction),
+        Some(&owner.pubkey()),
+        &[owner],
+        blockhash,
+    );
+    check_size(&tx)?;
+    confirm::send_and_confirm(&client, &tx, "Transfer failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_rpc_client::mock_sender::Mocks;
+
+    fn tree_client(max_depth: u32, max_buffer_size: u32, len: usize) -> RpcClient {
+        let mut data = vec![0u8; len.max(TREE_HEADER_SIZE)];
+        data[TREE_MAX_BUFFER_SIZE_OFFSET..TREE_MAX_BUFFER_SIZE_OFFSET + 4]
+            .copy_from_slice(&max_buffer_size.to_le_bytes());
+        data[TREE_MAX_DEPTH_OFFSET..TREE_MAX_DEPTH_OFFSET + 4]
+            .copy_from_slice(&max_depth.to_le_bytes());
+        data.truncate(len);
+        let account = json!({
+            "context": { "slot": 1 },
+            "value": {
+                "lamports": 1,
+                "data": [base64::engine::general_purpose::STANDARD.encode(&data), "base64"],
+                "owner": ACCOUNT_COMPRESSION_PROGRAM_ID.to_string(),
+                "executable": false,
+                "rentEpoch": 0,
+                "space": len,
+            },
+        });
+        RpcClient::new_mock_with_mocks(
+            "succeeds",
+            Mocks::from([(RpcRequest::GetAccountInfo, account)]),
+        )
+    }
+
+    #[test]
+    fn canopy_depth_from_account_size() {
+        // 31_800 is getConcurrentMerkleTreeAccountSize(14, 64) from the
+        // spl-account-compression SDK; each canopy level adds 2^level nodes.
+        let tree = Pubkey::new_unique();
+        assert_eq!(
+            canopy_depth(&tree_client(14, 64, 31_800), &tree).unwrap(),
+            0
+        );
+        assert_eq!(
+            canopy_depth(&tree_client(14, 64, 97_272), &tree).unwrap(),
+            10
+        );
+        assert_eq!(canopy_depth(&tree_client(3, 8, 1_496), &tree).unwrap(), 2);
+        assert!(matches!(
+            canopy_depth(&tree_client(0, 0, 4), &tree),
+            Err(ServiceError::Invalid(message)) if message.ends_with("is not a merkle tree account")
+        ));
+    }
+
+    #[test]
+    fn das_responses_are_checked() {
+        let asset_id = Pubkey::new_unique().to_string();
+        let das = |value: Value| {
+            RpcClient::new_mock_with_mocks(
+                "succeeds",
+                Mocks::from([(
+                    RpcRequest::Custom {
+                        method: "getAssetProof",
+                    },
+                    value,
+                )]),
+            )
+        };
+        let proof: AssetProof = das_call(
+            &das(json!({
+                "root": "11111111111111111111111111111111",
+                "proof": ["11111111111111111111111111111111"],
+                "node_index": 16384,
+                "leaf": "11111111111111111111111111111111",
+                "tree_id": "11111111111111111111111111111111",
+            })),
+            "getAssetProof",
+            &asset_id,
+        )
+        .unwrap();
+        assert_eq!((proof.node_index, proof.proof.len()), (16384, 1));
+
+        let err = das_call::<AssetProof>(&das(json!({ "root": 1 })), "getAssetProof", &asset_id)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            ServiceError::Rpc { action: "Unexpected DAS response", message }
+                if message.starts_with("getAssetProof: ")
+        ));
+    }
+
+    #[test]
+    fn inputs_are_validated() {
+        assert!(matches!(
+            parse_asset_id("nope"),
+            Err(ServiceError::Invalid(message)) if message == "Invalid asset id 'nope'"
+        ));
+        assert_eq!(
+            parse_hash("root", "11111111111111111111111111111111").unwrap(),
+            [0; 32]
+        );
+        assert!(parse_key("owner", "xyz").is_err());
+    }
+
+    #[test]
+    fn oversized_transfers_are_refused() {
+        let owner = Pubkey::new_unique();
+        let instruction = Instruction {
+            program_id: BUBBLEGUM_PROGRAM_ID,
+            accounts: (0..30)
+                .map(|_| AccountMeta::new_readonly(Pubkey::new_unique(), false))
+                .chain([AccountMeta::new_readonly(owner, true)])
+                .collect(),
+            data: vec![0; 108],
+        };
+        let tx = Transaction::new_with_payer(&[instruction], Some(&owner));
+        assert!(matches!(
+            check_size(&tx),
+            Err(ServiceError::Invalid(message)) if message.contains("over the 1232 byte limit")
+        ));
+
+        let small = Transaction::new_with_payer(
+            &[Instruction::new_with_bytes(
+                BUBBLEGUM_PROGRAM_ID,
+                &[0; 108],
+                vec![],
+            )],
+            Some(&owner),
+        );
+        assert!(check_size(&small).is_ok());
+    }
+}
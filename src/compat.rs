@@ -0,0 +1,321 @@
+use axum::{
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{sync::RwLock, time::Duration};
+
+use crate::{
+    clock::now_ms,
+    config::{Config, RpcCompatMode},
+    handlers::service_error,
+    logging, rpc,
+    service::ServiceError,
+    state::AppState,
+    tasks,
+};
+
+pub use crate::api::RpcStatus;
+
+// Set on write responses while the node is outside the supported range in
+// `warn` mode.
+const WARNING_HEADER: &str = "x-rpc-warning";
+
+// Parses "1.18.26" (and "1.18", or "2.0.0-beta" up to the dash) into a
+// comparable triple.
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(|p| p.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    parts.next().is_none().then_some((major, minor, patch))
+}
+
+// The last version check of the RPC node.
+#[derive(Default)]
+pub struct RpcCompat {
+    status: RwLock<Option<RpcStatus>>,
+}
+
+pub fn problems(config: &Config, version: &str, slot_lag: Option<u64>) -> Vec<String> {
+    let mut problems = Vec::new();
+    let parsed = parse_version(version);
+    if let Some(min) = &config.rpc_min_version {
+        if parsed.is_none_or(|v| Some(v) < parse_version(min)) {
+            problems.push(format!(
+                "RPC node runs {}, older than the supported minimum {}",
+                version, min
+            ));
+        }
+    }
+    if let Some(max) = &config.rpc_max_version {
+        if parsed.is_none_or(|v| Some(v) > parse_version(max)) {
+            problems.push(format!(
+                "RPC node runs {}, newer than the supported maximum {}",
+                version, max
+            ));
+        }
+    }
+    if let (Some(max_lag), Some(lag)) = (config.rpc_max_slot_lag, slot_lag) {
+        if lag > max_lag {
+            problems.push(format!(
+                "RPC node is {} slots behind the cluster (max {})",
+                lag, max_lag
+            ));
+        }
+    }
+    problems
+}
+
+// Blocking: asks the node for its version.
+pub fn check(rpc_url: &str) -> Result<RpcStatus, String> {
+    let version = rpc::client(rpc_url)
+        .get_version()
+        .map_err(|e| e.to_string())?;
+    Ok(RpcStatus {
+        problems: Vec::new(),
+        version: Some(version.solana_core),
+        feature_set: version.feature_set,
+        slot_lag: None,
+        error: None,
+        checked_at_ms: now_ms(),
+    })
+}
+
+impl RpcCompat {
+    pub fn status(&self, config: &Config, slot_lag: Option<u64>) -> Option<RpcStatus> {
+        let mut status = self.status.read().unwrap().clone()?;
+        status.slot_lag = slot_lag;
+        if let Some(version) = &status.version {
+            status.problems = problems(config, version, status.slot_lag);
+        }
+        Some(status)
+    }
+
+    fn record(&self, result: Result<RpcStatus, String>) {
+        let mut status = self.status.write().unwrap();
+        let updated = match (result, status.take()) {
+            (Ok(fresh), _) => fresh,
+            (Err(e), Some(previous)) => RpcStatus {
+                error: Some(e),
+                checked_at_ms: now_ms(),
+                ..previous
+            },
+            (Err(e), None) => RpcStatus {
+                version: None,
+                feature_set: None,
+                slot_lag: None,
+                problems: Vec::new(),
+                error: Some(e),
+                checked_at_ms: now_ms(),
+            },
+        };
+        *status = Some(updated);
+    }
+}
+
+// Checks the node at startup and then every
+// `rpc_version_check_interval_secs`, logging when it leaves or re-enters
+// the supported range.
+pub async fn watch(state: AppState) {
+    let mut compatible = true;
+    loop {
+        let config = state.config();
+        let rpc_url = config.rpc_url.clone();
+        let result = rpc::spawn_blocking(move || check(&rpc_url))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+        if result.is_ok() {
+            tasks::succeeded();
+        }
+        state.rpc_compat.record(result);
+        if let Some(status) = state
+            .rpc_compat
+            .status(&config, state.slots.lag_slots())
+            .filter(|s| s.compatible() != compatible)
+        {
+            compatible = status.compatible();
+            if compatible {
+                logging::info(format_args!(
+                    "RPC node {} is back within the supported range",
+                    config.rpc_url
+                ));
+            } else {
+                logging::warn(format_args!(
+                    "RPC node {}: {}",
+                    config.rpc_url,
+                    status.problems.join("; ")
+                ));
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(config.rpc_version_check_interval_secs)).await;
+    }
+}
+
+// Layered on the write routes: while the node is outside the supported
+// range they carry a warning header, or are refused in `refuse` mode.
+pub async fn guard(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let config = state.config();
+    let Some(status) = state
+        .rpc_compat
+        .status(&config, state.slots.lag_slots())
+        .filter(|s| !s.compatible())
+    else {
+        return next.run(req).await;
+    };
+    let problems = status.problems.join("; ");
+    if config.rpc_compat_mode == RpcCompatMode::Refuse {
+        return service_error(ServiceError::RpcIncompatible(problems)).into_response();
+    }
+    let mut res = next.run(req).await;
+    if let Ok(value) = HeaderValue::from_str(&problems) {
+        res.headers_mut().insert(WARNING_HEADER, value);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(min: Option<&str>, max: Option<&str>, max_lag: Option<u64>) -> Config {
+        Config {
+            rpc_min_version: min.map(String::from),
+            rpc_max_version: max.map(String::from),
+            rpc_max_slot_lag: max_lag,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn versions_parse_to_triples() {
+        assert_eq!(parse_version("1.18.26"), Some((1, 18, 26)));
+        assert_eq!(parse_version("1.18"), Some((1, 18, 0)));
+        assert_eq!(parse_version("2"), Some((2, 0, 0)));
+        assert_eq!(parse_version("2.0.0-beta"), Some((2, 0, 0)));
+        assert_eq!(parse_version("1.17.3+abc"), Some((1, 17, 3)));
+        assert_eq!(parse_version("1.2.3.4"), None);
+        assert_eq!(parse_version("one.two"), None);
+        assert_eq!(parse_version(""), None);
+        assert!(parse_version("1.9.0") < parse_version("1.10.0"));
+    }
+
+    #[test]
+    fn problems_against_the_supported_range() {
+        let config = limits(Some("1.17"), Some("1.18.26"), Some(100));
+        assert!(problems(&config, "1.18.26", Some(100)).is_empty());
+        assert!(problems(&config, "1.17.0", None).is_empty());
+        assert_eq!(
+            problems(&config, "1.16.9", None),
+            ["RPC node runs 1.16.9, older than the supported minimum 1.17"]
+        );
+        assert_eq!(
+            problems(&config, "2.0.0", Some(101)),
+            [
+                "RPC node runs 2.0.0, newer than the supported maximum 1.18.26",
+                "RPC node is 101 slots behind the cluster (max 100)",
+            ]
+        );
+        // A version that can't be parsed is outside any configured range.
+        assert_eq!(problems(&config, "garbage", None).len(), 2);
+        assert!(problems(&Config::default(), "garbage", Some(u64::MAX)).is_empty());
+    }
+
+    #[test]
+    fn failed_checks_keep_the_last_answer() {
+        let compat = RpcCompat::default();
+        let config = limits(Some("1.18"), None, Some(10));
+        assert!(compat.status(&config, None).is_none());
+
+        compat.record(Err("connection refused".into()));
+        let status = compat.status(&config, None).unwrap();
+        assert_eq!(status.version, None);
+        assert_eq!(status.error.as_deref(), Some("connection refused"));
+        assert!(status.compatible());
+
+        compat.record(Ok(RpcStatus {
+            version: Some("1.17.31".into()),
+            feature_set: Some(7),
+            slot_lag: None,
+            problems: Vec::new(),
+            error: None,
+            checked_at_ms: 1,
+        }));
+        compat.record(Err("timed out".into()));
+        let status = compat.status(&config, Some(11)).unwrap();
+        assert_eq!(status.version.as_deref(), Some("1.17.31"));
+        assert_eq!(status.feature_set, Some(7));
+        assert_eq!(status.error.as_deref(), Some("timed out"));
+        assert_eq!(status.slot_lag, Some(11));
+        assert_eq!(status.problems.len(), 2);
+        // Problems follow the config they're read against.
+        assert!(compat
+            .status(&Config::default(), Some(11))
+            .unwrap()
+            .compatible());
+    }
+
+    #[test]
+    fn check_asks_the_node() {
+        rpc::mock_for_tests();
+        let status = check("http://mock").unwrap();
+        assert_eq!(status.version.as_deref(), Some("1.18.26"));
+        assert_eq!(status.feature_set, Some(0));
+        assert!(status.error.is_none());
+    }
+
+    async fn serve(config: Config) -> (AppState, String) {
+        use axum::{middleware, routing::post, Router};
+
+        let state = AppState::for_tests(config);
+        let app = Router::new()
+            .route("/write", post(|| async { "ok" }))
+            .route_layer(middleware::from_fn_with_state(state.clone(), guard))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/write", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (state, url)
+    }
+
+    #[tokio::test]
+    async fn guard_warns_or_refuses() {
+        let old = || Ok(check_result("1.16.0"));
+        let client = reqwest::Client::new();
+
+        let (state, url) = serve(limits(Some("1.17"), None, None)).await;
+        let res = client.post(&url).send().await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert!(res.headers().get(WARNING_HEADER).is_none());
+        state.rpc_compat.record(old());
+        let res = client.post(&url).send().await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(
+            res.headers()[WARNING_HEADER],
+            "RPC node runs 1.16.0, older than the supported minimum 1.17"
+        );
+
+        let (state, url) = serve(Config {
+            rpc_compat_mode: RpcCompatMode::Refuse,
+            ..limits(Some("1.17"), None, None)
+        })
+        .await;
+        state.rpc_compat.record(old());
+        let res = client.post(&url).send().await.unwrap();
+        assert_eq!(res.status(), 503);
+        assert!(res.text().await.unwrap().contains("rpc_incompatible"));
+    }
+
+    fn check_result(version: &str) -> RpcStatus {
+        RpcStatus {
+            version: Some(version.into()),
+            feature_set: None,
+            slot_lag: None,
+            problems: Vec::new(),
+            error: None,
+            checked_at_ms: 0,
+        }
+    }
+}
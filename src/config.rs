@@ -0,0 +1,1856 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, fmt, fs, path::PathBuf, str::FromStr};
+
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+
+use crate::{
+    auth::{Priority, Role},
+    denials, deprecations,
+    features::FeatureState,
+    index,
+    service::AirdropLimits,
+};
+
+pub use crate::api::FrontendTheme;
+
+pub const DEFAULT_RPC_URL: &str = "https://api.devnet.solana.com";
+pub const DEFAULT_PORT: u16 = 3000;
+pub const DEFAULT_MAX_AIRDROP_SOL: u64 = 2;
+pub const DEFAULT_MIN_AIRDROP_LAMPORTS: u64 = 1;
+pub const DEFAULT_RPC_POOL_MAX_CONNECTIONS: usize = 64;
+pub const DEFAULT_RPC_POOL_IDLE_PER_HOST: usize = 16;
+pub const DEFAULT_RPC_POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+pub const DEFAULT_RPC_FANOUT_CONCURRENCY: usize = 8;
+pub const DEFAULT_RPC_FANOUT_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_RPC_VERSION_CHECK_INTERVAL_SECS: u64 = 300;
+pub const DEFAULT_SLOT_LAG_CHECK_INTERVAL_SECS: u64 = 15;
+pub const DEFAULT_SLO_WINDOW_SECS: u64 = 300;
+pub const DEFAULT_SLO_MIN_SUCCESS_RATE: f64 = 0.99;
+pub const DEFAULT_SLO_P95_LATENCY_MS: u64 = 2_000;
+pub const DEFAULT_SLO_MIN_REQUESTS: usize = 20;
+pub const DEFAULT_SHED_WINDOW_SECS: u64 = 30;
+pub const DEFAULT_SHED_RETRY_AFTER_SECS: u64 = 5;
+pub const DEFAULT_MAX_QUEUED_REQUESTS: usize = 1_000;
+pub const DEFAULT_QUEUE_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_IDEMPOTENCY_TTL_SECS: u64 = 86_400;
+pub const DEFAULT_DISPLAY_LOCALE: &str = "en-US";
+pub const DEFAULT_FRONTEND_ACCENT_COLOR: &str = "#3b82f6";
+pub const DEFAULT_SUMMARY_MAX_TRANSACTIONS: usize = 200;
+pub const DEFAULT_SUMMARY_CACHE_TTL_SECS: u64 = 300;
+pub const DEFAULT_HOT_REFRESH_SECS: u64 = 15;
+pub const DEFAULT_DENYLIST_REFRESH_SECS: u64 = 300;
+pub const DEFAULT_JWT_ROLES_CLAIM: &str = "roles";
+pub const DEFAULT_SIWS_DOMAIN: &str = "localhost";
+pub const DEFAULT_SIWS_SESSION_TTL_SECS: u64 = 3_600;
+pub const DEFAULT_NOTIFY_INTERVAL_SECS: u64 = 60;
+pub const DEFAULT_NOTIFY_COOLDOWN_SECS: u64 = 900;
+pub const DEFAULT_NOTIFY_AIRDROP_MAX_FAILURE_RATE: f64 = 0.5;
+pub const DEFAULT_TELEGRAM_API_URL: &str = "https://api.telegram.org";
+pub const DEFAULT_JUPITER_API_URL: &str = "https://lite-api.jup.ag/swap/v1";
+pub const DEFAULT_TOPUP_INTERVAL_SECS: u64 = 3_600;
+pub const DEFAULT_BALANCE_ALERT_INTERVAL_SECS: u64 = 60;
+pub const DEFAULT_BALANCE_WAIT_MAX_SECS: u64 = 60;
+pub const DEFAULT_BALANCE_WAIT_MAX_WAITERS: usize = 256;
+pub const DEFAULT_MAX_EVENT_STREAMS: usize = 64;
+pub const DEFAULT_TOPUP_MAX_TARGET_SOL: f64 = 10.0;
+pub const DEFAULT_TREASURY_CHECK_INTERVAL_SECS: u64 = 60;
+pub const DEFAULT_EVENT_NATS_SUBJECT: &str = "solana.events";
+pub const DEFAULT_EVENT_KAFKA_TOPIC: &str = "solana-events";
+pub const DEFAULT_EVENT_POLL_INTERVAL_SECS: u64 = 15;
+pub const DEFAULT_INDEX_RECONCILE_SECS: u64 = 300;
+pub const DEFAULT_TX_HISTORY_POLL_SECS: u64 = 60;
+pub const DEFAULT_TX_HISTORY_BATCH: usize = 100;
+pub const DEFAULT_TX_HISTORY_MAX_BACKFILL: usize = 1_000;
+pub const DEFAULT_EXPORT_ENDPOINT: &str = "https://s3.amazonaws.com";
+pub const DEFAULT_EXPORT_REGION: &str = "us-east-1";
+pub const DEFAULT_EXPORT_PREFIX: &str = "snapshots/";
+pub const DEFAULT_BULK_AIRDROP_CONCURRENCY: usize = 4;
+pub const DEFAULT_BULK_AIRDROP_MAX_ROWS: usize = 1_000;
+pub const DEFAULT_BULK_BALANCE_MAX_ADDRESSES: usize = 5_000;
+pub const DEFAULT_PROGRAM_ACCOUNTS_STREAM_MAX: usize = 100_000;
+pub const DEFAULT_VALIDATOR_BIN: &str = "solana-test-validator";
+pub const DEFAULT_VALIDATOR_LEDGER_DIR: &str = "test-ledger";
+pub const DEFAULT_VALIDATOR_RPC_PORT: u16 = 8899;
+pub const DEFAULT_VALIDATOR_STARTUP_TIMEOUT_SECS: u64 = 60;
+pub const DEFAULT_MAX_PROGRAM_SIZE: usize = 2 * 1024 * 1024;
+
+// Where RPC calls go: the configured node, or an in-memory mock ledger for
+// offline development.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    Rpc,
+    Mock,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rpc" => Ok(Backend::Rpc),
+            "mock" => Ok(Backend::Mock),
+            _ => Err(format!("unknown backend '{}'", s)),
+        }
+    }
+}
+
+// Where the persistent stores (audit log, allowlist, topups, watchlists,
+// orgs, API keys, approvals, labels, program names, jobs, quotas,
+// idempotency keys) keep their state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreBackend {
+    // Each store in the file at its `*_path`; stores without one are kept
+    // in memory only.
+    File,
+    // Every store in memory, lost on restart.
+    Memory,
+    // Every store in the SQLite database at `store_url`. Needs the `sqlite`
+    // feature.
+    Sqlite,
+    // Every store in the Postgres database at `store_url`. Needs the
+    // `postgres` feature. Stores are read once at startup and saved whole,
+    // without locking, so only one server should use a database at a time.
+    Postgres,
+}
+
+impl fmt::Display for StoreBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StoreBackend::File => "file",
+            StoreBackend::Memory => "memory",
+            StoreBackend::Sqlite => "sqlite",
+            StoreBackend::Postgres => "postgres",
+        })
+    }
+}
+
+impl FromStr for StoreBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(StoreBackend::File),
+            "memory" => Ok(StoreBackend::Memory),
+            "sqlite" => Ok(StoreBackend::Sqlite),
+            "postgres" => Ok(StoreBackend::Postgres),
+            _ => Err(format!("unknown store backend '{}'", s)),
+        }
+    }
+}
+
+// How snapshot exports are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    // Gzipped newline-delimited JSON.
+    Ndjson,
+    // Uncompressed Parquet, one row group per object.
+    Parquet,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ndjson" => Ok(ExportFormat::Ndjson),
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(format!("unknown export format '{}'", s)),
+        }
+    }
+}
+
+// What write routes do while the RPC node is outside the supported range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RpcCompatMode {
+    // Serve them with an x-rpc-warning header.
+    Warn,
+    // Answer 503 rpc_incompatible.
+    Refuse,
+}
+
+impl FromStr for RpcCompatMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(RpcCompatMode::Warn),
+            "refuse" => Ok(RpcCompatMode::Refuse),
+            _ => Err(format!("unknown RPC compatibility mode '{}'", s)),
+        }
+    }
+}
+
+// How severe an error has to be before it's sent to Sentry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SentryLevel {
+    // Shed load, RPC timeouts and other 503/504s.
+    Warning,
+    // Any other 5xx, RPC failures included.
+    Error,
+    // Panics, in handlers and background tasks.
+    Fatal,
+}
+
+impl FromStr for SentryLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warning" => Ok(SentryLevel::Warning),
+            "error" => Ok(SentryLevel::Error),
+            "fatal" => Ok(SentryLevel::Fatal),
+            _ => Err(format!("unknown Sentry level '{}'", s)),
+        }
+    }
+}
+
+// Most severe first, so a level also lets through everything above it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            _ => Err(format!("unknown log level '{}'", s)),
+        }
+    }
+}
+
+// A program (.so) or account (JSON dump) to load into the local validator
+// at `address`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValidatorPreload {
+    pub address: String,
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub rpc_url: String,
+    pub backend: Backend,
+    // Shared upstream connection pool: concurrent RPC requests are capped at
+    // rpc_pool_max_connections, and up to rpc_pool_idle_per_host idle
+    // connections per node are kept alive for reuse.
+    pub rpc_pool_max_connections: usize,
+    pub rpc_pool_idle_per_host: usize,
+    pub rpc_pool_idle_timeout_secs: u64,
+    // Negotiate HTTP/2 with https:// nodes that support it.
+    pub rpc_http2: bool,
+    // Endpoints that look up many wallets (watchlist overviews, bulk
+    // balances, balance alerts) make at most rpc_fanout_concurrency of
+    // their RPC calls at once, each given rpc_fanout_timeout_secs.
+    pub rpc_fanout_concurrency: usize,
+    pub rpc_fanout_timeout_secs: u64,
+    // Supported solana-core versions of the RPC node, inclusive, e.g.
+    // "1.17.0", and how many slots it may trail the cluster. Checked at
+    // startup and every rpc_version_check_interval_secs.
+    pub rpc_min_version: Option<String>,
+    pub rpc_max_version: Option<String>,
+    pub rpc_max_slot_lag: Option<u64>,
+    pub rpc_version_check_interval_secs: u64,
+    pub rpc_compat_mode: RpcCompatMode,
+    // Node whose slot counts as the cluster's when working out how far
+    // rpc_url lags; its own getHealth, then getMaxRetransmitSlot, when unset.
+    pub rpc_reference_url: Option<String>,
+    pub slot_lag_check_interval_secs: u64,
+    // /health reports degraded while the node lags more than this.
+    pub health_max_slot_lag: Option<u64>,
+    pub port: u16,
+    pub max_airdrop_sol: u64,
+    // Smallest airdrop accepted, so callers can't burn RPC faucet requests
+    // on dust.
+    pub min_airdrop_lamports: u64,
+    // Whole SOL each authenticated caller may airdrop per UTC day.
+    pub airdrop_daily_quota_sol: Option<u64>,
+    // Airdrops above this wait for an admin's approval under
+    // /admin/approvals instead of being sent right away.
+    pub airdrop_approval_threshold_lamports: Option<u64>,
+    // Airdrops are paid from the server keypair with a memo naming a
+    // receipt anyone can check at /receipts/{id}, and treasury-funded
+    // top-ups carry one too. Needs a keypair; without one airdrops keep
+    // coming from the RPC node's faucet.
+    pub airdrop_receipts: bool,
+    // Transfers in flight at once for a POST /admin/airdrop_bulk upload.
+    pub bulk_airdrop_concurrency: usize,
+    // Uploads with more rows are rejected.
+    pub bulk_airdrop_max_rows: usize,
+    // Longest address list POST /balances_bulk accepts.
+    pub bulk_balance_max_addresses: usize,
+    // Most accounts a streamed /programs/{program}/accounts sends, whatever
+    // its `limit`.
+    pub program_accounts_stream_max: usize,
+    // When set, airdrops are refused with this message ("maintenance").
+    pub airdrop_maintenance: Option<String>,
+    // Told to callers refused for maintenance; "later" when unset.
+    pub airdrop_maintenance_retry_after_secs: Option<u64>,
+    // Replaces the message of a denial, keyed by its code (see denials.rs).
+    // Config file only.
+    pub airdrop_denial_messages: HashMap<String, String>,
+    // Approvals are persisted here when set, otherwise kept in memory.
+    pub approvals_path: Option<PathBuf>,
+    // Hosts an approval's callback_url may point at; callbacks are refused
+    // while it's empty, so callers can't aim the server at internal hosts.
+    pub approval_callback_hosts: Vec<String>,
+    // Upstream RPC calls each authenticated caller may cause per UTC day,
+    // with per-subject overrides.
+    pub rpc_daily_budget: Option<u64>,
+    pub rpc_budgets: HashMap<String, u64>,
+    // Empty or containing "*" allows any origin.
+    pub cors_origins: Vec<String>,
+    // Route groups ("faucet", "dev", ...) or single route patterns to
+    // disable or hide; everything else is enabled. Admins can override
+    // these at runtime under /admin/features.
+    pub features: HashMap<String, FeatureState>,
+    // Return bare JSON bodies and `{"error","code"}` errors, as before the
+    // `{"success","data"|"error"}` envelope, for clients not yet migrated.
+    pub legacy_responses: bool,
+    // Accept .sol domains where a path takes a wallet address, resolving
+    // them through the SPL Name Service on every request.
+    pub resolve_sol_domains: bool,
+    // "YYYY-MM-DD" from which renamed request fields are only accepted
+    // under their new names (see `deprecations`); until then the old names
+    // work with a warning header.
+    pub deprecated_fields_cutoff: Option<String>,
+    pub admin_token: Option<String>,
+    pub slo_window_secs: u64,
+    pub slo_min_success_rate: f64,
+    pub slo_p95_latency_ms: u64,
+    pub slo_min_requests: usize,
+    // Read routes get 503 + Retry-After while the p95 of RPC calls over the
+    // last shed_window_secs, or the number of requests in flight, is above
+    // these. Each check is off when unset.
+    pub shed_rpc_p95_latency_ms: Option<u64>,
+    pub shed_max_in_flight: Option<usize>,
+    pub shed_window_secs: u64,
+    pub shed_retry_after_secs: u64,
+    // Read and airdrop requests beyond this many at once wait in a queue
+    // that serves higher priority tiers first; unlimited when unset.
+    pub max_concurrent_requests: Option<usize>,
+    pub max_queued_requests: usize,
+    pub queue_timeout_secs: u64,
+    // Principal subjects (JWT `sub`, SIWS wallet, "admin_token") to tiers.
+    pub priority_tiers: HashMap<String, Priority>,
+    // Each caller (principal subject, else client IP) may make this many
+    // read and airdrop requests a minute, in bursts of up to rate_limit_burst
+    // (default: the per-minute rate). Off when unset.
+    pub rate_limit_per_minute: Option<u32>,
+    pub rate_limit_burst: Option<u32>,
+    pub store_backend: StoreBackend,
+    // The database file for the `sqlite` backend, or the connection string
+    // (postgres:// URL or key=value settings) for `postgres`.
+    pub store_url: Option<String>,
+    // JSON lines audit trail; auditing is off when unset, unless the store
+    // backend is `memory`.
+    pub audit_log_path: Option<PathBuf>,
+    // Background jobs, per-caller quota counters and idempotency keys are
+    // persisted here when set, otherwise kept in memory.
+    pub jobs_path: Option<PathBuf>,
+    pub quotas_path: Option<PathBuf>,
+    pub idempotency_keys_path: Option<PathBuf>,
+    // How long a response is replayed for a repeated `Idempotency-Key`.
+    pub idempotency_ttl_secs: u64,
+    // Enables the /dev/* routes; they also require the admin token.
+    pub dev_mode: bool,
+    // Dev mode only: record HTTP exchanges and RPC calls to this directory,
+    // or answer RPC calls from a previous recording instead of the network.
+    pub record_dir: Option<PathBuf>,
+    pub replay_dir: Option<PathBuf>,
+    // Solana CLI style JSON keypair used to pay for and sign server-side transactions.
+    pub keypair_path: Option<PathBuf>,
+    // Check the RPC node, keypair, treasury and store files before serving,
+    // and exit non-zero if any check fails.
+    pub self_test: bool,
+    // Treasury balance the self-test requires; treasury_low_watermark_sol
+    // when unset.
+    pub self_test_min_treasury_sol: Option<f64>,
+    pub max_program_size: usize,
+    // Default for the per-request `sol_as_string` option.
+    pub sol_as_string: bool,
+    // Default for the per-request `lamports_as_string` option: lamport
+    // amounts in JSON responses as strings rather than numbers.
+    pub lamports_as_string: bool,
+    // Default for the per-request `strict` option: JSON bodies with fields
+    // their endpoint doesn't take are rejected (422) rather than the
+    // fields ignored.
+    pub strict_requests: bool,
+    // BCP 47 tag controlling separators in `format=display` amounts.
+    pub display_locale: String,
+    // The cluster the frontend and /config.json name ("devnet"); worked
+    // out from `rpc_url` when unset.
+    pub cluster_name: Option<String>,
+    // Branding of the frontend at /, read by it from /config.json so one
+    // build serves every deployment. The title defaults to "Solana
+    // <cluster> Airdrop".
+    pub frontend_title: Option<String>,
+    pub frontend_logo_url: Option<String>,
+    pub frontend_theme: FrontendTheme,
+    // A CSS hex color ("#3b82f6") for buttons and links.
+    pub frontend_accent_color: String,
+    // Full-history node used for historical lookups the primary RPC can't serve.
+    pub archival_rpc_url: Option<String>,
+    // DAS (Metaplex Read API) endpoint for compressed NFT lookups; the /cnft
+    // routes are unavailable when unset.
+    pub das_url: Option<String>,
+    // Jupiter swap API base, for /swap/quote and /swap/build.
+    pub jupiter_api_url: String,
+    // Anchor IDL JSON files, one per program, used to decode that program's
+    // accounts and instructions alongside the built-in decoders.
+    pub idl_dir: Option<PathBuf>,
+    // Upper bound on transactions fetched for one wallet summary.
+    pub summary_max_transactions: usize,
+    pub summary_cache_ttl_secs: u64,
+    // Wallets and mints fetched before the server starts listening and
+    // every hot_refresh_secs after, so balance and account lookups of them
+    // (the dashboard's, say) are answered from memory (see hot.rs).
+    pub hot_wallets: Vec<String>,
+    pub hot_mints: Vec<String>,
+    pub hot_refresh_secs: u64,
+    // Addresses refused by the write endpoints, one per line. The file and
+    // URL lists are merged and re-read every denylist_refresh_secs.
+    pub denylist_path: Option<PathBuf>,
+    pub denylist_url: Option<String>,
+    pub denylist_refresh_secs: u64,
+    // When set, airdrops only go to wallets registered via /admin/allowlist.
+    pub allowlist_only: bool,
+    pub allowlist_path: Option<PathBuf>,
+    // When false, read and airdrop routes stay open to anonymous callers.
+    pub require_auth: bool,
+    // JWT bearer auth is on when either key source is set: HS256 tokens are
+    // checked against the shared secret, RS256 tokens against the JWKS.
+    pub jwt_hs256_secret: Option<String>,
+    pub jwt_jwks_url: Option<String>,
+    pub jwt_issuer: Option<String>,
+    pub jwt_audience: Option<String>,
+    pub jwt_roles_claim: String,
+    // Claim values to roles, for tokens that don't use the role names directly.
+    pub jwt_role_mapping: HashMap<String, Role>,
+    // Sign-In-With-Solana. Off by default: anyone can sign in with a wallet
+    // of their own, so sessions only ever carry the reader role.
+    pub siws_enabled: bool,
+    // Domain shown in Sign-In-With-Solana messages.
+    pub siws_domain: String,
+    pub siws_session_ttl_secs: u64,
+    // When set, airdrops need a SIWS session for the receiving wallet.
+    pub siws_required: bool,
+    // Slack or Discord incoming webhook URLs; notifications are off when empty.
+    pub notify_webhooks: Vec<String>,
+    pub notify_interval_secs: u64,
+    // Minimum gap between two alerts of the same kind.
+    pub notify_cooldown_secs: u64,
+    // Alert when the server keypair's balance drops below this.
+    pub notify_treasury_min_sol: Option<f64>,
+    pub notify_airdrop_max_failure_rate: f64,
+    // How often the wallets under /admin/alerts are checked.
+    pub balance_alert_interval_secs: u64,
+    // The longest `wait_for_change` a /wallet/{pubkey}/balance request can
+    // ask for, and how many such requests can wait at once.
+    pub balance_wait_max_secs: u64,
+    pub balance_wait_max_waiters: usize,
+    // How many /ws/events/{program_id} clients can be connected at once;
+    // each holds a logs subscription on the node.
+    pub max_event_streams: usize,
+    // Starts the Telegram bot; needs a build with the `telegram` feature.
+    pub telegram_bot_token: Option<String>,
+    pub telegram_api_url: String,
+    // Server errors and panics are reported to this Sentry project; needs
+    // a build with the `sentry` feature.
+    pub sentry_dsn: Option<String>,
+    // Sentry's "production" when unset.
+    pub sentry_environment: Option<String>,
+    pub sentry_min_level: SentryLevel,
+    // Server log lines below this level are dropped; reloadable.
+    pub log_level: LogLevel,
+    // Registered CI wallets (and their last run) are persisted here when set.
+    pub topups_path: Option<PathBuf>,
+    pub topup_interval_secs: u64,
+    // Largest target balance a registration may ask for.
+    pub topup_max_target_sol: f64,
+    // Extra keypairs /admin/treasury/sweep consolidates back into the
+    // server keypair.
+    pub treasury_aux_keypairs: Vec<PathBuf>,
+    // Below this the server keypair only serves admins' writes.
+    pub treasury_low_watermark_sol: Option<f64>,
+    pub treasury_check_interval_secs: u64,
+    // Sinks for the event bus (see events.rs); /stream/events is always on.
+    // Each event is POSTed as JSON to every webhook.
+    pub event_webhooks: Vec<String>,
+    // nats://host:port; events go to `<subject>.<kind>`.
+    pub event_nats_url: Option<String>,
+    pub event_nats_subject: String,
+    // A Kafka REST Proxy (v2 API) that produces to `event_kafka_topic`.
+    pub event_kafka_rest_url: Option<String>,
+    pub event_kafka_topic: String,
+    // Programs whose transactions (and their logs) are published as events.
+    pub event_programs: Vec<String>,
+    pub event_poll_interval_secs: u64,
+    // Programs whose accounts are indexed by owner (see index.rs), as
+    // `program` or `program:offset`, the offset of the owner's pubkey in
+    // the account data (32, the SPL token account layout, by default).
+    pub index_programs: Vec<String>,
+    // The websocket endpoint for the index's program subscriptions, balance
+    // waits and /ws/events; `rpc_url` with a ws:// or wss:// scheme when
+    // unset.
+    pub index_ws_url: Option<String>,
+    // How often each index is rebuilt from getProgramAccounts, catching
+    // anything the subscriptions missed.
+    pub index_reconcile_secs: u64,
+    // Watchlist wallets get their transaction history backfilled and kept
+    // current locally (see tx_history.rs), for /wallet/{pubkey}/transactions.
+    pub tx_history: bool,
+    pub tx_history_poll_secs: u64,
+    // Transactions fetched per wallet per poll; each costs an RPC call.
+    pub tx_history_batch: usize,
+    // How far back a wallet is backfilled, in transactions.
+    pub tx_history_max_backfill: usize,
+    // Snapshot exports (see export.rs) go to this bucket; off when unset.
+    // Any S3-compatible endpoint works, GCS included (with HMAC keys and
+    // https://storage.googleapis.com).
+    pub export_bucket: Option<String>,
+    pub export_endpoint: String,
+    pub export_region: String,
+    // Each export is written under `<prefix><timestamp>/`.
+    pub export_prefix: String,
+    pub export_format: ExportFormat,
+    pub export_access_key_id: Option<String>,
+    pub export_secret_access_key: Option<String>,
+    // Exports only run on demand (POST /admin/export) when unset.
+    pub export_interval_secs: Option<u64>,
+    // Watchlists are persisted here when set, otherwise kept in memory.
+    pub watchlists_path: Option<PathBuf>,
+    // Orgs and their API keys are persisted here when set, otherwise kept
+    // in memory.
+    pub orgs_path: Option<PathBuf>,
+    // Salted hashes of the API keys issued through /admin/keys are
+    // persisted here when set, otherwise kept in memory.
+    pub api_keys_path: Option<PathBuf>,
+    // Address labels are persisted here when set, otherwise kept in memory.
+    pub labels_path: Option<PathBuf>,
+    // Program names added through /admin/programs are persisted here when
+    // set, otherwise kept in memory.
+    pub programs_path: Option<PathBuf>,
+    // IDLs uploaded through /idl are persisted here when set, otherwise
+    // kept in memory.
+    pub idls_path: Option<PathBuf>,
+    // Indexed watchlist transactions are appended here when set, otherwise
+    // kept in memory.
+    pub tx_history_path: Option<PathBuf>,
+    // Balance alerts added through /admin/alerts are persisted here when
+    // set, otherwise kept in memory.
+    pub balance_alerts_path: Option<PathBuf>,
+    // A JSON file of labels merged into the registry at startup: a list of
+    // {address, label, tags} or an object of address -> label.
+    pub labels_import_path: Option<PathBuf>,
+    // Local `solana-test-validator` managed through /dev/validator.
+    pub validator_bin: String,
+    pub validator_ledger_dir: PathBuf,
+    pub validator_rpc_port: u16,
+    pub validator_startup_timeout_secs: u64,
+    pub validator_programs: Vec<ValidatorPreload>,
+    pub validator_accounts: Vec<ValidatorPreload>,
+    // Account fixtures (JSON dumps) loaded with --account-dir; also where
+    // POST /dev/fixtures stores uploads.
+    pub validator_fixtures_dir: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            rpc_url: DEFAULT_RPC_URL.to_string(),
+            backend: Backend::Rpc,
+            rpc_pool_max_connections: DEFAULT_RPC_POOL_MAX_CONNECTIONS,
+            rpc_pool_idle_per_host: DEFAULT_RPC_POOL_IDLE_PER_HOST,
+            rpc_pool_idle_timeout_secs: DEFAULT_RPC_POOL_IDLE_TIMEOUT_SECS,
+            rpc_http2: true,
+            rpc_fanout_concurrency: DEFAULT_RPC_FANOUT_CONCURRENCY,
+            rpc_fanout_timeout_secs: DEFAULT_RPC_FANOUT_TIMEOUT_SECS,
+            rpc_min_version: None,
+            rpc_max_version: None,
+            rpc_max_slot_lag: None,
+            rpc_version_check_interval_secs: DEFAULT_RPC_VERSION_CHECK_INTERVAL_SECS,
+            rpc_compat_mode: RpcCompatMode::Warn,
+            rpc_reference_url: None,
+            slot_lag_check_interval_secs: DEFAULT_SLOT_LAG_CHECK_INTERVAL_SECS,
+            health_max_slot_lag: None,
+            port: DEFAULT_PORT,
+            max_airdrop_sol: DEFAULT_MAX_AIRDROP_SOL,
+            min_airdrop_lamports: DEFAULT_MIN_AIRDROP_LAMPORTS,
+            airdrop_daily_quota_sol: None,
+            airdrop_approval_threshold_lamports: None,
+            airdrop_receipts: false,
+            bulk_airdrop_concurrency: DEFAULT_BULK_AIRDROP_CONCURRENCY,
+            bulk_airdrop_max_rows: DEFAULT_BULK_AIRDROP_MAX_ROWS,
+            bulk_balance_max_addresses: DEFAULT_BULK_BALANCE_MAX_ADDRESSES,
+            program_accounts_stream_max: DEFAULT_PROGRAM_ACCOUNTS_STREAM_MAX,
+            airdrop_maintenance: None,
+            airdrop_maintenance_retry_after_secs: None,
+            airdrop_denial_messages: HashMap::new(),
+            approvals_path: None,
+            approval_callback_hosts: Vec::new(),
+            rpc_daily_budget: None,
+            rpc_budgets: HashMap::new(),
+            cors_origins: Vec::new(),
+            features: HashMap::new(),
+            legacy_responses: false,
+            resolve_sol_domains: false,
+            deprecated_fields_cutoff: None,
+            admin_token: None,
+            slo_window_secs: DEFAULT_SLO_WINDOW_SECS,
+            slo_min_success_rate: DEFAULT_SLO_MIN_SUCCESS_RATE,
+            slo_p95_latency_ms: DEFAULT_SLO_P95_LATENCY_MS,
+            slo_min_requests: DEFAULT_SLO_MIN_REQUESTS,
+            shed_rpc_p95_latency_ms: None,
+            shed_max_in_flight: None,
+            shed_window_secs: DEFAULT_SHED_WINDOW_SECS,
+            shed_retry_after_secs: DEFAULT_SHED_RETRY_AFTER_SECS,
+            max_concurrent_requests: None,
+            max_queued_requests: DEFAULT_MAX_QUEUED_REQUESTS,
+            queue_timeout_secs: DEFAULT_QUEUE_TIMEOUT_SECS,
+            priority_tiers: HashMap::new(),
+            rate_limit_per_minute: None,
+            rate_limit_burst: None,
+            store_backend: StoreBackend::File,
+            store_url: None,
+            audit_log_path: None,
+            jobs_path: None,
+            quotas_path: None,
+            idempotency_keys_path: None,
+            idempotency_ttl_secs: DEFAULT_IDEMPOTENCY_TTL_SECS,
+            dev_mode: false,
+            record_dir: None,
+            replay_dir: None,
+            keypair_path: None,
+            self_test: false,
+            self_test_min_treasury_sol: None,
+            max_program_size: DEFAULT_MAX_PROGRAM_SIZE,
+            sol_as_string: false,
+            lamports_as_string: false,
+            strict_requests: false,
+            display_locale: DEFAULT_DISPLAY_LOCALE.to_string(),
+            cluster_name: None,
+            frontend_title: None,
+            frontend_logo_url: None,
+            frontend_theme: FrontendTheme::Dark,
+            frontend_accent_color: DEFAULT_FRONTEND_ACCENT_COLOR.to_string(),
+            archival_rpc_url: None,
+            das_url: None,
+            jupiter_api_url: DEFAULT_JUPITER_API_URL.to_string(),
+            idl_dir: None,
+            summary_max_transactions: DEFAULT_SUMMARY_MAX_TRANSACTIONS,
+            summary_cache_ttl_secs: DEFAULT_SUMMARY_CACHE_TTL_SECS,
+            hot_wallets: Vec::new(),
+            hot_mints: Vec::new(),
+            hot_refresh_secs: DEFAULT_HOT_REFRESH_SECS,
+            denylist_path: None,
+            denylist_url: None,
+            denylist_refresh_secs: DEFAULT_DENYLIST_REFRESH_SECS,
+            allowlist_only: false,
+            allowlist_path: None,
+            require_auth: false,
+            jwt_hs256_secret: None,
+            jwt_jwks_url: None,
+            jwt_issuer: None,
+            jwt_audience: None,
+            jwt_roles_claim: DEFAULT_JWT_ROLES_CLAIM.to_string(),
+            jwt_role_mapping: HashMap::new(),
+            siws_enabled: false,
+            siws_domain: DEFAULT_SIWS_DOMAIN.to_string(),
+            siws_session_ttl_secs: DEFAULT_SIWS_SESSION_TTL_SECS,
+            siws_required: false,
+            notify_webhooks: Vec::new(),
+            notify_interval_secs: DEFAULT_NOTIFY_INTERVAL_SECS,
+            notify_cooldown_secs: DEFAULT_NOTIFY_COOLDOWN_SECS,
+            notify_treasury_min_sol: None,
+            notify_airdrop_max_failure_rate: DEFAULT_NOTIFY_AIRDROP_MAX_FAILURE_RATE,
+            balance_alert_interval_secs: DEFAULT_BALANCE_ALERT_INTERVAL_SECS,
+            balance_wait_max_secs: DEFAULT_BALANCE_WAIT_MAX_SECS,
+            balance_wait_max_waiters: DEFAULT_BALANCE_WAIT_MAX_WAITERS,
+            max_event_streams: DEFAULT_MAX_EVENT_STREAMS,
+            telegram_bot_token: None,
+            telegram_api_url: DEFAULT_TELEGRAM_API_URL.to_string(),
+            sentry_dsn: None,
+            sentry_environment: None,
+            sentry_min_level: SentryLevel::Error,
+            log_level: LogLevel::Info,
+            topups_path: None,
+            topup_interval_secs: DEFAULT_TOPUP_INTERVAL_SECS,
+            treasury_aux_keypairs: Vec::new(),
+            treasury_low_watermark_sol: None,
+            treasury_check_interval_secs: DEFAULT_TREASURY_CHECK_INTERVAL_SECS,
+            event_webhooks: Vec::new(),
+            event_nats_url: None,
+            event_nats_subject: DEFAULT_EVENT_NATS_SUBJECT.to_string(),
+            event_kafka_rest_url: None,
+            event_kafka_topic: DEFAULT_EVENT_KAFKA_TOPIC.to_string(),
+            event_programs: Vec::new(),
+            event_poll_interval_secs: DEFAULT_EVENT_POLL_INTERVAL_SECS,
+            index_programs: Vec::new(),
+            index_ws_url: None,
+            index_reconcile_secs: DEFAULT_INDEX_RECONCILE_SECS,
+            tx_history: false,
+            tx_history_poll_secs: DEFAULT_TX_HISTORY_POLL_SECS,
+            tx_history_batch: DEFAULT_TX_HISTORY_BATCH,
+            tx_history_max_backfill: DEFAULT_TX_HISTORY_MAX_BACKFILL,
+            export_bucket: None,
+            export_endpoint: DEFAULT_EXPORT_ENDPOINT.to_string(),
+            export_region: DEFAULT_EXPORT_REGION.to_string(),
+            export_prefix: DEFAULT_EXPORT_PREFIX.to_string(),
+            export_format: ExportFormat::Ndjson,
+            export_access_key_id: None,
+            export_secret_access_key: None,
+            export_interval_secs: None,
+            topup_max_target_sol: DEFAULT_TOPUP_MAX_TARGET_SOL,
+            watchlists_path: None,
+            orgs_path: None,
+            api_keys_path: None,
+            labels_path: None,
+            labels_import_path: None,
+            programs_path: None,
+            idls_path: None,
+            tx_history_path: None,
+            balance_alerts_path: None,
+            validator_bin: DEFAULT_VALIDATOR_BIN.to_string(),
+            validator_ledger_dir: PathBuf::from(DEFAULT_VALIDATOR_LEDGER_DIR),
+            validator_rpc_port: DEFAULT_VALIDATOR_RPC_PORT,
+            validator_startup_timeout_secs: DEFAULT_VALIDATOR_STARTUP_TIMEOUT_SECS,
+            validator_programs: Vec::new(),
+            validator_accounts: Vec::new(),
+            validator_fixtures_dir: None,
+        }
+    }
+}
+
+impl Config {
+    // Reads CONFIG_FILE (TOML) when set, then applies environment overrides.
+    pub fn load() -> Result<Config, String> {
+        let mut config = match env::var("CONFIG_FILE") {
+            Ok(path) => {
+                let contents = fs::read_to_string(&path)
+                    .map_err(|e| format!("failed to read config file '{}': {}", path, e))?;
+                toml::from_str(&contents)
+                    .map_err(|e| format!("invalid config file '{}': {}", path, e))?
+            }
+            Err(_) => Config::default(),
+        };
+
+        env_parse("BACKEND", &mut config.backend)?;
+        if let Ok(rpc_url) = env::var("RPC_URL") {
+            config.rpc_url = rpc_url;
+        }
+        env_parse(
+            "RPC_POOL_MAX_CONNECTIONS",
+            &mut config.rpc_pool_max_connections,
+        )?;
+        env_parse("RPC_POOL_IDLE_PER_HOST", &mut config.rpc_pool_idle_per_host)?;
+        env_parse(
+            "RPC_POOL_IDLE_TIMEOUT_SECS",
+            &mut config.rpc_pool_idle_timeout_secs,
+        )?;
+        env_parse("RPC_HTTP2", &mut config.rpc_http2)?;
+        env_parse("RPC_FANOUT_CONCURRENCY", &mut config.rpc_fanout_concurrency)?;
+        env_parse(
+            "RPC_FANOUT_TIMEOUT_SECS",
+            &mut config.rpc_fanout_timeout_secs,
+        )?;
+        if let Ok(version) = env::var("RPC_MIN_VERSION") {
+            config.rpc_min_version = Some(version);
+        }
+        if let Ok(version) = env::var("RPC_MAX_VERSION") {
+            config.rpc_max_version = Some(version);
+        }
+        if let Ok(lag) = env::var("RPC_MAX_SLOT_LAG") {
+            config.rpc_max_slot_lag = Some(
+                lag.parse()
+                    .map_err(|_| format!("RPC_MAX_SLOT_LAG has an invalid value '{}'", lag))?,
+            );
+        }
+        env_parse(
+            "RPC_VERSION_CHECK_INTERVAL_SECS",
+            &mut config.rpc_version_check_interval_secs,
+        )?;
+        env_parse("RPC_COMPAT_MODE", &mut config.rpc_compat_mode)?;
+        if let Ok(url) = env::var("RPC_REFERENCE_URL") {
+            config.rpc_reference_url = Some(url);
+        }
+        env_parse(
+            "SLOT_LAG_CHECK_INTERVAL_SECS",
+            &mut config.slot_lag_check_interval_secs,
+        )?;
+        if let Ok(lag) = env::var("HEALTH_MAX_SLOT_LAG") {
+            config.health_max_slot_lag = Some(
+                lag.parse()
+                    .map_err(|_| format!("HEALTH_MAX_SLOT_LAG has an invalid value '{}'", lag))?,
+            );
+        }
+        env_parse("PORT", &mut config.port)?;
+        env_parse("MAX_AIRDROP_SOL", &mut config.max_airdrop_sol)?;
+        env_parse("MIN_AIRDROP_LAMPORTS", &mut config.min_airdrop_lamports)?;
+        if let Ok(quota) = env::var("AIRDROP_DAILY_QUOTA_SOL") {
+            config.airdrop_daily_quota_sol = Some(quota.parse().map_err(|_| {
+                format!("AIRDROP_DAILY_QUOTA_SOL has an invalid value '{}'", quota)
+            })?);
+        }
+        if let Ok(threshold) = env::var("AIRDROP_APPROVAL_THRESHOLD_LAMPORTS") {
+            config.airdrop_approval_threshold_lamports = Some(threshold.parse().map_err(|_| {
+                format!(
+                    "AIRDROP_APPROVAL_THRESHOLD_LAMPORTS has an invalid value '{}'",
+                    threshold
+                )
+            })?);
+        }
+        env_parse("AIRDROP_RECEIPTS", &mut config.airdrop_receipts)?;
+        env_parse(
+            "BULK_AIRDROP_CONCURRENCY",
+            &mut config.bulk_airdrop_concurrency,
+        )?;
+        env_parse("BULK_AIRDROP_MAX_ROWS", &mut config.bulk_airdrop_max_rows)?;
+        env_parse(
+            "BULK_BALANCE_MAX_ADDRESSES",
+            &mut config.bulk_balance_max_addresses,
+        )?;
+        env_parse(
+            "PROGRAM_ACCOUNTS_STREAM_MAX",
+            &mut config.program_accounts_stream_max,
+        )?;
+        if let Ok(message) = env::var("AIRDROP_MAINTENANCE") {
+            config.airdrop_maintenance = Some(message);
+        }
+        if let Ok(secs) = env::var("AIRDROP_MAINTENANCE_RETRY_AFTER_SECS") {
+            config.airdrop_maintenance_retry_after_secs = Some(secs.parse().map_err(|_| {
+                format!(
+                    "AIRDROP_MAINTENANCE_RETRY_AFTER_SECS has an invalid value '{}'",
+                    secs
+                )
+            })?);
+        }
+        if let Ok(path) = env::var("APPROVALS_PATH") {
+            config.approvals_path = Some(PathBuf::from(path));
+        }
+        if let Ok(hosts) = env::var("APPROVAL_CALLBACK_HOSTS") {
+            config.approval_callback_hosts = hosts
+                .split(',')
+                .map(|h| h.trim().to_ascii_lowercase())
+                .filter(|h| !h.is_empty())
+                .collect();
+        }
+        if let Ok(budget) = env::var("RPC_DAILY_BUDGET") {
+            config.rpc_daily_budget = Some(
+                budget
+                    .parse()
+                    .map_err(|_| format!("RPC_DAILY_BUDGET has an invalid value '{}'", budget))?,
+            );
+        }
+        // e.g. RPC_BUDGETS="bulk-indexer=50000,monitoring=1000"
+        if let Ok(budgets) = env::var("RPC_BUDGETS") {
+            config.rpc_budgets = budgets
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|entry| {
+                    let (subject, budget) = entry.split_once('=').ok_or_else(|| {
+                        format!("RPC_BUDGETS entry '{}' must be subject=calls", entry)
+                    })?;
+                    let budget = budget.trim().parse().map_err(|_| {
+                        format!("RPC_BUDGETS entry '{}' has an invalid budget", entry)
+                    })?;
+                    Ok((subject.trim().to_string(), budget))
+                })
+                .collect::<Result<_, String>>()?;
+        }
+        if let Ok(origins) = env::var("CORS_ORIGINS") {
+            config.cors_origins = origins
+                .split(',')
+                .map(|o| o.trim().to_string())
+                .filter(|o| !o.is_empty())
+                .collect();
+        }
+        // e.g. FEATURES="dev=hidden,transfers=disabled,/swap/quote=disabled"
+        if let Ok(features) = env::var("FEATURES") {
+            config.features = features
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|entry| {
+                    let (name, state) = entry.split_once('=').ok_or_else(|| {
+                        format!("FEATURES entry '{}' must be feature=state", entry)
+                    })?;
+                    Ok((name.trim().to_string(), state.trim().parse()?))
+                })
+                .collect::<Result<_, String>>()?;
+        }
+        env_parse("LEGACY_RESPONSES", &mut config.legacy_responses)?;
+        env_parse("RESOLVE_SOL_DOMAINS", &mut config.resolve_sol_domains)?;
+        if let Ok(date) = env::var("DEPRECATED_FIELDS_CUTOFF") {
+            config.deprecated_fields_cutoff = Some(date);
+        }
+        if let Ok(token) = env::var("ADMIN_TOKEN") {
+            config.admin_token = Some(token);
+        }
+
+        env_parse("SLO_WINDOW_SECS", &mut config.slo_window_secs)?;
+        env_parse("SLO_MIN_SUCCESS_RATE", &mut config.slo_min_success_rate)?;
+        env_parse("SLO_P95_LATENCY_MS", &mut config.slo_p95_latency_ms)?;
+        env_parse("SLO_MIN_REQUESTS", &mut config.slo_min_requests)?;
+        if let Ok(latency) = env::var("SHED_RPC_P95_LATENCY_MS") {
+            config.shed_rpc_p95_latency_ms = Some(latency.parse().map_err(|_| {
+                format!("SHED_RPC_P95_LATENCY_MS has an invalid value '{}'", latency)
+            })?);
+        }
+        if let Ok(max) = env::var("SHED_MAX_IN_FLIGHT") {
+            config.shed_max_in_flight = Some(
+                max.parse()
+                    .map_err(|_| format!("SHED_MAX_IN_FLIGHT has an invalid value '{}'", max))?,
+            );
+        }
+        env_parse("SHED_WINDOW_SECS", &mut config.shed_window_secs)?;
+        env_parse("SHED_RETRY_AFTER_SECS", &mut config.shed_retry_after_secs)?;
+        if let Ok(max) = env::var("MAX_CONCURRENT_REQUESTS") {
+            config.max_concurrent_requests =
+                Some(max.parse().map_err(|_| {
+                    format!("MAX_CONCURRENT_REQUESTS has an invalid value '{}'", max)
+                })?);
+        }
+        env_parse("MAX_QUEUED_REQUESTS", &mut config.max_queued_requests)?;
+        env_parse("QUEUE_TIMEOUT_SECS", &mut config.queue_timeout_secs)?;
+        // e.g. PRIORITY_TIERS="monitoring=high,bulk-indexer=low"
+        if let Ok(tiers) = env::var("PRIORITY_TIERS") {
+            config.priority_tiers = tiers
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|entry| {
+                    let (subject, tier) = entry.split_once('=').ok_or_else(|| {
+                        format!("PRIORITY_TIERS entry '{}' must be subject=tier", entry)
+                    })?;
+                    Ok((subject.trim().to_string(), tier.trim().parse()?))
+                })
+                .collect::<Result<_, String>>()?;
+        }
+        if let Ok(rate) = env::var("RATE_LIMIT_PER_MINUTE") {
+            config.rate_limit_per_minute =
+                Some(rate.parse().map_err(|_| {
+                    format!("RATE_LIMIT_PER_MINUTE has an invalid value '{}'", rate)
+                })?);
+        }
+        if let Ok(burst) = env::var("RATE_LIMIT_BURST") {
+            config.rate_limit_burst = Some(
+                burst
+                    .parse()
+                    .map_err(|_| format!("RATE_LIMIT_BURST has an invalid value '{}'", burst))?,
+            );
+        }
+        env_parse("STORE_BACKEND", &mut config.store_backend)?;
+        if let Ok(url) = env::var("STORE_URL") {
+            config.store_url = Some(url);
+        }
+        if let Ok(path) = env::var("AUDIT_LOG_PATH") {
+            config.audit_log_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("JOBS_PATH") {
+            config.jobs_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("QUOTAS_PATH") {
+            config.quotas_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("IDEMPOTENCY_KEYS_PATH") {
+            config.idempotency_keys_path = Some(PathBuf::from(path));
+        }
+        env_parse("IDEMPOTENCY_TTL_SECS", &mut config.idempotency_ttl_secs)?;
+        env_parse("DEV_MODE", &mut config.dev_mode)?;
+        if let Ok(dir) = env::var("RECORD_DIR") {
+            config.record_dir = Some(PathBuf::from(dir));
+        }
+        if let Ok(dir) = env::var("REPLAY_DIR") {
+            config.replay_dir = Some(PathBuf::from(dir));
+        }
+        if let Ok(path) = env::var("KEYPAIR_PATH") {
+            config.keypair_path = Some(PathBuf::from(path));
+        }
+        env_parse("SELF_TEST", &mut config.self_test)?;
+        if let Ok(min_sol) = env::var("SELF_TEST_MIN_TREASURY_SOL") {
+            config.self_test_min_treasury_sol = Some(min_sol.parse().map_err(|_| {
+                format!(
+                    "SELF_TEST_MIN_TREASURY_SOL has an invalid value '{}'",
+                    min_sol
+                )
+            })?);
+        }
+        env_parse("MAX_PROGRAM_SIZE", &mut config.max_program_size)?;
+        env_parse("SOL_AS_STRING", &mut config.sol_as_string)?;
+        env_parse("LAMPORTS_AS_STRING", &mut config.lamports_as_string)?;
+        env_parse("STRICT_REQUESTS", &mut config.strict_requests)?;
+        if let Ok(locale) = env::var("DISPLAY_LOCALE") {
+            config.display_locale = locale;
+        }
+        if let Ok(name) = env::var("CLUSTER_NAME") {
+            config.cluster_name = Some(name);
+        }
+        if let Ok(title) = env::var("FRONTEND_TITLE") {
+            config.frontend_title = Some(title);
+        }
+        if let Ok(url) = env::var("FRONTEND_LOGO_URL") {
+            config.frontend_logo_url = Some(url);
+        }
+        env_parse("FRONTEND_THEME", &mut config.frontend_theme)?;
+        if let Ok(color) = env::var("FRONTEND_ACCENT_COLOR") {
+            config.frontend_accent_color = color;
+        }
+        if let Ok(url) = env::var("ARCHIVAL_RPC_URL") {
+            config.archival_rpc_url = Some(url);
+        }
+        if let Ok(url) = env::var("DAS_URL") {
+            config.das_url = Some(url);
+        }
+        if let Ok(url) = env::var("JUPITER_API_URL") {
+            config.jupiter_api_url = url;
+        }
+        if let Ok(dir) = env::var("IDL_DIR") {
+            config.idl_dir = Some(PathBuf::from(dir));
+        }
+        env_parse(
+            "SUMMARY_MAX_TRANSACTIONS",
+            &mut config.summary_max_transactions,
+        )?;
+        env_parse("SUMMARY_CACHE_TTL_SECS", &mut config.summary_cache_ttl_secs)?;
+        for (name, list) in [
+            ("HOT_WALLETS", &mut config.hot_wallets),
+            ("HOT_MINTS", &mut config.hot_mints),
+        ] {
+            if let Ok(addresses) = env::var(name) {
+                *list = addresses
+                    .split(',')
+                    .map(|a| a.trim().to_string())
+                    .filter(|a| !a.is_empty())
+                    .collect();
+            }
+        }
+        env_parse("HOT_REFRESH_SECS", &mut config.hot_refresh_secs)?;
+        if let Ok(path) = env::var("DENYLIST_PATH") {
+            config.denylist_path = Some(PathBuf::from(path));
+        }
+        if let Ok(url) = env::var("DENYLIST_URL") {
+            config.denylist_url = Some(url);
+        }
+        env_parse("DENYLIST_REFRESH_SECS", &mut config.denylist_refresh_secs)?;
+        env_parse("ALLOWLIST_ONLY", &mut config.allowlist_only)?;
+        if let Ok(path) = env::var("ALLOWLIST_PATH") {
+            config.allowlist_path = Some(PathBuf::from(path));
+        }
+        env_parse("REQUIRE_AUTH", &mut config.require_auth)?;
+        if let Ok(secret) = env::var("JWT_HS256_SECRET") {
+            config.jwt_hs256_secret = Some(secret);
+        }
+        if let Ok(url) = env::var("JWT_JWKS_URL") {
+            config.jwt_jwks_url = Some(url);
+        }
+        if let Ok(issuer) = env::var("JWT_ISSUER") {
+            config.jwt_issuer = Some(issuer);
+        }
+        if let Ok(audience) = env::var("JWT_AUDIENCE") {
+            config.jwt_audience = Some(audience);
+        }
+        if let Ok(claim) = env::var("JWT_ROLES_CLAIM") {
+            config.jwt_roles_claim = claim;
+        }
+        // e.g. JWT_ROLE_MAPPING="faucet:write=funder,ops=admin"
+        if let Ok(mapping) = env::var("JWT_ROLE_MAPPING") {
+            config.jwt_role_mapping = mapping
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|entry| {
+                    let (claim, role) = entry.split_once('=').ok_or_else(|| {
+                        format!("JWT_ROLE_MAPPING entry '{}' must be claim=role", entry)
+                    })?;
+                    Ok((claim.trim().to_string(), role.trim().parse()?))
+                })
+                .collect::<Result<_, String>>()?;
+        }
+        env_parse("SIWS_ENABLED", &mut config.siws_enabled)?;
+        if let Ok(domain) = env::var("SIWS_DOMAIN") {
+            config.siws_domain = domain;
+        }
+        env_parse("SIWS_SESSION_TTL_SECS", &mut config.siws_session_ttl_secs)?;
+        env_parse("SIWS_REQUIRED", &mut config.siws_required)?;
+        if let Ok(urls) = env::var("NOTIFY_WEBHOOKS") {
+            config.notify_webhooks = urls
+                .split(',')
+                .map(|u| u.trim().to_string())
+                .filter(|u| !u.is_empty())
+                .collect();
+        }
+        env_parse("NOTIFY_INTERVAL_SECS", &mut config.notify_interval_secs)?;
+        env_parse("NOTIFY_COOLDOWN_SECS", &mut config.notify_cooldown_secs)?;
+        if let Ok(min_sol) = env::var("NOTIFY_TREASURY_MIN_SOL") {
+            config.notify_treasury_min_sol = Some(min_sol.parse().map_err(|_| {
+                format!("NOTIFY_TREASURY_MIN_SOL has an invalid value '{}'", min_sol)
+            })?);
+        }
+        env_parse(
+            "NOTIFY_AIRDROP_MAX_FAILURE_RATE",
+            &mut config.notify_airdrop_max_failure_rate,
+        )?;
+        if let Ok(token) = env::var("TELEGRAM_BOT_TOKEN") {
+            config.telegram_bot_token = Some(token);
+        }
+        if let Ok(url) = env::var("TELEGRAM_API_URL") {
+            config.telegram_api_url = url;
+        }
+        if let Ok(dsn) = env::var("SENTRY_DSN") {
+            config.sentry_dsn = Some(dsn);
+        }
+        if let Ok(environment) = env::var("SENTRY_ENVIRONMENT") {
+            config.sentry_environment = Some(environment);
+        }
+        env_parse("SENTRY_MIN_LEVEL", &mut config.sentry_min_level)?;
+        env_parse("LOG_LEVEL", &mut config.log_level)?;
+        if let Ok(path) = env::var("TOPUPS_PATH") {
+            config.topups_path = Some(PathBuf::from(path));
+        }
+        env_parse("TOPUP_INTERVAL_SECS", &mut config.topup_interval_secs)?;
+        env_parse(
+            "BALANCE_ALERT_INTERVAL_SECS",
+            &mut config.balance_alert_interval_secs,
+        )?;
+        env_parse("BALANCE_WAIT_MAX_SECS", &mut config.balance_wait_max_secs)?;
+        env_parse(
+            "BALANCE_WAIT_MAX_WAITERS",
+            &mut config.balance_wait_max_waiters,
+        )?;
+        env_parse("MAX_EVENT_STREAMS", &mut config.max_event_streams)?;
+        env_parse("TOPUP_MAX_TARGET_SOL", &mut config.topup_max_target_sol)?;
+        if let Ok(paths) = env::var("TREASURY_AUX_KEYPAIRS") {
+            config.treasury_aux_keypairs = paths
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from)
+                .collect();
+        }
+        if let Ok(min_sol) = env::var("TREASURY_LOW_WATERMARK_SOL") {
+            config.treasury_low_watermark_sol = Some(min_sol.parse().map_err(|_| {
+                format!(
+                    "TREASURY_LOW_WATERMARK_SOL has an invalid value '{}'",
+                    min_sol
+                )
+            })?);
+        }
+        env_parse(
+            "TREASURY_CHECK_INTERVAL_SECS",
+            &mut config.treasury_check_interval_secs,
+        )?;
+        if let Ok(urls) = env::var("EVENT_WEBHOOKS") {
+            config.event_webhooks = urls
+                .split(',')
+                .map(|u| u.trim().to_string())
+                .filter(|u| !u.is_empty())
+                .collect();
+        }
+        if let Ok(url) = env::var("EVENT_NATS_URL") {
+            config.event_nats_url = Some(url);
+        }
+        if let Ok(subject) = env::var("EVENT_NATS_SUBJECT") {
+            config.event_nats_subject = subject;
+        }
+        if let Ok(url) = env::var("EVENT_KAFKA_REST_URL") {
+            config.event_kafka_rest_url = Some(url);
+        }
+        if let Ok(topic) = env::var("EVENT_KAFKA_TOPIC") {
+            config.event_kafka_topic = topic;
+        }
+        if let Ok(programs) = env::var("EVENT_PROGRAMS") {
+            config.event_programs = programs
+                .split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect();
+        }
+        env_parse(
+            "EVENT_POLL_INTERVAL_SECS",
+            &mut config.event_poll_interval_secs,
+        )?;
+        if let Ok(programs) = env::var("INDEX_PROGRAMS") {
+            config.index_programs = programs
+                .split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect();
+        }
+        if let Ok(url) = env::var("INDEX_WS_URL") {
+            config.index_ws_url = Some(url);
+        }
+        env_parse("INDEX_RECONCILE_SECS", &mut config.index_reconcile_secs)?;
+        env_parse("TX_HISTORY", &mut config.tx_history)?;
+        env_parse("TX_HISTORY_POLL_SECS", &mut config.tx_history_poll_secs)?;
+        env_parse("TX_HISTORY_BATCH", &mut config.tx_history_batch)?;
+        env_parse(
+            "TX_HISTORY_MAX_BACKFILL",
+            &mut config.tx_history_max_backfill,
+        )?;
+        if let Ok(bucket) = env::var("EXPORT_BUCKET") {
+            config.export_bucket = Some(bucket);
+        }
+        if let Ok(url) = env::var("EXPORT_ENDPOINT") {
+            config.export_endpoint = url;
+        }
+        if let Ok(region) = env::var("EXPORT_REGION") {
+            config.export_region = region;
+        }
+        if let Ok(prefix) = env::var("EXPORT_PREFIX") {
+            config.export_prefix = prefix;
+        }
+        env_parse("EXPORT_FORMAT", &mut config.export_format)?;
+        // The standard AWS variables, so existing credentials just work.
+        if let Ok(key) = env::var("AWS_ACCESS_KEY_ID") {
+            config.export_access_key_id = Some(key);
+        }
+        if let Ok(secret) = env::var("AWS_SECRET_ACCESS_KEY") {
+            config.export_secret_access_key = Some(secret);
+        }
+        if let Ok(secs) = env::var("EXPORT_INTERVAL_SECS") {
+            config.export_interval_secs =
+                Some(secs.parse().map_err(|_| {
+                    format!("EXPORT_INTERVAL_SECS has an invalid value '{}'", secs)
+                })?);
+        }
+        if let Ok(path) = env::var("WATCHLISTS_PATH") {
+            config.watchlists_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("ORGS_PATH") {
+            config.orgs_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("API_KEYS_PATH") {
+            config.api_keys_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("LABELS_PATH") {
+            config.labels_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("LABELS_IMPORT_PATH") {
+            config.labels_import_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("PROGRAMS_PATH") {
+            config.programs_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("IDLS_PATH") {
+            config.idls_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("TX_HISTORY_PATH") {
+            config.tx_history_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("BALANCE_ALERTS_PATH") {
+            config.balance_alerts_path = Some(PathBuf::from(path));
+        }
+        if let Ok(bin) = env::var("VALIDATOR_BIN") {
+            config.validator_bin = bin;
+        }
+        if let Ok(dir) = env::var("VALIDATOR_LEDGER_DIR") {
+            config.validator_ledger_dir = PathBuf::from(dir);
+        }
+        env_parse("VALIDATOR_RPC_PORT", &mut config.validator_rpc_port)?;
+        env_parse(
+            "VALIDATOR_STARTUP_TIMEOUT_SECS",
+            &mut config.validator_startup_timeout_secs,
+        )?;
+        // e.g. VALIDATOR_PROGRAMS="<program id>=target/deploy/app.so"
+        if let Ok(programs) = env::var("VALIDATOR_PROGRAMS") {
+            config.validator_programs = parse_preloads("VALIDATOR_PROGRAMS", &programs)?;
+        }
+        if let Ok(accounts) = env::var("VALIDATOR_ACCOUNTS") {
+            config.validator_accounts = parse_preloads("VALIDATOR_ACCOUNTS", &accounts)?;
+        }
+        if let Ok(dir) = env::var("VALIDATOR_FIXTURES_DIR") {
+            config.validator_fixtures_dir = Some(PathBuf::from(dir));
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.rpc_url.starts_with("http://") && !self.rpc_url.starts_with("https://") {
+            return Err(format!(
+                "rpc_url must be an http(s) URL, got '{}'",
+                self.rpc_url
+            ));
+        }
+        if let Some(url) = &self.archival_rpc_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!(
+                    "archival_rpc_url must be an http(s) URL, got '{}'",
+                    url
+                ));
+            }
+        }
+        if let Some(url) = &self.das_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("das_url must be an http(s) URL, got '{}'", url));
+            }
+        }
+        if !self.jupiter_api_url.starts_with("http://")
+            && !self.jupiter_api_url.starts_with("https://")
+        {
+            return Err(format!(
+                "jupiter_api_url must be an http(s) URL, got '{}'",
+                self.jupiter_api_url
+            ));
+        }
+        if let Some(url) = &self.denylist_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!(
+                    "denylist_url must be an http(s) URL, got '{}'",
+                    url
+                ));
+            }
+        }
+        for version in [&self.rpc_min_version, &self.rpc_max_version]
+            .into_iter()
+            .flatten()
+        {
+            if crate::compat::parse_version(version).is_none() {
+                return Err(format!(
+                    "rpc_min_version and rpc_max_version must be versions like '1.18.0', got '{}'",
+                    version
+                ));
+            }
+        }
+        if let (Some(min), Some(max)) = (&self.rpc_min_version, &self.rpc_max_version) {
+            if crate::compat::parse_version(min) > crate::compat::parse_version(max) {
+                return Err("rpc_min_version must not be above rpc_max_version".to_string());
+            }
+        }
+        if let Some(date) = &self.deprecated_fields_cutoff {
+            if deprecations::parse_date(date).is_none() {
+                return Err(format!(
+                    "deprecated_fields_cutoff must be a YYYY-MM-DD date, got '{}'",
+                    date
+                ));
+            }
+        }
+        if let Some(url) = &self.rpc_reference_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!(
+                    "rpc_reference_url must be an http(s) URL, got '{}'",
+                    url
+                ));
+            }
+        }
+        if self.slot_lag_check_interval_secs == 0 {
+            return Err("slot_lag_check_interval_secs must be greater than 0".to_string());
+        }
+        if self.rpc_version_check_interval_secs == 0 {
+            return Err("rpc_version_check_interval_secs must be greater than 0".to_string());
+        }
+        if self.rpc_pool_max_connections == 0 {
+            return Err("rpc_pool_max_connections must be greater than 0".to_string());
+        }
+        if self.denylist_refresh_secs == 0 {
+            return Err("denylist_refresh_secs must be greater than 0".to_string());
+        }
+        if self.max_airdrop_sol == 0 {
+            return Err("max_airdrop_sol must be greater than 0".to_string());
+        }
+        let Some(max_airdrop_lamports) = self.max_airdrop_sol.checked_mul(LAMPORTS_PER_SOL) else {
+            return Err("max_airdrop_sol is too large".to_string());
+        };
+        if self.min_airdrop_lamports == 0 || self.min_airdrop_lamports > max_airdrop_lamports {
+            return Err(
+                "min_airdrop_lamports must be between 1 and max_airdrop_sol in lamports"
+                    .to_string(),
+            );
+        }
+        if self.slo_window_secs == 0 {
+            return Err("slo_window_secs must be greater than 0".to_string());
+        }
+        if self.shed_window_secs == 0 {
+            return Err("shed_window_secs must be greater than 0".to_string());
+        }
+        if self.shed_retry_after_secs == 0 {
+            return Err("shed_retry_after_secs must be greater than 0".to_string());
+        }
+        if self.shed_rpc_p95_latency_ms == Some(0) || self.shed_max_in_flight == Some(0) {
+            return Err(
+                "shed_rpc_p95_latency_ms and shed_max_in_flight must be greater than 0 when set"
+                    .to_string(),
+            );
+        }
+        if self.max_concurrent_requests == Some(0) {
+            return Err("max_concurrent_requests must be greater than 0 when set".to_string());
+        }
+        if self.rate_limit_per_minute == Some(0) || self.rate_limit_burst == Some(0) {
+            return Err(
+                "rate_limit_per_minute and rate_limit_burst must be greater than 0 when set"
+                    .to_string(),
+            );
+        }
+        if matches!(
+            self.store_backend,
+            StoreBackend::Sqlite | StoreBackend::Postgres
+        ) && self.store_url.is_none()
+        {
+            return Err(format!(
+                "store_url is required for the {} store backend",
+                self.store_backend
+            ));
+        }
+        if self.idempotency_ttl_secs == 0 {
+            return Err("idempotency_ttl_secs must be greater than 0".to_string());
+        }
+        if self.queue_timeout_secs == 0 {
+            return Err("queue_timeout_secs must be greater than 0".to_string());
+        }
+        if self.summary_max_transactions == 0 {
+            return Err("summary_max_transactions must be greater than 0".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.slo_min_success_rate) {
+            return Err("slo_min_success_rate must be between 0 and 1".to_string());
+        }
+        if !crate::amount::is_supported_locale(&self.display_locale) {
+            return Err(format!(
+                "display_locale must be a locale tag like 'en-US', got '{}'",
+                self.display_locale
+            ));
+        }
+        let hex_color = |color: &str| {
+            color.strip_prefix('#').is_some_and(|hex| {
+                matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+            })
+        };
+        if !hex_color(&self.frontend_accent_color) {
+            return Err(format!(
+                "frontend_accent_color must be a hex color like '#3b82f6', got '{}'",
+                self.frontend_accent_color
+            ));
+        }
+        if self
+            .frontend_logo_url
+            .as_ref()
+            .is_some_and(|url| !url.starts_with("https://") && !url.starts_with("http://"))
+        {
+            return Err("frontend_logo_url must be an http(s) URL".to_string());
+        }
+        if let Some(url) = &self.jwt_jwks_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!(
+                    "jwt_jwks_url must be an http(s) URL, got '{}'",
+                    url
+                ));
+            }
+        }
+        if matches!(&self.jwt_hs256_secret, Some(secret) if secret.is_empty()) {
+            return Err("jwt_hs256_secret must not be empty when set".to_string());
+        }
+        if self.require_auth && self.admin_token.is_none() && !self.jwt_enabled() {
+            return Err(
+                "require_auth needs admin_token or JWT settings, otherwise no request can authenticate"
+                    .to_string(),
+            );
+        }
+        if self.siws_required && !self.siws_enabled {
+            return Err("siws_required needs siws_enabled".to_string());
+        }
+        // Sessions can't fund on their own under require_auth, and API keys
+        // and JWTs carry no wallet, so nobody but admins could airdrop.
+        if self.siws_required && self.require_auth {
+            return Err("siws_required can't be combined with require_auth".to_string());
+        }
+        if self.siws_domain.is_empty() || self.siws_domain.contains('\n') {
+            return Err("siws_domain must be a non-empty single line".to_string());
+        }
+        if self.siws_session_ttl_secs == 0 {
+            return Err("siws_session_ttl_secs must be greater than 0".to_string());
+        }
+        self.features
+            .keys()
+            .try_for_each(|name| crate::features::validate_name(name))?;
+        if let Some(url) = self
+            .notify_webhooks
+            .iter()
+            .find(|u| !u.starts_with("http://") && !u.starts_with("https://"))
+        {
+            return Err(format!(
+                "notify_webhooks must be http(s) URLs, got '{}'",
+                url
+            ));
+        }
+        if self.notify_interval_secs == 0 {
+            return Err("notify_interval_secs must be greater than 0".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.notify_airdrop_max_failure_rate) {
+            return Err("notify_airdrop_max_failure_rate must be between 0 and 1".to_string());
+        }
+        if self.telegram_bot_token.is_some() && !cfg!(feature = "telegram") {
+            return Err(
+                "telegram_bot_token is set but this build lacks the 'telegram' feature".to_string(),
+            );
+        }
+        if !self.telegram_api_url.starts_with("http://")
+            && !self.telegram_api_url.starts_with("https://")
+        {
+            return Err(format!(
+                "telegram_api_url must be an http(s) URL, got '{}'",
+                self.telegram_api_url
+            ));
+        }
+        if self.sentry_dsn.is_some() && !cfg!(feature = "sentry") {
+            return Err("sentry_dsn is set but this build lacks the 'sentry' feature".to_string());
+        }
+        if let Some(url) = self
+            .event_webhooks
+            .iter()
+            .chain(&self.event_kafka_rest_url)
+            .find(|u| !u.starts_with("http://") && !u.starts_with("https://"))
+        {
+            return Err(format!(
+                "event_webhooks and event_kafka_rest_url must be http(s) URLs, got '{}'",
+                url
+            ));
+        }
+        if let Some(url) = &self.event_nats_url {
+            if !url.starts_with("nats://") {
+                return Err(format!(
+                    "event_nats_url must be a nats:// URL, got '{}'",
+                    url
+                ));
+            }
+        }
+        if self.event_nats_subject.is_empty()
+            || self.event_nats_subject.contains(char::is_whitespace)
+        {
+            return Err("event_nats_subject must be non-empty without whitespace".to_string());
+        }
+        if self.event_kafka_topic.is_empty() || self.event_kafka_topic.contains('/') {
+            return Err("event_kafka_topic must be non-empty without '/'".to_string());
+        }
+        if let Some(program) = self
+            .event_programs
+            .iter()
+            .find(|p| Pubkey::from_str(p).is_err())
+        {
+            return Err(format!(
+                "event_programs has an invalid address '{}'",
+                program
+            ));
+        }
+        for (name, list) in [
+            ("hot_wallets", &self.hot_wallets),
+            ("hot_mints", &self.hot_mints),
+        ] {
+            if let Some(address) = list.iter().find(|a| Pubkey::from_str(a).is_err()) {
+                return Err(format!("{} has an invalid address '{}'", name, address));
+            }
+        }
+        if self.hot_refresh_secs == 0 {
+            return Err("hot_refresh_secs must be greater than 0".to_string());
+        }
+        if self.event_poll_interval_secs == 0 {
+            return Err("event_poll_interval_secs must be greater than 0".to_string());
+        }
+        for spec in &self.index_programs {
+            index::parse_spec(spec).map_err(|e| format!("index_programs: {}", e))?;
+        }
+        if self
+            .index_ws_url
+            .as_ref()
+            .is_some_and(|url| !url.starts_with("ws://") && !url.starts_with("wss://"))
+        {
+            return Err("index_ws_url must be a ws:// or wss:// URL".to_string());
+        }
+        if self.index_reconcile_secs == 0 {
+            return Err("index_reconcile_secs must be greater than 0".to_string());
+        }
+        if self.tx_history_poll_secs == 0 {
+            return Err("tx_history_poll_secs must be greater than 0".to_string());
+        }
+        if self.tx_history_batch == 0 {
+            return Err("tx_history_batch must be greater than 0".to_string());
+        }
+        if self
+            .airdrop_maintenance
+            .as_ref()
+            .is_some_and(|m| m.trim().is_empty())
+        {
+            return Err("airdrop_maintenance must not be empty".to_string());
+        }
+        denials::validate_codes(self.airdrop_denial_messages.keys())?;
+        if self.bulk_airdrop_concurrency == 0 {
+            return Err("bulk_airdrop_concurrency must be greater than 0".to_string());
+        }
+        if self.bulk_airdrop_max_rows == 0 {
+            return Err("bulk_airdrop_max_rows must be greater than 0".to_string());
+        }
+        if self.rpc_fanout_concurrency == 0 || self.rpc_fanout_timeout_secs == 0 {
+            return Err(
+                "rpc_fanout_concurrency and rpc_fanout_timeout_secs must be greater than 0"
+                    .to_string(),
+            );
+        }
+        if self.bulk_balance_max_addresses == 0 {
+            return Err("bulk_balance_max_addresses must be greater than 0".to_string());
+        }
+        if self.program_accounts_stream_max == 0 {
+            return Err("program_accounts_stream_max must be greater than 0".to_string());
+        }
+        if self.export_bucket.is_some()
+            && (self.export_access_key_id.is_none() || self.export_secret_access_key.is_none())
+        {
+            return Err(
+                "export_bucket needs export_access_key_id and export_secret_access_key".to_string(),
+            );
+        }
+        if !self.export_endpoint.starts_with("http://")
+            && !self.export_endpoint.starts_with("https://")
+        {
+            return Err(format!(
+                "export_endpoint must be an http(s) URL, got '{}'",
+                self.export_endpoint
+            ));
+        }
+        if self.export_interval_secs == Some(0) {
+            return Err("export_interval_secs must be greater than 0".to_string());
+        }
+        if (self.record_dir.is_some() || self.replay_dir.is_some()) && !self.dev_mode {
+            return Err("record_dir and replay_dir require dev_mode".to_string());
+        }
+        if self.backend == Backend::Mock && (self.record_dir.is_some() || self.replay_dir.is_some())
+        {
+            return Err("the mock backend can't be recorded or replayed".to_string());
+        }
+        if self.record_dir.is_some() && self.replay_dir.is_some() {
+            return Err("record_dir and replay_dir can't be used together".to_string());
+        }
+        if self.validator_startup_timeout_secs == 0 {
+            return Err("validator_startup_timeout_secs must be greater than 0".to_string());
+        }
+        for preload in self
+            .validator_programs
+            .iter()
+            .chain(&self.validator_accounts)
+        {
+            if Pubkey::from_str(&preload.address).is_err() {
+                return Err(format!(
+                    "invalid validator preload address '{}'",
+                    preload.address
+                ));
+            }
+        }
+        if self
+            .treasury_low_watermark_sol
+            .is_some_and(|sol| !sol.is_finite() || sol < 0.0)
+        {
+            return Err("treasury_low_watermark_sol must be 0 or more".to_string());
+        }
+        if self
+            .self_test_min_treasury_sol
+            .is_some_and(|sol| !sol.is_finite() || sol < 0.0)
+        {
+            return Err("self_test_min_treasury_sol must be 0 or more".to_string());
+        }
+        if self.treasury_check_interval_secs == 0 {
+            return Err("treasury_check_interval_secs must be greater than 0".to_string());
+        }
+        if self.topup_interval_secs == 0 {
+            return Err("topup_interval_secs must be greater than 0".to_string());
+        }
+        if self.balance_alert_interval_secs == 0 {
+            return Err("balance_alert_interval_secs must be greater than 0".to_string());
+        }
+        if self.balance_wait_max_secs == 0 {
+            return Err("balance_wait_max_secs must be greater than 0".to_string());
+        }
+        if !self.topup_max_target_sol.is_finite() || self.topup_max_target_sol <= 0.0 {
+            return Err("topup_max_target_sol must be greater than 0".to_string());
+        }
+        if matches!(&self.admin_token, Some(token) if token.is_empty()) {
+            return Err("admin_token must not be empty when set".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn rpc_budget(&self, subject: &str) -> Option<u64> {
+        self.rpc_budgets
+            .get(subject)
+            .copied()
+            .or(self.rpc_daily_budget)
+    }
+
+    // Orgs only have a budget when `rpc_budgets` names them ("org:<id>");
+    // the per-credential default doesn't cap a whole org.
+    pub fn org_rpc_budget(&self, subject: &str) -> Option<u64> {
+        self.rpc_budgets.get(subject).copied()
+    }
+
+    pub fn jwt_enabled(&self) -> bool {
+        self.jwt_hs256_secret.is_some() || self.jwt_jwks_url.is_some()
+    }
+
+    pub fn airdrop_limits(&self) -> AirdropLimits {
+        AirdropLimits {
+            min_lamports: self.min_airdrop_lamports,
+            max_lamports: self.max_airdrop_sol.saturating_mul(LAMPORTS_PER_SOL),
+        }
+    }
+
+    pub fn bind_addr(&self) -> String {
+        format!("0.0.0.0:{}", self.port)
+    }
+
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.cors_origins.is_empty() || self.cors_origins.iter().any(|o| o == "*" || o == origin)
+    }
+}
+
+fn parse_preloads(name: &str, value: &str) -> Result<Vec<ValidatorPreload>, String> {
+    value
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|entry| {
+            let (address, path) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("{} entry '{}' must be address=path", name, entry))?;
+            Ok(ValidatorPreload {
+                address: address.trim().to_string(),
+                path: PathBuf::from(path.trim()),
+            })
+        })
+        .collect()
+}
+
+fn env_parse<T: FromStr>(name: &str, field: &mut T) -> Result<(), String> {
+    if let Ok(value) = env::var(name) {
+        *field = value
+            .parse()
+            .map_err(|_| format!("{} has an invalid value '{}'", name, value))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_parse_back_to_their_variants() {
+        for backend in [
+            StoreBackend::File,
+            StoreBackend::Memory,
+            StoreBackend::Sqlite,
+            StoreBackend::Postgres,
+        ] {
+            assert_eq!(backend.to_string().parse::<StoreBackend>(), Ok(backend));
+        }
+        assert_eq!("mock".parse::<Backend>(), Ok(Backend::Mock));
+        assert_eq!("parquet".parse::<ExportFormat>(), Ok(ExportFormat::Parquet));
+        assert_eq!("refuse".parse::<RpcCompatMode>(), Ok(RpcCompatMode::Refuse));
+        assert_eq!("fatal".parse::<SentryLevel>(), Ok(SentryLevel::Fatal));
+        assert_eq!("warn".parse::<LogLevel>(), Ok(LogLevel::Warn));
+        assert_eq!(
+            "Mock".parse::<Backend>(),
+            Err("unknown backend 'Mock'".to_string())
+        );
+        assert!("debug".parse::<LogLevel>().is_err());
+        assert!(SentryLevel::Warning < SentryLevel::Fatal);
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert_eq!(Config::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_bad_settings() {
+        let rejected = |f: fn(&mut Config)| {
+            let mut config = Config::default();
+            f(&mut config);
+            config.validate().unwrap_err()
+        };
+        assert!(rejected(|c| c.rpc_url = "ws://localhost:8900".into()).starts_with("rpc_url"));
+        assert!(rejected(|c| c.das_url = Some("localhost".into())).starts_with("das_url"));
+        assert!(rejected(|c| c.rpc_min_version = Some("1.x".into())).contains("versions"));
+        assert_eq!(
+            rejected(|c| {
+                c.rpc_min_version = Some("1.18.0".into());
+                c.rpc_max_version = Some("1.17.9".into());
+            }),
+            "rpc_min_version must not be above rpc_max_version"
+        );
+        assert!(
+            rejected(|c| c.deprecated_fields_cutoff = Some("2024-13-01".into()))
+                .starts_with("deprecated_fields_cutoff")
+        );
+        assert!(rejected(|c| c.balance_wait_max_secs = 0).starts_with("balance_wait_max_secs"));
+        assert!(rejected(|c| c.topup_max_target_sol = f64::NAN).starts_with("topup_max_target"));
+        assert!(rejected(|c| c.admin_token = Some(String::new())).starts_with("admin_token"));
+    }
+
+    #[test]
+    fn named_budgets_override_the_daily_default() {
+        let mut config = Config {
+            rpc_daily_budget: Some(100),
+            ..Config::default()
+        };
+        config.rpc_budgets.insert("key:ci".into(), 5);
+        config.rpc_budgets.insert("org:acme".into(), 50);
+        assert_eq!(config.rpc_budget("key:ci"), Some(5));
+        assert_eq!(config.rpc_budget("key:other"), Some(100));
+        assert_eq!(config.org_rpc_budget("org:acme"), Some(50));
+        assert_eq!(config.org_rpc_budget("org:other"), None);
+    }
+
+    #[test]
+    fn limits_and_origins() {
+        let mut config = Config {
+            max_airdrop_sol: 3,
+            min_airdrop_lamports: 10,
+            ..Config::default()
+        };
+        let limits = config.airdrop_limits();
+        assert_eq!(
+            (limits.min_lamports, limits.max_lamports),
+            (10, 3 * LAMPORTS_PER_SOL)
+        );
+        assert!(config.allows_origin("https://anything.example"));
+        config.cors_origins = vec!["https://app.example".into()];
+        assert!(config.allows_origin("https://app.example"));
+        assert!(!config.allows_origin("https://evil.example"));
+        config.cors_origins.push("*".into());
+        assert!(config.allows_origin("https://evil.example"));
+    }
+
+    #[test]
+    fn preloads_are_address_path_pairs() {
+        assert_eq!(
+            parse_preloads("VALIDATOR_PROGRAMS", " A=prog.so , ,B = dump.json").unwrap(),
+            vec![
+                ValidatorPreload {
+                    address: "A".into(),
+                    path: "prog.so".into(),
+                },
+                ValidatorPreload {
+                    address: "B".into(),
+                    path: "dump.json".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            parse_preloads("VALIDATOR_PROGRAMS", "A").unwrap_err(),
+            "VALIDATOR_PROGRAMS entry 'A' must be address=path"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use solana_client::{
+    rpc_client::{RpcClient, SerializableTransaction},
+    rpc_request::RpcError,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::service::{rpc_error, ServiceError};
+
+pub use crate::api::ConfirmationReport;
+
+// Confirmation checks start this far apart and back off to MAX_INTERVAL.
+const MIN_INTERVAL: Duration = Duration::from_millis(400);
+const MAX_INTERVAL: Duration = Duration::from_secs(2);
+// The factor each unanswered check stretches the interval by.
+const BACKOFF: f64 = 1.5;
+// Confirmation latencies kept to tune the first check.
+const SAMPLES: usize = 64;
+
+// How long recent transactions took from being sent to being confirmed,
+// newest last.
+static LATENCIES: Mutex<VecDeque<Duration>> = Mutex::new(VecDeque::new());
+
+fn percentile(p: f64) -> Option<Duration> {
+    percentile_of(LATENCIES.lock().unwrap().iter().copied().collect(), p)
+}
+
+// The nearest-rank `p` percentile of `latencies`, in any order.
+fn percentile_of(mut latencies: Vec<Duration>, p: f64) -> Option<Duration> {
+    if latencies.is_empty() {
+        return None;
+    }
+    latencies.sort();
+    let index = ((latencies.len() - 1) as f64 * p).round() as usize;
+    Some(latencies[index])
+}
+
+pub fn report() -> ConfirmationReport {
+    let samples = LATENCIES.lock().unwrap().len();
+    ConfirmationReport {
+        samples,
+        p50_ms: percentile(0.5).map(|d| d.as_millis() as u64),
+        p90_ms: percentile(0.9).map(|d| d.as_millis() as u64),
+    }
+}
+
+// When to check a sent transaction next. The first check waits about as
+// long as recent transactions took to confirm, so most are confirmed on
+// the first call; later ones start at MIN_INTERVAL and back off to
+// MAX_INTERVAL, so a slow one is still reported soon after it lands
+// without a long wait costing a call every 400ms.
+pub struct Polling {
+    sent: Instant,
+    next: Option<Duration>,
+}
+
+impl Polling {
+    // For a transaction sent just now.
+    pub fn start() -> Self {
+        Polling {
+            sent: Instant::now(),
+            next: None,
+        }
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        match self.next {
+            None => {
+                self.next = Some(MIN_INTERVAL);
+                percentile(0.5)
+                    .unwrap_or(MIN_INTERVAL)
+                    .clamp(MIN_INTERVAL, MAX_INTERVAL)
+            }
+            Some(delay) => {
+                self.next = Some(delay.mul_f64(BACKOFF).min(MAX_INTERVAL));
+                delay
+            }
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.sent.elapsed()
+    }
+
+    // Records the transaction as confirmed now.
+    pub fn confirmed(self) {
+        let mut latencies = LATENCIES.lock().unwrap();
+        if latencies.len() == SAMPLES {
+            latencies.pop_front();
+        }
+        latencies.push_back(self.sent.elapsed());
+    }
+}
+
+// Blocking. RpcClient::send_and_confirm_transaction, checking on the
+// transaction by `Polling` instead of every 500ms: sends it, then waits
+// until it reaches the client's commitment, fails, or its blockhash
+// expires. Errors are `action`'s.
+pub fn send_and_confirm(
+    client: &RpcClient,
+    tx: &impl SerializableTransaction,
+    action: &'static str,
+) -> Result<Signature, ServiceError> {
+    let fail = rpc_error(action);
+    let signature = client.send_transaction(tx).map_err(&fail)?;
+    let blockhash = *tx.get_recent_blockhash();
+    let mut polling = Polling::start();
+    loop {
+        std::thread::sleep(polling.next_delay());
+        match client.get_signature_status(&signature).map_err(&fail)? {
+            Some(Ok(())) => {
+                polling.confirmed();
+                return Ok(signature);
+            }
+            Some(Err(e)) => return Err(fail(e.into())),
+            None => {
+                let valid = client
+                    .is_blockhash_valid(&blockhash, CommitmentConfig::processed())
+                    .map_err(&fail)?;
+                if !valid {
+                    return Err(fail(
+                        RpcError::ForUser(
+                            "unable to confirm transaction. This can happen in situations \
+                             such as transaction expiration and insufficient fee-payer funds"
+                                .to_string(),
+                        )
+                        .into(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc;
+    use solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        system_transaction,
+    };
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn percentiles_pick_the_nearest_rank() {
+        let latencies: Vec<Duration> = (1..=10).rev().map(ms).collect();
+        assert_eq!(percentile_of(latencies.clone(), 0.0), Some(ms(1)));
+        // Index 4.5 rounds to 5.
+        assert_eq!(percentile_of(latencies.clone(), 0.5), Some(ms(6)));
+        assert_eq!(percentile_of(latencies.clone(), 0.9), Some(ms(9)));
+        assert_eq!(percentile_of(latencies, 1.0), Some(ms(10)));
+        assert_eq!(percentile_of(vec![ms(700)], 0.9), Some(ms(700)));
+        assert_eq!(percentile_of(Vec::new(), 0.5), None);
+    }
+
+    #[test]
+    fn checks_back_off_to_the_maximum() {
+        let mut polling = Polling::start();
+        // The first waits about as long as recent confirmations took.
+        let first = polling.next_delay();
+        assert!(
+            (MIN_INTERVAL..=MAX_INTERVAL).contains(&first),
+            "{:?}",
+            first
+        );
+        let later: Vec<Duration> = (0..6).map(|_| polling.next_delay()).collect();
+        assert_eq!(
+            later,
+            [ms(400), ms(600), ms(900), ms(1_350), ms(2_000), ms(2_000)]
+        );
+    }
+
+    #[test]
+    fn only_recent_latencies_are_kept() {
+        for _ in 0..SAMPLES + 6 {
+            Polling::start().confirmed();
+        }
+        let report = report();
+        assert_eq!(report.samples, SAMPLES);
+        assert!(report.p50_ms.unwrap() <= report.p90_ms.unwrap());
+    }
+
+    #[test]
+    fn transactions_are_sent_and_confirmed() {
+        let chain = rpc::mock_for_tests();
+        let client = rpc::client("http://mock");
+        let payer = Keypair::new();
+        let to = Pubkey::new_unique();
+        chain.set_balance(payer.pubkey(), 1_000_000_000);
+        let blockhash = client.get_latest_blockhash().unwrap();
+        let tx = system_transaction::transfer(&payer, &to, 250_000_000, blockhash);
+
+        let signature = send_and_confirm(&client, &tx, "Transfer failed").unwrap();
+        assert_eq!(signature, tx.signatures[0]);
+        assert_eq!(client.get_balance(&to).unwrap(), 250_000_000);
+        assert!(report().samples > 0);
+
+        let broke = Keypair::new();
+        let tx = system_transaction::transfer(&broke, &to, 1, blockhash);
+        match send_and_confirm(&client, &tx, "Transfer failed") {
+            Err(ServiceError::Rpc { action, .. }) => assert_eq!(action, "Transfer failed"),
+            other => panic!(
+                "expected an RPC error, got {:?}",
+                other.map(|s| s.to_string())
+            ),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use solana_sdk::{clock::Slot, compute_budget};
+use solana_transaction_status::option_serializer::OptionSerializer;
+
+use crate::{history, rpc, service::ServiceError};
+
+// Runtime defaults applied when a transaction does not set its own limit.
+const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+const MAX_COMPUTE_UNIT_LIMIT: u64 = 1_400_000;
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+// ComputeBudgetInstruction discriminants.
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+pub struct SignerCost {
+    pub address: String,
+    pub fee_payer: bool,
+    // Base fee caused by this signer's signature.
+    pub signature_fee_lamports: u64,
+    // What the signer was actually charged; only the fee payer pays.
+    pub paid_lamports: u64,
+}
+
+pub struct TransactionCost {
+    pub slot: Slot,
+    pub block_time: Option<i64>,
+    pub succeeded: bool,
+    pub fee_lamports: u64,
+    pub base_fee_lamports: u64,
+    pub priority_fee_lamports: u64,
+    pub compute_unit_price_micro_lamports: u64,
+    pub compute_units_requested: u64,
+    pub compute_unit_limit_set: bool,
+    pub compute_units_consumed: Option<u64>,
+    pub signers: Vec<SignerCost>,
+}
+
+pub fn transaction_cost(rpc_url: &str, signature: &str) -> Result<TransactionCost, ServiceError> {
+    let client = rpc::client(rpc_url);
+    let tx = history::fetch_transaction(&client, signature)?;
+
+    let meta = tx
+        .transaction
+        .meta
+        .as_ref()
+        .ok_or_else(|| ServiceError::Rpc {
+            action: "Failed to read transaction meta",
+            message: format!("{} has no status meta", signature),
+        })?;
+    let decoded = tx
+        .transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| ServiceError::Rpc {
+            action: "Failed to decode transaction",
+            message: signature.to_string(),
+        })?;
+    let message = &decoded.message;
+    let keys = message.static_account_keys();
+
+    let mut limit = None;
+    let mut price = 0;
+    let mut other_instructions = 0;
+    for ix in message.instructions() {
+        let program = keys.get(ix.program_id_index as usize);
+        if program != Some(&compute_budget::id()) {
+            other_instructions += 1;
+            continue;
+        }
+        match ix.data.split_first() {
+            Some((&SET_COMPUTE_UNIT_LIMIT, rest)) => {
+                if let Ok(bytes) = rest.try_into() {
+                    limit = Some(u32::from_le_bytes(bytes) as u64);
+                }
+            }
+            Some((&SET_COMPUTE_UNIT_PRICE, rest)) => {
+                if let Ok(bytes) = rest.try_into() {
+                    price = u64::from_le_bytes(bytes);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let requested = limit
+        .unwrap_or(other_instructions * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT)
+        .min(MAX_COMPUTE_UNIT_LIMIT);
+    let priority_fee = (price as u128 * requested as u128).div_ceil(MICRO_LAMPORTS_PER_LAMPORT);
+    let priority_fee = u64::try_from(priority_fee)
+        .unwrap_or(u64::MAX)
+        .min(meta.fee);
+    let base_fee = meta.fee - priority_fee;
+
+    let num_signers = message.header().num_required_signatures as usize;
+    let per_signature = base_fee / num_signers.max(1) as u64;
+    let signers = keys
+        .iter()
+        .take(num_signers)
+        .enumerate()
+        .map(|(i, key)| SignerCost {
+            address: key.to_string(),
+            fee_payer: i == 0,
+            signature_fee_lamports: per_signature,
+            paid_lamports: if i == 0 { meta.fee } else { 0 },
+        })
+        .collect();
+
+    let consumed = match meta.compute_units_consumed {
+        OptionSerializer::Some(units) => Some(units),
+        _ => None,
+    };
+
+    Ok(TransactionCost {
+        slot: tx.slot,
+        block_time: tx.block_time,
+        succeeded: meta.err.is_none(),
+        fee_lamports: meta.fee,
+        base_fee_lamports: base_fee,
+        priority_fee_lamports: priority_fee,
+        compute_unit_price_micro_lamports: price,
+        compute_units_requested: requested,
+        compute_unit_limit_set: limit.is_some(),
+        compute_units_consumed: consumed,
+        signers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service;
+    use solana_sdk::{
+        compute_budget::ComputeBudgetInstruction,
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    };
+
+    #[test]
+    fn compute_budget_discriminants_match_the_sdk() {
+        let limit = ComputeBudgetInstruction::set_compute_unit_limit(300);
+        assert_eq!(limit.data, [SET_COMPUTE_UNIT_LIMIT, 44, 1, 0, 0]);
+        let price = ComputeBudgetInstruction::set_compute_unit_price(7);
+        assert_eq!(price.data, [SET_COMPUTE_UNIT_PRICE, 7, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn fees_are_split_into_base_and_priority() {
+        let chain = rpc::mock_for_tests();
+        let (payer, source) = (Keypair::new(), Keypair::new());
+        let to = Pubkey::new_unique();
+        chain.set_balance(payer.pubkey(), 1_000_000);
+        chain.set_balance(source.pubkey(), 1_000_000);
+
+        let client = rpc::client("http://mock");
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(300),
+                // 300 units at 10 lamports each.
+                ComputeBudgetInstruction::set_compute_unit_price(10_000_000),
+                system_instruction::transfer(&source.pubkey(), &to, 1_000),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &source],
+            client.get_latest_blockhash().unwrap(),
+        );
+        let signature = client.send_transaction(&tx).unwrap();
+
+        let cost = transaction_cost("http://mock", &signature.to_string()).unwrap();
+        assert!(cost.succeeded);
+        assert!(cost.compute_unit_limit_set);
+        assert_eq!(cost.compute_units_requested, 300);
+        assert_eq!(cost.compute_unit_price_micro_lamports, 10_000_000);
+        assert_eq!(cost.priority_fee_lamports, 3_000);
+        assert_eq!(
+            cost.base_fee_lamports + cost.priority_fee_lamports,
+            cost.fee_lamports
+        );
+        let signers: Vec<_> = cost
+            .signers
+            .iter()
+            .map(|s| (s.address.clone(), s.fee_payer, s.paid_lamports))
+            .collect();
+        assert_eq!(
+            signers,
+            [
+                (payer.pubkey().to_string(), true, cost.fee_lamports),
+                (source.pubkey().to_string(), false, 0),
+            ]
+        );
+        assert_eq!(
+            cost.signers[0].signature_fee_lamports,
+            cost.base_fee_lamports / 2
+        );
+
+        let plain = service::transfer("http://mock", &payer, &to, 1, false).unwrap();
+        let cost = transaction_cost("http://mock", &plain.to_string()).unwrap();
+        assert!(!cost.compute_unit_limit_set);
+        assert_eq!(
+            cost.compute_units_requested,
+            DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT
+        );
+        assert_eq!(cost.priority_fee_lamports, 0);
+        assert_eq!(cost.base_fee_lamports, cost.fee_lamports);
+    }
+}
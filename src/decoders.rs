@@ -0,0 +1,575 @@
+use serde_json::{json, Value};
+use solana_account_decoder::parse_account_data::{
+    parse_account_data, AccountAdditionalData, ParseAccountError,
+};
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_sdk::{
+    bpf_loader_upgradeable, instruction::CompiledInstruction, message::AccountKeys, pubkey,
+    pubkey::Pubkey, stake, system_program, vote,
+};
+use solana_transaction_status::parse_instruction;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::layout::Reader;
+
+pub use crate::api::Decoded;
+
+const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+const TOKEN_METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+pub const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+// Offset of `decimals` in a mint, the same for both token programs.
+const MINT_DECIMALS_OFFSET: usize = 44;
+
+// Per-request lookups a decoder may need beyond the account itself. Lives
+// for one endpoint call, so repeated lookups (e.g. the same mint across a
+// program's token accounts) hit RPC once.
+pub struct Context<'a> {
+    client: &'a RpcClient,
+    decimals: RefCell<HashMap<Pubkey, Option<u8>>>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(client: &'a RpcClient) -> Self {
+        Context {
+            client,
+            decimals: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn mint_decimals(&self, mint: &Pubkey) -> Option<u8> {
+        if let Some(decimals) = self.decimals.borrow().get(mint) {
+            return *decimals;
+        }
+        let decimals = self
+            .client
+            .get_account_data(mint)
+            .ok()
+            .and_then(|data| data.get(MINT_DECIMALS_OFFSET).copied());
+        self.decimals.borrow_mut().insert(*mint, decimals);
+        decimals
+    }
+}
+
+// Turns the raw data of one program's accounts, and optionally its
+// instructions, into JSON. Registered per program id; the registry picks the
+// decoder from an account's owner or the program an instruction calls.
+pub trait Decoder: Send + Sync {
+    // Reported next to every value this decoder produces.
+    fn name(&self) -> &str;
+
+    fn decode_account(&self, ctx: &Context, address: &Pubkey, data: &[u8])
+        -> Result<Value, String>;
+
+    // `accounts` are the instruction's accounts, in order. None when the
+    // decoder doesn't know this program's instructions.
+    fn decode_instruction(
+        &self,
+        _accounts: &[Pubkey],
+        _data: &[u8],
+    ) -> Option<Result<Value, String>> {
+        None
+    }
+
+    // Data the program logged with `sol_log_data` ("Program data:" lines),
+    // e.g. Anchor events. None when the decoder doesn't know its events.
+    fn decode_event(&self, _data: &[u8]) -> Option<Result<Value, String>> {
+        None
+    }
+
+    // Names of the events `decode_event` knows.
+    fn event_names(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+fn decoded(decoder: &dyn Decoder, result: Result<Value, String>) -> Decoded {
+    let (parsed, error) = match result {
+        Ok(parsed) => (Some(parsed), None),
+        Err(e) => (None, Some(e)),
+    };
+    Decoded {
+        decoder: decoder.name().to_string(),
+        parsed,
+        error,
+    }
+}
+
+// Decoders can be added while the server runs (IDLs uploaded through /idl).
+#[derive(Default)]
+pub struct Registry {
+    decoders: RwLock<HashMap<Pubkey, Arc<dyn Decoder>>>,
+}
+
+impl Registry {
+    // The programs the server understands out of the box.
+    pub fn builtin() -> Self {
+        let registry = Registry::default();
+        for (program_id, name) in [
+            (system_program::id(), "system"),
+            (spl_token::id(), "spl-token"),
+            (TOKEN_2022_PROGRAM_ID, "spl-token-2022"),
+            (
+                spl_associated_token_account::id(),
+                "spl-associated-token-account",
+            ),
+            (MEMO_PROGRAM_ID, "spl-memo"),
+            (stake::program::id(), "stake"),
+            (vote::program::id(), "vote"),
+            (bpf_loader_upgradeable::id(), "bpf-upgradeable-loader"),
+        ] {
+            registry.register(program_id, Arc::new(Native { program_id, name }));
+        }
+        registry.register(TOKEN_METADATA_PROGRAM_ID, Arc::new(TokenMetadata));
+        registry
+    }
+
+    // Replaces any decoder already registered for `program_id`.
+    pub fn register(&self, program_id: Pubkey, decoder: Arc<dyn Decoder>) {
+        self.decoders.write().unwrap().insert(program_id, decoder);
+    }
+
+    fn get(&self, program_id: &Pubkey) -> Option<Arc<dyn Decoder>> {
+        self.decoders.read().unwrap().get(program_id).cloned()
+    }
+
+    // None when no decoder is registered for `owner`, or there is no data
+    // to decode (plain system accounts).
+    pub fn decode_account(
+        &self,
+        ctx: &Context,
+        owner: &Pubkey,
+        address: &Pubkey,
+        data: &[u8],
+    ) -> Option<Decoded> {
+        let decoder = self.get(owner)?;
+        if data.is_empty() {
+            return None;
+        }
+        Some(decoded(
+            decoder.as_ref(),
+            decoder.decode_account(ctx, address, data),
+        ))
+    }
+
+    pub fn decode_instruction(
+        &self,
+        program_id: &Pubkey,
+        accounts: &[Pubkey],
+        data: &[u8],
+    ) -> Option<Decoded> {
+        let decoder = self.get(program_id)?;
+        let result = decoder.decode_instruction(accounts, data)?;
+        Some(decoded(decoder.as_ref(), result))
+    }
+
+    pub fn decode_event(&self, program_id: &Pubkey, data: &[u8]) -> Option<Decoded> {
+        let decoder = self.get(program_id)?;
+        let result = decoder.decode_event(data)?;
+        Some(decoded(decoder.as_ref(), result))
+    }
+
+    // Empty when no decoder for `program_id` knows its events.
+    pub fn event_names(&self, program_id: &Pubkey) -> Vec<String> {
+        self.get(program_id)
+            .map(|decoder| decoder.event_names())
+            .unwrap_or_default()
+    }
+}
+
+// Programs covered by the parsers behind jsonParsed RPC encoding.
+struct Native {
+    program_id: Pubkey,
+    name: &'static str,
+}
+
+impl Decoder for Native {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn decode_account(
+        &self,
+        ctx: &Context,
+        address: &Pubkey,
+        data: &[u8],
+    ) -> Result<Value, String> {
+        let parse = |spl_token_decimals| {
+            parse_account_data(
+                address,
+                &self.program_id,
+                data,
+                Some(AccountAdditionalData { spl_token_decimals }),
+            )
+        };
+        let parsed = match parse(None) {
+            // Token accounts need their mint's decimals for UI amounts.
+            Err(ParseAccountError::AdditionalDataMissing(_)) => {
+                let mint = Reader::new(data).pubkey()?;
+                parse(ctx.mint_decimals(&mint))
+            }
+            parsed => parsed,
+        };
+        parsed.map(|p| p.parsed).map_err(|e| e.to_string())
+    }
+
+    fn decode_instruction(
+        &self,
+        accounts: &[Pubkey],
+        data: &[u8],
+    ) -> Option<Result<Value, String>> {
+        let mut keys = accounts.to_vec();
+        keys.push(self.program_id);
+        let instruction = CompiledInstruction {
+            program_id_index: accounts.len() as u8,
+            accounts: (0..accounts.len() as u8).collect(),
+            data: data.to_vec(),
+        };
+        let parsed = parse_instruction::parse(
+            &self.program_id,
+            &instruction,
+            &AccountKeys::new(&keys, None),
+            None,
+        );
+        Some(parsed.map(|p| p.parsed).map_err(|e| e.to_string()))
+    }
+}
+
+// Metaplex Token Metadata accounts: metadata, master editions and prints.
+struct TokenMetadata;
+
+// Key enum tags.
+const EDITION_V1: u8 = 1;
+const MASTER_EDITION_V1: u8 = 2;
+const METADATA_V1: u8 = 4;
+const MASTER_EDITION_V2: u8 = 6;
+
+fn token_standard(tag: u8) -> &'static str {
+    match tag {
+        0 => "non_fungible",
+        1 => "fungible_asset",
+        2 => "fungible",
+        3 => "non_fungible_edition",
+        4 => "programmable_non_fungible",
+        5 => "programmable_non_fungible_edition",
+        _ => "unknown",
+    }
+}
+
+// Metadata strings are stored padded with NULs to their maximum length.
+fn padded_string(r: &mut Reader) -> Result<String, String> {
+    Ok(r.string()?.trim_end_matches('\0').to_string())
+}
+
+impl Decoder for TokenMetadata {
+    fn name(&self) -> &str {
+        "token-metadata"
+    }
+
+    fn decode_account(
+        &self,
+        _ctx: &Context,
+        _address: &Pubkey,
+        data: &[u8],
+    ) -> Result<Value, String> {
+        let mut r = Reader::new(data);
+        match r.u8()? {
+            METADATA_V1 => {
+                let update_authority = r.pubkey()?;
+                let mint = r.pubkey()?;
+                let name = padded_string(&mut r)?;
+                let symbol = padded_string(&mut r)?;
+                let uri = padded_string(&mut r)?;
+                let seller_fee_basis_points = r.u16()?;
+                let creators = r.option(|r| {
+                    let count = r.u32()?;
+                    (0..count)
+                        .map(|_| {
+                            Ok(json!({
+                                "address": r.pubkey()?.to_string(),
+                                "verified": r.bool()?,
+                                "share": r.u8()?,
+                            }))
+                        })
+                        .collect::<Result<Vec<_>, String>>()
+                })?;
+                let primary_sale_happened = r.bool()?;
+                let is_mutable = r.bool()?;
+                let edition_nonce = r.option(Reader::u8)?;
+                let token_standard = r.option(|r| r.u8().map(token_standard))?;
+                let collection = r.option(|r| {
+                    Ok(json!({
+                        "verified": r.bool()?,
+                        "key": r.pubkey()?.to_string(),
+                    }))
+                })?;
+                Ok(json!({
+                    "type": "metadata",
+                    "info": {
+                        "update_authority": update_authority.to_string(),
+                        "mint": mint.to_string(),
+                        "name": name,
+                        "symbol": symbol,
+                        "uri": uri,
+                        "seller_fee_basis_points": seller_fee_basis_points,
+                        "creators": creators,
+                        "primary_sale_happened": primary_sale_happened,
+                        "is_mutable": is_mutable,
+                        "edition_nonce": edition_nonce,
+                        "token_standard": token_standard,
+                        "collection": collection,
+                    },
+                }))
+            }
+            MASTER_EDITION_V1 | MASTER_EDITION_V2 => Ok(json!({
+                "type": "master_edition",
+                "info": {
+                    "supply": r.u64()?,
+                    "max_supply": r.option(Reader::u64)?,
+                },
+            })),
+            EDITION_V1 => Ok(json!({
+                "type": "edition",
+                "info": {
+                    "parent": r.pubkey()?.to_string(),
+                    "edition": r.u64()?,
+                },
+            })),
+            tag => Err(format!("unsupported token metadata account type {}", tag)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_rpc_client::mock_sender::Mocks;
+    use solana_sdk::{program_option::COption, program_pack::Pack, system_instruction};
+
+    fn offline() -> RpcClient {
+        RpcClient::new_mock("fails")
+    }
+
+    #[test]
+    fn decodes_a_system_transfer() {
+        let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = system_instruction::transfer(&from, &to, 42);
+        let decoded = Registry::builtin()
+            .decode_instruction(&ix.program_id, &[from, to], &ix.data)
+            .unwrap();
+        assert_eq!(decoded.decoder, "system");
+        assert_eq!(
+            decoded.parsed.unwrap(),
+            json!({
+                "type": "transfer",
+                "info": {
+                    "source": from.to_string(),
+                    "destination": to.to_string(),
+                    "lamports": 42,
+                },
+            })
+        );
+
+        let bad = Registry::builtin()
+            .decode_instruction(&system_program::id(), &[from], &[9, 9])
+            .unwrap();
+        assert!(bad.parsed.is_none() && bad.error.is_some());
+    }
+
+    #[test]
+    fn token_accounts_use_the_mint_decimals() {
+        let (mint, owner, address) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut mint_data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint {
+            mint_authority: COption::None,
+            supply: 1_000_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+        .pack_into_slice(&mut mint_data);
+        let mut account_data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint,
+            owner,
+            amount: 2_500_000,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut account_data);
+
+        let mint_account = json!({
+            "context": { "slot": 1 },
+            "value": {
+                "lamports": 1,
+                "data": [base64::engine::general_purpose::STANDARD.encode(&mint_data), "base64"],
+                "owner": spl_token::id().to_string(),
+                "executable": false,
+                "rentEpoch": 0,
+                "space": mint_data.len(),
+            },
+        });
+        // The mock answers once; the second decode has to use the cache.
+        let client = RpcClient::new_mock_with_mocks(
+            "succeeds",
+            Mocks::from([(RpcRequest::GetAccountInfo, mint_account)]),
+        );
+        let ctx = Context::new(&client);
+        let registry = Registry::builtin();
+        for _ in 0..2 {
+            let decoded = registry
+                .decode_account(&ctx, &spl_token::id(), &address, &account_data)
+                .unwrap();
+            assert_eq!(decoded.decoder, "spl-token");
+            let parsed = decoded.parsed.unwrap();
+            assert_eq!(parsed["type"], "account");
+            assert_eq!(parsed["info"]["tokenAmount"]["uiAmountString"], "2.5");
+            assert_eq!(parsed["info"]["owner"], owner.to_string());
+        }
+        assert_eq!(ctx.mint_decimals(&mint), Some(6));
+        assert_eq!(ctx.mint_decimals(&Pubkey::new_unique()), None);
+    }
+
+    fn padded(w: &mut Vec<u8>, value: &str, len: usize) {
+        w.extend_from_slice(&(len as u32).to_le_bytes());
+        w.extend_from_slice(value.as_bytes());
+        w.resize(w.len() + len - value.len(), 0);
+    }
+
+    #[test]
+    fn decodes_token_metadata() {
+        let (authority, mint, creator, collection) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut data = vec![METADATA_V1];
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(mint.as_ref());
+        padded(&mut data, "Test NFT", 32);
+        padded(&mut data, "TST", 10);
+        padded(&mut data, "https://example.com/1.json", 200);
+        data.extend_from_slice(&500u16.to_le_bytes());
+        data.extend_from_slice(&[1, 1, 0, 0, 0]);
+        data.extend_from_slice(creator.as_ref());
+        data.extend_from_slice(&[1, 100]); // verified, share
+        data.extend_from_slice(&[0, 1]); // primary_sale_happened, is_mutable
+        data.extend_from_slice(&[1, 254]); // edition_nonce
+        data.extend_from_slice(&[1, 4]); // programmable_non_fungible
+        data.extend_from_slice(&[1, 0]);
+        data.extend_from_slice(collection.as_ref());
+        data.resize(679, 0);
+
+        let client = offline();
+        let decoded = Registry::builtin()
+            .decode_account(
+                &Context::new(&client),
+                &TOKEN_METADATA_PROGRAM_ID,
+                &Pubkey::new_unique(),
+                &data,
+            )
+            .unwrap();
+        assert_eq!(decoded.decoder, "token-metadata");
+        assert_eq!(
+            decoded.parsed.unwrap(),
+            json!({
+                "type": "metadata",
+                "info": {
+                    "update_authority": authority.to_string(),
+                    "mint": mint.to_string(),
+                    "name": "Test NFT",
+                    "symbol": "TST",
+                    "uri": "https://example.com/1.json",
+                    "seller_fee_basis_points": 500,
+                    "creators": [{ "address": creator.to_string(), "verified": true, "share": 100 }],
+                    "primary_sale_happened": false,
+                    "is_mutable": true,
+                    "edition_nonce": 254,
+                    "token_standard": "programmable_non_fungible",
+                    "collection": { "verified": false, "key": collection.to_string() },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_editions() {
+        let ctx_client = offline();
+        let ctx = Context::new(&ctx_client);
+        let decode = |data: &[u8]| TokenMetadata.decode_account(&ctx, &Pubkey::new_unique(), data);
+
+        let mut master = vec![MASTER_EDITION_V2];
+        master.extend_from_slice(&3u64.to_le_bytes());
+        master.extend_from_slice(&[1, 10, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            decode(&master).unwrap(),
+            json!({ "type": "master_edition", "info": { "supply": 3, "max_supply": 10 } })
+        );
+
+        let parent = Pubkey::new_unique();
+        let mut edition = vec![EDITION_V1];
+        edition.extend_from_slice(parent.as_ref());
+        edition.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(
+            decode(&edition).unwrap(),
+            json!({ "type": "edition", "info": { "parent": parent.to_string(), "edition": 7 } })
+        );
+
+        assert_eq!(
+            decode(&[9]).unwrap_err(),
+            "unsupported token metadata account type 9"
+        );
+    }
+
+    struct Fixed;
+
+    impl Decoder for Fixed {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn decode_account(&self, _: &Context, _: &Pubkey, _: &[u8]) -> Result<Value, String> {
+            Ok(json!("account"))
+        }
+    }
+
+    #[test]
+    fn registered_decoders_replace_builtins() {
+        let client = offline();
+        let ctx = Context::new(&client);
+        let registry = Registry::builtin();
+        let program = Pubkey::new_unique();
+        assert!(registry
+            .decode_account(&ctx, &program, &program, &[1])
+            .is_none());
+
+        registry.register(program, Arc::new(Fixed));
+        registry.register(system_program::id(), Arc::new(Fixed));
+        let decoded = registry
+            .decode_account(&ctx, &program, &program, &[1])
+            .unwrap();
+        assert_eq!(
+            (decoded.decoder.as_str(), decoded.parsed),
+            ("fixed", Some(json!("account")))
+        );
+        // No data, nothing to decode; no instruction or event support.
+        assert!(registry
+            .decode_account(&ctx, &program, &program, &[])
+            .is_none());
+        assert!(registry
+            .decode_instruction(&system_program::id(), &[], &[2])
+            .is_none());
+        assert!(registry.decode_event(&program, &[1]).is_none());
+    }
+}
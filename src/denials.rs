@@ -0,0 +1,312 @@
+use axum::http::StatusCode;
+
+use crate::{
+    clock,
+    config::Config,
+    envelope::{ErrorResponse, Retry},
+    handlers::{service_error, ApiError},
+    service::ServiceError,
+};
+
+// Why an airdrop was refused. `code` is part of the API: clients branch on
+// it, so it never changes once published. The descriptions end up in
+// /openapi.json.
+pub struct Denial {
+    pub code: &'static str,
+    pub status: StatusCode,
+    pub reason: &'static str,
+    pub retry: &'static str,
+}
+
+pub const AIRDROP_DENIALS: &[Denial] = &[
+    Denial {
+        code: "amount_over_cap",
+        status: StatusCode::BAD_REQUEST,
+        reason: "The amount is above max_airdrop_sol.",
+        retry: "Never; ask for less.",
+    },
+    Denial {
+        code: "amount_under_min",
+        status: StatusCode::BAD_REQUEST,
+        reason: "The amount is below min_airdrop_lamports.",
+        retry: "Never; ask for more.",
+    },
+    Denial {
+        code: "quota_exceeded",
+        status: StatusCode::TOO_MANY_REQUESTS,
+        reason: "The caller's (or its org's) daily airdrop quota can't cover the amount.",
+        retry: "After retry_after_secs, when the UTC day and the quota roll over.",
+    },
+    Denial {
+        code: "faucet_rate_limited",
+        status: StatusCode::TOO_MANY_REQUESTS,
+        reason: "The RPC node's faucet refused the airdrop as too frequent.",
+        retry: "Later; the faucet doesn't say when.",
+    },
+    Denial {
+        code: "wallet_denylisted",
+        status: StatusCode::FORBIDDEN,
+        reason: "The wallet is on the denylist.",
+        retry: "Never.",
+    },
+    Denial {
+        code: "wallet_not_allowlisted",
+        status: StatusCode::FORBIDDEN,
+        reason: "allowlist_only is on and the wallet isn't registered.",
+        retry: "Never, until the wallet is registered.",
+    },
+    Denial {
+        code: "siws_required",
+        status: StatusCode::UNAUTHORIZED,
+        reason: "siws_required is on and the caller hasn't signed in with the wallet.",
+        retry: "Right away, after signing in.",
+    },
+    Denial {
+        code: "wallet_mismatch",
+        status: StatusCode::FORBIDDEN,
+        reason: "The sign-in session belongs to another wallet.",
+        retry: "Never with this session.",
+    },
+    Denial {
+        code: "maintenance",
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        reason: "Airdrops are paused for maintenance (airdrop_maintenance).",
+        retry: "After retry_after_secs when the operator gave one, otherwise later.",
+    },
+    Denial {
+        code: "treasury_low",
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        reason: "The treasury is below treasury_low_watermark_sol; writes are paused.",
+        retry: "Later, once the treasury is refilled.",
+    },
+    Denial {
+        code: "faucet_exhausted",
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        reason: "The RPC node's faucet has no SOL left to give.",
+        retry: "Later, once the faucet is refilled.",
+    },
+];
+
+fn denial(code: &str) -> &'static Denial {
+    AIRDROP_DENIALS
+        .iter()
+        .find(|d| d.code == code)
+        .expect("every code used below is in AIRDROP_DENIALS")
+}
+
+fn secs_to_next_utc_day() -> u64 {
+    86_400 - clock::now_secs() % 86_400
+}
+
+// The denial an error stands for, if it is one, and when to retry.
+pub fn classify(err: &ServiceError) -> Option<(&'static Denial, Retry)> {
+    let (code, retry) = match err {
+        ServiceError::AirdropTooLarge { .. } => ("amount_over_cap", Retry::Never),
+        ServiceError::AirdropTooSmall { .. } => ("amount_under_min", Retry::Never),
+        ServiceError::QuotaExceeded { .. } => {
+            ("quota_exceeded", Retry::AfterSecs(secs_to_next_utc_day()))
+        }
+        ServiceError::FaucetRateLimited(_) => ("faucet_rate_limited", Retry::Later),
+        ServiceError::Denylisted(_) => ("wallet_denylisted", Retry::Never),
+        ServiceError::NotAllowlisted(_) => ("wallet_not_allowlisted", Retry::Never),
+        ServiceError::SignInRequired => ("siws_required", Retry::Later),
+        ServiceError::SessionWalletMismatch(_) => ("wallet_mismatch", Retry::Never),
+        ServiceError::Maintenance {
+            retry_after_secs, ..
+        } => (
+            "maintenance",
+            retry_after_secs.map_or(Retry::Later, Retry::AfterSecs),
+        ),
+        ServiceError::TreasuryLow => ("treasury_low", Retry::Later),
+        ServiceError::FaucetExhausted(_) => ("faucet_exhausted", Retry::Later),
+        _ => return None,
+    };
+    Some((denial(code), retry))
+}
+
+// For `service_error`: the response for a denial.
+pub fn error(err: ServiceError) -> ApiError {
+    match classify(&err) {
+        Some((denial, retry)) => (
+            denial.status,
+            ErrorResponse::new(denial.status, err.to_string(), Some(denial.code)).retry(retry),
+        ),
+        None => {
+            let status = StatusCode::INTERNAL_SERVER_ERROR;
+            (status, ErrorResponse::new(status, err.to_string(), None))
+        }
+    }
+}
+
+// Like `service_error`, with the operator's wording from
+// `airdrop_denial_messages` for denials that have one.
+pub fn airdrop_error(config: &Config, err: ServiceError) -> ApiError {
+    let (status, res) = service_error(err);
+    match res
+        .code()
+        .and_then(|code| config.airdrop_denial_messages.get(code))
+    {
+        Some(message) => (status, res.with_message(message.clone())),
+        None => (status, res),
+    }
+}
+
+pub fn validate_codes<'a>(mut codes: impl Iterator<Item = &'a String>) -> Result<(), String> {
+    match codes.find(|c| AIRDROP_DENIALS.iter().all(|d| d.code != c.as_str())) {
+        Some(code) => Err(format!(
+            "airdrop_denial_messages has an unknown code '{}'",
+            code
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use solana_sdk::pubkey::Pubkey;
+    use std::collections::HashSet;
+
+    async fn read(res: ErrorResponse) -> (Option<String>, serde_json::Value) {
+        let res = res.into_response();
+        let retry_after = res
+            .headers()
+            .get("retry-after")
+            .map(|v| v.to_str().unwrap().to_string());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (retry_after, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[test]
+    fn codes_are_unique() {
+        let codes: HashSet<_> = AIRDROP_DENIALS.iter().map(|d| d.code).collect();
+        assert_eq!(codes.len(), AIRDROP_DENIALS.len());
+    }
+
+    #[test]
+    fn errors_map_to_denials() {
+        let wallet = Pubkey::new_unique();
+        let cases = [
+            (
+                ServiceError::AirdropTooLarge { max_lamports: 1 },
+                "amount_over_cap",
+            ),
+            (
+                ServiceError::AirdropTooSmall { min_lamports: 1 },
+                "amount_under_min",
+            ),
+            (
+                ServiceError::QuotaExceeded {
+                    remaining_lamports: 0,
+                },
+                "quota_exceeded",
+            ),
+            (
+                ServiceError::FaucetRateLimited("slow down".into()),
+                "faucet_rate_limited",
+            ),
+            (ServiceError::Denylisted(wallet), "wallet_denylisted"),
+            (
+                ServiceError::NotAllowlisted(wallet),
+                "wallet_not_allowlisted",
+            ),
+            (ServiceError::SignInRequired, "siws_required"),
+            (
+                ServiceError::SessionWalletMismatch(wallet),
+                "wallet_mismatch",
+            ),
+            (
+                ServiceError::Maintenance {
+                    message: "upgrading".into(),
+                    retry_after_secs: None,
+                },
+                "maintenance",
+            ),
+            (ServiceError::TreasuryLow, "treasury_low"),
+            (
+                ServiceError::FaucetExhausted("dry".into()),
+                "faucet_exhausted",
+            ),
+        ];
+        // Every denial is reachable.
+        assert_eq!(cases.len(), AIRDROP_DENIALS.len());
+        for (err, code) in &cases {
+            assert_eq!(classify(err).unwrap().0.code, *code);
+        }
+        assert!(classify(&ServiceError::InvalidWallet).is_none());
+
+        let quota = ServiceError::QuotaExceeded {
+            remaining_lamports: 0,
+        };
+        assert!(matches!(
+            classify(&quota).unwrap().1,
+            Retry::AfterSecs(1..=86_400)
+        ));
+        let maintenance = ServiceError::Maintenance {
+            message: "upgrading".into(),
+            retry_after_secs: Some(120),
+        };
+        assert!(matches!(
+            classify(&maintenance).unwrap().1,
+            Retry::AfterSecs(120)
+        ));
+        assert!(matches!(
+            classify(&ServiceError::TreasuryLow).unwrap().1,
+            Retry::Later
+        ));
+    }
+
+    #[tokio::test]
+    async fn denial_responses() {
+        let (status, res) = error(ServiceError::Maintenance {
+            message: "upgrading".into(),
+            retry_after_secs: Some(120),
+        });
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let (retry_after, body) = read(res).await;
+        assert_eq!(retry_after.as_deref(), Some("120"));
+        assert_eq!(body["error"]["code"], "maintenance");
+
+        let (status, res) = error(ServiceError::InvalidWallet);
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(res.code(), None);
+    }
+
+    #[tokio::test]
+    async fn operators_can_reword_denials() {
+        let config = Config {
+            airdrop_denial_messages: [(
+                "treasury_low".to_string(),
+                "The faucet is resting.".to_string(),
+            )]
+            .into(),
+            ..Config::default()
+        };
+        let (status, res) = airdrop_error(&config, ServiceError::TreasuryLow);
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let (_, body) = read(res).await;
+        assert_eq!(body["error"]["message"], "The faucet is resting.");
+        assert_eq!(body["error"]["code"], "treasury_low");
+
+        let (_, res) = airdrop_error(&config, ServiceError::SignInRequired);
+        let (_, body) = read(res).await;
+        assert_eq!(
+            body["error"]["message"],
+            ServiceError::SignInRequired.to_string()
+        );
+    }
+
+    #[test]
+    fn unknown_message_codes_are_rejected() {
+        let known = ["maintenance".to_string(), "treasury_low".to_string()];
+        assert!(validate_codes(known.iter()).is_ok());
+        let unknown = ["maintenance".to_string(), "too_greedy".to_string()];
+        assert_eq!(
+            validate_codes(unknown.iter()).unwrap_err(),
+            "airdrop_denial_messages has an unknown code 'too_greedy'"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::HashSet,
+    fs,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    config::Config,
+    service::{self, ServiceError},
+};
+
+#[derive(Default)]
+pub struct Denylist {
+    addresses: RwLock<Arc<HashSet<Pubkey>>>,
+}
+
+impl Denylist {
+    pub fn check(&self, wallet: &str) -> Result<(), ServiceError> {
+        let pubkey = service::parse_wallet(wallet)?;
+        if self.addresses.read().unwrap().contains(&pubkey) {
+            return Err(ServiceError::Denylisted(pubkey));
+        }
+        Ok(())
+    }
+
+    // Re-reads every configured source. On failure the previous list stays
+    // in place so a flaky remote never silently empties it.
+    pub async fn refresh(&self, config: &Config) -> Result<usize, String> {
+        let mut addresses = HashSet::new();
+
+        if let Some(path) = &config.denylist_path {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format!("failed to read denylist '{}': {}", path.display(), e))?;
+            addresses
+                .extend(parse_addresses(&text).map_err(|e| format!("{}: {}", path.display(), e))?);
+        }
+        if let Some(url) = &config.denylist_url {
+            let text = fetch(url)
+                .await
+                .map_err(|e| format!("failed to fetch denylist '{}': {}", url, e))?;
+            addresses.extend(parse_addresses(&text).map_err(|e| format!("{}: {}", url, e))?);
+        }
+
+        let count = addresses.len();
+        *self.addresses.write().unwrap() = Arc::new(addresses);
+        Ok(count)
+    }
+}
+
+async fn fetch(url: &str) -> Result<String, reqwest::Error> {
+    reqwest::get(url).await?.error_for_status()?.text().await
+}
+
+// One base58 address per line; blank lines and `#` comments are ignored.
+pub fn parse_addresses(text: &str) -> Result<HashSet<Pubkey>, String> {
+    let mut addresses = HashSet::new();
+    for (i, line) in text.lines().enumerate() {
+        let entry = line.split('#').next().unwrap_or_default().trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let pubkey = Pubkey::from_str(entry)
+            .map_err(|_| format!("line {}: invalid address '{}'", i + 1, entry))?;
+        addresses.insert(pubkey);
+    }
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::get, Router};
+
+    #[test]
+    fn lists_skip_comments_and_blank_lines() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let text = format!("# scammers\n{}\n\n  {}  # reported twice\n{}\n", a, b, a);
+        assert_eq!(parse_addresses(&text).unwrap(), HashSet::from([a, b]));
+        assert_eq!(
+            parse_addresses("# header\n\nnot-an-address\n").unwrap_err(),
+            "line 3: invalid address 'not-an-address'"
+        );
+    }
+
+    #[tokio::test]
+    async fn refresh_merges_sources_and_keeps_the_list_on_failure() {
+        let (listed, remote, other) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let path = std::env::temp_dir().join(format!("denylist-test-{}.txt", std::process::id()));
+        fs::write(&path, format!("{}\n", listed)).unwrap();
+        let body = format!("{}\n", remote);
+        let app = Router::new()
+            .route("/denylist.txt", get(move || async move { body }))
+            .route("/missing.txt", get(|| async { StatusCode::NOT_FOUND }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut config = Config {
+            denylist_path: Some(path.clone()),
+            denylist_url: Some(format!("http://{}/denylist.txt", addr)),
+            ..Config::default()
+        };
+        let denylist = Denylist::default();
+        assert_eq!(denylist.refresh(&config).await, Ok(2));
+        assert!(matches!(
+            denylist.check(&listed.to_string()),
+            Err(ServiceError::Denylisted(wallet)) if wallet == listed
+        ));
+        assert!(denylist.check(&remote.to_string()).is_err());
+        assert!(denylist.check(&other.to_string()).is_ok());
+
+        config.denylist_url = Some(format!("http://{}/missing.txt", addr));
+        assert!(denylist.refresh(&config).await.is_err());
+        assert!(denylist.check(&remote.to_string()).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,508 @@
+use futures_util::future::join_all;
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::Path,
+    pin::Pin,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::now_ms,
+    config::{Config, StoreBackend},
+    pool, rpc, store,
+};
+
+pub use crate::api::{Dependency, DependencyReport, DependencyStatus};
+
+// Each check gives up after this and counts as down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+// A report is reused this long, so a public status page polling the
+// endpoint doesn't turn into load on every dependency.
+const REPORT_TTL: Duration = Duration::from_secs(10);
+// Wrapped SOL and USDC, for a quote that any working Jupiter API answers.
+const QUOTE_INPUT_MINT: &str = "So11111111111111111111111111111111111111112";
+const QUOTE_OUTPUT_MINT: &str = "EPjFWdd5AufqSSqeM2qjts7GmtLA5RzuXrqcKPqtEWx";
+
+#[derive(Default)]
+struct Seen {
+    last_success_at_ms: Option<u64>,
+    last_failure_at_ms: Option<u64>,
+}
+
+// By dependency name, for the whole process.
+static SEEN: Mutex<Option<HashMap<String, Seen>>> = Mutex::new(None);
+static LAST_REPORT: Mutex<Option<(Instant, DependencyReport)>> = Mutex::new(None);
+
+fn http() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(CHECK_TIMEOUT)
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+type Probe = Pin<Box<dyn Future<Output = Result<Option<String>, String>> + Send>>;
+
+struct Check {
+    name: &'static str,
+    kind: &'static str,
+    target: String,
+    required: bool,
+    probe: Probe,
+}
+
+// The configured dependencies, each checked on its own: the RPC node and
+// the other nodes it is compared with or falls back to, the DAS API, the
+// Jupiter price API, the persistent store and the remote lists.
+fn checks(config: &Config) -> Vec<Check> {
+    let mut checks = vec![Check {
+        name: "rpc",
+        kind: "rpc",
+        target: pool::redact(&config.rpc_url),
+        required: true,
+        probe: Box::pin(rpc_slot(config.rpc_url.clone())),
+    }];
+    for (name, url) in [
+        ("rpc_reference", &config.rpc_reference_url),
+        ("rpc_archival", &config.archival_rpc_url),
+    ] {
+        if let Some(url) = url {
+            checks.push(Check {
+                name,
+                kind: "rpc",
+                target: pool::redact(url),
+                required: false,
+                probe: Box::pin(rpc_slot(url.clone())),
+            });
+        }
+    }
+    if let Some(url) = &config.das_url {
+        checks.push(Check {
+            name: "das",
+            kind: "das",
+            target: pool::redact(url),
+            required: false,
+            probe: Box::pin(json_rpc(url.clone())),
+        });
+    }
+    let quote_url = format!("{}/quote", config.jupiter_api_url.trim_end_matches('/'));
+    checks.push(Check {
+        name: "jupiter",
+        kind: "price",
+        target: pool::redact(&config.jupiter_api_url),
+        required: false,
+        probe: Box::pin(async move {
+            let request = http().get(quote_url).query(&[
+                ("inputMint", QUOTE_INPUT_MINT),
+                ("outputMint", QUOTE_OUTPUT_MINT),
+                ("amount", "1000000000"),
+            ]);
+            http_get(request).await
+        }),
+    });
+    checks.push(Check {
+        name: "store",
+        kind: "store",
+        target: config.store_backend.to_string(),
+        required: true,
+        probe: Box::pin(store_probe(config)),
+    });
+    for (name, url) in [
+        ("denylist", &config.denylist_url),
+        ("jwks", &config.jwt_jwks_url),
+    ] {
+        if let Some(url) = url {
+            checks.push(Check {
+                name,
+                kind: "http",
+                target: pool::redact(url),
+                required: false,
+                probe: Box::pin(http_get(http().get(url))),
+            });
+        }
+    }
+    checks
+}
+
+async fn rpc_slot(url: String) -> Result<Option<String>, String> {
+    let slot = rpc::spawn_blocking(move || rpc::client(&url).get_slot().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(Some(format!("slot {}", slot)))
+}
+
+// reqwest's errors name the URL, key and all.
+fn without_url(e: reqwest::Error) -> String {
+    e.without_url().to_string()
+}
+
+// Any JSON-RPC answer, an error included, shows the endpoint is serving.
+async fn json_rpc(url: String) -> Result<Option<String>, String> {
+    let body = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "getHealth"});
+    let res = http()
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(without_url)?;
+    let status = res.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status));
+    }
+    let reply: serde_json::Value = res.json().await.map_err(without_url)?;
+    if reply.get("result").is_none() && reply.get("error").is_none() {
+        return Err("not a JSON-RPC response".to_string());
+    }
+    Ok(None)
+}
+
+async fn http_get(request: reqwest::RequestBuilder) -> Result<Option<String>, String> {
+    let res = request.send().await.map_err(without_url)?;
+    let status = res.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status));
+    }
+    Ok(None)
+}
+
+// Every store file can be read, and its directory written for saves. The
+// SQL backends are connected to and their tables set up.
+fn store_probe(config: &Config) -> impl Future<Output = Result<Option<String>, String>> {
+    let paths: Vec<_> = match config.store_backend {
+        StoreBackend::File => store::file_paths(config)
+            .into_iter()
+            .map(|(name, path)| (name, path.clone()))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let database = matches!(
+        config.store_backend,
+        StoreBackend::Sqlite | StoreBackend::Postgres
+    )
+    .then(|| config.clone());
+    async move {
+        if let Some(config) = database {
+            return tokio::task::spawn_blocking(move || {
+                store::open(&config).map(|_| Some("connected".to_string()))
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+        let count = paths.len();
+        tokio::task::spawn_blocking(move || {
+            for (name, path) in &paths {
+                let dir = path
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."));
+                let metadata = std::fs::metadata(dir)
+                    .map_err(|e| format!("{} ({}): {}", name, dir.display(), e))?;
+                if metadata.permissions().readonly() {
+                    return Err(format!("{} ({}): read-only", name, dir.display()));
+                }
+                if path.exists() {
+                    std::fs::File::open(path)
+                        .map_err(|e| format!("{} ({}): {}", name, path.display(), e))?;
+                }
+            }
+            Ok(Some(format!("{} persisted stores", count)))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+// Checks every configured dependency at once, or returns the report from
+// the last `REPORT_TTL`.
+pub async fn report(config: &Config) -> DependencyReport {
+    if let Some((at, report)) = LAST_REPORT.lock().unwrap().as_ref() {
+        if at.elapsed() < REPORT_TTL {
+            return report.clone();
+        }
+    }
+    let report = check(config).await;
+    *LAST_REPORT.lock().unwrap() = Some((Instant::now(), report.clone()));
+    report
+}
+
+async fn check(config: &Config) -> DependencyReport {
+    let checks = checks(config).into_iter().map(|check| async move {
+        let Check {
+            name,
+            kind,
+            target,
+            required,
+            probe,
+        } = check;
+        let started = Instant::now();
+        let result = tokio::time::timeout(CHECK_TIMEOUT, probe)
+            .await
+            .unwrap_or_else(|_| Err(format!("no answer in {}s", CHECK_TIMEOUT.as_secs())));
+        let (status, detail, error) = match result {
+            Ok(detail) => (DependencyStatus::Up, detail, None),
+            Err(e) => (DependencyStatus::Down, None, Some(e)),
+        };
+        Dependency {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            target,
+            required,
+            status,
+            latency_ms: started.elapsed().as_millis() as u64,
+            checked_at_ms: 0,
+            last_success_at_ms: None,
+            last_failure_at_ms: None,
+            detail,
+            error,
+        }
+    });
+    let mut dependencies = join_all(checks).await;
+
+    let checked_at_ms = now_ms();
+    let mut seen = SEEN.lock().unwrap();
+    let seen = seen.get_or_insert_with(HashMap::new);
+    for dependency in &mut dependencies {
+        let entry = seen.entry(dependency.name.clone()).or_default();
+        match dependency.status {
+            DependencyStatus::Up => entry.last_success_at_ms = Some(checked_at_ms),
+            DependencyStatus::Down => entry.last_failure_at_ms = Some(checked_at_ms),
+        }
+        dependency.checked_at_ms = checked_at_ms;
+        dependency.last_success_at_ms = entry.last_success_at_ms;
+        dependency.last_failure_at_ms = entry.last_failure_at_ms;
+    }
+
+    let down = |required: bool| {
+        dependencies
+            .iter()
+            .any(|d| d.required == required && d.status == DependencyStatus::Down)
+    };
+    let status = if down(true) {
+        "down"
+    } else if down(false) {
+        "degraded"
+    } else {
+        "ok"
+    };
+    DependencyReport {
+        status: status.to_string(),
+        checked_at_ms,
+        dependencies,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::get, routing::post, Json, Router};
+    use std::path::PathBuf;
+    use tokio::net::TcpListener;
+
+    // Answers the probes: a JSON-RPC node, a quote API and a plain page.
+    async fn serve() -> String {
+        let app = Router::new()
+            .route(
+                "/rpc",
+                post(|| async {
+                    Json(serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": "ok"}))
+                }),
+            )
+            .route(
+                "/rpc-error",
+                post(|| async {
+                    Json(serde_json::json!({"jsonrpc": "2.0", "id": 1, "error": {"code": -32601}}))
+                }),
+            )
+            .route(
+                "/not-rpc",
+                post(|| async { Json(serde_json::json!({"hello": 1})) }),
+            )
+            .route(
+                "/broken",
+                post(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+            )
+            .route("/quote", get(|| async { "{}" }))
+            .route("/list", get(|| async { "[]" }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        url
+    }
+
+    // A local port with nothing listening on it.
+    async fn closed() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    #[test]
+    fn only_configured_dependencies_are_checked() {
+        let names =
+            |config: &Config| -> Vec<&str> { checks(config).iter().map(|c| c.name).collect() };
+        assert_eq!(names(&Config::default()), ["rpc", "jupiter", "store"]);
+
+        let config = Config {
+            rpc_url: "https://rpc.example.com/?api-key=secret".to_string(),
+            rpc_reference_url: Some("https://api.devnet.solana.com".to_string()),
+            archival_rpc_url: Some("https://archive.example.com:8899/key".to_string()),
+            das_url: Some("https://das.example.com".to_string()),
+            denylist_url: Some("https://lists.example.com/deny".to_string()),
+            jwt_jwks_url: Some("https://auth.example.com/jwks".to_string()),
+            ..Config::default()
+        };
+        let checks = checks(&config);
+        let summary: Vec<_> = checks
+            .iter()
+            .map(|c| (c.name, c.kind, c.target.as_str(), c.required))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("rpc", "rpc", "https://rpc.example.com", true),
+                (
+                    "rpc_reference",
+                    "rpc",
+                    "https://api.devnet.solana.com",
+                    false
+                ),
+                (
+                    "rpc_archival",
+                    "rpc",
+                    "https://archive.example.com:8899",
+                    false
+                ),
+                ("das", "das", "https://das.example.com", false),
+                ("jupiter", "price", "https://lite-api.jup.ag", false),
+                ("store", "store", "file", true),
+                ("denylist", "http", "https://lists.example.com", false),
+                ("jwks", "http", "https://auth.example.com", false),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn json_rpc_accepts_any_answer() {
+        let url = serve().await;
+        assert_eq!(json_rpc(format!("{}/rpc", url)).await, Ok(None));
+        assert_eq!(json_rpc(format!("{}/rpc-error", url)).await, Ok(None));
+        assert_eq!(
+            json_rpc(format!("{}/not-rpc", url)).await,
+            Err("not a JSON-RPC response".to_string())
+        );
+        assert_eq!(
+            json_rpc(format!("{}/broken", url)).await,
+            Err("HTTP 500 Internal Server Error".to_string())
+        );
+        let error = json_rpc(closed().await).await.unwrap_err();
+        assert!(!error.contains("127.0.0.1"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn store_files_must_be_readable_and_writable() {
+        let dir = std::env::temp_dir().join(format!("dependencies-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("labels.json"), "[]").unwrap();
+        let config = Config {
+            labels_path: Some(dir.join("labels.json")),
+            programs_path: Some(dir.join("programs.json")),
+            ..Config::default()
+        };
+        assert_eq!(
+            store_probe(&config).await,
+            Ok(Some("2 persisted stores".to_string()))
+        );
+
+        let missing = PathBuf::from("/nonexistent/dependencies-test/labels.json");
+        let config = Config {
+            labels_path: Some(missing),
+            ..Config::default()
+        };
+        let error = store_probe(&config).await.unwrap_err();
+        assert!(
+            error.starts_with("labels (/nonexistent/dependencies-test): "),
+            "{}",
+            error
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reports_roll_up_required_and_optional_checks() {
+        rpc::mock_for_tests();
+        let url = serve().await;
+        let config = Config {
+            rpc_url: "http://mock".to_string(),
+            jupiter_api_url: url.clone(),
+            das_url: Some(format!("{}/rpc", url)),
+            denylist_url: Some(format!("{}/list", url)),
+            ..Config::default()
+        };
+        let report = check(&config).await;
+        assert_eq!(report.status, "ok");
+        for dependency in &report.dependencies {
+            assert_eq!(
+                dependency.status,
+                DependencyStatus::Up,
+                "{}",
+                dependency.name
+            );
+            assert_eq!(dependency.checked_at_ms, report.checked_at_ms);
+            assert_eq!(dependency.last_success_at_ms, Some(report.checked_at_ms));
+            assert!(dependency.error.is_none());
+        }
+        assert!(report.dependencies[0]
+            .detail
+            .as_deref()
+            .unwrap()
+            .starts_with("slot "));
+
+        let down = closed().await;
+        let report = check(&Config {
+            das_url: Some(down.clone()),
+            ..config.clone()
+        })
+        .await;
+        assert_eq!(report.status, "degraded");
+        let das = report
+            .dependencies
+            .iter()
+            .find(|d| d.name == "das")
+            .unwrap();
+        assert_eq!(das.status, DependencyStatus::Down);
+        assert_eq!(das.last_failure_at_ms, Some(report.checked_at_ms));
+        // The earlier success is still reported.
+        assert!(das.last_success_at_ms.unwrap() <= report.checked_at_ms);
+        assert!(das.error.is_some());
+
+        let report = check(&Config {
+            labels_path: Some(PathBuf::from("/nonexistent/dependencies-test/labels.json")),
+            ..config
+        })
+        .await;
+        assert_eq!(report.status, "down");
+        let store = report
+            .dependencies
+            .iter()
+            .find(|d| d.name == "store")
+            .unwrap();
+        assert_eq!(store.status, DependencyStatus::Down);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reports_are_reused_for_a_while() {
+        rpc::mock_for_tests();
+        let config = Config {
+            rpc_url: "http://mock".to_string(),
+            jupiter_api_url: closed().await,
+            ..Config::default()
+        };
+        let first = report(&config).await;
+        let second = report(&config).await;
+        assert_eq!(first.checked_at_ms, second.checked_at_ms);
+        assert_eq!(first.status, second.status);
+    }
+}
@@ -0,0 +1,335 @@
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::Transaction,
+};
+
+use crate::{
+    confirm, rpc,
+    service::{rpc_error, ServiceError},
+};
+
+// Leaves room for the signature, message header and write instruction
+// metadata inside the 1232 byte packet limit.
+const WRITE_CHUNK_SIZE: usize = 900;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+// Serialized UpgradeableLoaderState::Buffer tag, followed by Some(authority).
+const BUFFER_TAG: [u8; 4] = [1, 0, 0, 0];
+const BUFFER_AUTHORITY_OFFSET: usize = 4;
+
+pub struct Deployment {
+    pub program_id: Pubkey,
+    pub buffer: Pubkey,
+    pub signature: Signature,
+    pub upgraded: bool,
+}
+
+pub struct BufferAccount {
+    pub address: Pubkey,
+    pub lamports: u64,
+}
+
+fn send(
+    client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    extra_signers: &[&Keypair],
+    action: &'static str,
+) -> Result<Signature, ServiceError> {
+    let blockhash = client.get_latest_blockhash().map_err(rpc_error(action))?;
+    let mut signers = vec![payer];
+    signers.extend_from_slice(extra_signers);
+
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &signers,
+        blockhash,
+    );
+    confirm::send_and_confirm(client, &tx, action)
+}
+
+pub fn validate_program(program: &[u8], max_size: usize) -> Result<(), ServiceError> {
+    if program.is_empty() {
+        return Err(ServiceError::Invalid("Program file is empty".to_string()));
+    }
+    if program.len() > max_size {
+        return Err(ServiceError::Invalid(format!(
+            "Program is {} bytes, larger than the {} byte limit",
+            program.len(),
+            max_size
+        )));
+    }
+    if !program.starts_with(ELF_MAGIC) {
+        return Err(ServiceError::Invalid(
+            "Program is not a compiled BPF shared object (.so)".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Upgrades are only possible when the server keypair is the program's
+// upgrade authority; checking up front avoids leaving a funded buffer behind.
+fn check_upgrade_authority(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    authority: &Pubkey,
+) -> Result<(), ServiceError> {
+    let (programdata, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    let account = client.get_account(&programdata).map_err(|_| {
+        ServiceError::Invalid(format!("{} is not an upgradeable program", program_id))
+    })?;
+
+    match account.deserialize_data::<UpgradeableLoaderState>() {
+        Ok(UpgradeableLoaderState::ProgramData {
+            upgrade_authority_address: Some(current),
+            ..
+        }) if current == *authority => Ok(()),
+        Ok(UpgradeableLoaderState::ProgramData { .. }) => Err(ServiceError::Invalid(format!(
+            "Server keypair is not the upgrade authority of {}",
+            program_id
+        ))),
+        _ => Err(ServiceError::Invalid(format!(
+            "{} is not an upgradeable program",
+            program_id
+        ))),
+    }
+}
+
+// Writes the program into a fresh buffer and then deploys it (or upgrades
+// `program_id`), with the server keypair as payer and upgrade authority.
+// `progress` is called with (step, completed, total) as chunks land.
+pub fn deploy_program(
+    rpc_url: &str,
+    payer: &Keypair,
+    program: &[u8],
+    program_id: Option<Pubkey>,
+    progress: impl Fn(&str, u64, u64),
+) -> Result<Deployment, ServiceError> {
+    let client = rpc::client(rpc_url);
+    let authority = payer.pubkey();
+
+    if let Some(program_id) = &program_id {
+        check_upgrade_authority(&client, program_id, &authority)?;
+    }
+
+    let chunks: Vec<&[u8]> = program.chunks(WRITE_CHUNK_SIZE).collect();
+    let total = chunks.len() as u64 + 2;
+
+    progress("create_buffer", 0, total);
+    let buffer = Keypair::new();
+    let buffer_lamports = client
+        .get_minimum_balance_for_rent_exemption(UpgradeableLoaderState::size_of_buffer(
+            program.len(),
+        ))
+        .map_err(rpc_error("Failed to get rent exemption"))?;
+    let create = bpf_loader_upgradeable::create_buffer(
+        &authority,
+        &buffer.pubkey(),
+        &authority,
+        buffer_lamports,
+        program.len(),
+    )
+    .map_err(|e| ServiceError::Invalid(e.to_string()))?;
+    send(
+        &client,
+        &create,
+        payer,
+        &[&buffer],
+        "Failed to create program buffer",
+    )?;
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        progress("write_buffer", i as u64 + 1, total);
+        let write = bpf_loader_upgradeable::write(
+            &buffer.pubkey(),
+            &authority,
+            (i * WRITE_CHUNK_SIZE) as u32,
+            chunk.to_vec(),
+        );
+        send(
+            &client,
+            &[write],
+            payer,
+            &[],
+            "Failed to write program buffer",
+        )?;
+    }
+
+    progress("deploy", total - 1, total);
+    let deployment = match program_id {
+        Some(program_id) => {
+            let upgrade = bpf_loader_upgradeable::upgrade(
+                &program_id,
+                &buffer.pubkey(),
+                &authority,
+                &authority,
+            );
+            let signature = send(&client, &[upgrade], payer, &[], "Program upgrade failed")?;
+            Deployment {
+                program_id,
+                buffer: buffer.pubkey(),
+                signature,
+                upgraded: true,
+            }
+        }
+        None => {
+            let program_keypair = Keypair::new();
+            let program_lamports = client
+                .get_minimum_balance_for_rent_exemption(UpgradeableLoaderState::size_of_program())
+                .map_err(rpc_error("Failed to get rent exemption"))?;
+            // Leave headroom so later upgrades can grow the program.
+            let deploy = bpf_loader_upgradeable::deploy_with_max_program_len(
+                &authority,
+                &program_keypair.pubkey(),
+                &buffer.pubkey(),
+                &authority,
+                program_lamports,
+                program.len() * 2,
+            )
+            .map_err(|e| ServiceError::Invalid(e.to_string()))?;
+            let signature = send(
+                &client,
+                &deploy,
+                payer,
+                &[&program_keypair],
+                "Program deployment failed",
+            )?;
+            Deployment {
+                program_id: program_keypair.pubkey(),
+                buffer: buffer.pubkey(),
+                signature,
+                upgraded: false,
+            }
+        }
+    };
+
+    progress("done", total, total);
+    Ok(deployment)
+}
+
+// Buffers left behind by failed or abandoned deployments still hold their
+// rent. Only buffers whose authority is the server keypair can be closed.
+pub fn list_buffers(rpc_url: &str, authority: &Pubkey) -> Result<Vec<BufferAccount>, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let mut authority_filter = vec![1];
+    authority_filter.extend_from_slice(authority.as_ref());
+
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, BUFFER_TAG.to_vec())),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                BUFFER_AUTHORITY_OFFSET,
+                authority_filter,
+            )),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig {
+                offset: 0,
+                length: 0,
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let accounts = client
+        .get_program_accounts_with_config(&bpf_loader_upgradeable::id(), config)
+        .map_err(rpc_error("Failed to list program buffers"))?;
+
+    Ok(accounts
+        .into_iter()
+        .map(|(address, account)| BufferAccount {
+            address,
+            lamports: account.lamports,
+        })
+        .collect())
+}
+
+pub fn close_buffer(
+    rpc_url: &str,
+    authority: &Keypair,
+    buffer: &Pubkey,
+) -> Result<Signature, ServiceError> {
+    let client = rpc::client(rpc_url);
+    let close = bpf_loader_upgradeable::close_any(
+        buffer,
+        &authority.pubkey(),
+        Some(&authority.pubkey()),
+        None,
+    );
+    send(
+        &client,
+        &[close],
+        authority,
+        &[],
+        "Failed to close program buffer",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn programs_must_be_small_elf_files() {
+        let program = [ELF_MAGIC, &[0; 60]].concat();
+        assert!(validate_program(&program, 64).is_ok());
+        assert_eq!(
+            validate_program(&[], 64).unwrap_err().to_string(),
+            "Program file is empty"
+        );
+        assert_eq!(
+            validate_program(&program, 63).unwrap_err().to_string(),
+            "Program is 64 bytes, larger than the 63 byte limit"
+        );
+        assert!(validate_program(b"#!/bin/sh", 64).is_err());
+    }
+
+    #[test]
+    fn buffer_filters_match_the_loader_layout() {
+        let authority = Pubkey::new_unique();
+        let data = bincode::serialize(&UpgradeableLoaderState::Buffer {
+            authority_address: Some(authority),
+        })
+        .unwrap();
+        assert_eq!(data[..4], BUFFER_TAG);
+        // Some(...) is tagged 1 before the authority.
+        assert_eq!(data[BUFFER_AUTHORITY_OFFSET], 1);
+        assert_eq!(data[BUFFER_AUTHORITY_OFFSET + 1..], authority.to_bytes());
+    }
+
+    #[test]
+    fn upgrades_need_an_existing_program() {
+        rpc::mock_for_tests();
+        let payer = Keypair::new();
+        let program_id = Pubkey::new_unique();
+        let program = [ELF_MAGIC, &[0; 60]].concat();
+        let Err(err) = deploy_program(
+            "http://mock",
+            &payer,
+            &program,
+            Some(program_id),
+            |step, _, _| panic!("started {} before checking the upgrade authority", step),
+        ) else {
+            panic!("upgraded a program that doesn't exist");
+        };
+        assert_eq!(
+            err.to_string(),
+            format!("{} is not an upgradeable program", program_id)
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use axum::{
+    body::{self, Body},
+    extract::{MatchedPath, Query, Request, State},
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+
+use crate::{
+    clock,
+    handlers::{error_response, service_error},
+    service::ServiceError,
+    state::AppState,
+};
+
+// Set on responses to requests that used a deprecated field name.
+const WARNING_HEADER: &str = "x-deprecated-fields";
+// Request bodies of the routes below are small; anything larger is left
+// for the handler's extractor to reject.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+// Request fields renamed in a newer version of the API, as (route, old
+// name, new name). The request types accept the old names through
+// `#[serde(alias)]`; this layer tells callers still sending them, and
+// refuses them from `deprecated_fields_cutoff` on.
+const RENAMED: &[(&str, &str, &str)] = &[
+    ("/get_balance", "wallet", "address"),
+    ("/get_airdrop", "wallet", "address"),
+    ("/get_airdrop", "sol", "amount_sol"),
+];
+
+// Days since 1970-01-01 of a "YYYY-MM-DD" date.
+pub fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if date.len() != 10 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(clock::days_from_civil(year, month, day))
+}
+
+fn today() -> i64 {
+    (clock::now_secs() / 86_400) as i64
+}
+
+// Top-level keys of a JSON or MessagePack object body.
+fn body_keys(bytes: &[u8]) -> Vec<String> {
+    let value = serde_json::from_slice::<serde_json::Value>(bytes)
+        .ok()
+        .or_else(|| rmp_serde::from_slice(bytes).ok());
+    match value {
+        Some(serde_json::Value::Object(fields)) => fields.keys().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Layered on the whole app, next to the feature flags.
+pub async fn check(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(route) = req.extensions().get::<MatchedPath>().map(|p| p.as_str()) else {
+        return next.run(req).await;
+    };
+    let renamed: Vec<(&str, &str)> = RENAMED
+        .iter()
+        .filter(|(r, _, _)| *r == route)
+        .map(|(_, old, new)| (*old, *new))
+        .collect();
+    if renamed.is_empty() {
+        return next.run(req).await;
+    }
+
+    let (parts, body) = req.into_parts();
+    let mut keys: Vec<String> = Query::<HashMap<String, String>>::try_from_uri(&parts.uri)
+        .map(|Query(params)| params.into_keys().collect())
+        .unwrap_or_default();
+    let bytes = match body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return error_response(StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large")
+                .into_response()
+        }
+    };
+    keys.extend(body_keys(&bytes));
+    let req = Request::from_parts(parts, Body::from(bytes));
+
+    let used: Vec<String> = renamed
+        .iter()
+        .filter(|(old, _)| keys.iter().any(|k| k == old))
+        .map(|(old, new)| format!("{} (use {})", old, new))
+        .collect();
+    if used.is_empty() {
+        return next.run(req).await;
+    }
+
+    let cutoff = state
+        .config()
+        .deprecated_fields_cutoff
+        .as_deref()
+        .and_then(|date| parse_date(date).map(|day| (date.to_string(), day)));
+    let warning = match &cutoff {
+        Some((_, day)) if today() >= *day => {
+            return service_error(ServiceError::DeprecatedFields(used.join(", "))).into_response()
+        }
+        Some((date, _)) => format!("{}; rejected from {}", used.join(", "), date),
+        None => used.join(", "),
+    };
+    let mut res = next.run(req).await;
+    if let Ok(value) = HeaderValue::from_str(&warning) {
+        res.headers_mut().insert(WARNING_HEADER, value);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use serde_json::json;
+
+    #[test]
+    fn dates_to_days() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("1969-12-31"), Some(-1));
+        assert_eq!(parse_date("2000-03-01"), Some(11_017));
+        assert_eq!(parse_date("2024-02-29"), Some(19_782));
+        for bad in [
+            "2024-13-01",
+            "2024-00-10",
+            "2024-01-32",
+            "2024-1-01",
+            "24-01-01x",
+            "",
+        ] {
+            assert_eq!(parse_date(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn keys_of_json_and_msgpack_bodies() {
+        let mut keys = body_keys(br#"{"wallet":"x","sol":1}"#);
+        keys.sort();
+        assert_eq!(keys, ["sol", "wallet"]);
+        let packed = rmp_serde::to_vec_named(&json!({ "address": "x" })).unwrap();
+        assert_eq!(body_keys(&packed), ["address"]);
+        assert!(body_keys(b"[1,2]").is_empty());
+        assert!(body_keys(b"").is_empty());
+    }
+
+    async fn serve(cutoff: Option<&str>) -> String {
+        use axum::{middleware, routing::post, Router};
+
+        let state = AppState::for_tests(Config {
+            deprecated_fields_cutoff: cutoff.map(String::from),
+            ..Config::default()
+        });
+        let app = Router::new()
+            .route("/get_balance", post(|| async { "ok" }))
+            .route("/other", post(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(state.clone(), check))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        url
+    }
+
+    #[tokio::test]
+    async fn old_names_are_flagged_then_refused() {
+        let client = reqwest::Client::new();
+        let url = serve(None).await;
+        let post = |path: &str, body: serde_json::Value| {
+            client.post(format!("{}{}", url, path)).json(&body).send()
+        };
+
+        let res = post("/get_balance", json!({ "wallet": "x" }))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers()[WARNING_HEADER], "wallet (use address)");
+        let res = post("/get_balance", json!({ "address": "x" }))
+            .await
+            .unwrap();
+        assert!(res.headers().get(WARNING_HEADER).is_none());
+        let res = post("/other", json!({ "wallet": "x" })).await.unwrap();
+        assert!(res.headers().get(WARNING_HEADER).is_none());
+        let res = client
+            .post(format!("{}/get_balance?wallet=x", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.headers()[WARNING_HEADER], "wallet (use address)");
+
+        let url = serve(Some("9999-12-31")).await;
+        let res = client
+            .post(format!("{}/get_balance", url))
+            .json(&json!({ "wallet": "x" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            res.headers()[WARNING_HEADER],
+            "wallet (use address); rejected from 9999-12-31"
+        );
+
+        let url = serve(Some("2000-01-01")).await;
+        let res = client
+            .post(format!("{}/get_balance", url))
+            .json(&json!({ "wallet": "x" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 400);
+        assert!(res.text().await.unwrap().contains("deprecated_field"));
+    }
+}
@@ -0,0 +1,267 @@
+use solana_account_decoder::{parse_token::spl_token_ids, UiAccountData};
+use solana_client::{
+    rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient},
+    rpc_request::TokenAccountsFilter,
+};
+use solana_sdk::{clock::Slot, commitment_config::CommitmentConfig, signature::Signature};
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::{
+    history::{self, Point},
+    rpc,
+    service::{self, rpc_error, ServiceError},
+};
+
+const SIGNATURE_PAGE_SIZE: usize = 1_000;
+// Token deltas between slots need one RPC call per transaction in range.
+const MAX_RANGE_TRANSACTIONS: usize = 500;
+
+#[derive(Clone, Copy)]
+pub struct TokenHolding {
+    pub decimals: u8,
+    pub amount: u128,
+}
+
+pub struct TokenDiff {
+    pub mint: String,
+    pub decimals: u8,
+    // Only known when comparing current holdings of two wallets.
+    pub from_amount: Option<u128>,
+    pub to_amount: Option<u128>,
+    pub delta: i128,
+}
+
+pub struct WalletDiff {
+    pub from_lamports: u64,
+    pub to_lamports: u64,
+    pub tokens: Vec<TokenDiff>,
+}
+
+impl WalletDiff {
+    pub fn lamports_delta(&self) -> i128 {
+        self.to_lamports as i128 - self.from_lamports as i128
+    }
+}
+
+pub fn token_holdings(
+    client: &RpcClient,
+    wallet: &str,
+) -> Result<BTreeMap<String, TokenHolding>, ServiceError> {
+    let pubkey = service::parse_wallet(wallet)?;
+    let mut holdings: BTreeMap<String, TokenHolding> = BTreeMap::new();
+
+    for program_id in spl_token_ids() {
+        let accounts = client
+            .get_token_accounts_by_owner(&pubkey, TokenAccountsFilter::ProgramId(program_id))
+            .map_err(rpc_error("Failed to get token accounts"))?;
+
+        for keyed in accounts {
+            let UiAccountData::Json(parsed) = keyed.account.data else {
+                continue;
+            };
+            let info = &parsed.parsed["info"];
+            let (Some(mint), Some(amount), Some(decimals)) = (
+                info["mint"].as_str(),
+                info["tokenAmount"]["amount"]
+                    .as_str()
+                    .and_then(|a| a.parse::<u128>().ok()),
+                info["tokenAmount"]["decimals"].as_u64(),
+            ) else {
+                continue;
+            };
+            let entry = holdings.entry(mint.to_string()).or_insert(TokenHolding {
+                decimals: decimals as u8,
+                amount: 0,
+            });
+            entry.amount += amount;
+        }
+    }
+    Ok(holdings)
+}
+
+// Current SOL and token holdings of `to` minus those of `from`.
+pub fn diff_wallets(rpc_url: &str, from: &str, to: &str) -> Result<WalletDiff, ServiceError> {
+    let from_lamports = service::get_balance(rpc_url, from)?;
+    let to_lamports = service::get_balance(rpc_url, to)?;
+
+    let client = rpc::client(rpc_url);
+    let from_tokens = token_holdings(&client, from)?;
+    let to_tokens = token_holdings(&client, to)?;
+
+    let mut mints: Vec<&String> = from_tokens.keys().chain(to_tokens.keys()).collect();
+    mints.sort();
+    mints.dedup();
+
+    let tokens = mints
+        .into_iter()
+        .filter_map(|mint| {
+            let before = from_tokens.get(mint);
+            let after = to_tokens.get(mint);
+            let from_amount = before.map_or(0, |h| h.amount);
+            let to_amount = after.map_or(0, |h| h.amount);
+            if from_amount == to_amount {
+                return None;
+            }
+            Some(TokenDiff {
+                mint: mint.clone(),
+                decimals: after.or(before).map_or(0, |h| h.decimals),
+                from_amount: Some(from_amount),
+                to_amount: Some(to_amount),
+                delta: to_amount as i128 - from_amount as i128,
+            })
+        })
+        .collect();
+
+    Ok(WalletDiff {
+        from_lamports,
+        to_lamports,
+        tokens,
+    })
+}
+
+// Change in one wallet's holdings between two slots. SOL comes from the
+// historical balance at each slot; token deltas are summed from the
+// transactions in (from_slot, to_slot] that reference the wallet.
+pub fn diff_slots(
+    rpc_url: &str,
+    wallet: &str,
+    from_slot: Slot,
+    to_slot: Slot,
+) -> Result<WalletDiff, ServiceError> {
+    if from_slot > to_slot {
+        return Err(ServiceError::Invalid(
+            "'from_slot' must not be after 'to_slot'".to_string(),
+        ));
+    }
+
+    let from = history::balance_at(rpc_url, wallet, Point::Slot(from_slot))?;
+    let to = history::balance_at(rpc_url, wallet, Point::Slot(to_slot))?;
+
+    let pubkey = service::parse_wallet(wallet)?;
+    let owner = pubkey.to_string();
+    let client = rpc::client(rpc_url);
+    let mut deltas: BTreeMap<String, (u8, i128)> = BTreeMap::new();
+    let mut fetched = 0;
+    let mut before = None;
+
+    'pages: loop {
+        let page = client
+            .get_signatures_for_address_with_config(
+                &pubkey,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(SIGNATURE_PAGE_SIZE),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .map_err(rpc_error("Failed to get signatures"))?;
+
+        for sig in &page {
+            if sig.slot <= from_slot {
+                break 'pages;
+            }
+            if sig.slot > to_slot || sig.err.is_some() {
+                continue;
+            }
+            if fetched == MAX_RANGE_TRANSACTIONS {
+                return Err(ServiceError::HistoryUnavailable(format!(
+                    "{} has more than {} transactions between slots {} and {}",
+                    pubkey, MAX_RANGE_TRANSACTIONS, from_slot, to_slot
+                )));
+            }
+            fetched += 1;
+
+            let tx = history::fetch_transaction(&client, &sig.signature)?;
+            let Some(meta) = tx.transaction.meta.as_ref() else {
+                continue;
+            };
+            let pre = history::owned_token_amounts(meta.pre_token_balances.as_ref().into(), &owner);
+            let post =
+                history::owned_token_amounts(meta.post_token_balances.as_ref().into(), &owner);
+            for (mint, (decimals, amount)) in &post {
+                deltas.entry(mint.clone()).or_insert((*decimals, 0)).1 += *amount as i128;
+            }
+            for (mint, (decimals, amount)) in &pre {
+                deltas.entry(mint.clone()).or_insert((*decimals, 0)).1 -= *amount as i128;
+            }
+        }
+
+        if page.len() < SIGNATURE_PAGE_SIZE {
+            break;
+        }
+        before = page
+            .last()
+            .and_then(|last| Signature::from_str(&last.signature).ok());
+    }
+
+    let tokens = deltas
+        .into_iter()
+        .filter(|(_, (_, delta))| *delta != 0)
+        .map(|(mint, (decimals, delta))| TokenDiff {
+            mint,
+            decimals,
+            from_amount: None,
+            to_amount: None,
+            delta,
+        })
+        .collect();
+
+    Ok(WalletDiff {
+        from_lamports: from.lamports,
+        to_lamports: to.lamports,
+        tokens,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+    };
+
+    fn slot() -> Slot {
+        rpc::client("http://mock").get_slot().unwrap()
+    }
+
+    #[test]
+    fn wallets_are_compared_by_current_holdings() {
+        let chain = rpc::mock_for_tests();
+        let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        chain.set_balance(from, 5_000);
+        chain.set_balance(to, 2_000);
+
+        let diff = diff_wallets("http://mock", &from.to_string(), &to.to_string()).unwrap();
+        assert_eq!((diff.from_lamports, diff.to_lamports), (5_000, 2_000));
+        assert_eq!(diff.lamports_delta(), -3_000);
+        assert!(diff.tokens.is_empty());
+        assert!(matches!(
+            diff_wallets("http://mock", "nope", &to.to_string()),
+            Err(ServiceError::InvalidWallet)
+        ));
+    }
+
+    #[test]
+    fn slots_are_compared_by_historical_balance() {
+        let chain = rpc::mock_for_tests();
+        let payer = Keypair::new();
+        let wallet = Pubkey::new_unique().to_string();
+        chain.set_balance(payer.pubkey(), 1_000_000);
+
+        service::transfer("http://mock", &payer, &wallet.parse().unwrap(), 700, false).unwrap();
+        let from_slot = slot();
+        service::transfer("http://mock", &payer, &wallet.parse().unwrap(), 300, false).unwrap();
+        let to_slot = slot();
+
+        let diff = diff_slots("http://mock", &wallet, from_slot, to_slot).unwrap();
+        assert_eq!((diff.from_lamports, diff.to_lamports), (700, 1_000));
+        assert_eq!(diff.lamports_delta(), 300);
+        assert!(diff.tokens.is_empty());
+        assert!(matches!(
+            diff_slots("http://mock", &wallet, to_slot, from_slot),
+            Err(ServiceError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,866 @@
+use axum::{
+    body::Bytes,
+    extract::{rejection::QueryRejection, FromRequest, Query, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{
+    de::{DeserializeOwned, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use crate::{api, freshness::SlotStatus, labels::LabelStore, rpc, state::AppState};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+const MSGPACK: &str = "application/msgpack";
+const MSGPACK_TYPES: [&str; 2] = [MSGPACK, "application/x-msgpack"];
+
+// Wire format for bodies, picked per request: MessagePack when the client
+// asks for it, JSON otherwise.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Format {
+    #[default]
+    Json,
+    MsgPack,
+}
+
+fn is_msgpack(media_type: &str) -> bool {
+    let essence = media_type.split(';').next().unwrap_or_default().trim();
+    MSGPACK_TYPES
+        .iter()
+        .any(|t| essence.eq_ignore_ascii_case(t))
+}
+
+// An Accept entry with `q=0` names a type the client refuses.
+fn refused(media_range: &str) -> bool {
+    media_range.split(';').skip(1).any(|param| {
+        param
+            .trim()
+            .strip_prefix("q=")
+            .and_then(|q| q.trim().parse::<f32>().ok())
+            == Some(0.0)
+    })
+}
+
+impl Format {
+    // MessagePack when any of the accepted media types is one of its names.
+    fn accepted(headers: &HeaderMap) -> Self {
+        let accepts = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|range| is_msgpack(range) && !refused(range));
+        if accepts {
+            Format::MsgPack
+        } else {
+            Format::Json
+        }
+    }
+
+    fn of_body(headers: &HeaderMap) -> Self {
+        match headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+        {
+            Some(content_type) if is_msgpack(content_type) => Format::MsgPack,
+            _ => Format::Json,
+        }
+    }
+
+    fn respond(self, value: &impl Serialize) -> Response {
+        match self {
+            Format::Json => axum::Json(value).into_response(),
+            // Named fields, so decoded bodies have the same keys as JSON.
+            Format::MsgPack => match rmp_serde::to_vec_named(value) {
+                Ok(bytes) => ([(header::CONTENT_TYPE, MSGPACK)], bytes).into_response(),
+                Err(e) => (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to encode response: {}", e),
+                )
+                    .into_response(),
+            },
+        }
+    }
+}
+
+// How responses produced while handling the current request are shaped.
+// Set by `scope` around every route; outside it responses use the JSON
+// envelope without a request id.
+#[derive(Clone, Default)]
+struct ResponseContext {
+    legacy: bool,
+    format: Format,
+    request_id: Option<String>,
+    // Set by `freshness::attach` on read routes.
+    freshness: Option<SlotStatus>,
+    observed_slot: Option<Arc<AtomicU64>>,
+    // Set while any address has a label.
+    labels: Option<Arc<LabelStore>>,
+    // JSON bodies carry lamport amounts as strings.
+    lamports_as_string: bool,
+    // Request bodies with fields their endpoint doesn't take are rejected.
+    strict: bool,
+}
+
+impl ResponseContext {
+    fn respond(&self, value: &impl Serialize) -> Response {
+        api::lamports::as_strings(self.lamports_as_string, || self.format.respond(value))
+    }
+}
+
+// Per-request overrides of `lamports_as_string` and `strict_requests`,
+// from the query string.
+#[derive(Default, Deserialize)]
+struct RequestOptions {
+    lamports_as_string: Option<bool>,
+    strict: Option<bool>,
+}
+
+tokio::task_local! {
+    static CONTEXT: ResponseContext;
+}
+
+fn context() -> ResponseContext {
+    CONTEXT.try_with(Clone::clone).unwrap_or_default()
+}
+
+// The id of the request being handled, for logs.
+pub fn request_id() -> Option<String> {
+    CONTEXT.try_with(|c| c.request_id.clone()).ok().flatten()
+}
+
+// Every body the API returns goes through here:
+// `{"success":true,"data":...}`, or the bare value with `legacy_responses`,
+// in JSON or MessagePack as the client asked.
+pub struct ResponseJson<T>(pub T);
+
+#[derive(Serialize)]
+struct Success<T> {
+    success: bool,
+    data: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<ReadContext>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_freshness: Option<SlotStatus>,
+    // The label of each known address in `data`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    labels: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Serialize)]
+struct ReadContext {
+    // The oldest slot any RPC answer behind the response was observed at;
+    // everything in `data` is at least this current.
+    slot: u64,
+}
+
+impl<T: Serialize> IntoResponse for ResponseJson<T> {
+    fn into_response(self) -> Response {
+        let context = context();
+        if context.legacy {
+            return context.respond(&self.0);
+        }
+        let labels = context.labels.as_ref().and_then(|labels| {
+            let names = labels.names(&serde_json::to_value(&self.0).ok()?);
+            (!names.is_empty()).then_some(names)
+        });
+        context.respond(&Success {
+            success: true,
+            data: self.0,
+            labels,
+            context: context
+                .observed_slot
+                .as_ref()
+                .map(|slot| slot.load(Ordering::Relaxed))
+                .filter(|slot| *slot != u64::MAX)
+                .map(|slot| ReadContext { slot }),
+            data_freshness: context.freshness.clone(),
+        })
+    }
+}
+
+// What a 5xx ErrorResponse said, left in its extensions for
+// `sentry::capture_errors`.
+#[cfg(feature = "sentry")]
+#[derive(Clone)]
+pub struct ServerError {
+    pub message: String,
+    pub code: Option<&'static str>,
+}
+
+#[derive(Debug)]
+pub struct ErrorResponse {
+    status: StatusCode,
+    message: String,
+    // Stable machine-readable reason for errors clients need to tell apart.
+    code: Option<&'static str>,
+    retry: Option<Retry>,
+}
+
+// Whether sending the same request again can succeed.
+#[derive(Clone, Copy, Debug)]
+pub enum Retry {
+    Never,
+    // Once the cause clears; when isn't known.
+    Later,
+    AfterSecs(u64),
+}
+
+#[derive(Serialize)]
+struct Failure {
+    success: bool,
+    error: ErrorBody,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    // The specific reason when there is one, otherwise the status in
+    // snake case, e.g. "bad_request".
+    code: String,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retryable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct LegacyError {
+    error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+}
+
+impl ErrorResponse {
+    pub fn new(status: StatusCode, message: String, code: Option<&'static str>) -> Self {
+        ErrorResponse {
+            status,
+            message,
+            code,
+            retry: None,
+        }
+    }
+
+    pub fn retry(mut self, retry: Retry) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    pub fn code(&self) -> Option<&'static str> {
+        self.code
+    }
+
+    pub fn with_message(mut self, message: String) -> Self {
+        self.message = message;
+        self
+    }
+}
+
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        let context = context();
+        #[cfg(feature = "sentry")]
+        let reported = self.status.is_server_error().then(|| ServerError {
+            message: self.message.clone(),
+            code: self.code,
+        });
+        let retry_after = match self.retry {
+            Some(Retry::AfterSecs(secs)) => Some(secs),
+            _ => None,
+        };
+        let mut res = if context.legacy {
+            let body = context.format.respond(&LegacyError {
+                error: self.message,
+                code: self.code,
+            });
+            (self.status, body).into_response()
+        } else {
+            self.into_body(context)
+        };
+        if let Some(secs) = retry_after {
+            res.headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        #[cfg(feature = "sentry")]
+        if let Some(reported) = reported {
+            res.extensions_mut().insert(reported);
+        }
+        res
+    }
+}
+
+impl ErrorResponse {
+    fn into_body(self, context: ResponseContext) -> Response {
+        let code = match self.code {
+            Some(code) => code.to_string(),
+            None => self
+                .status
+                .canonical_reason()
+                .unwrap_or("error")
+                .to_ascii_lowercase()
+                .replace(' ', "_"),
+        };
+        let body = context.format.respond(&Failure {
+            success: false,
+            error: ErrorBody {
+                code,
+                message: self.message,
+                request_id: context.request_id,
+                retryable: self.retry.map(|r| !matches!(r, Retry::Never)),
+                retry_after_secs: match self.retry {
+                    Some(Retry::AfterSecs(secs)) => Some(secs),
+                    _ => None,
+                },
+            },
+        });
+        (self.status, body).into_response()
+    }
+}
+
+// Runs a read handler with the slot its RPC answers were observed at, and
+// the last slot check when there is one, attached to its success bodies.
+pub async fn with_read_context(
+    freshness: Option<SlotStatus>,
+    response: impl Future<Output = Response>,
+) -> Response {
+    let observed = Arc::new(AtomicU64::new(u64::MAX));
+    let context = ResponseContext {
+        freshness,
+        observed_slot: Some(observed.clone()),
+        ..context()
+    };
+    rpc::observing_slot(observed, CONTEXT.scope(context, response)).await
+}
+
+fn valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LENGTH && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+// Tags the request with an id, the caller's `x-request-id` when it sent a
+// usable one, echoes it back in the response headers and fixes the
+// response shape for the handlers and middleware underneath.
+pub async fn scope(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{:032x}", rand::random::<u128>()));
+    let config = state.config();
+    let options = Query::<RequestOptions>::try_from_uri(req.uri())
+        .map(|Query(options)| options)
+        .unwrap_or_default();
+    let context = ResponseContext {
+        legacy: config.legacy_responses,
+        format: Format::accepted(req.headers()),
+        request_id: Some(request_id.clone()),
+        freshness: None,
+        observed_slot: None,
+        labels: (!state.labels.is_empty()).then(|| state.labels.clone()),
+        lamports_as_string: options
+            .lamports_as_string
+            .unwrap_or(config.lamports_as_string),
+        strict: options.strict.unwrap_or(config.strict_requests),
+    };
+
+    let mut res = CONTEXT.scope(context, next.run(req)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    res
+}
+
+// Request body extractor for JSON or, with a MessagePack content type,
+// MessagePack. Malformed bodies are rejected with the usual error shape.
+pub struct Json<T>(pub T);
+
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if Format::of_body(req.headers()) == Format::Json {
+            let axum::Json(value) = axum::Json::<serde_json::Value>::from_request(req, state)
+                .await
+                .map_err(|e| ErrorResponse::new(e.status(), e.body_text(), None))?;
+            let parsed = if context().strict {
+                T::deserialize(Strict(value))
+            } else {
+                serde_json::from_value(value)
+            };
+            return parsed.map(Json).map_err(|e| {
+                ErrorResponse::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    format!(
+                        "Failed to deserialize the JSON body into the target type: {}",
+                        e
+                    ),
+                    // Strict mode's rejections, which a client can fix by
+                    // dropping the field.
+                    e.to_string()
+                        .starts_with("unknown field")
+                        .then_some("unknown_field"),
+                )
+            });
+        }
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| ErrorResponse::new(e.status(), e.body_text(), None))?;
+        rmp_serde::from_slice(&bytes).map(Json).map_err(|e| {
+            ErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                format!("Failed to deserialize the MessagePack body: {}", e),
+                None,
+            )
+        })
+    }
+}
+
+// Deserializes as the value itself would, except that fields the target
+// struct doesn't declare (under any name or alias) are an error instead of
+// being ignored. Only the top level is checked: nested structs, flattened
+// ones and untagged enums see the plain value.
+struct Strict(serde_json::Value);
+
+macro_rules! forward_to_value {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.0.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Strict {
+    type Error = serde_json::Error;
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if let serde_json::Value::Object(object) = &self.0 {
+            if let Some(unknown) = object.keys().find(|key| !fields.contains(&key.as_str())) {
+                return Err(serde::de::Error::unknown_field(unknown, fields));
+            }
+        }
+        self.0.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_tuple_struct(name, len, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_value! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32
+        deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char
+        deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_option deserialize_unit deserialize_seq deserialize_map
+        deserialize_identifier deserialize_ignored_any
+    }
+}
+
+// An optional body: absent when the request has no content type.
+impl<T, S> axum::extract::OptionalFromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse;
+
+    async fn from_request(req: Request, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        if !req.headers().contains_key(header::CONTENT_TYPE) {
+            return Ok(None);
+        }
+        <Self as FromRequest<S>>::from_request(req, state)
+            .await
+            .map(Some)
+    }
+}
+
+// Query options read by the envelope and by the extractors handlers take
+// next to `Params` (AmountOptions, ConsistencyQuery), so never unknown.
+const SHARED_QUERY_OPTIONS: [&str; 5] = [
+    "lamports_as_string",
+    "strict",
+    "sol_as_string",
+    "format",
+    "consistent",
+];
+
+// Records the fields a struct declares when asked to deserialize one, and
+// fails; how strict mode learns them for a query string.
+struct FieldProbe<'a>(&'a mut Option<&'static [&'static str]>);
+
+impl<'de> Deserializer<'de> for FieldProbe<'_> {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = Some(fields);
+        Err(serde::de::Error::custom("probed"))
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(serde::de::Error::custom("not a struct"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+// None unless `T` is a struct; flattened ones aren't, as in `Strict`.
+fn declared_fields<T: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let mut fields = None;
+    let _ = T::deserialize(FieldProbe(&mut fields));
+    fields
+}
+
+// Parameters of a read endpoint: from the query string on GET, so reads can
+// be cached and linked, or from the body (see `Json`) on POST. Query values
+// are strings, so lamport amounts are read from either form there too; in
+// strict mode undeclared keys are rejected as in bodies.
+pub struct Params<T>(pub T);
+
+impl<T, S> FromRequest<S> for Params<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if req.method() == Method::GET || req.method() == Method::HEAD {
+            let rejected = |e: QueryRejection| ErrorResponse::new(e.status(), e.body_text(), None);
+            if let (true, Some(fields)) = (context().strict, declared_fields::<T>()) {
+                let Query(pairs) =
+                    Query::<Vec<(String, String)>>::try_from_uri(req.uri()).map_err(rejected)?;
+                let unknown = pairs
+                    .iter()
+                    .map(|(key, _)| key.as_str())
+                    .find(|key| !fields.contains(key) && !SHARED_QUERY_OPTIONS.contains(key));
+                if let Some(unknown) = unknown {
+                    let e: serde::de::value::Error =
+                        serde::de::Error::unknown_field(unknown, fields);
+                    return Err(ErrorResponse::new(
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        format!("Failed to deserialize query string: {}", e),
+                        Some("unknown_field"),
+                    ));
+                }
+            }
+            return Query::try_from_uri(req.uri())
+                .map(|Query(value)| Params(value))
+                .map_err(rejected);
+        }
+        Json::from_request(req, state)
+            .await
+            .map(|Json(value)| Params(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use axum::{
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use serde_json::{json, Value};
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Body {
+        #[serde(alias = "sol", with = "api::lamports")]
+        lamports: u64,
+        #[serde(default)]
+        memo: Option<String>,
+    }
+
+    fn headers(accept: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(accept).unwrap());
+        headers
+    }
+
+    #[test]
+    fn msgpack_is_negotiated_from_accept() {
+        assert!(Format::accepted(&headers("application/msgpack")) == Format::MsgPack);
+        assert!(
+            Format::accepted(&headers("application/json, Application/X-MsgPack; q=0.5"))
+                == Format::MsgPack
+        );
+        assert!(Format::accepted(&headers("application/msgpack;q=0")) == Format::Json);
+        assert!(Format::accepted(&headers("*/*")) == Format::Json);
+        assert!(Format::accepted(&HeaderMap::new()) == Format::Json);
+    }
+
+    #[test]
+    fn request_ids_must_be_printable_and_short() {
+        assert!(valid_request_id("req-123"));
+        assert!(!valid_request_id(""));
+        assert!(!valid_request_id("has space"));
+        assert!(!valid_request_id(&"a".repeat(129)));
+    }
+
+    #[test]
+    fn strict_rejects_undeclared_fields_only() {
+        let body: Body = Body::deserialize(Strict(json!({ "sol": 5 }))).unwrap();
+        assert_eq!(body.lamports, 5);
+        let err = Body::deserialize(Strict(json!({ "lamports": 5, "extra": 1 }))).unwrap_err();
+        assert!(err.to_string().starts_with("unknown field `extra`"));
+        let nested: Vec<Body> =
+            Vec::deserialize(Strict(json!([{ "lamports": 5, "extra": 1 }]))).unwrap();
+        assert_eq!(nested[0].lamports, 5);
+    }
+
+    #[test]
+    fn probes_find_declared_fields_and_aliases() {
+        let fields = declared_fields::<Body>().unwrap();
+        assert!(["lamports", "sol", "memo"]
+            .iter()
+            .all(|f| fields.contains(f)));
+        assert_eq!(declared_fields::<Vec<Body>>(), None);
+        assert_eq!(declared_fields::<BTreeMap<String, String>>(), None);
+    }
+
+    async fn serve(config: Config) -> String {
+        let state = AppState::for_tests(config);
+        let app = Router::new()
+            .route(
+                "/ok",
+                get(|| async {
+                    ResponseJson(Body {
+                        lamports: 5,
+                        memo: None,
+                    })
+                }),
+            )
+            .route(
+                "/limited",
+                get(|| async {
+                    ErrorResponse::new(StatusCode::TOO_MANY_REQUESTS, "Slow down".into(), None)
+                        .retry(Retry::AfterSecs(3))
+                }),
+            )
+            .route(
+                "/body",
+                post(|Json(body): Json<Body>| async move { ResponseJson(body) }),
+            )
+            .route(
+                "/params",
+                get(|Params(body): Params<Body>| async move { ResponseJson(body) }),
+            )
+            .layer(middleware::from_fn_with_state(state.clone(), scope))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn responses_are_enveloped() {
+        let base = serve(Config::default()).await;
+        let client = reqwest::Client::new();
+
+        let res = client
+            .get(format!("{}/ok?lamports_as_string=true", base))
+            .header("x-request-id", "abc-1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.headers()["x-request-id"], "abc-1");
+        let body: Value = res.json().await.unwrap();
+        assert_eq!(
+            body,
+            json!({ "success": true, "data": { "lamports": "5", "memo": null } })
+        );
+        // Only typed lamport fields, not anything named like one.
+        let res = client.get(format!("{}/ok", base)).send().await.unwrap();
+        let body: Value = res.json().await.unwrap();
+        assert_eq!(body["data"]["lamports"], 5);
+
+        let res = client
+            .get(format!("{}/limited", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 429);
+        assert_eq!(res.headers()["retry-after"], "3");
+        let id = res.headers()["x-request-id"].to_str().unwrap().to_string();
+        assert_eq!(id.len(), 32);
+        let body: Value = res.json().await.unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "success": false,
+                "error": {
+                    "code": "too_many_requests",
+                    "message": "Slow down",
+                    "request_id": id,
+                    "retryable": true,
+                    "retry_after_secs": 3,
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn msgpack_and_legacy_shapes() {
+        let base = serve(Config {
+            legacy_responses: true,
+            ..Config::default()
+        })
+        .await;
+        let client = reqwest::Client::new();
+
+        let bytes = rmp_serde::to_vec_named(&json!({ "lamports": 7 })).unwrap();
+        let res = client
+            .post(format!("{}/body", base))
+            .header("content-type", MSGPACK)
+            .header("accept", MSGPACK)
+            .body(bytes)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.headers()["content-type"], MSGPACK);
+        let body: Body = rmp_serde::from_slice(&res.bytes().await.unwrap()).unwrap();
+        assert_eq!(
+            body,
+            Body {
+                lamports: 7,
+                memo: None
+            }
+        );
+
+        let res = client
+            .get(format!("{}/limited", base))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = res.json().await.unwrap();
+        assert_eq!(body, json!({ "error": "Slow down" }));
+    }
+
+    #[tokio::test]
+    async fn bodies_take_string_lamports_and_can_be_strict() {
+        let base = serve(Config::default()).await;
+        let client = reqwest::Client::new();
+
+        let res = client
+            .post(format!("{}/body", base))
+            .json(&json!({ "lamports": "18446744073709551615", "extra": true }))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = res.json().await.unwrap();
+        assert_eq!(body["data"]["lamports"], u64::MAX);
+
+        let res = client
+            .post(format!("{}/body?strict=true", base))
+            .json(&json!({ "lamports": 1, "extra": true }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 422);
+        let body: Value = res.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "unknown_field");
+    }
+
+    #[tokio::test]
+    async fn query_params_take_big_lamports_and_can_be_strict() {
+        let base = serve(Config::default()).await;
+        let client = reqwest::Client::new();
+        let get = |query: &str| client.get(format!("{}/params?{}", base, query)).send();
+
+        let res = get("lamports=18446744073709551615&extra=1").await.unwrap();
+        let body: Value = res.json().await.unwrap();
+        assert_eq!(body["data"]["lamports"], u64::MAX);
+
+        // Aliases and the options other extractors read are declared.
+        let res = get("sol=5&strict=true&lamports_as_string=true&sol_as_string=true")
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let body: Value = res.json().await.unwrap();
+        assert_eq!(body["data"]["lamports"], "5");
+
+        let res = get("lamports=1&walet=x&strict=true").await.unwrap();
+        assert_eq!(res.status(), 422);
+        let body: Value = res.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "unknown_field");
+        let message = body["error"]["message"].as_str().unwrap();
+        assert!(
+            message.starts_with("Failed to deserialize query string: unknown field `walet`"),
+            "{}",
+            message
+        );
+    }
+}
@@ -0,0 +1,610 @@
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::option_serializer::OptionSerializer;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{tcp::OwnedWriteHalf, TcpStream},
+    sync::broadcast::{self, error::RecvError},
+};
+
+use crate::{
+    clock::now_ms, config::Config, confirm::Polling, history, logging, rpc, service::rpc_error,
+    state::AppState, tasks, telemetry,
+};
+
+pub use crate::api::{Event, EventKind};
+
+// Events a slow subscriber (the sinks, or an SSE client) may fall behind
+// by before it starts missing some.
+const CHANNEL_CAPACITY: usize = 1_024;
+const SINK_TIMEOUT: Duration = Duration::from_secs(10);
+// How long a faucet transaction is watched for confirmation.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+// Transactions fetched per program and poll; older ones are skipped.
+const MAX_PROGRAM_TRANSACTIONS: usize = 50;
+
+// Observations of the watchers, fanned out to the configured sinks by
+// `dispatch` and to /stream/events subscribers.
+pub struct EventBus {
+    sender: broadcast::Sender<Arc<Event>>,
+    next_id: AtomicU64,
+    // Last balance seen per wallet, to publish only changes.
+    balances: Mutex<HashMap<Pubkey, u64>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus {
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+            next_id: AtomicU64::new(1),
+            balances: Mutex::default(),
+        }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, kind: EventKind, data: serde_json::Value) {
+        let event = Event {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            kind,
+            timestamp_ms: now_ms(),
+            data,
+        };
+        // Nobody listening is fine.
+        let _ = self.sender.send(Arc::new(event));
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Event>> {
+        self.sender.subscribe()
+    }
+
+    // The first balance seen for a wallet only sets the baseline.
+    pub fn observe_balance(&self, wallet: &Pubkey, lamports: u64) {
+        let previous = self.balances.lock().unwrap().insert(*wallet, lamports);
+        if let Some(previous) = previous.filter(|p| *p != lamports) {
+            self.publish(
+                EventKind::BalanceChanged,
+                serde_json::json!({
+                    "wallet": wallet.to_string(),
+                    "previous_lamports": previous,
+                    "lamports": lamports,
+                    "change_lamports": lamports as i128 - previous as i128,
+                }),
+            );
+        }
+    }
+}
+
+// Publishes `faucet_confirmed` once `signature` is confirmed; failed or
+// expired transactions are dropped silently, their audit record already
+// says they were sent.
+pub fn confirm_faucet(
+    state: &AppState,
+    signature: Signature,
+    wallet: String,
+    lamports: u64,
+    source: &'static str,
+) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        let mut polling = Polling::start();
+        while polling.elapsed() < CONFIRM_TIMEOUT {
+            tokio::time::sleep(polling.next_delay()).await;
+            let rpc_url = state.config().rpc_url.clone();
+            let status = rpc::spawn_blocking(move || {
+                rpc::client(&rpc_url)
+                    .get_signature_status_with_commitment(&signature, CommitmentConfig::confirmed())
+                    .ok()
+                    .flatten()
+            })
+            .await;
+            match status {
+                Ok(Some(Ok(()))) => {
+                    polling.confirmed();
+                    state.events.publish(
+                        EventKind::FaucetConfirmed,
+                        serde_json::json!({
+                            "wallet": wallet,
+                            "lamports": lamports,
+                            "source": source,
+                            "signature": signature.to_string(),
+                        }),
+                    );
+                    return;
+                }
+                Ok(Some(Err(_))) => return,
+                _ => {}
+            }
+        }
+    });
+}
+
+// Publishes the new transactions of each of `event_programs`, polling
+// every `event_poll_interval_secs`. A program's history before it was
+// added isn't replayed.
+pub async fn watch_programs(state: AppState) {
+    // Per program polled so far, its newest signature (None while it has
+    // no transactions at all).
+    let mut last_seen: HashMap<Pubkey, Option<Signature>> = HashMap::new();
+    loop {
+        let config = state.config();
+        let programs: Vec<Pubkey> = config
+            .event_programs
+            .iter()
+            .filter_map(|p| Pubkey::from_str(p).ok())
+            .collect();
+        last_seen.retain(|program, _| programs.contains(program));
+        for program in programs {
+            let rpc_url = config.rpc_url.clone();
+            let seen = last_seen.get(&program).copied();
+            let polled = rpc::spawn_blocking(move || poll_program(&rpc_url, &program, seen)).await;
+            match polled {
+                Ok(Ok((newest, transactions))) => {
+                    if newest.is_some() || seen.is_none() {
+                        last_seen.insert(program, newest);
+                    }
+                    for data in transactions {
+                        state.events.publish(EventKind::ProgramTransaction, data);
+                    }
+                }
+                Ok(Err(e)) => {
+                    logging::warn(format_args!("Failed to poll program {}: {}", program, e))
+                }
+                Err(e) => logging::warn(format_args!("Failed to poll program {}: {}", program, e)),
+            }
+        }
+        tasks::succeeded();
+        tokio::time::sleep(Duration::from_secs(config.event_poll_interval_secs)).await;
+    }
+}
+
+// Blocking: the newest signature of `program` and, unless this is the
+// first poll (`seen` is None), the transactions since the last one seen,
+// oldest first.
+fn poll_program(
+    rpc_url: &str,
+    program: &Pubkey,
+    seen: Option<Option<Signature>>,
+) -> Result<(Option<Signature>, Vec<serde_json::Value>), crate::service::ServiceError> {
+    let client = rpc::client(rpc_url);
+    let _span = tracing::info_span!("rpc.poll_program", %rpc_url, %program).entered();
+    let signatures = client
+        .get_signatures_for_address_with_config(
+            program,
+            GetConfirmedSignaturesForAddress2Config {
+                before: None,
+                until: seen.flatten(),
+                limit: Some(if seen.is_some() {
+                    MAX_PROGRAM_TRANSACTIONS
+                } else {
+                    1
+                }),
+                commitment: Some(CommitmentConfig::confirmed()),
+            },
+        )
+        .map_err(rpc_error("Failed to get signatures"))?;
+    let newest = signatures
+        .first()
+        .and_then(|s| Signature::from_str(&s.signature).ok());
+    if seen.is_none() {
+        return Ok((newest, Vec::new()));
+    }
+
+    let mut transactions = Vec::with_capacity(signatures.len());
+    for status in signatures.iter().rev() {
+        let logs = match history::fetch_transaction(&client, &status.signature) {
+            Ok(tx) => match tx.transaction.meta.map(|meta| meta.log_messages) {
+                Some(OptionSerializer::Some(logs)) => logs,
+                _ => Vec::new(),
+            },
+            Err(e) => {
+                logging::warn(format_args!(
+                    "Failed to fetch transaction {}: {}",
+                    status.signature, e
+                ));
+                Vec::new()
+            }
+        };
+        transactions.push(serde_json::json!({
+            "program": program.to_string(),
+            "signature": status.signature,
+            "slot": status.slot,
+            "block_time": status.block_time,
+            "success": status.err.is_none(),
+            "logs": logs,
+        }));
+    }
+    Ok((newest, transactions))
+}
+
+// Delivers every event to the configured sinks, one at a time. A sink that
+// fails is logged and skipped; sinks that fall too far behind lose the
+// oldest events.
+pub async fn dispatch(state: AppState) {
+    let mut events = state.events.subscribe();
+    let mut sinks = Sinks::default();
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(missed)) => {
+                logging::warn(format_args!(
+                    "Event sinks fell behind and dropped {} events",
+                    missed
+                ));
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        sinks.send(&state.config(), &event).await;
+        tasks::succeeded();
+    }
+}
+
+#[derive(Default)]
+struct Sinks {
+    client: reqwest::Client,
+    nats: Option<Nats>,
+}
+
+impl Sinks {
+    async fn send(&mut self, config: &Config, event: &Event) {
+        for url in &config.event_webhooks {
+            let result = telemetry::traced(self.client.post(url))
+                .timeout(SINK_TIMEOUT)
+                .json(event)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+            if let Err(e) = result {
+                logging::warn(format_args!("Failed to send event to {}: {}", url, e));
+            }
+        }
+        if let Some(url) = &config.event_kafka_rest_url {
+            let body = serde_json::json!({
+                "records": [{ "key": event.kind.as_str(), "value": event }],
+            });
+            let url = format!(
+                "{}/topics/{}",
+                url.trim_end_matches('/'),
+                config.event_kafka_topic
+            );
+            let result = telemetry::traced(self.client.post(url))
+                .timeout(SINK_TIMEOUT)
+                .header("content-type", "application/vnd.kafka.json.v2+json")
+                .body(body.to_string())
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+            if let Err(e) = result {
+                logging::warn(format_args!("Failed to produce event to Kafka: {}", e));
+            }
+        }
+        match &config.event_nats_url {
+            Some(url) => {
+                let subject = format!("{}.{}", config.event_nats_subject, event.kind.as_str());
+                if let Err(e) = self.publish_nats(url, &subject, event).await {
+                    logging::warn(format_args!("Failed to publish event to NATS: {}", e));
+                }
+            }
+            None => self.nats = None,
+        }
+    }
+
+    // Reconnects (once per event) when the connection was dropped or the
+    // URL changed.
+    async fn publish_nats(
+        &mut self,
+        url: &str,
+        subject: &str,
+        event: &Event,
+    ) -> Result<(), String> {
+        let payload = serde_json::to_vec(event).map_err(|e| e.to_string())?;
+        if let Some(nats) = self.nats.as_mut().filter(|n| n.url == url) {
+            if nats.publish(subject, &payload).await.is_ok() {
+                return Ok(());
+            }
+        }
+        self.nats = None;
+        let mut nats = tokio::time::timeout(SINK_TIMEOUT, Nats::connect(url))
+            .await
+            .map_err(|_| "timed out connecting".to_string())??;
+        nats.publish(subject, &payload)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.nats = Some(nats);
+        Ok(())
+    }
+}
+
+// Just enough of the NATS client protocol to publish: CONNECT, PUB, and
+// answering the server's PINGs so it keeps the connection open.
+struct Nats {
+    url: String,
+    writer: Arc<tokio::sync::Mutex<OwnedWriteHalf>>,
+}
+
+impl Nats {
+    async fn connect(url: &str) -> Result<Self, String> {
+        let rest = url.strip_prefix("nats://").unwrap_or(url);
+        let (credentials, addr) = match rest.rsplit_once('@') {
+            Some((credentials, addr)) => (Some(credentials), addr),
+            None => (None, rest),
+        };
+        let addr = addr.trim_end_matches('/');
+        let stream = TcpStream::connect(addr)
+            .await
+            .map_err(|e| format!("{}: {}", addr, e))?;
+        let (reader, writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let mut info = String::new();
+        reader
+            .read_line(&mut info)
+            .await
+            .map_err(|e| e.to_string())?;
+        if !info.starts_with("INFO") {
+            return Err(format!("unexpected greeting from {}", addr));
+        }
+        let mut options = serde_json::json!({
+            "verbose": false,
+            "pedantic": false,
+            "name": env!("CARGO_PKG_NAME"),
+            "lang": "rust",
+            "version": env!("CARGO_PKG_VERSION"),
+        });
+        match credentials.map(|c| c.split_once(':')) {
+            Some(Some((user, pass))) => {
+                options["user"] = user.into();
+                options["pass"] = pass.into();
+            }
+            Some(None) => options["auth_token"] = credentials.unwrap_or_default().into(),
+            None => {}
+        }
+
+        let writer = Arc::new(tokio::sync::Mutex::new(writer));
+        writer
+            .lock()
+            .await
+            .write_all(format!("CONNECT {}\r\n", options).as_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let pong_writer = writer.clone();
+        tokio::spawn(async move {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match reader.read_line(&mut line).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(_) if line.starts_with("PING") => {
+                        if pong_writer
+                            .lock()
+                            .await
+                            .write_all(b"PONG\r\n")
+                            .await
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Ok(_) if line.starts_with("-ERR") => {
+                        logging::warn(format_args!("NATS server error: {}", line.trim_end()))
+                    }
+                    Ok(_) => {}
+                }
+            }
+        });
+
+        Ok(Nats {
+            url: url.to_string(),
+            writer,
+        })
+    }
+
+    async fn publish(&mut self, subject: &str, payload: &[u8]) -> std::io::Result<()> {
+        let mut frame = format!("PUB {} {}\r\n", subject, payload.len()).into_bytes();
+        frame.extend_from_slice(payload);
+        frame.extend_from_slice(b"\r\n");
+        self.writer.lock().await.write_all(&frame).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use solana_sdk::{
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    };
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+
+    #[test]
+    fn balance_changes_are_published_once() {
+        let bus = EventBus::default();
+        let mut events = bus.subscribe();
+        let wallet = Pubkey::new_unique();
+        bus.observe_balance(&wallet, 500);
+        bus.observe_balance(&wallet, 500);
+        bus.observe_balance(&wallet, 200);
+        bus.observe_balance(&wallet, 200);
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.id, 1);
+        assert!(matches!(event.kind, EventKind::BalanceChanged));
+        assert_eq!(
+            event.data,
+            json!({
+                "wallet": wallet.to_string(),
+                "previous_lamports": 500,
+                "lamports": 200,
+                "change_lamports": -300,
+            })
+        );
+        assert!(events.try_recv().is_err());
+        bus.publish(EventKind::FaucetConfirmed, json!({}));
+        assert_eq!(events.try_recv().unwrap().id, 2);
+    }
+
+    #[test]
+    fn programs_are_polled_from_the_newest_seen() {
+        let chain = rpc::mock_for_tests();
+        let payer = Keypair::new();
+        let watched = Pubkey::new_unique();
+        chain.set_balance(payer.pubkey(), 1_000_000_000);
+        let client = rpc::client("http://mock");
+        let transfer = |lamports| {
+            let tx = Transaction::new_signed_with_payer(
+                &[system_instruction::transfer(
+                    &payer.pubkey(),
+                    &watched,
+                    lamports,
+                )],
+                Some(&payer.pubkey()),
+                &[&payer],
+                client.get_latest_blockhash().unwrap(),
+            );
+            client.send_transaction(&tx).unwrap()
+        };
+
+        // Nothing yet, and the first poll only marks where to start.
+        let (newest, transactions) = poll_program("http://mock", &watched, None).unwrap();
+        assert_eq!((newest, transactions.len()), (None, 0));
+        let first = transfer(1_000);
+        let (newest, transactions) = poll_program("http://mock", &watched, None).unwrap();
+        assert_eq!((newest, transactions.len()), (Some(first), 0));
+
+        let second = transfer(2_000);
+        let third = transfer(3_000);
+        let (newest, transactions) =
+            poll_program("http://mock", &watched, Some(Some(first))).unwrap();
+        assert_eq!(newest, Some(third));
+        let signatures: Vec<_> = transactions.iter().map(|t| &t["signature"]).collect();
+        assert_eq!(
+            signatures,
+            [&json!(second.to_string()), &json!(third.to_string())]
+        );
+        assert_eq!(transactions[0]["program"], json!(watched.to_string()));
+        assert_eq!(transactions[0]["success"], json!(true));
+        assert!(transactions[0]["logs"].is_array());
+    }
+
+    fn event() -> Event {
+        Event {
+            id: 7,
+            kind: EventKind::FaucetConfirmed,
+            timestamp_ms: 1,
+            data: json!({ "wallet": "w" }),
+        }
+    }
+
+    #[tokio::test]
+    async fn webhooks_and_kafka_get_every_event() {
+        use axum::{extract::Path, http::HeaderMap, routing::post, Router};
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let hook_tx = tx.clone();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(move |body: String| async move {
+                    hook_tx.send(("hook".to_string(), body)).unwrap();
+                }),
+            )
+            .route(
+                "/topics/{topic}",
+                post(
+                    move |Path(topic): Path<String>, headers: HeaderMap, body: String| async move {
+                        assert_eq!(
+                            headers["content-type"],
+                            "application/vnd.kafka.json.v2+json"
+                        );
+                        tx.send((topic, body)).unwrap();
+                    },
+                ),
+            );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config = Config {
+            event_webhooks: vec![format!("{}/hook", url), format!("{}/missing", url)],
+            event_kafka_rest_url: Some(format!("{}/", url)),
+            event_kafka_topic: "wallet-events".to_string(),
+            ..Config::default()
+        };
+        Sinks::default().send(&config, &event()).await;
+
+        let (name, body) = rx.recv().await.unwrap();
+        assert_eq!(name, "hook");
+        assert_eq!(body, serde_json::to_string(&event()).unwrap());
+        let (topic, body) = rx.recv().await.unwrap();
+        assert_eq!(topic, "wallet-events");
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["records"][0]["key"], "faucet_confirmed");
+        assert_eq!(body["records"][0]["value"]["id"], 7);
+    }
+
+    #[tokio::test]
+    async fn nats_connects_answers_pings_and_publishes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut reader = BufReader::new(reader);
+            writer.write_all(b"INFO {}\r\n").await.unwrap();
+            let mut connect = String::new();
+            reader.read_line(&mut connect).await.unwrap();
+            writer.write_all(b"PING\r\n").await.unwrap();
+            let mut pong = String::new();
+            reader.read_line(&mut pong).await.unwrap();
+            let mut publish = String::new();
+            reader.read_line(&mut publish).await.unwrap();
+            let mut payload = vec![0; 5];
+            reader.read_exact(&mut payload).await.unwrap();
+            (connect, pong, publish, payload)
+        });
+
+        let url = format!("nats://alice:secret@{}", addr);
+        let mut nats = Nats::connect(&url).await.unwrap();
+        // Published after the PONG the reader task sends.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        nats.publish("events.test", b"hello").await.unwrap();
+
+        let (connect, pong, publish, payload) = server.await.unwrap();
+        let options: serde_json::Value =
+            serde_json::from_str(connect.strip_prefix("CONNECT ").unwrap()).unwrap();
+        assert_eq!(options["user"], "alice");
+        assert_eq!(options["pass"], "secret");
+        assert_eq!(options["verbose"], false);
+        assert_eq!(pong, "PONG\r\n");
+        assert_eq!(publish, "PUB events.test 5\r\n");
+        assert_eq!(payload, b"hello");
+    }
+
+    #[tokio::test]
+    async fn nats_needs_an_info_greeting() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(b"HTTP/1.1 400\r\n").await.unwrap();
+        });
+        let err = Nats::connect(&format!("token@{}", addr))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err, format!("unexpected greeting from {}", addr));
+    }
+}
@@ -0,0 +1,479 @@
+use axum::http::StatusCode;
+use base64::Engine;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::pubkey::Pubkey;
+use std::fmt::Write;
+
+use crate::{
+    amount, clock,
+    config::FrontendTheme,
+    decoders::Decoded,
+    frontend,
+    inspect::{self, DecodedAccount, DecodedInstruction, DecodedTransaction},
+    rpc,
+    service::{self, ServiceError},
+    state::AppState,
+};
+
+// Signatures listed on an address page.
+const RECENT_SIGNATURES: usize = 25;
+
+// Server-rendered pages over the same lookups as /tx/{signature} and
+// /accounts/{address}, for looking at activity in a browser. Every page
+// renders, errors included, as (status, HTML).
+pub type Page = (StatusCode, String);
+
+pub async fn transaction(state: &AppState, signature: String) -> Page {
+    let rpc_url = state.config().rpc_url.clone();
+    let decoders = state.decoders.clone();
+    let lookup = signature.clone();
+    let tx = rpc::spawn_blocking(move || inspect::transaction(&rpc_url, &decoders, &lookup)).await;
+    match tx {
+        Ok(Ok(tx)) => (StatusCode::OK, transaction_page(state, &signature, &tx)),
+        Ok(Err(e)) => error_page(state, "Transaction", &signature, e),
+        Err(e) => error_page(state, "Transaction", &signature, internal(e)),
+    }
+}
+
+pub async fn address(state: &AppState, address: String) -> Page {
+    let pubkey = match service::parse_wallet(&address) {
+        Ok(pubkey) => pubkey,
+        Err(e) => return error_page(state, "Address", &address, e),
+    };
+    let rpc_url = state.config().rpc_url.clone();
+    let decoders = state.decoders.clone();
+    let found = rpc::spawn_blocking(move || {
+        // A wallet that was never funded has no account, but may still
+        // have signatures.
+        let account = match inspect::account(&rpc_url, &decoders, &pubkey) {
+            Ok(account) => Some(account),
+            Err(ServiceError::Rpc { message, .. }) if message.contains("AccountNotFound") => None,
+            Err(e) => return Err(e),
+        };
+        let signatures = inspect::recent_signatures(&rpc_url, &pubkey, RECENT_SIGNATURES)?;
+        Ok((account, signatures))
+    })
+    .await;
+    match found {
+        Ok(Ok((account, signatures))) => (
+            StatusCode::OK,
+            address_page(state, &pubkey, account.as_ref(), &signatures),
+        ),
+        Ok(Err(e)) => error_page(state, "Address", &address, e),
+        Err(e) => error_page(state, "Address", &address, internal(e)),
+    }
+}
+
+fn internal(e: impl ToString) -> ServiceError {
+    ServiceError::Rpc {
+        action: "Failed to look up",
+        message: e.to_string(),
+    }
+}
+
+fn transaction_page(state: &AppState, signature: &str, tx: &DecodedTransaction) -> String {
+    let mut body = String::new();
+    body.push_str("<table>");
+    row(&mut body, "Signature", &code(signature));
+    row(
+        &mut body,
+        "Status",
+        if tx.succeeded {
+            "<span class=\"ok\">Success</span>"
+        } else {
+            "<span class=\"failed\">Failed</span>"
+        },
+    );
+    row(&mut body, "Slot", &tx.slot.to_string());
+    row(&mut body, "Block time", &block_time(tx.block_time));
+    row(&mut body, "Fee", &sol(tx.fee_lamports));
+    body.push_str("</table>");
+
+    let _ = write!(body, "<h2>Instructions ({})</h2>", tx.instructions.len());
+    body.push_str("<ol>");
+    for ix in &tx.instructions {
+        instruction(state, &mut body, ix);
+    }
+    body.push_str("</ol>");
+    if !tx.events.is_empty() {
+        let _ = write!(body, "<h2>Events ({})</h2>", tx.events.len());
+        body.push_str("<ol>");
+        for event in &tx.events {
+            body.push_str("<li><table>");
+            row(
+                &mut body,
+                "Program",
+                &address_link(state, &event.program_id),
+            );
+            decoded_row(&mut body, event.decoded.as_ref(), &event.data);
+            body.push_str("</table></li>");
+        }
+        body.push_str("</ol>");
+    }
+    let _ = write!(
+        body,
+        "<p class=\"muted\"><a href=\"/tx/{0}\">JSON</a></p>",
+        escape(signature)
+    );
+    page(state, "Transaction", &body)
+}
+
+fn decoded_row(body: &mut String, decoded: Option<&Decoded>, data: &[u8]) {
+    match decoded {
+        Some(decoded) => {
+            let json = serde_json::to_string_pretty(decoded).unwrap_or_default();
+            row(body, "Decoded", &format!("<pre>{}</pre>", escape(&json)));
+        }
+        None => {
+            let data = base64::engine::general_purpose::STANDARD.encode(data);
+            row(body, "Data (base64)", &code(&data));
+        }
+    }
+}
+
+fn instruction(state: &AppState, body: &mut String, ix: &DecodedInstruction) {
+    body.push_str("<li><table>");
+    row(body, "Program", &address_link(state, &ix.program_id));
+    let accounts: Vec<String> = ix.accounts.iter().map(|a| address_link(state, a)).collect();
+    row(body, "Accounts", &accounts.join("<br>"));
+    decoded_row(body, ix.decoded.as_ref(), &ix.data);
+    body.push_str("</table>");
+    if !ix.inner.is_empty() {
+        body.push_str("<p class=\"muted\">Inner instructions</p><ol>");
+        for inner in &ix.inner {
+            instruction(state, body, inner);
+        }
+        body.push_str("</ol>");
+    }
+    body.push_str("</li>");
+}
+
+fn address_page(
+    state: &AppState,
+    address: &Pubkey,
+    account: Option<&DecodedAccount>,
+    signatures: &[RpcConfirmedTransactionStatusWithSignature],
+) -> String {
+    let mut body = String::new();
+    body.push_str("<table>");
+    row(&mut body, "Address", &code(&address.to_string()));
+    if let Some(name) = name(state, address) {
+        row(&mut body, "Name", &escape(&name));
+    }
+    match account {
+        Some(account) => {
+            row(&mut body, "Balance", &sol(account.account.lamports));
+            row(
+                &mut body,
+                "Owner",
+                &address_link(state, &account.account.owner),
+            );
+            row(
+                &mut body,
+                "Executable",
+                if account.account.executable {
+                    "Yes"
+                } else {
+                    "No"
+                },
+            );
+            row(
+                &mut body,
+                "Data",
+                &format!("{} bytes", account.account.data.len()),
+            );
+        }
+        None => row(&mut body, "Balance", "0 SOL (no account)"),
+    }
+    body.push_str("</table>");
+    let _ = write!(
+        body,
+        "<img src=\"/qr?data={0}&amp;size=160\" width=\"160\" height=\"160\" alt=\"QR code of {0}\">",
+        address
+    );
+    if let Some(decoded) = account.and_then(|a| a.decoded.as_ref()) {
+        let json = serde_json::to_string_pretty(decoded).unwrap_or_default();
+        let _ = write!(body, "<h2>Decoded</h2><pre>{}</pre>", escape(&json));
+    }
+
+    let _ = write!(body, "<h2>Recent transactions ({})</h2>", signatures.len());
+    if signatures.is_empty() {
+        body.push_str("<p class=\"muted\">None.</p>");
+    } else {
+        body.push_str(
+            "<table><tr><th>Signature</th><th>Slot</th><th>Time</th><th>Status</th></tr>",
+        );
+        for status in signatures {
+            let _ = write!(
+                body,
+                "<tr><td><a href=\"/explorer/tx/{0}\"><code>{1}</code></a></td>\
+                 <td>{2}</td><td>{3}</td><td>{4}</td></tr>",
+                escape(&status.signature),
+                escape(&shorten(&status.signature)),
+                status.slot,
+                block_time(status.block_time),
+                if status.err.is_none() {
+                    "<span class=\"ok\">Success</span>"
+                } else {
+                    "<span class=\"failed\">Failed</span>"
+                },
+            );
+        }
+        body.push_str("</table>");
+    }
+    if account.is_some() {
+        let _ = write!(
+            body,
+            "<p class=\"muted\"><a href=\"/accounts/{0}\">JSON</a></p>",
+            address
+        );
+    }
+    page(state, "Address", &body)
+}
+
+fn error_page(state: &AppState, kind: &str, subject: &str, e: ServiceError) -> Page {
+    let status = match e {
+        ServiceError::InvalidWallet | ServiceError::Invalid(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::BAD_GATEWAY,
+    };
+    let body = format!(
+        "<table><tr><th>{}</th><td>{}</td></tr></table><p class=\"failed\">{}</p>",
+        kind,
+        code(subject),
+        escape(&e.to_string())
+    );
+    (status, page(state, kind, &body))
+}
+
+// The program's or label's name of an address.
+fn name(state: &AppState, address: &Pubkey) -> Option<String> {
+    state.programs.name(address).or_else(|| {
+        state
+            .labels
+            .names(&serde_json::Value::String(address.to_string()))
+            .into_values()
+            .next()
+    })
+}
+
+fn address_link(state: &AppState, address: &Pubkey) -> String {
+    let name = name(state, address)
+        .map(|name| format!(" <span class=\"muted\">{}</span>", escape(&name)))
+        .unwrap_or_default();
+    format!(
+        "<a href=\"/explorer/address/{0}\"><code>{0}</code></a>{1}",
+        address, name
+    )
+}
+
+fn row(body: &mut String, label: &str, html: &str) {
+    let _ = write!(body, "<tr><th>{}</th><td>{}</td></tr>", label, html);
+}
+
+fn code(text: &str) -> String {
+    format!("<code>{}</code>", escape(text))
+}
+
+fn sol(lamports: u64) -> String {
+    format!(
+        "{} SOL <span class=\"muted\">({} lamports)</span>",
+        amount::lamports_to_sol_decimal(lamports),
+        lamports
+    )
+}
+
+fn block_time(time: Option<i64>) -> String {
+    match time {
+        Some(secs) if secs >= 0 => clock::rfc3339(secs as u64 * 1_000),
+        _ => "Unknown".to_string(),
+    }
+}
+
+// The first and last characters of a signature.
+fn shorten(signature: &str) -> String {
+    match (
+        signature.get(..12),
+        signature.get(signature.len().saturating_sub(12)..),
+    ) {
+        (Some(head), Some(tail)) if signature.len() > 30 => format!("{}…{}", head, tail),
+        _ => signature.to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// The page around `body`, in the frontend's title and theme.
+fn page(state: &AppState, heading: &str, body: &str) -> String {
+    let branding = frontend::config(state);
+    let light = matches!(branding.theme.mode, FrontendTheme::Light);
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="UTF-8">
+  <title>{heading} · {title}</title>
+  <style>
+    :root {{ --accent: {accent}; }}
+    body {{
+      font-family: Arial, sans-serif;
+      max-width: 960px;
+      margin: 2rem auto;
+      padding: 0 1rem;
+      background-color: {background};
+      color: {text};
+    }}
+    a {{ color: var(--accent); text-decoration: none; }}
+    a:hover {{ text-decoration: underline; }}
+    table {{ border-collapse: collapse; width: 100%; margin-bottom: 1rem; }}
+    th, td {{ text-align: left; vertical-align: top; padding: 0.35rem 0.6rem; }}
+    th {{ color: {muted}; font-weight: normal; width: 9rem; }}
+    code {{ word-break: break-all; }}
+    pre {{ background-color: {panel}; padding: 0.75rem; border-radius: 6px; white-space: pre-wrap; margin: 0; }}
+    li {{ margin-bottom: 1rem; }}
+    .muted {{ color: {muted}; }}
+    .ok {{ color: #22c55e; }}
+    .failed {{ color: #ef4444; }}
+  </style>
+</head>
+<body>
+  <p class="muted"><a href="/">{title}</a> · {cluster}</p>
+  <h1>{heading}</h1>
+  {body}
+</body>
+</html>
+"#,
+        heading = escape(heading),
+        title = escape(&branding.title),
+        cluster = escape(&branding.cluster),
+        accent = escape(&branding.theme.accent_color),
+        background = if light { "#ffffff" } else { "#1e1e1e" },
+        text = if light { "#1f2937" } else { "#f0f0f0" },
+        muted = if light { "#4b5563" } else { "#aaa" },
+        panel = if light { "#f3f4f6" } else { "#2b2b2b" },
+        body = body,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    fn mock_state() -> AppState {
+        AppState::for_tests(Config {
+            rpc_url: "http://mock".to_string(),
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(
+            escape("<a href=\"x\">'&'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+        assert_eq!(escape("plain"), "plain");
+    }
+
+    #[test]
+    fn long_signatures_are_shortened() {
+        assert_eq!(shorten("short"), "short");
+        let thirty = "a".repeat(30);
+        assert_eq!(shorten(&thirty), thirty);
+        let signature = format!("{}{}{}", "A".repeat(12), "x".repeat(64), "Z".repeat(12));
+        assert_eq!(
+            shorten(&signature),
+            format!("{}…{}", "A".repeat(12), "Z".repeat(12))
+        );
+    }
+
+    #[test]
+    fn block_times_and_amounts_render() {
+        assert_eq!(block_time(Some(0)), "1970-01-01T00:00:00Z");
+        assert_eq!(block_time(Some(951_782_400)), "2000-02-29T00:00:00Z");
+        assert_eq!(block_time(Some(-1)), "Unknown");
+        assert_eq!(block_time(None), "Unknown");
+        assert_eq!(
+            sol(1_500_000_000),
+            "1.5 SOL <span class=\"muted\">(1500000000 lamports)</span>"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn invalid_addresses_render_an_error_page() {
+        let state = mock_state();
+        let (status, html) = address(&state, "<not-a-wallet>".to_string()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(html.contains("<code>&lt;not-a-wallet&gt;</code>"));
+        assert!(!html.contains("<not-a-wallet>"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unfunded_addresses_render_without_an_account() {
+        let state = mock_state();
+        let (status, html) = address(&state, Pubkey::new_unique().to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("0 SOL (no account)"));
+        assert!(html.contains("<h2>Recent transactions (0)</h2>"));
+        assert!(!html.contains("/accounts/"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn funded_addresses_link_their_transactions() {
+        let chain = rpc::mock_for_tests();
+        let state = mock_state();
+        let payer = Keypair::new();
+        let wallet = Pubkey::new_unique();
+        chain.set_balance(payer.pubkey(), 10_000_000_000);
+        state.labels.set(wallet, "Cold <storage>", &[]).unwrap();
+        let signature =
+            service::transfer("http://mock", &payer, &wallet, 1_500_000_000, true).unwrap();
+
+        let (status, html) = address(&state, wallet.to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("<tr><th>Name</th><td>Cold &lt;storage&gt;</td></tr>"));
+        assert!(html.contains("1.5 SOL"));
+        assert!(html.contains("<h2>Recent transactions (1)</h2>"));
+        assert!(html.contains(&format!("href=\"/explorer/tx/{}\"", signature)));
+        assert!(html.contains(&format!("<a href=\"/accounts/{}\">JSON</a>", wallet)));
+        // The owner links to the registry's name for it.
+        assert!(html.contains(&format!(
+            "<a href=\"/explorer/address/{0}\"><code>{0}</code></a> \
+             <span class=\"muted\">System Program</span>",
+            solana_sdk::system_program::id()
+        )));
+
+        let (status, html) = transaction(&state, signature.to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("<span class=\"ok\">Success</span>"));
+        assert!(html.contains("<h2>Instructions (1)</h2>"));
+        assert!(html.contains("<span class=\"muted\">Cold &lt;storage&gt;</span>"));
+        assert!(html.contains("(5000 lamports)"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unknown_transactions_render_an_error_page() {
+        let state = mock_state();
+        let (status, html) = transaction(&state, "garbage".to_string()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(html.contains("Invalid transaction signature"));
+
+        let unknown = solana_sdk::signature::Signature::new_unique().to_string();
+        let (status, _) = transaction(&state, unknown).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+    }
+}
@@ -0,0 +1,223 @@
+use futures_util::{stream, Stream, StreamExt};
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::sync::Semaphore;
+
+use crate::{config::Config, rpc, service::ServiceError};
+
+pub use crate::api::Failures;
+
+// How far a fan-out spreads: calls in flight at once and how long each may
+// take. From `rpc_fanout_concurrency` and `rpc_fanout_timeout_secs`.
+#[derive(Clone, Copy)]
+pub struct Limits {
+    pub concurrency: usize,
+    pub timeout: Duration,
+}
+
+impl Limits {
+    pub fn new(config: &Config) -> Self {
+        Limits {
+            concurrency: config.rpc_fanout_concurrency,
+            timeout: Duration::from_secs(config.rpc_fanout_timeout_secs),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum CallError {
+    Failed(String),
+    TimedOut(Duration),
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::Failed(message) => f.write_str(message),
+            CallError::TimedOut(timeout) => write!(f, "no answer in {}s", timeout.as_secs()),
+        }
+    }
+}
+
+// Runs the blocking `call` for every item on the blocking pool, at most
+// `limits.concurrency` at once, and yields the results in item order as
+// they come in. A call that fails or runs past `limits.timeout` is an
+// error in its own slot; the others carry on. A timed-out call keeps its
+// slot until it actually returns, so a stuck node can't end up with more
+// than `concurrency` calls in flight.
+pub fn stream<I, T, F>(
+    limits: Limits,
+    items: impl IntoIterator<Item = I>,
+    call: F,
+) -> impl Stream<Item = Result<T, CallError>>
+where
+    I: Send + 'static,
+    T: Send + 'static,
+    F: Fn(I) -> Result<T, ServiceError> + Send + Sync + 'static,
+{
+    let concurrency = limits.concurrency.max(1);
+    let slots = Arc::new(Semaphore::new(concurrency));
+    let call = Arc::new(call);
+    stream::iter(items)
+        .map(move |item| {
+            let slots = slots.clone();
+            let call = call.clone();
+            async move {
+                let slot = slots
+                    .acquire_owned()
+                    .await
+                    .expect("the semaphore is never closed");
+                let task = rpc::spawn_blocking(move || {
+                    let _slot = slot;
+                    call(item)
+                });
+                match tokio::time::timeout(limits.timeout, task).await {
+                    Ok(Ok(result)) => result.map_err(|e| CallError::Failed(e.to_string())),
+                    Ok(Err(e)) => Err(CallError::Failed(e.to_string())),
+                    Err(_) => Err(CallError::TimedOut(limits.timeout)),
+                }
+            }
+        })
+        .buffered(concurrency)
+}
+
+// `stream`, collected.
+pub async fn run<I, T, F>(
+    limits: Limits,
+    items: impl IntoIterator<Item = I>,
+    call: F,
+) -> Vec<Result<T, CallError>>
+where
+    I: Send + 'static,
+    T: Send + 'static,
+    F: Fn(I) -> Result<T, ServiceError> + Send + Sync + 'static,
+{
+    stream(limits, items, call).collect().await
+}
+
+// Tallies the failed calls of a fan-out.
+pub fn failures<T>(results: &[Result<T, CallError>]) -> Failures {
+    let mut failures = Failures {
+        calls: results.len(),
+        ..Default::default()
+    };
+    for error in results.iter().filter_map(|r| r.as_ref().err()) {
+        failures.failed += 1;
+        if matches!(error, CallError::TimedOut(_)) {
+            failures.timed_out += 1;
+        }
+        *failures.errors.entry(error.to_string()).or_default() += 1;
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn limits(concurrency: usize, timeout_ms: u64) -> Limits {
+        Limits {
+            concurrency,
+            timeout: Duration::from_millis(timeout_ms),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn results_keep_item_order() {
+        // Later items answer first.
+        let results = run(limits(4, 5_000), 0..8u64, |i| {
+            std::thread::sleep(Duration::from_millis(80 - i * 10));
+            if i == 5 {
+                return Err(ServiceError::Invalid("five is out".to_string()));
+            }
+            Ok(i * 10)
+        })
+        .await;
+        let values: Vec<Option<u64>> = results.iter().map(|r| r.as_ref().ok().copied()).collect();
+        assert_eq!(
+            values,
+            [
+                Some(0),
+                Some(10),
+                Some(20),
+                Some(30),
+                Some(40),
+                None,
+                Some(60),
+                Some(70)
+            ]
+        );
+        assert_eq!(results[5].as_ref().unwrap_err().to_string(), "five is out");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn calls_in_flight_stay_under_the_limit() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let (now, peak) = (in_flight.clone(), most.clone());
+        let results = run(limits(3, 5_000), 0..12, move |i| {
+            let current = now.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(current, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+            now.fetch_sub(1, Ordering::SeqCst);
+            Ok(i)
+        })
+        .await;
+        assert_eq!(results.len(), 12);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(most.load(Ordering::SeqCst), 3);
+
+        // No concurrency still makes progress, one call at a time.
+        let results = run(limits(0, 5_000), ["a", "b"], |s| Ok(s.len())).await;
+        assert_eq!(results.len(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn slow_calls_time_out_on_their_own() {
+        let results = run(limits(2, 100), [0u64, 400, 0], |ms| {
+            std::thread::sleep(Duration::from_millis(ms));
+            Ok(ms)
+        })
+        .await;
+        assert_eq!(results[0].as_ref().unwrap(), &0);
+        assert!(matches!(results[1], Err(CallError::TimedOut(_))));
+        assert_eq!(results[2].as_ref().unwrap(), &0);
+        assert_eq!(
+            CallError::TimedOut(Duration::from_secs(5)).to_string(),
+            "no answer in 5s"
+        );
+    }
+
+    #[test]
+    fn failures_are_tallied_by_message() {
+        let results: Vec<Result<u8, CallError>> = vec![
+            Ok(1),
+            Err(CallError::Failed("node is behind".to_string())),
+            Err(CallError::TimedOut(Duration::from_secs(3))),
+            Err(CallError::Failed("node is behind".to_string())),
+        ];
+        let failures = failures(&results);
+        assert_eq!(failures.calls, 4);
+        assert_eq!(failures.failed, 3);
+        assert_eq!(failures.timed_out, 1);
+        assert_eq!(
+            failures.errors.into_iter().collect::<Vec<_>>(),
+            [
+                ("no answer in 3s".to_string(), 1),
+                ("node is behind".to_string(), 2)
+            ]
+        );
+        assert!(super::failures::<u8>(&[Ok(1)]).is_empty());
+    }
+
+    #[test]
+    fn limits_come_from_the_config() {
+        let limits = Limits::new(&Config {
+            rpc_fanout_concurrency: 7,
+            rpc_fanout_timeout_secs: 9,
+            ..Config::default()
+        });
+        assert_eq!(limits.concurrency, 7);
+        assert_eq!(limits.timeout, Duration::from_secs(9));
+    }
+}
@@ -0,0 +1,443 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{collections::BTreeMap, sync::Mutex};
+
+use crate::{
+    config::Config,
+    handlers::{error_response, service_error, ApiError},
+    service::ServiceError,
+    state::AppState,
+};
+
+pub use crate::api::{FeatureInfo, FeatureState};
+
+// Never subject to flags, so a bad flag can always be undone.
+const CONTROL_ROUTE: &str = "/admin/features";
+const MAX_OVERRIDES: usize = 100;
+
+// Named route groups a flag can switch as a whole. A trailing "*" matches
+// every route under the prefix.
+const GROUPS: &[(&str, &[&str])] = &[
+    // Everything that pays out of the treasury for a requester.
+    (
+        "faucet",
+        &[
+            "/get_airdrop",
+            "/approvals/*",
+            "/topups",
+            "/topups/*",
+            "/admin/airdrop_bulk",
+            "/admin/airdrop_bulk/*",
+            "/admin/approvals/{id}/approve",
+        ],
+    ),
+    ("transfers", &["/cnft/{asset_id}/transfer", "/swap/build"]),
+    ("swaps", &["/swap/*"]),
+    ("watchlists", &["/watchlists", "/watchlists/*"]),
+    ("governance", &["/governance/*"]),
+    ("stake_pools", &["/stake-pools/*", "/dev/stake-pools/*"]),
+    ("dev", &["/dev/*"]),
+    ("siws", &["/auth/siws/*"]),
+    // Long-lived connections: server-sent events.
+    (
+        "streaming",
+        &["/stream/*", "/tx/{signature}/stream", "/ws/*"],
+    ),
+];
+
+// Flags set through /admin/features, on top of the `features` config.
+// Kept in memory only; the config is where a deployment's surface is
+// meant to be pinned down.
+#[derive(Default)]
+pub struct Features {
+    overrides: Mutex<BTreeMap<String, FeatureState>>,
+}
+
+fn group_routes(name: &str) -> Option<&'static [&'static str]> {
+    GROUPS.iter().find(|(n, _)| *n == name).map(|(_, r)| *r)
+}
+
+fn matches(pattern: &str, route: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => route.starts_with(prefix),
+        None => pattern == route,
+    }
+}
+
+// A flag names a group or a route pattern as registered, e.g.
+// "/wallet/{pubkey}/summary".
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name == CONTROL_ROUTE {
+        return Err(format!("{} can't be switched off", CONTROL_ROUTE));
+    }
+    if group_routes(name).is_none() && !name.starts_with('/') {
+        let groups: Vec<&str> = GROUPS.iter().map(|(n, _)| *n).collect();
+        return Err(format!(
+            "unknown feature '{}', expected a route pattern or one of: {}",
+            name,
+            groups.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+impl Features {
+    // Sets or, for None, clears runtime overrides.
+    pub fn update(&self, changes: BTreeMap<String, Option<FeatureState>>) -> Result<(), String> {
+        changes.keys().try_for_each(|name| validate_name(name))?;
+        let mut overrides = self.overrides.lock().unwrap();
+        let mut updated = overrides.clone();
+        for (name, state) in changes {
+            match state {
+                Some(state) => updated.insert(name, state),
+                None => updated.remove(&name),
+            };
+        }
+        if updated.len() > MAX_OVERRIDES {
+            return Err(format!("at most {} overrides are allowed", MAX_OVERRIDES));
+        }
+        *overrides = updated;
+        Ok(())
+    }
+
+    fn get(&self, config: &Config, name: &str) -> Option<FeatureState> {
+        self.overrides
+            .lock()
+            .unwrap()
+            .get(name)
+            .or_else(|| config.features.get(name))
+            .copied()
+    }
+
+    // A flag on the route itself wins over its groups'; of those, the first
+    // group that isn't enabled decides.
+    pub fn state_for(&self, config: &Config, route: &str) -> FeatureState {
+        if route == CONTROL_ROUTE {
+            return FeatureState::Enabled;
+        }
+        if let Some(state) = self.get(config, route) {
+            return state;
+        }
+        GROUPS
+            .iter()
+            .filter(|(_, routes)| routes.iter().any(|p| matches(p, route)))
+            .filter_map(|(name, _)| self.get(config, name))
+            .find(|s| *s != FeatureState::Enabled)
+            .unwrap_or(FeatureState::Enabled)
+    }
+
+    // Every group, then every route with a flag of its own.
+    pub fn list(&self, config: &Config) -> Vec<FeatureInfo> {
+        let overrides = self.overrides.lock().unwrap().clone();
+        let info = |name: &str, routes: Vec<&'static str>| {
+            let configured = config.features.get(name).copied();
+            let runtime_override = overrides.get(name).copied();
+            FeatureInfo {
+                name: name.to_string(),
+                state: runtime_override
+                    .or(configured)
+                    .unwrap_or(FeatureState::Enabled),
+                configured,
+                runtime_override,
+                routes: routes.iter().map(|r| r.to_string()).collect(),
+            }
+        };
+        let mut features: Vec<FeatureInfo> = GROUPS
+            .iter()
+            .map(|(name, routes)| info(name, routes.to_vec()))
+            .collect();
+        let mut routes: Vec<&String> = config
+            .features
+            .keys()
+            .chain(overrides.keys())
+            .filter(|name| group_routes(name).is_none())
+            .collect();
+        routes.sort();
+        routes.dedup();
+        features.extend(routes.into_iter().map(|route| info(route, Vec::new())));
+        features
+    }
+}
+
+// What a hidden route and a route that doesn't exist both answer.
+pub fn not_found() -> ApiError {
+    error_response(StatusCode::NOT_FOUND, "Not found")
+}
+
+// Layered on the whole app, outside the auth guards, so a hidden route
+// looks the same to everyone.
+pub async fn gate(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(route) = req.extensions().get::<MatchedPath>().map(|p| p.as_str()) else {
+        return next.run(req).await;
+    };
+    match state.features.state_for(&state.config(), route) {
+        FeatureState::Enabled => next.run(req).await,
+        FeatureState::Disabled => {
+            service_error(ServiceError::FeatureDisabled(route.to_string())).into_response()
+        }
+        FeatureState::Hidden => not_found().into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(items: &[(&str, Option<FeatureState>)]) -> BTreeMap<String, Option<FeatureState>> {
+        items
+            .iter()
+            .map(|(name, state)| (name.to_string(), *state))
+            .collect()
+    }
+
+    #[test]
+    fn names_are_groups_or_routes() {
+        assert!(validate_name("faucet").is_ok());
+        assert!(validate_name("/wallet/{pubkey}/summary").is_ok());
+        assert_eq!(
+            validate_name("/admin/features").unwrap_err(),
+            "/admin/features can't be switched off"
+        );
+        assert!(validate_name("faucets")
+            .unwrap_err()
+            .starts_with("unknown feature 'faucets', expected a route pattern or one of: faucet,"));
+    }
+
+    #[test]
+    fn route_flags_win_over_groups() {
+        let config = Config {
+            features: std::collections::HashMap::from([("dev".to_string(), FeatureState::Hidden)]),
+            ..Config::default()
+        };
+        let features = Features::default();
+        assert_eq!(
+            features.state_for(&config, "/dev/buffers"),
+            FeatureState::Hidden
+        );
+        assert_eq!(
+            features.state_for(&config, "/health"),
+            FeatureState::Enabled
+        );
+
+        // /swap/build is in both "transfers" and "swaps"; the first one
+        // that isn't enabled decides.
+        features
+            .update(changes(&[
+                ("transfers", Some(FeatureState::Enabled)),
+                ("swaps", Some(FeatureState::Disabled)),
+            ]))
+            .unwrap();
+        assert_eq!(
+            features.state_for(&config, "/swap/build"),
+            FeatureState::Disabled
+        );
+        assert_eq!(
+            features.state_for(&config, "/swap/quote"),
+            FeatureState::Disabled
+        );
+
+        features
+            .update(changes(&[
+                ("/swap/build", Some(FeatureState::Enabled)),
+                ("dev", Some(FeatureState::Enabled)),
+            ]))
+            .unwrap();
+        assert_eq!(
+            features.state_for(&config, "/swap/build"),
+            FeatureState::Enabled
+        );
+        assert_eq!(
+            features.state_for(&config, "/dev/buffers"),
+            FeatureState::Enabled
+        );
+
+        // Clearing the override falls back to the config.
+        features.update(changes(&[("dev", None)])).unwrap();
+        assert_eq!(
+            features.state_for(&config, "/dev/buffers"),
+            FeatureState::Hidden
+        );
+        assert_eq!(
+            features.state_for(&config, CONTROL_ROUTE),
+            FeatureState::Enabled
+        );
+    }
+
+    #[test]
+    fn invalid_updates_change_nothing() {
+        let features = Features::default();
+        let config = Config::default();
+        assert!(features
+            .update(changes(&[
+                ("faucet", Some(FeatureState::Disabled)),
+                ("bogus", Some(FeatureState::Disabled)),
+            ]))
+            .is_err());
+        assert_eq!(
+            features.state_for(&config, "/get_airdrop"),
+            FeatureState::Enabled
+        );
+
+        let many: Vec<(String, Option<FeatureState>)> = (0..=MAX_OVERRIDES)
+            .map(|i| (format!("/route/{}", i), Some(FeatureState::Hidden)))
+            .collect();
+        assert_eq!(
+            features.update(many.into_iter().collect()).unwrap_err(),
+            "at most 100 overrides are allowed"
+        );
+    }
+
+    #[test]
+    fn list_shows_groups_then_flagged_routes() {
+        let config = Config {
+            features: std::collections::HashMap::from([
+                ("/b".to_string(), FeatureState::Hidden),
+                ("siws".to_string(), FeatureState::Disabled),
+            ]),
+            ..Config::default()
+        };
+        let features = Features::default();
+        features
+            .update(changes(&[
+                ("/a", Some(FeatureState::Disabled)),
+                ("/b", Some(FeatureState::Enabled)),
+            ]))
+            .unwrap();
+
+        let list = features.list(&config);
+        let names: Vec<&str> = list.iter().map(|f| f.name.as_str()).collect();
+        let groups: Vec<&str> = GROUPS.iter().map(|(n, _)| *n).collect();
+        assert_eq!(names[..GROUPS.len()], groups);
+        assert_eq!(&names[GROUPS.len()..], ["/a", "/b"]);
+
+        let siws = list.iter().find(|f| f.name == "siws").unwrap();
+        assert_eq!(siws.state, FeatureState::Disabled);
+        assert_eq!(siws.routes, ["/auth/siws/*"]);
+        let b = list.last().unwrap();
+        assert_eq!(
+            (b.state, b.configured, b.runtime_override),
+            (
+                FeatureState::Enabled,
+                Some(FeatureState::Hidden),
+                Some(FeatureState::Enabled)
+            )
+        );
+    }
+
+    #[test]
+    fn streaming_switches_every_stream() {
+        let config = Config {
+            features: std::collections::HashMap::from([(
+                "streaming".to_string(),
+                FeatureState::Disabled,
+            )]),
+            ..Config::default()
+        };
+        let features = Features::default();
+        for route in [
+            "/stream/events",
+            "/tx/{signature}/stream",
+            "/ws/events/{program_id}",
+        ] {
+            assert_eq!(features.state_for(&config, route), FeatureState::Disabled);
+        }
+        assert_eq!(
+            features.state_for(&config, "/tx/{signature}"),
+            FeatureState::Enabled
+        );
+    }
+
+    #[tokio::test]
+    async fn gate_hides_or_refuses_routes() {
+        use axum::{middleware, routing::get, Router};
+
+        let state = AppState::for_tests(Config::default());
+        state
+            .features
+            .update(changes(&[
+                ("dev", Some(FeatureState::Hidden)),
+                ("/health", Some(FeatureState::Disabled)),
+            ]))
+            .unwrap();
+        let app = Router::new()
+            .route("/dev/buffers", get(|| async { "buffers" }))
+            .route("/health", get(|| async { "ok" }))
+            .route("/version", get(|| async { "1" }))
+            .layer(middleware::from_fn_with_state(state.clone(), gate))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let status = |path: &'static str| {
+            let url = format!("{}{}", base, path);
+            async move { reqwest::get(url).await.unwrap().status() }
+        };
+        assert_eq!(status("/dev/buffers").await, 404);
+        assert_eq!(status("/health").await, 403);
+        assert_eq!(status("/version").await, 200);
+
+        let res = reqwest::get(format!("{}/dev/buffers", base)).await.unwrap();
+        let body: serde_json::Value = res.json().await.unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "success": false,
+                "error": {"code": "not_found", "message": "Not found"},
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn hiding_the_faucet_hides_every_payout() {
+        use axum::{middleware, routing::post, Router};
+
+        let state = AppState::for_tests(Config::default());
+        state
+            .features
+            .update(changes(&[("faucet", Some(FeatureState::Hidden))]))
+            .unwrap();
+        let routes = [
+            "/get_airdrop",
+            "/admin/airdrop_bulk",
+            "/admin/approvals/{id}/approve",
+        ];
+        let app = routes
+            .iter()
+            .fold(Router::new(), |app, route| {
+                app.route(route, post(|| async { "paid" }))
+            })
+            .route("/admin/approvals/{id}/reject", post(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(state.clone(), gate))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        for path in [
+            "/get_airdrop",
+            "/admin/airdrop_bulk",
+            "/admin/approvals/a1/approve",
+        ] {
+            let res = client
+                .post(format!("{}{}", base, path))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(res.status(), 404, "{}", path);
+            let body: serde_json::Value = res.json().await.unwrap();
+            assert_eq!(body["error"]["code"], "not_found", "{}", path);
+        }
+        let res = client
+            .post(format!("{}/admin/approvals/a1/reject", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+    }
+}
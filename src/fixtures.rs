@@ -0,0 +1,150 @@
+use solana_account_decoder::UiAccount;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::{fs, path::Path, str::FromStr};
+
+pub use crate::api::{Fixture, FixtureSummary};
+
+fn summary(fixture: &Fixture, account: &Account) -> FixtureSummary {
+    FixtureSummary {
+        pubkey: fixture.pubkey.clone(),
+        owner: account.owner.to_string(),
+        lamports: account.lamports,
+        data_len: account.data.len(),
+        executable: account.executable,
+    }
+}
+
+fn validate(fixture: &Fixture) -> Result<Account, String> {
+    Pubkey::from_str(&fixture.pubkey)
+        .map_err(|_| format!("invalid fixture address '{}'", fixture.pubkey))?;
+    let account: UiAccount = serde_json::from_value(fixture.account.clone())
+        .map_err(|e| format!("invalid account for fixture '{}': {}", fixture.pubkey, e))?;
+    Pubkey::from_str(&account.owner)
+        .map_err(|_| format!("invalid owner for fixture '{}'", fixture.pubkey))?;
+    account.decode::<Account>().ok_or_else(|| {
+        format!(
+            "fixture '{}' must have base64 or base58 account data",
+            fixture.pubkey
+        )
+    })
+}
+
+// Validates every fixture before writing any, then stores each as
+// `<pubkey>.json`, replacing an earlier fixture for the same address.
+pub fn save(dir: &Path, fixtures: &[Fixture]) -> Result<Vec<FixtureSummary>, String> {
+    let accounts = fixtures
+        .iter()
+        .map(validate)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    fs::create_dir_all(dir).map_err(|e| format!("failed to create '{}': {}", dir.display(), e))?;
+    let mut saved = Vec::new();
+    for (fixture, account) in fixtures.iter().zip(&accounts) {
+        let path = dir.join(format!("{}.json", fixture.pubkey));
+        let text = serde_json::to_string_pretty(fixture).map_err(|e| e.to_string())?;
+        fs::write(&path, text)
+            .map_err(|e| format!("failed to write '{}': {}", path.display(), e))?;
+        saved.push(summary(fixture, account));
+    }
+    Ok(saved)
+}
+
+pub fn list(dir: &Path) -> Result<Vec<FixtureSummary>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("failed to read '{}': {}", dir.display(), e)),
+    };
+
+    let mut fixtures = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read '{}': {}", path.display(), e))?;
+        let fixture: Fixture = serde_json::from_str(&text)
+            .map_err(|e| format!("invalid fixture '{}': {}", path.display(), e))?;
+        let account = validate(&fixture)?;
+        fixtures.push(summary(&fixture, &account));
+    }
+    fixtures.sort_by(|a, b| a.pubkey.cmp(&b.pubkey));
+    Ok(fixtures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fixture(pubkey: &str, data: &str) -> Fixture {
+        Fixture {
+            pubkey: pubkey.to_string(),
+            account: json!({
+                "lamports": 1_461_600,
+                "data": [data, "base64"],
+                "owner": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 3,
+            }),
+        }
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("fixtures-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn save_then_list_summarizes_each_account() {
+        let dir = temp_dir("save");
+        let a = Pubkey::new_unique().to_string();
+        let b = Pubkey::new_unique().to_string();
+        // "AQID" is base64 for [1, 2, 3].
+        let saved = save(&dir, &[fixture(&b, "AQID"), fixture(&a, "")]).unwrap();
+        assert_eq!(saved[0].pubkey, b);
+        assert_eq!(saved[0].data_len, 3);
+        assert_eq!(saved[0].lamports, 1_461_600);
+        assert_eq!(
+            saved[0].owner,
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+        );
+
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        let listed = list(&dir).unwrap();
+        let mut expected = vec![a, b];
+        expected.sort();
+        assert_eq!(
+            listed.iter().map(|f| f.pubkey.clone()).collect::<Vec<_>>(),
+            expected
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn one_bad_fixture_writes_nothing() {
+        let dir = temp_dir("invalid");
+        let good = fixture(&Pubkey::new_unique().to_string(), "AQID");
+        let err = save(&dir, &[good, fixture("not-a-pubkey", "AQID")]).unwrap_err();
+        assert_eq!(err, "invalid fixture address 'not-a-pubkey'");
+        assert!(!dir.exists());
+
+        let mut bad_owner = fixture(&Pubkey::new_unique().to_string(), "AQID");
+        bad_owner.account["owner"] = json!("nope");
+        assert!(save(&dir, &[bad_owner])
+            .unwrap_err()
+            .starts_with("invalid owner"));
+
+        let mut parsed = fixture(&Pubkey::new_unique().to_string(), "AQID");
+        parsed.account["data"] = json!({ "parsed": {}, "program": "spl-token", "space": 3 });
+        assert!(save(&dir, &[parsed])
+            .unwrap_err()
+            .ends_with("must have base64 or base58 account data"));
+    }
+
+    #[test]
+    fn missing_dir_lists_nothing() {
+        assert!(list(&temp_dir("missing")).unwrap().is_empty());
+    }
+}
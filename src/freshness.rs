@@ -0,0 +1,177 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use solana_client::{
+    client_error::ClientErrorKind,
+    rpc_client::RpcClient,
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use std::{sync::RwLock, time::Duration};
+
+use crate::{clock::now_ms, envelope, rpc, state::AppState, tasks};
+
+pub use crate::api::{LagSource, SlotStatus};
+
+// Roughly how long a slot takes, for turning a slot lag into time.
+const MS_PER_SLOT: u64 = 400;
+
+// How far the configured node trails the cluster, checked every
+// `slot_lag_check_interval_secs`.
+#[derive(Default)]
+pub struct SlotTracker {
+    status: RwLock<Option<SlotStatus>>,
+}
+
+// Healthy nodes are within the health check's slot distance, so count as
+// current; unhealthy ones report how far behind they are when they know.
+fn health_lag(client: &RpcClient) -> Option<u64> {
+    match client.get_health() {
+        Ok(()) => Some(0),
+        Err(e) => match e.kind() {
+            ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                data: RpcResponseErrorData::NodeUnhealthy { num_slots_behind },
+                ..
+            }) => *num_slots_behind,
+            _ => None,
+        },
+    }
+}
+
+// Blocking: the node's slot and the best lag estimate available.
+pub fn check(rpc_url: &str, reference_url: Option<&str>) -> Result<SlotStatus, String> {
+    let client = rpc::client(rpc_url);
+    let slot = client.get_slot().map_err(|e| e.to_string())?;
+    let lag = match reference_url {
+        Some(url) => rpc::client(url)
+            .get_slot()
+            .ok()
+            .map(|reference| (reference.saturating_sub(slot), LagSource::Reference)),
+        None => None,
+    }
+    .or_else(|| health_lag(&client).map(|lag| (lag, LagSource::Health)))
+    .or_else(|| {
+        client
+            .get_max_retransmit_slot()
+            .ok()
+            .map(|max| (max.saturating_sub(slot), LagSource::Retransmit))
+    });
+    Ok(SlotStatus {
+        slot,
+        lag_slots: lag.map(|(lag, _)| lag),
+        lag_ms_estimate: lag.map(|(lag, _)| lag * MS_PER_SLOT),
+        lag_source: lag.map(|(_, source)| source),
+        checked_at_ms: now_ms(),
+        error: None,
+    })
+}
+
+impl SlotTracker {
+    pub fn status(&self) -> Option<SlotStatus> {
+        self.status.read().unwrap().clone()
+    }
+
+    pub fn lag_slots(&self) -> Option<u64> {
+        self.status().and_then(|s| s.lag_slots)
+    }
+
+    fn record(&self, result: Result<SlotStatus, String>) {
+        let mut status = self.status.write().unwrap();
+        match (result, status.as_mut()) {
+            (Ok(fresh), _) => *status = Some(fresh),
+            (Err(e), Some(previous)) => previous.error = Some(e),
+            // Nothing to report until the node has answered once.
+            (Err(_), None) => {}
+        }
+    }
+}
+
+pub async fn watch(state: AppState) {
+    loop {
+        let config = state.config();
+        let rpc_url = config.rpc_url.clone();
+        let reference_url = config.rpc_reference_url.clone();
+        let result = rpc::spawn_blocking(move || check(&rpc_url, reference_url.as_deref()))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+        if result.is_ok() {
+            tasks::succeeded();
+        }
+        state.slots.record(result);
+        tokio::time::sleep(Duration::from_secs(config.slot_lag_check_interval_secs)).await;
+    }
+}
+
+// Layered on the read routes: their responses carry the slot their data
+// was observed at as `context.slot`, and the last slot check as
+// `data_freshness`, so clients can tell how current the data is.
+pub async fn attach(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    envelope::with_read_context(state.slots.status(), next.run(req)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_rpc_client::mock_sender::Mocks;
+
+    fn status(slot: u64) -> SlotStatus {
+        SlotStatus {
+            slot,
+            lag_slots: Some(3),
+            lag_ms_estimate: Some(3 * MS_PER_SLOT),
+            lag_source: Some(LagSource::Health),
+            checked_at_ms: 0,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn health_lag_from_the_node() {
+        let healthy = RpcClient::new_mock_with_mocks(
+            "succeeds".to_string(),
+            Mocks::from([(RpcRequest::GetHealth, json!("ok"))]),
+        );
+        assert_eq!(health_lag(&healthy), Some(0));
+        // An answer that isn't a health response says nothing about lag.
+        let unknown = RpcClient::new_mock("succeeds".to_string());
+        assert_eq!(health_lag(&unknown), None);
+    }
+
+    #[test]
+    fn check_prefers_the_reference_node() {
+        rpc::mock_for_tests();
+        let status = check("http://mock", Some("http://reference")).unwrap();
+        assert_eq!(status.lag_slots, Some(0));
+        assert_eq!(status.lag_ms_estimate, Some(0));
+        assert!(matches!(status.lag_source, Some(LagSource::Reference)));
+        assert!(status.error.is_none());
+
+        let status = check("http://mock", None).unwrap();
+        assert!(matches!(status.lag_source, Some(LagSource::Health)));
+    }
+
+    #[test]
+    fn failed_checks_keep_the_last_slot() {
+        let tracker = SlotTracker::default();
+        tracker.record(Err("connection refused".into()));
+        assert!(tracker.status().is_none());
+        assert_eq!(tracker.lag_slots(), None);
+
+        tracker.record(Ok(status(100)));
+        assert_eq!(tracker.lag_slots(), Some(3));
+        assert_eq!(tracker.status().unwrap().lag_ms_estimate, Some(1_200));
+
+        tracker.record(Err("timed out".into()));
+        let kept = tracker.status().unwrap();
+        assert_eq!(kept.slot, 100);
+        assert_eq!(kept.error.as_deref(), Some("timed out"));
+
+        tracker.record(Ok(status(101)));
+        let fresh = tracker.status().unwrap();
+        assert_eq!(fresh.slot, 101);
+        assert!(fresh.error.is_none());
+    }
+}
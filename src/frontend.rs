@@ -0,0 +1,133 @@
+use crate::{
+    config::{Backend, Config},
+    features::FeatureState,
+    state::AppState,
+};
+
+pub use crate::api::{Faucet, FrontendConfig, FrontendFeatures, Theme};
+
+// `cluster_name`, or the cluster `rpc_url` points at.
+pub fn cluster_name(config: &Config) -> String {
+    if let Some(name) = &config.cluster_name {
+        return name.clone();
+    }
+    if config.backend == Backend::Mock {
+        return "mock".to_string();
+    }
+    let url = config.rpc_url.to_ascii_lowercase();
+    let name = if url.contains("devnet") {
+        "devnet"
+    } else if url.contains("testnet") {
+        "testnet"
+    } else if url.contains("mainnet") {
+        "mainnet-beta"
+    } else if url.contains("localhost") || url.contains("127.0.0.1") {
+        "localnet"
+    } else {
+        "custom"
+    };
+    name.to_string()
+}
+
+pub fn config(state: &AppState) -> FrontendConfig {
+    let config = state.config();
+    let enabled = |route: &str| state.features.state_for(&config, route) == FeatureState::Enabled;
+    let cluster = cluster_name(&config);
+    let title = config.frontend_title.clone().unwrap_or_else(|| {
+        let mut chars = cluster.chars();
+        let capitalized: String = chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default();
+        format!("Solana {} Airdrop", capitalized)
+    });
+    FrontendConfig {
+        title,
+        theme: Theme {
+            mode: config.frontend_theme,
+            accent_color: config.frontend_accent_color.clone(),
+            logo_url: config.frontend_logo_url.clone(),
+        },
+        faucet: Faucet {
+            enabled: enabled("/get_airdrop"),
+            min_airdrop_lamports: config.min_airdrop_lamports,
+            max_airdrop_sol: config.max_airdrop_sol,
+            default_airdrop_sol: config.max_airdrop_sol.min(1),
+            daily_quota_sol: config.airdrop_daily_quota_sol,
+            approval_threshold_lamports: config.airdrop_approval_threshold_lamports,
+            receipts: config.airdrop_receipts && state.keypair.is_some(),
+        },
+        features: FrontendFeatures {
+            balance: enabled("/get_balance"),
+            sign_in: config.siws_enabled && enabled("/auth/siws/challenge"),
+            require_auth: config.require_auth,
+            sign_in_required: config.siws_required,
+        },
+        cluster,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn rpc(url: &str) -> Config {
+        Config {
+            backend: Backend::Rpc,
+            rpc_url: url.to_string(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn clusters_from_the_rpc_url() {
+        for (url, cluster) in [
+            ("https://api.devnet.solana.com", "devnet"),
+            ("https://api.TESTNET.solana.com", "testnet"),
+            ("https://api.mainnet-beta.solana.com", "mainnet-beta"),
+            ("http://localhost:8899", "localnet"),
+            ("http://127.0.0.1:8899", "localnet"),
+            ("https://rpc.example.com", "custom"),
+        ] {
+            assert_eq!(cluster_name(&rpc(url)), cluster, "{}", url);
+        }
+        let mock = Config {
+            backend: Backend::Mock,
+            ..rpc("https://api.devnet.solana.com")
+        };
+        assert_eq!(cluster_name(&mock), "mock");
+        let named = Config {
+            cluster_name: Some("staging".to_string()),
+            ..mock
+        };
+        assert_eq!(cluster_name(&named), "staging");
+    }
+
+    #[test]
+    fn config_follows_settings_and_flags() {
+        let state = AppState::for_tests(Config {
+            max_airdrop_sol: 5,
+            ..rpc("https://api.devnet.solana.com")
+        });
+        let frontend = config(&state);
+        assert_eq!(frontend.cluster, "devnet");
+        assert_eq!(frontend.title, "Solana Devnet Airdrop");
+        assert_eq!(frontend.faucet.max_airdrop_sol, 5);
+        assert_eq!(frontend.faucet.default_airdrop_sol, 1);
+        assert!(frontend.faucet.enabled && frontend.features.balance);
+        assert!(!frontend.faucet.receipts);
+
+        let state = AppState::for_tests(Config {
+            frontend_title: Some("Team faucet".to_string()),
+            max_airdrop_sol: 0,
+            features: HashMap::from([("faucet".to_string(), FeatureState::Disabled)]),
+            ..rpc("http://localhost:8899")
+        });
+        let frontend = config(&state);
+        assert_eq!(frontend.title, "Team faucet");
+        assert_eq!(frontend.faucet.default_airdrop_sol, 0);
+        assert!(!frontend.faucet.enabled);
+        assert!(frontend.features.balance);
+    }
+}
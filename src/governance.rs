@@ -0,0 +1,413 @@
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    layout::Reader,
+    logging, rpc,
+    service::{rpc_error, ServiceError},
+};
+
+pub use crate::api::{Proposal, ProposalOption, Timeline, VoteCounts};
+
+// GovernanceAccountType tags. Governance and token owner records keep their
+// realm at offset 1, as proposals keep their governance.
+const REALM_TYPES: [u8; 2] = [1, 16];
+const GOVERNANCE_TYPES: [u8; 8] = [3, 4, 9, 10, 18, 19, 20, 21];
+const PROPOSAL_V1: u8 = 5;
+const PROPOSAL_V2: u8 = 14;
+const PARENT_OFFSET: usize = 1;
+
+fn proposal_state(tag: u8) -> &'static str {
+    match tag {
+        0 => "draft",
+        1 => "signing_off",
+        2 => "voting",
+        3 => "succeeded",
+        4 => "executing",
+        5 => "completed",
+        6 => "cancelled",
+        7 => "defeated",
+        8 => "executing_with_errors",
+        9 => "vetoed",
+        _ => "unknown",
+    }
+}
+
+fn vote_result(tag: u8) -> &'static str {
+    match tag {
+        0 => "none",
+        1 => "succeeded",
+        2 => "defeated",
+        _ => "unknown",
+    }
+}
+
+// ProposalV2, as written by spl-governance 3.x.
+fn parse_proposal(address: &Pubkey, data: &[u8]) -> Result<Proposal, String> {
+    let mut r = Reader::new(data);
+    match r.u8()? {
+        PROPOSAL_V2 => {}
+        PROPOSAL_V1 => return Err("v1 proposals are not supported".to_string()),
+        _ => return Err("not a proposal account".to_string()),
+    }
+    let governance = r.pubkey()?;
+    let governing_token_mint = r.pubkey()?;
+    let state = proposal_state(r.u8()?);
+    let token_owner_record = r.pubkey()?;
+    let signatories_count = r.u8()?;
+    let signatories_signed_off_count = r.u8()?;
+    let vote_type = match r.u8()? {
+        0 => "single_choice",
+        _ => {
+            // choice_type, min/max voter options, max winning options.
+            r.take(4)?;
+            "multi_choice"
+        }
+    };
+    let option_count = r.u32()?;
+    let mut options = Vec::new();
+    for _ in 0..option_count {
+        let label = r.string()?;
+        let vote_weight = r.u64()?;
+        let vote_result = vote_result(r.u8()?);
+        let transactions_executed_count = r.u16()?;
+        let transactions_count = r.u16()?;
+        let _transactions_next_index = r.u16()?;
+        options.push(ProposalOption {
+            label,
+            vote_weight,
+            vote_result: vote_result.to_string(),
+            transactions_count,
+            transactions_executed_count,
+        });
+    }
+    let deny_vote_weight = r.option(Reader::u64)?;
+    let _reserved = r.u8()?;
+    let abstain_vote_weight = r.option(Reader::u64)?;
+    let _start_voting_at = r.option(Reader::i64)?;
+    let draft_at = r.i64()?;
+    let signing_off_at = r.option(Reader::i64)?;
+    let voting_at = r.option(Reader::i64)?;
+    let voting_at_slot = r.option(Reader::u64)?;
+    let voting_completed_at = r.option(Reader::i64)?;
+    let executing_at = r.option(Reader::i64)?;
+    let closed_at = r.option(Reader::i64)?;
+    let _execution_flags = r.u8()?;
+    let max_vote_weight = r.option(Reader::u64)?;
+    let max_voting_time_secs = r.option(Reader::u32)?;
+    let vote_threshold = r.option(|r| {
+        Ok(match r.u8()? {
+            0 => format!("yes_vote_percentage:{}", r.u8()?),
+            1 => format!("quorum_percentage:{}", r.u8()?),
+            _ => "disabled".to_string(),
+        })
+    })?;
+    r.take(64)?;
+    let name = r.string()?;
+    let description_link = r.string()?;
+    let veto_vote_weight = r.u64()?;
+
+    Ok(Proposal {
+        address: address.to_string(),
+        governance: governance.to_string(),
+        governing_token_mint: governing_token_mint.to_string(),
+        token_owner_record: token_owner_record.to_string(),
+        name,
+        description_link,
+        state: state.to_string(),
+        vote_type: vote_type.to_string(),
+        votes: VoteCounts {
+            yes: options
+                .first()
+                .filter(|_| vote_type == "single_choice")
+                .map(|o| o.vote_weight),
+            no: deny_vote_weight,
+            abstain: abstain_vote_weight,
+            veto: veto_vote_weight,
+            max_vote_weight,
+        },
+        timeline: Timeline {
+            draft_at,
+            signing_off_at,
+            voting_at,
+            voting_at_slot,
+            voting_completed_at,
+            executing_at,
+            closed_at,
+        },
+        signatories_count,
+        signatories_signed_off_count,
+        vote_threshold,
+        max_voting_time_secs,
+        options,
+    })
+}
+
+fn parent_filter(parent: &Pubkey) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+        PARENT_OFFSET,
+        parent.to_bytes().to_vec(),
+    ))
+}
+
+// The governance program that owns `realm`; realms can live under any
+// deployment of it.
+fn realm_program(client: &RpcClient, realm: &Pubkey) -> Result<Pubkey, ServiceError> {
+    let account = client
+        .get_account(realm)
+        .map_err(rpc_error("Failed to load realm"))?;
+    if !account
+        .data
+        .first()
+        .is_some_and(|t| REALM_TYPES.contains(t))
+    {
+        return Err(ServiceError::Invalid(format!("{} is not a realm", realm)));
+    }
+    Ok(account.owner)
+}
+
+// Every proposal in the realm, newest draft first.
+pub fn proposals(rpc_url: &str, realm: &Pubkey) -> Result<Vec<Proposal>, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.governance_proposals", %rpc_url, %realm).entered();
+    let program_id = realm_program(&client, realm)?;
+
+    // Only the type tag is needed to pick the governances out of the
+    // realm's other accounts (token owner records can be numerous).
+    let children = client
+        .get_program_accounts_with_config(
+            &program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![parent_filter(realm)]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    data_slice: Some(UiDataSliceConfig {
+                        offset: 0,
+                        length: 1,
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .map_err(rpc_error("Failed to list governances"))?;
+
+    let mut proposals = Vec::new();
+    for (governance, account) in children {
+        if !account
+            .data
+            .first()
+            .is_some_and(|t| GOVERNANCE_TYPES.contains(t))
+        {
+            continue;
+        }
+        let accounts = client
+            .get_program_accounts_with_config(
+                &program_id,
+                RpcProgramAccountsConfig {
+                    filters: Some(vec![
+                        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, vec![PROPOSAL_V2])),
+                        parent_filter(&governance),
+                    ]),
+                    account_config: RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .map_err(rpc_error("Failed to list proposals"))?;
+        for (address, account) in accounts {
+            match parse_proposal(&address, &account.data) {
+                Ok(proposal) => proposals.push(proposal),
+                Err(e) => logging::warn(format_args!("Skipping proposal {}: {}", address, e)),
+            }
+        }
+    }
+    proposals.sort_by_key(|p| std::cmp::Reverse(p.timeline.draft_at));
+    Ok(proposals)
+}
+
+pub fn proposal(rpc_url: &str, realm: &Pubkey, address: &Pubkey) -> Result<Proposal, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.governance_proposal", %rpc_url, %address).entered();
+    let program_id = realm_program(&client, realm)?;
+    let account = client
+        .get_account(address)
+        .map_err(rpc_error("Failed to load proposal"))?;
+    let not_in_realm =
+        || ServiceError::Invalid(format!("{} is not a proposal in realm {}", address, realm));
+    if account.owner != program_id {
+        return Err(not_in_realm());
+    }
+    let proposal = parse_proposal(address, &account.data)
+        .map_err(|e| ServiceError::Invalid(format!("{}: {}", address, e)))?;
+
+    let governance: Pubkey = proposal.governance.parse().map_err(|_| not_in_realm())?;
+    let governance = client
+        .get_account_data(&governance)
+        .map_err(rpc_error("Failed to load governance"))?;
+    if governance.get(PARENT_OFFSET..PARENT_OFFSET + 32) != Some(realm.as_ref()) {
+        return Err(not_in_realm());
+    }
+    Ok(proposal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+            self.0.extend_from_slice(bytes);
+            self
+        }
+        fn u8(&mut self, v: u8) -> &mut Self {
+            self.bytes(&[v])
+        }
+        fn u16(&mut self, v: u16) -> &mut Self {
+            self.bytes(&v.to_le_bytes())
+        }
+        fn u32(&mut self, v: u32) -> &mut Self {
+            self.bytes(&v.to_le_bytes())
+        }
+        fn u64(&mut self, v: u64) -> &mut Self {
+            self.bytes(&v.to_le_bytes())
+        }
+        fn i64(&mut self, v: i64) -> &mut Self {
+            self.bytes(&v.to_le_bytes())
+        }
+        fn string(&mut self, v: &str) -> &mut Self {
+            self.u32(v.len() as u32).bytes(v.as_bytes())
+        }
+        fn key(&mut self, key: &Pubkey) -> &mut Self {
+            self.bytes(key.as_ref())
+        }
+    }
+
+    // A single choice proposal that passed: voting opened at 1_700_000_100
+    // and completed at 1_700_259_300, with 60% yes needed.
+    fn proposal_data(governance: &Pubkey, mint: &Pubkey, owner_record: &Pubkey) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.u8(PROPOSAL_V2)
+            .key(governance)
+            .key(mint)
+            .u8(3) // succeeded
+            .key(owner_record)
+            .u8(1)
+            .u8(1)
+            .u8(0) // single choice
+            .u32(1)
+            .string("Approve")
+            .u64(7_000_000)
+            .u8(1) // succeeded
+            .u16(0)
+            .u16(2)
+            .u16(2)
+            .u8(1) // deny_vote_weight
+            .u64(1_000_000)
+            .u8(0)
+            .u8(0) // abstain_vote_weight
+            .u8(0) // start_voting_at
+            .i64(1_700_000_000) // draft_at
+            .u8(1)
+            .i64(1_700_000_050) // signing_off_at
+            .u8(1)
+            .i64(1_700_000_100) // voting_at
+            .u8(1)
+            .u64(250_000_000) // voting_at_slot
+            .u8(1)
+            .i64(1_700_259_300) // voting_completed_at
+            .u8(0) // executing_at
+            .u8(0) // closed_at
+            .u8(0) // execution_flags
+            .u8(1)
+            .u64(10_000_000) // max_vote_weight
+            .u8(1)
+            .u32(259_200) // max_voting_time
+            .u8(1)
+            .u8(0)
+            .u8(60) // yes_vote_percentage 60
+            .bytes(&[0; 64])
+            .string("Fund the grants program")
+            .string("https://example.com/proposal")
+            .u64(0); // veto_vote_weight
+        w.0
+    }
+
+    #[test]
+    fn parses_a_v2_proposal() {
+        let (address, governance, mint, record) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let proposal =
+            parse_proposal(&address, &proposal_data(&governance, &mint, &record)).unwrap();
+
+        assert_eq!(proposal.address, address.to_string());
+        assert_eq!(proposal.governance, governance.to_string());
+        assert_eq!(proposal.governing_token_mint, mint.to_string());
+        assert_eq!(proposal.token_owner_record, record.to_string());
+        assert_eq!(proposal.name, "Fund the grants program");
+        assert_eq!(proposal.description_link, "https://example.com/proposal");
+        assert_eq!(proposal.state, "succeeded");
+        assert_eq!(proposal.vote_type, "single_choice");
+        assert_eq!(proposal.votes.yes, Some(7_000_000));
+        assert_eq!(proposal.votes.no, Some(1_000_000));
+        assert_eq!(proposal.votes.abstain, None);
+        assert_eq!(proposal.votes.max_vote_weight, Some(10_000_000));
+        assert_eq!(proposal.timeline.draft_at, 1_700_000_000);
+        assert_eq!(proposal.timeline.voting_at_slot, Some(250_000_000));
+        assert_eq!(proposal.timeline.voting_completed_at, Some(1_700_259_300));
+        assert_eq!(proposal.timeline.closed_at, None);
+        assert_eq!(
+            proposal.vote_threshold.as_deref(),
+            Some("yes_vote_percentage:60")
+        );
+        assert_eq!(proposal.max_voting_time_secs, Some(259_200));
+        assert_eq!(proposal.options.len(), 1);
+        assert_eq!(proposal.options[0].vote_result, "succeeded");
+        assert_eq!(proposal.options[0].transactions_count, 2);
+    }
+
+    #[test]
+    fn rejects_other_accounts() {
+        let key = Pubkey::new_unique();
+        let mut data = proposal_data(&key, &key, &key);
+        assert_eq!(
+            parse_proposal(&key, &data[..200]).err().unwrap(),
+            "account data is truncated"
+        );
+        data[0] = PROPOSAL_V1;
+        assert_eq!(
+            parse_proposal(&key, &data).err().unwrap(),
+            "v1 proposals are not supported"
+        );
+        data[0] = REALM_TYPES[0];
+        assert_eq!(
+            parse_proposal(&key, &data).err().unwrap(),
+            "not a proposal account"
+        );
+    }
+
+    #[test]
+    fn tags_have_names() {
+        assert_eq!(proposal_state(2), "voting");
+        assert_eq!(proposal_state(9), "vetoed");
+        assert_eq!(proposal_state(10), "unknown");
+        assert_eq!(vote_result(2), "defeated");
+        assert_eq!(vote_result(3), "unknown");
+    }
+}
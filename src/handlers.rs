@@ -0,0 +1,4547 @@
+use axum::{
+    body::Body,
+    extract::{ws::WebSocketUpgrade, FromRequest, Multipart, Path, Query, Request, State},
+    http::{header, StatusCode},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        Html, IntoResponse, Response,
+    },
+    Extension,
+};
+use base64::Engine;
+use futures_util::{stream, Stream, StreamExt};
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::{
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    address::{AddressPath, WalletAddress},
+    alerts::{self, BalanceAlert, NewAlert},
+    amount::{self, AmountOptions, SolValue},
+    anchor_events,
+    api::{
+        AccountResponse, AirdropReply, AirdropRequest, AirdropResponse, AllowlistAddRequest,
+        AllowlistAddResponse, AllowlistResponse, AnchorEventQuery, ApprovalsQuery,
+        ApprovalsResponse, AssetProofResponse, AuditQuery, AuditQueryResponse, BalanceAlertRequest,
+        BalanceAlertsResponse, BalanceAtQuery, BalanceAtResponse, BufferInfo, BulkBalancesQuery,
+        CloseBuffersRequest, CloseBuffersResponse, ClosedBuffer, CnftTransferRequest,
+        CnftTransferResponse, ConsistencyQuery, CounterpartySummary, CreateKeyRequest,
+        CreateOrgRequest, DecodeRequest, DecodeResponse, DiffSide, DryRunAirdropResponse,
+        EventStreamQuery, FailedBuffer, FeeResponse, FixturesResponse, GetBalance,
+        GetBalanceResponse, HealthDetailsResponse, HealthResponse, IndexStatusResponse,
+        IndexedAccountsQuery, IndexedAccountsResponse, InstructionResponse, IssueKeyRequest,
+        IssuedKeyResponse, JobAcceptedResponse, KeysQuery, KeysResponse, LabelQuery,
+        LabelsResponse, ListBuffersResponse, LoadFixturesRequest, MemoSearchQuery,
+        MemoSearchResponse, MintNftRequest, MintNftResponse, MockBalanceRequest, OrgReport,
+        OrgsResponse, PendingAirdropResponse, ProgramAccountsFormat, ProgramAccountsQuery,
+        ProgramAccountsResponse, ProgramEventResponse, ProgramsResponse, ProposalsQuery,
+        ProposalsResponse, QrQuery, RebalanceRequest, RebalanceResponse, RejectRequest,
+        SetLabelRequest, SetProgramNameRequest, SignMessageRequest, SignMessageResponse,
+        SignerCostResponse, SimulationResult, SiwsChallengeRequest, SiwsChallengeResponse,
+        SiwsSessionResponse, SiwsVerifyRequest, StakePoolDepositRequest, StakePoolFees,
+        StakePoolResponse, StakePoolTransactionResponse, StakePoolWithdrawRequest, SummaryQuery,
+        SwapBuildRequest, SwapBuildResponse, TokenActivitySummary, TokenDiffResponse, TopupRequest,
+        TransactionCostResponse, TransactionResponse, TransactionStatusEvent, TreasuryQuery,
+        TreasuryResponse, ValidatorListResponse, ValidatorStakeResponse, VerifySignatureRequest,
+        VerifySignatureResponse, WalletBalanceQuery, WalletBalanceResponse, WalletDiffRequest,
+        WalletDiffResponse, WalletSummaryResponse, WalletTransactionsQuery,
+        WalletTransactionsResponse, WatchlistRequest, WatchlistsResponse,
+    },
+    approvals::{Approval, ApprovalStatus, DecisionError},
+    audit::{self, Actor, AuditRecord, Caller},
+    auth::{self, Principal, Role},
+    balance_wait,
+    bulk::{self, RowError, RowResult},
+    catalog,
+    chaos::{ChaosRule, ChaosStatus},
+    cnft,
+    confirm::{self, Polling},
+    cost, denials,
+    dependencies::{self, DependencyReport},
+    deploy, diff,
+    envelope::{ErrorResponse, Json, Params, ResponseJson},
+    events::{self, Event, EventKind},
+    explorer,
+    export::{ExportError, ExportReport},
+    fanout,
+    features::{self, FeatureInfo, FeatureState},
+    fixtures, frontend,
+    governance::{self, Proposal},
+    history::{self, BalanceSource, Point},
+    idl::{self, Idl, IdlSummary, IdlUploadQuery},
+    index::Lookup,
+    inspect::{self, DecodedAccount, DecodedEvent, DecodedInstruction},
+    jobs::Job,
+    labels::Label,
+    links::{self, Linked},
+    logging,
+    mock::{Faults, MockChain, MockStatus},
+    nft::{self, NftMetadata},
+    notify::{self, OrgEvent},
+    openapi,
+    orgs::{self, Org, OrgSettings},
+    panics, pool,
+    programs::{KnownProgram, ProgramRegistry},
+    qr::{self, QrFormat},
+    receipts::{self, Receipt},
+    rpc,
+    service::{self, ServiceError},
+    signing::{self, SignedFormat},
+    slo::RouteReport,
+    stake_pool::{self, Fee, PoolAction, PoolTransaction},
+    state::{AppState, ReloadReport},
+    summary,
+    swap::{self, QuoteQuery},
+    topups::{self, Topup},
+    treasury,
+    tx_history::{self, TxKind},
+    usage::{self, RouteRpcReport, UsageReport},
+    validator::ValidatorStatus,
+    version::{self, BuildInfo},
+    watchlists::{self, Overview, Watchlist},
+};
+#[cfg(feature = "profiling")]
+use crate::{
+    api::{PprofQuery, ProfileQuery},
+    pprof::{self, ProfileError},
+    profiling::{self, CpuProfile, RuntimeReport},
+};
+
+impl From<Fee> for FeeResponse {
+    fn from(fee: Fee) -> Self {
+        FeeResponse {
+            numerator: fee.numerator,
+            denominator: fee.denominator,
+        }
+    }
+}
+
+fn account_response(account: DecodedAccount, programs: &ProgramRegistry) -> AccountResponse {
+    AccountResponse {
+        address: account.address.to_string(),
+        owner: account.account.owner.to_string(),
+        owner_name: programs.name(&account.account.owner),
+        lamports: account.account.lamports,
+        executable: account.account.executable,
+        rent_epoch: account.account.rent_epoch,
+        space: account.account.data.len(),
+        data: base64::engine::general_purpose::STANDARD.encode(&account.account.data),
+        decoded: account.decoded,
+    }
+}
+
+fn instruction_response(ix: DecodedInstruction, programs: &ProgramRegistry) -> InstructionResponse {
+    InstructionResponse {
+        program_id: ix.program_id.to_string(),
+        program_name: programs.name(&ix.program_id),
+        accounts: ix.accounts.iter().map(|a| a.to_string()).collect(),
+        data: base64::engine::general_purpose::STANDARD.encode(&ix.data),
+        decoded: ix.decoded,
+        inner_instructions: ix
+            .inner
+            .into_iter()
+            .map(|ix| instruction_response(ix, programs))
+            .collect(),
+    }
+}
+
+fn event_response(event: DecodedEvent, programs: &ProgramRegistry) -> ProgramEventResponse {
+    ProgramEventResponse {
+        program_id: event.program_id.to_string(),
+        program_name: programs.name(&event.program_id),
+        data: base64::engine::general_purpose::STANDARD.encode(&event.data),
+        decoded: event.decoded,
+    }
+}
+
+pub type ApiError = (StatusCode, ErrorResponse);
+
+pub fn error_response(status: StatusCode, error: impl Into<String>) -> ApiError {
+    (status, ErrorResponse::new(status, error.into(), None))
+}
+
+pub fn service_error(err: ServiceError) -> ApiError {
+    let (status, code) = match err {
+        ServiceError::InvalidWallet | ServiceError::Invalid(_) => (StatusCode::BAD_REQUEST, None),
+        ServiceError::HistoryUnavailable(_) => (StatusCode::UNPROCESSABLE_ENTITY, None),
+        // Airdrop denials, with their retry advice.
+        ServiceError::AirdropTooSmall { .. }
+        | ServiceError::AirdropTooLarge { .. }
+        | ServiceError::Denylisted(_)
+        | ServiceError::NotAllowlisted(_)
+        | ServiceError::SignInRequired
+        | ServiceError::SessionWalletMismatch(_)
+        | ServiceError::QuotaExceeded { .. }
+        | ServiceError::TreasuryLow
+        | ServiceError::Maintenance { .. }
+        | ServiceError::FaucetRateLimited(_)
+        | ServiceError::FaucetExhausted(_) => return denials::error(err),
+        ServiceError::RpcBudgetExceeded { .. } => {
+            (StatusCode::TOO_MANY_REQUESTS, Some("rpc_budget_exceeded"))
+        }
+        ServiceError::FeatureDisabled(_) => (StatusCode::FORBIDDEN, Some("feature_disabled")),
+        ServiceError::RpcIncompatible(_) => {
+            (StatusCode::SERVICE_UNAVAILABLE, Some("rpc_incompatible"))
+        }
+        ServiceError::DeprecatedFields(_) => (StatusCode::BAD_REQUEST, Some("deprecated_field")),
+        ServiceError::Rpc { .. } => (StatusCode::INTERNAL_SERVER_ERROR, None),
+    };
+    (status, ErrorResponse::new(status, err.to_string(), code))
+}
+
+fn require_keypair(state: &AppState) -> Result<Arc<Keypair>, ApiError> {
+    state.keypair.clone().ok_or_else(|| {
+        error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Server keypair is not configured",
+        )
+    })
+}
+
+pub async fn api_index(State(state): State<AppState>) -> ResponseJson<catalog::ApiIndex> {
+    ResponseJson(catalog::index(&state.config(), &state.features))
+}
+
+pub async fn frontend_config(
+    State(state): State<AppState>,
+) -> axum::Json<frontend::FrontendConfig> {
+    // Bare, not enveloped: it is the frontend's own file.
+    axum::Json(frontend::config(&state))
+}
+
+pub async fn serve_html() -> Html<&'static str> {
+    Html(include_str!("../public/index.html"))
+}
+
+// async fn serve_html() -> impl axum::response::IntoResponse {
+//     Html(include_str!("../static/index.html"))
+// }
+
+// Degraded when a route misses its SLO or the node lags more than
+// `health_max_slot_lag`.
+fn health_status(state: &AppState, routes: &[RouteReport]) -> (StatusCode, String) {
+    let config = state.config();
+    let lagging = config
+        .health_max_slot_lag
+        .zip(state.slots.lag_slots())
+        .is_some_and(|(max, lag)| lag > max);
+    if routes.iter().all(|r| r.slo_met) && !lagging {
+        (StatusCode::OK, "healthy".to_string())
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "degraded".to_string())
+    }
+}
+
+// Anyone can check a receipt: the answer is what the chain shows plus
+// the audit record, without who asked for the airdrop.
+pub async fn get_receipt(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<ResponseJson<Receipt>, ApiError> {
+    let keypair = require_keypair(&state)?;
+    let record = state
+        .audit
+        .find_receipt(&id)
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "Receipt not found"))?;
+    let config = state.config();
+    let receipt =
+        rpc::spawn_blocking(move || receipts::verify(&config, &keypair.pubkey(), &id, record))
+            .await
+            .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(service_error)?;
+    Ok(ResponseJson(receipt))
+}
+
+pub async fn openapi() -> axum::Json<serde_json::Value> {
+    axum::Json(openapi::document())
+}
+
+pub async fn health_check(
+    State(state): State<AppState>,
+) -> (StatusCode, ResponseJson<HealthResponse>) {
+    let config = state.config();
+    let (code, status) = health_status(&state, &state.slo.report(&config));
+
+    (
+        code,
+        ResponseJson(HealthResponse {
+            status,
+            rpc_url: config.rpc_url.clone(),
+            rpc: state.rpc_compat.status(&config, state.slots.lag_slots()),
+        }),
+    )
+}
+
+pub async fn health_details(
+    State(state): State<AppState>,
+) -> (StatusCode, ResponseJson<HealthDetailsResponse>) {
+    let config = state.config();
+    let routes = state.slo.report(&config);
+    let (code, status) = health_status(&state, &routes);
+
+    (
+        code,
+        ResponseJson(HealthDetailsResponse {
+            status,
+            rpc_url: config.rpc_url.clone(),
+            rpc: state.rpc_compat.status(&config, state.slots.lag_slots()),
+            slots: state.slots.status(),
+            window_secs: config.slo_window_secs,
+            routes,
+            load: state.shedder.report(&config),
+            queue: state.queue.report(),
+            rpc_pool: pool::report(),
+            tasks: state.tasks.report(),
+            panics: panics::report(),
+            confirmations: confirm::report(),
+        }),
+    )
+}
+
+pub async fn version() -> ResponseJson<BuildInfo> {
+    ResponseJson(version::info())
+}
+
+// 503 while a required dependency (the RPC node, the store) is down, so
+// status pages and load balancers can tell from the code alone.
+pub async fn health_dependencies(
+    State(state): State<AppState>,
+) -> (StatusCode, ResponseJson<DependencyReport>) {
+    let report = dependencies::report(&state.config()).await;
+    let code = if report.status == "down" {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    (code, ResponseJson(report))
+}
+
+pub async fn get_balance(
+    State(state): State<AppState>,
+    Query(options): Query<AmountOptions>,
+    Params(payload): Params<GetBalance>,
+) -> Result<ResponseJson<GetBalanceResponse>, ApiError> {
+    let config = state.config();
+    let cached = service::parse_wallet(&payload.address)
+        .ok()
+        .and_then(|wallet| state.hot.balance(&config, &wallet));
+    let balance = match cached {
+        Some(balance) => balance,
+        None => {
+            let rpc_url = config.rpc_url.clone();
+            let address = payload.address.clone();
+            rpc::spawn_blocking(move || service::get_balance(&rpc_url, &address))
+                .await
+                .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+                .map_err(service_error)?
+        }
+    };
+
+    Ok(ResponseJson(GetBalanceResponse {
+        wallet: payload.address,
+        balance_lamports: balance,
+        balance_sol: SolValue::new(balance, amount::sol_as_string(&options, &config)),
+        balance_sol_decimal: amount::lamports_to_sol_decimal(balance),
+        balance_display: amount::display(&options, balance, &config),
+    }))
+}
+
+pub async fn get_wallet_balance(
+    State(state): State<AppState>,
+    AddressPath(WalletAddress(wallet)): AddressPath<WalletAddress>,
+    Query(query): Query<WalletBalanceQuery>,
+    Query(options): Query<AmountOptions>,
+) -> Result<ResponseJson<WalletBalanceResponse>, ApiError> {
+    let config = state.config();
+    let started = Instant::now();
+    let wait = match &query.wait_for_change {
+        None => {
+            let balance = match state.hot.balance(&config, &wallet) {
+                Some(balance) => balance,
+                None => balance_wait::confirmed_balance(&config.rpc_url, wallet)
+                    .await
+                    .map_err(service_error)?,
+            };
+            balance_wait::Wait {
+                previous: query.previous_lamports.unwrap_or(balance),
+                balance,
+            }
+        }
+        Some(text) => {
+            let timeout = balance_wait::parse_wait(text).map_err(service_error)?;
+            if timeout > Duration::from_secs(config.balance_wait_max_secs) {
+                return Err(error_response(
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "wait_for_change can be at most {}s",
+                        config.balance_wait_max_secs
+                    ),
+                ));
+            }
+            let Some(_waiter) = balance_wait::Waiter::enter(&config) else {
+                return Err(error_response(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Too many requests are waiting on balances; try again shortly",
+                ));
+            };
+            balance_wait::wait_for_change(&config, wallet, query.previous_lamports, timeout)
+                .await
+                .map_err(service_error)?
+        }
+    };
+
+    Ok(ResponseJson(WalletBalanceResponse {
+        wallet: wallet.to_string(),
+        balance_lamports: wait.balance,
+        balance_sol: SolValue::new(wait.balance, amount::sol_as_string(&options, &config)),
+        balance_sol_decimal: amount::lamports_to_sol_decimal(wait.balance),
+        balance_display: amount::display(&options, wait.balance, &config),
+        previous_lamports: wait.previous,
+        changed: wait.changed(),
+        waited_ms: started.elapsed().as_millis() as u64,
+    }))
+}
+
+pub async fn get_balance_at(
+    State(state): State<AppState>,
+    AddressPath(wallet): AddressPath<WalletAddress>,
+    Query(query): Query<BalanceAtQuery>,
+    Query(options): Query<AmountOptions>,
+) -> Result<ResponseJson<BalanceAtResponse>, ApiError> {
+    let point = match (query.slot, query.block_time) {
+        (Some(slot), None) => Point::Slot(slot),
+        (None, Some(time)) => Point::BlockTime(time),
+        _ => {
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                "Provide exactly one of 'slot' or 'block_time'",
+            ))
+        }
+    };
+
+    let config = state.config();
+    let (rpc_url, rpc) = match &config.archival_rpc_url {
+        Some(url) => (url.clone(), "archival"),
+        None => (config.rpc_url.clone(), "primary"),
+    };
+
+    let lookup_wallet = wallet.to_string();
+    let balance = rpc::spawn_blocking(move || history::balance_at(&rpc_url, &lookup_wallet, point))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(service_error)?;
+
+    let (source, source_signature, source_slot) = match balance.source {
+        BalanceSource::PostBalance { signature, slot } => {
+            ("post_balance", Some(signature), Some(slot))
+        }
+        BalanceSource::PreBalance { signature, slot } => {
+            ("pre_balance", Some(signature), Some(slot))
+        }
+        BalanceSource::NoHistory => ("no_history", None, None),
+    };
+
+    Ok(ResponseJson(BalanceAtResponse {
+        wallet: wallet.to_string(),
+        slot: query.slot,
+        block_time: query.block_time,
+        balance_lamports: balance.lamports,
+        balance_sol: SolValue::new(balance.lamports, amount::sol_as_string(&options, &config)),
+        balance_sol_decimal: amount::lamports_to_sol_decimal(balance.lamports),
+        balance_display: amount::display(&options, balance.lamports, &config),
+        source: source.to_string(),
+        source_signature,
+        source_slot,
+        rpc: rpc.to_string(),
+    }))
+}
+
+pub async fn get_wallet_transactions(
+    State(state): State<AppState>,
+    AddressPath(wallet): AddressPath<WalletAddress>,
+    Query(query): Query<WalletTransactionsQuery>,
+) -> Result<ResponseJson<WalletTransactionsResponse>, ApiError> {
+    if !state.config().tx_history {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Transaction history indexing is off (tx_history)",
+        ));
+    }
+    if !state.watchlists.wallets().contains(&wallet.0) {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Only wallets on a watchlist have indexed history",
+        ));
+    }
+    let bad_request = |message: String| error_response(StatusCode::BAD_REQUEST, message);
+    let filter = tx_history::Filter {
+        kind: query
+            .kind
+            .as_deref()
+            .map(TxKind::from_str)
+            .transpose()
+            .map_err(bad_request)?,
+        min_lamports: query
+            .min_amount
+            .as_deref()
+            .map(bulk::parse_sol)
+            .transpose()
+            .map_err(bad_request)?,
+        since: query.since,
+        until: query.until,
+        before: query.before,
+        limit: query.limit.unwrap_or(100).clamp(1, 1_000),
+    };
+    let Some(found) = state.tx_history.query(&wallet.0, &filter) else {
+        return Err(error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The wallet's history is still being indexed",
+        ));
+    };
+    let config = state.config();
+    Ok(ResponseJson(WalletTransactionsResponse {
+        wallet: wallet.to_string(),
+        transactions: found
+            .transactions
+            .into_iter()
+            .map(|tx| Linked {
+                explorer_urls: links::transaction(&config, &tx.signature),
+                item: tx,
+            })
+            .collect(),
+        indexed: found.indexed,
+        backfill_complete: found.backfill_complete,
+        next_before: found.next_before,
+    }))
+}
+
+pub async fn search_memos(
+    State(state): State<AppState>,
+    Query(query): Query<MemoSearchQuery>,
+) -> Result<ResponseJson<MemoSearchResponse>, ApiError> {
+    let config = state.config();
+    if !config.tx_history {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Transaction history indexing is off (tx_history)",
+        ));
+    }
+    if query.q.trim().is_empty() {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "'q' must have at least one word",
+        ));
+    }
+    let limit = query.limit.unwrap_or(100).clamp(1, 1_000);
+    Ok(ResponseJson(MemoSearchResponse {
+        matches: state
+            .tx_history
+            .search_memos(&query.q, limit)
+            .into_iter()
+            .map(|m| Linked {
+                explorer_urls: links::transaction(&config, &m.signature),
+                item: m,
+            })
+            .collect(),
+        query: query.q,
+    }))
+}
+
+pub async fn get_wallet_summary(
+    State(state): State<AppState>,
+    AddressPath(wallet): AddressPath<WalletAddress>,
+    Query(query): Query<SummaryQuery>,
+    Query(options): Query<AmountOptions>,
+    Query(consistency): Query<ConsistencyQuery>,
+) -> Result<ResponseJson<WalletSummaryResponse>, ApiError> {
+    let days = query.days.unwrap_or(7);
+    if !(1..=90).contains(&days) {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "'days' must be between 1 and 90",
+        ));
+    }
+
+    let pubkey = wallet.0;
+    let wallet = wallet.to_string();
+    let config = state.config();
+    let ttl = Duration::from_secs(config.summary_cache_ttl_secs);
+    let pin = consistency_pin(&state, &consistency).await?;
+
+    // A cached summary may predate the pinned slot.
+    let cached = match pin {
+        Some(_) => None,
+        None => state.summaries.get(&pubkey, days, ttl),
+    };
+    let (summary, cached) = match cached {
+        Some(summary) => (summary, true),
+        None => {
+            let rpc_url = config.rpc_url.clone();
+            let max = config.summary_max_transactions;
+            let lookup_wallet = wallet.clone();
+            let summary = rpc::spawn_blocking_pinned(pin, move || {
+                summary::summarize(&rpc_url, &lookup_wallet, days, max)
+            })
+            .await
+            .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(service_error)?;
+            state.summaries.insert(pubkey, days, summary.clone());
+            (summary, false)
+        }
+    };
+
+    let as_string = amount::sol_as_string(&options, &config);
+    Ok(ResponseJson(WalletSummaryResponse {
+        wallet,
+        days: summary.days,
+        transaction_count: summary.transaction_count,
+        failed_transaction_count: summary.failed_transaction_count,
+        received_lamports: summary.received_lamports,
+        received_sol: SolValue::new(summary.received_lamports, as_string),
+        received_sol_decimal: amount::lamports_to_sol_decimal(summary.received_lamports),
+        sent_lamports: summary.sent_lamports,
+        sent_sol: SolValue::new(summary.sent_lamports, as_string),
+        sent_sol_decimal: amount::lamports_to_sol_decimal(summary.sent_lamports),
+        fees_lamports: summary.fees_lamports,
+        top_counterparties: summary
+            .top_counterparties
+            .into_iter()
+            .map(|c| CounterpartySummary {
+                address: c.address,
+                transactions: c.transactions,
+                sent_to_lamports: c.sent_to_lamports,
+                received_from_lamports: c.received_from_lamports,
+            })
+            .collect(),
+        token_activity: summary
+            .token_activity
+            .into_iter()
+            .map(|t| TokenActivitySummary {
+                mint: t.mint,
+                decimals: t.decimals,
+                received_amount: t.received_raw.to_string(),
+                sent_amount: t.sent_raw.to_string(),
+                transactions: t.transactions,
+            })
+            .collect(),
+        truncated: summary.truncated,
+        cached,
+        computed_at_ms: summary.computed_at_ms,
+    }))
+}
+
+// The slot a `consistent=true` read pins its sub-reads to.
+async fn consistency_pin(
+    state: &AppState,
+    query: &ConsistencyQuery,
+) -> Result<Option<u64>, ApiError> {
+    if !query.consistent {
+        return Ok(None);
+    }
+    let rpc_url = state.config().rpc_url.clone();
+    rpc::spawn_blocking(move || rpc::current_slot(&rpc_url))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(Some)
+        .map_err(service_error)
+}
+
+pub async fn wallet_diff(
+    State(state): State<AppState>,
+    Query(options): Query<AmountOptions>,
+    Query(consistency): Query<ConsistencyQuery>,
+    Params(payload): Params<WalletDiffRequest>,
+) -> Result<ResponseJson<WalletDiffResponse>, ApiError> {
+    let config = state.config();
+
+    let (mode, from, to, result) = match payload {
+        WalletDiffRequest {
+            wallets: Some(wallets),
+            wallet: None,
+            from_slot: None,
+            to_slot: None,
+        } => {
+            let [from, to]: [String; 2] = wallets.try_into().map_err(|_| {
+                error_response(
+                    StatusCode::BAD_REQUEST,
+                    "'wallets' must contain exactly two wallets",
+                )
+            })?;
+            let rpc_url = config.rpc_url.clone();
+            let (a, b) = (from.clone(), to.clone());
+            let pin = consistency_pin(&state, &consistency).await?;
+            let result =
+                rpc::spawn_blocking_pinned(pin, move || diff::diff_wallets(&rpc_url, &a, &b)).await;
+            ("wallets", (from, None), (to, None), result)
+        }
+        WalletDiffRequest {
+            wallets: None,
+            wallet: Some(wallet),
+            from_slot: Some(from_slot),
+            to_slot: Some(to_slot),
+        } => {
+            let rpc_url = config
+                .archival_rpc_url
+                .clone()
+                .unwrap_or_else(|| config.rpc_url.clone());
+            let lookup_wallet = wallet.clone();
+            let result = rpc::spawn_blocking(move || {
+                diff::diff_slots(&rpc_url, &lookup_wallet, from_slot, to_slot)
+            })
+            .await;
+            (
+                "slots",
+                (wallet.clone(), Some(from_slot)),
+                (wallet, Some(to_slot)),
+                result,
+            )
+        }
+        _ => {
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                "Provide either 'wallets' (two wallets) or 'wallet' with 'from_slot' and 'to_slot'",
+            ))
+        }
+    };
+    let result = result
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(service_error)?;
+
+    let delta = result.lamports_delta();
+    Ok(ResponseJson(WalletDiffResponse {
+        mode: mode.to_string(),
+        from: DiffSide {
+            wallet: from.0,
+            slot: from.1,
+            balance_lamports: result.from_lamports,
+            balance_sol_decimal: amount::lamports_to_sol_decimal(result.from_lamports),
+        },
+        to: DiffSide {
+            wallet: to.0,
+            slot: to.1,
+            balance_lamports: result.to_lamports,
+            balance_sol_decimal: amount::lamports_to_sol_decimal(result.to_lamports),
+        },
+        lamports_delta: delta,
+        sol_delta: SolValue::signed(delta, amount::sol_as_string(&options, &config)),
+        sol_delta_decimal: amount::signed_lamports_to_sol_decimal(delta),
+        tokens: result
+            .tokens
+            .into_iter()
+            .map(|t| TokenDiffResponse {
+                mint: t.mint,
+                decimals: t.decimals,
+                from_amount: t.from_amount.map(|a| a.to_string()),
+                to_amount: t.to_amount.map(|a| a.to_string()),
+                delta: t.delta.to_string(),
+            })
+            .collect(),
+    }))
+}
+
+pub async fn get_transaction_cost(
+    State(state): State<AppState>,
+    Path(signature): Path<String>,
+    Query(options): Query<AmountOptions>,
+) -> Result<ResponseJson<TransactionCostResponse>, ApiError> {
+    let config = state.config();
+    let rpc_url = config.rpc_url.clone();
+    let lookup_signature = signature.clone();
+    let cost = rpc::spawn_blocking(move || cost::transaction_cost(&rpc_url, &lookup_signature))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(service_error)?;
+
+    Ok(ResponseJson(TransactionCostResponse {
+        explorer_urls: links::transaction(&config, &signature),
+        signature,
+        slot: cost.slot,
+        block_time: cost.block_time,
+        status: if cost.succeeded { "success" } else { "failed" }.to_string(),
+        fee_lamports: cost.fee_lamports,
+        fee_sol: SolValue::new(cost.fee_lamports, amount::sol_as_string(&options, &config)),
+        fee_sol_decimal: amount::lamports_to_sol_decimal(cost.fee_lamports),
+        base_fee_lamports: cost.base_fee_lamports,
+        priority_fee_lamports: cost.priority_fee_lamports,
+        compute_unit_price_micro_lamports: cost.compute_unit_price_micro_lamports,
+        compute_units_requested: cost.compute_units_requested,
+        compute_unit_limit_source: if cost.compute_unit_limit_set {
+            "instruction"
+        } else {
+            "default"
+        }
+        .to_string(),
+        compute_units_consumed: cost.compute_units_consumed,
+        signers: cost
+            .signers
+            .into_iter()
+            .map(|s| SignerCostResponse {
+                address: s.address,
+                fee_payer: s.fee_payer,
+                signature_fee_lamports: s.signature_fee_lamports,
+                paid_lamports: s.paid_lamports,
+            })
+            .collect(),
+    }))
+}
+
+// How long /tx/{signature}/stream follows a transaction that isn't
+// finalized.
+const TRANSACTION_STREAM_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Server-sent `status` events as a transaction moves from "pending" to
+// "finalized", one per change, checked as confirm::Polling says. The
+// stream ends after "finalized" or a failure, or with a `timeout` event
+// once TRANSACTION_STREAM_TIMEOUT passes; for following an airdrop sent
+// with `wait: false`.
+pub async fn stream_transaction(
+    State(state): State<AppState>,
+    Path(signature): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, axum::Error>>>, ApiError> {
+    let signature = Signature::from_str(&signature)
+        .map_err(|_| error_response(StatusCode::BAD_REQUEST, "Invalid transaction signature"))?;
+    // The polling, the last commitment sent and whether it was checked yet.
+    let following = Some((Polling::start(), None::<String>, false));
+    let stream = stream::unfold(following, move |following| {
+        let state = state.clone();
+        async move {
+            let (mut polling, mut last, mut checked) = following?;
+            loop {
+                if polling.elapsed() >= TRANSACTION_STREAM_TIMEOUT {
+                    let sse = SseEvent::default().event("timeout").json_data(
+                        serde_json::json!({"signature": signature.to_string(), "commitment": last}),
+                    );
+                    return Some((sse, None));
+                }
+                if checked {
+                    tokio::time::sleep(polling.next_delay()).await;
+                }
+                checked = true;
+                let rpc_url = state.config().rpc_url.clone();
+                let progress = rpc::spawn_blocking(move || {
+                    service::transaction_progress(&rpc_url, &signature)
+                })
+                .await;
+                // A failed check is retried on the next one.
+                let Ok(Ok(progress)) = progress else {
+                    continue;
+                };
+                if last.as_deref() == Some(progress.commitment.as_str()) {
+                    continue;
+                }
+                let done = progress.error.is_some() || progress.commitment == "finalized";
+                last = Some(progress.commitment.clone());
+                let sse = SseEvent::default()
+                    .event("status")
+                    .json_data(TransactionStatusEvent {
+                        signature: signature.to_string(),
+                        explorer_urls: links::transaction(&state.config(), &signature),
+                        commitment: progress.commitment,
+                        slot: progress.slot,
+                        error: progress.error,
+                    });
+                return Some((sse, (!done).then_some((polling, last, checked))));
+            }
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+pub async fn get_account(
+    State(state): State<AppState>,
+    AddressPath(WalletAddress(address)): AddressPath<WalletAddress>,
+) -> Result<ResponseJson<AccountResponse>, ApiError> {
+    let config = state.config();
+    if let Some(account) = state.hot.account(&config, &address) {
+        return Ok(ResponseJson(account_response(account, &state.programs)));
+    }
+    let rpc_url = config.rpc_url.clone();
+    let decoders = state.decoders.clone();
+    let account = rpc::spawn_blocking(move || inspect::account(&rpc_url, &decoders, &address))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(service_error)?;
+    Ok(ResponseJson(account_response(account, &state.programs)))
+}
+
+pub async fn get_program_accounts(
+    State(state): State<AppState>,
+    AddressPath(WalletAddress(program_id)): AddressPath<WalletAddress>,
+    Query(query): Query<ProgramAccountsQuery>,
+) -> Result<Response, ApiError> {
+    if query.format != ProgramAccountsFormat::Json {
+        return stream_program_accounts(&state, program_id, query).await;
+    }
+    let limit = query.limit.unwrap_or(100).min(1_000);
+    let rpc_url = state.config().rpc_url.clone();
+    let decoders = state.decoders.clone();
+    let (total, accounts) = rpc::spawn_blocking(move || {
+        inspect::program_accounts(&rpc_url, &decoders, &program_id, limit)
+    })
+    .await
+    .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(service_error)?;
+    Ok(ResponseJson(ProgramAccountsResponse {
+        program: program_id.to_string(),
+        program_name: state.programs.name(&program_id),
+        total,
+        accounts: accounts
+            .into_iter()
+            .map(|account| account_response(account, &state.programs))
+            .collect(),
+    })
+    .into_response())
+}
+
+// Program accounts fetched, decoded and written out a page at a time, so
+// a program with tens of MB of accounts never has them all in memory: only
+// the address list is held for the whole response. Sends up to `limit`
+// accounts, capped at program_accounts_stream_max; x-total-count has how
+// many the program owns. A client that stops reading stops the decoding
+// too.
+async fn stream_program_accounts(
+    state: &AppState,
+    program_id: Pubkey,
+    query: ProgramAccountsQuery,
+) -> Result<Response, ApiError> {
+    let config = state.config();
+    let limit = query
+        .limit
+        .unwrap_or(usize::MAX)
+        .min(config.program_accounts_stream_max);
+    let rpc_url = config.rpc_url.clone();
+    let mut addresses = rpc::spawn_blocking({
+        let rpc_url = rpc_url.clone();
+        move || inspect::program_account_addresses(&rpc_url, &program_id)
+    })
+    .await
+    .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(service_error)?;
+    let total = addresses.len();
+    addresses.truncate(limit);
+
+    let format = query.format;
+    let decoders = state.decoders.clone();
+    let programs = state.programs.clone();
+    let pages = stream::iter(addresses)
+        .chunks(rpc::MAX_ACCOUNTS_PER_CALL)
+        .then(move |chunk| {
+            let rpc_url = rpc_url.clone();
+            let decoders = decoders.clone();
+            let programs = programs.clone();
+            async move {
+                let decoded = rpc::spawn_blocking(move || {
+                    inspect::load_program_accounts(&rpc_url, &decoders, &program_id, &chunk)
+                })
+                .await
+                .map_err(|e| e.to_string())?
+                .map_err(|e| e.to_string())?;
+                decoded
+                    .into_iter()
+                    .map(|account| serde_json::to_string(&account_response(account, &programs)))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| e.to_string())
+            }
+        });
+    let body = program_accounts_body(pages, format);
+    let content_type = match format {
+        ProgramAccountsFormat::Ndjson => "application/x-ndjson",
+        _ => "application/json",
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::HeaderName::from_static("x-total-count"),
+                total.to_string(),
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+// The pages of serialized accounts as the response body in `format`. Pages
+// can come back empty (every account closed or reassigned), so whether a
+// comma is due is tracked across pages, not per page.
+fn program_accounts_body(
+    pages: impl Stream<Item = Result<Vec<String>, String>>,
+    format: ProgramAccountsFormat,
+) -> impl Stream<Item = Result<String, String>> {
+    let mut first = true;
+    let chunks = pages.map(move |page| {
+        page.map(|accounts| {
+            let mut out = String::new();
+            for json in accounts {
+                if format == ProgramAccountsFormat::JsonArray && !first {
+                    out.push(',');
+                }
+                first = false;
+                out.push_str(&json);
+                if format == ProgramAccountsFormat::Ndjson {
+                    out.push('\n');
+                }
+            }
+            out
+        })
+    });
+    let (open, close) = match format {
+        ProgramAccountsFormat::JsonArray => ("[", "]"),
+        _ => ("", ""),
+    };
+    stream::once(async move { Ok(open.to_string()) })
+        .chain(chunks)
+        .chain(stream::once(async move { Ok(close.to_string()) }))
+}
+
+pub async fn get_transaction(
+    State(state): State<AppState>,
+    Path(signature): Path<String>,
+) -> Result<ResponseJson<TransactionResponse>, ApiError> {
+    let rpc_url = state.config().rpc_url.clone();
+    let decoders = state.decoders.clone();
+    let lookup_signature = signature.clone();
+    let tx =
+        rpc::spawn_blocking(move || inspect::transaction(&rpc_url, &decoders, &lookup_signature))
+            .await
+            .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(service_error)?;
+    Ok(ResponseJson(TransactionResponse {
+        explorer_urls: links::transaction(&state.config(), &signature),
+        signature,
+        slot: tx.slot,
+        block_time: tx.block_time,
+        status: if tx.succeeded { "success" } else { "failed" }.to_string(),
+        fee_lamports: tx.fee_lamports,
+        instructions: tx
+            .instructions
+            .into_iter()
+            .map(|ix| instruction_response(ix, &state.programs))
+            .collect(),
+        events: tx
+            .events
+            .into_iter()
+            .map(|event| event_response(event, &state.programs))
+            .collect(),
+    }))
+}
+
+pub async fn qr_code(Query(query): Query<QrQuery>) -> Result<Response, ApiError> {
+    let bad_request = |message: &str| error_response(StatusCode::BAD_REQUEST, message);
+    let data = match (query.data, &query.recipient) {
+        (Some(data), None) => data,
+        (None, Some(recipient)) => qr::TransferRequest {
+            recipient,
+            amount: query.amount.as_deref(),
+            spl_token: query.spl_token.as_deref(),
+            references: query
+                .reference
+                .as_deref()
+                .map(|r| r.split(',').map(str::trim).collect())
+                .unwrap_or_default(),
+            label: query.label.as_deref(),
+            message: query.message.as_deref(),
+            memo: query.memo.as_deref(),
+        }
+        .url()
+        .map_err(service_error)?,
+        _ => {
+            return Err(bad_request(
+                "Give either 'data' or a Solana Pay 'recipient'",
+            ))
+        }
+    };
+    let size = query.size.unwrap_or(qr::DEFAULT_SIZE);
+    if !(qr::MIN_SIZE..=qr::MAX_SIZE).contains(&size) {
+        return Err(bad_request(&format!(
+            "'size' must be between {} and {} pixels",
+            qr::MIN_SIZE,
+            qr::MAX_SIZE
+        )));
+    }
+    let margin = query.margin.unwrap_or(qr::DEFAULT_MARGIN);
+    if margin > qr::MAX_MARGIN {
+        return Err(bad_request(&format!(
+            "'margin' must be at most {} modules",
+            qr::MAX_MARGIN
+        )));
+    }
+
+    let code = qr::encode(data.as_bytes(), query.ec).map_err(service_error)?;
+    let body = match query.format {
+        QrFormat::Svg => code.to_svg(size, margin).into_bytes(),
+        QrFormat::Png => code.to_png(size, margin),
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, query.format.content_type()),
+            // The same query always renders the same image.
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+pub async fn explorer_transaction(
+    State(state): State<AppState>,
+    Path(signature): Path<String>,
+) -> (StatusCode, Html<String>) {
+    let (status, html) = explorer::transaction(&state, signature).await;
+    (status, Html(html))
+}
+
+pub async fn explorer_address(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> (StatusCode, Html<String>) {
+    let (status, html) = explorer::address(&state, address).await;
+    (status, Html(html))
+}
+
+pub async fn decode_data(
+    Json(payload): Json<DecodeRequest>,
+) -> Result<ResponseJson<DecodeResponse>, ApiError> {
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(&payload.data)
+        .map_err(|e| {
+            error_response(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid base64 data: {}", e),
+            )
+        })?;
+    let data = data.get(payload.offset..).ok_or_else(|| {
+        error_response(
+            StatusCode::BAD_REQUEST,
+            format!("'offset' is past the end of the {} byte data", data.len()),
+        )
+    })?;
+    let (decoded, bytes_read) = idl::decode_schema(&payload.schema, data)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))?;
+    Ok(ResponseJson(DecodeResponse {
+        decoded,
+        bytes_read,
+        trailing_bytes: data.len() - bytes_read,
+    }))
+}
+
+pub async fn verify_signature(
+    Params(payload): Params<VerifySignatureRequest>,
+) -> Result<ResponseJson<VerifySignatureResponse>, ApiError> {
+    let pubkey = service::parse_wallet(&payload.pubkey).map_err(service_error)?;
+    let signature = signing::parse_signature(&payload.signature).map_err(service_error)?;
+    let message =
+        signing::decode_message(&payload.message, payload.encoding).map_err(service_error)?;
+    let verification =
+        signing::verify(&pubkey, &signature, &message, payload.format).map_err(service_error)?;
+    Ok(ResponseJson(VerifySignatureResponse {
+        valid: verification.valid,
+        pubkey: payload.pubkey,
+        format: verification.format.to_string(),
+        offchain_message: verification.offchain,
+    }))
+}
+
+pub async fn sign_message(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(payload): Json<SignMessageRequest>,
+) -> Result<ResponseJson<SignMessageResponse>, ApiError> {
+    let key = payload
+        .key
+        .unwrap_or_else(|| signing::DEFAULT_KEY.to_string());
+    let signer = if key == signing::SERVER_KEY {
+        if payload.format == SignedFormat::Raw {
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                "The server keypair only signs offchain messages",
+            ));
+        }
+        require_keypair(&state)?
+    } else {
+        state.dev_keys.get_or_create(&key).map_err(service_error)?
+    };
+    let message =
+        signing::decode_message(&payload.message, payload.encoding).map_err(service_error)?;
+    let (signature, signed) =
+        signing::sign(&signer, &message, payload.format).map_err(service_error)?;
+    state.audit.record(
+        audit::record(
+            "sign_message",
+            actor,
+            serde_json::json!({ "key": key, "pubkey": signer.pubkey().to_string() }),
+        )
+        .signature(signature),
+    );
+
+    Ok(ResponseJson(SignMessageResponse {
+        key,
+        pubkey: signer.pubkey().to_string(),
+        signature: signature.to_string(),
+        format: if payload.format == SignedFormat::Raw {
+            "raw"
+        } else {
+            "offchain"
+        }
+        .to_string(),
+        signed_message: base64::engine::general_purpose::STANDARD.encode(signed),
+    }))
+}
+
+fn require_das_url(state: &AppState) -> Result<String, ApiError> {
+    state.config().das_url.clone().ok_or_else(|| {
+        error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Compressed NFT lookups need das_url to be configured",
+        )
+    })
+}
+
+pub async fn get_asset_proof(
+    State(state): State<AppState>,
+    Path(asset_id): Path<String>,
+) -> Result<ResponseJson<AssetProofResponse>, ApiError> {
+    let das_url = require_das_url(&state)?;
+    let lookup_asset = asset_id.clone();
+    let proof = rpc::spawn_blocking(move || cnft::asset_proof(&das_url, &lookup_asset))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(service_error)?;
+
+    Ok(ResponseJson(AssetProofResponse {
+        asset_id,
+        tree: proof.tree_id,
+        root: proof.root,
+        leaf: proof.leaf,
+        node_index: proof.node_index,
+        proof: proof.proof,
+    }))
+}
+
+pub async fn transfer_cnft(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    principal: Option<Extension<Principal>>,
+    Path(asset_id): Path<String>,
+    Json(payload): Json<CnftTransferRequest>,
+) -> Result<ResponseJson<CnftTransferResponse>, ApiError> {
+    let das_url = require_das_url(&state)?;
+    let new_owner = service::parse_wallet(&payload.new_owner).map_err(service_error)?;
+    let record = audit::record(
+        "cnft_transfer",
+        actor,
+        serde_json::json!({ "asset_id": asset_id, "new_owner": payload.new_owner }),
+    );
+    if let Err(e) = state.check_recipient(&payload.new_owner) {
+        state.audit.record(record.failed(&e));
+        return Err(service_error(e));
+    }
+    let config = state.config();
+    let signer = if payload.submit {
+        if !config.dev_mode {
+            return Err(error_response(
+                StatusCode::FORBIDDEN,
+                "Submitting transfers is only available in dev mode",
+            ));
+        }
+        auth::require_admin(principal.as_deref(), "Submitting transfers")?;
+        Some(require_keypair(&state)?)
+    } else {
+        None
+    };
+
+    let rpc_url = config.rpc_url.clone();
+    let lookup_asset = asset_id.clone();
+    let result = rpc::spawn_blocking(move || {
+        let plan = cnft::plan_transfer(&das_url, &rpc_url, &lookup_asset, &new_owner)?;
+        match &signer {
+            Some(keypair) if keypair.pubkey() != plan.owner => Err(ServiceError::Invalid(format!(
+                "Asset is owned by {}, not the server keypair",
+                plan.owner
+            ))),
+            Some(keypair) => {
+                let signature = cnft::submit_transfer(&rpc_url, keypair, &plan)?;
+                Ok((plan, None, Some(signature)))
+            }
+            None => {
+                let transaction = cnft::unsigned_transfer(&rpc_url, &plan)?;
+                Ok((plan, Some(transaction), None))
+            }
+        }
+    })
+    .await
+    .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let (plan, transaction, signature) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            if payload.submit {
+                state.audit.record(record.failed(&e));
+            }
+            return Err(service_error(e));
+        }
+    };
+    if let Some(signature) = signature {
+        state.audit.record(record.signature(signature));
+    }
+
+    let urls = signature.map(|s| links::transaction(&state.config(), &s));
+    Ok(ResponseJson(CnftTransferResponse {
+        asset_id,
+        owner: plan.owner.to_string(),
+        new_owner: payload.new_owner,
+        tree: plan.tree.to_string(),
+        leaf_index: plan.leaf_index,
+        proof_nodes: plan.proof_len,
+        canopy_depth: plan.canopy_depth,
+        transaction,
+        transaction_signature: signature.map(|s| s.to_string()),
+        explorer_url: urls.as_ref().map(|u| u.solana_explorer.clone()),
+        explorer_urls: urls,
+    }))
+}
+
+pub async fn get_swap_quote(
+    State(state): State<AppState>,
+    Query(query): Query<QuoteQuery>,
+) -> Result<ResponseJson<serde_json::Value>, ApiError> {
+    let config = state.config();
+    let quote = state
+        .jupiter
+        .quote(&config, &query)
+        .await
+        .map_err(service_error)?;
+    Ok(ResponseJson(quote))
+}
+
+pub async fn build_swap(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    principal: Option<Extension<Principal>>,
+    Json(payload): Json<SwapBuildRequest>,
+) -> Result<ResponseJson<SwapBuildResponse>, ApiError> {
+    let user = service::parse_wallet(&payload.user).map_err(service_error)?;
+    let config = state.config();
+    let signer = if payload.submit {
+        if !config.dev_mode {
+            return Err(error_response(
+                StatusCode::FORBIDDEN,
+                "Submitting swaps is only available in dev mode",
+            ));
+        }
+        auth::require_admin(principal.as_deref(), "Submitting swaps")?;
+        let keypair = require_keypair(&state)?;
+        if keypair.pubkey() != user {
+            return Err(error_response(
+                StatusCode::FORBIDDEN,
+                "Only swaps for the server keypair can be submitted",
+            ));
+        }
+        Some(keypair)
+    } else {
+        None
+    };
+
+    if let Err(e) = state.check_recipient(&payload.user) {
+        let record = audit::record("swap", actor, serde_json::json!({ "user": payload.user }));
+        state.audit.record(record.failed(&e));
+        return Err(service_error(e));
+    }
+    // Every submission is audited, including one whose build fails.
+    let record = signer
+        .is_some()
+        .then(|| audit::record("swap", actor, serde_json::json!({ "user": payload.user })));
+    let built = match state.jupiter.build(&config, payload.quote, &user).await {
+        Ok(built) => built,
+        Err(e) => {
+            if let Some(record) = record {
+                state.audit.record(record.failed(&e));
+            }
+            return Err(service_error(e));
+        }
+    };
+    let (Some(signer), Some(record)) = (signer, record) else {
+        return Ok(ResponseJson(SwapBuildResponse {
+            user: payload.user,
+            transaction: Some(built.transaction),
+            last_valid_block_height: built.last_valid_block_height,
+            transaction_signature: None,
+            explorer_url: None,
+            explorer_urls: None,
+        }));
+    };
+
+    let rpc_url = config.rpc_url.clone();
+    let transaction = built.transaction;
+    let signature = rpc::spawn_blocking(move || swap::submit(&rpc_url, &signer, &transaction))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let signature = match signature {
+        Ok(signature) => signature,
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            return Err(service_error(e));
+        }
+    };
+    state.audit.record(record.signature(signature));
+
+    let urls = links::transaction(&state.config(), &signature);
+    Ok(ResponseJson(SwapBuildResponse {
+        user: payload.user,
+        transaction: None,
+        last_valid_block_height: built.last_valid_block_height,
+        transaction_signature: Some(signature.to_string()),
+        explorer_url: Some(urls.solana_explorer.clone()),
+        explorer_urls: Some(urls),
+    }))
+}
+
+pub async fn list_proposals(
+    State(state): State<AppState>,
+    AddressPath(WalletAddress(address)): AddressPath<WalletAddress>,
+    Query(query): Query<ProposalsQuery>,
+) -> Result<ResponseJson<ProposalsResponse>, ApiError> {
+    let rpc_url = state.config().rpc_url.clone();
+    let mut proposals = rpc::spawn_blocking(move || governance::proposals(&rpc_url, &address))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(service_error)?;
+
+    if let Some(wanted) = &query.state {
+        proposals.retain(|p| p.state == wanted.as_str());
+    }
+    let total = proposals.len();
+    proposals.truncate(query.limit.unwrap_or(100).min(1_000));
+    Ok(ResponseJson(ProposalsResponse {
+        realm: address.to_string(),
+        total,
+        proposals,
+    }))
+}
+
+pub async fn get_proposal(
+    State(state): State<AppState>,
+    AddressPath((WalletAddress(realm), WalletAddress(proposal))): AddressPath<(
+        WalletAddress,
+        WalletAddress,
+    )>,
+) -> Result<ResponseJson<Proposal>, ApiError> {
+    let rpc_url = state.config().rpc_url.clone();
+    let proposal = rpc::spawn_blocking(move || governance::proposal(&rpc_url, &realm, &proposal))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(service_error)?;
+    Ok(ResponseJson(proposal))
+}
+
+pub async fn siws_challenge(
+    State(state): State<AppState>,
+    Json(payload): Json<SiwsChallengeRequest>,
+) -> Result<ResponseJson<SiwsChallengeResponse>, ApiError> {
+    if !state.config().siws_enabled {
+        return Err(features::not_found());
+    }
+    let wallet = service::parse_wallet(&payload.wallet).map_err(service_error)?;
+    let challenge = state
+        .sessions
+        .challenge(&state.config().siws_domain, wallet)
+        .map_err(|e| error_response(StatusCode::TOO_MANY_REQUESTS, e.to_string()))?;
+
+    Ok(ResponseJson(SiwsChallengeResponse {
+        wallet: payload.wallet,
+        nonce: challenge.nonce,
+        message: challenge.message,
+        expires_at_ms: challenge.expires_at_ms,
+    }))
+}
+
+pub async fn siws_verify(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(payload): Json<SiwsVerifyRequest>,
+) -> Result<ResponseJson<SiwsSessionResponse>, ApiError> {
+    if !state.config().siws_enabled {
+        return Err(features::not_found());
+    }
+    let wallet = service::parse_wallet(&payload.wallet).map_err(service_error)?;
+    let ttl = Duration::from_secs(state.config().siws_session_ttl_secs);
+    let record = audit::record(
+        "siws_login",
+        actor,
+        serde_json::json!({ "wallet": payload.wallet }),
+    );
+
+    let session = match state
+        .sessions
+        .verify(wallet, &payload.nonce, &payload.signature, ttl)
+    {
+        Ok(session) => session,
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            return Err(error_response(StatusCode::UNAUTHORIZED, e.to_string()));
+        }
+    };
+    state.audit.record(record);
+
+    Ok(ResponseJson(SiwsSessionResponse {
+        token: session.token,
+        wallet: session.wallet.to_string(),
+        expires_at_ms: session.expires_at_ms,
+    }))
+}
+
+pub async fn register_topup(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    principal: Option<Extension<Principal>>,
+    Json(payload): Json<TopupRequest>,
+) -> Result<(StatusCode, ResponseJson<Topup>), ApiError> {
+    let config = state.config();
+    let wallet = service::parse_wallet(&payload.wallet).map_err(service_error)?;
+    let target_sol = payload.target_sol.unwrap_or(payload.min_sol * 2.0);
+    if payload.min_sol <= 0.0 || target_sol < payload.min_sol {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "'min_sol' must be positive and 'target_sol' at least 'min_sol'",
+        ));
+    }
+    if target_sol > config.topup_max_target_sol {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "'target_sol' may be at most {} SOL",
+                config.topup_max_target_sol
+            ),
+        ));
+    }
+    // A standing top-up is a recurring airdrop, so the same policy applies.
+    state
+        .check_airdrop(principal.as_ref().map(|Extension(p)| p), &payload.wallet)
+        .map_err(service_error)?;
+
+    let record = audit::record(
+        "topup_register",
+        actor.clone(),
+        serde_json::json!({
+            "wallet": payload.wallet,
+            "min_sol": payload.min_sol,
+            "target_sol": target_sol,
+        }),
+    );
+    let topup = Topup {
+        wallet: payload.wallet,
+        min_lamports: sol_to_lamports(payload.min_sol),
+        target_lamports: sol_to_lamports(target_sol),
+        registered_by: actor.subject.or(actor.ip),
+        registered_at_ms: record.timestamp_ms,
+        last_run: None,
+    };
+    if let Err(e) = state.topups.register(wallet, topup.clone()) {
+        state.audit.record(record.failed(&e));
+        return Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e));
+    }
+    state.audit.record(record);
+
+    // Check right away so CI doesn't wait for the next scheduled pass.
+    let run_state = state.clone();
+    tokio::spawn(async move { topups::run_one(&run_state, wallet).await });
+
+    Ok((StatusCode::CREATED, ResponseJson(topup)))
+}
+
+pub async fn get_topup(
+    State(state): State<AppState>,
+    AddressPath(WalletAddress(wallet)): AddressPath<WalletAddress>,
+) -> Result<ResponseJson<Topup>, ApiError> {
+    state.topups.get(&wallet).map(ResponseJson).ok_or_else(|| {
+        error_response(
+            StatusCode::NOT_FOUND,
+            "Wallet is not registered for top-ups",
+        )
+    })
+}
+
+pub async fn remove_topup(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    AddressPath(wallet): AddressPath<WalletAddress>,
+) -> Result<StatusCode, ApiError> {
+    let pubkey = wallet.0;
+    let record = audit::record(
+        "topup_remove",
+        actor,
+        serde_json::json!({ "wallet": wallet }),
+    );
+    match state.topups.remove(&pubkey) {
+        Ok(true) => {
+            state.audit.record(record);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Wallet is not registered for top-ups",
+        )),
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+fn dry_run_airdrop(
+    wallet: &str,
+    lamports: u64,
+    needs_approval: bool,
+    preview: receipts::Preview,
+) -> DryRunAirdropResponse {
+    let fee = preview.simulation.as_ref().map(|s| s.fee_lamports);
+    let failure = preview.simulation.as_ref().and_then(|s| s.error.clone());
+    let message = match (&failure, needs_approval) {
+        (Some(e), _) => format!("Dry run: the transfer would fail: {}", e),
+        (None, true) => format!(
+            "Dry run: the airdrop of {} SOL would wait for an admin's approval",
+            amount::lamports_to_sol_decimal(lamports)
+        ),
+        (None, false) => format!(
+            "Dry run: {} SOL would be sent to {}",
+            amount::lamports_to_sol_decimal(lamports),
+            wallet
+        ),
+    };
+    DryRunAirdropResponse {
+        success: failure.is_none(),
+        dry_run: true,
+        message,
+        wallet: wallet.to_string(),
+        airdrop_amount_lamports: lamports,
+        source: preview.source.to_string(),
+        needs_approval,
+        wallet_balance_lamports: preview.wallet_balance,
+        resulting_wallet_balance_lamports: preview.wallet_balance.saturating_add(lamports),
+        estimated_fee_lamports: fee,
+        treasury_balance_lamports: preview.treasury_balance,
+        resulting_treasury_balance_lamports: preview.treasury_balance.map(|balance| {
+            balance
+                .saturating_sub(lamports)
+                .saturating_sub(fee.unwrap_or_default())
+        }),
+        simulation: preview.simulation.map(|s| SimulationResult {
+            error: s.error,
+            units_consumed: s.units_consumed,
+            logs: s.logs,
+        }),
+    }
+}
+
+pub async fn get_airdrop(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    principal: Option<Extension<Principal>>,
+    Json(payload): Json<AirdropRequest>,
+) -> Result<(StatusCode, ResponseJson<AirdropReply>), ApiError> {
+    let config = state.config();
+    let client_ip = actor.ip.clone().unwrap_or_else(|| "unknown".to_string());
+    let lamports = service::airdrop_lamports(payload.amount_sol, payload.lamports);
+    let record = audit::record(
+        "airdrop",
+        actor,
+        serde_json::json!({
+            "wallet": payload.address,
+            "sol": payload.amount_sol,
+            "lamports": lamports.as_ref().ok(),
+        }),
+    );
+    let lamports = match lamports {
+        Ok(lamports) => lamports,
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            return Err(denials::airdrop_error(&config, e));
+        }
+    };
+
+    let principal = principal.map(|Extension(p)| p);
+    let quota_subject = usage::quota_subject(
+        principal.as_ref().and_then(|p| p.subject.as_deref()),
+        Some(&client_ip),
+    );
+    let subject = Some(quota_subject.as_str());
+    // Keys of an org also draw on the org's shared quota.
+    let org = principal
+        .as_ref()
+        .and_then(|p| p.org.as_deref())
+        .and_then(|org| state.orgs.get(org));
+    let org_subject = org.as_ref().map(|org| orgs::usage_subject(&org.id));
+    let limits = config.airdrop_limits();
+    let needs_approval = config
+        .airdrop_approval_threshold_lamports
+        .is_some_and(|threshold| lamports > threshold);
+    let release = || {
+        state.usage.release_airdrop(subject, lamports);
+        state
+            .usage
+            .release_airdrop(org_subject.as_deref(), lamports);
+    };
+    let checked = service::check_airdrop_amount(lamports, limits)
+        .and_then(|()| state.check_airdrop(principal.as_ref(), &payload.address))
+        .and_then(|()| {
+            state
+                .usage
+                .reserve_airdrop(subject, lamports, config.airdrop_daily_quota_sol)
+        })
+        .and_then(|()| {
+            state
+                .usage
+                .reserve_airdrop(
+                    org_subject.as_deref(),
+                    lamports,
+                    org.as_ref().and_then(|org| org.airdrop_daily_quota_sol),
+                )
+                .inspect_err(|_| state.usage.release_airdrop(subject, lamports))
+        });
+    // Dry runs hold the quota only for the checks and aren't audited:
+    // nothing was done.
+    if payload.dry_run {
+        let previewed = match checked.inspect(|()| release()) {
+            Ok(()) => {
+                let (preview_config, keypair) = (config.clone(), state.keypair.clone());
+                let wallet = payload.address.clone();
+                rpc::spawn_blocking(move || {
+                    receipts::preview_airdrop(
+                        &preview_config,
+                        keypair.as_deref(),
+                        &wallet,
+                        lamports,
+                    )
+                })
+                .await
+                .unwrap_or_else(|e| {
+                    Err(ServiceError::Rpc {
+                        action: "Airdrop preview failed",
+                        message: e.to_string(),
+                    })
+                })
+            }
+            Err(e) => Err(e),
+        };
+        return previewed
+            .map(|preview| {
+                (
+                    StatusCode::OK,
+                    ResponseJson(AirdropReply::DryRun(dry_run_airdrop(
+                        &payload.address,
+                        lamports,
+                        needs_approval,
+                        preview,
+                    ))),
+                )
+            })
+            .map_err(|e| denials::airdrop_error(&config, e));
+    }
+    let wait = payload.wait.unwrap_or(true);
+    // The quota stays reserved while the airdrop awaits approval and is
+    // given back if it is rejected or fails.
+    let result = match checked {
+        Ok(()) if needs_approval => state
+            .approvals
+            .create(
+                &payload.address,
+                lamports,
+                record.actor.clone(),
+                payload.callback_url.clone(),
+                &config.approval_callback_hosts,
+            )
+            .map(Err)
+            .inspect_err(|_| release()),
+        Ok(()) => {
+            let (fund_config, keypair) = (config.clone(), state.keypair.clone());
+            let wallet = payload.address.clone();
+            rpc::spawn_blocking(move || {
+                receipts::fund_airdrop(&fund_config, keypair.as_deref(), &wallet, lamports, wait)
+            })
+            .await
+            .unwrap_or_else(|e| {
+                Err(ServiceError::Rpc {
+                    action: "Airdrop failed",
+                    message: e.to_string(),
+                })
+            })
+            .map(Ok)
+            .inspect_err(|_| release())
+        }
+        Err(e) => Err(e),
+    };
+    let funded = match result {
+        Ok(Ok(funded)) => funded,
+        Ok(Err(approval)) => {
+            state.audit.record(AuditRecord {
+                outcome: "pending".to_string(),
+                params: serde_json::json!({
+                    "wallet": payload.address,
+                    "sol": payload.amount_sol,
+                    "lamports": lamports,
+                    "approval": approval.id,
+                }),
+                ..record
+            });
+            let text = format!(
+                "Airdrop of {} SOL to {} is awaiting approval ({})",
+                amount::lamports_to_sol_decimal(lamports),
+                payload.address,
+                approval.id
+            );
+            let notify_state = state.clone();
+            tokio::spawn(async move {
+                let config = notify_state.config();
+                notify_state
+                    .notifier
+                    .notify(&config, notify::Event::ApprovalPending, &text)
+                    .await;
+            });
+            return Ok((
+                StatusCode::ACCEPTED,
+                ResponseJson(AirdropReply::Pending(PendingAirdropResponse {
+                    success: true,
+                    message: format!(
+                        "Airdrops above {} SOL need an admin's approval; this one is pending.",
+                        amount::lamports_to_sol_decimal(
+                            config
+                                .airdrop_approval_threshold_lamports
+                                .unwrap_or_default()
+                        )
+                    ),
+                    wallet: payload.address,
+                    airdrop_amount_lamports: lamports,
+                    status_url: format!("/approvals/{}", approval.id),
+                    approval_id: approval.id,
+                    status: approval.status,
+                })),
+            ));
+        }
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            if let (Some(org), ServiceError::QuotaExceeded { .. }) = (org.clone(), &e) {
+                let text = format!("An airdrop to {} was refused: {}", payload.address, e);
+                notify_org(&state, org, OrgEvent::QuotaReached, text);
+            }
+            if let ServiceError::Denylisted(wallet) = &e {
+                let text = format!(
+                    "Denylisted wallet {} attempted an airdrop from {}",
+                    wallet, client_ip
+                );
+                let notify_state = state.clone();
+                tokio::spawn(async move {
+                    let config = notify_state.config();
+                    notify_state
+                        .notifier
+                        .notify(&config, notify::Event::Suspicious, &text)
+                        .await;
+                });
+            }
+            return Err(denials::airdrop_error(&config, e));
+        }
+    };
+    let sig = funded.signature;
+    state
+        .audit
+        .record(record.signature(sig).receipt(funded.receipt.clone()));
+    events::confirm_faucet(
+        &state,
+        sig,
+        payload.address.clone(),
+        lamports,
+        funded.source,
+    );
+
+    let explorer_urls = links::transaction(&config, &sig);
+    let explorer_url = explorer_urls.solana_explorer.clone();
+    let projection = match service::parse_wallet(&payload.address) {
+        Ok(wallet) if wait => {
+            let rpc_url = config.rpc_url.clone();
+            rpc::spawn_blocking(move || service::project_balance(&rpc_url, &wallet, &sig, lamports))
+                .await
+                .ok()
+                .and_then(Result::ok)
+        }
+        _ => None,
+    };
+
+    logging::info(format_args!("Airdrop txn: {}", explorer_url));
+    if let Some(org) = org {
+        let text = format!(
+            "Airdropped {} SOL to {}: {}",
+            amount::lamports_to_sol_decimal(lamports),
+            payload.address,
+            explorer_url
+        );
+        notify_org(&state, org, OrgEvent::Airdrop, text);
+    }
+
+    Ok((
+        StatusCode::OK,
+        ResponseJson(AirdropReply::Sent(AirdropResponse {
+            success: true,
+            message: format!("Airdrop of {} SOL requested successfully! Use the 'Check Balance' button to see your updated balance.", amount::lamports_to_sol_decimal(lamports)),
+            wallet: payload.address,
+            airdrop_amount_sol: lamports_to_sol(lamports),
+            airdrop_amount_lamports: lamports,
+            transaction_signature: sig.to_string(),
+            explorer_url,
+            explorer_urls: Some(explorer_urls),
+            receipt_id: funded.receipt,
+            commitment: projection.as_ref().map(|p| p.commitment.clone()),
+            confirmed_balance_lamports: projection.as_ref().map(|p| p.confirmed_balance),
+            projected_balance_lamports: projection.map(|p| p.projected_balance),
+            status_url: Some(format!("/tx/{}/stream", sig)),
+        })),
+    ))
+}
+
+pub async fn list_balance_alerts(
+    State(state): State<AppState>,
+) -> ResponseJson<BalanceAlertsResponse> {
+    ResponseJson(BalanceAlertsResponse {
+        alerts: state.alerts.list(),
+    })
+}
+
+pub async fn create_balance_alert(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(payload): Json<BalanceAlertRequest>,
+) -> Result<(StatusCode, ResponseJson<BalanceAlert>), ApiError> {
+    let wallet = payload
+        .wallet
+        .unwrap_or_else(|| alerts::TREASURY.to_string());
+    let record = audit::record(
+        "balance_alert_create",
+        actor,
+        serde_json::json!({
+            "wallet": wallet,
+            "below_sol": payload.below_sol,
+            "hysteresis_sol": payload.hysteresis_sol,
+        }),
+    );
+    if wallet != alerts::TREASURY {
+        if let Err(e) = state.check_watched(&wallet) {
+            state.audit.record(record.failed(&e));
+            return Err(service_error(e));
+        }
+    }
+    let created = state.alerts.create(NewAlert {
+        name: payload.name,
+        wallet,
+        below_sol: payload.below_sol,
+        hysteresis_sol: payload.hysteresis_sol,
+        target: payload.target,
+        format: payload.format,
+    });
+    match created {
+        Ok(alert) => {
+            state.audit.record(record);
+            Ok((StatusCode::CREATED, ResponseJson(alert)))
+        }
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(service_error(e))
+        }
+    }
+}
+
+pub async fn remove_balance_alert(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, ApiError> {
+    let record = audit::record(
+        "balance_alert_remove",
+        actor,
+        serde_json::json!({ "id": id }),
+    );
+    match state.alerts.remove(id) {
+        Ok(true) => {
+            state.audit.record(record);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Err(error_response(
+            StatusCode::NOT_FOUND,
+            "No balance alert with that id",
+        )),
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+pub async fn treasury_status(
+    State(state): State<AppState>,
+    Query(query): Query<TreasuryQuery>,
+) -> Result<ResponseJson<TreasuryResponse>, ApiError> {
+    let keypair = require_keypair(&state)?;
+    let config = state.config();
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let aux = state.treasury.aux.clone();
+    let blocking_config = config.clone();
+    let (balance, auxiliary, outflows) = rpc::spawn_blocking(move || {
+        let rpc_url = &blocking_config.rpc_url;
+        let address = keypair.pubkey().to_string();
+        (
+            service::get_balance(rpc_url, &address),
+            treasury::aux_balances(rpc_url, &aux),
+            treasury::recent_outflows(&blocking_config, &keypair.pubkey(), limit),
+        )
+    })
+    .await
+    .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let balance = balance.map_err(service_error)?;
+
+    Ok(ResponseJson(TreasuryResponse {
+        address: require_keypair(&state)?.pubkey().to_string(),
+        balance_lamports: balance,
+        balance_sol_decimal: amount::lamports_to_sol_decimal(balance),
+        low_watermark_sol: config.treasury_low_watermark_sol,
+        paused: state.treasury.is_low(),
+        auxiliary,
+        recent_outflows: outflows.map_err(service_error)?,
+    }))
+}
+
+async fn rebalance_to(
+    state: AppState,
+    actor: Actor,
+    action: &str,
+    target: u64,
+) -> Result<ResponseJson<RebalanceResponse>, ApiError> {
+    let keypair = require_keypair(&state)?;
+    if state.treasury.aux.is_empty() {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "No auxiliary keypairs are configured (TREASURY_AUX_KEYPAIRS)",
+        ));
+    }
+    let config = state.config();
+    let aux = state.treasury.aux.clone();
+    let movements =
+        rpc::spawn_blocking(move || treasury::rebalance(&config, &keypair, &aux, target))
+            .await
+            .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let record = audit::record(
+        action,
+        actor,
+        serde_json::json!({
+            "target_lamports": target,
+            "movements": movements
+                .iter()
+                .filter(|m| m.signature.is_some() || m.error.is_some())
+                .map(|m| serde_json::json!({
+                    "address": m.address,
+                    "moved_lamports": m.moved_lamports.to_string(),
+                    "signature": m.signature,
+                    "error": m.error,
+                }))
+                .collect::<Vec<_>>(),
+        }),
+    );
+    let failed = movements.iter().filter(|m| m.error.is_some()).count();
+    state.audit.record(if failed == 0 {
+        record
+    } else {
+        record.failed(format!("{} of {} keypairs failed", failed, movements.len()))
+    });
+    Ok(ResponseJson(RebalanceResponse {
+        target_lamports: target,
+        movements,
+    }))
+}
+
+// Moves everything the auxiliary keypairs hold, less fees, back into the
+// treasury.
+pub async fn sweep_treasury(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+) -> Result<ResponseJson<RebalanceResponse>, ApiError> {
+    rebalance_to(state, actor, "treasury_sweep", 0).await
+}
+
+// Tops each auxiliary keypair up to, or sweeps it down to, the target.
+pub async fn rebalance_treasury(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(payload): Json<RebalanceRequest>,
+) -> Result<ResponseJson<RebalanceResponse>, ApiError> {
+    rebalance_to(state, actor, "treasury_rebalance", payload.target_lamports).await
+}
+
+// The last export, scheduled or manual, and how it went.
+pub async fn last_export(
+    State(state): State<AppState>,
+) -> Result<ResponseJson<ExportReport>, ApiError> {
+    state
+        .exporter
+        .last()
+        .map(ResponseJson)
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "No export has run yet"))
+}
+
+pub async fn run_export(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+) -> Result<ResponseJson<ExportReport>, ApiError> {
+    let record = audit::record("export", actor, serde_json::json!({}));
+    match state.exporter.run(&state, "manual").await {
+        Ok(report) => {
+            let keys: Vec<&str> = report.objects.iter().map(|o| o.key.as_str()).collect();
+            state.audit.record(AuditRecord {
+                params: serde_json::json!({ "objects": keys }),
+                ..record
+            });
+            Ok(ResponseJson(report))
+        }
+        Err(ExportError::NotConfigured) => Err(error_response(
+            StatusCode::NOT_FOUND,
+            "export_bucket is not configured",
+        )),
+        Err(ExportError::Running) => Err(error_response(
+            StatusCode::CONFLICT,
+            "An export is already running",
+        )),
+        Err(ExportError::Failed(e)) => {
+            state.audit.record(record.failed(&e));
+            Err(error_response(StatusCode::BAD_GATEWAY, e))
+        }
+    }
+}
+
+pub async fn get_approval(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<ResponseJson<Approval>, ApiError> {
+    state
+        .approvals
+        .get(&id)
+        .map(ResponseJson)
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "Approval not found"))
+}
+
+pub async fn list_approvals(
+    State(state): State<AppState>,
+    Query(query): Query<ApprovalsQuery>,
+) -> ResponseJson<ApprovalsResponse> {
+    let approvals = state.approvals.list(query.status);
+    ResponseJson(ApprovalsResponse {
+        total: approvals.len(),
+        approvals,
+    })
+}
+
+fn decision_error(e: DecisionError) -> ApiError {
+    let status = match e {
+        DecisionError::NotFound => StatusCode::NOT_FOUND,
+        DecisionError::AlreadyDecided(_) => StatusCode::CONFLICT,
+        DecisionError::Save(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, e.to_string())
+}
+
+// Gives back the quota reserved when the approval was requested.
+fn release_approval(state: &AppState, approval: &Approval) {
+    let requester = &approval.requested_by;
+    let subject = usage::quota_subject(requester.subject.as_deref(), requester.ip.as_deref());
+    state
+        .usage
+        .release_airdrop(Some(&subject), approval.lamports);
+    state.usage.release_airdrop(
+        requester.org.as_deref().map(orgs::usage_subject).as_deref(),
+        approval.lamports,
+    );
+}
+
+fn send_approval_callback(state: &AppState, approval: &Approval) {
+    let state = state.clone();
+    let approval = approval.clone();
+    tokio::spawn(async move {
+        let hosts = &state.config().approval_callback_hosts;
+        state.approvals.send_callback(&approval, hosts).await
+    });
+}
+
+pub async fn approve_airdrop(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path(id): Path<String>,
+) -> Result<ResponseJson<Approval>, ApiError> {
+    let decided_by = actor.subject.clone();
+    let record = audit::record("airdrop_approve", actor, serde_json::json!({ "id": id }));
+    // The recipient was screened when the request was queued, but it may
+    // have been denylisted (or dropped from the allowlist) since; such an
+    // approval is rejected instead of paid.
+    let screened = state
+        .approvals
+        .get(&id)
+        .filter(|approval| approval.status == ApprovalStatus::Pending)
+        .map(|approval| state.check_recipient(&approval.wallet));
+    if let Some(Err(e)) = screened {
+        let rejected = state.approvals.decide(
+            &id,
+            ApprovalStatus::Rejected,
+            decided_by,
+            Some(e.to_string()),
+        );
+        state.audit.record(record.failed(&e));
+        if let Ok(approval) = rejected {
+            release_approval(&state, &approval);
+            send_approval_callback(&state, &approval);
+        }
+        return Err(service_error(e));
+    }
+    let approval = match state
+        .approvals
+        .decide(&id, ApprovalStatus::Approved, decided_by, None)
+    {
+        Ok(approval) => approval,
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            return Err(decision_error(e));
+        }
+    };
+
+    let config = state.config();
+    let keypair = state.keypair.clone();
+    let (wallet, lamports) = (approval.wallet.clone(), approval.lamports);
+    let result = rpc::spawn_blocking(move || {
+        receipts::fund_airdrop(&config, keypair.as_deref(), &wallet, lamports, true)
+    })
+    .await
+    .unwrap_or_else(|e| {
+        Err(ServiceError::Rpc {
+            action: "Airdrop failed",
+            message: e.to_string(),
+        })
+    });
+    let record = AuditRecord {
+        params: serde_json::json!({
+            "id": id,
+            "wallet": approval.wallet,
+            "lamports": approval.lamports,
+        }),
+        ..record
+    };
+    let record = match &result {
+        Ok(funded) => {
+            events::confirm_faucet(
+                &state,
+                funded.signature,
+                approval.wallet.clone(),
+                approval.lamports,
+                funded.source,
+            );
+            record
+                .signature(funded.signature)
+                .receipt(funded.receipt.clone())
+        }
+        Err(e) => {
+            release_approval(&state, &approval);
+            record.failed(e)
+        }
+    };
+    state.audit.record(record);
+
+    let approval = state
+        .approvals
+        .finish(
+            &id,
+            result
+                .map(|funded| funded.signature.to_string())
+                .map_err(|e| e.to_string()),
+        )
+        .map_err(decision_error)?;
+    send_approval_callback(&state, &approval);
+    Ok(ResponseJson(approval))
+}
+
+pub async fn reject_airdrop(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path(id): Path<String>,
+    payload: Option<Json<RejectRequest>>,
+) -> Result<ResponseJson<Approval>, ApiError> {
+    let payload = payload.map(|Json(p)| p).unwrap_or_default();
+    let decided_by = actor.subject.clone();
+    let record = audit::record(
+        "airdrop_reject",
+        actor,
+        serde_json::json!({ "id": id, "reason": payload.reason }),
+    );
+    match state
+        .approvals
+        .decide(&id, ApprovalStatus::Rejected, decided_by, payload.reason)
+    {
+        Ok(approval) => {
+            state.audit.record(record);
+            release_approval(&state, &approval);
+            send_approval_callback(&state, &approval);
+            Ok(ResponseJson(approval))
+        }
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(decision_error(e))
+        }
+    }
+}
+
+fn notify_org(state: &AppState, org: Org, event: OrgEvent, text: String) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        let config = state.config();
+        state.notifier.notify_org(&config, &org, event, &text).await;
+    });
+}
+
+// Watchlists belong to the credential that created them, or are shared
+// by an org's keys, so every watchlist route needs one.
+fn watchlist_owner(principal: Option<Extension<Principal>>) -> Result<String, ApiError> {
+    let principal = principal.map(|Extension(p)| p);
+    if let Some(org) = principal.as_ref().and_then(|p| p.org.as_deref()) {
+        return Ok(orgs::usage_subject(org));
+    }
+    principal.and_then(|p| p.subject).ok_or_else(|| {
+        error_response(
+            StatusCode::UNAUTHORIZED,
+            "Watchlists are kept per credential; send a bearer token",
+        )
+    })
+}
+
+fn watchlist_not_found() -> ApiError {
+    error_response(StatusCode::NOT_FOUND, "Watchlist not found")
+}
+
+pub async fn create_watchlist(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    principal: Option<Extension<Principal>>,
+    Json(payload): Json<WatchlistRequest>,
+) -> Result<(StatusCode, ResponseJson<Watchlist>), ApiError> {
+    let owner = watchlist_owner(principal)?;
+    let record = audit::record(
+        "watchlist_create",
+        actor,
+        serde_json::json!({ "name": payload.name, "wallets": payload.wallets.len() }),
+    );
+    for wallet in &payload.wallets {
+        if let Err(e) = state.check_watched(wallet) {
+            state.audit.record(record.failed(&e));
+            return Err(service_error(e));
+        }
+    }
+    match state
+        .watchlists
+        .create(&owner, &payload.name, &payload.wallets)
+    {
+        Ok(watchlist) => {
+            state.audit.record(AuditRecord {
+                params: serde_json::json!({
+                    "id": watchlist.id,
+                    "name": watchlist.name,
+                    "wallets": watchlist.wallets.len(),
+                }),
+                ..record
+            });
+            Ok((StatusCode::CREATED, ResponseJson(watchlist)))
+        }
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(service_error(e))
+        }
+    }
+}
+
+pub async fn list_watchlists(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+) -> Result<ResponseJson<WatchlistsResponse>, ApiError> {
+    let owner = watchlist_owner(principal)?;
+    let watchlists = state.watchlists.list(&owner);
+    Ok(ResponseJson(WatchlistsResponse {
+        total: watchlists.len(),
+        watchlists,
+    }))
+}
+
+pub async fn get_watchlist(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<u64>,
+) -> Result<ResponseJson<Watchlist>, ApiError> {
+    let owner = watchlist_owner(principal)?;
+    state
+        .watchlists
+        .get(&owner, id)
+        .map(ResponseJson)
+        .ok_or_else(watchlist_not_found)
+}
+
+pub async fn delete_watchlist(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, ApiError> {
+    let owner = watchlist_owner(principal)?;
+    let record = audit::record("watchlist_delete", actor, serde_json::json!({ "id": id }));
+    match state.watchlists.remove(&owner, id) {
+        Ok(true) => {
+            state.audit.record(record);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Err(watchlist_not_found()),
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+pub async fn get_watchlist_overview(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<u64>,
+    Query(consistency): Query<ConsistencyQuery>,
+) -> Result<ResponseJson<Overview>, ApiError> {
+    let owner = watchlist_owner(principal)?;
+    let watchlist = state
+        .watchlists
+        .get(&owner, id)
+        .ok_or_else(watchlist_not_found)?;
+    let config = state.config();
+    let pin = consistency_pin(&state, &consistency).await?;
+    Ok(ResponseJson(
+        rpc::pinned(
+            pin,
+            watchlists::overview(config.clone(), fanout::Limits::new(&config), watchlist),
+        )
+        .await,
+    ))
+}
+
+// Server-sent events for everything published on the event bus. A client
+// that falls behind gets a comment saying how many events it missed.
+pub async fn stream_events(
+    State(state): State<AppState>,
+    Query(query): Query<EventStreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, axum::Error>>>, ApiError> {
+    let kinds = match &query.kinds {
+        Some(kinds) => Some(
+            kinds
+                .split(',')
+                .map(|k| k.trim().parse::<EventKind>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))?,
+        ),
+        None => None,
+    };
+    let wanted = move |event: &Event| kinds.as_ref().is_none_or(|k| k.contains(&event.kind));
+    let stream = stream::unfold(state.events.subscribe(), move |mut events| {
+        let wanted = wanted.clone();
+        async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) if wanted(&event) => {
+                        let sse = SseEvent::default()
+                            .event(event.kind.as_str())
+                            .id(event.id.to_string())
+                            .json_data(&*event);
+                        return Some((sse, events));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(missed)) => {
+                        let sse = SseEvent::default().comment(format!("missed {} events", missed));
+                        return Some((Ok(sse), events));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// A websocket of the Anchor events `program` logs, decoded with the IDL
+// registered for it (from IDL_DIR or an upload), optionally only the
+// events named in `?events=`.
+pub async fn stream_anchor_events(
+    State(state): State<AppState>,
+    AddressPath(WalletAddress(program)): AddressPath<WalletAddress>,
+    Query(query): Query<AnchorEventQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let known = state.decoders.event_names(&program);
+    if known.is_empty() {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            format!("No IDL with events is registered for {}", program),
+        ));
+    }
+    let names = match &query.events {
+        Some(names) => {
+            let names: Vec<String> = names.split(',').map(|n| n.trim().to_string()).collect();
+            if let Some(unknown) = names.iter().find(|n| !known.contains(n)) {
+                return Err(error_response(
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "Unknown event '{}'; the IDL has {}",
+                        unknown,
+                        known.join(", ")
+                    ),
+                ));
+            }
+            Some(names)
+        }
+        None => None,
+    };
+    let config = state.config();
+    let Some(slot) = anchor_events::Slot::take(&config) else {
+        return Err(error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Too many event streams are open; try again shortly",
+        ));
+    };
+    let decoders = state.decoders.clone();
+    Ok(upgrade.on_upgrade(move |socket| {
+        anchor_events::forward(socket, slot, config, decoders, program, names)
+    }))
+}
+
+pub async fn my_usage(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+) -> Result<ResponseJson<UsageReport>, ApiError> {
+    let Some(subject) = principal.and_then(|Extension(p)| p.subject) else {
+        return Err(error_response(
+            StatusCode::UNAUTHORIZED,
+            "Usage is tracked per credential; send a bearer token",
+        ));
+    };
+    Ok(ResponseJson(state.usage.report(&subject, &state.config())))
+}
+
+pub async fn all_usage(State(state): State<AppState>) -> ResponseJson<Vec<UsageReport>> {
+    ResponseJson(state.usage.report_all(&state.config()))
+}
+
+pub async fn route_usage(State(state): State<AppState>) -> ResponseJson<Vec<RouteRpcReport>> {
+    ResponseJson(state.usage.route_report())
+}
+
+pub async fn reload_config(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+) -> Result<ResponseJson<ReloadReport>, ApiError> {
+    let report = state
+        .reload_config(actor)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))?;
+
+    logging::info(format_args!("Config reloaded via admin API: {:?}", report));
+    Ok(ResponseJson(report))
+}
+
+pub async fn list_allowlist(
+    State(state): State<AppState>,
+) -> Result<ResponseJson<AllowlistResponse>, ApiError> {
+    Ok(ResponseJson(AllowlistResponse {
+        enforced: state.config().allowlist_only,
+        wallets: state
+            .allowlist
+            .list()
+            .iter()
+            .map(|w| w.to_string())
+            .collect(),
+    }))
+}
+
+pub async fn add_to_allowlist(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(payload): Json<AllowlistAddRequest>,
+) -> Result<ResponseJson<AllowlistAddResponse>, ApiError> {
+    let wallets = payload
+        .wallets
+        .iter()
+        .map(|w| service::parse_wallet(w))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(service_error)?;
+
+    let record = audit::record(
+        "allowlist_add",
+        actor,
+        serde_json::json!({ "wallets": payload.wallets }),
+    );
+    let added = match state.allowlist.add(&wallets) {
+        Ok(added) => added,
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            return Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+    };
+    state.audit.record(record);
+
+    Ok(ResponseJson(AllowlistAddResponse {
+        added: added.iter().map(|w| w.to_string()).collect(),
+        total: state.allowlist.list().len(),
+    }))
+}
+
+pub async fn remove_from_allowlist(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    AddressPath(wallet): AddressPath<WalletAddress>,
+) -> Result<StatusCode, ApiError> {
+    let pubkey = wallet.0;
+    let record = audit::record(
+        "allowlist_remove",
+        actor,
+        serde_json::json!({ "wallet": wallet }),
+    );
+    let removed = match state.allowlist.remove(&pubkey) {
+        Ok(removed) => removed,
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            return Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+    };
+    if !removed {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Wallet is not on the allowlist",
+        ));
+    }
+    state.audit.record(record);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_labels(
+    State(state): State<AppState>,
+    Query(query): Query<LabelQuery>,
+) -> ResponseJson<LabelsResponse> {
+    ResponseJson(LabelsResponse {
+        labels: state.labels.search(
+            query.q.as_deref(),
+            query.tag.as_deref(),
+            query.limit.unwrap_or(100).min(1_000),
+        ),
+    })
+}
+
+pub async fn set_label(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    AddressPath(address): AddressPath<WalletAddress>,
+    Json(payload): Json<SetLabelRequest>,
+) -> Result<ResponseJson<Label>, ApiError> {
+    let record = audit::record(
+        "label_set",
+        actor,
+        serde_json::json!({
+            "address": address,
+            "label": payload.label,
+            "tags": payload.tags,
+        }),
+    );
+    match state.labels.set(address.0, &payload.label, &payload.tags) {
+        Ok(label) => {
+            state.audit.record(record);
+            Ok(ResponseJson(label))
+        }
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(service_error(e))
+        }
+    }
+}
+
+pub async fn remove_label(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    AddressPath(address): AddressPath<WalletAddress>,
+) -> Result<StatusCode, ApiError> {
+    let record = audit::record(
+        "label_remove",
+        actor,
+        serde_json::json!({ "address": address }),
+    );
+    let removed = match state.labels.remove(&address.0) {
+        Ok(removed) => removed,
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            return Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+    };
+    if !removed {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "The address has no label",
+        ));
+    }
+    state.audit.record(record);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_programs(State(state): State<AppState>) -> ResponseJson<ProgramsResponse> {
+    ResponseJson(ProgramsResponse {
+        programs: state.programs.list(),
+    })
+}
+
+pub async fn set_program_name(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    AddressPath(WalletAddress(program_id)): AddressPath<WalletAddress>,
+    Json(payload): Json<SetProgramNameRequest>,
+) -> Result<ResponseJson<KnownProgram>, ApiError> {
+    let record = audit::record(
+        "program_name_set",
+        actor,
+        serde_json::json!({ "program_id": program_id.to_string(), "name": payload.name }),
+    );
+    match state.programs.set(program_id, &payload.name) {
+        Ok(program) => {
+            state.audit.record(record);
+            Ok(ResponseJson(program))
+        }
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(service_error(e))
+        }
+    }
+}
+
+// Decodes the program's instructions, accounts and events with `idl` from
+// now on, in place of any decoder it had.
+pub async fn upload_idl(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Query(query): Query<IdlUploadQuery>,
+    Json(payload): Json<serde_json::Value>,
+) -> Result<ResponseJson<IdlSummary>, ApiError> {
+    let (address, parsed) = Idl::parse(&payload)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, format!("Invalid IDL: {}", e)))?;
+    let program_id = match query.program_id {
+        Some(program_id) => Pubkey::from_str(&program_id)
+            .map_err(|_| error_response(StatusCode::BAD_REQUEST, "Invalid program_id"))?,
+        None => address.ok_or_else(|| {
+            error_response(
+                StatusCode::BAD_REQUEST,
+                "The IDL has no program address; give 'program_id'",
+            )
+        })?,
+    };
+    let summary = parsed.summary(&program_id);
+    let record = audit::record(
+        "idl_upload",
+        actor,
+        serde_json::json!({ "program_id": summary.program_id, "name": summary.name }),
+    );
+    if let Err(e) = state.idls.save(program_id, payload) {
+        state.audit.record(record.failed(&e));
+        return Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e));
+    }
+    state.decoders.register(program_id, Arc::new(parsed));
+    state.audit.record(record);
+    Ok(ResponseJson(summary))
+}
+
+pub async fn remove_program_name(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    AddressPath(WalletAddress(program_id)): AddressPath<WalletAddress>,
+) -> Result<StatusCode, ApiError> {
+    let record = audit::record(
+        "program_name_remove",
+        actor,
+        serde_json::json!({ "program_id": program_id.to_string() }),
+    );
+    let removed = match state.programs.remove(&program_id) {
+        Ok(removed) => removed,
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            return Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+    };
+    if !removed {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "The program has no custom name",
+        ));
+    }
+    state.audit.record(record);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn index_status(State(state): State<AppState>) -> ResponseJson<IndexStatusResponse> {
+    ResponseJson(IndexStatusResponse {
+        programs: state.index.status(),
+    })
+}
+
+pub async fn indexed_accounts(
+    State(state): State<AppState>,
+    AddressPath(WalletAddress(program)): AddressPath<WalletAddress>,
+    Query(query): Query<IndexedAccountsQuery>,
+) -> Result<ResponseJson<IndexedAccountsResponse>, ApiError> {
+    let WalletAddress(owner) = query.owner.parse().map_err(service_error)?;
+    match state.index.owned_by(&program, &owner) {
+        Lookup::Found(owned) => Ok(ResponseJson(IndexedAccountsResponse {
+            program: program.to_string(),
+            owner: query.owner.to_string(),
+            accounts: owned.accounts,
+            reconciled_at_ms: owned.reconciled_at_ms,
+            live: owned.live,
+        })),
+        Lookup::NotIndexed => Err(error_response(
+            StatusCode::NOT_FOUND,
+            "The program is not indexed; see index_programs",
+        )),
+        Lookup::Building => Err(error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The program's index is still being built",
+        )),
+    }
+}
+
+fn require_mock() -> Result<&'static Arc<MockChain>, ApiError> {
+    rpc::mock().ok_or_else(|| error_response(StatusCode::NOT_FOUND, "Mock backend is not enabled"))
+}
+
+pub async fn mock_status() -> Result<ResponseJson<MockStatus>, ApiError> {
+    Ok(ResponseJson(require_mock()?.status()))
+}
+
+pub async fn set_mock_faults(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(faults): Json<Faults>,
+) -> Result<ResponseJson<MockStatus>, ApiError> {
+    let chain = require_mock()?;
+    if !(0.0..=1.0).contains(&faults.error_rate) {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "'error_rate' must be between 0 and 1",
+        ));
+    }
+    state.audit.record(audit::record(
+        "mock_faults",
+        actor,
+        serde_json::to_value(&faults).unwrap_or_default(),
+    ));
+    chain.set_faults(faults);
+    Ok(ResponseJson(chain.status()))
+}
+
+pub async fn set_mock_balance(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    AddressPath(wallet): AddressPath<WalletAddress>,
+    Json(payload): Json<MockBalanceRequest>,
+) -> Result<ResponseJson<MockStatus>, ApiError> {
+    let chain = require_mock()?;
+    let pubkey = wallet.0;
+    state.audit.record(audit::record(
+        "mock_balance",
+        actor,
+        serde_json::json!({ "wallet": wallet, "lamports": payload.lamports }),
+    ));
+    chain.set_balance(pubkey, payload.lamports);
+    Ok(ResponseJson(chain.status()))
+}
+
+pub async fn reset_mock(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+) -> Result<ResponseJson<MockStatus>, ApiError> {
+    let chain = require_mock()?;
+    state
+        .audit
+        .record(audit::record("mock_reset", actor, serde_json::Value::Null));
+    chain.reset();
+    Ok(ResponseJson(chain.status()))
+}
+
+fn require_dev_mode(state: &AppState) -> Result<(), ApiError> {
+    if !state.config().dev_mode {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Chaos injection is only available in dev mode",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "profiling")]
+fn profile_window(seconds: Option<u64>, default: u64) -> Result<Duration, ApiError> {
+    match seconds.unwrap_or(default) {
+        secs @ 1..=profiling::MAX_SECS => Ok(Duration::from_secs(secs)),
+        _ => Err(error_response(
+            StatusCode::BAD_REQUEST,
+            format!("seconds must be between 1 and {}", profiling::MAX_SECS),
+        )),
+    }
+}
+
+// All three answer once the window is over. /debug/pprof samples stacks
+// across the whole process: an SVG flamegraph by default, `format=folded`
+// for collapsed stacks or `format=pprof` for `go tool pprof`.
+#[cfg(feature = "profiling")]
+pub async fn debug_pprof(Query(query): Query<PprofQuery>) -> Result<Response, ApiError> {
+    let window = profile_window(query.seconds, profiling::DEFAULT_CPU_SECS)?;
+    let profile_error = |e| match e {
+        ProfileError::Busy => error_response(
+            StatusCode::CONFLICT,
+            "A CPU profile is already being taken, retry once it is done",
+        ),
+        ProfileError::Failed(e) => error_response(StatusCode::NOT_IMPLEMENTED, e),
+    };
+    let profile = pprof::profile(window).await.map_err(profile_error)?;
+    let (content_type, body) = profile.render(query.format).map_err(profile_error)?;
+    Ok(([(header::CONTENT_TYPE, content_type)], body).into_response())
+}
+
+#[cfg(feature = "profiling")]
+pub async fn debug_cpu(
+    Query(query): Query<ProfileQuery>,
+) -> Result<ResponseJson<CpuProfile>, ApiError> {
+    let window = profile_window(query.seconds, profiling::DEFAULT_CPU_SECS)?;
+    profiling::cpu(window)
+        .await
+        .map(ResponseJson)
+        .map_err(|e| error_response(StatusCode::NOT_IMPLEMENTED, e))
+}
+
+#[cfg(feature = "profiling")]
+pub async fn debug_tokio(
+    Query(query): Query<ProfileQuery>,
+) -> Result<ResponseJson<RuntimeReport>, ApiError> {
+    let window = profile_window(query.seconds, profiling::DEFAULT_TOKIO_SECS)?;
+    Ok(ResponseJson(profiling::runtime(window).await))
+}
+
+pub async fn chaos_status(State(state): State<AppState>) -> ResponseJson<ChaosStatus> {
+    ResponseJson(state.chaos.status(state.config().dev_mode))
+}
+
+pub async fn set_chaos_rules(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(rules): Json<Vec<ChaosRule>>,
+) -> Result<ResponseJson<ChaosStatus>, ApiError> {
+    require_dev_mode(&state)?;
+    let params = serde_json::to_value(&rules).unwrap_or_default();
+    state
+        .chaos
+        .set_rules(rules)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))?;
+    state
+        .audit
+        .record(audit::record("chaos_rules", actor, params));
+    Ok(ResponseJson(state.chaos.status(true)))
+}
+
+pub async fn clear_chaos_rules(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+) -> ResponseJson<ChaosStatus> {
+    state
+        .audit
+        .record(audit::record("chaos_clear", actor, serde_json::Value::Null));
+    state.chaos.clear();
+    ResponseJson(state.chaos.status(state.config().dev_mode))
+}
+
+pub async fn list_features(State(state): State<AppState>) -> ResponseJson<Vec<FeatureInfo>> {
+    ResponseJson(state.features.list(&state.config()))
+}
+
+// Body maps features to a state, or to null to drop the override and fall
+// back to the config.
+pub async fn update_features(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(changes): Json<BTreeMap<String, Option<FeatureState>>>,
+) -> Result<ResponseJson<Vec<FeatureInfo>>, ApiError> {
+    let params = serde_json::to_value(&changes).unwrap_or_default();
+    state
+        .features
+        .update(changes)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))?;
+    state
+        .audit
+        .record(audit::record("features_update", actor, params));
+    Ok(ResponseJson(state.features.list(&state.config())))
+}
+
+pub async fn query_audit_log(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<ResponseJson<AuditQueryResponse>, ApiError> {
+    if !state.audit.is_enabled() {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Audit log is disabled",
+        ));
+    }
+
+    let records = state
+        .audit
+        .query(
+            query.action.as_deref(),
+            query.org.as_deref(),
+            query.since_ms,
+            query.limit.unwrap_or(100).min(1_000),
+        )
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    Ok(ResponseJson(AuditQueryResponse { records }))
+}
+
+fn org_report(state: &AppState, org: Org) -> OrgReport {
+    let usage = state.usage.org_report(&org, &state.config());
+    OrgReport {
+        watchlists: state.watchlists.list(&orgs::usage_subject(&org.id)).len(),
+        keys: state.keys.list(Some(&org.id)),
+        id: org.id,
+        name: org.name,
+        airdrop_daily_quota_sol: org.airdrop_daily_quota_sol,
+        webhooks: org.webhooks,
+        created_at_ms: org.created_at_ms,
+        usage,
+    }
+}
+
+fn org_not_found() -> ApiError {
+    error_response(StatusCode::NOT_FOUND, "Org not found")
+}
+
+pub async fn list_orgs(State(state): State<AppState>) -> ResponseJson<OrgsResponse> {
+    let orgs: Vec<OrgReport> = state
+        .orgs
+        .list()
+        .into_iter()
+        .map(|org| org_report(&state, org))
+        .collect();
+    ResponseJson(OrgsResponse {
+        total: orgs.len(),
+        orgs,
+    })
+}
+
+pub async fn create_org(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(payload): Json<CreateOrgRequest>,
+) -> Result<(StatusCode, ResponseJson<OrgReport>), ApiError> {
+    let record = audit::record(
+        "org_create",
+        actor,
+        serde_json::json!({
+            "org": payload.id,
+            "name": payload.settings.name,
+            "airdrop_daily_quota_sol": payload.settings.airdrop_daily_quota_sol,
+        }),
+    );
+    match state.orgs.create(&payload.id, payload.settings) {
+        Ok(org) => {
+            state.audit.record(record);
+            Ok((StatusCode::CREATED, ResponseJson(org_report(&state, org))))
+        }
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(service_error(e))
+        }
+    }
+}
+
+pub async fn get_org(
+    State(state): State<AppState>,
+    Path(org): Path<String>,
+) -> Result<ResponseJson<OrgReport>, ApiError> {
+    let org = state.orgs.get(&org).ok_or_else(org_not_found)?;
+    Ok(ResponseJson(org_report(&state, org)))
+}
+
+pub async fn update_org(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path(org): Path<String>,
+    Json(payload): Json<OrgSettings>,
+) -> Result<ResponseJson<OrgReport>, ApiError> {
+    let record = audit::record(
+        "org_update",
+        actor,
+        serde_json::json!({
+            "org": org,
+            "name": payload.name,
+            "airdrop_daily_quota_sol": payload.airdrop_daily_quota_sol,
+        }),
+    );
+    match state.orgs.update(&org, payload) {
+        Ok(Some(org)) => {
+            state.audit.record(record);
+            Ok(ResponseJson(org_report(&state, org)))
+        }
+        Ok(None) => Err(org_not_found()),
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(service_error(e))
+        }
+    }
+}
+
+pub async fn delete_org(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path(org): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let record = audit::record("org_delete", actor, serde_json::json!({ "org": org }));
+    // Keys go first, so a failure can't leave working keys of a deleted org.
+    let result = state
+        .keys
+        .revoke_org(&org)
+        .and_then(|()| state.orgs.remove(&org));
+    match result {
+        Ok(true) => {
+            state.audit.record(record);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Err(org_not_found()),
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+fn key_not_found() -> ApiError {
+    error_response(StatusCode::NOT_FOUND, "Key not found")
+}
+
+fn create_key_for(
+    state: &AppState,
+    actor: Actor,
+    role: Role,
+    org: Option<String>,
+    label: Option<String>,
+) -> Result<(StatusCode, ResponseJson<IssuedKeyResponse>), ApiError> {
+    let record = audit::record(
+        "key_create",
+        actor,
+        serde_json::json!({ "org": org, "role": role, "label": label }),
+    );
+    if org
+        .as_ref()
+        .is_some_and(|org| state.orgs.get(org).is_none())
+    {
+        return Err(org_not_found());
+    }
+    match state.keys.create(role, org.as_deref(), label) {
+        Ok((key, api_key)) => {
+            state.audit.record(AuditRecord {
+                params: serde_json::json!({
+                    "id": key.id,
+                    "org": key.org,
+                    "role": key.role,
+                    "label": key.label,
+                }),
+                ..record
+            });
+            Ok((
+                StatusCode::CREATED,
+                ResponseJson(IssuedKeyResponse { key, api_key }),
+            ))
+        }
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(service_error(e))
+        }
+    }
+}
+
+fn rotate_key_for(
+    state: &AppState,
+    actor: Actor,
+    id: u64,
+    org: Option<&str>,
+) -> Result<ResponseJson<IssuedKeyResponse>, ApiError> {
+    let record = audit::record(
+        "key_rotate",
+        actor,
+        serde_json::json!({ "id": id, "org": org }),
+    );
+    match state.keys.rotate(id, org) {
+        Ok(Some((key, api_key))) => {
+            state.audit.record(record);
+            Ok(ResponseJson(IssuedKeyResponse { key, api_key }))
+        }
+        Ok(None) => Err(key_not_found()),
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(service_error(e))
+        }
+    }
+}
+
+fn revoke_key_for(
+    state: &AppState,
+    actor: Actor,
+    id: u64,
+    org: Option<&str>,
+) -> Result<StatusCode, ApiError> {
+    let record = audit::record(
+        "key_revoke",
+        actor,
+        serde_json::json!({ "id": id, "org": org }),
+    );
+    match state.keys.revoke(id, org) {
+        Ok(true) => {
+            state.audit.record(record);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Err(key_not_found()),
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+pub async fn list_keys(
+    State(state): State<AppState>,
+    Query(query): Query<KeysQuery>,
+) -> ResponseJson<KeysResponse> {
+    let keys = state.keys.list(query.org.as_deref());
+    ResponseJson(KeysResponse {
+        total: keys.len(),
+        keys,
+    })
+}
+
+pub async fn create_key(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(payload): Json<CreateKeyRequest>,
+) -> Result<(StatusCode, ResponseJson<IssuedKeyResponse>), ApiError> {
+    create_key_for(&state, actor, payload.role, payload.org, payload.label)
+}
+
+pub async fn rotate_key(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path(id): Path<u64>,
+) -> Result<ResponseJson<IssuedKeyResponse>, ApiError> {
+    rotate_key_for(&state, actor, id, None)
+}
+
+pub async fn revoke_key(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, ApiError> {
+    revoke_key_for(&state, actor, id, None)
+}
+
+pub async fn issue_org_key(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path(org): Path<String>,
+    Json(payload): Json<IssueKeyRequest>,
+) -> Result<(StatusCode, ResponseJson<IssuedKeyResponse>), ApiError> {
+    create_key_for(&state, actor, payload.role, Some(org), payload.label)
+}
+
+pub async fn rotate_org_key(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path((org, id)): Path<(String, u64)>,
+) -> Result<ResponseJson<IssuedKeyResponse>, ApiError> {
+    rotate_key_for(&state, actor, id, Some(&org))
+}
+
+pub async fn revoke_org_key(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path((org, id)): Path<(String, u64)>,
+) -> Result<StatusCode, ApiError> {
+    revoke_key_for(&state, actor, id, Some(&org))
+}
+
+// The audit log narrowed to requests made with the org's keys.
+pub async fn org_audit_log(
+    state: State<AppState>,
+    Path(org): Path<String>,
+    Query(query): Query<AuditQuery>,
+) -> Result<ResponseJson<AuditQueryResponse>, ApiError> {
+    query_audit_log(
+        state,
+        Query(AuditQuery {
+            org: Some(org),
+            ..query
+        }),
+    )
+    .await
+}
+
+pub async fn deploy_program(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, ResponseJson<JobAcceptedResponse>), ApiError> {
+    let payer = require_keypair(&state)?;
+
+    let mut program = None;
+    let mut program_id = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e.to_string()))?
+    {
+        match field.name() {
+            Some("program") => {
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| error_response(StatusCode::BAD_REQUEST, e.to_string()))?;
+                program = Some(bytes);
+            }
+            Some("program_id") => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| error_response(StatusCode::BAD_REQUEST, e.to_string()))?;
+                let id = Pubkey::from_str(text.trim())
+                    .map_err(|_| error_response(StatusCode::BAD_REQUEST, "Invalid program_id"))?;
+                program_id = Some(id);
+            }
+            _ => {}
+        }
+    }
+
+    let Some(program) = program else {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "Missing 'program' file field",
+        ));
+    };
+    let config = state.config();
+    deploy::validate_program(&program, config.max_program_size).map_err(service_error)?;
+
+    let job_id = state.jobs.create("program_deploy");
+    let record = audit::record(
+        "program_deploy",
+        actor,
+        serde_json::json!({
+            "program_id": program_id.map(|id| id.to_string()),
+            "size": program.len(),
+            "job_id": job_id,
+        }),
+    );
+
+    let jobs = state.jobs.clone();
+    let audit = state.audit.clone();
+    rpc::spawn_blocking(move || {
+        let result = deploy::deploy_program(
+            &config.rpc_url,
+            &payer,
+            &program,
+            program_id,
+            |step, completed, total| jobs.progress(job_id, step, completed, total),
+        );
+
+        match result {
+            Ok(deployment) => {
+                audit.record(record.signature(deployment.signature));
+                let urls = links::transaction(&config, &deployment.signature);
+                jobs.succeed(
+                    job_id,
+                    serde_json::json!({
+                        "program_id": deployment.program_id.to_string(),
+                        "buffer": deployment.buffer.to_string(),
+                        "upgraded": deployment.upgraded,
+                        "transaction_signature": deployment.signature.to_string(),
+                        "explorer_url": urls.solana_explorer,
+                        "explorer_urls": urls,
+                    }),
+                );
+            }
+            Err(e) => {
+                audit.record(record.failed(&e));
+                jobs.fail(job_id, e);
+            }
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        ResponseJson(JobAcceptedResponse {
+            job_id,
+            status_url: format!("/dev/jobs/{}", job_id),
+        }),
+    ))
+}
+
+// Row errors listed in a rejected bulk upload; the rest are counted.
+const MAX_LISTED_ROW_ERRORS: usize = 20;
+
+fn invalid_upload(errors: Vec<RowError>) -> ApiError {
+    let mut message: Vec<String> = errors
+        .iter()
+        .take(MAX_LISTED_ROW_ERRORS)
+        .map(|e| match e.line {
+            0 => e.error.clone(),
+            line => format!("line {}: {}", line, e.error),
+        })
+        .collect();
+    if errors.len() > MAX_LISTED_ROW_ERRORS {
+        message.push(format!("and {} more", errors.len() - MAX_LISTED_ROW_ERRORS));
+    }
+    error_response(
+        StatusCode::BAD_REQUEST,
+        format!("Invalid upload: {}", message.join("; ")),
+    )
+}
+
+// Takes a `file` field of wallet,amount rows, checks every row and that
+// the treasury covers the total, then pays them as a background job.
+pub async fn bulk_airdrop(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, ResponseJson<JobAcceptedResponse>), ApiError> {
+    let payer = require_keypair(&state)?;
+
+    let mut csv = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e.to_string()))?
+    {
+        if field.name() == Some("file") {
+            let text = field
+                .text()
+                .await
+                .map_err(|e| error_response(StatusCode::BAD_REQUEST, e.to_string()))?;
+            csv = Some(text);
+        }
+    }
+    let Some(csv) = csv else {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "Missing 'file' field",
+        ));
+    };
+
+    let config = state.config();
+    let mut errors = Vec::new();
+    let rows = match bulk::parse(&csv, config.bulk_airdrop_max_rows) {
+        Ok(rows) => rows,
+        Err(e) => {
+            errors = e;
+            Vec::new()
+        }
+    };
+    for row in &rows {
+        let wallet = row.wallet.to_string();
+        if let Err(e) = state.check_recipient(&wallet) {
+            let record = audit::record(
+                bulk::JOB_KIND,
+                actor.clone(),
+                serde_json::json!({
+                    "wallet": wallet,
+                    "lamports": row.lamports,
+                    "line": row.line,
+                }),
+            );
+            state.audit.record(record.failed(&e));
+            errors.push(RowError {
+                line: row.line,
+                error: e.to_string(),
+            });
+        }
+    }
+    if !errors.is_empty() {
+        return Err(invalid_upload(errors));
+    }
+
+    let total: u64 = rows.iter().map(|r| r.lamports).sum();
+    let rpc_url = config.rpc_url.clone();
+    let treasury = payer.pubkey().to_string();
+    let balance = rpc::spawn_blocking(move || service::get_balance(&rpc_url, &treasury))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(service_error)?;
+    if balance < total {
+        return Err(error_response(
+            StatusCode::CONFLICT,
+            format!(
+                "The treasury holds {} SOL; the upload needs {} SOL",
+                lamports_to_sol(balance),
+                lamports_to_sol(total)
+            ),
+        ));
+    }
+
+    let job_id = state.jobs.create(bulk::JOB_KIND);
+    tokio::spawn(bulk::run(state.clone(), job_id, payer, actor, rows));
+
+    Ok((
+        StatusCode::ACCEPTED,
+        ResponseJson(JobAcceptedResponse {
+            job_id,
+            status_url: format!("/admin/airdrop_bulk/{}", job_id),
+        }),
+    ))
+}
+
+fn bulk_job(state: &AppState, id: u64) -> Result<Job, ApiError> {
+    state
+        .jobs
+        .get(id)
+        .filter(|job| job.kind == bulk::JOB_KIND)
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "Job not found"))
+}
+
+pub async fn bulk_airdrop_status(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<ResponseJson<Job>, ApiError> {
+    bulk_job(&state, id).map(ResponseJson)
+}
+
+// Takes the address list as the body, or as the `file` field of a
+// multipart upload, and streams a row per address back as NDJSON or CSV.
+pub async fn bulk_balances(
+    State(state): State<AppState>,
+    Query(query): Query<BulkBalancesQuery>,
+    request: Request,
+) -> Result<Response, ApiError> {
+    let multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("multipart/form-data"));
+    let text = if multipart {
+        let mut multipart = Multipart::from_request(request, &state)
+            .await
+            .map_err(|e| error_response(StatusCode::BAD_REQUEST, e.body_text()))?;
+        let mut text = None;
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| error_response(StatusCode::BAD_REQUEST, e.to_string()))?
+        {
+            if field.name() == Some("file") {
+                let field_text = field
+                    .text()
+                    .await
+                    .map_err(|e| error_response(StatusCode::BAD_REQUEST, e.to_string()))?;
+                text = Some(field_text);
+            }
+        }
+        text.ok_or_else(|| error_response(StatusCode::BAD_REQUEST, "Missing 'file' field"))?
+    } else {
+        String::from_request(request, &state)
+            .await
+            .map_err(|e| error_response(StatusCode::BAD_REQUEST, e.body_text()))?
+    };
+
+    let config = state.config();
+    let addresses =
+        bulk::parse_addresses(&text, config.bulk_balance_max_addresses).map_err(invalid_upload)?;
+    let body = Body::from_stream(bulk::balances(
+        config.rpc_url.clone(),
+        fanout::Limits::new(&config),
+        addresses,
+        query.format,
+    ));
+    Ok(([(header::CONTENT_TYPE, query.format.content_type())], body).into_response())
+}
+
+pub async fn bulk_airdrop_results(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<([(header::HeaderName, String); 2], String), ApiError> {
+    let job = bulk_job(&state, id)?;
+    let Some(result) = job.result else {
+        return Err(error_response(
+            StatusCode::CONFLICT,
+            "The job hasn't finished yet",
+        ));
+    };
+    let results: Vec<RowResult> = serde_json::from_value(result["rows"].clone())
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"airdrop_bulk_{}.csv\"", id),
+            ),
+        ],
+        bulk::results_csv(&results),
+    ))
+}
+
+// Audits a validator lifecycle action and maps its result.
+fn validator_response(
+    state: &AppState,
+    actor: Actor,
+    action: &'static str,
+    result: Result<ValidatorStatus, String>,
+) -> Result<ResponseJson<ValidatorStatus>, ApiError> {
+    let record = audit::record(action, actor, serde_json::Value::Null);
+    match result {
+        Ok(status) => {
+            state.audit.record(record);
+            Ok(ResponseJson(status))
+        }
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            Err(error_response(StatusCode::CONFLICT, e))
+        }
+    }
+}
+
+pub async fn validator_status(State(state): State<AppState>) -> ResponseJson<ValidatorStatus> {
+    ResponseJson(state.validator.status().await)
+}
+
+pub async fn start_validator(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+) -> Result<ResponseJson<ValidatorStatus>, ApiError> {
+    let result = state.validator.start(&state, false).await;
+    validator_response(&state, actor, "validator_start", result)
+}
+
+pub async fn stop_validator(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+) -> Result<ResponseJson<ValidatorStatus>, ApiError> {
+    let result = state.validator.stop(&state).await;
+    validator_response(&state, actor, "validator_stop", result)
+}
+
+pub async fn reset_validator(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+) -> Result<ResponseJson<ValidatorStatus>, ApiError> {
+    let result = state.validator.reset(&state).await;
+    validator_response(&state, actor, "validator_reset", result)
+}
+
+fn require_fixtures_dir(state: &AppState) -> Result<PathBuf, ApiError> {
+    state
+        .config()
+        .validator_fixtures_dir
+        .clone()
+        .ok_or_else(|| {
+            error_response(
+                StatusCode::NOT_FOUND,
+                "validator_fixtures_dir is not configured",
+            )
+        })
+}
+
+pub async fn list_fixtures(
+    State(state): State<AppState>,
+) -> Result<ResponseJson<FixturesResponse>, ApiError> {
+    let dir = require_fixtures_dir(&state)?;
+    let fixtures =
+        fixtures::list(&dir).map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(ResponseJson(FixturesResponse {
+        fixtures,
+        validator: state.validator.status().await,
+    }))
+}
+
+pub async fn load_fixtures(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(payload): Json<LoadFixturesRequest>,
+) -> Result<ResponseJson<FixturesResponse>, ApiError> {
+    let dir = require_fixtures_dir(&state)?;
+    let record = audit::record(
+        "fixtures_load",
+        actor,
+        serde_json::json!({
+            "pubkeys": payload.fixtures.iter().map(|f| &f.pubkey).collect::<Vec<_>>(),
+            "reset": payload.reset,
+        }),
+    );
+
+    let fixtures = match fixtures::save(&dir, &payload.fixtures) {
+        Ok(fixtures) => fixtures,
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            return Err(error_response(StatusCode::BAD_REQUEST, e));
+        }
+    };
+    let validator = if payload.reset && state.validator.status().await.running {
+        match state.validator.reset(&state).await {
+            Ok(status) => status,
+            Err(e) => {
+                state.audit.record(record.failed(&e));
+                return Err(error_response(StatusCode::CONFLICT, e));
+            }
+        }
+    } else {
+        state.validator.status().await
+    };
+    state.audit.record(record);
+
+    Ok(ResponseJson(FixturesResponse {
+        fixtures,
+        validator,
+    }))
+}
+
+pub async fn mint_nft(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(payload): Json<MintNftRequest>,
+) -> Result<ResponseJson<MintNftResponse>, ApiError> {
+    let payer = require_keypair(&state)?;
+    let owner = service::parse_wallet(&payload.wallet).map_err(service_error)?;
+    let metadata = NftMetadata {
+        name: payload.name,
+        symbol: payload.symbol,
+        uri: payload.uri,
+    };
+    nft::validate(&metadata).map_err(service_error)?;
+    let record = audit::record(
+        "nft_mint",
+        actor,
+        serde_json::json!({ "wallet": payload.wallet, "name": metadata.name, "uri": metadata.uri }),
+    );
+
+    let rpc_url = state.config().rpc_url.clone();
+    let minted = rpc::spawn_blocking(move || nft::mint(&rpc_url, &payer, &owner, &metadata))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let minted = match minted {
+        Ok(minted) => minted,
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            return Err(service_error(e));
+        }
+    };
+    state.audit.record(record.signature(minted.signature));
+
+    let urls = links::transaction(&state.config(), &minted.signature);
+    Ok(ResponseJson(MintNftResponse {
+        wallet: payload.wallet,
+        mint: minted.mint.to_string(),
+        token_account: minted.token_account.to_string(),
+        metadata: minted.metadata.to_string(),
+        master_edition: minted.master_edition.to_string(),
+        transaction_signature: minted.signature.to_string(),
+        explorer_url: urls.solana_explorer.clone(),
+        explorer_urls: urls,
+    }))
+}
+
+pub async fn get_stake_pool(
+    State(state): State<AppState>,
+    AddressPath(pool): AddressPath<WalletAddress>,
+) -> Result<ResponseJson<StakePoolResponse>, ApiError> {
+    let address = pool.0;
+    let rpc_url = state.config().rpc_url.clone();
+    let (stake_pool, epoch) =
+        rpc::spawn_blocking(move || stake_pool::stake_pool(&rpc_url, &address))
+            .await
+            .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(service_error)?;
+
+    let exchange_rate = match stake_pool.pool_token_supply {
+        0 => 1.0,
+        supply => stake_pool.total_lamports as f64 / supply as f64,
+    };
+    Ok(ResponseJson(StakePoolResponse {
+        pool: pool.to_string(),
+        program_id: stake_pool.program_id.to_string(),
+        manager: stake_pool.manager.to_string(),
+        staker: stake_pool.staker.to_string(),
+        pool_mint: stake_pool.pool_mint.to_string(),
+        token_program_id: stake_pool.token_program_id.to_string(),
+        reserve_stake: stake_pool.reserve_stake.to_string(),
+        validator_list: stake_pool.validator_list.to_string(),
+        manager_fee_account: stake_pool.manager_fee_account.to_string(),
+        total_lamports: stake_pool.total_lamports,
+        total_sol_decimal: amount::lamports_to_sol_decimal(stake_pool.total_lamports),
+        pool_token_supply: stake_pool.pool_token_supply,
+        exchange_rate,
+        last_update_epoch: stake_pool.last_update_epoch,
+        current_epoch: epoch,
+        needs_update: stake_pool.last_update_epoch < epoch,
+        fees: StakePoolFees {
+            epoch: stake_pool.epoch_fee.into(),
+            sol_deposit: stake_pool.sol_deposit_fee.into(),
+            sol_withdrawal: stake_pool.sol_withdrawal_fee.into(),
+            stake_deposit: stake_pool.stake_deposit_fee.into(),
+            stake_withdrawal: stake_pool.stake_withdrawal_fee.into(),
+        },
+        sol_deposit_authority: stake_pool.sol_deposit_authority.map(|a| a.to_string()),
+        sol_withdraw_authority: stake_pool.sol_withdraw_authority.map(|a| a.to_string()),
+    }))
+}
+
+pub async fn get_stake_pool_validators(
+    State(state): State<AppState>,
+    AddressPath(pool): AddressPath<WalletAddress>,
+) -> Result<ResponseJson<ValidatorListResponse>, ApiError> {
+    let address = pool.0;
+    let rpc_url = state.config().rpc_url.clone();
+    let list = rpc::spawn_blocking(move || stake_pool::validator_list(&rpc_url, &address))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(service_error)?;
+
+    Ok(ResponseJson(ValidatorListResponse {
+        pool: pool.to_string(),
+        max_validators: list.max_validators,
+        total_active_stake_lamports: list
+            .validators
+            .iter()
+            .map(|v| v.active_stake_lamports)
+            .sum(),
+        validators: list
+            .validators
+            .into_iter()
+            .map(|v| ValidatorStakeResponse {
+                vote_account: v.vote_account.to_string(),
+                active_stake_lamports: v.active_stake_lamports,
+                transient_stake_lamports: v.transient_stake_lamports,
+                last_update_epoch: v.last_update_epoch,
+                status: v.status.to_string(),
+            })
+            .collect(),
+    }))
+}
+
+async fn stake_pool_transaction(
+    state: AppState,
+    actor: Actor,
+    pool: String,
+    wallet: String,
+    action: PoolAction,
+    submit: bool,
+) -> Result<ResponseJson<StakePoolTransactionResponse>, ApiError> {
+    let pool_address = service::parse_wallet(&pool).map_err(service_error)?;
+    let wallet_address = service::parse_wallet(&wallet).map_err(service_error)?;
+    let signer = if submit {
+        let keypair = require_keypair(&state)?;
+        if keypair.pubkey() != wallet_address {
+            return Err(error_response(
+                StatusCode::FORBIDDEN,
+                "Only transactions for the server keypair can be submitted",
+            ));
+        }
+        Some(keypair)
+    } else {
+        None
+    };
+    let record = audit::record(
+        match action {
+            PoolAction::DepositSol { .. } => "stake_pool_deposit",
+            PoolAction::WithdrawSol { .. } => "stake_pool_withdraw",
+        },
+        actor,
+        match action {
+            PoolAction::DepositSol { lamports } => {
+                serde_json::json!({ "pool": pool, "lamports": lamports })
+            }
+            PoolAction::WithdrawSol { pool_tokens } => {
+                serde_json::json!({ "pool": pool, "pool_tokens": pool_tokens })
+            }
+        },
+    );
+
+    let rpc_url = state.config().rpc_url.clone();
+    let result = rpc::spawn_blocking(move || {
+        stake_pool::run(
+            &rpc_url,
+            &pool_address,
+            &wallet_address,
+            action,
+            signer.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let (transaction, signature) = match result {
+        Ok(PoolTransaction::Unsigned(transaction)) => (Some(transaction), None),
+        Ok(PoolTransaction::Submitted(signature)) => {
+            state.audit.record(record.signature(signature));
+            (None, Some(signature))
+        }
+        Err(e) => {
+            if submit {
+                state.audit.record(record.failed(&e));
+            }
+            return Err(service_error(e));
+        }
+    };
+    let urls = signature.map(|s| links::transaction(&state.config(), &s));
+    Ok(ResponseJson(StakePoolTransactionResponse {
+        pool,
+        wallet,
+        transaction,
+        transaction_signature: signature.map(|s| s.to_string()),
+        explorer_url: urls.as_ref().map(|u| u.solana_explorer.clone()),
+        explorer_urls: urls,
+    }))
+}
+
+pub async fn stake_pool_deposit(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path(pool): Path<String>,
+    Json(payload): Json<StakePoolDepositRequest>,
+) -> Result<ResponseJson<StakePoolTransactionResponse>, ApiError> {
+    if payload.lamports == 0 {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "'lamports' must be greater than 0",
+        ));
+    }
+    let action = PoolAction::DepositSol {
+        lamports: payload.lamports,
+    };
+    stake_pool_transaction(state, actor, pool, payload.wallet, action, payload.submit).await
+}
+
+pub async fn stake_pool_withdraw(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Path(pool): Path<String>,
+    Json(payload): Json<StakePoolWithdrawRequest>,
+) -> Result<ResponseJson<StakePoolTransactionResponse>, ApiError> {
+    if payload.pool_tokens == 0 {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "'pool_tokens' must be greater than 0",
+        ));
+    }
+    let action = PoolAction::WithdrawSol {
+        pool_tokens: payload.pool_tokens,
+    };
+    stake_pool_transaction(state, actor, pool, payload.wallet, action, payload.submit).await
+}
+
+pub async fn get_job(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<ResponseJson<Job>, ApiError> {
+    state
+        .jobs
+        .get(id)
+        .map(ResponseJson)
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "Job not found"))
+}
+
+pub async fn list_buffers(
+    State(state): State<AppState>,
+    Query(options): Query<AmountOptions>,
+) -> Result<ResponseJson<ListBuffersResponse>, ApiError> {
+    let authority = require_keypair(&state)?.pubkey();
+    let config = state.config();
+    let sol_as_string = amount::sol_as_string(&options, &config);
+    let rpc_url = config.rpc_url.clone();
+
+    let buffers = rpc::spawn_blocking(move || deploy::list_buffers(&rpc_url, &authority))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(service_error)?;
+
+    Ok(ResponseJson(ListBuffersResponse {
+        authority: authority.to_string(),
+        total_lamports: buffers.iter().map(|b| b.lamports).sum(),
+        buffers: buffers
+            .into_iter()
+            .map(|b| BufferInfo {
+                address: b.address.to_string(),
+                lamports: b.lamports,
+                sol: SolValue::new(b.lamports, sol_as_string),
+                sol_decimal: amount::lamports_to_sol_decimal(b.lamports),
+                display: amount::display(&options, b.lamports, &config),
+            })
+            .collect(),
+    }))
+}
+
+pub async fn close_buffers(
+    State(state): State<AppState>,
+    Caller(actor): Caller,
+    Json(payload): Json<CloseBuffersRequest>,
+) -> Result<ResponseJson<CloseBuffersResponse>, ApiError> {
+    let authority = require_keypair(&state)?;
+    let config = state.config();
+    let audit = state.audit.clone();
+
+    let response = rpc::spawn_blocking(move || {
+        let rpc_url = &config.rpc_url;
+        let owned = deploy::list_buffers(rpc_url, &authority.pubkey())?;
+
+        let targets: Vec<String> = match payload.buffers {
+            Some(buffers) => buffers,
+            None => owned.iter().map(|b| b.address.to_string()).collect(),
+        };
+
+        let mut closed = Vec::new();
+        let mut failed = Vec::new();
+        for address in targets {
+            let Some(buffer) = owned.iter().find(|b| b.address.to_string() == address) else {
+                failed.push(FailedBuffer {
+                    address,
+                    error: "Not a program buffer owned by the server keypair".to_string(),
+                });
+                continue;
+            };
+
+            let record = audit::record(
+                "program_buffer_close",
+                actor.clone(),
+                serde_json::json!({ "buffer": address, "lamports": buffer.lamports }),
+            );
+            match deploy::close_buffer(rpc_url, &authority, &buffer.address) {
+                Ok(signature) => {
+                    audit.record(record.signature(signature));
+                    closed.push(ClosedBuffer {
+                        address,
+                        lamports: buffer.lamports,
+                        transaction_signature: signature.to_string(),
+                        explorer_urls: links::transaction(&config, &signature),
+                    });
+                }
+                Err(e) => {
+                    audit.record(record.failed(&e));
+                    failed.push(FailedBuffer {
+                        address,
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok::<_, ServiceError>(CloseBuffersResponse {
+            recipient: authority.pubkey().to_string(),
+            reclaimed_lamports: closed.iter().map(|c| c.lamports).sum(),
+            closed,
+            failed,
+        })
+    })
+    .await
+    .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(service_error)?;
+
+    Ok(ResponseJson(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    async fn message(err: ApiError) -> (StatusCode, serde_json::Value) {
+        let res = err.1.into_response();
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (err.0, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn service_errors_map_to_statuses_and_codes() {
+        let mapped = |err| {
+            let (status, res) = service_error(err);
+            (status, res.code())
+        };
+        assert_eq!(
+            mapped(ServiceError::InvalidWallet),
+            (StatusCode::BAD_REQUEST, None)
+        );
+        assert_eq!(
+            mapped(ServiceError::HistoryUnavailable("pruned".into())),
+            (StatusCode::UNPROCESSABLE_ENTITY, None)
+        );
+        assert_eq!(
+            mapped(ServiceError::RpcBudgetExceeded { budget: 10 }),
+            (StatusCode::TOO_MANY_REQUESTS, Some("rpc_budget_exceeded"))
+        );
+        assert_eq!(
+            mapped(ServiceError::FeatureDisabled("/swap".into())),
+            (StatusCode::FORBIDDEN, Some("feature_disabled"))
+        );
+        assert_eq!(
+            mapped(ServiceError::RpcIncompatible("too old".into())),
+            (StatusCode::SERVICE_UNAVAILABLE, Some("rpc_incompatible"))
+        );
+        assert_eq!(
+            mapped(ServiceError::Rpc {
+                action: "Failed to get balance",
+                message: "timed out".into(),
+            }),
+            (StatusCode::INTERNAL_SERVER_ERROR, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn upload_errors_list_the_first_rows() {
+        let errors = (0..=MAX_LISTED_ROW_ERRORS + 1)
+            .map(|line| RowError {
+                line,
+                error: "bad row".into(),
+            })
+            .collect();
+        let (status, body) = message(invalid_upload(errors)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let text = body["error"]["message"].as_str().unwrap();
+        assert!(text.starts_with("Invalid upload: bad row; line 1: bad row;"));
+        assert!(text.ends_with("; and 2 more"));
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn profile_windows_are_bounded() {
+        assert_eq!(profile_window(None, 5).ok(), Some(Duration::from_secs(5)));
+        assert_eq!(
+            profile_window(Some(profiling::MAX_SECS), 5).ok(),
+            Some(Duration::from_secs(profiling::MAX_SECS))
+        );
+        assert!(profile_window(Some(0), 5).is_err());
+        assert!(profile_window(Some(profiling::MAX_SECS + 1), 5).is_err());
+    }
+
+    // The handlers call the blocking RPC client, as on the server's runtime.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn balances_come_from_the_rpc_node() {
+        let state = AppState::for_tests(Config::default());
+        let wallet = Pubkey::new_unique();
+        rpc::mock_for_tests().set_balance(wallet, 1_500_000_000);
+
+        let Ok(ResponseJson(res)) = get_balance(
+            State(state.clone()),
+            Query(AmountOptions::default()),
+            Params(GetBalance {
+                address: wallet.to_string(),
+            }),
+        )
+        .await
+        else {
+            panic!("the balance lookup failed");
+        };
+        assert_eq!(res.balance_lamports, 1_500_000_000);
+        assert_eq!(res.balance_sol_decimal, "1.5");
+
+        let Err(err) = get_balance(
+            State(state),
+            Query(AmountOptions::default()),
+            Params(GetBalance {
+                address: "nope".into(),
+            }),
+        )
+        .await
+        else {
+            panic!("an invalid address was accepted");
+        };
+        let (status, body) = message(err).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["message"], "Invalid wallet address");
+    }
+
+    fn principal(role: Role) -> Option<Extension<Principal>> {
+        Some(Extension(Principal {
+            subject: Some(role.as_str().to_string()),
+            role: Some(role),
+            wallet: None,
+            org: None,
+            priority: crate::auth::Priority::Normal,
+        }))
+    }
+
+    // A local port with nothing listening on it.
+    async fn closed_url() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn only_admins_submit_swaps() {
+        let keypair = Keypair::new();
+        let mut state = AppState::for_tests(Config {
+            dev_mode: true,
+            jupiter_api_url: closed_url().await,
+            ..Config::default()
+        });
+        state.keypair = Some(Arc::new(keypair.insecure_clone()));
+        let request = || {
+            Json(SwapBuildRequest {
+                quote: serde_json::json!({}),
+                user: keypair.pubkey().to_string(),
+                submit: true,
+            })
+        };
+
+        for caller in [None, principal(Role::Funder)] {
+            let Err(err) = build_swap(
+                State(state.clone()),
+                Caller(Actor::default()),
+                caller,
+                request(),
+            )
+            .await
+            else {
+                panic!("a swap was submitted without an admin");
+            };
+            let (status, body) = message(err).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert_eq!(
+                body["error"]["message"],
+                "Submitting swaps requires the 'admin' role"
+            );
+        }
+        assert!(state
+            .audit
+            .query(Some("swap"), None, None, 10)
+            .unwrap()
+            .is_empty());
+
+        // An admin's submission is audited even when the build fails.
+        let result = build_swap(
+            State(state.clone()),
+            Caller(Actor::default()),
+            principal(Role::Admin),
+            request(),
+        )
+        .await;
+        assert!(result.is_err());
+        let records = state.audit.query(Some("swap"), None, None, 10).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].outcome, "failure");
+    }
+
+    #[tokio::test]
+    async fn the_treasury_only_signs_offchain_messages() {
+        let treasury = Keypair::new();
+        let mut state = AppState::for_tests(Config::default());
+        state.keypair = Some(Arc::new(treasury.insecure_clone()));
+        let sign = |key: Option<&str>, format| {
+            sign_message(
+                State(state.clone()),
+                Caller(Actor::default()),
+                Json(SignMessageRequest {
+                    message: "hello".into(),
+                    encoding: signing::MessageEncoding::Utf8,
+                    format,
+                    key: key.map(String::from),
+                }),
+            )
+        };
+
+        let ResponseJson(signed) = sign(None, SignedFormat::Raw).await.unwrap();
+        assert_eq!(signed.key, signing::DEFAULT_KEY);
+        assert_ne!(signed.pubkey, treasury.pubkey().to_string());
+
+        let Err(err) = sign(Some(signing::SERVER_KEY), SignedFormat::Raw).await else {
+            panic!("the treasury signed raw bytes");
+        };
+        let (status, body) = message(err).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["error"]["message"],
+            "The server keypair only signs offchain messages"
+        );
+
+        let ResponseJson(signed) = sign(Some(signing::SERVER_KEY), SignedFormat::Auto)
+            .await
+            .unwrap();
+        assert_eq!(signed.pubkey, treasury.pubkey().to_string());
+        assert_eq!(signed.format, "offchain");
+    }
+
+    // Puts `wallet` on the state's denylist.
+    async fn deny(state: &AppState, wallet: &Pubkey) {
+        let path = std::env::temp_dir().join(format!("denylist-{}.txt", wallet));
+        std::fs::write(&path, wallet.to_string()).unwrap();
+        let config = Config {
+            denylist_path: Some(path.clone()),
+            ..Config::default()
+        };
+        state.denylist.refresh(&config).await.unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn cnft_transfers_check_the_recipient_and_submitter() {
+        let state = AppState::for_tests(Config {
+            dev_mode: true,
+            das_url: Some(closed_url().await),
+            ..Config::default()
+        });
+        let denied = Pubkey::new_unique();
+        deny(&state, &denied).await;
+        let transfer = |new_owner: &Pubkey, caller| {
+            transfer_cnft(
+                State(state.clone()),
+                Caller(Actor::default()),
+                caller,
+                Path("asset".to_string()),
+                Json(CnftTransferRequest {
+                    new_owner: new_owner.to_string(),
+                    submit: true,
+                }),
+            )
+        };
+
+        let Err(err) = transfer(&denied, principal(Role::Admin)).await else {
+            panic!("a transfer to a denylisted wallet went through");
+        };
+        let (status, body) = message(err).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"]["code"], "wallet_denylisted");
+
+        let Err(err) = transfer(&Pubkey::new_unique(), principal(Role::Funder)).await else {
+            panic!("a transfer was submitted without an admin");
+        };
+        let (status, body) = message(err).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(
+            body["error"]["message"],
+            "Submitting transfers requires the 'admin' role"
+        );
+    }
+
+    fn failures(state: &AppState, action: &str) -> Vec<AuditRecord> {
+        state
+            .audit
+            .query(Some(action), None, None, 10)
+            .unwrap()
+            .into_iter()
+            .filter(|r| r.outcome == "failure")
+            .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn bulk_airdrops_respect_allowlist_only_mode() {
+        let mut state = AppState::for_tests(Config {
+            allowlist_only: true,
+            ..Config::default()
+        });
+        state.keypair = Some(Arc::new(Keypair::new()));
+        let (listed, unlisted) = (Pubkey::new_unique(), Pubkey::new_unique());
+        state.allowlist.add(&[listed]).unwrap();
+
+        let body = format!(
+            "--x\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\n\
+             wallet,amount\n{},0.5\n{},0.5\n\r\n--x--\r\n",
+            listed, unlisted
+        );
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=x")
+            .body(axum::body::Body::from(body))
+            .unwrap();
+        let multipart = Multipart::from_request(request, &state).await.unwrap();
+        let Err(err) =
+            bulk_airdrop(State(state.clone()), Caller(Actor::default()), multipart).await
+        else {
+            panic!("an upload with an unlisted wallet was accepted");
+        };
+        let (status, body) = message(err).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let text = body["error"]["message"].as_str().unwrap();
+        assert!(text.starts_with("Invalid upload: line 3: "), "{}", text);
+        let denied = failures(&state, bulk::JOB_KIND);
+        assert_eq!(denied.len(), 1);
+        assert_eq!(denied[0].params["wallet"], unlisted.to_string());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn transfers_and_watches_refuse_denylisted_wallets() {
+        let state = AppState::for_tests(Config {
+            das_url: Some(closed_url().await),
+            ..Config::default()
+        });
+        let denied = Pubkey::new_unique();
+        deny(&state, &denied).await;
+        let code = |err: ApiError| async move {
+            let (status, body) = message(err).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            body["error"]["code"].clone()
+        };
+
+        let Err(err) = build_swap(
+            State(state.clone()),
+            Caller(Actor::default()),
+            None,
+            Json(SwapBuildRequest {
+                quote: serde_json::json!({}),
+                user: denied.to_string(),
+                submit: false,
+            }),
+        )
+        .await
+        else {
+            panic!("a swap for a denylisted wallet was built");
+        };
+        assert_eq!(code(err).await, "wallet_denylisted");
+        assert_eq!(failures(&state, "swap").len(), 1);
+
+        let Err(err) = transfer_cnft(
+            State(state.clone()),
+            Caller(Actor::default()),
+            None,
+            Path("asset".to_string()),
+            Json(CnftTransferRequest {
+                new_owner: denied.to_string(),
+                submit: false,
+            }),
+        )
+        .await
+        else {
+            panic!("a transfer to a denylisted wallet was built");
+        };
+        assert_eq!(code(err).await, "wallet_denylisted");
+        assert_eq!(failures(&state, "cnft_transfer").len(), 1);
+
+        let alert = serde_json::from_value(serde_json::json!({
+            "wallet": denied.to_string(),
+            "below_sol": 1.0,
+            "target": "https://hooks.example.com/alert",
+        }))
+        .unwrap();
+        let Err(err) =
+            create_balance_alert(State(state.clone()), Caller(Actor::default()), Json(alert)).await
+        else {
+            panic!("a denylisted wallet was watched");
+        };
+        assert_eq!(code(err).await, "wallet_denylisted");
+        assert_eq!(failures(&state, "balance_alert_create").len(), 1);
+
+        let Err(err) = create_watchlist(
+            State(state.clone()),
+            Caller(Actor::default()),
+            principal(Role::Reader),
+            Json(WatchlistRequest {
+                name: "mixed".to_string(),
+                wallets: vec![Pubkey::new_unique().to_string(), denied.to_string()],
+            }),
+        )
+        .await
+        else {
+            panic!("a denylisted wallet was added to a watchlist");
+        };
+        assert_eq!(code(err).await, "wallet_denylisted");
+        assert_eq!(failures(&state, "watchlist_create").len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn approvals_rescreen_the_recipient_before_paying() {
+        let state = AppState::for_tests(Config::default());
+        let wallet = Pubkey::new_unique();
+        let approval = state
+            .approvals
+            .create(&wallet.to_string(), 1, Actor::system("api"), None, &[])
+            .unwrap();
+        deny(&state, &wallet).await;
+
+        let Err(err) = approve_airdrop(
+            State(state.clone()),
+            Caller(Actor::default()),
+            Path(approval.id.clone()),
+        )
+        .await
+        else {
+            panic!("an approval for a denylisted wallet was paid");
+        };
+        let (status, body) = message(err).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"]["code"], "wallet_denylisted");
+        let approval = state.approvals.get(&approval.id).unwrap();
+        assert_eq!(approval.status, ApprovalStatus::Rejected);
+        assert!(approval.signature.is_none());
+        assert_eq!(failures(&state, "airdrop_approve").len(), 1);
+    }
+
+    // Leaving the key off doesn't leave the quota behind: anonymous
+    // callers draw on one per IP.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn anonymous_airdrops_are_held_to_the_daily_quota() {
+        let state = AppState::for_tests(Config {
+            airdrop_daily_quota_sol: Some(1),
+            ..Config::default()
+        });
+        let airdrop = |ip: &str| {
+            get_airdrop(
+                State(state.clone()),
+                Caller(Actor {
+                    ip: Some(ip.to_string()),
+                    ..Actor::default()
+                }),
+                None,
+                Json(AirdropRequest {
+                    address: Pubkey::new_unique().to_string(),
+                    amount_sol: Some(1),
+                    ..AirdropRequest::default()
+                }),
+            )
+        };
+
+        let (status, _) = airdrop("203.0.113.7").await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let Err(err) = airdrop("203.0.113.7").await else {
+            panic!("an anonymous caller went over the daily quota");
+        };
+        let (status, body) = message(err).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["error"]["code"], "quota_exceeded");
+        let (status, _) = airdrop("198.51.100.1").await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let report = state.usage.report("ip:203.0.113.7", &state.config());
+        assert_eq!(report.quota.used_today_sol, 1);
+    }
+
+    #[tokio::test]
+    async fn program_account_pages_are_joined_into_one_array() {
+        let page = |accounts: &[&str]| Ok(accounts.iter().map(|a| a.to_string()).collect());
+        let pages = stream::iter(vec![page(&[]), page(&["1", "2"]), page(&[]), page(&["3"])]);
+        let body: Vec<String> = program_accounts_body(pages, ProgramAccountsFormat::JsonArray)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(body.concat(), "[1,2,3]");
+
+        let pages = stream::iter(vec![page(&[]), page(&["1"]), page(&["2"])]);
+        let body: Vec<String> = program_accounts_body(pages, ProgramAccountsFormat::Ndjson)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(body.concat(), "1\n2\n");
+
+        let empty = stream::iter(vec![page(&[])]);
+        let body: Vec<String> = program_accounts_body(empty, ProgramAccountsFormat::JsonArray)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(body.concat(), "[]");
+    }
+}
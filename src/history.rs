@@ -0,0 +1,323 @@
+use solana_client::{
+    rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient},
+    rpc_config::RpcTransactionConfig,
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_sdk::{
+    clock::Slot, commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature,
+};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, UiLoadedAddresses, UiTransactionEncoding,
+    UiTransactionTokenBalance,
+};
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{
+    rpc,
+    service::{self, rpc_error, ServiceError},
+};
+
+const SIGNATURE_PAGE_SIZE: usize = 1_000;
+// Caps the work a single lookup can cause on very busy addresses.
+const MAX_SIGNATURE_PAGES: usize = 20;
+
+pub enum Point {
+    Slot(Slot),
+    BlockTime(i64),
+}
+
+impl Point {
+    fn includes(&self, sig: &RpcConfirmedTransactionStatusWithSignature) -> bool {
+        match self {
+            Point::Slot(slot) => sig.slot <= *slot,
+            Point::BlockTime(time) => sig.block_time.is_some_and(|t| t <= *time),
+        }
+    }
+}
+
+pub enum BalanceSource {
+    // Post-balance of the last transaction at or before the point.
+    PostBalance { signature: String, slot: Slot },
+    // Pre-balance of the earliest transaction after the point.
+    PreBalance { signature: String, slot: Slot },
+    // The address has never appeared in a transaction.
+    NoHistory,
+}
+
+pub struct HistoricalBalance {
+    pub lamports: u64,
+    pub source: BalanceSource,
+}
+
+pub fn fetch_transaction(
+    client: &RpcClient,
+    signature: &str,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta, ServiceError> {
+    let signature = Signature::from_str(signature)
+        .map_err(|_| ServiceError::Invalid("Invalid transaction signature".to_string()))?;
+    client
+        .get_transaction_with_config(
+            &signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .map_err(rpc_error("Failed to fetch transaction"))
+}
+
+// Static keys followed by addresses loaded from lookup tables, matching the
+// order of the pre/post balance arrays in the transaction meta.
+pub fn account_keys(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Vec<Pubkey> {
+    let Some(decoded) = tx.transaction.transaction.decode() else {
+        return Vec::new();
+    };
+    let mut keys = decoded.message.static_account_keys().to_vec();
+
+    let loaded: Option<&UiLoadedAddresses> = tx
+        .transaction
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.loaded_addresses.as_ref().into());
+    if let Some(loaded) = loaded {
+        for address in loaded.writable.iter().chain(loaded.readonly.iter()) {
+            if let Ok(key) = Pubkey::from_str(address) {
+                keys.push(key);
+            }
+        }
+    }
+    keys
+}
+
+// (pre, post) lamports of `pubkey` in the transaction, if it was involved.
+pub fn balance_change(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    pubkey: &Pubkey,
+) -> Option<(u64, u64)> {
+    let meta = tx.transaction.meta.as_ref()?;
+    let index = account_keys(tx).iter().position(|k| k == pubkey)?;
+    Some((
+        *meta.pre_balances.get(index)?,
+        *meta.post_balances.get(index)?,
+    ))
+}
+
+// Raw token amounts per mint, summed over the accounts owned by `owner`.
+pub fn owned_token_amounts(
+    balances: Option<&Vec<UiTransactionTokenBalance>>,
+    owner: &str,
+) -> HashMap<String, (u8, u128)> {
+    let mut amounts: HashMap<String, (u8, u128)> = HashMap::new();
+    for balance in balances.into_iter().flatten() {
+        let owned = Option::<&String>::from(balance.owner.as_ref()).is_some_and(|o| o == owner);
+        if !owned {
+            continue;
+        }
+        let amount = balance.ui_token_amount.amount.parse::<u128>().unwrap_or(0);
+        let entry = amounts
+            .entry(balance.mint.clone())
+            .or_insert((balance.ui_token_amount.decimals, 0));
+        entry.1 += amount;
+    }
+    amounts
+}
+
+pub fn balance_at(
+    rpc_url: &str,
+    wallet: &str,
+    point: Point,
+) -> Result<HistoricalBalance, ServiceError> {
+    let pubkey = service::parse_wallet(wallet)?;
+    let client = rpc::client(rpc_url);
+
+    if let Point::Slot(slot) = point {
+        let current = client.get_slot().map_err(rpc_error("Failed to get slot"))?;
+        if slot > current {
+            return Err(ServiceError::Invalid(format!(
+                "Slot {} is in the future (current slot is {})",
+                slot, current
+            )));
+        }
+    }
+
+    let mut before = None;
+    let mut earliest_after: Option<RpcConfirmedTransactionStatusWithSignature> = None;
+    let mut exhausted = false;
+
+    for _ in 0..MAX_SIGNATURE_PAGES {
+        let page = client
+            .get_signatures_for_address_with_config(
+                &pubkey,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(SIGNATURE_PAGE_SIZE),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .map_err(rpc_error("Failed to get signatures"))?;
+
+        if let Some(found) = page.iter().find(|sig| point.includes(sig)) {
+            let tx = fetch_transaction(&client, &found.signature)?;
+            let (_, post) = balance_change(&tx, &pubkey).ok_or_else(|| ServiceError::Rpc {
+                action: "Failed to read transaction balances",
+                message: format!("{} has no balance entry for {}", found.signature, pubkey),
+            })?;
+            return Ok(HistoricalBalance {
+                lamports: post,
+                source: BalanceSource::PostBalance {
+                    signature: found.signature.clone(),
+                    slot: found.slot,
+                },
+            });
+        }
+
+        exhausted = page.len() < SIGNATURE_PAGE_SIZE;
+        if let Some(last) = page.last() {
+            before = Signature::from_str(&last.signature).ok();
+            earliest_after = Some(last.clone());
+        }
+        if exhausted {
+            break;
+        }
+    }
+
+    if !exhausted {
+        return Err(ServiceError::HistoryUnavailable(format!(
+            "{} has more than {} transactions after the requested point",
+            pubkey,
+            MAX_SIGNATURE_PAGES * SIGNATURE_PAGE_SIZE
+        )));
+    }
+
+    // Everything returned is newer than the point. That only proves the
+    // balance if the node's history actually reaches back that far.
+    let first = client
+        .get_first_available_block()
+        .map_err(rpc_error("Failed to get first available block"))?;
+    let covered = match point {
+        Point::Slot(slot) => first <= slot,
+        Point::BlockTime(time) => client
+            .get_block_time(first)
+            .map(|first_time| first_time <= time)
+            .unwrap_or(false),
+    };
+    if !covered {
+        return Err(ServiceError::HistoryUnavailable(format!(
+            "The configured RPC node only has history from slot {}; configure archival_rpc_url to look further back",
+            first
+        )));
+    }
+
+    match earliest_after {
+        Some(sig) => {
+            let tx = fetch_transaction(&client, &sig.signature)?;
+            let (pre, _) = balance_change(&tx, &pubkey).ok_or_else(|| ServiceError::Rpc {
+                action: "Failed to read transaction balances",
+                message: format!("{} has no balance entry for {}", sig.signature, pubkey),
+            })?;
+            Ok(HistoricalBalance {
+                lamports: pre,
+                source: BalanceSource::PreBalance {
+                    signature: sig.signature,
+                    slot: sig.slot,
+                },
+            })
+        }
+        None => Ok(HistoricalBalance {
+            lamports: 0,
+            source: BalanceSource::NoHistory,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    fn slot() -> Slot {
+        rpc::client("http://mock").get_slot().unwrap()
+    }
+
+    #[test]
+    fn balances_are_read_around_the_point() {
+        let chain = rpc::mock_for_tests();
+        let payer = Keypair::new();
+        let wallet = Pubkey::new_unique();
+        chain.set_balance(payer.pubkey(), 1_000_000);
+        let address = wallet.to_string();
+
+        let at = balance_at("http://mock", &address, Point::Slot(slot())).unwrap();
+        assert!(matches!(
+            (at.lamports, at.source),
+            (0, BalanceSource::NoHistory)
+        ));
+
+        let before_first = slot();
+        let first = service::transfer("http://mock", &payer, &wallet, 1_000, false).unwrap();
+        let between = slot();
+        service::transfer("http://mock", &payer, &wallet, 500, false).unwrap();
+
+        let at = balance_at("http://mock", &address, Point::Slot(before_first)).unwrap();
+        assert_eq!(at.lamports, 0);
+        assert!(
+            matches!(at.source, BalanceSource::PreBalance { signature, .. } if signature == first.to_string())
+        );
+        let at = balance_at("http://mock", &address, Point::Slot(between)).unwrap();
+        assert_eq!(at.lamports, 1_000);
+        assert!(
+            matches!(at.source, BalanceSource::PostBalance { signature, .. } if signature == first.to_string())
+        );
+        assert_eq!(
+            balance_at("http://mock", &address, Point::Slot(slot()))
+                .unwrap()
+                .lamports,
+            1_500
+        );
+        assert!(matches!(
+            balance_at("http://mock", &address, Point::Slot(slot() + 1_000)),
+            Err(ServiceError::Invalid(_))
+        ));
+
+        let client = rpc::client("http://mock");
+        let tx = fetch_transaction(&client, &first.to_string()).unwrap();
+        assert_eq!(balance_change(&tx, &wallet), Some((0, 1_000)));
+        assert_eq!(balance_change(&tx, &Pubkey::new_unique()), None);
+        assert!(matches!(
+            fetch_transaction(&client, "not-a-signature"),
+            Err(ServiceError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn token_amounts_are_summed_per_mint_for_the_owner() {
+        let balance = |mint: &str, owner: &str, amount: &str| -> UiTransactionTokenBalance {
+            serde_json::from_value(json!({
+                "accountIndex": 1,
+                "mint": mint,
+                "owner": owner,
+                "uiTokenAmount": {
+                    "uiAmount": null,
+                    "decimals": 6,
+                    "amount": amount,
+                    "uiAmountString": "0",
+                },
+            }))
+            .unwrap()
+        };
+        let balances = vec![
+            balance("usdc", "alice", "1500000"),
+            balance("usdc", "alice", "250000"),
+            balance("usdc", "bob", "7"),
+            balance("bonk", "alice", "340282366920938463463374607431768211455"),
+        ];
+        let amounts = owned_token_amounts(Some(&balances), "alice");
+        assert_eq!(amounts.len(), 2);
+        assert_eq!(amounts["usdc"], (6, 1_750_000));
+        assert_eq!(amounts["bonk"], (6, u128::MAX));
+        assert!(owned_token_amounts(None, "alice").is_empty());
+    }
+}
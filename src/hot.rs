@@ -0,0 +1,301 @@
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    config::Config,
+    decoders::Registry,
+    events::{Event, EventKind},
+    fanout::{self, Failures},
+    inspect::{self, DecodedAccount},
+    logging, rpc,
+    service::{rpc_error, ServiceError},
+    state::AppState,
+    tasks,
+};
+
+// The accounts of `hot_wallets` and `hot_mints`, loaded before the server
+// listens and kept warm by `keep_warm`, so balance and account lookups of
+// them are answered without a round trip to the RPC node.
+#[derive(Default)]
+pub struct HotCache {
+    // `None` for an address with no account (yet).
+    entries: RwLock<HashMap<Pubkey, (Instant, Option<DecodedAccount>)>>,
+}
+
+impl HotCache {
+    // The balance of a hot address, when it was refreshed recently enough;
+    // an address with no account has none.
+    pub fn balance(&self, config: &Config, address: &Pubkey) -> Option<u64> {
+        self.get(config, address, |account| {
+            account.map(|a| a.account.lamports).unwrap_or_default()
+        })
+    }
+
+    // The decoded account of a hot address, when it has one and it was
+    // refreshed recently enough.
+    pub fn account(&self, config: &Config, address: &Pubkey) -> Option<DecodedAccount> {
+        self.get(config, address, |account| account.cloned())
+            .flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    // An entry outlives one missed refresh, not two: past that the node is
+    // asked again rather than serving a balance that may be long gone.
+    fn get<T>(
+        &self,
+        config: &Config,
+        address: &Pubkey,
+        f: impl FnOnce(Option<&DecodedAccount>) -> T,
+    ) -> Option<T> {
+        let max_age = Duration::from_secs(config.hot_refresh_secs * 2);
+        let entries = self.entries.read().unwrap();
+        let (at, account) = entries.get(address)?;
+        (at.elapsed() < max_age).then(|| f(account.as_ref()))
+    }
+}
+
+// The configured hot wallets and mints, once each.
+fn addresses(config: &Config) -> Vec<Pubkey> {
+    let mut seen = HashSet::new();
+    config
+        .hot_wallets
+        .iter()
+        .chain(&config.hot_mints)
+        .filter_map(|a| Pubkey::from_str(a).ok())
+        .filter(|a| seen.insert(*a))
+        .collect()
+}
+
+// Blocking: one getMultipleAccounts call for a chunk of addresses, each
+// account decoded as /accounts/{address} would.
+fn load_chunk(
+    rpc_url: &str,
+    decoders: &Registry,
+    chunk: &[Pubkey],
+) -> Result<Vec<(Pubkey, Option<DecodedAccount>)>, ServiceError> {
+    let client = rpc::client(rpc_url);
+    let _span = tracing::info_span!("rpc.hot_accounts", %rpc_url, count = chunk.len()).entered();
+    let accounts = client
+        .get_multiple_accounts(chunk)
+        .map_err(rpc_error("Failed to get accounts"))?;
+    let found: Vec<_> = chunk
+        .iter()
+        .zip(&accounts)
+        .filter_map(|(address, account)| Some((*address, account.clone()?)))
+        .collect();
+    let mut decoded: HashMap<Pubkey, DecodedAccount> =
+        inspect::decode_accounts(rpc_url, decoders, found)
+            .into_iter()
+            .map(|account| (account.address, account))
+            .collect();
+    Ok(chunk
+        .iter()
+        .map(|address| (*address, decoded.remove(address)))
+        .collect())
+}
+
+// Loads every hot address, in chunks of MAX_ACCOUNTS_PER_CALL fanned out
+// within the configured limits, and drops entries of addresses no longer
+// configured. A chunk that fails keeps its previous entries, which age out
+// if it keeps failing.
+pub async fn refresh(state: &AppState) -> Failures {
+    let config = state.config();
+    let addresses = addresses(&config);
+    let chunks: Vec<Vec<Pubkey>> = addresses
+        .chunks(rpc::MAX_ACCOUNTS_PER_CALL)
+        .map(<[Pubkey]>::to_vec)
+        .collect();
+    let rpc_url = config.rpc_url.clone();
+    let decoders = state.decoders.clone();
+    let results = fanout::run(fanout::Limits::new(&config), chunks, move |chunk| {
+        load_chunk(&rpc_url, &decoders, &chunk)
+    })
+    .await;
+    let failures = fanout::failures(&results);
+
+    let at = Instant::now();
+    let mut entries = state.hot.entries.write().unwrap();
+    entries.retain(|address, _| addresses.contains(address));
+    for (address, account) in results.into_iter().flatten().flatten() {
+        entries.insert(address, (at, account));
+    }
+    failures
+}
+
+// A faucet transfer to, or a watcher seeing a new balance of, a hot
+// address makes its entry stale.
+fn touches(config: &Config, event: &Event) -> bool {
+    if !matches!(
+        event.kind,
+        EventKind::BalanceChanged | EventKind::FaucetConfirmed
+    ) {
+        return false;
+    }
+    let Some(wallet) = event.data.get("wallet").and_then(|w| w.as_str()) else {
+        return false;
+    };
+    config.hot_wallets.iter().any(|w| w == wallet)
+}
+
+// Refreshes the hot addresses every `hot_refresh_secs`, and right away when
+// an event shows one of them changed.
+pub async fn keep_warm(state: AppState) {
+    let mut events = state.events.subscribe();
+    loop {
+        let config = state.config();
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(config.hot_refresh_secs);
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => break,
+                event = events.recv() => match event {
+                    Ok(event) if touches(&config, &event) => break,
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(_)) => break,
+                    Err(RecvError::Closed) => return,
+                },
+            }
+        }
+        if config.hot_wallets.is_empty() && config.hot_mints.is_empty() {
+            tasks::succeeded();
+            continue;
+        }
+        let failures = refresh(&state).await;
+        if failures.is_empty() {
+            tasks::succeeded();
+        } else {
+            logging::warn(format_args!(
+                "Failed to refresh some hot accounts: {}",
+                failures
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn hot(wallets: &[Pubkey], mints: &[Pubkey]) -> Config {
+        Config {
+            rpc_url: "http://mock".to_string(),
+            hot_wallets: wallets.iter().map(Pubkey::to_string).collect(),
+            hot_mints: mints.iter().map(Pubkey::to_string).collect(),
+            ..Config::default()
+        }
+    }
+
+    fn event(kind: EventKind, data: serde_json::Value) -> Event {
+        Event {
+            id: 1,
+            kind,
+            timestamp_ms: 0,
+            data,
+        }
+    }
+
+    #[test]
+    fn addresses_are_listed_once() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut config = hot(&[a, b, a], &[b]);
+        config.hot_mints.push("not-an-address".to_string());
+        assert_eq!(addresses(&config), [a, b]);
+        assert!(addresses(&Config::default()).is_empty());
+    }
+
+    #[test]
+    fn only_changes_to_hot_wallets_touch_them() {
+        let wallet = Pubkey::new_unique();
+        let config = hot(&[wallet], &[]);
+        let data = json!({"wallet": wallet.to_string()});
+        assert!(touches(
+            &config,
+            &event(EventKind::BalanceChanged, data.clone())
+        ));
+        assert!(touches(
+            &config,
+            &event(EventKind::FaucetConfirmed, data.clone())
+        ));
+        let other = json!({"wallet": Pubkey::new_unique().to_string()});
+        assert!(!touches(&config, &event(EventKind::BalanceChanged, other)));
+        assert!(!touches(
+            &config,
+            &event(EventKind::BalanceChanged, json!({}))
+        ));
+        assert!(!touches(
+            &config,
+            &event(EventKind::ProgramTransaction, data)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn refresh_loads_and_drops_entries() {
+        let chain = rpc::mock_for_tests();
+        let (funded, empty) = (Pubkey::new_unique(), Pubkey::new_unique());
+        chain.set_balance(funded, 2_000_000_000);
+        let config = hot(&[funded], &[empty]);
+        let state = AppState::for_tests(config.clone());
+
+        assert!(refresh(&state).await.is_empty());
+        assert_eq!(state.hot.len(), 2);
+        assert_eq!(state.hot.balance(&config, &funded), Some(2_000_000_000));
+        let account = state.hot.account(&config, &funded).unwrap();
+        assert_eq!(account.account.lamports, 2_000_000_000);
+        // Known to have no account: a zero balance, not a cache miss.
+        assert_eq!(state.hot.balance(&config, &empty), Some(0));
+        assert!(state.hot.account(&config, &empty).is_none());
+        assert_eq!(state.hot.balance(&config, &Pubkey::new_unique()), None);
+
+        // Entries don't outlive twice the refresh interval.
+        let stale = Config {
+            hot_refresh_secs: 0,
+            ..config.clone()
+        };
+        assert_eq!(state.hot.balance(&stale, &funded), None);
+
+        // Addresses no longer configured are dropped.
+        let mut fewer = AppState::for_tests(hot(&[funded], &[]));
+        fewer.hot = state.hot.clone();
+        refresh(&fewer).await;
+        assert_eq!(state.hot.len(), 1);
+        assert!(state.hot.balance(&config, &empty).is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn events_refresh_right_away() {
+        let chain = rpc::mock_for_tests();
+        let wallet = Pubkey::new_unique();
+        chain.set_balance(wallet, 1_000);
+        let config = Config {
+            hot_refresh_secs: 3_600,
+            ..hot(&[wallet], &[])
+        };
+        let state = AppState::for_tests(config.clone());
+        refresh(&state).await;
+        tokio::spawn(keep_warm(state.clone()));
+        // Let it subscribe.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        chain.set_balance(wallet, 5_000);
+        state.events.publish(
+            EventKind::BalanceChanged,
+            json!({"wallet": wallet.to_string()}),
+        );
+        for _ in 0..100 {
+            if state.hot.balance(&config, &wallet) == Some(5_000) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("the hot wallet was not refreshed");
+    }
+}
@@ -0,0 +1,454 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    auth::Principal, clock::now_ms, handlers::error_response, logging, state::AppState,
+    store::Store,
+};
+
+const STORE_NAME: &str = "idempotency_keys";
+const HEADER: &str = "idempotency-key";
+const MAX_KEY_LEN: usize = 255;
+// Request bodies are hashed in full; fund routes take small JSON bodies.
+const MAX_BODY: usize = 1024 * 1024;
+// Completed keys beyond this count are dropped, oldest first.
+const MAX_KEYS: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: String,
+}
+
+impl IntoResponse for SavedResponse {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let mut res = (status, self.body).into_response();
+        if let Some(content_type) = self
+            .content_type
+            .and_then(|c| HeaderValue::from_str(&c).ok())
+        {
+            res.headers_mut().insert(header::CONTENT_TYPE, content_type);
+        }
+        res.headers_mut()
+            .insert("idempotent-replayed", HeaderValue::from_static("true"));
+        res
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Entry {
+    fingerprint: String,
+    created_ms: u64,
+    // None while the first request is still running.
+    response: Option<SavedResponse>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Claim {
+    // First use of the key; the request should run.
+    New,
+    Replay(SavedResponse),
+    InFlight,
+    // The key was used for a different request.
+    Mismatch,
+}
+
+// Responses to POSTs sent with an `Idempotency-Key`, keyed by caller and
+// key. Saved as JSON under "idempotency_keys" in the store
+// (`idempotency_keys_path` for files) once a response is known, so a retry
+// after a restart still replays instead of sending a second airdrop.
+pub struct IdempotencyStore {
+    store: Arc<dyn Store>,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl IdempotencyStore {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let mut entries: HashMap<String, Entry> = match store.read(STORE_NAME)? {
+            Some(text) => serde_json::from_str(&text).map_err(|e| {
+                format!(
+                    "invalid idempotency keys in {}: {}",
+                    store.location(STORE_NAME),
+                    e
+                )
+            })?,
+            None => HashMap::new(),
+        };
+        entries.retain(|_, e| e.response.is_some());
+        Ok(IdempotencyStore {
+            store,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    fn save(&self) {
+        if !self.store.persists(STORE_NAME) {
+            return;
+        }
+        let result = {
+            let entries = self.entries.lock().unwrap();
+            let done: HashMap<_, _> = entries
+                .iter()
+                .filter(|(_, e)| e.response.is_some())
+                .collect();
+            serde_json::to_string(&done)
+        }
+        .map_err(|e| e.to_string())
+        .and_then(|text| self.store.write(STORE_NAME, &text));
+        if let Err(e) = result {
+            logging::warn(format_args!("Failed to save idempotency keys: {}", e));
+        }
+    }
+
+    pub fn begin(&self, key: &str, fingerprint: &str, now_ms: u64, ttl_ms: u64) -> Claim {
+        let expired = |e: &Entry| now_ms.saturating_sub(e.created_ms) >= ttl_ms;
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(entry) if entry.response.is_some() && expired(entry) => {}
+            Some(entry) if entry.fingerprint != fingerprint => return Claim::Mismatch,
+            Some(Entry {
+                response: Some(saved),
+                ..
+            }) => return Claim::Replay(saved.clone()),
+            Some(_) => return Claim::InFlight,
+            None => {}
+        }
+        if entries.len() >= MAX_KEYS {
+            entries.retain(|_, e| e.response.is_none() || !expired(e));
+        }
+        if entries.len() >= MAX_KEYS {
+            let oldest = entries
+                .iter()
+                .filter(|(_, e)| e.response.is_some())
+                .min_by_key(|(_, e)| e.created_ms)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key.to_string(),
+            Entry {
+                fingerprint: fingerprint.to_string(),
+                created_ms: now_ms,
+                response: None,
+            },
+        );
+        Claim::New
+    }
+
+    // Records the response to replay for `key`, or with None forgets the
+    // key so the request can be retried.
+    pub fn finish(&self, key: &str, response: Option<SavedResponse>) {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            match response {
+                Some(response) => {
+                    if let Some(entry) = entries.get_mut(key) {
+                        entry.response = Some(response);
+                    }
+                }
+                None => {
+                    entries.remove(key);
+                }
+            }
+        }
+        self.save();
+    }
+}
+
+// Releases the key if the request is dropped before it finishes, e.g. when
+// the client disconnects.
+struct Pending {
+    store: Arc<IdempotencyStore>,
+    key: Option<String>,
+}
+
+impl Pending {
+    fn finish(mut self, response: Option<SavedResponse>) {
+        if let Some(key) = self.key.take() {
+            self.store.finish(&key, response);
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.store.finish(&key, None);
+        }
+    }
+}
+
+fn fingerprint(method: &Method, uri: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b" ");
+    hasher.update(uri);
+    hasher.update(b"\n");
+    hasher.update(body);
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// Layered inside the auth guard so keys are scoped to the principal
+// subject, else the client IP. Server errors aren't replayed; the key is
+// released so the caller can retry.
+pub async fn replay(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    if req.method() != Method::POST {
+        return next.run(req).await;
+    }
+    let Some(key) = req.headers().get(HEADER) else {
+        return next.run(req).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LEN => key.to_string(),
+        _ => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Idempotency-Key must be 1 to {} visible ASCII characters",
+                    MAX_KEY_LEN
+                ),
+            )
+            .into_response()
+        }
+    };
+    let caller = req
+        .extensions()
+        .get::<Principal>()
+        .and_then(|p| p.subject.clone())
+        .or_else(|| {
+            req.extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        })
+        .unwrap_or_default();
+    let scoped = format!("{} {}", caller, key);
+
+    let (parts, body) = req.into_parts();
+    let Ok(body) = to_bytes(body, MAX_BODY).await else {
+        return error_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Request body is too large for an Idempotency-Key",
+        )
+        .into_response();
+    };
+    let fingerprint = fingerprint(&parts.method, &parts.uri.to_string(), &body);
+    let ttl_ms = state.config().idempotency_ttl_secs.saturating_mul(1_000);
+    match state
+        .idempotency
+        .begin(&scoped, &fingerprint, now_ms(), ttl_ms)
+    {
+        Claim::New => {}
+        Claim::Replay(saved) => return saved.into_response(),
+        Claim::InFlight => {
+            return error_response(
+                StatusCode::CONFLICT,
+                "A request with this Idempotency-Key is still in progress",
+            )
+            .into_response()
+        }
+        Claim::Mismatch => {
+            return error_response(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Idempotency-Key was already used for a different request",
+            )
+            .into_response()
+        }
+    }
+    let pending = Pending {
+        store: state.idempotency.clone(),
+        key: Some(scoped),
+    };
+
+    let res = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if res.status().is_server_error() {
+        pending.finish(None);
+        return res;
+    }
+    let (parts, body) = res.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        pending.finish(None);
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to read the response body",
+        )
+        .into_response();
+    };
+    // Responses that aren't text are passed through but not replayed.
+    let saved = String::from_utf8(bytes.to_vec())
+        .ok()
+        .map(|body| SavedResponse {
+            status: parts.status.as_u16(),
+            content_type: parts
+                .headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+            body,
+        });
+    pending.finish(saved);
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    const TTL_MS: u64 = 60_000;
+
+    fn saved(body: &str) -> SavedResponse {
+        SavedResponse {
+            status: 200,
+            content_type: Some("application/json".to_string()),
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn repeated_key_replays_the_first_response() {
+        let keys = IdempotencyStore::load(Arc::new(MemoryStore::default())).unwrap();
+        assert_eq!(keys.begin("a k1", "f1", 0, TTL_MS), Claim::New);
+        assert_eq!(keys.begin("a k1", "f1", 1, TTL_MS), Claim::InFlight);
+        keys.finish("a k1", Some(saved("{}")));
+        assert_eq!(
+            keys.begin("a k1", "f1", 2, TTL_MS),
+            Claim::Replay(saved("{}"))
+        );
+        assert_eq!(keys.begin("a k1", "f2", 3, TTL_MS), Claim::Mismatch);
+        // Keys are scoped to the caller.
+        assert_eq!(keys.begin("b k1", "f2", 4, TTL_MS), Claim::New);
+    }
+
+    #[test]
+    fn released_and_expired_keys_can_be_reused() {
+        let keys = IdempotencyStore::load(Arc::new(MemoryStore::default())).unwrap();
+        assert_eq!(keys.begin("a k1", "f1", 0, TTL_MS), Claim::New);
+        keys.finish("a k1", None);
+        assert_eq!(keys.begin("a k1", "f2", 1, TTL_MS), Claim::New);
+        keys.finish("a k1", Some(saved("{}")));
+        assert_eq!(keys.begin("a k1", "f3", TTL_MS + 1, TTL_MS), Claim::New);
+    }
+
+    #[test]
+    fn completed_keys_survive_a_reload() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        let keys = IdempotencyStore::load(store.clone()).unwrap();
+        keys.begin("a done", "f1", 0, TTL_MS);
+        keys.finish("a done", Some(saved("{\"ok\":true}")));
+        keys.begin("a running", "f2", 0, TTL_MS);
+        keys.finish("a other", None);
+
+        let reloaded = IdempotencyStore::load(store).unwrap();
+        assert_eq!(
+            reloaded.begin("a done", "f1", 1, TTL_MS),
+            Claim::Replay(saved("{\"ok\":true}"))
+        );
+        // Nothing answered the request that was running, so it may run again.
+        assert_eq!(reloaded.begin("a running", "f2", 1, TTL_MS), Claim::New);
+    }
+
+    #[test]
+    fn dropped_request_releases_its_key() {
+        let keys = Arc::new(IdempotencyStore::load(Arc::new(MemoryStore::default())).unwrap());
+        keys.begin("a k1", "f1", 0, TTL_MS);
+        drop(Pending {
+            store: keys.clone(),
+            key: Some("a k1".to_string()),
+        });
+        assert_eq!(keys.begin("a k1", "f2", 1, TTL_MS), Claim::New);
+    }
+
+    #[tokio::test]
+    async fn retried_post_replays_without_running_again() {
+        use crate::config::Config;
+        use axum::{middleware, routing::post, Router};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let state = AppState::for_tests(Config::default());
+        let app = Router::new()
+            .route(
+                "/get_airdrop",
+                post({
+                    let runs = runs.clone();
+                    move || async move {
+                        let n = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                        format!("{{\"run\":{}}}", n)
+                    }
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(state.clone(), replay))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        });
+        let client = reqwest::Client::new();
+        let send = |key: &'static str, body: &'static str| {
+            client
+                .post(format!("http://{}/get_airdrop", addr))
+                .header(HEADER, key)
+                .body(body)
+                .send()
+        };
+
+        let first = send("k1", "{\"sol\":1}").await.unwrap();
+        assert!(first.headers().get("idempotent-replayed").is_none());
+        assert_eq!(first.text().await.unwrap(), "{\"run\":1}");
+
+        let again = send("k1", "{\"sol\":1}").await.unwrap();
+        assert_eq!(again.status(), 200);
+        assert_eq!(again.headers()["idempotent-replayed"], "true");
+        assert_eq!(again.text().await.unwrap(), "{\"run\":1}");
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        let other = send("k1", "{\"sol\":2}").await.unwrap();
+        assert_eq!(other.status(), 422);
+        let fresh = send("k2", "{\"sol\":2}").await.unwrap();
+        assert_eq!(fresh.text().await.unwrap(), "{\"run\":2}");
+    }
+
+    #[test]
+    fn fingerprint_covers_method_path_and_body() {
+        let base = fingerprint(&Method::POST, "/get_airdrop", b"{\"sol\":1}");
+        assert_eq!(
+            base,
+            fingerprint(&Method::POST, "/get_airdrop", b"{\"sol\":1}")
+        );
+        assert_ne!(
+            base,
+            fingerprint(&Method::POST, "/get_airdrop", b"{\"sol\":2}")
+        );
+        assert_ne!(base, fingerprint(&Method::POST, "/topups", b"{\"sol\":1}"));
+        assert_eq!(
+            fingerprint(&Method::POST, "", b""),
+            // sha256("POST \n")
+            "8d8fb6af9633ba9f902bd3d10daae86544670d86a5e74809ee0377d23803829c"
+        );
+    }
+}
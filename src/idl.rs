@@ -0,0 +1,777 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use solana_sdk::{hash, pubkey::Pubkey};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    decoders::{Context, Decoder},
+    layout::Reader,
+    store::Store,
+};
+
+pub use crate::api::{IdlSummary, IdlUploadQuery};
+
+const STORE_NAME: &str = "idls";
+
+// Borsh types as named in Anchor IDLs.
+#[derive(Clone, Debug)]
+pub enum Type {
+    Bool,
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    U128,
+    I128,
+    F32,
+    F64,
+    Pubkey,
+    String,
+    Bytes,
+    Vec(Box<Type>),
+    Option(Box<Type>),
+    Array(Box<Type>, usize),
+    Defined(String),
+}
+
+pub type Defined<'a> = dyn Fn(&str, &mut Reader) -> Result<Value, String> + 'a;
+
+#[derive(Clone, Debug)]
+pub enum Fields {
+    Named(Vec<(String, Type)>),
+    Tuple(Vec<Type>),
+    Unit,
+}
+
+#[derive(Clone, Debug)]
+enum TypeDef {
+    Struct(Fields),
+    Enum(Vec<(String, Fields)>),
+}
+
+// An account or event: a discriminator, then the struct's fields.
+struct Layout {
+    name: String,
+    discriminator: Vec<u8>,
+    fields: Fields,
+}
+
+struct Instruction {
+    name: String,
+    discriminator: Vec<u8>,
+    accounts: Vec<String>,
+    args: Vec<(String, Type)>,
+}
+
+// Decoder for an Anchor program, built from its IDL. Both the legacy
+// (< 0.30) and current IDL formats are understood.
+pub struct Idl {
+    name: String,
+    accounts: Vec<Layout>,
+    instructions: Vec<Instruction>,
+    events: Vec<Layout>,
+    types: HashMap<String, TypeDef>,
+}
+
+// Prefixes the instruction data of events emitted with Anchor's
+// `emit_cpi!`, which the program sends to itself as an inner instruction.
+const EVENT_IX_TAG: [u8; 8] = 0x1d9a_cb51_2ea5_45e4_u64.to_le_bytes();
+
+impl Type {
+    pub fn parse(value: &Value) -> Result<Type, String> {
+        if let Some(name) = value.as_str() {
+            return Ok(match name {
+                "bool" => Type::Bool,
+                "u8" => Type::U8,
+                "i8" => Type::I8,
+                "u16" => Type::U16,
+                "i16" => Type::I16,
+                "u32" => Type::U32,
+                "i32" => Type::I32,
+                "u64" => Type::U64,
+                "i64" => Type::I64,
+                "u128" => Type::U128,
+                "i128" => Type::I128,
+                "f32" => Type::F32,
+                "f64" => Type::F64,
+                "publicKey" | "pubkey" => Type::Pubkey,
+                "string" => Type::String,
+                "bytes" => Type::Bytes,
+                // Shorthand accepted in ad-hoc schemas, e.g. "vec<pubkey>".
+                other => match other.strip_suffix('>').and_then(|t| t.split_once('<')) {
+                    Some(("vec", inner)) => Type::Vec(Box::new(Type::parse(&json!(inner))?)),
+                    Some(("option", inner)) => Type::Option(Box::new(Type::parse(&json!(inner))?)),
+                    _ => return Err(format!("unsupported type '{}'", other)),
+                },
+            });
+        }
+        if let Some(inner) = value.get("vec") {
+            return Ok(Type::Vec(Box::new(Type::parse(inner)?)));
+        }
+        if let Some(inner) = value.get("option") {
+            return Ok(Type::Option(Box::new(Type::parse(inner)?)));
+        }
+        if let Some([inner, len]) = value
+            .get("array")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+        {
+            let len = len
+                .as_u64()
+                .ok_or_else(|| format!("unsupported array length {}", len))?;
+            return Ok(Type::Array(Box::new(Type::parse(inner)?), len as usize));
+        }
+        match value.get("defined") {
+            Some(Value::String(name)) => Ok(Type::Defined(name.clone())),
+            Some(defined) => match defined.get("name").and_then(Value::as_str) {
+                Some(name) => Ok(Type::Defined(name.to_string())),
+                None => Err(format!("unsupported type {}", value)),
+            },
+            None => Err(format!("unsupported type {}", value)),
+        }
+    }
+
+    // `defined` decodes named types, which only an IDL can resolve.
+    pub fn decode(&self, r: &mut Reader, defined: &Defined) -> Result<Value, String> {
+        Ok(match self {
+            Type::Bool => json!(r.bool()?),
+            Type::U8 => json!(r.u8()?),
+            Type::I8 => json!(r.i8()?),
+            Type::U16 => json!(r.u16()?),
+            Type::I16 => json!(r.i16()?),
+            Type::U32 => json!(r.u32()?),
+            Type::I32 => json!(r.i32()?),
+            Type::U64 => json!(r.u64()?),
+            Type::I64 => json!(r.i64()?),
+            // Beyond what JSON numbers hold exactly.
+            Type::U128 => json!(r.u128()?.to_string()),
+            Type::I128 => json!(r.i128()?.to_string()),
+            Type::F32 => json!(r.f32()?),
+            Type::F64 => json!(r.f64()?),
+            Type::Pubkey => json!(r.pubkey()?.to_string()),
+            Type::String => json!(r.string()?),
+            Type::Bytes => {
+                let len = r.u32()? as usize;
+                json!(r.take(len)?)
+            }
+            Type::Vec(inner) => {
+                let len = r.u32()? as usize;
+                // Each element takes at least a byte; refuse lengths the
+                // data can't hold before allocating for them.
+                if len > r.remaining() {
+                    return Err("account data is truncated".to_string());
+                }
+                Value::Array(
+                    (0..len)
+                        .map(|_| inner.decode(r, defined))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            Type::Option(inner) => r
+                .option(|r| inner.decode(r, defined))?
+                .unwrap_or(Value::Null),
+            Type::Array(inner, len) => Value::Array(
+                (0..*len)
+                    .map(|_| inner.decode(r, defined))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Type::Defined(name) => defined(name, r)?,
+        })
+    }
+}
+
+impl Fields {
+    fn parse(fields: Option<&Value>) -> Result<Fields, String> {
+        let Some(fields) = fields.and_then(Value::as_array) else {
+            return Ok(Fields::Unit);
+        };
+        if fields.iter().all(|f| f.get("name").is_some()) {
+            fields
+                .iter()
+                .map(named_field)
+                .collect::<Result<_, _>>()
+                .map(Fields::Named)
+        } else {
+            fields
+                .iter()
+                .map(Type::parse)
+                .collect::<Result<_, _>>()
+                .map(Fields::Tuple)
+        }
+    }
+}
+
+fn named_field(field: &Value) -> Result<(String, Type), String> {
+    let name = field
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or("field without a name")?;
+    let ty = field
+        .get("type")
+        .ok_or_else(|| format!("field '{}' has no type", name))?;
+    let ty = Type::parse(ty).map_err(|e| format!("field '{}': {}", name, e))?;
+    Ok((name.to_string(), ty))
+}
+
+// Decodes `data` against a field list in IDL syntax ([{"name", "type"}]),
+// for programs that don't publish an IDL. Returns the fields and the number
+// of bytes they took.
+pub fn decode_schema(schema: &[Value], data: &[u8]) -> Result<(Value, usize), String> {
+    let mut r = Reader::new(data);
+    let mut fields = Map::new();
+    for field in schema {
+        let (name, ty) = named_field(field)?;
+        let value = ty
+            .decode(&mut r, &|defined, _| {
+                Err(format!("named type '{}' needs an IDL", defined))
+            })
+            .map_err(|e| format!("field '{}': {}", name, e))?;
+        fields.insert(name, value);
+    }
+    Ok((Value::Object(fields), data.len() - r.remaining()))
+}
+
+fn type_def(ty: &Value) -> Result<TypeDef, String> {
+    match ty.get("kind").and_then(Value::as_str) {
+        Some("struct") => Ok(TypeDef::Struct(Fields::parse(ty.get("fields"))?)),
+        Some("enum") => ty
+            .get("variants")
+            .and_then(Value::as_array)
+            .ok_or("enum without variants")?
+            .iter()
+            .map(|v| {
+                let name = v
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .ok_or("variant without a name")?;
+                Ok((name.to_string(), Fields::parse(v.get("fields"))?))
+            })
+            .collect::<Result<_, String>>()
+            .map(TypeDef::Enum),
+        kind => Err(format!("unsupported type kind {:?}", kind)),
+    }
+}
+
+fn discriminator(value: Option<&Value>, namespace: &str, name: &str) -> Vec<u8> {
+    match value.and_then(Value::as_array) {
+        Some(bytes) => bytes
+            .iter()
+            .filter_map(|b| b.as_u64().map(|b| b as u8))
+            .collect(),
+        None => hash::hash(format!("{}:{}", namespace, name).as_bytes()).to_bytes()[..8].to_vec(),
+    }
+}
+
+// Legacy IDLs name instructions in camelCase, while their discriminators
+// hash the Rust (snake_case) name.
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// Account names in nested account groups are flattened in order.
+fn account_names(accounts: &[Value], out: &mut Vec<String>) {
+    for account in accounts {
+        match account.get("accounts").and_then(Value::as_array) {
+            Some(group) => account_names(group, out),
+            None => out.push(
+                account
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+impl Idl {
+    // The IDL's program id, if it records one.
+    pub fn parse(idl: &Value) -> Result<(Option<Pubkey>, Idl), String> {
+        let address = idl
+            .get("address")
+            .or_else(|| idl.pointer("/metadata/address"))
+            .and_then(Value::as_str)
+            .map(|a| a.parse().map_err(|_| format!("invalid address '{}'", a)))
+            .transpose()?;
+        let name = idl
+            .get("name")
+            .or_else(|| idl.pointer("/metadata/name"))
+            .and_then(Value::as_str)
+            .unwrap_or("anchor")
+            .to_string();
+        let list = |key| {
+            idl.get(key)
+                .and_then(Value::as_array)
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+        };
+
+        let mut types = HashMap::new();
+        for ty in list("types") {
+            let name = ty.get("name").and_then(Value::as_str).unwrap_or_default();
+            let def = ty
+                .get("type")
+                .ok_or_else(|| format!("type '{}' has no body", name))?;
+            types.insert(
+                name.to_string(),
+                type_def(def).map_err(|e| format!("type '{}': {}", name, e))?,
+            );
+        }
+
+        let layouts = |key, namespace| -> Result<Vec<Layout>, String> {
+            let mut layouts = Vec::new();
+            for layout in list(key) {
+                let name = layout
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| format!("{} without a name", namespace))?;
+                // Current IDLs list layouts under `types`; legacy events
+                // carry their own `fields`.
+                let fields = match layout.get("type").map(type_def).transpose()? {
+                    Some(TypeDef::Struct(fields)) => fields,
+                    Some(TypeDef::Enum(_)) => {
+                        return Err(format!("{} '{}' is not a struct", namespace, name));
+                    }
+                    None => match (layout.get("fields"), types.get(name)) {
+                        (Some(fields), _) => Fields::parse(Some(fields))?,
+                        (None, Some(TypeDef::Struct(fields))) => fields.clone(),
+                        _ => return Err(format!("{} '{}' has no struct type", namespace, name)),
+                    },
+                };
+                layouts.push(Layout {
+                    name: name.to_string(),
+                    discriminator: discriminator(layout.get("discriminator"), namespace, name),
+                    fields,
+                });
+            }
+            Ok(layouts)
+        };
+        let accounts = layouts("accounts", "account")?;
+        let events = layouts("events", "event")?;
+
+        let mut instructions = Vec::new();
+        for ix in list("instructions") {
+            let name = ix
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or("instruction without a name")?;
+            let mut names = Vec::new();
+            account_names(
+                ix.get("accounts")
+                    .and_then(Value::as_array)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default(),
+                &mut names,
+            );
+            let args = match Fields::parse(ix.get("args"))? {
+                Fields::Named(args) => args,
+                Fields::Unit => Vec::new(),
+                Fields::Tuple(_) => return Err(format!("instruction '{}' has unnamed args", name)),
+            };
+            instructions.push(Instruction {
+                name: name.to_string(),
+                discriminator: discriminator(ix.get("discriminator"), "global", &snake_case(name)),
+                accounts: names,
+                args,
+            });
+        }
+
+        Ok((
+            address,
+            Idl {
+                name,
+                accounts,
+                instructions,
+                events,
+                types,
+            },
+        ))
+    }
+
+    // What /idl reports back about an uploaded IDL.
+    pub fn summary(&self, program_id: &Pubkey) -> IdlSummary {
+        let names = |layouts: &[Layout]| layouts.iter().map(|l| l.name.clone()).collect();
+        IdlSummary {
+            program_id: program_id.to_string(),
+            name: self.name.clone(),
+            instructions: self.instructions.iter().map(|ix| ix.name.clone()).collect(),
+            accounts: names(&self.accounts),
+            events: names(&self.events),
+        }
+    }
+
+    fn decode_layout(&self, layouts: &[Layout], data: &[u8], what: &str) -> Result<Value, String> {
+        let layout = layouts
+            .iter()
+            .find(|l| data.starts_with(&l.discriminator))
+            .ok_or_else(|| format!("no {} type in the IDL matches this data", what))?;
+        let mut r = Reader::new(&data[layout.discriminator.len()..]);
+        Ok(json!({
+            "type": layout.name,
+            "info": self.decode_fields(&layout.fields, &mut r)?,
+        }))
+    }
+
+    fn decode(&self, ty: &Type, r: &mut Reader) -> Result<Value, String> {
+        ty.decode(r, &|name, r| self.decode_defined(name, r))
+    }
+
+    fn decode_defined(&self, name: &str, r: &mut Reader) -> Result<Value, String> {
+        match self.types.get(name) {
+            Some(TypeDef::Struct(fields)) => self.decode_fields(fields, r),
+            Some(TypeDef::Enum(variants)) => {
+                let tag = r.u8()? as usize;
+                let (variant, fields) = variants
+                    .get(tag)
+                    .ok_or_else(|| format!("invalid {} variant {}", name, tag))?;
+                Ok(match fields {
+                    Fields::Unit => json!(variant),
+                    fields => json!({ variant: self.decode_fields(fields, r)? }),
+                })
+            }
+            None => Err(format!("unknown type '{}'", name)),
+        }
+    }
+
+    fn decode_fields(&self, fields: &Fields, r: &mut Reader) -> Result<Value, String> {
+        Ok(match fields {
+            Fields::Named(fields) => {
+                let mut map = Map::new();
+                for (name, ty) in fields {
+                    map.insert(name.clone(), self.decode(ty, r)?);
+                }
+                Value::Object(map)
+            }
+            Fields::Tuple(types) => Value::Array(
+                types
+                    .iter()
+                    .map(|ty| self.decode(ty, r))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Fields::Unit => Value::Null,
+        })
+    }
+}
+
+impl Decoder for Idl {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn decode_account(
+        &self,
+        _ctx: &Context,
+        _address: &Pubkey,
+        data: &[u8],
+    ) -> Result<Value, String> {
+        self.decode_layout(&self.accounts, data, "account")
+    }
+
+    fn decode_instruction(
+        &self,
+        accounts: &[Pubkey],
+        data: &[u8],
+    ) -> Option<Result<Value, String>> {
+        if let Some(event) = data.strip_prefix(&EVENT_IX_TAG) {
+            return self.decode_event(event);
+        }
+        let Some(ix) = self
+            .instructions
+            .iter()
+            .find(|ix| data.starts_with(&ix.discriminator))
+        else {
+            return Some(Err(
+                "no instruction in the IDL matches this data".to_string()
+            ));
+        };
+        let mut r = Reader::new(&data[ix.discriminator.len()..]);
+        let mut args = Map::new();
+        for (name, ty) in &ix.args {
+            match self.decode(ty, &mut r) {
+                Ok(value) => args.insert(name.clone(), value),
+                Err(e) => return Some(Err(format!("arg '{}': {}", name, e))),
+            };
+        }
+        let named: Map<String, Value> = ix
+            .accounts
+            .iter()
+            .zip(accounts)
+            .map(|(name, key)| (name.clone(), json!(key.to_string())))
+            .collect();
+        Some(Ok(json!({
+            "type": ix.name,
+            "info": { "args": args, "accounts": named },
+        })))
+    }
+
+    fn decode_event(&self, data: &[u8]) -> Option<Result<Value, String>> {
+        if self.events.is_empty() {
+            return None;
+        }
+        Some(self.decode_layout(&self.events, data, "event"))
+    }
+
+    fn event_names(&self) -> Vec<String> {
+        self.events.iter().map(|e| e.name.clone()).collect()
+    }
+}
+
+// Every `*.json` IDL in `dir`, keyed by program id. IDLs that don't record
+// their address are keyed by the file name, e.g. `<program id>.json`.
+pub fn load_dir(dir: &Path) -> Result<Vec<(Pubkey, Idl)>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("failed to read IDL dir '{}': {}", dir.display(), e))?;
+    let mut idls = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let invalid = |e: String| format!("invalid IDL '{}': {}", path.display(), e);
+        let contents = fs::read_to_string(&path).map_err(|e| invalid(e.to_string()))?;
+        let value: Value = serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+        let (address, idl) = Idl::parse(&value).map_err(invalid)?;
+        let address = match address {
+            Some(address) => address,
+            None => path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| invalid("no program address in the IDL or file name".to_string()))?,
+        };
+        idls.push((address, idl));
+    }
+    Ok(idls)
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedIdl {
+    program_id: String,
+    idl: Value,
+}
+
+// IDLs uploaded through /idl, saved as JSON under "idls" in the store
+// (`idls_path` for files) so they are decoded with again after a restart.
+pub struct IdlStore {
+    store: Arc<dyn Store>,
+    saved: Mutex<BTreeMap<Pubkey, Value>>,
+}
+
+impl IdlStore {
+    // With the IDLs saved so far, to register.
+    pub fn load(store: Arc<dyn Store>) -> Result<(Self, Vec<(Pubkey, Idl)>), String> {
+        let mut saved = BTreeMap::new();
+        let mut idls = Vec::new();
+        if let Some(text) = store.read(STORE_NAME)? {
+            let invalid =
+                |e: String| format!("invalid IDLs in {}: {}", store.location(STORE_NAME), e);
+            let list: Vec<SavedIdl> =
+                serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+            for entry in list {
+                let program_id = Pubkey::from_str(&entry.program_id)
+                    .map_err(|_| invalid(format!("invalid program id '{}'", entry.program_id)))?;
+                let (_, idl) = Idl::parse(&entry.idl).map_err(invalid)?;
+                idls.push((program_id, idl));
+                saved.insert(program_id, entry.idl);
+            }
+        }
+        let idls_store = IdlStore {
+            store,
+            saved: Mutex::new(saved),
+        };
+        Ok((idls_store, idls))
+    }
+
+    // Replaces any IDL saved for `program_id`.
+    pub fn save(&self, program_id: Pubkey, idl: Value) -> Result<(), String> {
+        let mut saved = self.saved.lock().unwrap();
+        let previous = saved.insert(program_id, idl);
+        let list: Vec<SavedIdl> = saved
+            .iter()
+            .map(|(program_id, idl)| SavedIdl {
+                program_id: program_id.to_string(),
+                idl: idl.clone(),
+            })
+            .collect();
+        let result = serde_json::to_string(&list)
+            .map_err(|e| e.to_string())
+            .and_then(|text| self.store.write(STORE_NAME, &text));
+        if result.is_err() {
+            match previous {
+                Some(previous) => saved.insert(program_id, previous),
+                None => saved.remove(&program_id),
+            };
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use solana_rpc_client::rpc_client::RpcClient;
+
+    const DEPOSIT: [u8; 8] = [242, 35, 198, 137, 82, 225, 242, 182];
+    const DEPOSITED: [u8; 8] = [111, 141, 26, 45, 161, 35, 100, 57];
+    const VAULT: [u8; 8] = [211, 8, 232, 43, 2, 152, 117, 119];
+
+    // In the legacy (< 0.30) format, with hashed discriminators.
+    fn legacy(program_id: &Pubkey) -> Value {
+        json!({
+            "name": "vault",
+            "metadata": { "address": program_id.to_string() },
+            "instructions": [{
+                "name": "deposit",
+                "accounts": [{ "name": "vault" }, { "name": "payer" }],
+                "args": [
+                    { "name": "amount", "type": "u64" },
+                    { "name": "memo", "type": { "option": "string" } },
+                ],
+            }],
+            "accounts": [{
+                "name": "Vault",
+                "type": { "kind": "struct", "fields": [
+                    { "name": "owner", "type": "publicKey" },
+                    { "name": "total", "type": "u64" },
+                ] },
+            }],
+            "events": [{
+                "name": "Deposited",
+                "fields": [
+                    { "name": "amount", "type": "u64", "index": false },
+                    { "name": "side", "type": { "defined": "Side" }, "index": false },
+                ],
+            }],
+            "types": [{
+                "name": "Side",
+                "type": { "kind": "enum", "variants": [{ "name": "Bid" }, { "name": "Ask" }] },
+            }],
+        })
+    }
+
+    fn deposited(amount: u64, side: u8) -> Vec<u8> {
+        let mut data = DEPOSITED.to_vec();
+        data.extend(amount.to_le_bytes());
+        data.push(side);
+        data
+    }
+
+    #[test]
+    fn discriminators_hash_the_namespace_and_rust_name() {
+        assert_eq!(
+            discriminator(None, "global", &snake_case("initialize")),
+            [175, 175, 109, 31, 13, 152, 155, 237]
+        );
+        assert_eq!(discriminator(None, "event", "Deposited"), DEPOSITED);
+        assert_eq!(
+            discriminator(Some(&json!([1, 2, 3])), "event", "X"),
+            [1, 2, 3]
+        );
+        assert_eq!(snake_case("closeAccountV2"), "close_account_v2");
+    }
+
+    #[test]
+    fn legacy_idls_decode_instructions_accounts_and_events() {
+        let program_id = Pubkey::new_unique();
+        let (address, idl) = Idl::parse(&legacy(&program_id)).unwrap();
+        assert_eq!(address, Some(program_id));
+
+        let (vault, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = DEPOSIT.to_vec();
+        data.extend(500u64.to_le_bytes());
+        data.extend([1, 2, 0, 0, 0, b'h', b'i']);
+        assert_eq!(
+            idl.decode_instruction(&[vault, payer], &data)
+                .unwrap()
+                .unwrap(),
+            json!({
+                "type": "deposit",
+                "info": {
+                    "args": { "amount": 500, "memo": "hi" },
+                    "accounts": { "vault": vault.to_string(), "payer": payer.to_string() },
+                },
+            })
+        );
+
+        let client = RpcClient::new("http://127.0.0.1:1".to_string());
+        let owner = Pubkey::new_unique();
+        let mut data = VAULT.to_vec();
+        data.extend(owner.to_bytes());
+        data.extend(9u64.to_le_bytes());
+        assert_eq!(
+            idl.decode_account(&Context::new(&client), &vault, &data)
+                .unwrap(),
+            json!({ "type": "Vault", "info": { "owner": owner.to_string(), "total": 9 } })
+        );
+
+        let event = json!({ "type": "Deposited", "info": { "amount": 7, "side": "Ask" } });
+        assert_eq!(idl.decode_event(&deposited(7, 1)).unwrap().unwrap(), event);
+        // Emitted with emit_cpi!, as an instruction to itself.
+        let mut cpi = EVENT_IX_TAG.to_vec();
+        cpi.extend(deposited(7, 1));
+        assert_eq!(idl.decode_instruction(&[], &cpi).unwrap().unwrap(), event);
+        assert!(idl.decode_event(&[0; 16]).unwrap().is_err());
+    }
+
+    #[test]
+    fn current_idls_take_event_layouts_from_types() {
+        let idl = json!({
+            "address": Pubkey::new_unique().to_string(),
+            "metadata": { "name": "vault" },
+            "instructions": [],
+            "events": [{ "name": "Deposited", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8] }],
+            "types": [{
+                "name": "Deposited",
+                "type": { "kind": "struct", "fields": [{ "name": "amount", "type": "u64" }] },
+            }],
+        });
+        let (_, idl) = Idl::parse(&idl).unwrap();
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        data.extend(3u64.to_le_bytes());
+        assert_eq!(
+            idl.decode_event(&data).unwrap().unwrap(),
+            json!({ "type": "Deposited", "info": { "amount": 3 } })
+        );
+        let summary = idl.summary(&Pubkey::default());
+        assert_eq!(summary.name, "vault");
+        assert_eq!(summary.events, ["Deposited"]);
+        assert!(summary.instructions.is_empty());
+    }
+
+    #[test]
+    fn uploaded_idls_are_loaded_again() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        let program_id = Pubkey::new_unique();
+        let (idls, loaded) = IdlStore::load(store.clone()).unwrap();
+        assert!(loaded.is_empty());
+        idls.save(program_id, legacy(&program_id)).unwrap();
+
+        let (_, loaded) = IdlStore::load(store).unwrap();
+        assert_eq!(loaded.len(), 1);
+        let (id, idl) = &loaded[0];
+        assert_eq!(*id, program_id);
+        assert_eq!(idl.summary(id).instructions, ["deposit"]);
+    }
+}
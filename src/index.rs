@@ -0,0 +1,530 @@
+use futures_util::{stream::select_all, StreamExt};
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+};
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::{
+    collections::{BTreeSet, HashMap},
+    str::FromStr,
+    sync::RwLock,
+    time::Duration,
+};
+
+use crate::{
+    clock::now_ms,
+    logging, rpc,
+    service::{rpc_error, ServiceError},
+    state::AppState,
+    tasks,
+};
+
+pub use crate::api::{IndexStatus, IndexedAccount};
+
+// The SPL token account layout: the mint, then the owner.
+const DEFAULT_OWNER_OFFSET: usize = 32;
+const OWNER_LENGTH: usize = 32;
+const MAX_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(60);
+// How often the subscriptions check whether `index_programs` changed.
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// An `index_programs` entry: `program` or `program:offset`.
+pub fn parse_spec(spec: &str) -> Result<(Pubkey, usize), String> {
+    let (program, offset) = match spec.split_once(':') {
+        Some((program, offset)) => {
+            let offset = offset
+                .parse()
+                .map_err(|_| format!("invalid owner offset in '{}'", spec))?;
+            (program, offset)
+        }
+        None => (spec, DEFAULT_OWNER_OFFSET),
+    };
+    let program =
+        Pubkey::from_str(program).map_err(|_| format!("invalid program id in '{}'", spec))?;
+    Ok((program, offset))
+}
+
+fn configured(specs: &[String]) -> Vec<(Pubkey, usize)> {
+    specs.iter().filter_map(|s| parse_spec(s).ok()).collect()
+}
+
+// `rpc_url` with its http(s) scheme swapped for ws(s).
+pub fn ws_url(rpc_url: &str) -> String {
+    if let Some(rest) = rpc_url.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = rpc_url.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        rpc_url.to_string()
+    }
+}
+
+struct Entry {
+    owner: Pubkey,
+    lamports: u64,
+    // The slot this state was seen at: a snapshot's, or a notification's.
+    slot: u64,
+}
+
+struct ProgramIndex {
+    owner_offset: usize,
+    accounts: HashMap<Pubkey, Entry>,
+    by_owner: HashMap<Pubkey, BTreeSet<Pubkey>>,
+    reconciled_at_ms: Option<u64>,
+    // A subscription is delivering updates.
+    live: bool,
+}
+
+impl ProgramIndex {
+    fn new(owner_offset: usize) -> Self {
+        ProgramIndex {
+            owner_offset,
+            accounts: HashMap::new(),
+            by_owner: HashMap::new(),
+            reconciled_at_ms: None,
+            live: false,
+        }
+    }
+
+    // The owner from the slice of account data the RPC node sent back.
+    fn owner(data: &[u8]) -> Option<Pubkey> {
+        let bytes: [u8; OWNER_LENGTH] = data.get(..OWNER_LENGTH)?.try_into().ok()?;
+        Some(Pubkey::new_from_array(bytes))
+    }
+
+    fn remove(&mut self, address: &Pubkey) {
+        if let Some(entry) = self.accounts.remove(address) {
+            if let Some(owned) = self.by_owner.get_mut(&entry.owner) {
+                owned.remove(address);
+                if owned.is_empty() {
+                    self.by_owner.remove(&entry.owner);
+                }
+            }
+        }
+    }
+
+    fn insert(&mut self, address: Pubkey, entry: Entry) {
+        self.remove(&address);
+        self.by_owner
+            .entry(entry.owner)
+            .or_default()
+            .insert(address);
+        self.accounts.insert(address, entry);
+    }
+
+    // A change to one account, unless the index already has a newer state
+    // of it. Closed accounts, and those too short to hold an owner, leave.
+    fn update(&mut self, address: Pubkey, account: &Account, slot: u64) {
+        if self.accounts.get(&address).is_some_and(|e| e.slot > slot) {
+            return;
+        }
+        match Self::owner(&account.data).filter(|_| account.lamports > 0) {
+            Some(owner) => self.insert(
+                address,
+                Entry {
+                    owner,
+                    lamports: account.lamports,
+                    slot,
+                },
+            ),
+            None => self.remove(&address),
+        }
+    }
+
+    // Replaces the index with a getProgramAccounts snapshot taken at or
+    // after `slot`, keeping the accounts notifications have since moved on.
+    fn rebuild(&mut self, slot: u64, accounts: Vec<(Pubkey, Account)>) {
+        let newer: Vec<(Pubkey, Entry)> = self
+            .accounts
+            .drain()
+            .filter(|(_, entry)| entry.slot > slot)
+            .collect();
+        self.by_owner.clear();
+        for (address, account) in accounts {
+            self.update(address, &account, slot);
+        }
+        for (address, entry) in newer {
+            self.insert(address, entry);
+        }
+        self.reconciled_at_ms = Some(now_ms());
+    }
+}
+
+pub struct OwnedAccounts {
+    pub accounts: Vec<IndexedAccount>,
+    pub reconciled_at_ms: u64,
+    pub live: bool,
+}
+
+pub enum Lookup {
+    Found(OwnedAccounts),
+    NotIndexed,
+    // Configured, but not reconciled yet.
+    Building,
+}
+
+// Accounts of each of `index_programs`, by the owner read from their data,
+// so owner lookups don't need a getProgramAccounts call each. Rebuilt from
+// getProgramAccounts every `index_reconcile_secs` and kept current between
+// rebuilds by websocket program subscriptions.
+#[derive(Default)]
+pub struct AccountIndex {
+    programs: RwLock<HashMap<Pubkey, ProgramIndex>>,
+}
+
+impl AccountIndex {
+    // Brings the indexed programs in line with the config: new ones start
+    // empty, removed ones are dropped, a changed offset starts over.
+    fn sync(&self, specs: &[(Pubkey, usize)]) {
+        let mut programs = self.programs.write().unwrap();
+        programs.retain(|program, index| {
+            specs
+                .iter()
+                .any(|(p, offset)| p == program && *offset == index.owner_offset)
+        });
+        for (program, offset) in specs {
+            programs
+                .entry(*program)
+                .or_insert_with(|| ProgramIndex::new(*offset));
+        }
+    }
+
+    fn set_live(&self, specs: &[(Pubkey, usize)], live: bool) {
+        let mut programs = self.programs.write().unwrap();
+        for (program, _) in specs {
+            if let Some(index) = programs.get_mut(program) {
+                index.live = live;
+            }
+        }
+    }
+
+    pub fn owned_by(&self, program: &Pubkey, owner: &Pubkey) -> Lookup {
+        let programs = self.programs.read().unwrap();
+        let Some(index) = programs.get(program) else {
+            return Lookup::NotIndexed;
+        };
+        let Some(reconciled_at_ms) = index.reconciled_at_ms else {
+            return Lookup::Building;
+        };
+        let accounts = index
+            .by_owner
+            .get(owner)
+            .into_iter()
+            .flatten()
+            .filter_map(|address| {
+                index.accounts.get(address).map(|entry| IndexedAccount {
+                    address: address.to_string(),
+                    lamports: entry.lamports,
+                    slot: entry.slot,
+                })
+            })
+            .collect();
+        Lookup::Found(OwnedAccounts {
+            accounts,
+            reconciled_at_ms,
+            live: index.live,
+        })
+    }
+
+    pub fn status(&self) -> Vec<IndexStatus> {
+        let programs = self.programs.read().unwrap();
+        let mut status: Vec<IndexStatus> = programs
+            .iter()
+            .map(|(program, index)| IndexStatus {
+                program: program.to_string(),
+                owner_offset: index.owner_offset,
+                accounts: index.accounts.len(),
+                owners: index.by_owner.len(),
+                reconciled_at_ms: index.reconciled_at_ms,
+                live: index.live,
+            })
+            .collect();
+        status.sort_by(|a, b| a.program.cmp(&b.program));
+        status
+    }
+}
+
+fn account_config(owner_offset: usize) -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        // Only the owner is needed.
+        data_slice: Some(UiDataSliceConfig {
+            offset: owner_offset,
+            length: OWNER_LENGTH,
+        }),
+        commitment: Some(CommitmentConfig::confirmed()),
+        min_context_slot: None,
+    }
+}
+
+// Blocking: every account of `program`, with a slot the snapshot is at
+// least as new as.
+fn snapshot(
+    rpc_url: &str,
+    program: &Pubkey,
+    owner_offset: usize,
+) -> Result<(u64, Vec<(Pubkey, Account)>), ServiceError> {
+    let client = rpc::client(rpc_url);
+    let _span = tracing::info_span!("rpc.index_snapshot", %rpc_url, %program).entered();
+    let slot = client
+        .get_slot_with_commitment(CommitmentConfig::confirmed())
+        .map_err(rpc_error("Failed to get slot"))?;
+    let accounts = client
+        .get_program_accounts_with_config(
+            program,
+            RpcProgramAccountsConfig {
+                account_config: account_config(owner_offset),
+                ..Default::default()
+            },
+        )
+        .map_err(rpc_error("Failed to list program accounts"))?;
+    Ok((slot, accounts))
+}
+
+// Rebuilds each index from getProgramAccounts every `index_reconcile_secs`.
+pub async fn reconcile(state: AppState) {
+    loop {
+        let config = state.config();
+        let specs = configured(&config.index_programs);
+        state.index.sync(&specs);
+        for (program, offset) in specs {
+            let rpc_url = config.rpc_url.clone();
+            match rpc::spawn_blocking(move || snapshot(&rpc_url, &program, offset)).await {
+                Ok(Ok((slot, accounts))) => {
+                    let mut programs = state.index.programs.write().unwrap();
+                    if let Some(index) = programs.get_mut(&program) {
+                        index.rebuild(slot, accounts);
+                    }
+                }
+                Ok(Err(e)) => {
+                    logging::warn(format_args!("Failed to index program {}: {}", program, e))
+                }
+                Err(e) => logging::warn(format_args!("Failed to index program {}: {}", program, e)),
+            }
+        }
+        tasks::succeeded();
+        tokio::time::sleep(Duration::from_secs(config.index_reconcile_secs)).await;
+    }
+}
+
+// Keeps the indexes current between rebuilds with a program subscription
+// each, reconnecting with backoff when the websocket drops and
+// resubscribing when `index_programs` changes.
+pub async fn subscribe(state: AppState) {
+    let mut delay = Duration::from_secs(1);
+    loop {
+        let config = state.config();
+        let specs = configured(&config.index_programs);
+        // The mock backend has no websocket; reconciliation alone keeps
+        // its indexes.
+        if specs.is_empty() || rpc::mock().is_some() {
+            tasks::succeeded();
+            tokio::time::sleep(CONFIG_CHECK_INTERVAL).await;
+            continue;
+        }
+        let url = config
+            .index_ws_url
+            .clone()
+            .unwrap_or_else(|| ws_url(&config.rpc_url));
+        state.index.sync(&specs);
+        match follow(&state, &url, &specs).await {
+            Ok(()) => delay = Duration::from_secs(1),
+            Err(e) => {
+                logging::warn(format_args!("Index subscriptions on {} failed: {}", url, e));
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RESUBSCRIBE_DELAY);
+            }
+        }
+    }
+}
+
+// Applies notifications until the websocket drops (an error) or the
+// configured programs change (Ok).
+async fn follow(state: &AppState, url: &str, specs: &[(Pubkey, usize)]) -> Result<(), String> {
+    let client = PubsubClient::new(url).await.map_err(|e| e.to_string())?;
+    let mut streams = Vec::with_capacity(specs.len());
+    for (program, offset) in specs {
+        let config = RpcProgramAccountsConfig {
+            account_config: account_config(*offset),
+            ..Default::default()
+        };
+        let (stream, _unsubscribe) = client
+            .program_subscribe(program, Some(config))
+            .await
+            .map_err(|e| e.to_string())?;
+        let program = *program;
+        streams.push(stream.map(move |update| (program, update)).boxed());
+    }
+    state.index.set_live(specs, true);
+    let mut updates = select_all(streams);
+    let mut check = tokio::time::interval(CONFIG_CHECK_INTERVAL);
+    let result = loop {
+        tokio::select! {
+            update = updates.next() => {
+                let Some((program, update)) = update else {
+                    break Err("the subscription closed".to_string());
+                };
+                let Ok(address) = Pubkey::from_str(&update.value.pubkey) else {
+                    continue;
+                };
+                let Some(account) = update.value.account.decode::<Account>() else {
+                    continue;
+                };
+                let mut programs = state.index.programs.write().unwrap();
+                if let Some(index) = programs.get_mut(&program) {
+                    index.update(address, &account, update.context.slot);
+                }
+            }
+            _ = check.tick() => {
+                tasks::succeeded();
+                if configured(&state.config().index_programs) != specs {
+                    break Ok(());
+                }
+            }
+        }
+    };
+    state.index.set_live(specs, false);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(owner: &Pubkey, lamports: u64) -> Account {
+        Account {
+            lamports,
+            data: owner.to_bytes().to_vec(),
+            ..Account::default()
+        }
+    }
+
+    fn owned(index: &AccountIndex, program: &Pubkey, owner: &Pubkey) -> Vec<(String, u64, u64)> {
+        match index.owned_by(program, owner) {
+            Lookup::Found(found) => found
+                .accounts
+                .into_iter()
+                .map(|a| (a.address, a.lamports, a.slot))
+                .collect(),
+            _ => panic!("expected the program to be indexed"),
+        }
+    }
+
+    #[test]
+    fn specs_and_urls() {
+        let program = Pubkey::new_unique();
+        assert_eq!(parse_spec(&program.to_string()), Ok((program, 32)));
+        assert_eq!(parse_spec(&format!("{}:8", program)), Ok((program, 8)));
+        assert_eq!(
+            parse_spec(&format!("{}:x", program)).unwrap_err(),
+            format!("invalid owner offset in '{}:x'", program)
+        );
+        assert_eq!(
+            parse_spec("nope:8").unwrap_err(),
+            "invalid program id in 'nope:8'"
+        );
+        assert_eq!(
+            configured(&["nope".to_string(), program.to_string()]),
+            [(program, 32)]
+        );
+
+        assert_eq!(ws_url("https://rpc.example/x"), "wss://rpc.example/x");
+        assert_eq!(ws_url("http://127.0.0.1:8899"), "ws://127.0.0.1:8899");
+        assert_eq!(ws_url("ws://already"), "ws://already");
+    }
+
+    #[test]
+    fn updates_follow_the_newest_state() {
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let address = Pubkey::new_unique();
+        let mut index = ProgramIndex::new(32);
+
+        index.update(address, &account(&alice, 10), 5);
+        // Older news is ignored; a new owner moves the account.
+        index.update(address, &account(&bob, 20), 4);
+        assert_eq!(index.accounts[&address].owner, alice);
+        index.update(address, &account(&bob, 30), 6);
+        assert!(!index.by_owner.contains_key(&alice));
+        assert_eq!(index.by_owner[&bob].len(), 1);
+
+        // Closed accounts and ones too short for an owner leave.
+        index.update(address, &account(&bob, 0), 7);
+        assert!(index.accounts.is_empty() && index.by_owner.is_empty());
+        let short = Account {
+            lamports: 1,
+            data: vec![1; 31],
+            ..Account::default()
+        };
+        index.update(address, &short, 8);
+        assert!(index.accounts.is_empty());
+    }
+
+    #[test]
+    fn rebuilds_keep_newer_notifications() {
+        let owner = Pubkey::new_unique();
+        let (kept, stale, gone) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut index = ProgramIndex::new(32);
+        index.update(kept, &account(&owner, 1), 120);
+        index.update(stale, &account(&owner, 1), 90);
+        index.update(gone, &account(&owner, 1), 90);
+
+        index.rebuild(
+            100,
+            vec![(kept, account(&owner, 5)), (stale, account(&owner, 7))],
+        );
+        assert_eq!(index.accounts.len(), 2);
+        assert_eq!(index.accounts[&kept].lamports, 1);
+        assert_eq!(index.accounts[&kept].slot, 120);
+        assert_eq!(index.accounts[&stale].lamports, 7);
+        assert_eq!(index.by_owner[&owner].len(), 2);
+        assert!(index.reconciled_at_ms.is_some());
+    }
+
+    #[test]
+    fn lookups_by_owner() {
+        let (program, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (owner, address) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let index = AccountIndex::default();
+        assert!(matches!(
+            index.owned_by(&program, &owner),
+            Lookup::NotIndexed
+        ));
+
+        index.sync(&[(program, 0), (other, 32)]);
+        assert!(matches!(index.owned_by(&program, &owner), Lookup::Building));
+        index
+            .programs
+            .write()
+            .unwrap()
+            .get_mut(&program)
+            .unwrap()
+            .rebuild(3, vec![(address, account(&owner, 9))]);
+        assert_eq!(
+            owned(&index, &program, &owner),
+            [(address.to_string(), 9, 3)]
+        );
+        assert!(owned(&index, &program, &Pubkey::new_unique()).is_empty());
+
+        index.set_live(&[(program, 0)], true);
+        let status = index.status();
+        let indexed = status
+            .iter()
+            .find(|s| s.program == program.to_string())
+            .unwrap();
+        assert_eq!(
+            (indexed.accounts, indexed.owners, indexed.live),
+            (1, 1, true)
+        );
+        assert_eq!(status.len(), 2);
+
+        // A changed offset starts over; a dropped program is forgotten.
+        index.sync(&[(program, 8)]);
+        assert!(matches!(index.owned_by(&program, &owner), Lookup::Building));
+        assert!(matches!(index.owned_by(&other, &owner), Lookup::NotIndexed));
+    }
+}
@@ -0,0 +1,385 @@
+use base64::Engine;
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_sdk::{
+    account::Account, bs58, clock::Slot, commitment_config::CommitmentConfig, pubkey::Pubkey,
+};
+use solana_transaction_status::{UiInstruction, UiTransactionStatusMeta};
+
+use crate::{
+    decoders::{Context, Decoded, Registry},
+    history, rpc,
+    service::{rpc_error, ServiceError},
+};
+
+#[derive(Clone)]
+pub struct DecodedAccount {
+    pub address: Pubkey,
+    pub account: Account,
+    pub decoded: Option<Decoded>,
+}
+
+pub struct DecodedInstruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<Pubkey>,
+    pub data: Vec<u8>,
+    pub decoded: Option<Decoded>,
+    // Instructions this one invoked, in execution order.
+    pub inner: Vec<DecodedInstruction>,
+}
+
+pub struct DecodedEvent {
+    pub program_id: Pubkey,
+    pub data: Vec<u8>,
+    pub decoded: Option<Decoded>,
+}
+
+pub struct DecodedTransaction {
+    pub slot: Slot,
+    pub block_time: Option<i64>,
+    pub succeeded: bool,
+    pub fee_lamports: u64,
+    pub instructions: Vec<DecodedInstruction>,
+    pub events: Vec<DecodedEvent>,
+}
+
+pub fn account(
+    rpc_url: &str,
+    decoders: &Registry,
+    address: &Pubkey,
+) -> Result<DecodedAccount, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.get_account", %rpc_url, %address).entered();
+    let account = client
+        .get_account(address)
+        .map_err(rpc_error("Failed to load account"))?;
+    let ctx = Context::new(&client);
+    let decoded = decoders.decode_account(&ctx, &account.owner, address, &account.data);
+    Ok(DecodedAccount {
+        address: *address,
+        account,
+        decoded,
+    })
+}
+
+// The newest `limit` signatures involving `address`, newest first.
+pub fn recent_signatures(
+    rpc_url: &str,
+    address: &Pubkey,
+    limit: usize,
+) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.get_signatures_for_address", %rpc_url, %address).entered();
+    client
+        .get_signatures_for_address_with_config(
+            address,
+            GetConfirmedSignaturesForAddress2Config {
+                before: None,
+                until: None,
+                limit: Some(limit),
+                commitment: Some(CommitmentConfig::confirmed()),
+            },
+        )
+        .map_err(rpc_error("Failed to get signatures"))
+}
+
+// The first `limit` of the program's accounts by address, and the total.
+pub fn program_accounts(
+    rpc_url: &str,
+    decoders: &Registry,
+    program_id: &Pubkey,
+    limit: usize,
+) -> Result<(usize, Vec<DecodedAccount>), ServiceError> {
+    let mut addresses = program_account_addresses(rpc_url, program_id)?;
+    let total = addresses.len();
+    addresses.truncate(limit);
+    let mut accounts = Vec::with_capacity(addresses.len());
+    for page in addresses.chunks(rpc::MAX_ACCOUNTS_PER_CALL) {
+        accounts.extend(load_program_accounts(rpc_url, decoders, program_id, page)?);
+    }
+    Ok((total, accounts))
+}
+
+// The addresses of all the program's accounts, sorted. Asks for an empty
+// data slice, so a program with tens of MB of account data costs 32 bytes
+// an account here; the data is fetched a page at a time by `load_accounts`.
+pub fn program_account_addresses(
+    rpc_url: &str,
+    program_id: &Pubkey,
+) -> Result<Vec<Pubkey>, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.get_program_accounts", %rpc_url, %program_id).entered();
+    let accounts = client
+        .get_program_accounts_with_config(
+            program_id,
+            RpcProgramAccountsConfig {
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    data_slice: Some(UiDataSliceConfig {
+                        offset: 0,
+                        length: 0,
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .map_err(rpc_error("Failed to list program accounts"))?;
+    let mut addresses: Vec<Pubkey> = accounts.into_iter().map(|(address, _)| address).collect();
+    addresses.sort();
+    Ok(addresses)
+}
+
+// Blocking: one getMultipleAccounts call for a page of at most
+// MAX_ACCOUNTS_PER_CALL addresses, decoded. Accounts closed since they were
+// listed are left out.
+pub fn load_accounts(
+    rpc_url: &str,
+    decoders: &Registry,
+    page: &[Pubkey],
+) -> Result<Vec<DecodedAccount>, ServiceError> {
+    let client = rpc::client(rpc_url);
+    let _span =
+        tracing::info_span!("rpc.program_accounts_page", %rpc_url, count = page.len()).entered();
+    let accounts = client
+        .get_multiple_accounts(page)
+        .map_err(rpc_error("Failed to get accounts"))?;
+    let found = page
+        .iter()
+        .zip(accounts)
+        .filter_map(|(address, account)| Some((*address, account?)))
+        .collect();
+    Ok(decode_accounts(rpc_url, decoders, found))
+}
+
+// `load_accounts` for a page of `program_account_addresses`: accounts
+// reassigned to another owner since they were listed are left out too.
+pub fn load_program_accounts(
+    rpc_url: &str,
+    decoders: &Registry,
+    program_id: &Pubkey,
+    page: &[Pubkey],
+) -> Result<Vec<DecodedAccount>, ServiceError> {
+    let mut accounts = load_accounts(rpc_url, decoders, page)?;
+    accounts.retain(|account| account.account.owner == *program_id);
+    Ok(accounts)
+}
+
+pub fn decode_accounts(
+    rpc_url: &str,
+    decoders: &Registry,
+    accounts: Vec<(Pubkey, Account)>,
+) -> Vec<DecodedAccount> {
+    let client = rpc::client(rpc_url);
+    let ctx = Context::new(&client);
+    accounts
+        .into_iter()
+        .map(|(address, account)| DecodedAccount {
+            decoded: decoders.decode_account(&ctx, &account.owner, &address, &account.data),
+            address,
+            account,
+        })
+        .collect()
+}
+
+fn instruction(
+    decoders: &Registry,
+    keys: &[Pubkey],
+    program_id_index: u8,
+    account_indexes: &[u8],
+    data: Vec<u8>,
+) -> DecodedInstruction {
+    let key = |i: u8| keys.get(i as usize).copied().unwrap_or_default();
+    let program_id = key(program_id_index);
+    let accounts: Vec<Pubkey> = account_indexes.iter().map(|&i| key(i)).collect();
+    DecodedInstruction {
+        decoded: decoders.decode_instruction(&program_id, &accounts, &data),
+        program_id,
+        accounts,
+        data,
+        inner: Vec::new(),
+    }
+}
+
+fn inner_instructions(
+    decoders: &Registry,
+    keys: &[Pubkey],
+    meta: &UiTransactionStatusMeta,
+    index: usize,
+) -> Vec<DecodedInstruction> {
+    let inner: Option<&Vec<_>> = meta.inner_instructions.as_ref().into();
+    inner
+        .into_iter()
+        .flatten()
+        .filter(|set| set.index as usize == index)
+        .flat_map(|set| &set.instructions)
+        .filter_map(|ix| match ix {
+            UiInstruction::Compiled(ix) => Some(instruction(
+                decoders,
+                keys,
+                ix.program_id_index,
+                &ix.accounts,
+                bs58::decode(&ix.data).into_vec().unwrap_or_default(),
+            )),
+            // Only returned for jsonParsed requests.
+            UiInstruction::Parsed(_) => None,
+        })
+        .collect()
+}
+
+// "Program data:" lines, each attributed to the program running when it
+// was logged. Lines with several fields are taken as one, concatenated.
+pub fn events(decoders: &Registry, logs: &[String]) -> Vec<DecodedEvent> {
+    let mut running: Vec<Pubkey> = Vec::new();
+    let mut events = Vec::new();
+    for line in logs {
+        if let Some(fields) = line.strip_prefix("Program data: ") {
+            let Some(&program_id) = running.last() else {
+                continue;
+            };
+            let data: Vec<u8> = fields
+                .split_whitespace()
+                .filter_map(|f| base64::engine::general_purpose::STANDARD.decode(f).ok())
+                .flatten()
+                .collect();
+            events.push(DecodedEvent {
+                decoded: decoders.decode_event(&program_id, &data),
+                program_id,
+                data,
+            });
+            continue;
+        }
+        let Some(rest) = line.strip_prefix("Program ") else {
+            continue;
+        };
+        let mut words = rest.split_whitespace();
+        let (Some(program), Some(what)) = (words.next(), words.next()) else {
+            continue;
+        };
+        match what {
+            "invoke" => running.extend(program.parse::<Pubkey>()),
+            "success" | "failed:" => {
+                running.pop();
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+pub fn transaction(
+    rpc_url: &str,
+    decoders: &Registry,
+    signature: &str,
+) -> Result<DecodedTransaction, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.get_transaction", %rpc_url, %signature).entered();
+    let tx = history::fetch_transaction(&client, signature)?;
+    let meta = tx
+        .transaction
+        .meta
+        .as_ref()
+        .ok_or_else(|| ServiceError::Rpc {
+            action: "Failed to read transaction meta",
+            message: format!("{} has no status meta", signature),
+        })?;
+    let decoded = tx
+        .transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| ServiceError::Rpc {
+            action: "Failed to decode transaction",
+            message: signature.to_string(),
+        })?;
+    let keys = history::account_keys(&tx);
+    let logs: Option<&Vec<String>> = meta.log_messages.as_ref().into();
+
+    let instructions = decoded
+        .message
+        .instructions()
+        .iter()
+        .enumerate()
+        .map(|(index, ix)| DecodedInstruction {
+            inner: inner_instructions(decoders, &keys, meta, index),
+            ..instruction(
+                decoders,
+                &keys,
+                ix.program_id_index,
+                &ix.accounts,
+                ix.data.clone(),
+            )
+        })
+        .collect();
+
+    Ok(DecodedTransaction {
+        slot: tx.slot,
+        block_time: tx.block_time,
+        succeeded: meta.err.is_none(),
+        fee_lamports: meta.fee,
+        instructions,
+        events: events(decoders, logs.map(Vec::as_slice).unwrap_or_default()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl::Idl;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn logged_data_goes_to_the_running_program() {
+        let program_id = Pubkey::new_unique();
+        let idl = json!({
+            "address": program_id.to_string(),
+            "metadata": { "name": "vault" },
+            "events": [{ "name": "Deposited", "discriminator": [9, 9, 9, 9, 9, 9, 9, 9] }],
+            "types": [{
+                "name": "Deposited",
+                "type": { "kind": "struct", "fields": [{ "name": "amount", "type": "u8" }] },
+            }],
+        });
+        let decoders = Registry::default();
+        decoders.register(program_id, Arc::new(Idl::parse(&idl).unwrap().1));
+
+        let b64 = |data: &[u8]| base64::engine::general_purpose::STANDARD.encode(data);
+        let system = "11111111111111111111111111111111";
+        let logs: Vec<String> = [
+            format!("Program {} invoke [1]", program_id),
+            format!("Program {} invoke [2]", system),
+            format!("Program data: {}", b64(&[1, 2])),
+            format!("Program {} success", system),
+            "Program log: Instruction: Deposit".to_string(),
+            format!("Program data: {} {}", b64(&[9; 8]), b64(&[4])),
+            format!(
+                "Program {} consumed 1200 of 200000 compute units",
+                program_id
+            ),
+            format!("Program {} success", program_id),
+            format!("Program data: {}", b64(&[3])),
+        ]
+        .into();
+
+        let events = events(&decoders, &logs);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].program_id.to_string(), system);
+        assert_eq!(events[0].data, [1, 2]);
+        assert!(events[0].decoded.is_none());
+        assert_eq!(events[1].program_id, program_id);
+        let decoded = events[1].decoded.as_ref().unwrap();
+        assert_eq!(decoded.decoder, "vault");
+        assert_eq!(
+            decoded.parsed,
+            Some(json!({ "type": "Deposited", "info": { "amount": 4 } }))
+        );
+    }
+}
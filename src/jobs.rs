@@ -0,0 +1,155 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{logging, store::Store};
+
+pub use crate::api::{Job, JobProgress, JobStatus};
+
+const STORE_NAME: &str = "jobs";
+
+// Finished jobs beyond this count are dropped, oldest first.
+const MAX_RETAINED_JOBS: usize = 1_000;
+
+// Saved as JSON under "jobs" in the store (`jobs_path` for the file
+// backend) whenever a job starts or finishes; progress updates are not
+// saved. A job still pending or running when the server stopped is loaded
+// back as failed, since nothing is working on it any more.
+pub struct JobStore {
+    store: Arc<dyn Store>,
+    next_id: AtomicU64,
+    jobs: Mutex<BTreeMap<u64, Job>>,
+}
+
+impl JobStore {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let mut jobs = BTreeMap::new();
+        if let Some(text) = store.read(STORE_NAME)? {
+            let saved: Vec<Job> = serde_json::from_str(&text)
+                .map_err(|e| format!("invalid jobs in {}: {}", store.location(STORE_NAME), e))?;
+            for mut job in saved {
+                if matches!(job.status, JobStatus::Pending | JobStatus::Running) {
+                    job.status = JobStatus::Failed;
+                    job.error = Some("Interrupted by a server restart".to_string());
+                }
+                jobs.insert(job.id, job);
+            }
+        }
+        Ok(JobStore {
+            store,
+            next_id: AtomicU64::new(jobs.keys().next_back().copied().unwrap_or(0)),
+            jobs: Mutex::new(jobs),
+        })
+    }
+
+    pub fn create(&self, kind: &str) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut jobs = self.jobs.lock().unwrap();
+
+        if jobs.len() >= MAX_RETAINED_JOBS {
+            let finished = jobs
+                .values()
+                .find(|j| matches!(j.status, JobStatus::Succeeded | JobStatus::Failed))
+                .map(|j| j.id);
+            if let Some(finished) = finished {
+                jobs.remove(&finished);
+            }
+        }
+
+        jobs.insert(
+            id,
+            Job {
+                id,
+                kind: kind.to_string(),
+                status: JobStatus::Pending,
+                progress: None,
+                result: None,
+                error: None,
+            },
+        );
+        self.save(&jobs);
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<Job> {
+        self.jobs.lock().unwrap().get(&id).cloned()
+    }
+
+    pub fn progress(&self, id: u64, step: &str, completed: u64, total: u64) {
+        self.update(id, |job| {
+            job.status = JobStatus::Running;
+            job.progress = Some(JobProgress {
+                step: step.to_string(),
+                completed,
+                total,
+            });
+        });
+    }
+
+    pub fn succeed(&self, id: u64, result: serde_json::Value) {
+        self.update(id, |job| {
+            job.status = JobStatus::Succeeded;
+            job.result = Some(result);
+        });
+        self.save(&self.jobs.lock().unwrap());
+    }
+
+    pub fn fail(&self, id: u64, error: impl ToString) {
+        self.update(id, |job| {
+            job.status = JobStatus::Failed;
+            job.error = Some(error.to_string());
+        });
+        self.save(&self.jobs.lock().unwrap());
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(&id) {
+            f(job);
+        }
+    }
+
+    // A job that can't be saved still runs; its status is only lost if the
+    // server restarts.
+    fn save(&self, jobs: &BTreeMap<u64, Job>) {
+        if !self.store.persists(STORE_NAME) {
+            return;
+        }
+        let jobs: Vec<&Job> = jobs.values().collect();
+        let result = serde_json::to_string(&jobs)
+            .map_err(|e| e.to_string())
+            .and_then(|text| self.store.write(STORE_NAME, &text));
+        if let Err(e) = result {
+            logging::warn(format_args!("Failed to save jobs: {}", e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    #[test]
+    fn jobs_survive_a_reload_and_unfinished_ones_fail() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        let jobs = JobStore::load(store.clone()).unwrap();
+        let done = jobs.create("deploy");
+        jobs.succeed(done, serde_json::json!({"program": "abc"}));
+        let running = jobs.create("deploy");
+        jobs.progress(running, "write", 1, 10);
+
+        let reloaded = JobStore::load(store).unwrap();
+        let done = reloaded.get(done).unwrap();
+        assert!(done.status == JobStatus::Succeeded);
+        assert_eq!(done.result, Some(serde_json::json!({"program": "abc"})));
+        let running = reloaded.get(running).unwrap();
+        assert!(running.status == JobStatus::Failed);
+        assert!(running.error.is_some());
+        // Ids carry on from the saved ones.
+        assert_eq!(reloaded.create("deploy"), 3);
+    }
+}
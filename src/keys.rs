@@ -0,0 +1,432 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::{bs58, hash};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{auth::Role, clock::now_ms, orgs, service::ServiceError, store::Store};
+
+pub use crate::api::KeyInfo;
+
+const STORE_NAME: &str = "api_keys";
+// Bearer tokens starting with this are API keys: "sk_<id>_<secret>".
+pub const API_KEY_PREFIX: &str = "sk_";
+const MAX_KEYS: usize = 1_000;
+const MAX_KEYS_PER_ORG: usize = 50;
+const MAX_LABEL_LENGTH: usize = 64;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct StoredKey {
+    id: u64,
+    role: Role,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    org: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    created_at_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rotated_at_ms: Option<u64>,
+    // The key itself is only shown when it is issued; this keeps
+    // SHA-256(salt || key) with a random salt per key.
+    salt: String,
+    hash: String,
+}
+
+// The saved document. Key lists from before `next_id` was kept load with
+// ids continuing after the largest one left.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedKeys {
+    Keys { next_id: u64, keys: Vec<StoredKey> },
+    List(Vec<StoredKey>),
+}
+
+#[derive(Serialize)]
+struct SavingKeys<'a> {
+    next_id: u64,
+    keys: Vec<&'a StoredKey>,
+}
+
+pub struct Authenticated {
+    pub org: Option<String>,
+    pub subject: String,
+    pub role: Role,
+}
+
+// API keys issued at runtime, either for the whole instance or for an
+// org. Changes take effect on the next request. Saved as JSON under
+// "api_keys" in the store (`api_keys_path` for files).
+pub struct KeyStore {
+    store: Arc<dyn Store>,
+    // Ids aren't reused, so a revoked key's usage and audit trail stay its
+    // own; the last id issued is saved with the keys, as revoked keys are
+    // not.
+    next_id: AtomicU64,
+    entries: Mutex<BTreeMap<u64, StoredKey>>,
+}
+
+fn salted_hash(salt: &str, key: &str) -> String {
+    hash::hashv(&[salt.as_bytes(), key.as_bytes()]).to_string()
+}
+
+// Compares every byte so the time taken doesn't tell how much matched.
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+// Returns a fresh key for `id` and the salt and hash to store for it.
+fn generate(id: u64) -> (String, String, String) {
+    let key = format!(
+        "{}{}_{}",
+        API_KEY_PREFIX,
+        id,
+        bs58::encode(rand::random::<[u8; 32]>()).into_string()
+    );
+    let salt = bs58::encode(rand::random::<[u8; 16]>()).into_string();
+    let hash = salted_hash(&salt, &key);
+    (key, salt, hash)
+}
+
+// Usage subject of a key, e.g. "key:3", or "org:team-a/key:3" for org keys.
+fn subject(key: &StoredKey) -> String {
+    match &key.org {
+        Some(org) => format!("{}/key:{}", orgs::usage_subject(org), key.id),
+        None => format!("key:{}", key.id),
+    }
+}
+
+impl StoredKey {
+    fn info(&self) -> KeyInfo {
+        KeyInfo {
+            id: self.id,
+            role: self.role,
+            org: self.org.clone(),
+            label: self.label.clone(),
+            created_at_ms: self.created_at_ms,
+            rotated_at_ms: self.rotated_at_ms,
+        }
+    }
+}
+
+impl KeyStore {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let mut entries = BTreeMap::new();
+        let mut next_id = 0;
+        if let Some(text) = store.read(STORE_NAME)? {
+            let saved: SavedKeys = serde_json::from_str(&text).map_err(|e| {
+                format!("invalid API keys in {}: {}", store.location(STORE_NAME), e)
+            })?;
+            let keys = match saved {
+                SavedKeys::Keys { next_id: id, keys } => {
+                    next_id = id;
+                    keys
+                }
+                SavedKeys::List(keys) => keys,
+            };
+            for key in keys {
+                entries.insert(key.id, key);
+            }
+        }
+        let last = entries.keys().next_back().copied().unwrap_or_default();
+        Ok(KeyStore {
+            store,
+            next_id: AtomicU64::new(next_id.max(last)),
+            entries: Mutex::new(entries),
+        })
+    }
+
+    // Returns the key's record and the key itself, which isn't stored.
+    // Org keys can read and fund but never administer the instance.
+    pub fn create(
+        &self,
+        role: Role,
+        org: Option<&str>,
+        label: Option<String>,
+    ) -> Result<(KeyInfo, String), ServiceError> {
+        if org.is_some() && role == Role::Admin {
+            return Err(ServiceError::Invalid(
+                "Org keys can have the 'reader' or 'funder' role".to_string(),
+            ));
+        }
+        if label
+            .as_ref()
+            .is_some_and(|l| l.chars().count() > MAX_LABEL_LENGTH)
+        {
+            return Err(ServiceError::Invalid(format!(
+                "Key labels can be at most {} characters",
+                MAX_LABEL_LENGTH
+            )));
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_KEYS {
+            return Err(ServiceError::Invalid(format!(
+                "At most {} API keys can be issued",
+                MAX_KEYS
+            )));
+        }
+        if org.is_some()
+            && entries.values().filter(|k| k.org.as_deref() == org).count() >= MAX_KEYS_PER_ORG
+        {
+            return Err(ServiceError::Invalid(format!(
+                "An org can have at most {} keys",
+                MAX_KEYS_PER_ORG
+            )));
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let (key, salt, hash) = generate(id);
+        let stored = StoredKey {
+            id,
+            role,
+            org: org.map(str::to_string),
+            label,
+            created_at_ms: now_ms(),
+            rotated_at_ms: None,
+            salt,
+            hash,
+        };
+        let info = stored.info();
+        entries.insert(id, stored);
+        if let Err(e) = self.save(&entries) {
+            entries.remove(&id);
+            return Err(save_error(e));
+        }
+        Ok((info, key))
+    }
+
+    // Replaces the key with a new one under the same id, so its role,
+    // usage and audit trail carry over. The old key stops working at once.
+    // `org` limits the lookup to that org's keys.
+    pub fn rotate(
+        &self,
+        id: u64,
+        org: Option<&str>,
+    ) -> Result<Option<(KeyInfo, String)>, ServiceError> {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(stored) = entries
+            .get_mut(&id)
+            .filter(|k| org.is_none() || k.org.as_deref() == org)
+        else {
+            return Ok(None);
+        };
+        let previous = stored.clone();
+        let (key, salt, hash) = generate(id);
+        stored.salt = salt;
+        stored.hash = hash;
+        stored.rotated_at_ms = Some(now_ms());
+        let info = stored.info();
+        if let Err(e) = self.save(&entries) {
+            entries.insert(id, previous);
+            return Err(save_error(e));
+        }
+        Ok(Some((info, key)))
+    }
+
+    pub fn revoke(&self, id: u64, org: Option<&str>) -> Result<bool, String> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries
+            .get(&id)
+            .is_none_or(|k| org.is_some() && k.org.as_deref() != org)
+        {
+            return Ok(false);
+        }
+        let Some(previous) = entries.remove(&id) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.save(&entries) {
+            entries.insert(id, previous);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    // Revokes every key of an org, when the org is deleted.
+    pub fn revoke_org(&self, org: &str) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap();
+        let previous = entries.clone();
+        entries.retain(|_, k| k.org.as_deref() != Some(org));
+        if let Err(e) = self.save(&entries) {
+            *entries = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // All keys, or only an org's.
+    pub fn list(&self, org: Option<&str>) -> Vec<KeyInfo> {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|k| org.is_none() || k.org.as_deref() == org)
+            .map(StoredKey::info)
+            .collect()
+    }
+
+    pub fn has_admin(&self) -> bool {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .any(|k| k.role == Role::Admin)
+    }
+
+    pub fn authenticate(&self, key: &str) -> Option<Authenticated> {
+        let id = key
+            .strip_prefix(API_KEY_PREFIX)?
+            .split_once('_')?
+            .0
+            .parse::<u64>()
+            .ok()?;
+        let entries = self.entries.lock().unwrap();
+        let stored = entries.get(&id)?;
+        if !constant_time_eq(&salted_hash(&stored.salt, key), &stored.hash) {
+            return None;
+        }
+        Some(Authenticated {
+            org: stored.org.clone(),
+            subject: subject(stored),
+            role: stored.role,
+        })
+    }
+
+    fn save(&self, entries: &BTreeMap<u64, StoredKey>) -> Result<(), String> {
+        if !self.store.persists(STORE_NAME) {
+            return Ok(());
+        }
+        let saving = SavingKeys {
+            next_id: self.next_id.load(Ordering::Relaxed),
+            keys: entries.values().collect(),
+        };
+        let text = serde_json::to_string_pretty(&saving).map_err(|e| e.to_string())?;
+        self.store.write(STORE_NAME, &text)
+    }
+}
+
+fn save_error(message: String) -> ServiceError {
+    ServiceError::Rpc {
+        action: "Failed to save API keys",
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    fn authenticated(keys: &KeyStore, key: &str) -> Option<(Option<String>, String, Role)> {
+        keys.authenticate(key)
+            .map(|found| (found.org, found.subject, found.role))
+    }
+
+    #[test]
+    fn keys_authenticate_until_rotated_or_revoked() {
+        let keys = KeyStore::load(Arc::new(MemoryStore::default())).unwrap();
+        let (info, key) = keys.create(Role::Funder, None, Some("ci".into())).unwrap();
+        assert_eq!((info.id, info.label.as_deref()), (1, Some("ci")));
+        assert!(key.starts_with("sk_1_"));
+        assert_eq!(
+            authenticated(&keys, &key),
+            Some((None, "key:1".to_string(), Role::Funder))
+        );
+        // A key for another id, or with a wrong secret, doesn't match.
+        assert_eq!(
+            authenticated(&keys, &key.replacen("sk_1_", "sk_2_", 1)),
+            None
+        );
+        assert_eq!(authenticated(&keys, &format!("{}x", key)), None);
+        assert_eq!(authenticated(&keys, "Bearer nonsense"), None);
+
+        let (rotated, new_key) = keys.rotate(1, None).unwrap().unwrap();
+        assert_eq!(rotated.id, 1);
+        assert!(rotated.rotated_at_ms.is_some());
+        assert_eq!(authenticated(&keys, &key), None);
+        assert_eq!(
+            authenticated(&keys, &new_key).map(|(_, subject, _)| subject),
+            Some("key:1".to_string())
+        );
+
+        assert!(keys.revoke(1, None).unwrap());
+        assert!(!keys.revoke(1, None).unwrap());
+        assert_eq!(authenticated(&keys, &new_key), None);
+        assert!(keys.rotate(1, None).unwrap().is_none());
+        assert!(keys.list(None).is_empty());
+    }
+
+    #[test]
+    fn org_keys_stay_within_their_org() {
+        let keys = KeyStore::load(Arc::new(MemoryStore::default())).unwrap();
+        assert!(keys.create(Role::Admin, Some("acme"), None).is_err());
+        let (_, acme) = keys.create(Role::Reader, Some("acme"), None).unwrap();
+        let (_, other) = keys.create(Role::Funder, Some("other"), None).unwrap();
+        let (_, admin) = keys.create(Role::Admin, None, None).unwrap();
+        assert!(keys.has_admin());
+        assert_eq!(
+            authenticated(&keys, &acme),
+            Some((
+                Some("acme".to_string()),
+                format!("{}/key:1", orgs::usage_subject("acme")),
+                Role::Reader
+            ))
+        );
+        assert_eq!(keys.list(Some("acme")).len(), 1);
+
+        // An org can't reach another org's keys.
+        assert!(keys.rotate(2, Some("acme")).unwrap().is_none());
+        assert!(!keys.revoke(2, Some("acme")).unwrap());
+        assert!(authenticated(&keys, &other).is_some());
+
+        keys.revoke_org("acme").unwrap();
+        assert_eq!(authenticated(&keys, &acme), None);
+        assert!(authenticated(&keys, &other).is_some());
+        assert!(authenticated(&keys, &admin).is_some());
+        let ids: Vec<u64> = keys.list(None).iter().map(|k| k.id).collect();
+        assert_eq!(ids, [2, 3]);
+    }
+
+    #[test]
+    fn ids_are_not_reused_after_a_restart() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        let keys = KeyStore::load(store.clone()).unwrap();
+        let (_, first) = keys.create(Role::Reader, None, None).unwrap();
+        keys.create(Role::Funder, None, None).unwrap();
+        assert!(keys.revoke(2, None).unwrap());
+
+        let reloaded = KeyStore::load(store.clone()).unwrap();
+        assert_eq!(
+            authenticated(&reloaded, &first).map(|(_, subject, _)| subject),
+            Some("key:1".to_string())
+        );
+        let (info, _) = reloaded.create(Role::Reader, None, None).unwrap();
+        assert_eq!(info.id, 3);
+
+        // Lists saved before the last id was kept continue after the
+        // largest id left.
+        let legacy =
+            serde_json::to_string(&vec![reloaded.entries.lock().unwrap()[&1].clone()]).unwrap();
+        store.write(STORE_NAME, &legacy).unwrap();
+        let (info, _) = KeyStore::load(store)
+            .unwrap()
+            .create(Role::Reader, None, None)
+            .unwrap();
+        assert_eq!(info.id, 2);
+    }
+
+    #[test]
+    fn constant_time_eq_compares_whole_strings() {
+        assert!(constant_time_eq("s3cret", "s3cret"));
+        assert!(constant_time_eq("", ""));
+        assert!(!constant_time_eq("s3cret", "s3creT"));
+        assert!(!constant_time_eq("s3cret", "s3cre"));
+        assert!(!constant_time_eq("s3cre", "s3cret"));
+        assert!(!constant_time_eq("", "s3cret"));
+    }
+}
@@ -0,0 +1,401 @@
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+
+use crate::{clock::now_ms, service::ServiceError, store::Store};
+
+pub use crate::api::Label;
+
+const STORE_NAME: &str = "labels";
+const MAX_LABEL_LENGTH: usize = 64;
+const MAX_TAGS: usize = 16;
+const MAX_TAG_LENGTH: usize = 32;
+// Base58 addresses are 32 to 44 characters; shorter or longer strings in
+// a response are skipped without trying to decode them.
+const ADDRESS_LENGTHS: std::ops::RangeInclusive<usize> = 32..=44;
+
+// An import file: a list of labels, or an object of address -> label.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ImportFile {
+    List(Vec<Label>),
+    Map(HashMap<String, String>),
+}
+
+// Human names for addresses ("team treasury", "CI wallet 3"), managed under
+// /admin/labels and saved as JSON under "labels" in the store (`labels_path`
+// for files). Every response names the labelled addresses it mentions.
+pub struct LabelStore {
+    store: Arc<dyn Store>,
+    entries: RwLock<BTreeMap<Pubkey, Label>>,
+}
+
+fn invalid(message: String) -> ServiceError {
+    ServiceError::Invalid(message)
+}
+
+// Trims the label and tags; tags are lowercased and deduplicated.
+fn normalize(label: &str, tags: &[String]) -> Result<(String, Vec<String>), ServiceError> {
+    let label = label.trim();
+    if label.is_empty() || label.chars().count() > MAX_LABEL_LENGTH {
+        return Err(invalid(format!(
+            "Labels must be 1-{} characters",
+            MAX_LABEL_LENGTH
+        )));
+    }
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH {
+            return Err(invalid(format!(
+                "Tags must be 1-{} characters",
+                MAX_TAG_LENGTH
+            )));
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TAGS {
+        return Err(invalid(format!("At most {} tags per address", MAX_TAGS)));
+    }
+    Ok((label.to_string(), normalized))
+}
+
+impl LabelStore {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let mut entries = BTreeMap::new();
+        if let Some(text) = store.read(STORE_NAME)? {
+            let labels: Vec<Label> = serde_json::from_str(&text)
+                .map_err(|e| format!("invalid labels in {}: {}", store.location(STORE_NAME), e))?;
+            for label in labels {
+                let address = Pubkey::from_str(&label.address).map_err(|_| {
+                    format!(
+                        "invalid address '{}' in {}",
+                        label.address,
+                        store.location(STORE_NAME)
+                    )
+                })?;
+                entries.insert(address, label);
+            }
+        }
+        Ok(LabelStore {
+            store,
+            entries: RwLock::new(entries),
+        })
+    }
+
+    // Merges a JSON file of labels into the registry; its entries replace
+    // any already saved for the same address. Returns how many it held.
+    pub fn import(&self, path: &Path) -> Result<usize, String> {
+        let context = |e: String| format!("labels import '{}': {}", path.display(), e);
+        let text = fs::read_to_string(path).map_err(|e| context(e.to_string()))?;
+        let imported = match serde_json::from_str(&text).map_err(|e| context(e.to_string()))? {
+            ImportFile::List(labels) => labels,
+            ImportFile::Map(labels) => labels
+                .into_iter()
+                .map(|(address, label)| Label {
+                    address,
+                    label,
+                    tags: Vec::new(),
+                    updated_at_ms: 0,
+                })
+                .collect(),
+        };
+
+        let mut parsed = Vec::with_capacity(imported.len());
+        for entry in imported {
+            let address = Pubkey::from_str(entry.address.trim())
+                .map_err(|_| context(format!("invalid address '{}'", entry.address)))?;
+            let (label, tags) =
+                normalize(&entry.label, &entry.tags).map_err(|e| context(e.to_string()))?;
+            parsed.push((address, label, tags));
+        }
+
+        let count = parsed.len();
+        let mut entries = self.entries.write().unwrap();
+        let previous = entries.clone();
+        for (address, label, tags) in parsed {
+            entries.insert(
+                address,
+                Label {
+                    address: address.to_string(),
+                    label,
+                    tags,
+                    updated_at_ms: now_ms(),
+                },
+            );
+        }
+        if let Err(e) = self.save(&entries) {
+            *entries = previous;
+            return Err(e);
+        }
+        Ok(count)
+    }
+
+    pub fn set(
+        &self,
+        address: Pubkey,
+        label: &str,
+        tags: &[String],
+    ) -> Result<Label, ServiceError> {
+        let (label, tags) = normalize(label, tags)?;
+        let entry = Label {
+            address: address.to_string(),
+            label,
+            tags,
+            updated_at_ms: now_ms(),
+        };
+        let mut entries = self.entries.write().unwrap();
+        let previous = entries.insert(address, entry.clone());
+        if let Err(e) = self.save(&entries) {
+            match previous {
+                Some(previous) => entries.insert(address, previous),
+                None => entries.remove(&address),
+            };
+            return Err(ServiceError::Rpc {
+                action: "Failed to save label",
+                message: e,
+            });
+        }
+        Ok(entry)
+    }
+
+    pub fn remove(&self, address: &Pubkey) -> Result<bool, String> {
+        let mut entries = self.entries.write().unwrap();
+        let Some(previous) = entries.remove(address) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.save(&entries) {
+            entries.insert(*address, previous);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.read().unwrap().is_empty()
+    }
+
+    // Labels whose address, label or a tag contains `query` (case
+    // insensitive), narrowed to those tagged `tag` when given.
+    pub fn search(&self, query: Option<&str>, tag: Option<&str>, limit: usize) -> Vec<Label> {
+        let query = query.map(str::to_lowercase);
+        let tag = tag.map(|t| t.trim().to_lowercase());
+        self.entries
+            .read()
+            .unwrap()
+            .values()
+            .filter(|l| tag.as_ref().is_none_or(|t| l.tags.contains(t)))
+            .filter(|l| {
+                query.as_ref().is_none_or(|q| {
+                    l.address.to_lowercase().contains(q)
+                        || l.label.to_lowercase().contains(q)
+                        || l.tags.iter().any(|t| t.contains(q))
+                })
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    // The label of every known address among the strings (and object keys)
+    // of `value`.
+    pub fn names(&self, value: &serde_json::Value) -> BTreeMap<String, String> {
+        let entries = self.entries.read().unwrap();
+        let mut names = BTreeMap::new();
+        let mut lookup = |s: &str| {
+            if !ADDRESS_LENGTHS.contains(&s.len()) || names.contains_key(s) {
+                return;
+            }
+            if let Some(label) = Pubkey::from_str(s).ok().and_then(|a| entries.get(&a)) {
+                names.insert(s.to_string(), label.label.clone());
+            }
+        };
+        let mut pending = vec![value];
+        while let Some(value) = pending.pop() {
+            match value {
+                serde_json::Value::String(s) => lookup(s),
+                serde_json::Value::Array(items) => pending.extend(items),
+                serde_json::Value::Object(fields) => {
+                    for (key, field) in fields {
+                        lookup(key);
+                        pending.push(field);
+                    }
+                }
+                _ => {}
+            }
+        }
+        names
+    }
+
+    fn save(&self, entries: &BTreeMap<Pubkey, Label>) -> Result<(), String> {
+        if !self.store.persists(STORE_NAME) {
+            return Ok(());
+        }
+        let labels: Vec<&Label> = entries.values().collect();
+        let text = serde_json::to_string_pretty(&labels).map_err(|e| e.to_string())?;
+        self.store.write(STORE_NAME, &text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::FlakyStore;
+    use serde_json::json;
+
+    fn invalid<T>(result: Result<T, ServiceError>) -> String {
+        match result {
+            Err(ServiceError::Invalid(message)) => message,
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn labels_and_tags_are_normalized() {
+        let labels = LabelStore::load(Arc::new(FlakyStore::default())).unwrap();
+        let address = Pubkey::new_unique();
+        let label = labels
+            .set(address, "  Team treasury ", &tags(&["Ops", " ops", "CI"]))
+            .unwrap();
+        assert_eq!(label.label, "Team treasury");
+        assert_eq!(label.tags, ["ops", "ci"]);
+
+        assert_eq!(
+            invalid(labels.set(address, " ", &[])),
+            "Labels must be 1-64 characters"
+        );
+        assert_eq!(
+            invalid(labels.set(address, &"é".repeat(65), &[])),
+            "Labels must be 1-64 characters"
+        );
+        assert!(labels.set(address, &"é".repeat(64), &[]).is_ok());
+        assert_eq!(
+            invalid(labels.set(address, "x", &tags(&[""]))),
+            "Tags must be 1-32 characters"
+        );
+        let many: Vec<String> = (0..17).map(|i| i.to_string()).collect();
+        assert_eq!(
+            invalid(labels.set(address, "x", &many)),
+            "At most 16 tags per address"
+        );
+    }
+
+    #[test]
+    fn changes_persist_or_roll_back() {
+        let store = Arc::new(FlakyStore::default());
+        let labels = LabelStore::load(store.clone()).unwrap();
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        labels.set(a, "first", &[]).unwrap();
+
+        store.fail(true);
+        assert!(labels.set(a, "renamed", &[]).is_err());
+        assert!(labels.set(b, "second", &[]).is_err());
+        assert!(labels.remove(&a).is_err());
+        store.fail(false);
+        let names: Vec<_> = labels
+            .search(None, None, 10)
+            .into_iter()
+            .map(|l| l.label)
+            .collect();
+        assert_eq!(names, ["first"]);
+
+        let reloaded = LabelStore::load(store.clone()).unwrap();
+        assert_eq!(reloaded.search(None, None, 10)[0].address, a.to_string());
+        assert!(labels.remove(&a).unwrap());
+        assert!(!labels.remove(&a).unwrap());
+        assert!(LabelStore::load(store).unwrap().is_empty());
+    }
+
+    #[test]
+    fn imports_replace_whole_entries() {
+        let store = Arc::new(FlakyStore::default());
+        let labels = LabelStore::load(store.clone()).unwrap();
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        labels.set(a, "old", &tags(&["keep"])).unwrap();
+        let path = std::env::temp_dir().join(format!("labels-import-{}.json", std::process::id()));
+
+        fs::write(
+            &path,
+            json!({ a.to_string(): "new", b.to_string(): "other" }).to_string(),
+        )
+        .unwrap();
+        assert_eq!(labels.import(&path).unwrap(), 2);
+        let a_label = labels.search(Some(&a.to_string()), None, 1).remove(0);
+        assert_eq!((a_label.label.as_str(), a_label.tags.len()), ("new", 0));
+
+        fs::write(
+            &path,
+            json!([{ "address": b.to_string(), "label": "tagged", "tags": ["X"], "updated_at_ms": 0 }])
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(labels.import(&path).unwrap(), 1);
+        assert_eq!(labels.search(None, Some("x"), 10)[0].label, "tagged");
+
+        // A bad entry imports nothing.
+        fs::write(
+            &path,
+            json!({ a.to_string(): "again", "nope": "bad" }).to_string(),
+        )
+        .unwrap();
+        let err = labels.import(&path).unwrap_err();
+        assert!(err.ends_with("invalid address 'nope'"), "{}", err);
+        store.fail(true);
+        fs::write(&path, json!({ a.to_string(): "again" }).to_string()).unwrap();
+        assert!(labels.import(&path).is_err());
+        store.fail(false);
+        assert_eq!(labels.search(Some("again"), None, 10).len(), 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn search_and_names() {
+        let labels = LabelStore::load(Arc::new(FlakyStore::default())).unwrap();
+        let (a, b, c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        labels.set(a, "Team Treasury", &tags(&["ops"])).unwrap();
+        labels.set(b, "CI wallet", &tags(&["ci", "ops"])).unwrap();
+
+        let found = |query, tag| -> Vec<String> {
+            labels
+                .search(query, tag, 10)
+                .into_iter()
+                .map(|l| l.label)
+                .collect()
+        };
+        assert_eq!(found(Some("treasury"), None), ["Team Treasury"]);
+        assert_eq!(found(None, Some(" CI ")), ["CI wallet"]);
+        assert_eq!(found(Some("wallet"), Some("ops")), ["CI wallet"]);
+        assert_eq!(found(Some("OPS"), None).len(), 2);
+        assert_eq!(labels.search(None, None, 1).len(), 1);
+
+        let names = labels.names(&json!({
+            "from": a.to_string(),
+            "accounts": [c.to_string(), { "deep": [a.to_string()] }],
+            b.to_string(): 1,
+            "short": "abc",
+        }));
+        assert_eq!(
+            names,
+            BTreeMap::from([
+                (a.to_string(), "Team Treasury".to_string()),
+                (b.to_string(), "CI wallet".to_string()),
+            ])
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use solana_sdk::pubkey::Pubkey;
+
+// Borsh reader for the on-chain account layouts decoded by hand, for
+// programs whose crates aren't dependencies.
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < len {
+            return Err("account data is truncated".to_string());
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn i8(&mut self) -> Result<i8, String> {
+        Ok(self.take(1)?[0] as i8)
+    }
+
+    pub fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn i16(&mut self) -> Result<i16, String> {
+        Ok(i16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, String> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn u128(&mut self) -> Result<u128, String> {
+        Ok(u128::from_le_bytes(self.take(16)?.try_into().unwrap()))
+    }
+
+    pub fn i128(&mut self) -> Result<i128, String> {
+        Ok(i128::from_le_bytes(self.take(16)?.try_into().unwrap()))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn pubkey(&mut self) -> Result<Pubkey, String> {
+        Ok(Pubkey::try_from(self.take(32)?).unwrap())
+    }
+
+    pub fn string(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|e| e.to_string())
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn option<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<Option<T>, String> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => read(self).map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_borsh_little_endian() {
+        let mut data = vec![0xff, 1];
+        data.extend_from_slice(&(-2i16).to_le_bytes());
+        data.extend_from_slice(&0xdead_beefu32.to_le_bytes());
+        data.extend_from_slice(&u64::MAX.to_le_bytes());
+        data.extend_from_slice(&1.5f64.to_le_bytes());
+        data.extend_from_slice(&[3, 0, 0, 0, b'a', b'b', b'c']);
+        data.extend_from_slice(&[0, 1, 42]);
+
+        let mut r = Reader::new(&data);
+        assert_eq!(r.i8().unwrap(), -1);
+        assert!(r.bool().unwrap());
+        assert_eq!(r.i16().unwrap(), -2);
+        assert_eq!(r.u32().unwrap(), 0xdead_beef);
+        assert_eq!(r.u64().unwrap(), u64::MAX);
+        assert_eq!(r.f64().unwrap(), 1.5);
+        assert_eq!(r.string().unwrap(), "abc");
+        assert_eq!(r.option(Reader::u8).unwrap(), None);
+        assert_eq!(r.option(Reader::u8).unwrap(), Some(42));
+        assert_eq!(r.remaining(), 0);
+    }
+
+    #[test]
+    fn short_data_is_an_error() {
+        let key = Pubkey::new_unique();
+        let mut r = Reader::new(key.as_ref());
+        assert_eq!(r.pubkey().unwrap(), key);
+        assert_eq!(r.u8().unwrap_err(), "account data is truncated");
+
+        let mut r = Reader::new(&[5, 0, 0, 0, b'a']);
+        assert_eq!(r.string().unwrap_err(), "account data is truncated");
+        let mut r = Reader::new(&[1, 0, 0, 0, 0xff]);
+        assert!(r.string().is_err());
+    }
+}
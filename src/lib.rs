@@ -0,0 +1,7 @@
+// The server is the binary (main.rs). The library only carries what Rust
+// consumers of its HTTP API need: the shared request and response types
+// (the solana-axum-api crate, which also builds for wasm32), and with the
+// `client` feature a typed client.
+pub use solana_axum_api as api;
+#[cfg(feature = "client")]
+pub mod client;
@@ -0,0 +1,177 @@
+use reqwest::Url;
+use std::fmt::Display;
+
+use crate::{api::ExplorerUrls, config::Config, frontend};
+
+pub use crate::api::Linked;
+
+// The `cluster` query parameter of each explorer, for the cluster the
+// server is on. Mainnet is every explorer's default and needs none.
+struct Clusters {
+    solana_explorer: Vec<(&'static str, String)>,
+    solscan: Vec<(&'static str, String)>,
+    // solana.fm can't be pointed at an arbitrary node.
+    solana_fm: Option<&'static str>,
+}
+
+fn clusters(config: &Config) -> Clusters {
+    let cluster = |name: &str| vec![("cluster", name.to_string())];
+    match frontend::cluster_name(config).to_ascii_lowercase().as_str() {
+        "devnet" => Clusters {
+            solana_explorer: cluster("devnet"),
+            solscan: cluster("devnet"),
+            solana_fm: Some("devnet-solana"),
+        },
+        "testnet" => Clusters {
+            solana_explorer: cluster("testnet"),
+            solscan: cluster("testnet"),
+            solana_fm: Some("testnet-solana"),
+        },
+        "mainnet" | "mainnet-beta" => Clusters {
+            solana_explorer: Vec::new(),
+            solscan: Vec::new(),
+            solana_fm: Some("mainnet-alpha"),
+        },
+        // A local validator's URL carries no key, so the explorers can be
+        // told where it is.
+        "localnet" => {
+            let custom = vec![
+                ("cluster", "custom".to_string()),
+                ("customUrl", config.rpc_url.clone()),
+            ];
+            Clusters {
+                solana_explorer: custom.clone(),
+                solscan: custom,
+                solana_fm: Some("localnet-solana"),
+            }
+        }
+        // Any other node's URL may carry a provider key, so it is left for
+        // the explorer to remember.
+        _ => Clusters {
+            solana_explorer: cluster("custom"),
+            solscan: cluster("custom"),
+            solana_fm: None,
+        },
+    }
+}
+
+fn url(base: &str, params: &[(&str, String)]) -> String {
+    let url = if params.is_empty() {
+        Url::parse(base)
+    } else {
+        Url::parse_with_params(base, params)
+    };
+    url.map(String::from).unwrap_or_else(|_| base.to_string())
+}
+
+// Where to look at transaction `signature` on each explorer, on the
+// server's cluster. Every response naming a transaction carries these.
+pub fn transaction(config: &Config, signature: &impl Display) -> ExplorerUrls {
+    let clusters = clusters(config);
+    ExplorerUrls {
+        solana_explorer: url(
+            &format!("https://explorer.solana.com/tx/{}", signature),
+            &clusters.solana_explorer,
+        ),
+        solscan: url(
+            &format!("https://solscan.io/tx/{}", signature),
+            &clusters.solscan,
+        ),
+        solana_fm: clusters.solana_fm.map(|cluster| {
+            url(
+                &format!("https://solana.fm/tx/{}", signature),
+                &[("cluster", cluster.to_string())],
+            )
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNATURE: &str =
+        "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW";
+
+    fn on(rpc_url: &str) -> ExplorerUrls {
+        let config = Config {
+            rpc_url: rpc_url.to_string(),
+            ..Config::default()
+        };
+        transaction(&config, &SIGNATURE)
+    }
+
+    #[test]
+    fn links_name_the_cluster() {
+        let devnet = on("https://api.devnet.solana.com");
+        assert_eq!(
+            devnet.solana_explorer,
+            format!(
+                "https://explorer.solana.com/tx/{}?cluster=devnet",
+                SIGNATURE
+            )
+        );
+        assert_eq!(
+            devnet.solscan,
+            format!("https://solscan.io/tx/{}?cluster=devnet", SIGNATURE)
+        );
+        assert_eq!(
+            devnet.solana_fm,
+            Some(format!(
+                "https://solana.fm/tx/{}?cluster=devnet-solana",
+                SIGNATURE
+            ))
+        );
+
+        let mainnet = on("https://api.mainnet-beta.solana.com");
+        assert_eq!(
+            mainnet.solana_explorer,
+            format!("https://explorer.solana.com/tx/{}", SIGNATURE)
+        );
+        assert_eq!(
+            mainnet.solscan,
+            format!("https://solscan.io/tx/{}", SIGNATURE)
+        );
+    }
+
+    #[test]
+    fn only_a_local_validator_url_is_shared() {
+        let local = on("http://127.0.0.1:8899");
+        assert!(local
+            .solana_explorer
+            .ends_with("?cluster=custom&customUrl=http%3A%2F%2F127.0.0.1%3A8899"));
+        assert_eq!(
+            local
+                .solana_fm
+                .as_deref()
+                .map(|u| u.ends_with("localnet-solana")),
+            Some(true)
+        );
+
+        let private = on("https://rpc.example.com/?api-key=secret");
+        assert!(!private.solana_explorer.contains("secret"));
+        assert!(!private.solscan.contains("secret"));
+        assert!(private.solana_explorer.ends_with("?cluster=custom"));
+        assert_eq!(private.solana_fm, None);
+    }
+
+    #[test]
+    fn linked_records_gain_a_field() {
+        #[derive(serde::Serialize)]
+        struct Record {
+            signature: &'static str,
+            slot: u64,
+        }
+        let linked = Linked {
+            explorer_urls: on("https://api.devnet.solana.com"),
+            item: Record {
+                signature: SIGNATURE,
+                slot: 7,
+            },
+        };
+        let value = serde_json::to_value(linked).unwrap();
+        assert_eq!(value["signature"], SIGNATURE);
+        assert_eq!(value["slot"], 7);
+        assert!(value["explorer_urls"]["solana_explorer"].is_string());
+    }
+}
@@ -0,0 +1,53 @@
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::config::LogLevel;
+
+// Set from `log_level` at startup and again on every config reload.
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+// Errors and warnings go to stderr, everything else to stdout.
+pub fn error(message: impl Display) {
+    if enabled(LogLevel::Error) {
+        eprintln!("{}", message);
+    }
+}
+
+pub fn warn(message: impl Display) {
+    if enabled(LogLevel::Warn) {
+        eprintln!("{}", message);
+    }
+}
+
+pub fn info(message: impl Display) {
+    if enabled(LogLevel::Info) {
+        println!("{}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_filters_less_severe_messages() {
+        set_level(LogLevel::Warn);
+        assert!(enabled(LogLevel::Error));
+        assert!(enabled(LogLevel::Warn));
+        assert!(!enabled(LogLevel::Info));
+        set_level(LogLevel::Error);
+        assert!(!enabled(LogLevel::Warn));
+        set_level(LogLevel::Info);
+        assert!(enabled(LogLevel::Info));
+    }
+}
@@ -1,184 +1,853 @@
+mod address;
+mod alerts;
+mod allowlist;
+mod amount;
+mod anchor_events;
+mod approvals;
+mod audit;
+mod auth;
+mod balance_wait;
+mod bulk;
+mod catalog;
+mod chaos;
+mod clock;
+mod cnft;
+mod compat;
+mod config;
+mod confirm;
+mod cost;
+mod decoders;
+mod denials;
+mod denylist;
+mod dependencies;
+mod deploy;
+mod deprecations;
+mod diff;
+mod envelope;
+mod events;
+mod explorer;
+mod export;
+mod fanout;
+mod features;
+mod fixtures;
+mod freshness;
+mod frontend;
+mod governance;
+mod handlers;
+mod history;
+mod hot;
+mod idempotency;
+mod idl;
+mod index;
+mod inspect;
+mod jobs;
+mod keys;
+mod labels;
+mod layout;
+mod links;
+mod logging;
+mod mock;
+mod nft;
+mod notify;
+mod openapi;
+mod orgs;
+mod panics;
+mod parquet;
+mod pool;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "profiling")]
+mod pprof;
+#[cfg(feature = "profiling")]
+mod profiling;
+mod programs;
+mod qr;
+mod queue;
+mod ratelimit;
+mod receipts;
+mod recording;
+mod rpc;
+mod selftest;
+#[cfg(feature = "sentry")]
+mod sentry;
+mod service;
+mod shed;
+mod signing;
+mod siws;
+mod slo;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stake_pool;
+mod state;
+mod store;
+mod summary;
+mod swap;
+mod tasks;
+#[cfg(feature = "telegram")]
+mod telegram;
+mod telemetry;
+mod topups;
+mod treasury;
+mod tx_history;
+mod usage;
+mod validator;
+mod version;
+mod watchlists;
+
+use audit::Actor;
+use auth::{DevOnly, Require};
 use axum::{
-    http::StatusCode,
-    response::Json as ResponseJson,
-    routing::{get, post},
-    Json, Router,
-    response::Html
+    body::Body,
+    extract::DefaultBodyLimit,
+    http::{HeaderValue, Request},
+    middleware,
+    routing::{delete, get, post, put},
+    Router,
 };
-use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
-use std::{env, str::FromStr};
+use clap::{Parser, Subcommand};
+use config::Config;
+use solana_axum_api as api;
+use state::AppState;
+use std::{net::SocketAddr, process::ExitCode};
 use tokio::net::TcpListener;
-use tower_http::cors::CorsLayer;
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    cors::{AllowOrigin, CorsLayer},
+    trace::TraceLayer,
+};
 
-#[derive(Deserialize)]
-struct GetBalance {
-    wallet: String,
+#[derive(Parser)]
+#[command(
+    version,
+    long_version = version::LONG_VERSION,
+    about = "Solana devnet balance and airdrop server"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Serialize)]
-struct GetBalanceResponse {
-    wallet: String,
-    balance_lamports: u64,
-    balance_sol: f64,
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP server (default)
+    Serve,
+    /// Validate the configuration and print the resolved values
+    CheckConfig,
+    /// Check the RPC node, keypair, treasury and store files, then exit
+    SelfTest,
+    /// Probe a running server's /health endpoint, exiting non-zero if unhealthy
+    Healthcheck {
+        /// Health endpoint to probe (defaults to this instance's local /health)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Request a one-off devnet airdrop without starting the server
+    Airdrop { wallet: String, sol: u64 },
 }
 
-#[derive(Deserialize)]
-struct AirdropRequest {
-    wallet: String,
-    sol: u64,
-}
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
 
-#[derive(Serialize)]
-struct AirdropResponse {
-    success: bool,
-    message: String,
-    wallet: String,
-    airdrop_amount_sol: u64,
-    transaction_signature: String,
-    explorer_url: String,
-}
+    let result = match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve().await,
+        Command::CheckConfig => check_config(),
+        Command::SelfTest => self_test().await,
+        Command::Healthcheck { url } => healthcheck(url).await,
+        Command::Airdrop { wallet, sol } => airdrop(wallet, sol).await,
+    };
 
-#[derive(Serialize)]
-struct ErrorResponse {
-    error: String,
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
 
-#[derive(Serialize)]
-struct HealthResponse {
-    status: String,
-    rpc_url: String,
-}
+async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "otel")]
+    let tracer_provider = telemetry::init()?;
+
+    let config = Config::load()?;
+    logging::set_level(config.log_level);
+    let addr = config.bind_addr();
+    let port = config.port;
+    // Multipart framing overhead on top of the program itself.
+    let deploy_body_limit = config.max_program_size + 64 * 1024;
+
+    let state = AppState::new(config)?;
+    let treasury = state
+        .keypair
+        .as_deref()
+        .map(solana_sdk::signer::Signer::pubkey);
+    rpc::init(&state.config(), treasury)?;
+    #[cfg(feature = "sentry")]
+    sentry::init(&state.config())?;
+    logging::info(format_args!("{}", version::banner(&state.config())));
+    if state.config().self_test {
+        selftest::report(&selftest::run(&state).await)?;
+    }
+
+    // Fail closed: a configured denylist that can't be loaded stops startup.
+    let config = state.config();
+    if config.denylist_path.is_some() || config.denylist_url.is_some() {
+        let count = state.denylist.refresh(&config).await?;
+        logging::info(format_args!("denylist loaded: {} addresses", count));
+    }
+    if let Some(path) = &config.labels_import_path {
+        let count = state.labels.import(path)?;
+        logging::info(format_args!("labels imported: {} addresses", count));
+    }
+    // Before listening, so the first lookups of hot addresses after a
+    // deploy don't wait on the RPC node.
+    if !config.hot_wallets.is_empty() || !config.hot_mints.is_empty() {
+        let failures = hot::refresh(&state).await;
+        if !failures.is_empty() {
+            logging::warn(format_args!(
+                "Failed to load some hot accounts: {}",
+                failures
+            ));
+        }
+        logging::info(format_args!(
+            "hot cache primed: {} accounts",
+            state.hot.len()
+        ));
+    }
+    let supervisor = state.tasks.clone();
+    supervisor.spawn("denylist_refresh", &state, refresh_denylist);
+    supervisor.spawn("notify_monitor", &state, notify::monitor);
+    supervisor.spawn("topup_scheduler", &state, topups::scheduler);
+    supervisor.spawn("treasury_watch", &state, treasury::watch);
+    supervisor.spawn("balance_alerts", &state, alerts::watch);
+    supervisor.spawn("rpc_compat_watch", &state, compat::watch);
+    supervisor.spawn("slot_lag_watch", &state, freshness::watch);
+    supervisor.spawn("event_dispatch", &state, events::dispatch);
+    supervisor.spawn("program_events", &state, events::watch_programs);
+    supervisor.spawn("index_reconcile", &state, index::reconcile);
+    supervisor.spawn("index_subscriptions", &state, index::subscribe);
+    supervisor.spawn("tx_history", &state, tx_history::run);
+    supervisor.spawn("export_scheduler", &state, export::scheduler);
+    supervisor.spawn("hot_cache", &state, hot::keep_warm);
+    supervisor.spawn("usage_persist", &state, usage::persist);
+    #[cfg(feature = "telegram")]
+    supervisor.spawn("telegram_bot", &state, telegram::run);
+
+    #[cfg(unix)]
+    supervisor.spawn("sighup_reload", &state, reload_on_sighup);
+
+    let cors_state = state.clone();
+    let cors = CorsLayer::permissive().allow_origin(AllowOrigin::predicate(
+        move |origin: &HeaderValue, _| {
+            origin
+                .to_str()
+                .map(|origin| cors_state.config().allows_origin(origin))
+                .unwrap_or(false)
+        },
+    ));
+
+    let public = Router::new()
+        .route("/", get(handlers::serve_html))
+        .route("/api", get(handlers::api_index))
+        .route("/config.json", get(handlers::frontend_config))
+        .route("/health", get(handlers::health_check))
+        .route("/health/details", get(handlers::health_details))
+        .route("/health/dependencies", get(handlers::health_dependencies))
+        .route("/openapi.json", get(handlers::openapi))
+        .route("/qr", get(handlers::qr_code))
+        .route("/version", get(handlers::version))
+        .route("/receipts/{id}", get(handlers::get_receipt))
+        .route("/auth/siws/challenge", post(handlers::siws_challenge))
+        .route("/auth/siws/verify", post(handlers::siws_verify));
+
+    let read = Router::new()
+        .route(
+            "/get_balance",
+            get(handlers::get_balance).post(handlers::get_balance),
+        )
+        .route("/wallet/{pubkey}/balance_at", get(handlers::get_balance_at))
+        .route(
+            "/wallet/{pubkey}/summary",
+            get(handlers::get_wallet_summary),
+        )
+        .route(
+            "/wallet/{pubkey}/transactions",
+            get(handlers::get_wallet_transactions),
+        )
+        .route("/search/memos", get(handlers::search_memos))
+        .route(
+            "/wallet/diff",
+            get(handlers::wallet_diff).post(handlers::wallet_diff),
+        )
+        .route("/balances_bulk", post(handlers::bulk_balances))
+        .route("/labels", get(handlers::list_labels))
+        .route("/programs", get(handlers::list_programs))
+        .route("/index", get(handlers::index_status))
+        .route("/index/{program}/accounts", get(handlers::indexed_accounts))
+        .route("/accounts/{address}", get(handlers::get_account))
+        .route(
+            "/programs/{program}/accounts",
+            get(handlers::get_program_accounts),
+        )
+        .route("/tx/{signature}", get(handlers::get_transaction))
+        .route(
+            "/explorer/tx/{signature}",
+            get(handlers::explorer_transaction),
+        )
+        .route(
+            "/explorer/address/{address}",
+            get(handlers::explorer_address),
+        )
+        .route("/decode", post(handlers::decode_data))
+        .route(
+            "/verify_signature",
+            get(handlers::verify_signature).post(handlers::verify_signature),
+        )
+        .route("/tx/{signature}/cost", get(handlers::get_transaction_cost))
+        .route("/tx/{signature}/stream", get(handlers::stream_transaction))
+        .route("/topups/{wallet}", get(handlers::get_topup))
+        .route("/cnft/{asset_id}/proof", get(handlers::get_asset_proof))
+        .route("/swap/quote", get(handlers::get_swap_quote))
+        .route("/stake-pools/{pool}", get(handlers::get_stake_pool))
+        .route(
+            "/governance/{realm}/proposals",
+            get(handlers::list_proposals),
+        )
+        .route(
+            "/governance/{realm}/proposals/{proposal}",
+            get(handlers::get_proposal),
+        )
+        .route(
+            "/stake-pools/{pool}/validators",
+            get(handlers::get_stake_pool_validators),
+        )
+        .route(
+            "/watchlists",
+            get(handlers::list_watchlists).post(handlers::create_watchlist),
+        )
+        .route(
+            "/watchlists/{id}",
+            get(handlers::get_watchlist).delete(handlers::delete_watchlist),
+        )
+        .route(
+            "/watchlists/{id}/overview",
+            get(handlers::get_watchlist_overview),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            usage::enforce_rpc_budget,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            freshness::attach,
+        ))
+        // Added after the budget layer so callers can still see their usage.
+        .route("/me/usage", get(handlers::my_usage))
+        .route_layer(middleware::from_fn_with_state(state.clone(), usage::track))
+        .route_layer(middleware::from_fn_with_state(state.clone(), queue::limit))
+        // Balance polls and other reads are the first to go under load.
+        .route_layer(middleware::from_fn_with_state(state.clone(), shed::shed))
+        // Long-lived and make no RPC calls of their own, so they are neither
+        // queued nor shed; websockets are capped by `max_event_streams`.
+        .route("/stream/events", get(handlers::stream_events))
+        .route(
+            "/ws/events/{program_id}",
+            get(handlers::stream_anchor_events),
+        )
+        // Can wait for a minute without using the node; waiters are capped
+        // by `balance_wait_max_waiters` instead of the queue, and
+        // `shed::track` leaves them out of the in-flight count.
+        .route(
+            "/wallet/{pubkey}/balance",
+            get(handlers::get_wallet_balance),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            ratelimit::limit,
+        ))
+        .route_layer(middleware::from_extractor_with_state::<
+            Require<auth::Reader>,
+            _,
+        >(state.clone()));
+
+    let fund = Router::new()
+        .route("/get_airdrop", post(handlers::get_airdrop))
+        .route("/topups", post(handlers::register_topup))
+        .route("/topups/{wallet}", delete(handlers::remove_topup))
+        .route("/cnft/{asset_id}/transfer", post(handlers::transfer_cnft))
+        .route("/swap/build", post(handlers::build_swap))
+        // Only the routes above pause while the treasury is low or the RPC
+        // node is outside the supported range.
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            treasury::guard,
+        ))
+        .route_layer(middleware::from_fn_with_state(state.clone(), compat::guard))
+        .route("/approvals/{id}", get(handlers::get_approval))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            usage::enforce_rpc_budget,
+        ))
+        .route_layer(middleware::from_fn_with_state(state.clone(), usage::track))
+        .route_layer(middleware::from_fn_with_state(state.clone(), queue::limit))
+        // Replays run nothing, so they skip the queue but not the rate limit.
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            idempotency::replay,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            ratelimit::limit,
+        ))
+        .route_layer(middleware::from_extractor_with_state::<
+            Require<auth::Funder>,
+            _,
+        >(state.clone()));
+
+    let admin = Router::new()
+        .route("/admin/config/reload", post(handlers::reload_config))
+        .route("/admin/audit", get(handlers::query_audit_log))
+        .route(
+            "/admin/allowlist",
+            get(handlers::list_allowlist).post(handlers::add_to_allowlist),
+        )
+        .route(
+            "/admin/allowlist/{wallet}",
+            delete(handlers::remove_from_allowlist),
+        )
+        .route(
+            "/admin/programs/{program}",
+            put(handlers::set_program_name).delete(handlers::remove_program_name),
+        )
+        // Outside /admin, but admin only like everything in this router.
+        .route("/idl", post(handlers::upload_idl))
+        .route(
+            "/admin/labels/{address}",
+            put(handlers::set_label).delete(handlers::remove_label),
+        )
+        .route("/admin/usage", get(handlers::all_usage))
+        .route("/admin/treasury", get(handlers::treasury_status))
+        .route(
+            "/admin/alerts",
+            get(handlers::list_balance_alerts).post(handlers::create_balance_alert),
+        )
+        .route("/admin/alerts/{id}", delete(handlers::remove_balance_alert))
+        .route("/admin/treasury/sweep", post(handlers::sweep_treasury))
+        .route(
+            "/admin/export",
+            get(handlers::last_export).post(handlers::run_export),
+        )
+        .route(
+            "/admin/treasury/rebalance",
+            post(handlers::rebalance_treasury),
+        )
+        .route("/admin/airdrop_bulk", post(handlers::bulk_airdrop))
+        .route(
+            "/admin/airdrop_bulk/{id}",
+            get(handlers::bulk_airdrop_status),
+        )
+        .route(
+            "/admin/airdrop_bulk/{id}/results.csv",
+            get(handlers::bulk_airdrop_results),
+        )
+        .route("/admin/approvals", get(handlers::list_approvals))
+        .route(
+            "/admin/approvals/{id}/approve",
+            post(handlers::approve_airdrop),
+        )
+        .route(
+            "/admin/approvals/{id}/reject",
+            post(handlers::reject_airdrop),
+        )
+        .route(
+            "/admin/keys",
+            get(handlers::list_keys).post(handlers::create_key),
+        )
+        .route("/admin/keys/{key}", delete(handlers::revoke_key))
+        .route("/admin/keys/{key}/rotate", post(handlers::rotate_key))
+        .route(
+            "/admin/orgs",
+            get(handlers::list_orgs).post(handlers::create_org),
+        )
+        .route(
+            "/admin/orgs/{org}",
+            get(handlers::get_org)
+                .put(handlers::update_org)
+                .delete(handlers::delete_org),
+        )
+        .route("/admin/orgs/{org}/keys", post(handlers::issue_org_key))
+        .route(
+            "/admin/orgs/{org}/keys/{key}",
+            delete(handlers::revoke_org_key),
+        )
+        .route(
+            "/admin/orgs/{org}/keys/{key}/rotate",
+            post(handlers::rotate_org_key),
+        )
+        .route("/admin/orgs/{org}/audit", get(handlers::org_audit_log))
+        .route("/admin/usage/routes", get(handlers::route_usage))
+        .route("/admin/mock", get(handlers::mock_status))
+        .route("/admin/mock/faults", put(handlers::set_mock_faults))
+        .route(
+            "/admin/mock/accounts/{wallet}",
+            put(handlers::set_mock_balance),
+        )
+        .route("/admin/mock/reset", post(handlers::reset_mock))
+        .route(
+            "/admin/features",
+            get(handlers::list_features).put(handlers::update_features),
+        )
+        .route(
+            "/admin/chaos",
+            get(handlers::chaos_status)
+                .put(handlers::set_chaos_rules)
+                .delete(handlers::clear_chaos_rules),
+        );
+    #[cfg(feature = "profiling")]
+    let admin = admin
+        .route("/debug/pprof", get(handlers::debug_pprof))
+        .route("/debug/cpu", get(handlers::debug_cpu))
+        .route("/debug/tokio", get(handlers::debug_tokio));
+    let admin = admin
+        .route_layer(middleware::from_fn_with_state(state.clone(), usage::track))
+        .route_layer(middleware::from_extractor_with_state::<
+            Require<auth::Admin>,
+            _,
+        >(state.clone()));
+
+    let dev = Router::new()
+        .route(
+            "/dev/deploy",
+            post(handlers::deploy_program).layer(DefaultBodyLimit::max(deploy_body_limit)),
+        )
+        .route("/dev/jobs/{id}", get(handlers::get_job))
+        .route("/dev/nft/mint", post(handlers::mint_nft))
+        .route("/dev/sign_message", post(handlers::sign_message))
+        .route(
+            "/dev/stake-pools/{pool}/deposit",
+            post(handlers::stake_pool_deposit),
+        )
+        .route(
+            "/dev/stake-pools/{pool}/withdraw",
+            post(handlers::stake_pool_withdraw),
+        )
+        .route("/dev/buffers", get(handlers::list_buffers))
+        .route("/dev/buffers/close", post(handlers::close_buffers))
+        .route("/dev/validator", get(handlers::validator_status))
+        .route("/dev/validator/start", post(handlers::start_validator))
+        .route("/dev/validator/stop", post(handlers::stop_validator))
+        .route("/dev/validator/reset", post(handlers::reset_validator))
+        .route(
+            "/dev/fixtures",
+            get(handlers::list_fixtures).post(handlers::load_fixtures),
+        )
+        .route_layer(middleware::from_extractor_with_state::<DevOnly, _>(
+            state.clone(),
+        ));
+
+    let app = public
+        .merge(read)
+        .merge(fund)
+        .merge(admin)
+        .merge(dev)
+        // Unknown routes answer as hidden ones do.
+        .fallback(|| async { features::not_found() })
+        .route_layer(middleware::from_fn_with_state(state.clone(), slo::track))
+        .route_layer(middleware::from_fn_with_state(state.clone(), shed::track))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            usage::count_rpc,
+        ))
+        // Outside the SLO tracker so injected failures don't trip alerts.
+        .route_layer(middleware::from_fn_with_state(state.clone(), chaos::inject))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            deprecations::check,
+        ))
+        // Outermost, so disabled routes cost nothing and hidden ones 404
+        // before auth.
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            features::gate,
+        ))
+        // Inside `envelope::scope`, so a panic's 500 carries the request id.
+        .layer(CatchPanicLayer::custom(panics::response));
+    // Around the panic catcher, so panics are reported too.
+    #[cfg(feature = "sentry")]
+    let app = app.layer(middleware::from_fn(sentry::capture_errors));
+    let app = app
+        .layer(middleware::from_fn(recording::record))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            envelope::scope,
+        ))
+        .layer(cors)
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .with_state(state);
 
-async fn serve_html() -> Html<&'static str> {
-    Html(include_str!("../public/index.html"))
+    let listener = TcpListener::bind(&addr).await?;
+    logging::info(format_args!("listening on http://localhost:{}", port));
+    logging::info(format_args!(
+        "health check: http://localhost:{}/health",
+        port
+    ));
+
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+
+    #[cfg(feature = "otel")]
+    tracer_provider.shutdown()?;
+    Ok(())
 }
 
-// async fn serve_html() -> impl axum::response::IntoResponse {
-//     Html(include_str!("../static/index.html"))
-// }
+fn request_span(req: &Request<Body>) -> tracing::Span {
+    let span = tracing::info_span!(
+        "http.request",
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+
+    #[cfg(feature = "otel")]
+    {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+        let _ = span.set_parent(telemetry::parent_context(req.headers()));
+    }
 
-async fn health_check() -> ResponseJson<HealthResponse> {
-    let rpc_url = env::var("RPC_URL").unwrap_or_else(|_| "https://api.devnet.solana.com".to_string());
-    ResponseJson(HealthResponse {
-        status: "healthy".to_string(),
-        rpc_url,
-    })
+    span
 }
 
-async fn get_balance(
-    Json(payload): Json<GetBalance>,
-) -> Result<ResponseJson<GetBalanceResponse>, (StatusCode, ResponseJson<ErrorResponse>)> {
-    let rpc_url = env::var("RPC_URL").unwrap_or_else(|_| "https://api.devnet.solana.com".to_string());
-    let client = RpcClient::new(&rpc_url);
-    
-    let pubkey = match Pubkey::from_str(&payload.wallet) {
-        Ok(key) => key,
-        Err(_) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                ResponseJson(ErrorResponse {
-                    error: "Invalid wallet address".to_string(),
-                }),
-            ));
+async fn refresh_denylist(state: AppState) {
+    loop {
+        let interval = state.config().denylist_refresh_secs;
+        tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+
+        let config = state.config();
+        if config.denylist_path.is_none() && config.denylist_url.is_none() {
+            continue;
         }
-    };
+        match state.denylist.refresh(&config).await {
+            Ok(_) => tasks::succeeded(),
+            Err(e) => logging::warn(format_args!(
+                "Denylist refresh failed, keeping previous list: {}",
+                e
+            )),
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn reload_on_sighup(state: AppState) {
+    use tokio::signal::unix::{signal, SignalKind};
 
-    let balance = match client.get_balance(&pubkey) {
-        Ok(balance) => balance,
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
         Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                ResponseJson(ErrorResponse {
-                    error: format!("Failed to get balance: {}", e),
-                }),
-            ));
+            logging::warn(format_args!("Failed to install SIGHUP handler: {}", e));
+            return;
         }
     };
 
-    Ok(ResponseJson(GetBalanceResponse {
-        wallet: payload.wallet,
-        balance_lamports: balance,
-        balance_sol: balance as f64 / LAMPORTS_PER_SOL as f64,
-    }))
+    while hangups.recv().await.is_some() {
+        match state.reload_config(Actor::system("sighup")) {
+            Ok(report) => {
+                logging::info(format_args!("Config reloaded on SIGHUP: {:?}", report));
+                tasks::succeeded();
+            }
+            Err(e) => logging::warn(format_args!(
+                "Config reload failed, keeping previous config: {}",
+                e
+            )),
+        }
+    }
 }
 
-async fn get_airdrop(
-    Json(payload): Json<AirdropRequest>,
-) -> Result<ResponseJson<AirdropResponse>, (StatusCode, ResponseJson<ErrorResponse>)> {
-    let rpc_url = env::var("RPC_URL").unwrap_or_else(|_| "https://api.devnet.solana.com".to_string());
-    let client = RpcClient::new(&rpc_url);
-    
-    let pubkey = match Pubkey::from_str(&payload.wallet) {
-        Ok(key) => key,
-        Err(_) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                ResponseJson(ErrorResponse {
-                    error: "Invalid wallet address".to_string(),
-                }),
-            ));
+fn check_config() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    println!("configuration OK");
+    println!("  rpc_url:         {}", config.rpc_url);
+    println!("  bind:            {}", config.bind_addr());
+    println!("  max_airdrop_sol: {}", config.max_airdrop_sol);
+    if config.cors_origins.is_empty() {
+        println!("  cors_origins:    *");
+    } else {
+        println!("  cors_origins:    {}", config.cors_origins.join(", "));
+    }
+    println!(
+        "  admin api:       {}",
+        if config.admin_token.is_some() {
+            "enabled"
+        } else {
+            "disabled"
         }
+    );
+    println!("  dev_mode:        {}", config.dev_mode);
+    if let Some(path) = &config.keypair_path {
+        let keypair = solana_sdk::signature::read_keypair_file(path)
+            .map_err(|e| format!("failed to read keypair '{}': {}", path.display(), e))?;
+        println!(
+            "  keypair:         {}",
+            solana_sdk::signer::Signer::pubkey(&keypair)
+        );
+    }
+    Ok(())
+}
+
+async fn self_test() -> Result<(), Box<dyn std::error::Error>> {
+    let state = AppState::new(Config::load()?)?;
+    let treasury = state
+        .keypair
+        .as_deref()
+        .map(solana_sdk::signer::Signer::pubkey);
+    rpc::init(&state.config(), treasury)?;
+    selftest::report(&selftest::run(&state).await)?;
+    Ok(())
+}
+
+async fn healthcheck(url: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let url = match url {
+        Some(url) => url,
+        None => format!("http://localhost:{}/health", Config::load()?.port),
     };
 
-    let lamports_amount = payload.sol * LAMPORTS_PER_SOL;
-    
-    if lamports_amount > 2 * LAMPORTS_PER_SOL {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            ResponseJson(ErrorResponse {
-                error: "Airdrop amount too large (max 2 SOL)".to_string(),
-            }),
-        ));
+    let res = reqwest::get(&url).await?;
+    if !res.status().is_success() {
+        return Err(format!("{} returned {}", url, res.status()).into());
     }
 
-    let sig = match client.request_airdrop(&pubkey, lamports_amount) {
-        Ok(sig) => sig,
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                ResponseJson(ErrorResponse {
-                    error: format!("Airdrop failed: {}", e),
-                }),
-            ));
+    let body: serde_json::Value = res.json().await?;
+    match health_status(&body) {
+        Some("healthy") => {
+            println!("healthy");
+            Ok(())
+        }
+        status => Err(format!("{} reported status {:?}", url, status).into()),
+    }
+}
+
+// The status /health reports: inside the envelope's `data`, or at the top
+// level with `legacy_responses` on.
+fn health_status(body: &serde_json::Value) -> Option<&str> {
+    body.get("data")
+        .unwrap_or(body)
+        .get("status")
+        .and_then(|s| s.as_str())
+}
+
+async fn airdrop(wallet: String, sol: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let state = AppState::new(Config::load()?)?;
+    let treasury = state
+        .keypair
+        .as_deref()
+        .map(solana_sdk::signer::Signer::pubkey);
+    rpc::init(&state.config(), treasury)?;
+
+    let funded = fund_airdrop(&state, &wallet, sol).await?;
+    println!("signature: {}", funded.signature);
+    println!(
+        "explorer:  {}",
+        links::transaction(&state.config(), &funded.signature).solana_explorer
+    );
+    if let Some(receipt) = funded.receipt {
+        println!("receipt:   {}", receipt);
+    }
+    Ok(())
+}
+
+// The operator skips quotas and approvals, but the recipient is screened
+// and paid the same way as over the API.
+async fn fund_airdrop(
+    state: &AppState,
+    wallet: &str,
+    sol: u64,
+) -> Result<receipts::Funded, service::ServiceError> {
+    let record = audit::record(
+        "airdrop",
+        Actor::system("cli"),
+        serde_json::json!({ "wallet": wallet, "sol": sol }),
+    );
+    let config = state.config();
+    let checked = service::sol_to_lamports(sol).and_then(|lamports| {
+        service::check_airdrop_amount(lamports, config.airdrop_limits())?;
+        state.check_recipient(wallet)?;
+        Ok(lamports)
+    });
+    let result = match checked {
+        Ok(lamports) => {
+            let (fund_config, keypair) = (config.clone(), state.keypair.clone());
+            let fund_wallet = wallet.to_string();
+            rpc::spawn_blocking(move || {
+                receipts::fund_airdrop(
+                    &fund_config,
+                    keypair.as_deref(),
+                    &fund_wallet,
+                    lamports,
+                    true,
+                )
+            })
+            .await
+            .unwrap_or_else(|e| {
+                Err(service::ServiceError::Rpc {
+                    action: "Airdrop failed",
+                    message: e.to_string(),
+                })
+            })
         }
+        Err(e) => Err(e),
     };
 
-    let explorer_url = format!("https://explorer.solana.com/tx/{}?cluster=devnet", sig);
-    
-    println!("Airdrop txn: {}", explorer_url);
-
-    Ok(ResponseJson(AirdropResponse {
-        success: true,
-        message: format!("Airdrop of {} SOL requested successfully! Use the 'Check Balance' button to see your updated balance.", payload.sol),
-        wallet: payload.wallet,
-        airdrop_amount_sol: payload.sol,
-        transaction_signature: sig.to_string(),
-        explorer_url,
-    }))
+    match &result {
+        Ok(funded) => state.audit.record(
+            record
+                .signature(funded.signature)
+                .receipt(funded.receipt.clone()),
+        ),
+        Err(e) => state.audit.record(record.failed(e)),
+    }
+    result
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    if env::var("RPC_URL").is_err() {
-        env::set_var("RPC_URL", "https://api.devnet.solana.com");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn health_status_reads_the_envelope() {
+        let body = json!({"success": true, "data": {"status": "healthy"}});
+        assert_eq!(health_status(&body), Some("healthy"));
     }
 
-    let app = Router::new()
-        .route("/", get(serve_html))
-        .route("/health", get(health_check))
-        .route("/get_balance", post(get_balance))
-        .route("/get_airdrop", post(get_airdrop))
-        .layer(CorsLayer::permissive()); 
+    #[test]
+    fn health_status_reads_legacy_responses() {
+        assert_eq!(
+            health_status(&json!({"status": "healthy"})),
+            Some("healthy")
+        );
+        assert_eq!(health_status(&json!({"success": false})), None);
+    }
 
-    let port = env::var("PORT").unwrap_or_else(|_| "3000".to_string());
-    let addr = format!("0.0.0.0:{}", port);
-    
-    let listener = TcpListener::bind(&addr).await?;
-    println!("listening on http://localhost:{}", port);
-    println!("health check: http://localhost:{}/health", port);
-    
-    axum::serve(listener, app).await?;
-    Ok(())
-}
\ No newline at end of file
+    #[tokio::test(flavor = "multi_thread")]
+    async fn cli_airdrops_are_screened_and_audited() {
+        let state = AppState::for_tests(Config {
+            allowlist_only: true,
+            ..Config::default()
+        });
+        let pubkey = solana_sdk::pubkey::Pubkey::new_unique();
+        let wallet = pubkey.to_string();
+        assert!(fund_airdrop(&state, &wallet, 1).await.is_err());
+
+        state.allowlist.add(&[pubkey]).unwrap();
+        let funded = fund_airdrop(&state, &wallet, 1).await.unwrap();
+        assert_eq!(funded.source, "airdrop");
+
+        let records = state.audit.query(Some("airdrop"), None, None, 10).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].actor.source, "cli");
+        assert_eq!(records[0].signature, Some(funded.signature.to_string()));
+        assert_eq!(records[1].outcome, "failure");
+    }
+}
@@ -0,0 +1,531 @@
+use crate::{clock::now_secs, decoders::MEMO_PROGRAM_ID};
+use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde_json::{json, Value};
+use solana_client::{
+    client_error::{ClientErrorKind, Result as ClientResult},
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::{
+    compute_budget,
+    hash::{hashv, Hash},
+    native_token::LAMPORTS_PER_SOL,
+    program_utils::limited_deserialize,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Signature,
+    system_instruction::SystemInstruction,
+    system_program,
+};
+use solana_transaction_status::{EncodedTransaction, TransactionBinaryEncoding};
+
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+pub use crate::api::{Faults, MockStatus};
+
+const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+// The server keypair starts with this much so transfers and top-ups work
+// without any setup.
+const TREASURY_SOL: u64 = 1_000;
+const BLOCKHASH_VALIDITY_SLOTS: u64 = 150;
+const SIMULATED_UNITS: u64 = 150;
+
+#[derive(Clone, Default)]
+struct Ledger {
+    slot: u64,
+    balances: HashMap<Pubkey, u64>,
+    confirmed: HashMap<Signature, u64>,
+    // getTransaction answers for the transactions sent through the mock.
+    transactions: HashMap<Signature, Value>,
+    // getSignaturesForAddress answers: (signature, slot, block time,
+    // account keys) per transaction, in the order they were sent.
+    history: Vec<(Signature, u64, u64, Vec<Pubkey>)>,
+    next_signature: u64,
+}
+
+impl Ledger {
+    fn new(treasury: Option<Pubkey>) -> Self {
+        let mut ledger = Ledger {
+            slot: 1,
+            ..Ledger::default()
+        };
+        if let Some(treasury) = treasury {
+            ledger
+                .balances
+                .insert(treasury, TREASURY_SOL * LAMPORTS_PER_SOL);
+        }
+        ledger
+    }
+
+    fn blockhash(&self) -> Hash {
+        hashv(&[b"mock-blockhash", &self.slot.to_le_bytes()])
+    }
+
+    // Deterministic, so a run against the mock produces the same
+    // signatures every time.
+    fn synthetic_signature(&mut self) -> Signature {
+        self.next_signature += 1;
+        let seed = self.next_signature.to_le_bytes();
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(hashv(&[b"mock-signature", &seed]).as_ref());
+        bytes[32..].copy_from_slice(hashv(&[b"mock-signature-2", &seed]).as_ref());
+        Signature::from(bytes)
+    }
+
+    fn confirm(&mut self, signature: Signature) {
+        self.slot += 1;
+        self.confirmed.insert(signature, self.slot);
+    }
+
+    fn context(&self, value: Value) -> Value {
+        json!({ "context": { "slot": self.slot }, "value": value })
+    }
+
+    // Funded wallets are system accounts without data; others don't exist.
+    fn account(&self, wallet: &Pubkey) -> Value {
+        match self.balances.get(wallet) {
+            Some(&lamports) if lamports > 0 => json!({
+                "lamports": lamports,
+                "owner": system_program::id().to_string(),
+                "data": ["", "base64"],
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 0,
+            }),
+            _ => Value::Null,
+        }
+    }
+}
+
+// In-memory stand-in for an RPC node (BACKEND=mock). Handles the calls this
+// server makes: balances, airdrops, system transfers and confirmation.
+// Transactions sent to it can be fetched back and funded wallets are
+// listed as system program accounts; other history lookups see an empty
+// ledger and program deploys are rejected.
+pub struct MockChain {
+    treasury: Option<Pubkey>,
+    ledger: Mutex<Ledger>,
+    faults: Mutex<Faults>,
+}
+
+fn param(params: &Value, index: usize) -> Result<&Value, String> {
+    params
+        .get(index)
+        .ok_or_else(|| format!("missing parameter {}", index))
+}
+
+fn pubkey_param(params: &Value, index: usize) -> Result<Pubkey, String> {
+    param(params, index)?
+        .as_str()
+        .and_then(|s| Pubkey::from_str(s).ok())
+        .ok_or_else(|| format!("parameter {} is not a valid address", index))
+}
+
+impl MockChain {
+    pub fn new(treasury: Option<Pubkey>) -> Self {
+        MockChain {
+            treasury,
+            ledger: Mutex::new(Ledger::new(treasury)),
+            faults: Mutex::new(Faults::default()),
+        }
+    }
+
+    pub fn status(&self) -> MockStatus {
+        let ledger = self.ledger.lock().unwrap();
+        MockStatus {
+            slot: ledger.slot,
+            accounts: ledger
+                .balances
+                .iter()
+                .map(|(k, v)| (k.to_string(), *v))
+                .collect(),
+            faults: self.faults.lock().unwrap().clone(),
+        }
+    }
+
+    pub fn set_faults(&self, faults: Faults) {
+        *self.faults.lock().unwrap() = faults;
+    }
+
+    pub fn set_balance(&self, wallet: Pubkey, lamports: u64) {
+        let mut ledger = self.ledger.lock().unwrap();
+        ledger.balances.insert(wallet, lamports);
+        ledger.slot += 1;
+    }
+
+    pub fn reset(&self) {
+        *self.ledger.lock().unwrap() = Ledger::new(self.treasury);
+        *self.faults.lock().unwrap() = Faults::default();
+    }
+
+    fn injected_fault(&self, method: &str) -> Option<String> {
+        let mut faults = self.faults.lock().unwrap();
+        if !faults.methods.is_empty() && !faults.methods.iter().any(|m| m == method) {
+            return None;
+        }
+        let fail = if faults.fail_next > 0 {
+            faults.fail_next -= 1;
+            true
+        } else {
+            faults.error_rate > 0.0 && rand::random::<f64>() < faults.error_rate
+        };
+        fail.then(|| {
+            faults
+                .message
+                .clone()
+                .unwrap_or_else(|| format!("injected failure for {}", method))
+        })
+    }
+
+    fn handle(&self, method: &str, params: &Value) -> Result<Value, String> {
+        if let Some(message) = self.injected_fault(method) {
+            return Err(message);
+        }
+
+        let mut ledger = self.ledger.lock().unwrap();
+        match method {
+            "getVersion" => Ok(json!({ "solana-core": "1.18.26", "feature-set": 0 })),
+            "getHealth" => Ok(json!("ok")),
+            "getSlot" | "getBlockHeight" => Ok(json!(ledger.slot)),
+            "getFirstAvailableBlock" => Ok(json!(0)),
+            "getBlockTime" => Ok(json!(now_secs())),
+            "getBalance" => {
+                let wallet = pubkey_param(params, 0)?;
+                let lamports = ledger.balances.get(&wallet).copied().unwrap_or(0);
+                Ok(ledger.context(json!(lamports)))
+            }
+            "getAccountInfo" => {
+                let wallet = pubkey_param(params, 0)?;
+                Ok(ledger.context(ledger.account(&wallet)))
+            }
+            "getMultipleAccounts" => {
+                let accounts = param(params, 0)?
+                    .as_array()
+                    .ok_or("parameter 0 is not a list of addresses")?
+                    .iter()
+                    .map(|a| {
+                        a.as_str()
+                            .and_then(|s| Pubkey::from_str(s).ok())
+                            .map(|wallet| ledger.account(&wallet))
+                            .ok_or("invalid address")
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(ledger.context(json!(accounts)))
+            }
+            "getMinimumBalanceForRentExemption" => {
+                let size = param(params, 0)?.as_u64().unwrap_or(0);
+                Ok(json!(Rent::default().minimum_balance(size as usize)))
+            }
+            "getLatestBlockhash" => Ok(ledger.context(json!({
+                "blockhash": ledger.blockhash().to_string(),
+                "lastValidBlockHeight": ledger.slot + BLOCKHASH_VALIDITY_SLOTS,
+            }))),
+            "isBlockhashValid" => Ok(ledger.context(json!(true))),
+            "getFeeForMessage" => Ok(ledger.context(json!(LAMPORTS_PER_SIGNATURE))),
+            "requestAirdrop" => {
+                let wallet = pubkey_param(params, 0)?;
+                let lamports = param(params, 1)?
+                    .as_u64()
+                    .ok_or("airdrop amount must be a number")?;
+                *ledger.balances.entry(wallet).or_default() += lamports;
+                let signature = ledger.synthetic_signature();
+                ledger.confirm(signature);
+                Ok(json!(signature.to_string()))
+            }
+            "sendTransaction" => send_transaction(&mut ledger, params),
+            // Sends the transaction against a copy of the ledger.
+            "simulateTransaction" => {
+                let err = match send_transaction(&mut ledger.clone(), params) {
+                    Ok(_) => Value::Null,
+                    Err(e) if e.contains("insufficient funds") => json!("InsufficientFundsForFee"),
+                    Err(e) => return Err(e),
+                };
+                Ok(ledger.context(json!({
+                    "err": err,
+                    "logs": [],
+                    "accounts": null,
+                    // What a system transfer costs; the mock doesn't meter.
+                    "unitsConsumed": SIMULATED_UNITS,
+                    "returnData": null,
+                })))
+            }
+            "getSignatureStatuses" => {
+                let statuses: Vec<Value> = param(params, 0)?
+                    .as_array()
+                    .map(|sigs| {
+                        sigs.iter()
+                            .map(|s| {
+                                let slot = s
+                                    .as_str()
+                                    .and_then(|s| Signature::from_str(s).ok())
+                                    .and_then(|s| ledger.confirmed.get(&s));
+                                match slot {
+                                    Some(slot) => json!({
+                                        "slot": slot,
+                                        "confirmations": null,
+                                        "err": null,
+                                        "status": { "Ok": null },
+                                        "confirmationStatus": "finalized",
+                                    }),
+                                    None => Value::Null,
+                                }
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Ok(ledger.context(json!(statuses)))
+            }
+            "getSignaturesForAddress" => signatures_for_address(&ledger, params),
+            "getTransaction" => {
+                let signature = param(params, 0)?
+                    .as_str()
+                    .and_then(|s| Signature::from_str(s).ok())
+                    .ok_or("invalid signature")?;
+                Ok(ledger
+                    .transactions
+                    .get(&signature)
+                    .cloned()
+                    .unwrap_or(Value::Null))
+            }
+            "getTokenAccountsByOwner" => Ok(ledger.context(json!([]))),
+            "getProgramAccounts" => {
+                // Every funded wallet is a system account; nothing else
+                // owns accounts here.
+                let accounts: Vec<Value> = if pubkey_param(params, 0)? == system_program::id() {
+                    ledger
+                        .balances
+                        .keys()
+                        .map(|wallet| (wallet, ledger.account(wallet)))
+                        .filter(|(_, account)| !account.is_null())
+                        .map(|(wallet, account)| {
+                            json!({ "pubkey": wallet.to_string(), "account": account })
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                let with_context = params
+                    .get(1)
+                    .and_then(|c| c.get("withContext"))
+                    .and_then(|w| w.as_bool())
+                    .unwrap_or(false);
+                if with_context {
+                    Ok(ledger.context(json!(accounts)))
+                } else {
+                    Ok(json!(accounts))
+                }
+            }
+            _ => Err(format!("{} is not supported by the mock backend", method)),
+        }
+    }
+}
+
+// Newest first, honouring `before`, `until` and `limit`.
+fn signatures_for_address(ledger: &Ledger, params: &Value) -> Result<Value, String> {
+    let address = param(params, 0)?
+        .as_str()
+        .and_then(|s| Pubkey::from_str(s).ok())
+        .ok_or("invalid address")?;
+    let config = params.get(1);
+    let signature = |name: &str| {
+        config
+            .and_then(|c| c.get(name))
+            .and_then(|s| s.as_str())
+            .and_then(|s| Signature::from_str(s).ok())
+    };
+    let (before, until) = (signature("before"), signature("until"));
+    let limit = config
+        .and_then(|c| c.get("limit"))
+        .and_then(|l| l.as_u64())
+        .unwrap_or(1_000) as usize;
+    let mut newest_first = ledger
+        .history
+        .iter()
+        .rev()
+        .filter(|(_, _, _, keys)| keys.contains(&address))
+        .peekable();
+    if let Some(before) = before {
+        while newest_first.next_if(|(s, ..)| *s != before).is_some() {}
+        newest_first.next();
+    }
+    let statuses: Vec<Value> = newest_first
+        .take_while(|(s, ..)| Some(*s) != until)
+        .take(limit)
+        .map(|(signature, slot, block_time, _)| {
+            json!({
+                "signature": signature.to_string(),
+                "slot": slot,
+                "err": null,
+                "memo": null,
+                "blockTime": block_time,
+                "confirmationStatus": "confirmed",
+            })
+        })
+        .collect();
+    Ok(json!(statuses))
+}
+
+// Applies a transaction made of system transfers (plus compute budget and
+// memo instructions, which are ignored). Anything else is refused, as is a
+// transaction whose payer can't cover it.
+fn send_transaction(ledger: &mut Ledger, params: &Value) -> Result<Value, String> {
+    let data = param(params, 0)?
+        .as_str()
+        .ok_or("transaction must be a string")?;
+    let encoding = match params
+        .get(1)
+        .and_then(|c| c.get("encoding"))
+        .and_then(|e| e.as_str())
+    {
+        Some("base64") => TransactionBinaryEncoding::Base64,
+        _ => TransactionBinaryEncoding::Base58,
+    };
+    let tx = EncodedTransaction::Binary(data.to_string(), encoding)
+        .decode()
+        .ok_or("failed to decode transaction")?;
+    if tx.signatures.is_empty() || !tx.verify_with_results().into_iter().all(|ok| ok) {
+        return Err("Transaction signature verification failure".to_string());
+    }
+    if ledger.confirmed.contains_key(&tx.signatures[0]) {
+        return Err("Transaction has already been processed".to_string());
+    }
+
+    let keys = tx.message.static_account_keys();
+    let payer = keys[0];
+    let mut debits: HashMap<Pubkey, u64> = HashMap::new();
+    let mut credits: HashMap<Pubkey, u64> = HashMap::new();
+    *debits.entry(payer).or_default() += LAMPORTS_PER_SIGNATURE * tx.signatures.len() as u64;
+
+    for ix in tx.message.instructions() {
+        let program = keys
+            .get(ix.program_id_index as usize)
+            .ok_or("invalid program index")?;
+        if *program == compute_budget::id() || *program == MEMO_PROGRAM_ID {
+            continue;
+        }
+        if *program != system_program::id() {
+            return Err(format!(
+                "program {} is not supported by the mock backend",
+                program
+            ));
+        }
+        let Ok(SystemInstruction::Transfer { lamports }) = limited_deserialize(&ix.data) else {
+            return Err("only system transfers are supported by the mock backend".to_string());
+        };
+        let account = |i: usize| {
+            ix.accounts
+                .get(i)
+                .and_then(|&k| keys.get(k as usize))
+                .copied()
+                .ok_or("invalid account index")
+        };
+        *debits.entry(account(0)?).or_default() += lamports;
+        *credits.entry(account(1)?).or_default() += lamports;
+    }
+
+    for (account, debit) in &debits {
+        let available = ledger.balances.get(account).copied().unwrap_or(0)
+            + credits.get(account).copied().unwrap_or(0);
+        if available < *debit {
+            return Err(format!(
+                "Transaction simulation failed: insufficient funds in {}",
+                account
+            ));
+        }
+    }
+    let balances = |ledger: &Ledger| -> Vec<u64> {
+        keys.iter()
+            .map(|k| ledger.balances.get(k).copied().unwrap_or(0))
+            .collect()
+    };
+    let pre_balances = balances(ledger);
+    for (account, credit) in credits {
+        *ledger.balances.entry(account).or_default() += credit;
+    }
+    for (account, debit) in debits {
+        *ledger.balances.entry(account).or_default() -= debit;
+    }
+    let post_balances = balances(ledger);
+
+    let signature = tx.signatures[0];
+    ledger.confirm(signature);
+    let encoded = bincode::serialize(&tx).map_err(|e| e.to_string())?;
+    let record = json!({
+        "slot": ledger.slot,
+        "blockTime": now_secs(),
+        "transaction": [BASE64_STANDARD.encode(encoded), "base64"],
+        "meta": {
+            "err": null,
+            "status": { "Ok": null },
+            "fee": LAMPORTS_PER_SIGNATURE * tx.signatures.len() as u64,
+            "preBalances": pre_balances,
+            "postBalances": post_balances,
+            "innerInstructions": [],
+            "logMessages": [],
+            "preTokenBalances": [],
+            "postTokenBalances": [],
+            "rewards": [],
+        },
+    });
+    ledger.transactions.insert(signature, record);
+    ledger
+        .history
+        .push((signature, ledger.slot, now_secs(), keys.to_vec()));
+    Ok(json!(signature.to_string()))
+}
+
+pub struct MockSender {
+    chain: Arc<MockChain>,
+}
+
+impl MockSender {
+    pub fn new(chain: Arc<MockChain>) -> Self {
+        MockSender { chain }
+    }
+}
+
+#[async_trait]
+impl RpcSender for MockSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        self.chain
+            .handle(&request.to_string(), &params)
+            .map_err(|e| ClientErrorKind::Custom(e).into())
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        "mock".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn program_accounts_lists_funded_wallets_under_the_system_program() {
+        let treasury = Pubkey::new_unique();
+        let chain = MockChain::new(Some(treasury));
+        let system =
+            json!([system_program::id().to_string(), {"dataSlice": {"offset": 0, "length": 0}}]);
+        let accounts = chain.handle("getProgramAccounts", &system).unwrap();
+        assert_eq!(accounts.as_array().unwrap().len(), 1);
+        assert_eq!(accounts[0]["pubkey"], treasury.to_string());
+        assert_eq!(
+            accounts[0]["account"]["owner"],
+            system_program::id().to_string()
+        );
+
+        let other = json!([Pubkey::new_unique().to_string(), {"withContext": true}]);
+        let accounts = chain.handle("getProgramAccounts", &other).unwrap();
+        assert_eq!(accounts["value"], json!([]));
+    }
+}
@@ -0,0 +1,309 @@
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_instruction, system_program, sysvar,
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+
+use crate::{
+    confirm, rpc,
+    service::{rpc_error, ServiceError},
+};
+
+const TOKEN_METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+// MetadataInstruction variant indexes.
+const CREATE_MASTER_EDITION_V3: u8 = 17;
+const CREATE_METADATA_ACCOUNT_V3: u8 = 33;
+
+// Token Metadata's own limits, checked here for a clearer error.
+const MAX_NAME_LENGTH: usize = 32;
+const MAX_SYMBOL_LENGTH: usize = 10;
+const MAX_URI_LENGTH: usize = 200;
+
+pub struct NftMetadata {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+pub struct MintedNft {
+    pub mint: Pubkey,
+    pub token_account: Pubkey,
+    pub metadata: Pubkey,
+    pub master_edition: Pubkey,
+    pub signature: Signature,
+}
+
+pub fn validate(metadata: &NftMetadata) -> Result<(), ServiceError> {
+    for (field, value, max) in [
+        ("name", &metadata.name, MAX_NAME_LENGTH),
+        ("symbol", &metadata.symbol, MAX_SYMBOL_LENGTH),
+        ("uri", &metadata.uri, MAX_URI_LENGTH),
+    ] {
+        if value.len() > max {
+            return Err(ServiceError::Invalid(format!(
+                "'{}' must be at most {} bytes",
+                field, max
+            )));
+        }
+    }
+    if metadata.name.is_empty() {
+        return Err(ServiceError::Invalid(
+            "'name' must not be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"metadata",
+            TOKEN_METADATA_PROGRAM_ID.as_ref(),
+            mint.as_ref(),
+        ],
+        &TOKEN_METADATA_PROGRAM_ID,
+    )
+    .0
+}
+
+fn master_edition_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"metadata",
+            TOKEN_METADATA_PROGRAM_ID.as_ref(),
+            mint.as_ref(),
+            b"edition",
+        ],
+        &TOKEN_METADATA_PROGRAM_ID,
+    )
+    .0
+}
+
+fn borsh_string(data: &mut Vec<u8>, value: &str) {
+    data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    data.extend_from_slice(value.as_bytes());
+}
+
+// CreateMetadataAccountV3 with no creators, collection or uses; mutable so
+// test metadata can be fixed up later.
+fn create_metadata(
+    metadata: &Pubkey,
+    mint: &Pubkey,
+    authority: &Pubkey,
+    nft: &NftMetadata,
+) -> Instruction {
+    let mut data = vec![CREATE_METADATA_ACCOUNT_V3];
+    borsh_string(&mut data, &nft.name);
+    borsh_string(&mut data, &nft.symbol);
+    borsh_string(&mut data, &nft.uri);
+    // seller_fee_basis_points, then None for creators, collection and uses.
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(&[0, 0, 0]);
+    // is_mutable, then None for collection_details.
+    data.extend_from_slice(&[1, 0]);
+
+    Instruction {
+        program_id: TOKEN_METADATA_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*metadata, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data,
+    }
+}
+
+// CreateMasterEditionV3 with a max supply of 0, so no prints can be made.
+// Takes over the mint and freeze authorities.
+fn create_master_edition(
+    edition: &Pubkey,
+    mint: &Pubkey,
+    authority: &Pubkey,
+    metadata: &Pubkey,
+) -> Instruction {
+    let mut data = vec![CREATE_MASTER_EDITION_V3, 1];
+    data.extend_from_slice(&0u64.to_le_bytes());
+
+    Instruction {
+        program_id: TOKEN_METADATA_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*edition, false),
+            AccountMeta::new(*mint, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(*metadata, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data,
+    }
+}
+
+// Mints a 1-of-1 Metaplex NFT to `owner` in one transaction. The server
+// keypair pays and stays the update authority.
+pub fn mint(
+    rpc_url: &str,
+    payer: &Keypair,
+    owner: &Pubkey,
+    nft: &NftMetadata,
+) -> Result<MintedNft, ServiceError> {
+    let client = rpc::client(rpc_url);
+    let authority = payer.pubkey();
+    let mint = Keypair::new();
+    let mint_address = mint.pubkey();
+    let token_account = get_associated_token_address(owner, &mint_address);
+    let metadata = metadata_address(&mint_address);
+    let master_edition = master_edition_address(&mint_address);
+
+    let _span = tracing::info_span!("rpc.mint_nft", %rpc_url, mint = %mint_address).entered();
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)
+        .map_err(rpc_error("NFT mint failed"))?;
+    let token_error = |e: solana_sdk::program_error::ProgramError| ServiceError::Rpc {
+        action: "NFT mint failed",
+        message: e.to_string(),
+    };
+    let instructions = [
+        system_instruction::create_account(
+            &authority,
+            &mint_address,
+            rent,
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::id(),
+            &mint_address,
+            &authority,
+            Some(&authority),
+            0,
+        )
+        .map_err(token_error)?,
+        create_associated_token_account_idempotent(
+            &authority,
+            owner,
+            &mint_address,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::mint_to(
+            &spl_token::id(),
+            &mint_address,
+            &token_account,
+            &authority,
+            &[],
+            1,
+        )
+        .map_err(token_error)?,
+        create_metadata(&metadata, &mint_address, &authority, nft),
+        create_master_edition(&master_edition, &mint_address, &authority, &metadata),
+    ];
+
+    let blockhash = client
+        .get_latest_blockhash()
+        .map_err(rpc_error("NFT mint failed"))?;
+    let tx = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&authority),
+        &[payer, &mint],
+        blockhash,
+    );
+    let signature = confirm::send_and_confirm(&client, &tx, "NFT mint failed")?;
+
+    Ok(MintedNft {
+        mint: mint_address,
+        token_account,
+        metadata,
+        master_edition,
+        signature,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nft(name: &str, symbol: &str, uri: &str) -> NftMetadata {
+        NftMetadata {
+            name: name.to_string(),
+            symbol: symbol.to_string(),
+            uri: uri.to_string(),
+        }
+    }
+
+    #[test]
+    fn metadata_limits_are_checked() {
+        assert!(validate(&nft("Test", "TST", "https://example.com/1.json")).is_ok());
+        let message = |nft: NftMetadata| match validate(&nft) {
+            Err(ServiceError::Invalid(message)) => message,
+            _ => panic!("expected a validation error"),
+        };
+        assert_eq!(
+            message(nft(&"n".repeat(33), "", "")),
+            "'name' must be at most 32 bytes"
+        );
+        assert_eq!(
+            message(nft("Test", "TOOLONGSYMB", "")),
+            "'symbol' must be at most 10 bytes"
+        );
+        assert_eq!(
+            message(nft("Test", "", &"u".repeat(201))),
+            "'uri' must be at most 200 bytes"
+        );
+        assert_eq!(message(nft("", "", "")), "'name' must not be empty");
+    }
+
+    #[test]
+    fn create_metadata_encodes_v3_args() {
+        let (metadata, mint, authority) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let ix = create_metadata(&metadata, &mint, &authority, &nft("Ab", "C", "u"));
+        assert_eq!(ix.program_id, TOKEN_METADATA_PROGRAM_ID);
+        assert_eq!(
+            ix.data,
+            [
+                33, // CreateMetadataAccountV3
+                2, 0, 0, 0, b'A', b'b', // name
+                1, 0, 0, 0, b'C', // symbol
+                1, 0, 0, 0, b'u', // uri
+                0, 0, // seller_fee_basis_points
+                0, 0, 0, // creators, collection, uses
+                1, 0, // is_mutable, collection_details
+            ]
+        );
+        assert_eq!(ix.accounts[0], AccountMeta::new(metadata, false));
+        assert_eq!(ix.accounts[1], AccountMeta::new_readonly(mint, false));
+        assert!(ix.accounts[2..5]
+            .iter()
+            .all(|a| a.pubkey == authority && a.is_signer));
+    }
+
+    #[test]
+    fn master_edition_has_no_prints() {
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let (metadata, edition) = (metadata_address(&mint), master_edition_address(&mint));
+        assert_ne!(metadata, edition);
+
+        let ix = create_master_edition(&edition, &mint, &authority, &metadata);
+        assert_eq!(ix.data, [17, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(ix.accounts[0], AccountMeta::new(edition, false));
+        assert_eq!(ix.accounts[5], AccountMeta::new(metadata, false));
+    }
+}
@@ -0,0 +1,307 @@
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, signature::Signer};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{config::Config, logging, orgs::Org, rpc, state::AppState, tasks, telemetry};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    TreasuryLow,
+    TreasuryRecovered,
+    AirdropFailures,
+    RpcUnhealthy,
+    RpcRecovered,
+    Suspicious,
+    ApprovalPending,
+}
+
+// Posts alerts to the configured Slack / Discord incoming webhooks. Each
+// event kind is sent at most once per `notify_cooldown_secs`.
+#[derive(Default)]
+pub struct Notifier {
+    client: reqwest::Client,
+    last_sent: Mutex<HashMap<Event, Instant>>,
+    // Per org, when it was last told its airdrop quota ran out.
+    quota_last_sent: Mutex<HashMap<String, Instant>>,
+}
+
+// What an org's own webhooks hear about.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OrgEvent {
+    Airdrop,
+    // Sent at most once per `notify_cooldown_secs` per org.
+    QuotaReached,
+}
+
+impl Notifier {
+    pub async fn notify(&self, config: &Config, event: Event, text: &str) {
+        if config.notify_webhooks.is_empty() {
+            return;
+        }
+        {
+            let mut last_sent = self.last_sent.lock().unwrap();
+            let cooldown = Duration::from_secs(config.notify_cooldown_secs);
+            if last_sent
+                .get(&event)
+                .is_some_and(|t| t.elapsed() < cooldown)
+            {
+                return;
+            }
+            last_sent.insert(event, Instant::now());
+        }
+        self.post(&config.notify_webhooks, text).await;
+    }
+
+    pub async fn notify_org(&self, config: &Config, org: &Org, event: OrgEvent, text: &str) {
+        if org.webhooks.is_empty() {
+            return;
+        }
+        if event == OrgEvent::QuotaReached {
+            let mut last_sent = self.quota_last_sent.lock().unwrap();
+            let cooldown = Duration::from_secs(config.notify_cooldown_secs);
+            if last_sent
+                .get(&org.id)
+                .is_some_and(|t| t.elapsed() < cooldown)
+            {
+                return;
+            }
+            last_sent.insert(org.id.clone(), Instant::now());
+        }
+        self.post(&org.webhooks, text).await;
+    }
+
+    // One message to one target, outside the per-event cooldown: balance
+    // alerts have their own hysteresis instead.
+    pub async fn send(&self, url: &str, text: &str) {
+        self.post(&[url.to_string()], text).await;
+    }
+
+    pub async fn send_json(&self, url: &str, body: &serde_json::Value) {
+        let result = telemetry::traced(self.client.post(url))
+            .json(body)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            logging::warn(format_args!("Failed to send notification: {}", e));
+        }
+    }
+
+    async fn post(&self, urls: &[String], text: &str) {
+        for url in urls {
+            // Discord expects `content`, Slack (and most Slack-compatible
+            // receivers) expect `text`.
+            let body = if url.contains("discord.com") || url.contains("discordapp.com") {
+                serde_json::json!({ "content": text })
+            } else {
+                serde_json::json!({ "text": text })
+            };
+            let result = telemetry::traced(self.client.post(url))
+                .json(&body)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+            if let Err(e) = result {
+                logging::warn(format_args!("Failed to send notification: {}", e));
+            }
+        }
+    }
+}
+
+// Periodic checks for conditions nobody would otherwise notice until users
+// complain: RPC health, treasury balance and airdrop failure rate.
+pub async fn monitor(state: AppState) {
+    let mut rpc_healthy = true;
+    loop {
+        let interval = state.config().notify_interval_secs;
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        let config = state.config();
+        if config.notify_webhooks.is_empty() {
+            continue;
+        }
+
+        let rpc_url = config.rpc_url.clone();
+        let keypair = state.keypair.clone();
+        let checks = tokio::task::spawn_blocking(move || {
+            let client = rpc::client(&rpc_url);
+            let health = client.get_health().map_err(|e| e.to_string());
+            let treasury = keypair.map(|k| (k.pubkey(), client.get_balance(&k.pubkey())));
+            (health, treasury)
+        })
+        .await;
+        let Ok((health, treasury)) = checks else {
+            continue;
+        };
+
+        match health {
+            Err(e) if rpc_healthy => {
+                rpc_healthy = false;
+                let text = format!("RPC endpoint {} is unhealthy: {}", config.rpc_url, e);
+                state
+                    .notifier
+                    .notify(&config, Event::RpcUnhealthy, &text)
+                    .await;
+            }
+            Ok(()) if !rpc_healthy => {
+                rpc_healthy = true;
+                let text = format!("RPC endpoint {} has recovered", config.rpc_url);
+                state
+                    .notifier
+                    .notify(&config, Event::RpcRecovered, &text)
+                    .await;
+            }
+            _ => {}
+        }
+
+        if let (Some(min_sol), Some((treasury, Ok(lamports)))) =
+            (config.notify_treasury_min_sol, treasury)
+        {
+            let sol = lamports as f64 / LAMPORTS_PER_SOL as f64;
+            if sol < min_sol {
+                let text = format!(
+                    "Treasury {} is down to {} SOL (threshold {} SOL)",
+                    treasury, sol, min_sol
+                );
+                state
+                    .notifier
+                    .notify(&config, Event::TreasuryLow, &text)
+                    .await;
+            }
+        }
+
+        tasks::succeeded();
+
+        let airdrops = state
+            .slo
+            .report(&config)
+            .into_iter()
+            .find(|r| r.route == "/get_airdrop");
+        if let Some(report) = airdrops {
+            let failure_rate = 1.0 - report.success_rate;
+            if report.requests >= config.slo_min_requests
+                && failure_rate > config.notify_airdrop_max_failure_rate
+            {
+                let text = format!(
+                    "{:.0}% of the last {} airdrops failed",
+                    failure_rate * 100.0,
+                    report.requests
+                );
+                state
+                    .notifier
+                    .notify(&config, Event::AirdropFailures, &text)
+                    .await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::Path, routing::post, Json, Router};
+    use std::sync::Arc;
+
+    type Received = Arc<Mutex<Vec<(String, serde_json::Value)>>>;
+
+    // A webhook receiver keeping what each path was sent.
+    async fn receiver() -> (String, Received) {
+        let received = Received::default();
+        let log = received.clone();
+        let app = Router::new().route(
+            "/{*path}",
+            post(
+                move |Path(path): Path<String>, Json(body): Json<serde_json::Value>| {
+                    let log = log.clone();
+                    async move { log.lock().unwrap().push((path, body)) }
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{}", addr), received)
+    }
+
+    #[tokio::test]
+    async fn events_are_posted_once_per_cooldown() {
+        let (base, received) = receiver().await;
+        let config = Config {
+            notify_webhooks: vec![
+                format!("{}/slack", base),
+                format!("{}/discord.com/api/webhooks/1", base),
+            ],
+            notify_cooldown_secs: 60,
+            ..Config::default()
+        };
+        let notifier = Notifier::default();
+        notifier.notify(&config, Event::TreasuryLow, "low").await;
+        notifier
+            .notify(&config, Event::TreasuryLow, "still low")
+            .await;
+        notifier.notify(&config, Event::RpcUnhealthy, "down").await;
+
+        let received = received.lock().unwrap().clone();
+        assert_eq!(
+            received,
+            [
+                ("slack".to_string(), serde_json::json!({ "text": "low" })),
+                (
+                    "discord.com/api/webhooks/1".to_string(),
+                    serde_json::json!({ "content": "low" })
+                ),
+                ("slack".to_string(), serde_json::json!({ "text": "down" })),
+                (
+                    "discord.com/api/webhooks/1".to_string(),
+                    serde_json::json!({ "content": "down" })
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn org_quota_alerts_cool_down_per_org() {
+        let (base, received) = receiver().await;
+        let config = Config {
+            notify_cooldown_secs: 60,
+            ..Config::default()
+        };
+        let org = |id: &str| Org {
+            id: id.to_string(),
+            name: id.to_string(),
+            airdrop_daily_quota_sol: None,
+            webhooks: vec![format!("{}/{}", base, id)],
+            created_at_ms: 0,
+        };
+        let notifier = Notifier::default();
+        for _ in 0..2 {
+            notifier
+                .notify_org(&config, &org("acme"), OrgEvent::QuotaReached, "quota")
+                .await;
+            notifier
+                .notify_org(&config, &org("acme"), OrgEvent::Airdrop, "airdrop")
+                .await;
+        }
+        notifier
+            .notify_org(&config, &org("initech"), OrgEvent::QuotaReached, "quota")
+            .await;
+
+        let paths: Vec<(String, serde_json::Value)> = received.lock().unwrap().clone();
+        let texts: Vec<(&str, &str)> = paths
+            .iter()
+            .map(|(path, body)| (path.as_str(), body["text"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                ("acme", "quota"),
+                ("acme", "airdrop"),
+                ("acme", "airdrop"),
+                ("initech", "quota"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::denials::AIRDROP_DENIALS;
+
+fn error_schema(codes: &[&str]) -> Value {
+    json!({
+        "type": "object",
+        "required": ["success", "error"],
+        "properties": {
+            "success": { "type": "boolean", "enum": [false] },
+            "error": {
+                "type": "object",
+                "required": ["code", "message"],
+                "properties": {
+                    "code": { "type": "string", "enum": codes },
+                    "message": { "type": "string" },
+                    "request_id": { "type": "string" },
+                    "retryable": {
+                        "type": "boolean",
+                        "description": "Whether the same request can succeed later.",
+                    },
+                    "retry_after_secs": {
+                        "type": "integer",
+                        "description": "When known, how long to wait first; also sent as Retry-After.",
+                    },
+                },
+            },
+        },
+    })
+}
+
+// The airdrop denials grouped by status, as OpenAPI responses.
+fn denial_responses() -> BTreeMap<String, Value> {
+    let mut by_status: BTreeMap<u16, Vec<_>> = BTreeMap::new();
+    for denial in AIRDROP_DENIALS {
+        by_status
+            .entry(denial.status.as_u16())
+            .or_default()
+            .push(denial);
+    }
+    by_status
+        .into_iter()
+        .map(|(status, denials)| {
+            let description = denials
+                .iter()
+                .map(|d| format!("`{}`: {} Retry: {}", d.code, d.reason, d.retry))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let codes: Vec<&str> = denials.iter().map(|d| d.code).collect();
+            let response = json!({
+                "description": description,
+                "content": { "application/json": { "schema": error_schema(&codes) } },
+            });
+            (status.to_string(), response)
+        })
+        .collect()
+}
+
+// Served at /openapi.json. Covers the airdrop endpoint and every reason it
+// may refuse a request.
+pub fn document() -> Value {
+    let mut responses = denial_responses();
+    responses.insert(
+        "200".to_string(),
+        json!({ "description": "The airdrop was sent; `transaction_signature` identifies it." }),
+    );
+    responses.insert(
+        "202".to_string(),
+        json!({ "description": "The airdrop is above the approval threshold and awaits an admin; poll `status_url`." }),
+    );
+    let denials: Vec<Value> = AIRDROP_DENIALS
+        .iter()
+        .map(|d| {
+            json!({
+                "code": d.code,
+                "status": d.status.as_u16(),
+                "reason": d.reason,
+                "retry": d.retry,
+            })
+        })
+        .collect();
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": {
+            "/get_airdrop": {
+                "post": {
+                    "summary": "Request an airdrop",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": { "$ref": "#/components/schemas/AirdropRequest" },
+                            },
+                        },
+                    },
+                    "responses": responses,
+                },
+            },
+        },
+        "components": {
+            "schemas": {
+                "AirdropRequest": {
+                    "type": "object",
+                    "required": ["address"],
+                    "properties": {
+                        "address": { "type": "string", "description": "Base58 wallet address." },
+                        "amount_sol": { "type": "integer", "description": "Whole SOL; or give `lamports`." },
+                        "lamports": { "type": "integer" },
+                        "callback_url": {
+                            "type": "string",
+                            "description": "Told the outcome of an airdrop held for approval. Its host must be one of the server's approval_callback_hosts.",
+                        },
+                        "dry_run": {
+                            "type": "boolean",
+                            "description": "Check and simulate the airdrop without sending it.",
+                        },
+                        "wait": {
+                            "type": "boolean",
+                            "description": "false: reply once the airdrop is sent, without waiting for it to confirm; follow it at the reply's status_url.",
+                        },
+                    },
+                },
+            },
+        },
+        "x-airdrop-denials": denials,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denials_are_grouped_by_status() {
+        let responses = denial_responses();
+        let statuses: Vec<&str> = responses.keys().map(String::as_str).collect();
+        assert_eq!(statuses, ["400", "401", "403", "429", "503"]);
+        let codes = &responses["429"]["content"]["application/json"]["schema"]["properties"]
+            ["error"]["properties"]["code"]["enum"];
+        assert_eq!(codes, &json!(["quota_exceeded", "faucet_rate_limited"]));
+        let description = responses["401"]["description"].as_str().unwrap();
+        assert_eq!(
+            description,
+            "`siws_required`: siws_required is on and the caller hasn't signed in with the \
+             wallet. Retry: Right away, after signing in."
+        );
+    }
+
+    #[test]
+    fn document_lists_every_denial() {
+        let document = document();
+        let responses = &document["paths"]["/get_airdrop"]["post"]["responses"];
+        for status in ["200", "202", "400", "401", "403", "429", "503"] {
+            assert!(responses.get(status).is_some(), "{}", status);
+        }
+        let denials = document["x-airdrop-denials"].as_array().unwrap();
+        assert_eq!(denials.len(), AIRDROP_DENIALS.len());
+        assert_eq!(denials[0]["code"], "amount_over_cap");
+        assert_eq!(denials[0]["status"], 400);
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{clock::now_ms, service::ServiceError, store::Store};
+
+pub use crate::api::{Org, OrgSettings};
+
+const STORE_NAME: &str = "orgs";
+const MAX_ORG_ID_LENGTH: usize = 32;
+const MAX_NAME_LENGTH: usize = 64;
+const MAX_WEBHOOKS: usize = 5;
+
+fn validate(settings: &OrgSettings) -> Result<(), ServiceError> {
+    let name = settings.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(ServiceError::Invalid(format!(
+            "Org names must be 1-{} characters",
+            MAX_NAME_LENGTH
+        )));
+    }
+    if settings.webhooks.len() > MAX_WEBHOOKS {
+        return Err(ServiceError::Invalid(format!(
+            "An org can have at most {} webhooks",
+            MAX_WEBHOOKS
+        )));
+    }
+    if let Some(url) = settings
+        .webhooks
+        .iter()
+        .find(|u| !u.starts_with("http://") && !u.starts_with("https://"))
+    {
+        return Err(ServiceError::Invalid(format!(
+            "Webhooks must be http(s) URLs, got '{}'",
+            url
+        )));
+    }
+    Ok(())
+}
+
+fn apply(settings: OrgSettings, org: &mut Org) {
+    org.name = settings.name.trim().to_string();
+    org.airdrop_daily_quota_sol = settings.airdrop_daily_quota_sol;
+    org.webhooks = settings.webhooks;
+}
+
+// Tenants sharing the instance, each with its own API keys (see
+// `KeyStore`). Saved as JSON under "orgs" in the store (`orgs_path` for
+// files).
+pub struct OrgStore {
+    store: Arc<dyn Store>,
+    entries: Mutex<BTreeMap<String, Org>>,
+}
+
+// The usage tracker subject that accumulates all of an org's keys.
+pub fn usage_subject(org: &str) -> String {
+    format!("org:{}", org)
+}
+
+pub fn is_usage_subject(subject: &str) -> bool {
+    subject.starts_with("org:") && !subject.contains('/')
+}
+
+impl OrgStore {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let mut entries = BTreeMap::new();
+        if let Some(text) = store.read(STORE_NAME)? {
+            let orgs: Vec<Org> = serde_json::from_str(&text)
+                .map_err(|e| format!("invalid orgs in {}: {}", store.location(STORE_NAME), e))?;
+            for org in orgs {
+                entries.insert(org.id.clone(), org);
+            }
+        }
+        Ok(OrgStore {
+            store,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    pub fn create(&self, id: &str, settings: OrgSettings) -> Result<Org, ServiceError> {
+        if id.is_empty()
+            || id.len() > MAX_ORG_ID_LENGTH
+            || !id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err(ServiceError::Invalid(format!(
+                "Org ids must be 1-{} lowercase letters, digits or '-'",
+                MAX_ORG_ID_LENGTH
+            )));
+        }
+        validate(&settings)?;
+        let mut entries = self.entries.lock().unwrap();
+        if entries.contains_key(id) {
+            return Err(ServiceError::Invalid(format!(
+                "Org '{}' already exists",
+                id
+            )));
+        }
+        let mut org = Org {
+            id: id.to_string(),
+            name: String::new(),
+            airdrop_daily_quota_sol: None,
+            webhooks: Vec::new(),
+            created_at_ms: now_ms(),
+        };
+        apply(settings, &mut org);
+        entries.insert(org.id.clone(), org.clone());
+        if let Err(e) = self.save(&entries) {
+            entries.remove(id);
+            return Err(save_error(e));
+        }
+        Ok(org)
+    }
+
+    pub fn list(&self) -> Vec<Org> {
+        self.entries.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Org> {
+        self.entries.lock().unwrap().get(id).cloned()
+    }
+
+    // Replaces the org's settings; its keys are kept.
+    pub fn update(&self, id: &str, settings: OrgSettings) -> Result<Option<Org>, ServiceError> {
+        validate(&settings)?;
+        let mut entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.get_mut(id) else {
+            return Ok(None);
+        };
+        let previous = entry.clone();
+        apply(settings, entry);
+        let org = entry.clone();
+        if let Err(e) = self.save(&entries) {
+            entries.insert(id.to_string(), previous);
+            return Err(save_error(e));
+        }
+        Ok(Some(org))
+    }
+
+    pub fn remove(&self, id: &str) -> Result<bool, String> {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(previous) = entries.remove(id) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.save(&entries) {
+            entries.insert(id.to_string(), previous);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn save(&self, entries: &BTreeMap<String, Org>) -> Result<(), String> {
+        if !self.store.persists(STORE_NAME) {
+            return Ok(());
+        }
+        let orgs: Vec<&Org> = entries.values().collect();
+        let text = serde_json::to_string_pretty(&orgs).map_err(|e| e.to_string())?;
+        self.store.write(STORE_NAME, &text)
+    }
+}
+
+fn save_error(message: String) -> ServiceError {
+    ServiceError::Rpc {
+        action: "Failed to save orgs",
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::FlakyStore;
+
+    fn settings(name: &str, webhooks: &[&str]) -> OrgSettings {
+        OrgSettings {
+            name: name.to_string(),
+            airdrop_daily_quota_sol: Some(10),
+            webhooks: webhooks.iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    fn invalid<T>(result: Result<T, ServiceError>) -> String {
+        match result {
+            Err(ServiceError::Invalid(message)) => message,
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn ids_and_settings_are_validated() {
+        let orgs = OrgStore::load(Arc::new(FlakyStore::default())).unwrap();
+        let org = orgs.create("acme-1", settings(" Acme ", &[])).unwrap();
+        assert_eq!(org.name, "Acme");
+
+        for id in ["", "Acme", "acme_1", &"a".repeat(33)] {
+            assert_eq!(
+                invalid(orgs.create(id, settings("Acme", &[]))),
+                "Org ids must be 1-32 lowercase letters, digits or '-'"
+            );
+        }
+        assert_eq!(
+            invalid(orgs.create("acme-1", settings("Again", &[]))),
+            "Org 'acme-1' already exists"
+        );
+        assert_eq!(
+            invalid(orgs.create("blank", settings("   ", &[]))),
+            "Org names must be 1-64 characters"
+        );
+        assert_eq!(
+            invalid(orgs.create("hooks", settings("Hooks", &["http://a"; 6]))),
+            "An org can have at most 5 webhooks"
+        );
+        assert_eq!(
+            invalid(orgs.create("ftp", settings("Ftp", &["ftp://example.com"]))),
+            "Webhooks must be http(s) URLs, got 'ftp://example.com'"
+        );
+    }
+
+    #[test]
+    fn changes_persist_and_roll_back_on_failure() {
+        let backing = Arc::new(FlakyStore::default());
+        let orgs = OrgStore::load(backing.clone()).unwrap();
+        orgs.create("acme", settings("Acme", &[])).unwrap();
+        orgs.create("globex", settings("Globex", &[])).unwrap();
+
+        let updated = orgs
+            .update("acme", settings("Acme Corp", &["https://example.com/hook"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.webhooks, ["https://example.com/hook"]);
+        assert!(orgs
+            .update("missing", settings("X", &[]))
+            .unwrap()
+            .is_none());
+
+        backing.fail(true);
+        assert!(orgs.create("initech", settings("Initech", &[])).is_err());
+        assert!(orgs.update("acme", settings("Renamed", &[])).is_err());
+        assert_eq!(orgs.remove("globex"), Err("disk full".to_string()));
+        assert_eq!(orgs.get("acme").unwrap().name, "Acme Corp");
+        assert_eq!(orgs.list().len(), 2);
+        backing.fail(false);
+
+        assert_eq!(orgs.remove("globex"), Ok(true));
+        assert_eq!(orgs.remove("globex"), Ok(false));
+        let reloaded = OrgStore::load(backing).unwrap();
+        let ids: Vec<_> = reloaded.list().into_iter().map(|o| o.id).collect();
+        assert_eq!(ids, ["acme"]);
+        assert_eq!(reloaded.get("acme").unwrap().name, "Acme Corp");
+    }
+
+    #[test]
+    fn usage_subjects() {
+        assert_eq!(usage_subject("acme"), "org:acme");
+        assert!(is_usage_subject("org:acme"));
+        assert!(!is_usage_subject("org:acme/key-1"));
+        assert!(!is_usage_subject("key:acme"));
+    }
+}
@@ -0,0 +1,124 @@
+use axum::{http::StatusCode, response::IntoResponse, response::Response};
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{clock::now_ms, envelope, envelope::ErrorResponse, logging, tasks};
+
+pub use crate::api::PanicReport;
+
+// The error code of a panic's 500.
+pub const CODE: &str = "internal_panic";
+
+static TOTAL: AtomicU64 = AtomicU64::new(0);
+// When the last one happened and its message.
+static LAST: Mutex<Option<(u64, String)>> = Mutex::new(None);
+
+// The response for a request whose handler panicked, in place of a dropped
+// connection: a 500 in the usual error shape, request id included. The
+// panic message is logged and counted, but not sent to the caller.
+pub fn response(payload: Box<dyn Any + Send + 'static>) -> Response {
+    let message = tasks::panic_message(payload);
+    TOTAL.fetch_add(1, Ordering::Relaxed);
+    *LAST.lock().unwrap() = Some((now_ms(), message.clone()));
+    logging::error(format_args!(
+        "Handler panicked (request {}): {}",
+        envelope::request_id().unwrap_or_else(|| "unknown".to_string()),
+        message
+    ));
+    let res = ErrorResponse::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal server error".to_string(),
+        Some(CODE),
+    )
+    .into_response();
+    // Sentry gets the panic's message in place of the caller's.
+    #[cfg(feature = "sentry")]
+    let res = {
+        let mut res = res;
+        res.extensions_mut().insert(envelope::ServerError {
+            message,
+            code: Some(CODE),
+        });
+        res
+    };
+    res
+}
+
+pub fn report() -> PanicReport {
+    let last = LAST.lock().unwrap().clone();
+    PanicReport {
+        total: TOTAL.load(Ordering::Relaxed),
+        last_at_ms: last.as_ref().map(|(at, _)| *at),
+        last_message: last.map(|(_, message)| message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+    use tower_http::catch_panic::CatchPanicLayer;
+
+    #[tokio::test]
+    async fn panics_become_counted_500s() {
+        let before = report().total;
+        let res = response(Box::new("vault key missing"));
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        #[cfg(feature = "sentry")]
+        assert_eq!(
+            res.extensions()
+                .get::<envelope::ServerError>()
+                .unwrap()
+                .message,
+            "vault key missing"
+        );
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "success": false,
+                "error": {"code": CODE, "message": "Internal server error"},
+            })
+        );
+        // The message stays in the logs.
+        assert!(!String::from_utf8_lossy(&bytes).contains("vault key"));
+
+        let report = report();
+        assert!(report.total > before);
+        assert!(report.last_at_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn handlers_that_panic_still_answer() {
+        let app = Router::new()
+            .route(
+                "/boom",
+                get(|| async {
+                    if true {
+                        panic!("handler blew up");
+                    }
+                }),
+            )
+            .route("/fine", get(|| async { "ok" }))
+            .layer(CatchPanicLayer::custom(response));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let res = reqwest::get(format!("{}/boom", url)).await.unwrap();
+        assert_eq!(res.status(), 500);
+        let body: serde_json::Value = res.json().await.unwrap();
+        assert_eq!(body["error"]["code"], CODE);
+        // The server is still serving.
+        let res = reqwest::get(format!("{}/fine", url)).await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "ok");
+    }
+}
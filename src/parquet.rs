@@ -0,0 +1,624 @@
+use serde_json::Value;
+
+// A minimal Parquet writer for snapshot exports: one row group, every
+// column OPTIONAL with one uncompressed, PLAIN-encoded data page, and the
+// footer in Thrift's compact protocol. Enough for pandas, DuckDB, Spark
+// or Athena to read, without a Parquet dependency.
+
+const MAGIC: &[u8] = b"PAR1";
+
+// parquet.thrift's enums, as far as they are used here.
+const TYPE_BOOLEAN: i32 = 0;
+const TYPE_INT64: i32 = 2;
+const TYPE_DOUBLE: i32 = 5;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const REPETITION_OPTIONAL: i32 = 1;
+const CONVERTED_UTF8: i32 = 0;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_DATA: i32 = 0;
+
+// Thrift compact protocol type ids.
+const T_I32: u8 = 5;
+const T_I64: u8 = 6;
+const T_BINARY: u8 = 8;
+const T_LIST: u8 = 9;
+const T_STRUCT: u8 = 12;
+
+// A column's type, the narrowest that fits all of its values. Anything
+// but booleans and numbers (strings, arrays, objects) is a UTF-8 string,
+// nested values as their JSON.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Boolean,
+    Int64,
+    Double,
+    String,
+}
+
+impl Kind {
+    fn of(value: &Value) -> Kind {
+        match value {
+            Value::Bool(_) => Kind::Boolean,
+            Value::Number(n) if n.is_i64() => Kind::Int64,
+            Value::Number(_) => Kind::Double,
+            _ => Kind::String,
+        }
+    }
+
+    fn merge(self, other: Kind) -> Kind {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Kind::Int64, Kind::Double) | (Kind::Double, Kind::Int64) => Kind::Double,
+            _ => Kind::String,
+        }
+    }
+
+    fn physical_type(self) -> i32 {
+        match self {
+            Kind::Boolean => TYPE_BOOLEAN,
+            Kind::Int64 => TYPE_INT64,
+            Kind::Double => TYPE_DOUBLE,
+            Kind::String => TYPE_BYTE_ARRAY,
+        }
+    }
+}
+
+struct Column<'a> {
+    name: &'a str,
+    kind: Kind,
+    // One per row; `None` where the row has no value.
+    values: Vec<Option<&'a Value>>,
+}
+
+// The columns of `rows`: every top-level key of their objects, by name.
+// Nulls and missing keys are null.
+fn columns(rows: &[Value]) -> Vec<Column<'_>> {
+    let mut columns: Vec<Column> = Vec::new();
+    for row in rows.iter().filter_map(Value::as_object) {
+        for name in row.keys() {
+            if !columns.iter().any(|c| c.name == name) {
+                columns.push(Column {
+                    name,
+                    kind: Kind::Boolean,
+                    values: Vec::new(),
+                });
+            }
+        }
+    }
+    columns.sort_by_key(|c| c.name);
+    for column in &mut columns {
+        column.values = rows
+            .iter()
+            .map(|row| row.get(column.name).filter(|v| !v.is_null()))
+            .collect();
+        column.kind = column
+            .values
+            .iter()
+            .flatten()
+            .map(|v| Kind::of(v))
+            .reduce(Kind::merge)
+            .unwrap_or(Kind::String);
+    }
+    columns
+}
+
+// The page's values: definition levels (1 present, 0 null) as runs of the
+// RLE/bit-packing hybrid, then the present values PLAIN-encoded.
+fn page_data(column: &Column) -> Vec<u8> {
+    let mut levels = Vec::new();
+    let present: Vec<bool> = column.values.iter().map(Option::is_some).collect();
+    for run in present.chunk_by(|a, b| a == b) {
+        varint(&mut levels, (run.len() as u64) << 1);
+        levels.push(u8::from(run[0]));
+    }
+    let mut data = Vec::new();
+    data.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+    data.extend_from_slice(&levels);
+
+    let values = column.values.iter().flatten();
+    match column.kind {
+        Kind::Boolean => {
+            let bits: Vec<bool> = values.map(|v| v.as_bool().unwrap_or_default()).collect();
+            data.extend(bits.chunks(8).map(|byte| {
+                byte.iter()
+                    .enumerate()
+                    .fold(0u8, |acc, (i, bit)| acc | (u8::from(*bit) << i))
+            }));
+        }
+        Kind::Int64 => {
+            for v in values {
+                data.extend_from_slice(&v.as_i64().unwrap_or_default().to_le_bytes());
+            }
+        }
+        Kind::Double => {
+            for v in values {
+                data.extend_from_slice(&v.as_f64().unwrap_or_default().to_le_bytes());
+            }
+        }
+        Kind::String => {
+            for v in values {
+                let text = match v {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                data.extend_from_slice(text.as_bytes());
+            }
+        }
+    }
+    data
+}
+
+// `rows` (JSON objects) as a Parquet file.
+pub fn write(rows: &[Value]) -> Vec<u8> {
+    let columns = columns(rows);
+    let mut file = MAGIC.to_vec();
+    // (offset, size) of each column chunk.
+    let mut chunks = Vec::with_capacity(columns.len());
+    for column in &columns {
+        let data = page_data(column);
+        let mut header = Thrift::default();
+        header.i32(1, PAGE_DATA);
+        header.i32(2, data.len() as i32);
+        header.i32(3, data.len() as i32);
+        header.begin_struct(5);
+        header.i32(1, rows.len() as i32);
+        header.i32(2, ENCODING_PLAIN);
+        header.i32(3, ENCODING_RLE);
+        header.i32(4, ENCODING_RLE);
+        header.end_struct();
+        let header = header.finish();
+
+        let offset = file.len() as i64;
+        file.extend_from_slice(&header);
+        file.extend_from_slice(&data);
+        chunks.push((offset, (header.len() + data.len()) as i64));
+    }
+
+    let mut meta = Thrift::default();
+    meta.i32(1, 1);
+    meta.list(2, T_STRUCT, columns.len() + 1);
+    meta.begin_element();
+    meta.binary(4, b"schema");
+    meta.i32(5, columns.len() as i32);
+    meta.end_struct();
+    for column in &columns {
+        meta.begin_element();
+        meta.i32(1, column.kind.physical_type());
+        meta.i32(3, REPETITION_OPTIONAL);
+        meta.binary(4, column.name.as_bytes());
+        if column.kind == Kind::String {
+            meta.i32(6, CONVERTED_UTF8);
+        }
+        meta.end_struct();
+    }
+    meta.i64(3, rows.len() as i64);
+    meta.list(4, T_STRUCT, 1);
+    meta.begin_element();
+    meta.list(1, T_STRUCT, columns.len());
+    for (column, (offset, size)) in columns.iter().zip(&chunks) {
+        meta.begin_element();
+        meta.i64(2, *offset);
+        meta.begin_struct(3);
+        meta.i32(1, column.kind.physical_type());
+        meta.list(2, T_I32, 2);
+        meta.element_i32(ENCODING_PLAIN);
+        meta.element_i32(ENCODING_RLE);
+        meta.list(3, T_BINARY, 1);
+        meta.element_binary(column.name.as_bytes());
+        meta.i32(4, CODEC_UNCOMPRESSED);
+        meta.i64(5, rows.len() as i64);
+        meta.i64(6, *size);
+        meta.i64(7, *size);
+        meta.i64(9, *offset);
+        meta.end_struct();
+        meta.end_struct();
+    }
+    meta.i64(2, chunks.iter().map(|(_, size)| size).sum());
+    meta.i64(3, rows.len() as i64);
+    meta.end_struct();
+    meta.binary(6, b"solana-axum-server");
+    let meta = meta.finish();
+
+    file.extend_from_slice(&meta);
+    file.extend_from_slice(&(meta.len() as u32).to_le_bytes());
+    file.extend_from_slice(MAGIC);
+    file
+}
+
+fn varint(buf: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buf.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+// A struct in Thrift's compact protocol. Field ids are written as deltas
+// from the previous field of the same struct, so each open struct keeps
+// its last id.
+struct Thrift {
+    buf: Vec<u8>,
+    last_ids: Vec<i16>,
+}
+
+impl Default for Thrift {
+    fn default() -> Self {
+        Thrift {
+            buf: Vec::new(),
+            last_ids: vec![0],
+        }
+    }
+}
+
+impl Thrift {
+    fn field(&mut self, id: i16, kind: u8) {
+        let last = self.last_ids.last_mut().expect("a struct is open");
+        match id - *last {
+            delta @ 1..=15 => self.buf.push((delta as u8) << 4 | kind),
+            _ => {
+                self.buf.push(kind);
+                varint(&mut self.buf, zigzag(id.into()));
+            }
+        }
+        *last = id;
+    }
+
+    fn i32(&mut self, id: i16, n: i32) {
+        self.field(id, T_I32);
+        varint(&mut self.buf, zigzag(n.into()));
+    }
+
+    fn i64(&mut self, id: i16, n: i64) {
+        self.field(id, T_I64);
+        varint(&mut self.buf, zigzag(n));
+    }
+
+    fn binary(&mut self, id: i16, bytes: &[u8]) {
+        self.field(id, T_BINARY);
+        self.element_binary(bytes);
+    }
+
+    fn list(&mut self, id: i16, kind: u8, len: usize) {
+        self.field(id, T_LIST);
+        if len < 15 {
+            self.buf.push((len as u8) << 4 | kind);
+        } else {
+            self.buf.push(0xf0 | kind);
+            varint(&mut self.buf, len as u64);
+        }
+    }
+
+    fn element_i32(&mut self, n: i32) {
+        varint(&mut self.buf, zigzag(n.into()));
+    }
+
+    fn element_binary(&mut self, bytes: &[u8]) {
+        varint(&mut self.buf, bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn begin_struct(&mut self, id: i16) {
+        self.field(id, T_STRUCT);
+        self.last_ids.push(0);
+    }
+
+    // A struct element of a list.
+    fn begin_element(&mut self) {
+        self.last_ids.push(0);
+    }
+
+    fn end_struct(&mut self) {
+        self.buf.push(0);
+        self.last_ids.pop();
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.buf.push(0);
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    // A Thrift compact value as read back: integers, binaries, lists and
+    // structs by field id are all a footer uses.
+    #[derive(Debug, PartialEq)]
+    enum T {
+        Int(i64),
+        Bin(Vec<u8>),
+        List(Vec<T>),
+        Struct(BTreeMap<i16, T>),
+    }
+
+    impl T {
+        fn int(&self) -> i64 {
+            match self {
+                T::Int(n) => *n,
+                other => panic!("not an integer: {:?}", other),
+            }
+        }
+
+        fn bin(&self) -> &[u8] {
+            match self {
+                T::Bin(b) => b,
+                other => panic!("not a binary: {:?}", other),
+            }
+        }
+
+        fn list(&self) -> &[T] {
+            match self {
+                T::List(items) => items,
+                other => panic!("not a list: {:?}", other),
+            }
+        }
+
+        fn get(&self, id: i16) -> &T {
+            match self {
+                T::Struct(fields) => &fields[&id],
+                other => panic!("not a struct: {:?}", other),
+            }
+        }
+
+        fn has(&self, id: i16) -> bool {
+            matches!(self, T::Struct(fields) if fields.contains_key(&id))
+        }
+    }
+
+    struct Reader<'a> {
+        buf: &'a [u8],
+        pos: usize,
+    }
+
+    impl Reader<'_> {
+        fn byte(&mut self) -> u8 {
+            self.pos += 1;
+            self.buf[self.pos - 1]
+        }
+
+        fn varint(&mut self) -> u64 {
+            let (mut n, mut shift) = (0u64, 0);
+            loop {
+                let b = self.byte();
+                n |= u64::from(b & 0x7f) << shift;
+                if b & 0x80 == 0 {
+                    return n;
+                }
+                shift += 7;
+            }
+        }
+
+        fn zigzag(&mut self) -> i64 {
+            let n = self.varint();
+            (n >> 1) as i64 ^ -((n & 1) as i64)
+        }
+
+        fn value(&mut self, kind: u8) -> T {
+            match kind {
+                T_I32 | T_I64 => T::Int(self.zigzag()),
+                T_BINARY => {
+                    let len = self.varint() as usize;
+                    self.pos += len;
+                    T::Bin(self.buf[self.pos - len..self.pos].to_vec())
+                }
+                T_LIST => {
+                    let header = self.byte();
+                    let len = match header >> 4 {
+                        15 => self.varint() as usize,
+                        len => len as usize,
+                    };
+                    T::List((0..len).map(|_| self.value(header & 0x0f)).collect())
+                }
+                T_STRUCT => self.structure(),
+                other => panic!("unexpected type {}", other),
+            }
+        }
+
+        fn structure(&mut self) -> T {
+            let (mut fields, mut last) = (BTreeMap::new(), 0i16);
+            loop {
+                let header = self.byte();
+                if header == 0 {
+                    return T::Struct(fields);
+                }
+                let id = match header >> 4 {
+                    0 => self.zigzag() as i16,
+                    delta => last + i16::from(delta),
+                };
+                fields.insert(id, self.value(header & 0x0f));
+                last = id;
+            }
+        }
+    }
+
+    fn footer(file: &[u8]) -> T {
+        assert_eq!(&file[..4], MAGIC);
+        assert_eq!(&file[file.len() - 4..], MAGIC);
+        let len = u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap());
+        let start = file.len() - 8 - len as usize;
+        let mut reader = Reader {
+            buf: file,
+            pos: start,
+        };
+        let meta = reader.structure();
+        assert_eq!(reader.pos, file.len() - 8, "footer length");
+        meta
+    }
+
+    // The definition levels and value bytes of the data page at `offset`.
+    fn page(file: &[u8], offset: usize, rows: usize) -> (Vec<bool>, Vec<u8>) {
+        let mut reader = Reader {
+            buf: file,
+            pos: offset,
+        };
+        let header = reader.structure();
+        assert_eq!(header.get(1).int(), i64::from(PAGE_DATA));
+        let size = header.get(3).int() as usize;
+        assert_eq!(header.get(2).int() as usize, size);
+        assert_eq!(header.get(5).get(1).int() as usize, rows);
+        assert_eq!(header.get(5).get(2).int(), i64::from(ENCODING_PLAIN));
+        let data = &file[reader.pos..reader.pos + size];
+
+        let levels_len = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+        let mut levels = Reader {
+            buf: &data[4..4 + levels_len],
+            pos: 0,
+        };
+        let mut present = Vec::new();
+        while levels.pos < levels.buf.len() {
+            let run = levels.varint();
+            assert_eq!(run & 1, 0, "only RLE runs are written");
+            let level = levels.byte() == 1;
+            present.extend(std::iter::repeat_n(level, (run >> 1) as usize));
+        }
+        (present, data[4 + levels_len..].to_vec())
+    }
+
+    #[test]
+    fn thrift_compact_encoding_matches_the_spec() {
+        assert_eq!(zigzag(0), 0);
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+        assert_eq!(zigzag(-64), 127);
+        assert_eq!(zigzag(i64::MIN), u64::MAX);
+        let mut buf = Vec::new();
+        varint(&mut buf, 300);
+        assert_eq!(buf, [0xac, 0x02]);
+
+        let mut t = Thrift::default();
+        // Field 1, i32 1: delta 1, type 5, then zigzag(1).
+        t.i32(1, 1);
+        // Field 3, binary "ab": delta 2, type 8, then length and bytes.
+        t.binary(3, b"ab");
+        // Field 20 is a delta of 17, too far for the short form.
+        t.i64(20, -1);
+        // A list of two i32s: size 2, element type 5.
+        t.list(21, T_I32, 2);
+        t.element_i32(0);
+        t.element_i32(3);
+        assert_eq!(
+            t.finish(),
+            [0x15, 0x02, 0x28, 0x02, b'a', b'b', 0x06, 0x28, 0x01, 0x19, 0x25, 0x00, 0x06, 0x00]
+        );
+    }
+
+    #[test]
+    fn writes_a_readable_file() {
+        let rows = vec![
+            json!({"wallet": "A", "lamports": 5, "ok": true, "watched_as": ["topup"]}),
+            json!({"wallet": "B", "lamports": null, "ok": false, "ratio": 0.5}),
+            json!({"wallet": "C", "lamports": -7, "ok": true, "ratio": 2}),
+        ];
+        let file = write(&rows);
+        let meta = footer(&file);
+        assert_eq!(meta.get(1).int(), 1);
+        assert_eq!(meta.get(3).int(), 3);
+        assert_eq!(meta.get(6).bin(), b"solana-axum-server");
+
+        let schema = meta.get(2).list();
+        assert_eq!(schema[0].get(4).bin(), b"schema");
+        assert_eq!(schema[0].get(5).int(), 5);
+        let expected = [
+            ("lamports", TYPE_INT64),
+            ("ok", TYPE_BOOLEAN),
+            ("ratio", TYPE_DOUBLE),
+            ("wallet", TYPE_BYTE_ARRAY),
+            ("watched_as", TYPE_BYTE_ARRAY),
+        ];
+        for (element, (name, physical)) in schema[1..].iter().zip(expected) {
+            assert_eq!(element.get(4).bin(), name.as_bytes());
+            assert_eq!(element.get(1).int(), i64::from(physical));
+            assert_eq!(element.get(3).int(), i64::from(REPETITION_OPTIONAL));
+            assert_eq!(element.has(6), physical == TYPE_BYTE_ARRAY);
+        }
+
+        let row_groups = meta.get(4).list();
+        assert_eq!(row_groups.len(), 1);
+        assert_eq!(row_groups[0].get(3).int(), 3);
+        let chunks = row_groups[0].get(1).list();
+        let page_of = |i: usize| {
+            let column = chunks[i].get(3);
+            assert_eq!(column.get(3).list()[0].bin(), expected[i].0.as_bytes());
+            assert_eq!(column.get(4).int(), i64::from(CODEC_UNCOMPRESSED));
+            page(&file, column.get(9).int() as usize, 3)
+        };
+
+        let (present, data) = page_of(0);
+        assert_eq!(present, [true, false, true]);
+        let mut lamports = 5i64.to_le_bytes().to_vec();
+        lamports.extend_from_slice(&(-7i64).to_le_bytes());
+        assert_eq!(data, lamports);
+
+        let (present, data) = page_of(1);
+        assert_eq!(present, [true, true, true]);
+        assert_eq!(data, [0b101]);
+
+        let (present, data) = page_of(2);
+        assert_eq!(present, [false, true, true]);
+        let mut ratios = 0.5f64.to_le_bytes().to_vec();
+        ratios.extend_from_slice(&2f64.to_le_bytes());
+        assert_eq!(data, ratios);
+
+        let (present, data) = page_of(3);
+        assert_eq!(present, [true, true, true]);
+        assert_eq!(data, b"\x01\0\0\0A\x01\0\0\0B\x01\0\0\0C");
+
+        let (present, data) = page_of(4);
+        assert_eq!(present, [true, false, false]);
+        assert_eq!(data, b"\x09\0\0\0[\"topup\"]");
+
+        // Chunks are laid out back to back after the magic.
+        let total: i64 = chunks.iter().map(|c| c.get(3).get(7).int()).sum();
+        assert_eq!(row_groups[0].get(2).int(), total);
+        assert_eq!(chunks[0].get(2).int(), 4);
+    }
+
+    // The same file as above, read back by the `parquet` crate rather than
+    // by this module's own idea of the format.
+    #[test]
+    fn independent_reader_reads_the_file() {
+        use ::parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let rows = vec![
+            json!({"wallet": "A", "lamports": 5, "ok": true, "watched_as": ["topup"]}),
+            json!({"wallet": "B", "lamports": null, "ok": false, "ratio": 0.5}),
+            json!({"wallet": "C", "lamports": -7, "ok": true, "ratio": 2}),
+        ];
+        let path = std::env::temp_dir().join(format!("export-{}.parquet", std::process::id()));
+        std::fs::write(&path, write(&rows)).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let read: Vec<Value> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().to_json_value())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+        assert_eq!(
+            read,
+            [
+                json!({"wallet": "A", "lamports": 5, "ok": true, "ratio": null, "watched_as": "[\"topup\"]"}),
+                json!({"wallet": "B", "lamports": null, "ok": false, "ratio": 0.5, "watched_as": null}),
+                json!({"wallet": "C", "lamports": -7, "ok": true, "ratio": 2.0, "watched_as": null}),
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_an_empty_file() {
+        let meta = footer(&write(&[]));
+        assert_eq!(meta.get(3).int(), 0);
+        assert_eq!(meta.get(2).list().len(), 1);
+    }
+}
@@ -0,0 +1,201 @@
+// The store's Postgres connection, through tokio-postgres. The Store trait
+// is blocking and is called from async handlers, so the client lives on a
+// thread of its own with a single-threaded runtime; queries are handed to
+// it and their rows waited for.
+use std::{sync::mpsc as std_mpsc, thread};
+use tokio::sync::mpsc;
+use tokio_postgres::{types::ToSql, Client, NoTls};
+
+use crate::store::Connection;
+
+struct Query {
+    sql: String,
+    params: Vec<String>,
+    reply: std_mpsc::Sender<Result<Vec<String>, String>>,
+}
+
+pub struct Postgres {
+    queries: mpsc::UnboundedSender<Query>,
+}
+
+async fn connect(conninfo: &str) -> Result<Client, String> {
+    let (client, connection) = tokio_postgres::connect(conninfo, NoTls)
+        .await
+        .map_err(|e| format!("failed to connect to postgres: {}", e))?;
+    // Notices ("relation already exists, skipping") are dropped here.
+    tokio::spawn(connection);
+    Ok(client)
+}
+
+async fn run(client: &Client, sql: &str, params: &[String]) -> Result<Vec<String>, String> {
+    let params: Vec<&(dyn ToSql + Sync)> = params.iter().map(|p| p as _).collect();
+    let rows = client
+        .query(sql, &params)
+        .await
+        .map_err(|e| e.as_db_error().map_or(e.to_string(), |db| db.to_string()))?;
+    rows.iter()
+        .map(|row| {
+            row.try_get::<_, Option<String>>(0)
+                .map(Option::unwrap_or_default)
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+impl Postgres {
+    // `conninfo` is a postgres:// URL or key=value settings. TLS isn't
+    // supported; the store is expected on a private network.
+    pub fn connect(conninfo: &str) -> Result<Self, String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| format!("failed to start postgres client: {}", e))?;
+        let conninfo = conninfo.to_string();
+        let (queries, mut incoming) = mpsc::unbounded_channel::<Query>();
+        let (connected, outcome) = std_mpsc::channel();
+        thread::Builder::new()
+            .name("postgres-store".to_string())
+            .spawn(move || {
+                runtime.block_on(async move {
+                    let mut client = match connect(&conninfo).await {
+                        Ok(client) => client,
+                        Err(e) => {
+                            let _ = connected.send(Err(e));
+                            return;
+                        }
+                    };
+                    let _ = connected.send(Ok(()));
+                    while let Some(query) = incoming.recv().await {
+                        // Reconnects once if the server went away, e.g.
+                        // after a restart.
+                        if client.is_closed() {
+                            if let Ok(fresh) = connect(&conninfo).await {
+                                client = fresh;
+                            }
+                        }
+                        let result = run(&client, &query.sql, &query.params).await;
+                        let _ = query.reply.send(result);
+                    }
+                })
+            })
+            .map_err(|e| format!("failed to start postgres client: {}", e))?;
+        outcome
+            .recv()
+            .map_err(|_| "failed to connect to postgres: client stopped".to_string())??;
+        Ok(Postgres { queries })
+    }
+}
+
+impl Connection for Postgres {
+    const ID_COLUMN: &'static str = "BIGSERIAL PRIMARY KEY";
+
+    fn query(&mut self, sql: &str, params: &[&str]) -> Result<Vec<String>, String> {
+        let (reply, result) = std_mpsc::channel();
+        self.queries
+            .send(Query {
+                sql: sql.to_string(),
+                params: params.iter().map(|p| p.to_string()).collect(),
+                reply,
+            })
+            .map_err(|_| "postgres client stopped".to_string())?;
+        result
+            .recv()
+            .map_err(|_| "postgres client stopped".to_string())?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{SqlStore, Store};
+
+    // Stands in for the client thread: answers each query with `rows` and
+    // hands back the SQL and parameters it was sent.
+    fn fake(
+        rows: fn(&str) -> Vec<String>,
+    ) -> (Postgres, std_mpsc::Receiver<(String, Vec<String>)>) {
+        let (queries, mut incoming) = mpsc::unbounded_channel::<Query>();
+        let (seen, received) = std_mpsc::channel();
+        thread::spawn(move || {
+            while let Some(query) = incoming.blocking_recv() {
+                let result = Ok(rows(&query.sql));
+                let _ = seen.send((query.sql, query.params));
+                let _ = query.reply.send(result);
+            }
+        });
+        (Postgres { queries }, received)
+    }
+
+    #[test]
+    fn store_statements_and_parameters() {
+        let (conn, seen) = fake(|sql| match sql {
+            s if s.starts_with("SELECT line") => vec!["{\"a\":1}".into(), String::new()],
+            _ => Vec::new(),
+        });
+        let store = SqlStore::open(conn, "postgres test".to_string()).unwrap();
+        let schema: Vec<_> = seen.try_iter().map(|(sql, _)| sql).collect();
+        assert_eq!(schema.len(), 3);
+        assert!(
+            schema[1].contains("id BIGSERIAL PRIMARY KEY"),
+            "{}",
+            schema[1]
+        );
+
+        let tricky = "it's'); DROP TABLE t; --";
+        store.write("orgs", tricky).unwrap();
+        let (sql, params) = seen.recv().unwrap();
+        assert!(sql.starts_with("INSERT INTO store_documents"), "{}", sql);
+        assert!(sql.contains("VALUES ($1, $2)"), "{}", sql);
+        assert_eq!(params, ["orgs", tricky]);
+
+        store.append("audit_log", "{}").unwrap();
+        let (sql, params) = seen.recv().unwrap();
+        assert_eq!(
+            sql,
+            "INSERT INTO store_log_lines (name, line) VALUES ($1, $2)"
+        );
+        assert_eq!(params, ["audit_log", "{}"]);
+
+        // Log lines are joined, NULLs having come back empty.
+        assert_eq!(
+            store.read("audit_log").unwrap().as_deref(),
+            Some("{\"a\":1}\n\n")
+        );
+        let (sql, params) = seen.recv().unwrap();
+        assert_eq!(
+            sql,
+            "SELECT line FROM store_log_lines WHERE name = $1 ORDER BY id"
+        );
+        assert_eq!(params, ["audit_log"]);
+    }
+
+    #[test]
+    fn stopped_client_is_an_error() {
+        let (queries, incoming) = mpsc::unbounded_channel::<Query>();
+        drop(incoming);
+        let mut conn = Postgres { queries };
+        assert_eq!(
+            conn.query("SELECT 1", &[]).unwrap_err(),
+            "postgres client stopped"
+        );
+    }
+
+    #[test]
+    fn connect_errors_are_reported() {
+        // Nothing listens on port 1.
+        let err = Postgres::connect("host=127.0.0.1 port=1 connect_timeout=2")
+            .err()
+            .unwrap();
+        assert!(
+            err.starts_with("failed to connect to postgres: "),
+            "{}",
+            err
+        );
+        let err = Postgres::connect("postgres://[bad").err().unwrap();
+        assert!(
+            err.starts_with("failed to connect to postgres: "),
+            "{}",
+            err
+        );
+    }
+}
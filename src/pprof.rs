@@ -0,0 +1,185 @@
+use ::pprof::{protos::Message, ProfilerGuardBuilder, Report};
+use flate2::{write::GzEncoder, Compression};
+use std::{collections::BTreeMap, fmt::Write as _, io::Write as _, time::Duration};
+
+pub use crate::api::PprofFormat;
+
+// CPU profiles for /debug/pprof, sampled by the pprof crate: a SIGPROF
+// timer interrupts whichever thread is on the CPU FREQUENCY_HZ times a
+// CPU-second and records its stack. Stacks are symbolized once the window
+// is over and rendered as a flamegraph, folded stacks or a pprof protobuf.
+
+// Off the round 100 so sampling doesn't lock step with periodic work.
+const FREQUENCY_HZ: i32 = 99;
+// Unwinding through these can deadlock inside the signal handler, so
+// samples that land in them are skipped.
+const BLOCKLIST: [&str; 4] = ["libc", "libgcc", "pthread", "vdso"];
+
+#[derive(Debug)]
+pub enum ProfileError {
+    // Another profile is being taken.
+    Busy,
+    Failed(String),
+}
+
+impl From<::pprof::Error> for ProfileError {
+    fn from(e: ::pprof::Error) -> Self {
+        match e {
+            ::pprof::Error::Running => ProfileError::Busy,
+            e => ProfileError::Failed(format!("CPU profiling failed: {}", e)),
+        }
+    }
+}
+
+pub struct Profile {
+    report: Report,
+}
+
+impl Profile {
+    // Symbolized stacks, root first under their thread's name, with how
+    // many samples hit each.
+    pub fn stacks(&self) -> BTreeMap<Vec<String>, u64> {
+        let mut stacks = BTreeMap::new();
+        for (frames, count) in &self.report.data {
+            let stack: Vec<String> = std::iter::once(frames.thread_name_or_id())
+                .chain(
+                    frames
+                        .frames
+                        .iter()
+                        .rev()
+                        .flat_map(|frame| frame.iter().rev().map(|symbol| symbol.name())),
+                )
+                .collect();
+            *stacks.entry(stack).or_insert(0) += (*count).max(0) as u64;
+        }
+        stacks
+    }
+
+    pub fn render(&self, format: PprofFormat) -> Result<(&'static str, Vec<u8>), ProfileError> {
+        match format {
+            PprofFormat::Svg => {
+                let mut svg = Vec::new();
+                self.report.flamegraph(&mut svg)?;
+                Ok(("image/svg+xml", svg))
+            }
+            PprofFormat::Folded => Ok(("text/plain; charset=utf-8", folded(self).into_bytes())),
+            PprofFormat::Pprof => Ok(("application/octet-stream", encode(self)?)),
+        }
+    }
+}
+
+// Samples CPU stacks across the whole process for `window`. The profiler
+// stops however this ends, a caller hanging up mid-window included.
+pub async fn profile(window: Duration) -> Result<Profile, ProfileError> {
+    let guard = ProfilerGuardBuilder::default()
+        .frequency(FREQUENCY_HZ)
+        .blocklist(&BLOCKLIST)
+        .build()?;
+    tokio::time::sleep(window).await;
+    let report = tokio::task::spawn_blocking(move || guard.report().build())
+        .await
+        .map_err(|e| ProfileError::Failed(e.to_string()))??;
+    Ok(Profile { report })
+}
+
+// Brendan Gregg's collapsed format: `root;caller;leaf count` per line, as
+// read by flamegraph.pl, inferno and speedscope.
+pub fn folded(profile: &Profile) -> String {
+    let mut out = String::new();
+    for (stack, count) in profile.stacks() {
+        let _ = writeln!(out, "{} {}", stack.join(";"), count);
+    }
+    out
+}
+
+// The profile as gzipped profile.proto, for `go tool pprof`.
+fn encode(profile: &Profile) -> Result<Vec<u8>, ProfileError> {
+    let proto = profile
+        .report
+        .pprof()?
+        .write_to_bytes()
+        .map_err(|e| ProfileError::Failed(format!("Failed to encode the profile: {}", e)))?;
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(&proto)
+        .and_then(|_| gz.finish())
+        .map_err(|e| ProfileError::Failed(format!("Failed to compress the profile: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::pprof::protos;
+    use flate2::read::GzDecoder;
+    use std::{
+        io::Read,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    #[inline(never)]
+    fn spin(done: &AtomicBool) -> u64 {
+        let mut x = 0u64;
+        while !done.load(Ordering::Relaxed) {
+            x = std::hint::black_box(x.wrapping_mul(6364136223846793005).wrapping_add(1));
+        }
+        x
+    }
+
+    // One test, as only one profile can be taken at a time.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn samples_a_busy_thread() {
+        // Spins until the profile is done: starting the profiler can take
+        // a while in a debug build, so a fixed spin may end too early.
+        let done = Arc::new(AtomicBool::new(false));
+        let busy = std::thread::Builder::new()
+            .name("spinner".into())
+            .spawn({
+                let done = done.clone();
+                move || spin(&done)
+            })
+            .unwrap();
+        let p = profile(Duration::from_millis(500)).await;
+        done.store(true, Ordering::Relaxed);
+        busy.join().unwrap();
+        let p = p.unwrap();
+
+        let stacks = p.stacks();
+        let samples: u64 = stacks.values().sum();
+        assert!(samples > 10, "only {} samples", samples);
+        assert!(
+            stacks
+                .keys()
+                .any(|stack| stack[0] == "spinner"
+                    && stack.iter().any(|f| f.ends_with("tests::spin")))
+        );
+
+        let text = folded(&p);
+        assert_eq!(text.lines().count(), stacks.len());
+        assert!(text.lines().any(|l| l.starts_with("spinner;")));
+
+        let (content_type, svg) = p.render(PprofFormat::Svg).unwrap();
+        assert_eq!(content_type, "image/svg+xml");
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.contains("tests::spin"));
+
+        let (_, gz) = p.render(PprofFormat::Pprof).unwrap();
+        let mut raw = Vec::new();
+        GzDecoder::new(&gz[..]).read_to_end(&mut raw).unwrap();
+        let decoded = protos::Profile::parse_from_bytes(&raw).unwrap();
+        assert!(!decoded.sample.is_empty());
+        assert!(decoded
+            .string_table
+            .iter()
+            .any(|s| s.ends_with("tests::spin")));
+
+        // A second profile can start once the first is done, not during it.
+        let _guard = ProfilerGuardBuilder::default().build().unwrap();
+        assert!(matches!(
+            profile(Duration::from_millis(10)).await,
+            Err(ProfileError::Busy)
+        ));
+    }
+}
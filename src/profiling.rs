@@ -0,0 +1,225 @@
+use std::{
+    collections::HashMap,
+    fs,
+    time::{Duration, Instant},
+};
+use tokio::runtime::Handle;
+
+pub use crate::api::{CpuProfile, RuntimeReport, ThreadCpu, WorkerReport};
+
+// Sampling windows, in seconds, of /debug/pprof and /debug/cpu, and of
+// /debug/tokio.
+pub const DEFAULT_CPU_SECS: u64 = 10;
+pub const DEFAULT_TOKIO_SECS: u64 = 1;
+pub const MAX_SECS: u64 = 60;
+// /proc reports CPU time in USER_HZ ticks, 100 a second on every Linux.
+const TICKS_PER_SEC: u64 = 100;
+// A worker this busy that never parked over the window is stuck on
+// something that isn't yielding, e.g. a blocking call.
+const BLOCKED_BUSY_PERCENT: f64 = 95.0;
+
+// Name and CPU ticks (user and system) of every thread of this process.
+fn thread_ticks() -> Result<HashMap<u32, (String, u64)>, String> {
+    let tasks = fs::read_dir("/proc/self/task")
+        .map_err(|e| format!("Per-thread CPU needs Linux /proc: {}", e))?;
+    let mut threads = HashMap::new();
+    for task in tasks.flatten() {
+        let Some(tid) = task.file_name().to_str().and_then(|t| t.parse().ok()) else {
+            continue;
+        };
+        // The thread may have exited since the listing.
+        let Ok(stat) = fs::read_to_string(task.path().join("stat")) else {
+            continue;
+        };
+        // `tid (name) state ...`; the name can hold spaces and parentheses.
+        let (Some(open), Some(close)) = (stat.find('('), stat.rfind(')')) else {
+            continue;
+        };
+        let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+        // utime and stime, fields 14 and 15 of stat(5), counting from the state.
+        let ticks = |i: usize| {
+            fields
+                .get(i)
+                .and_then(|f| f.parse::<u64>().ok())
+                .unwrap_or_default()
+        };
+        threads.insert(
+            tid,
+            (stat[open + 1..close].to_string(), ticks(11) + ticks(12)),
+        );
+    }
+    Ok(threads)
+}
+
+fn percent(part: Duration, whole: Duration) -> f64 {
+    if whole.is_zero() {
+        return 0.0;
+    }
+    (part.as_secs_f64() / whole.as_secs_f64() * 1_000.0).round() / 10.0
+}
+
+// CPU time of each thread over `window`: which threads (runtime workers,
+// the blocking pool running RPC calls, ...) the process is busy in.
+pub async fn cpu(window: Duration) -> Result<CpuProfile, String> {
+    let before = thread_ticks()?;
+    let started = Instant::now();
+    tokio::time::sleep(window).await;
+    let after = thread_ticks()?;
+    let elapsed = started.elapsed();
+
+    let mut threads: Vec<ThreadCpu> = after
+        .into_iter()
+        .filter_map(|(tid, (name, ticks))| {
+            let start = before.get(&tid).map(|(_, t)| *t).unwrap_or_default();
+            let cpu_ms = ticks.saturating_sub(start) * 1_000 / TICKS_PER_SEC;
+            (cpu_ms > 0).then(|| ThreadCpu {
+                tid,
+                name,
+                cpu_ms,
+                cpu_percent: percent(Duration::from_millis(cpu_ms), elapsed),
+            })
+        })
+        .collect();
+    threads.sort_by(|a, b| b.cpu_ms.cmp(&a.cpu_ms).then(a.tid.cmp(&b.tid)));
+    Ok(CpuProfile {
+        window_ms: elapsed.as_millis() as u64,
+        cpu_ms: threads.iter().map(|t| t.cpu_ms).sum(),
+        threads,
+    })
+}
+
+// How busy each runtime worker was over `window`, flagging the ones that
+// never parked while almost always busy.
+pub async fn runtime(window: Duration) -> RuntimeReport {
+    let metrics = Handle::current().metrics();
+    let workers = metrics.num_workers();
+    let sample = || {
+        (0..workers)
+            .map(|w| {
+                (
+                    metrics.worker_total_busy_duration(w),
+                    metrics.worker_park_count(w),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let before = sample();
+    let started = Instant::now();
+    tokio::time::sleep(window).await;
+    let after = sample();
+    let elapsed = started.elapsed();
+
+    let worker_stats: Vec<WorkerReport> = before
+        .into_iter()
+        .zip(after)
+        .enumerate()
+        .map(|(worker, ((busy_before, parks_before), (busy, parks)))| {
+            let busy = busy.saturating_sub(busy_before);
+            let parks = parks.saturating_sub(parks_before);
+            let busy_percent = percent(busy, elapsed);
+            WorkerReport {
+                worker,
+                busy_ms: busy.as_millis() as u64,
+                busy_percent,
+                parks,
+                blocked: parks == 0 && busy_percent >= BLOCKED_BUSY_PERCENT,
+            }
+        })
+        .collect();
+    RuntimeReport {
+        window_ms: elapsed.as_millis() as u64,
+        workers,
+        alive_tasks: metrics.num_alive_tasks(),
+        global_queue_depth: metrics.global_queue_depth(),
+        blocked_workers: worker_stats.iter().filter(|w| w.blocked).count(),
+        worker_stats,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentages_round_to_a_tenth() {
+        let ms = Duration::from_millis;
+        assert_eq!(percent(ms(250), ms(1_000)), 25.0);
+        assert_eq!(percent(ms(1), ms(3)), 33.3);
+        assert_eq!(percent(ms(2), ms(3)), 66.7);
+        assert_eq!(percent(ms(2_000), ms(1_000)), 200.0);
+        assert_eq!(percent(ms(5), Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn threads_are_read_from_proc() {
+        let (started, running) = std::sync::mpsc::channel();
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let named = std::thread::Builder::new()
+            .name("ticks (test) 1".to_string())
+            .spawn(move || {
+                started.send(()).unwrap();
+                let _ = stopped.recv();
+            })
+            .unwrap();
+        running.recv().unwrap();
+        let threads = thread_ticks().unwrap();
+        // Names with spaces and parentheses survive.
+        assert!(threads.values().any(|(name, _)| name == "ticks (test) 1"));
+        stop.send(()).unwrap();
+        named.join().unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn busy_threads_show_up_in_the_profile() {
+        let spinner = std::thread::Builder::new()
+            .name("spinner".to_string())
+            .spawn(|| {
+                let started = Instant::now();
+                while started.elapsed() < Duration::from_millis(600) {
+                    std::hint::spin_loop();
+                }
+            })
+            .unwrap();
+        let profile = cpu(Duration::from_millis(300)).await.unwrap();
+        spinner.join().unwrap();
+
+        assert!(profile.window_ms >= 300);
+        let spinning = profile
+            .threads
+            .iter()
+            .find(|t| t.name == "spinner")
+            .unwrap();
+        assert!(spinning.cpu_ms >= 100, "{}", spinning.cpu_ms);
+        assert!(spinning.cpu_percent > 0.0);
+        assert_eq!(
+            profile.cpu_ms,
+            profile.threads.iter().map(|t| t.cpu_ms).sum::<u64>()
+        );
+        assert!(profile
+            .threads
+            .windows(2)
+            .all(|pair| pair[0].cpu_ms >= pair[1].cpu_ms));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn runtime_reports_every_worker() {
+        let report = runtime(Duration::from_millis(50)).await;
+        assert!(report.window_ms >= 50);
+        assert_eq!(report.workers, 2);
+        assert_eq!(
+            report
+                .worker_stats
+                .iter()
+                .map(|w| w.worker)
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert_eq!(
+            report.blocked_workers,
+            report.worker_stats.iter().filter(|w| w.blocked).count()
+        );
+        for worker in &report.worker_stats {
+            assert!(!worker.blocked || worker.parks == 0);
+        }
+    }
+}
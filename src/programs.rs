@@ -0,0 +1,361 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+
+use crate::{service::ServiceError, store::Store};
+
+pub use crate::api::{KnownProgram, ProgramSource};
+
+const STORE_NAME: &str = "programs";
+const MAX_NAME_LENGTH: usize = 64;
+
+// Shipped names, by program id.
+const BUILTIN: &[(&str, &str)] = &[
+    ("11111111111111111111111111111111", "System Program"),
+    (
+        "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+        "Token Program",
+    ),
+    (
+        "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+        "Token-2022 Program",
+    ),
+    (
+        "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+        "Associated Token Account Program",
+    ),
+    (
+        "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+        "Memo Program",
+    ),
+    (
+        "Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo",
+        "Memo Program (v1)",
+    ),
+    (
+        "ComputeBudget111111111111111111111111111111",
+        "Compute Budget Program",
+    ),
+    (
+        "Stake11111111111111111111111111111111111111",
+        "Stake Program",
+    ),
+    (
+        "Vote111111111111111111111111111111111111111",
+        "Vote Program",
+    ),
+    (
+        "Config1111111111111111111111111111111111111",
+        "Config Program",
+    ),
+    (
+        "AddressLookupTab1e1111111111111111111111111",
+        "Address Lookup Table Program",
+    ),
+    (
+        "BPFLoader1111111111111111111111111111111111",
+        "BPF Loader (deprecated)",
+    ),
+    ("BPFLoader2111111111111111111111111111111111", "BPF Loader"),
+    (
+        "BPFLoaderUpgradeab1e11111111111111111111111",
+        "BPF Upgradeable Loader",
+    ),
+    (
+        "Ed25519SigVerify111111111111111111111111111",
+        "Ed25519 Program",
+    ),
+    (
+        "KeccakSecp256k11111111111111111111111111111",
+        "Secp256k1 Program",
+    ),
+    (
+        "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s",
+        "Metaplex Token Metadata",
+    ),
+    (
+        "BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY",
+        "Metaplex Bubblegum",
+    ),
+    (
+        "CndyV3LdqHUfDLmE5naZjVN8rBZz4tqhdefbAnjHG3JR",
+        "Metaplex Candy Machine v3",
+    ),
+    (
+        "Guard1JwRhJkVH6XZhzoYxeBVQe872VH6QggF4BWmS9g",
+        "Metaplex Candy Guard",
+    ),
+    (
+        "cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK",
+        "SPL Account Compression",
+    ),
+    ("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV", "SPL Noop"),
+    (
+        "SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy",
+        "SPL Stake Pool",
+    ),
+    (
+        "GovER5Lthms3bLBqWub97yVrMmEogzX7xNjdXpPPCVZw",
+        "SPL Governance",
+    ),
+    (
+        "namesLPneVptA9Z5rqUDD9tMTWEJwofgaYwp8cawRkX",
+        "SPL Name Service",
+    ),
+    (
+        "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4",
+        "Jupiter Aggregator v6",
+    ),
+    (
+        "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc",
+        "Orca Whirlpools",
+    ),
+    (
+        "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8",
+        "Raydium AMM v4",
+    ),
+    (
+        "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK",
+        "Raydium CLMM",
+    ),
+    ("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX", "OpenBook"),
+    ("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY", "Phoenix"),
+    (
+        "MarBmsSgKXdrN1egZf5sqe1TMai9K1rChYNDJgjq7aD",
+        "Marinade Finance",
+    ),
+    (
+        "dRiftyHA39MWEi3m9aunc5MzRF1JYuBsbn6VPcn33UH",
+        "Drift Protocol v2",
+    ),
+    (
+        "KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD",
+        "Kamino Lending",
+    ),
+    ("SQDS4ep65T869zMMBKyuUq6aD6EgTu8psMjkvj52pCf", "Squads v4"),
+    (
+        "FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH",
+        "Pyth Oracle",
+    ),
+];
+
+#[derive(Serialize, Deserialize)]
+struct SavedProgram {
+    program_id: String,
+    name: String,
+}
+
+// Friendly names for program ids, shown next to owners and instruction
+// programs in account and transaction responses. Ships with BUILTIN;
+// additions are saved as JSON under "programs" in the store
+// (`programs_path` for files).
+pub struct ProgramRegistry {
+    store: Arc<dyn Store>,
+    builtin: HashMap<Pubkey, &'static str>,
+    custom: RwLock<BTreeMap<Pubkey, String>>,
+}
+
+impl ProgramRegistry {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let builtin = BUILTIN
+            .iter()
+            .map(|(id, name)| {
+                let id = Pubkey::from_str(id).expect("builtin program ids are valid");
+                (id, *name)
+            })
+            .collect();
+        let mut custom = BTreeMap::new();
+        if let Some(text) = store.read(STORE_NAME)? {
+            let saved: Vec<SavedProgram> = serde_json::from_str(&text).map_err(|e| {
+                format!("invalid programs in {}: {}", store.location(STORE_NAME), e)
+            })?;
+            for program in saved {
+                let id = Pubkey::from_str(&program.program_id).map_err(|_| {
+                    format!(
+                        "invalid program id '{}' in {}",
+                        program.program_id,
+                        store.location(STORE_NAME)
+                    )
+                })?;
+                custom.insert(id, program.name);
+            }
+        }
+        Ok(ProgramRegistry {
+            store,
+            builtin,
+            custom: RwLock::new(custom),
+        })
+    }
+
+    pub fn name(&self, program_id: &Pubkey) -> Option<String> {
+        if let Some(name) = self.custom.read().unwrap().get(program_id) {
+            return Some(name.clone());
+        }
+        self.builtin.get(program_id).map(|name| name.to_string())
+    }
+
+    // Every known program, custom names first, each sorted by name.
+    pub fn list(&self) -> Vec<KnownProgram> {
+        let custom = self.custom.read().unwrap();
+        let mut custom_programs: Vec<KnownProgram> = custom
+            .iter()
+            .map(|(id, name)| KnownProgram {
+                program_id: id.to_string(),
+                name: name.clone(),
+                source: ProgramSource::Custom,
+            })
+            .collect();
+        let mut builtin: Vec<KnownProgram> = self
+            .builtin
+            .iter()
+            .filter(|(id, _)| !custom.contains_key(id))
+            .map(|(id, name)| KnownProgram {
+                program_id: id.to_string(),
+                name: name.to_string(),
+                source: ProgramSource::Builtin,
+            })
+            .collect();
+        custom_programs.sort_by(|a, b| a.name.cmp(&b.name));
+        builtin.sort_by(|a, b| a.name.cmp(&b.name));
+        custom_programs.extend(builtin);
+        custom_programs
+    }
+
+    pub fn set(&self, program_id: Pubkey, name: &str) -> Result<KnownProgram, ServiceError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            return Err(ServiceError::Invalid(format!(
+                "Program names must be 1-{} characters",
+                MAX_NAME_LENGTH
+            )));
+        }
+        let mut custom = self.custom.write().unwrap();
+        let previous = custom.insert(program_id, name.to_string());
+        if let Err(e) = self.save(&custom) {
+            match previous {
+                Some(previous) => custom.insert(program_id, previous),
+                None => custom.remove(&program_id),
+            };
+            return Err(ServiceError::Rpc {
+                action: "Failed to save program",
+                message: e,
+            });
+        }
+        Ok(KnownProgram {
+            program_id: program_id.to_string(),
+            name: name.to_string(),
+            source: ProgramSource::Custom,
+        })
+    }
+
+    // Only custom names can be removed; a builtin name comes back when the
+    // custom one replacing it is removed.
+    pub fn remove(&self, program_id: &Pubkey) -> Result<bool, String> {
+        let mut custom = self.custom.write().unwrap();
+        let Some(previous) = custom.remove(program_id) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.save(&custom) {
+            custom.insert(*program_id, previous);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn save(&self, custom: &BTreeMap<Pubkey, String>) -> Result<(), String> {
+        if !self.store.persists(STORE_NAME) {
+            return Ok(());
+        }
+        let saved: Vec<SavedProgram> = custom
+            .iter()
+            .map(|(id, name)| SavedProgram {
+                program_id: id.to_string(),
+                name: name.clone(),
+            })
+            .collect();
+        let text = serde_json::to_string_pretty(&saved).map_err(|e| e.to_string())?;
+        self.store.write(STORE_NAME, &text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::FlakyStore;
+
+    fn registry(store: &Arc<FlakyStore>) -> ProgramRegistry {
+        ProgramRegistry::load(store.clone()).unwrap()
+    }
+
+    #[test]
+    fn builtin_names() {
+        let programs = registry(&Arc::new(FlakyStore::default()));
+        assert_eq!(
+            programs.name(&solana_sdk::system_program::id()).as_deref(),
+            Some("System Program")
+        );
+        assert_eq!(programs.name(&Pubkey::new_unique()), None);
+        let list = programs.list();
+        assert_eq!(list.len(), BUILTIN.len());
+        assert_eq!(list[0].name, "Address Lookup Table Program");
+        assert!(list.windows(2).all(|w| w[0].name <= w[1].name));
+    }
+
+    #[test]
+    fn custom_names_override_and_persist() {
+        let store = Arc::new(FlakyStore::default());
+        let programs = registry(&store);
+        let (system, mine) = (solana_sdk::system_program::id(), Pubkey::new_unique());
+        programs.set(system, " The System ").unwrap();
+        programs.set(mine, "Zeta").unwrap();
+        assert_eq!(programs.name(&system).as_deref(), Some("The System"));
+
+        let list = programs.list();
+        let head: Vec<_> = list[..2].iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(head, ["The System", "Zeta"]);
+        assert!(matches!(list[0].source, ProgramSource::Custom));
+        assert!(matches!(list[2].source, ProgramSource::Builtin));
+        // The builtin entry is replaced, not listed twice.
+        assert_eq!(list.len(), BUILTIN.len() + 1);
+
+        assert_eq!(registry(&store).name(&mine).as_deref(), Some("Zeta"));
+        assert!(programs.remove(&system).unwrap());
+        assert!(!programs.remove(&system).unwrap());
+        assert_eq!(programs.name(&system).as_deref(), Some("System Program"));
+    }
+
+    #[test]
+    fn invalid_or_unsaved_changes_change_nothing() {
+        let store = Arc::new(FlakyStore::default());
+        let programs = registry(&store);
+        let id = Pubkey::new_unique();
+        for name in ["  ", &"x".repeat(65)] {
+            match programs.set(id, name) {
+                Err(ServiceError::Invalid(message)) => {
+                    assert_eq!(message, "Program names must be 1-64 characters")
+                }
+                _ => panic!("expected a validation error"),
+            }
+        }
+        programs.set(id, "Mine").unwrap();
+        store.fail(true);
+        assert!(programs.set(id, "Renamed").is_err());
+        assert!(programs.remove(&id).is_err());
+        store.fail(false);
+        assert_eq!(programs.name(&id).as_deref(), Some("Mine"));
+    }
+
+    #[test]
+    fn saved_ids_are_checked_on_load() {
+        let store = Arc::new(FlakyStore::default());
+        store
+            .write(STORE_NAME, r#"[{"program_id":"nope","name":"Bad"}]"#)
+            .unwrap();
+        let err = ProgramRegistry::load(store).err().unwrap();
+        assert!(err.starts_with("invalid program id 'nope' in "), "{}", err);
+    }
+}
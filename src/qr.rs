@@ -0,0 +1,900 @@
+use flate2::{write::ZlibEncoder, Compression};
+use std::{fmt::Write as _, io::Write as _};
+
+use crate::service::{self, ServiceError};
+
+pub use crate::api::{EcLevel, QrFormat};
+
+// QR codes (ISO/IEC 18004) in byte mode, for GET /qr. Picks the smallest
+// version (1-40) that holds the data at the requested error correction
+// level, and the mask with the lowest penalty.
+
+pub const DEFAULT_SIZE: u32 = 256;
+pub const MIN_SIZE: u32 = 64;
+pub const MAX_SIZE: u32 = 2_048;
+// The quiet zone around the code, in modules. Four is what the standard
+// asks for.
+pub const DEFAULT_MARGIN: u32 = 4;
+pub const MAX_MARGIN: u32 = 16;
+
+// The two bits for `ec` in the format information.
+fn level_bits(ec: EcLevel) -> u32 {
+    match ec {
+        EcLevel::L => 1,
+        EcLevel::M => 0,
+        EcLevel::Q => 3,
+        EcLevel::H => 2,
+    }
+}
+
+// By error correction level (L, M, Q, H), then version; version 0 is unused.
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [
+        0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28,
+        30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28,
+        28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    ],
+    [
+        0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30,
+        30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24,
+        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+];
+
+const ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [
+        0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13,
+        14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25,
+    ],
+    [
+        0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21,
+        23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+    ],
+    [
+        0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29,
+        34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68,
+    ],
+    [
+        0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32,
+        35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81,
+    ],
+];
+
+pub struct QrCode {
+    // Modules per side.
+    size: usize,
+    // Row by row, true for dark.
+    modules: Vec<bool>,
+    // Finder, timing, alignment and format modules, which masks skip.
+    function: Vec<bool>,
+}
+
+// Modules left for data and error correction once the function patterns
+// are drawn.
+fn raw_data_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignments = version / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+fn data_codewords(version: usize, ec: EcLevel) -> usize {
+    let ec = ec as usize;
+    raw_data_modules(version) / 8
+        - ECC_CODEWORDS_PER_BLOCK[ec][version] as usize
+            * ERROR_CORRECTION_BLOCKS[ec][version] as usize
+}
+
+fn count_bits(version: usize) -> usize {
+    if version < 10 {
+        8
+    } else {
+        16
+    }
+}
+
+// Bytes `data` can hold at most at `ec`, in the largest version.
+pub fn capacity(ec: EcLevel) -> usize {
+    (data_codewords(40, ec) * 8 - 4 - count_bits(40)) / 8
+}
+
+pub fn encode(data: &[u8], ec: EcLevel) -> Result<QrCode, ServiceError> {
+    let version = (1..=40)
+        .find(|&version| {
+            data.len() < 1 << count_bits(version)
+                && 4 + count_bits(version) + 8 * data.len() <= data_codewords(version, ec) * 8
+        })
+        .ok_or_else(|| {
+            ServiceError::Invalid(format!(
+                "{} bytes don't fit in a QR code at level {:?} (at most {})",
+                data.len(),
+                ec,
+                capacity(ec)
+            ))
+        })?;
+
+    // Byte mode, the length, the data, then a terminator and padding.
+    let capacity_bits = data_codewords(version, ec) * 8;
+    let mut bits = Vec::with_capacity(capacity_bits);
+    let push = |bits: &mut Vec<bool>, value: usize, count: usize| {
+        bits.extend((0..count).rev().map(|i| (value >> i) & 1 != 0));
+    };
+    push(&mut bits, 0b0100, 4);
+    push(&mut bits, data.len(), count_bits(version));
+    for &byte in data {
+        push(&mut bits, byte as usize, 8);
+    }
+    let terminator = (capacity_bits - bits.len()).min(4);
+    push(&mut bits, 0, terminator);
+    let to_byte = (8 - bits.len() % 8) % 8;
+    push(&mut bits, 0, to_byte);
+    for pad in [0xEC, 0x11].into_iter().cycle() {
+        if bits.len() >= capacity_bits {
+            break;
+        }
+        push(&mut bits, pad, 8);
+    }
+    let codewords: Vec<u8> = bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+        .collect();
+
+    let mut qr = QrCode::new(version);
+    qr.draw_function_patterns(version);
+    qr.draw_codewords(&with_error_correction(&codewords, version, ec));
+    let mask = (0..8)
+        .min_by_key(|&mask| {
+            qr.apply_mask(mask);
+            qr.draw_format_bits(ec, mask);
+            let penalty = qr.penalty();
+            // Masks are their own inverse.
+            qr.apply_mask(mask);
+            penalty
+        })
+        .unwrap_or_default();
+    qr.apply_mask(mask);
+    qr.draw_format_bits(ec, mask);
+    Ok(qr)
+}
+
+// Splits the data into blocks, appends each block's error correction and
+// interleaves them.
+fn with_error_correction(data: &[u8], version: usize, ec: EcLevel) -> Vec<u8> {
+    let blocks = ERROR_CORRECTION_BLOCKS[ec as usize][version] as usize;
+    let ecc_len = ECC_CODEWORDS_PER_BLOCK[ec as usize][version] as usize;
+    let raw_codewords = raw_data_modules(version) / 8;
+    let short_blocks = blocks - raw_codewords % blocks;
+    let short_block_len = raw_codewords / blocks;
+    let divisor = reed_solomon_divisor(ecc_len);
+
+    let mut split = Vec::with_capacity(blocks);
+    let mut offset = 0;
+    for i in 0..blocks {
+        let len = short_block_len - ecc_len + usize::from(i >= short_blocks);
+        let mut block = data[offset..offset + len].to_vec();
+        offset += len;
+        let ecc = reed_solomon_remainder(&block, &divisor);
+        // Short blocks get a placeholder so every block lines up.
+        if i < short_blocks {
+            block.push(0);
+        }
+        block.extend(ecc);
+        split.push(block);
+    }
+
+    let mut interleaved = Vec::with_capacity(raw_codewords);
+    for i in 0..split[0].len() {
+        for (j, block) in split.iter().enumerate() {
+            if i != short_block_len - ecc_len || j >= short_blocks {
+                interleaved.push(block[i]);
+            }
+        }
+    }
+    interleaved
+}
+
+// Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1.
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z: u16 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((y as u16 >> i) & 1) * x as u16;
+    }
+    z as u8
+}
+
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut divisor = vec![0u8; degree];
+    divisor[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            divisor[j] = gf_multiply(divisor[j], root);
+            if j + 1 < degree {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    divisor
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut remainder = vec![0u8; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ remainder.remove(0);
+        remainder.push(0);
+        for (r, &d) in remainder.iter_mut().zip(divisor) {
+            *r ^= gf_multiply(d, factor);
+        }
+    }
+    remainder
+}
+
+// The centers of the version's alignment patterns, along either axis.
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = if version == 32 {
+        26
+    } else {
+        (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+    let mut positions = vec![6];
+    let mut position = version * 4 + 17 - 7;
+    for _ in 0..count - 1 {
+        positions.insert(1, position);
+        position -= step;
+    }
+    positions
+}
+
+// The 15 format information bits: the level and mask, a (15, 5) BCH code
+// of them, and the standard's XOR pattern.
+fn format_bits(ec: EcLevel, mask: u32) -> u32 {
+    let data = level_bits(ec) << 3 | mask;
+    let mut remainder = data;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    (data << 10 | remainder) ^ 0x5412
+}
+
+// The 18 version information bits of versions 7 and up: the version and an
+// (18, 6) Golay code of it.
+fn version_bits(version: usize) -> u32 {
+    let mut remainder = version as u32;
+    for _ in 0..12 {
+        remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+    }
+    (version as u32) << 12 | remainder
+}
+
+impl QrCode {
+    fn new(version: usize) -> Self {
+        let size = version * 4 + 17;
+        QrCode {
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        }
+    }
+
+    fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+        // Finder patterns with their separators.
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4isize..=4 {
+                for dx in -4isize..=4 {
+                    let (x, y) = (cx as isize + dx, cy as isize + dy);
+                    if (0..size as isize).contains(&x) && (0..size as isize).contains(&y) {
+                        let distance = dx.abs().max(dy.abs());
+                        self.set_function(x as usize, y as usize, distance != 2 && distance != 4);
+                    }
+                }
+            }
+        }
+        let positions = alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &cx) in positions.iter().enumerate() {
+            for (j, &cy) in positions.iter().enumerate() {
+                // Those corners are the finder patterns'.
+                if [(0, 0), (0, last), (last, 0)].contains(&(i, j)) {
+                    continue;
+                }
+                for dy in -2isize..=2 {
+                    for dx in -2isize..=2 {
+                        let distance = dx.abs().max(dy.abs());
+                        self.set_function(
+                            (cx as isize + dx) as usize,
+                            (cy as isize + dy) as usize,
+                            distance != 1,
+                        );
+                    }
+                }
+            }
+        }
+        // Reserved until the mask is chosen.
+        self.draw_format_bits(EcLevel::M, 0);
+        if version >= 7 {
+            let bits = version_bits(version);
+            for i in 0..18 {
+                let dark = (bits >> i) & 1 != 0;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.set_function(a, b, dark);
+                self.set_function(b, a, dark);
+            }
+        }
+    }
+
+    fn draw_format_bits(&mut self, ec: EcLevel, mask: u32) {
+        let bits = format_bits(ec, mask);
+        let bit = |i: usize| (bits >> i) & 1 != 0;
+        let size = self.size;
+
+        // Around the top left finder.
+        for i in 0..6 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+        // Split between the other two.
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    // Fills the non-function modules in the zigzag order, two columns at a
+    // time from the bottom right.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size as isize;
+        let mut i = 0;
+        let mut right = size - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            let upward = (right + 1) & 2 == 0;
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = (right - j) as usize;
+                    let y = (if upward {
+                        size - 1 - vertical
+                    } else {
+                        vertical
+                    }) as usize;
+                    if !self.function[y * self.size + x] && i < codewords.len() * 8 {
+                        self.modules[y * self.size + x] =
+                            (codewords[i / 8] >> (7 - i % 8)) & 1 != 0;
+                        i += 1;
+                    }
+                }
+            }
+            right -= 2;
+        }
+    }
+
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                let index = y * self.size + x;
+                if invert && !self.function[index] {
+                    self.modules[index] = !self.modules[index];
+                }
+            }
+        }
+    }
+
+    // The standard's penalty score of the masked symbol: long runs, 2x2
+    // blocks, finder-like patterns and an uneven dark/light balance.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let mut penalty = 0;
+        for horizontal in [true, false] {
+            for i in 0..size {
+                let line: Vec<bool> = (0..size)
+                    .map(|j| {
+                        if horizontal {
+                            self.is_dark(j, i)
+                        } else {
+                            self.is_dark(i, j)
+                        }
+                    })
+                    .collect();
+
+                let mut run = 1;
+                for j in 1..=size {
+                    if j < size && line[j] == line[j - 1] {
+                        run += 1;
+                        continue;
+                    }
+                    if run >= 5 {
+                        penalty += run - 2;
+                    }
+                    run = 1;
+                }
+
+                // Dark-light-dark-dark-dark-light-dark with four light
+                // modules (or the edge) on either side.
+                let dark = |j: isize| j >= 0 && (j as usize) < size && line[j as usize];
+                let light = |from: isize| (from..from + 4).all(|j| !dark(j));
+                for start in 0..size.saturating_sub(6) as isize {
+                    let finder = [true, false, true, true, true, false, true]
+                        .iter()
+                        .enumerate()
+                        .all(|(k, &d)| dark(start + k as isize) == d);
+                    if finder && (light(start - 4) || light(start + 7)) {
+                        penalty += 40;
+                    }
+                }
+            }
+        }
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let color = self.is_dark(x, y);
+                if color == self.is_dark(x + 1, y)
+                    && color == self.is_dark(x, y + 1)
+                    && color == self.is_dark(x + 1, y + 1)
+                {
+                    penalty += 3;
+                }
+            }
+        }
+        let total = size * size;
+        let dark = self.modules.iter().filter(|&&d| d).count();
+        let deviation = (dark * 100).abs_diff(total * 50) / total;
+        penalty + deviation / 5 * 10
+    }
+
+    // A `size` pixels wide SVG with a light background and `margin` light
+    // modules around the code.
+    pub fn to_svg(&self, size: u32, margin: u32) -> String {
+        let margin = margin as usize;
+        let width = self.size + margin * 2;
+        let mut path = String::new();
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if self.is_dark(x, y) {
+                    let _ = write!(path, "M{},{}h1v1h-1z", x + margin, y + margin);
+                }
+            }
+        }
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{size}\" height=\"{size}\" \
+             viewBox=\"0 0 {width} {width}\" shape-rendering=\"crispEdges\">\
+             <rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\
+             <path d=\"{path}\" fill=\"#000000\"/></svg>\n"
+        )
+    }
+
+    // A grayscale PNG at most `size` pixels wide: modules are a whole
+    // number of pixels, so the image is rounded down to fit.
+    pub fn to_png(&self, size: u32, margin: u32) -> Vec<u8> {
+        let margin = margin as usize;
+        let modules = self.size + margin * 2;
+        let scale = (size as usize / modules).max(1);
+        let width = modules * scale;
+
+        let mut raw = Vec::with_capacity((width + 1) * width);
+        for py in 0..width {
+            // No filter on any row.
+            raw.push(0);
+            let y = (py / scale).checked_sub(margin).filter(|&y| y < self.size);
+            for px in 0..width {
+                let x = (px / scale).checked_sub(margin).filter(|&x| x < self.size);
+                let dark = matches!((x, y), (Some(x), Some(y)) if self.is_dark(x, y));
+                raw.push(if dark { 0x00 } else { 0xFF });
+            }
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        let _ = encoder.write_all(&raw);
+        let compressed = encoder.finish().unwrap_or_default();
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((width as u32).to_be_bytes());
+        header.extend((width as u32).to_be_bytes());
+        // 8-bit grayscale, deflate, no interlacing.
+        header.extend([8, 0, 0, 0, 0]);
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut png, b"IHDR", &header);
+        png_chunk(&mut png, b"IDAT", &compressed);
+        png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+// A Solana Pay transfer request: solana:<recipient>?amount=...
+pub struct TransferRequest<'a> {
+    pub recipient: &'a str,
+    pub amount: Option<&'a str>,
+    pub spl_token: Option<&'a str>,
+    pub references: Vec<&'a str>,
+    pub label: Option<&'a str>,
+    pub message: Option<&'a str>,
+    pub memo: Option<&'a str>,
+}
+
+impl TransferRequest<'_> {
+    pub fn url(&self) -> Result<String, ServiceError> {
+        service::parse_wallet(self.recipient)?;
+        if let Some(token) = self.spl_token {
+            service::parse_wallet(token)
+                .map_err(|_| ServiceError::Invalid(format!("Invalid spl_token '{}'", token)))?;
+        }
+        for reference in &self.references {
+            service::parse_wallet(reference)
+                .map_err(|_| ServiceError::Invalid(format!("Invalid reference '{}'", reference)))?;
+        }
+        let mut params = Vec::new();
+        if let Some(amount) = self.amount {
+            // A plain decimal, in SOL or the token's units.
+            let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+            let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+            if whole.is_empty() || !digits(whole) || !digits(fraction) {
+                return Err(ServiceError::Invalid(format!(
+                    "Invalid amount '{}': use a decimal like '0.5'",
+                    amount
+                )));
+            }
+            params.push(("amount", amount));
+        }
+        if let Some(token) = self.spl_token {
+            params.push(("spl-token", token));
+        }
+        for reference in &self.references {
+            params.push(("reference", reference));
+        }
+        for (name, value) in [
+            ("label", self.label),
+            ("message", self.message),
+            ("memo", self.memo),
+        ] {
+            if let Some(value) = value {
+                params.push((name, value));
+            }
+        }
+
+        let mut url = format!("solana:{}", self.recipient);
+        for (i, (name, value)) in params.iter().enumerate() {
+            let _ = write!(
+                url,
+                "{}{}={}",
+                if i == 0 { '?' } else { '&' },
+                name,
+                encode_component(value)
+            );
+        }
+        Ok(url)
+    }
+}
+
+// Percent-encodes everything but the characters encodeURIComponent leaves.
+fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'!'
+            | b'~'
+            | b'*'
+            | b'\''
+            | b'('
+            | b')' => encoded.push(byte as char),
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads a symbol back the way a scanner would: format information,
+    // unmasking, the zigzag codeword order, de-interleaving and the byte
+    // mode segment. Each block's error correction is checked on the way.
+    fn decode(qr: &QrCode) -> (EcLevel, u32, Vec<u8>) {
+        let version = (qr.size - 17) / 4;
+        let mut format = 0;
+        let mut take = |i: usize, x: usize, y: usize| format |= u32::from(qr.is_dark(x, y)) << i;
+        for i in 0..6 {
+            take(i, 8, i);
+        }
+        take(6, 8, 7);
+        take(7, 8, 8);
+        take(8, 7, 8);
+        for i in 9..15 {
+            take(i, 14 - i, 8);
+        }
+        let (ec, mask) = [EcLevel::L, EcLevel::M, EcLevel::Q, EcLevel::H]
+            .into_iter()
+            .flat_map(|ec| (0..8).map(move |mask| (ec, mask)))
+            .find(|&(ec, mask)| format_bits(ec, mask) == format)
+            .expect("valid format information");
+
+        let mut unmasked = QrCode {
+            size: qr.size,
+            modules: qr.modules.clone(),
+            function: qr.function.clone(),
+        };
+        unmasked.apply_mask(mask);
+        let mut bits = Vec::new();
+        let size = qr.size;
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            let upward = (right + 1) & 2 == 0;
+            for step in 0..size {
+                let y = if upward { size - 1 - step } else { step };
+                for x in [right, right - 1] {
+                    if !qr.function[y * size + x] {
+                        bits.push(unmasked.is_dark(x, y));
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+        let codewords: Vec<u8> = bits
+            .chunks_exact(8)
+            .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | u8::from(bit)))
+            .collect();
+
+        let blocks = ERROR_CORRECTION_BLOCKS[ec as usize][version] as usize;
+        let ecc_len = ECC_CODEWORDS_PER_BLOCK[ec as usize][version] as usize;
+        let total = raw_data_modules(version) / 8;
+        let long_blocks = total % blocks;
+        let short_data = total / blocks - ecc_len;
+        let data_len = |b: usize| short_data + usize::from(b >= blocks - long_blocks);
+        let mut split: Vec<Vec<u8>> = vec![Vec::new(); blocks];
+        let mut next = codewords.iter().copied();
+        for i in 0..=short_data {
+            for (b, block) in split.iter_mut().enumerate() {
+                if i < data_len(b) {
+                    block.push(next.next().unwrap());
+                }
+            }
+        }
+        let mut data = Vec::new();
+        for _ in 0..ecc_len {
+            for block in &mut split {
+                block.push(next.next().unwrap());
+            }
+        }
+        for (b, block) in split.iter().enumerate() {
+            let (block_data, ecc) = block.split_at(data_len(b));
+            assert_eq!(
+                reed_solomon_remainder(block_data, &reed_solomon_divisor(ecc_len)),
+                ecc,
+                "block {} error correction",
+                b
+            );
+            data.extend_from_slice(block_data);
+        }
+
+        let bit = |i: usize| (data[i / 8] >> (7 - i % 8)) & 1;
+        let read = |from: usize, count: usize| {
+            (from..from + count).fold(0, |acc, i| acc << 1 | bit(i) as usize)
+        };
+        assert_eq!(read(0, 4), 0b0100, "byte mode");
+        let len = read(4, count_bits(version));
+        let start = 4 + count_bits(version);
+        let bytes = (0..len).map(|i| read(start + i * 8, 8) as u8).collect();
+        (ec, mask, bytes)
+    }
+
+    #[test]
+    fn reed_solomon_matches_the_hello_world_1_m_example() {
+        // thonky.com's QR code tutorial: "HELLO WORLD" at 1-M.
+        let data = [
+            32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17,
+        ];
+        assert_eq!(
+            reed_solomon_remainder(&data, &reed_solomon_divisor(10)),
+            [196, 35, 39, 119, 235, 215, 231, 226, 93, 23]
+        );
+    }
+
+    #[test]
+    fn gf_multiply_reduces_by_the_qr_polynomial() {
+        assert_eq!(gf_multiply(0x80, 0x02), 0x1D);
+        assert_eq!(gf_multiply(0x02, 0x02), 0x04);
+        assert_eq!(gf_multiply(0x8E, 0x02), 0x01);
+        for x in 0..=255u8 {
+            assert_eq!(gf_multiply(x, 1), x);
+            assert_eq!(gf_multiply(x, 0), 0);
+            assert_eq!(gf_multiply(x, 0x53), gf_multiply(0x53, x));
+        }
+    }
+
+    #[test]
+    fn format_bits_match_the_standard_table() {
+        let table = [
+            (EcLevel::L, 0, 0b111011111000100),
+            (EcLevel::L, 7, 0b110100101110110),
+            (EcLevel::M, 0, 0b101010000010010),
+            (EcLevel::M, 5, 0b100000011001110),
+            (EcLevel::Q, 0, 0b011010101011111),
+            (EcLevel::H, 0, 0b001011010001001),
+        ];
+        for (ec, mask, bits) in table {
+            assert_eq!(format_bits(ec, mask), bits, "{:?} mask {}", ec, mask);
+        }
+    }
+
+    #[test]
+    fn version_bits_match_the_standard_table() {
+        assert_eq!(version_bits(7), 0b000111110010010100);
+        assert_eq!(version_bits(8), 0b001000010110111100);
+        assert_eq!(version_bits(40), 0b101000110001101001);
+    }
+
+    #[test]
+    fn alignment_positions_match_the_standard_table() {
+        assert!(alignment_positions(1).is_empty());
+        assert_eq!(alignment_positions(2), [6, 18]);
+        assert_eq!(alignment_positions(7), [6, 22, 38]);
+        assert_eq!(alignment_positions(32), [6, 34, 60, 86, 112, 138]);
+        assert_eq!(alignment_positions(40), [6, 30, 58, 86, 114, 142, 170]);
+    }
+
+    #[test]
+    fn byte_capacities_match_the_standard_table() {
+        assert_eq!(data_codewords(1, EcLevel::M), 16);
+        assert_eq!(data_codewords(1, EcLevel::L), 19);
+        assert_eq!(capacity(EcLevel::L), 2953);
+        assert_eq!(capacity(EcLevel::M), 2331);
+        assert_eq!(capacity(EcLevel::Q), 1663);
+        assert_eq!(capacity(EcLevel::H), 1273);
+        // Version 1 holds 17, 14, 11 and 7 bytes.
+        assert_eq!(encode(&[b'a'; 14], EcLevel::M).unwrap().size, 21);
+        assert_eq!(encode(&[b'a'; 15], EcLevel::M).unwrap().size, 25);
+        assert_eq!(encode(&[b'a'; 17], EcLevel::L).unwrap().size, 21);
+        assert_eq!(encode(&[b'a'; 7], EcLevel::H).unwrap().size, 21);
+        assert!(encode(&vec![b'a'; 2954], EcLevel::L).is_err());
+    }
+
+    #[test]
+    fn encoded_symbols_decode_back() {
+        let long: Vec<u8> = (0..1_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        for (data, ec) in [
+            (&b"HELLO WORLD"[..], EcLevel::M),
+            (
+                b"solana:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN?amount=1",
+                EcLevel::Q,
+            ),
+            (&long[..300], EcLevel::H),
+            (&long[..], EcLevel::L),
+        ] {
+            let qr = encode(data, ec).unwrap();
+            let (decoded_ec, mask, decoded) = decode(&qr);
+            assert_eq!(decoded, data);
+            assert_eq!(decoded_ec as usize, ec as usize);
+            assert!(mask < 8);
+            let version = (qr.size - 17) / 4;
+            if version >= 7 {
+                // The copy above the bottom left finder.
+                let bits = version_bits(version);
+                for i in 0..18 {
+                    let dark = qr.is_dark(i / 3, qr.size - 11 + i % 3);
+                    assert_eq!(dark, (bits >> i) & 1 != 0, "version bit {}", i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn png_chunks_carry_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        let png = encode(b"HELLO WORLD", EcLevel::M)
+            .unwrap()
+            .to_png(DEFAULT_SIZE, DEFAULT_MARGIN);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(
+            &png[png.len() - 12..],
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]
+        );
+        // 29 modules with the margin fit 8 pixels each in 256.
+        assert_eq!(&png[16..24], [0, 0, 0, 232, 0, 0, 0, 232]);
+    }
+
+    #[test]
+    fn transfer_request_matches_the_solana_pay_example() {
+        let request = TransferRequest {
+            recipient: "mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN",
+            amount: Some("1"),
+            spl_token: None,
+            references: Vec::new(),
+            label: Some("Michael"),
+            message: Some("Thanks for all the fish"),
+            memo: Some("OrderId12345"),
+        };
+        assert_eq!(
+            request.url().unwrap(),
+            "solana:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN?amount=1&label=Michael\
+             &message=Thanks%20for%20all%20the%20fish&memo=OrderId12345"
+        );
+        let bad = TransferRequest {
+            amount: Some("1e9"),
+            ..request
+        };
+        assert!(bad.url().is_err());
+    }
+}
@@ -0,0 +1,358 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+use crate::{
+    auth::{self, Priority},
+    config::Config,
+    shed,
+    state::AppState,
+};
+
+pub use crate::api::{QueueReport, TierReport};
+
+const TIERS: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+// A tier's pass advances by STRIDE / weight per dispatch, so while every
+// tier has requests waiting High gets 8, Normal 4 and Low 1 of every 13
+// free slots.
+const STRIDE: u64 = 8;
+
+fn weight(priority: Priority) -> u64 {
+    match priority {
+        Priority::High => 8,
+        Priority::Normal => 4,
+        Priority::Low => 1,
+    }
+}
+
+#[derive(Default)]
+struct Tier {
+    waiting: VecDeque<oneshot::Sender<Permit>>,
+    pass: u64,
+    queued: u64,
+    rejected: u64,
+}
+
+#[derive(Default)]
+struct Queues {
+    running: usize,
+    limit: Option<usize>,
+    // Indexed by Priority as usize.
+    tiers: [Tier; 3],
+    // Pass of the last dispatched request; a tier that was idle resumes
+    // from here instead of catching up on turns it didn't use.
+    virtual_time: u64,
+}
+
+impl Queues {
+    fn waiting(&self) -> usize {
+        self.tiers.iter().map(|t| t.waiting.len()).sum()
+    }
+
+    fn has_slot(&self) -> bool {
+        self.limit.is_none_or(|limit| self.running < limit)
+    }
+
+    // Weighted fair choice: the waiting tier with the lowest pass, higher
+    // priority first on ties.
+    fn next(&mut self) -> Option<oneshot::Sender<Permit>> {
+        let priority = TIERS
+            .into_iter()
+            .filter(|p| !self.tiers[*p as usize].waiting.is_empty())
+            .min_by_key(|p| self.tiers[*p as usize].pass)?;
+        let tier = &mut self.tiers[priority as usize];
+        self.virtual_time = tier.pass;
+        tier.pass += STRIDE / weight(priority);
+        tier.waiting.pop_front()
+    }
+}
+
+// Concurrency limit for the read and airdrop routes. Requests over
+// max_concurrent_requests wait here and are let through by priority tier.
+#[derive(Default)]
+pub struct RequestQueue {
+    queues: Mutex<Queues>,
+}
+
+// Holds one of the limited slots; dropping it passes the slot on.
+pub struct Permit {
+    queue: Option<Arc<RequestQueue>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.take() {
+            queue.queues.lock().unwrap().running -= 1;
+            queue.dispatch();
+        }
+    }
+}
+
+impl RequestQueue {
+    pub fn report(&self) -> QueueReport {
+        let queues = self.queues.lock().unwrap();
+        QueueReport {
+            running: queues.running,
+            limit: queues.limit,
+            tiers: TIERS
+                .into_iter()
+                .map(|priority| {
+                    let tier = &queues.tiers[priority as usize];
+                    TierReport {
+                        priority,
+                        waiting: tier.waiting.len(),
+                        queued: tier.queued,
+                        rejected: tier.rejected,
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    // None when no limit is configured.
+    async fn acquire(
+        self: &Arc<Self>,
+        priority: Priority,
+        config: &Config,
+    ) -> Result<Option<Permit>, String> {
+        let rx = {
+            let mut queues = self.queues.lock().unwrap();
+            queues.limit = config.max_concurrent_requests;
+            if queues.limit.is_none() {
+                return Ok(None);
+            }
+            if queues.has_slot() && queues.waiting() == 0 {
+                queues.running += 1;
+                return Ok(Some(Permit {
+                    queue: Some(self.clone()),
+                }));
+            }
+
+            // Waiters whose request was cancelled don't hold a place.
+            for tier in &mut queues.tiers {
+                tier.waiting.retain(|tx| !tx.is_closed());
+            }
+            let full = queues.waiting() >= config.max_queued_requests;
+            let virtual_time = queues.virtual_time;
+            let tier = &mut queues.tiers[priority as usize];
+            if full {
+                tier.rejected += 1;
+                return Err("Request queue is full, retry later".to_string());
+            }
+            if tier.waiting.is_empty() {
+                tier.pass = tier.pass.max(virtual_time);
+            }
+            let (tx, rx) = oneshot::channel();
+            tier.waiting.push_back(tx);
+            tier.queued += 1;
+            rx
+        };
+        // A raised limit may have left free slots behind the queue.
+        self.dispatch();
+
+        let timeout = Duration::from_secs(config.queue_timeout_secs);
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(permit)) => Ok(Some(permit)),
+            // Dropping `rx` returns a permit that was sent in the meantime.
+            _ => {
+                self.queues.lock().unwrap().tiers[priority as usize].rejected += 1;
+                Err("Timed out waiting in the request queue, retry later".to_string())
+            }
+        }
+    }
+
+    fn dispatch(self: &Arc<Self>) {
+        loop {
+            let tx = {
+                let mut queues = self.queues.lock().unwrap();
+                if !queues.has_slot() {
+                    return;
+                }
+                let Some(tx) = queues.next() else {
+                    return;
+                };
+                queues.running += 1;
+                tx
+            };
+            if let Err(mut permit) = tx.send(Permit {
+                queue: Some(self.clone()),
+            }) {
+                // The waiter went away; take the slot back here rather than
+                // through Permit's Drop, which would recurse into dispatch.
+                permit.queue = None;
+                self.queues.lock().unwrap().running -= 1;
+            }
+        }
+    }
+}
+
+pub async fn limit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let config = state.config();
+    match state
+        .queue
+        .acquire(auth::request_priority(&req), &config)
+        .await
+    {
+        Ok(permit) => {
+            let res = next.run(req).await;
+            drop(permit);
+            res
+        }
+        Err(message) => shed::overloaded(&config, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn letter(priority: Priority) -> char {
+        match priority {
+            Priority::High => 'H',
+            Priority::Normal => 'N',
+            Priority::Low => 'L',
+        }
+    }
+
+    fn dispatch_order(queues: &mut Queues, count: usize) -> String {
+        (0..count)
+            .map(|_| {
+                let before = queues.tiers.each_ref().map(|t| t.waiting.len());
+                queues.next().unwrap();
+                let priority = TIERS
+                    .into_iter()
+                    .find(|p| queues.tiers[*p as usize].waiting.len() < before[*p as usize])
+                    .unwrap();
+                letter(priority)
+            })
+            .collect()
+    }
+
+    fn fill(queues: &mut Queues, priority: Priority, count: usize) {
+        for _ in 0..count {
+            queues.tiers[priority as usize]
+                .waiting
+                .push_back(oneshot::channel().0);
+        }
+    }
+
+    #[test]
+    fn busy_tiers_share_slots_eight_four_one() {
+        let mut queues = Queues::default();
+        for priority in TIERS {
+            fill(&mut queues, priority, 30);
+        }
+        let order = dispatch_order(&mut queues, 26);
+        assert_eq!(&order[..13], "HNLHHNHHNHHNH");
+        assert_eq!(&order[13..], "HNLHHNHHNHHNH");
+    }
+
+    #[test]
+    fn an_idle_tier_does_not_catch_up() {
+        let mut queues = Queues::default();
+        fill(&mut queues, Priority::High, 30);
+        assert_eq!(dispatch_order(&mut queues, 8), "HHHHHHHH");
+
+        // Low was idle for those eight turns. Resuming from the virtual
+        // time, as acquire does, it gets its one slot in nine rather than
+        // the next eight in a row.
+        queues.tiers[Priority::Low as usize].pass = queues.virtual_time;
+        fill(&mut queues, Priority::Low, 10);
+        assert_eq!(dispatch_order(&mut queues, 10), "LHHHHHHHHL");
+    }
+
+    fn config(limit: usize, queued: usize) -> Config {
+        Config {
+            max_concurrent_requests: Some(limit),
+            max_queued_requests: queued,
+            queue_timeout_secs: 5,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn no_limit_means_no_permit() {
+        let queue = Arc::new(RequestQueue::default());
+        let permit = queue.acquire(Priority::Low, &Config::default()).await;
+        assert!(permit.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn a_freed_slot_goes_to_the_higher_tier() {
+        let queue = Arc::new(RequestQueue::default());
+        let config = config(1, 10);
+        let held = queue.acquire(Priority::Low, &config).await.unwrap();
+
+        let low = tokio::spawn({
+            let (queue, config) = (queue.clone(), config.clone());
+            async move { queue.acquire(Priority::Low, &config).await }
+        });
+        tokio::task::yield_now().await;
+        let high = tokio::spawn({
+            let (queue, config) = (queue.clone(), config.clone());
+            async move { queue.acquire(Priority::High, &config).await }
+        });
+        while queue
+            .report()
+            .tiers
+            .iter()
+            .map(|t| t.waiting)
+            .sum::<usize>()
+            < 2
+        {
+            tokio::task::yield_now().await;
+        }
+
+        drop(held);
+        let high = high.await.unwrap().unwrap();
+        assert!(!low.is_finished());
+        assert_eq!(queue.report().running, 1);
+        drop(high);
+        drop(low.await.unwrap().unwrap());
+
+        let report = queue.report();
+        assert_eq!(report.running, 0);
+        let queued: Vec<_> = report.tiers.iter().map(|t| (t.waiting, t.queued)).collect();
+        assert_eq!(queued, [(0, 1), (0, 0), (0, 1)]);
+    }
+
+    #[tokio::test]
+    async fn full_queue_and_timeout_are_rejected() {
+        let queue = Arc::new(RequestQueue::default());
+        let _held = queue
+            .acquire(Priority::Normal, &config(1, 0))
+            .await
+            .unwrap();
+        assert_eq!(
+            queue
+                .acquire(Priority::Normal, &config(1, 0))
+                .await
+                .err()
+                .unwrap(),
+            "Request queue is full, retry later"
+        );
+
+        let timeout = Config {
+            queue_timeout_secs: 0,
+            ..config(1, 10)
+        };
+        assert_eq!(
+            queue
+                .acquire(Priority::Normal, &timeout)
+                .await
+                .err()
+                .unwrap(),
+            "Timed out waiting in the request queue, retry later"
+        );
+        let normal = &queue.report().tiers[1];
+        assert_eq!((normal.queued, normal.rejected), (1, 2));
+    }
+}
@@ -0,0 +1,132 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{collections::HashMap, net::SocketAddr, sync::Mutex};
+
+use crate::{
+    auth::Principal, clock::now_ms, config::Config, handlers::error_response, state::AppState,
+};
+
+// Anyone can open a new IP or SIWS session, so the table is capped; full
+// buckets carry no state and are dropped first.
+const MAX_TRACKED_CALLERS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated_ms: u64,
+}
+
+// Token bucket per caller. The rate and burst are passed in on every call,
+// so a config reload takes effect on the next request.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    // Takes one token from `caller`'s bucket, or returns how many seconds
+    // until one is available.
+    pub fn take(&self, caller: &str, per_minute: u32, burst: u32, now_ms: u64) -> Result<(), u64> {
+        let per_ms = f64::from(per_minute) / 60_000.0;
+        let burst = f64::from(burst.max(1));
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CALLERS && !buckets.contains_key(caller) {
+            buckets.retain(|_, b| {
+                b.tokens + now_ms.saturating_sub(b.updated_ms) as f64 * per_ms < burst
+            });
+        }
+        if buckets.len() >= MAX_TRACKED_CALLERS && !buckets.contains_key(caller) {
+            return Err(60);
+        }
+        let bucket = buckets.entry(caller.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated_ms: now_ms,
+        });
+        let elapsed = now_ms.saturating_sub(bucket.updated_ms) as f64;
+        bucket.tokens = (bucket.tokens + elapsed * per_ms).min(burst);
+        bucket.updated_ms = now_ms;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(((1.0 - bucket.tokens) / per_ms / 1_000.0).ceil() as u64)
+    }
+
+    // `take` at the configured rate, for callers of the HTTP API and the
+    // Telegram bot alike. Always Ok without `rate_limit_per_minute`.
+    pub fn check(&self, config: &Config, caller: &str) -> Result<(), u64> {
+        let Some(per_minute) = config.rate_limit_per_minute else {
+            return Ok(());
+        };
+        let burst = config.rate_limit_burst.unwrap_or(per_minute);
+        self.take(caller, per_minute, burst, now_ms())
+    }
+}
+
+// Layered inside the auth guard so callers are keyed by principal subject;
+// anonymous callers fall back to their IP.
+pub async fn limit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let caller = req
+        .extensions()
+        .get::<Principal>()
+        .and_then(|p| p.subject.clone())
+        .or_else(|| {
+            req.extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        })
+        .unwrap_or_default();
+    match state.rate_limiter.check(&state.config(), &caller) {
+        Ok(()) => next.run(req).await,
+        Err(retry_after) => {
+            let mut res = error_response(
+                StatusCode::TOO_MANY_REQUESTS,
+                "Rate limit exceeded, retry later",
+            )
+            .into_response();
+            res.headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+            res
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_allows_burst_then_refills_at_rate() {
+        let limiter = RateLimiter::default();
+        for _ in 0..3 {
+            assert_eq!(limiter.take("a", 60, 3, 0), Ok(()));
+        }
+        assert_eq!(limiter.take("a", 60, 3, 0), Err(1));
+        // Other callers have their own bucket.
+        assert_eq!(limiter.take("b", 60, 3, 0), Ok(()));
+        assert_eq!(limiter.take("a", 60, 3, 1_000), Ok(()));
+        assert_eq!(limiter.take("a", 60, 3, 1_000), Err(1));
+    }
+
+    #[test]
+    fn new_rate_applies_to_existing_buckets() {
+        let limiter = RateLimiter::default();
+        assert_eq!(limiter.take("a", 1, 1, 0), Ok(()));
+        assert_eq!(limiter.take("a", 1, 1, 1_000), Err(59));
+        assert_eq!(limiter.take("a", 60, 1, 2_000), Ok(()));
+    }
+
+    #[test]
+    fn full_buckets_are_dropped_at_capacity() {
+        let limiter = RateLimiter::default();
+        for i in 0..MAX_TRACKED_CALLERS {
+            limiter.take(&i.to_string(), 60, 2, 0).unwrap();
+        }
+        assert_eq!(limiter.take("new", 60, 2, 0), Err(60));
+        assert_eq!(limiter.take("new", 60, 2, 1_000), Ok(()));
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,348 @@
+use solana_sdk::{
+    program_utils::limited_deserialize,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_instruction::SystemInstruction,
+    system_program,
+};
+
+use crate::{
+    audit::AuditRecord,
+    config::Config,
+    decoders::MEMO_PROGRAM_ID,
+    history, links, rpc,
+    service::{self, ServiceError},
+};
+
+pub use crate::api::{Receipt, ReceiptChecks, ReceiptRecord};
+
+const MEMO_PREFIX: &str = "receipt:";
+
+// The memo a receipted transfer carries.
+pub fn memo(id: &str) -> String {
+    format!("{}{}", MEMO_PREFIX, id)
+}
+
+// Blocking. A treasury transfer, carrying a receipt memo when
+// `airdrop_receipts` is on; returns the receipt id with the signature.
+// Without `confirm` it returns as soon as the node accepted the transfer.
+pub fn pay(
+    config: &Config,
+    payer: &Keypair,
+    to: &Pubkey,
+    lamports: u64,
+    confirm: bool,
+) -> Result<(Signature, Option<String>), ServiceError> {
+    if !config.airdrop_receipts {
+        return Ok((
+            service::transfer(&config.rpc_url, payer, to, lamports, confirm)?,
+            None,
+        ));
+    }
+    let id = format!("{:032x}", rand::random::<u128>());
+    let signature =
+        service::transfer_with_memo(&config.rpc_url, payer, to, lamports, &memo(&id), confirm)?;
+    Ok((signature, Some(id)))
+}
+
+pub struct Funded {
+    pub signature: Signature,
+    // "airdrop" (the RPC node's faucet) or "treasury".
+    pub source: &'static str,
+    pub receipt: Option<String>,
+}
+
+// Blocking. Sends an airdrop from the RPC node's faucet, or, with
+// `airdrop_receipts` on and a server keypair, as a treasury transfer with
+// a receipt memo: faucet airdrops are built by the node and can't carry one.
+// A treasury transfer is waited on until confirmed only with `confirm`; the
+// faucet's never are.
+pub fn fund_airdrop(
+    config: &Config,
+    keypair: Option<&Keypair>,
+    wallet: &str,
+    lamports: u64,
+    confirm: bool,
+) -> Result<Funded, ServiceError> {
+    let limits = config.airdrop_limits();
+    match keypair.filter(|_| config.airdrop_receipts) {
+        Some(payer) => {
+            let to = service::parse_wallet(wallet)?;
+            service::check_airdrop_amount(lamports, limits)?;
+            let (signature, receipt) = pay(config, payer, &to, lamports, confirm)?;
+            Ok(Funded {
+                signature,
+                source: "treasury",
+                receipt,
+            })
+        }
+        None => Ok(Funded {
+            signature: service::request_airdrop(&config.rpc_url, wallet, lamports, limits)?,
+            source: "airdrop",
+            receipt: None,
+        }),
+    }
+}
+
+pub struct Preview {
+    pub source: &'static str,
+    pub wallet_balance: u64,
+    // Treasury transfers only.
+    pub treasury_balance: Option<u64>,
+    pub simulation: Option<service::SimulatedTransfer>,
+}
+
+// Blocking. What `fund_airdrop` would do, without sending anything: the
+// balances it would change and, for treasury transfers, the transfer
+// simulated with a receipt memo like the one it would carry.
+pub fn preview_airdrop(
+    config: &Config,
+    keypair: Option<&Keypair>,
+    wallet: &str,
+    lamports: u64,
+) -> Result<Preview, ServiceError> {
+    let wallet_balance = service::get_balance(&config.rpc_url, wallet)?;
+    match keypair.filter(|_| config.airdrop_receipts) {
+        Some(payer) => {
+            let to = service::parse_wallet(wallet)?;
+            let simulation = service::simulate_transfer(
+                &config.rpc_url,
+                payer,
+                &to,
+                lamports,
+                Some(&memo(&format!("{:032x}", 0))),
+            )?;
+            let treasury_balance =
+                service::get_balance(&config.rpc_url, &payer.pubkey().to_string())?;
+            Ok(Preview {
+                source: "treasury",
+                wallet_balance,
+                treasury_balance: Some(treasury_balance),
+                simulation: Some(simulation),
+            })
+        }
+        None => Ok(Preview {
+            source: "airdrop",
+            wallet_balance,
+            treasury_balance: None,
+            simulation: None,
+        }),
+    }
+}
+
+// Blocking: checks the receipted transaction on chain against its audit
+// record.
+pub fn verify(
+    config: &Config,
+    treasury: &Pubkey,
+    id: &str,
+    record: AuditRecord,
+) -> Result<Receipt, ServiceError> {
+    let signature = record
+        .signature
+        .clone()
+        .ok_or_else(|| ServiceError::Invalid("The receipt has no transaction".to_string()))?;
+    let rpc_url = &config.rpc_url;
+    let client = rpc::client(rpc_url);
+    let _span = tracing::info_span!("rpc.verify_receipt", %rpc_url, id).entered();
+    let tx = history::fetch_transaction(&client, &signature)?;
+
+    let wallet = record.params["wallet"]
+        .as_str()
+        .and_then(|w| service::parse_wallet(w).ok());
+    let lamports = record.params["lamports"].as_u64();
+    let succeeded = tx
+        .transaction
+        .meta
+        .as_ref()
+        .is_some_and(|meta| meta.err.is_none());
+    let (paid_by_treasury, memo_matches, transfer_matches) =
+        match tx.transaction.transaction.decode() {
+            Some(decoded) => {
+                let keys = decoded.message.static_account_keys();
+                let expected_memo = memo(id);
+                let mut memo_matches = false;
+                let mut transfer_matches = false;
+                for ix in decoded.message.instructions() {
+                    let program = keys.get(ix.program_id_index as usize);
+                    let account = |i: usize| ix.accounts.get(i).and_then(|&k| keys.get(k as usize));
+                    if program == Some(&MEMO_PROGRAM_ID) && ix.data == expected_memo.as_bytes() {
+                        memo_matches = true;
+                    }
+                    if program == Some(&system_program::id()) {
+                        if let Ok(SystemInstruction::Transfer { lamports: sent }) =
+                            limited_deserialize(&ix.data)
+                        {
+                            transfer_matches |= account(0) == Some(treasury)
+                                && account(1) == wallet.as_ref()
+                                && Some(sent) == lamports;
+                        }
+                    }
+                }
+                (
+                    keys.first() == Some(treasury),
+                    memo_matches,
+                    transfer_matches,
+                )
+            }
+            None => (false, false, false),
+        };
+
+    let checks = ReceiptChecks {
+        transaction_succeeded: succeeded,
+        paid_by_treasury,
+        memo_matches,
+        transfer_matches,
+    };
+    Ok(Receipt {
+        receipt_id: id.to_string(),
+        verified: succeeded && paid_by_treasury && memo_matches && transfer_matches,
+        explorer_urls: links::transaction(config, &signature),
+        signature,
+        treasury: treasury.to_string(),
+        slot: tx.slot,
+        block_time: tx.block_time,
+        checks,
+        record: ReceiptRecord {
+            timestamp_ms: record.timestamp_ms,
+            action: record.action,
+            params: record.params,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(signature: Option<Signature>, wallet: &Pubkey, lamports: u64) -> AuditRecord {
+        serde_json::from_value(json!({
+            "timestamp_ms": 1,
+            "action": "airdrop",
+            "actor": { "source": "test" },
+            "params": { "wallet": wallet.to_string(), "lamports": lamports },
+            "outcome": "success",
+            "signature": signature.map(|s| s.to_string()),
+        }))
+        .unwrap()
+    }
+
+    fn receipts_on() -> Config {
+        Config {
+            rpc_url: "http://mock".to_string(),
+            airdrop_receipts: true,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn receipted_transfers_verify() {
+        let chain = rpc::mock_for_tests();
+        let treasury = Keypair::new();
+        let wallet = Pubkey::new_unique();
+        chain.set_balance(treasury.pubkey(), 10_000_000_000);
+        let config = receipts_on();
+
+        let funded = fund_airdrop(
+            &config,
+            Some(&treasury),
+            &wallet.to_string(),
+            1_000_000,
+            true,
+        )
+        .unwrap();
+        assert_eq!(funded.source, "treasury");
+        let id = funded.receipt.unwrap();
+        assert_eq!(id.len(), 32);
+
+        let receipt = verify(
+            &config,
+            &treasury.pubkey(),
+            &id,
+            record(Some(funded.signature), &wallet, 1_000_000),
+        )
+        .unwrap();
+        assert!(receipt.verified);
+        assert_eq!(receipt.signature, funded.signature.to_string());
+
+        // Another amount, another receipt id, another treasury.
+        let receipt = verify(
+            &config,
+            &treasury.pubkey(),
+            &id,
+            record(Some(funded.signature), &wallet, 2_000_000),
+        )
+        .unwrap();
+        assert!(!receipt.verified);
+        assert!(receipt.checks.memo_matches && !receipt.checks.transfer_matches);
+        let receipt = verify(
+            &config,
+            &treasury.pubkey(),
+            &"0".repeat(32),
+            record(Some(funded.signature), &wallet, 1_000_000),
+        )
+        .unwrap();
+        assert!(!receipt.checks.memo_matches && receipt.checks.transfer_matches);
+        let receipt = verify(
+            &config,
+            &Pubkey::new_unique(),
+            &id,
+            record(Some(funded.signature), &wallet, 1_000_000),
+        )
+        .unwrap();
+        assert!(!receipt.checks.paid_by_treasury && !receipt.checks.transfer_matches);
+        assert!(receipt.checks.transaction_succeeded);
+
+        match verify(&config, &treasury.pubkey(), &id, record(None, &wallet, 1)) {
+            Err(ServiceError::Invalid(message)) => {
+                assert_eq!(message, "The receipt has no transaction")
+            }
+            _ => panic!("expected an invalid request error"),
+        }
+    }
+
+    #[test]
+    fn faucet_airdrops_carry_no_receipt() {
+        let chain = rpc::mock_for_tests();
+        let treasury = Keypair::new();
+        chain.set_balance(treasury.pubkey(), 10_000_000_000);
+        let wallet = Pubkey::new_unique().to_string();
+        let config = Config {
+            airdrop_receipts: false,
+            ..receipts_on()
+        };
+        let funded = fund_airdrop(&config, Some(&treasury), &wallet, 1_000_000, true).unwrap();
+        assert_eq!(funded.source, "airdrop");
+        assert!(funded.receipt.is_none());
+        let (_, receipt) = pay(&config, &treasury, &Pubkey::new_unique(), 1, true).unwrap();
+        assert!(receipt.is_none());
+        // Treasury airdrops are held to the same limits as the faucet's.
+        assert!(matches!(
+            fund_airdrop(&receipts_on(), Some(&treasury), &wallet, u64::MAX, true),
+            Err(ServiceError::AirdropTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn previews_send_nothing() {
+        let chain = rpc::mock_for_tests();
+        let treasury = Keypair::new();
+        let wallet = Pubkey::new_unique();
+        chain.set_balance(treasury.pubkey(), 10_000_000_000);
+        chain.set_balance(wallet, 42);
+
+        let preview =
+            preview_airdrop(&receipts_on(), Some(&treasury), &wallet.to_string(), 1_000).unwrap();
+        assert_eq!(preview.source, "treasury");
+        assert_eq!(preview.wallet_balance, 42);
+        assert_eq!(preview.treasury_balance, Some(10_000_000_000));
+        assert!(preview.simulation.unwrap().error.is_none());
+
+        let preview = preview_airdrop(&receipts_on(), None, &wallet.to_string(), 1_000).unwrap();
+        assert_eq!(preview.source, "airdrop");
+        assert!(preview.treasury_balance.is_none() && preview.simulation.is_none());
+        let client = rpc::client("http://mock");
+        assert_eq!(client.get_balance(&wallet).unwrap(), 42);
+    }
+}
@@ -0,0 +1,645 @@
+use async_trait::async_trait;
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::Request,
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use solana_client::{
+    client_error::{ClientErrorKind, Result as ClientResult},
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use crate::{clock::now_ms, logging, pool::PooledSender, rpc};
+
+const RPC_LOG: &str = "rpc.jsonl";
+const HTTP_LOG: &str = "http.jsonl";
+// Larger bodies (program uploads) pass through unrecorded.
+const MAX_RECORDED_BODY: usize = 1024 * 1024;
+// RPC calls kept in memory for attaching to in-flight exchanges.
+const MAX_RECENT_RPC: usize = 10_000;
+const REDACTED_HEADERS: [&str; 3] = ["authorization", "cookie", "set-cookie"];
+// JSON body fields, at any depth, that carry credentials (issued API keys,
+// SIWS session tokens).
+const REDACTED_FIELDS: [&str; 4] = ["api_key", "token", "secret", "password"];
+// Streamed responses: recording would hold them until they end, which for
+// server-sent events is never.
+const STREAMING_TYPES: [&str; 2] = ["text/event-stream", "application/x-ndjson"];
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RpcInteraction {
+    pub seq: u64,
+    pub at_ms: u64,
+    pub method: String,
+    pub params: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RecordedMessage {
+    headers: BTreeMap<String, String>,
+    // None when the body was binary, multipart, streamed, too large to
+    // keep or a credential route's.
+    body: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Exchange {
+    id: u64,
+    at_ms: u64,
+    duration_ms: u64,
+    method: String,
+    uri: String,
+    request: RecordedMessage,
+    status: u16,
+    response: RecordedMessage,
+    // Every RPC call made while the request was in flight. Under concurrent
+    // load this can include calls made for other requests.
+    rpc: Vec<RpcInteraction>,
+}
+
+fn append_line(file: &Mutex<File>, value: &impl Serialize) {
+    let line = match serde_json::to_string(value) {
+        Ok(line) => line,
+        Err(e) => {
+            logging::warn(format_args!("Failed to serialise recording: {}", e));
+            return;
+        }
+    };
+    if let Err(e) = writeln!(file.lock().unwrap(), "{}", line) {
+        logging::warn(format_args!("Failed to write recording: {}", e));
+    }
+}
+
+// Dev-mode recorder. Appends each RPC call to `rpc.jsonl` and each HTTP
+// request/response pair (with the RPC calls it caused) to `http.jsonl`.
+pub struct Recorder {
+    next_seq: AtomicU64,
+    next_exchange: AtomicU64,
+    recent: Mutex<VecDeque<RpcInteraction>>,
+    rpc_log: Mutex<File>,
+    http_log: Mutex<File>,
+}
+
+impl Recorder {
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create '{}': {}", dir.display(), e))?;
+        let open = |name: &str| {
+            let path = dir.join(name);
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map(Mutex::new)
+                .map_err(|e| format!("failed to open '{}': {}", path.display(), e))
+        };
+        Ok(Recorder {
+            next_seq: AtomicU64::new(1),
+            next_exchange: AtomicU64::new(1),
+            recent: Mutex::new(VecDeque::new()),
+            rpc_log: open(RPC_LOG)?,
+            http_log: open(HTTP_LOG)?,
+        })
+    }
+
+    fn record_rpc(
+        &self,
+        method: String,
+        params: serde_json::Value,
+        outcome: &ClientResult<serde_json::Value>,
+    ) {
+        let interaction = RpcInteraction {
+            seq: self.next_seq.fetch_add(1, Ordering::Relaxed),
+            at_ms: now_ms(),
+            method,
+            params,
+            result: outcome.as_ref().ok().cloned(),
+            error: outcome.as_ref().err().map(|e| e.to_string()),
+        };
+        append_line(&self.rpc_log, &interaction);
+
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == MAX_RECENT_RPC {
+            recent.pop_front();
+        }
+        recent.push_back(interaction);
+    }
+
+    fn rpc_after(&self, seq: u64) -> Vec<RpcInteraction> {
+        let recent = self.recent.lock().unwrap();
+        recent.iter().filter(|i| i.seq >= seq).cloned().collect()
+    }
+}
+
+pub struct RecordingSender {
+    inner: PooledSender,
+    recorder: Arc<Recorder>,
+}
+
+impl RecordingSender {
+    pub fn new(inner: PooledSender, recorder: Arc<Recorder>) -> Self {
+        RecordingSender { inner, recorder }
+    }
+}
+
+#[async_trait]
+impl RpcSender for RecordingSender {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        let outcome = self.inner.send(request, params.clone()).await;
+        self.recorder
+            .record_rpc(request.to_string(), params, &outcome);
+        outcome
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+type Recorded = Result<serde_json::Value, String>;
+
+// Answers RPC calls from a previous recording, matched on method and
+// params. Calls that were made several times (e.g. status polling) replay
+// their responses in order, repeating the last one.
+pub struct Replayer {
+    responses: Mutex<HashMap<(String, String), VecDeque<Recorded>>>,
+}
+
+impl Replayer {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let path = dir.join(RPC_LOG);
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read '{}': {}", path.display(), e))?;
+
+        let mut responses: HashMap<(String, String), VecDeque<Recorded>> = HashMap::new();
+        for (line_no, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let interaction: RpcInteraction = serde_json::from_str(line)
+                .map_err(|e| format!("{}:{}: {}", path.display(), line_no + 1, e))?;
+            let recorded = match (interaction.result, interaction.error) {
+                (Some(result), _) => Ok(result),
+                (None, Some(error)) => Err(error),
+                (None, None) => Ok(serde_json::Value::Null),
+            };
+            responses
+                .entry((interaction.method, interaction.params.to_string()))
+                .or_default()
+                .push_back(recorded);
+        }
+        Ok(Replayer {
+            responses: Mutex::new(responses),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.responses
+            .lock()
+            .unwrap()
+            .values()
+            .map(|r| r.len())
+            .sum()
+    }
+
+    fn answer(&self, method: &str, params: &serde_json::Value) -> Recorded {
+        let mut responses = self.responses.lock().unwrap();
+        let recorded = responses
+            .get_mut(&(method.to_string(), params.to_string()))
+            .and_then(|queue| {
+                if queue.len() > 1 {
+                    queue.pop_front()
+                } else {
+                    queue.front().cloned()
+                }
+            });
+        recorded.unwrap_or_else(|| Err(format!("no recorded response for {} {}", method, params)))
+    }
+}
+
+pub struct ReplaySender {
+    url: String,
+    replayer: Arc<Replayer>,
+}
+
+impl ReplaySender {
+    pub fn new(url: &str, replayer: Arc<Replayer>) -> Self {
+        ReplaySender {
+            url: url.to_string(),
+            replayer,
+        }
+    }
+}
+
+#[async_trait]
+impl RpcSender for ReplaySender {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        self.replayer
+            .answer(&request.to_string(), &params)
+            .map_err(|e| ClientErrorKind::Custom(e).into())
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+}
+
+fn recorded_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    let mut recorded = BTreeMap::new();
+    for (name, value) in headers {
+        let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+            "<redacted>"
+        } else {
+            value.to_str().unwrap_or("<binary>")
+        };
+        recorded
+            .entry(name.to_string())
+            .and_modify(|v: &mut String| {
+                v.push_str(", ");
+                v.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+    recorded
+}
+
+// Routes whose bodies are credentials through and through (sign-in, key
+// issuance and rotation); only their headers are recorded.
+fn credential_route(path: &str) -> bool {
+    path.starts_with("/auth/")
+        || path.starts_with("/admin/keys")
+        || (path.starts_with("/admin/orgs/") && path.contains("/keys"))
+}
+
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                if REDACTED_FIELDS.contains(&name.as_str()) {
+                    *field = serde_json::Value::from("<redacted>");
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn recorded_body(bytes: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(mut json) => {
+            redact(&mut json);
+            Some(json.to_string())
+        }
+        Err(_) => Some(text.to_string()),
+    }
+}
+
+// Whether a response can be buffered for recording: not a stream, and of
+// a known size under the limit.
+fn bufferable(headers: &HeaderMap, body: &Body) -> bool {
+    let streaming = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| STREAMING_TYPES.iter().any(|t| v.starts_with(t)));
+    let small = body
+        .size_hint()
+        .exact()
+        .is_some_and(|len| len <= MAX_RECORDED_BODY as u64);
+    !streaming && small
+}
+
+fn recordable(headers: &HeaderMap) -> bool {
+    let multipart = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("multipart/"));
+    let too_large = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|len| len > MAX_RECORDED_BODY);
+    !multipart && !too_large
+}
+
+fn internal_error(message: &str) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, message.to_string()).into_response()
+}
+
+pub async fn record(req: Request, next: Next) -> Response {
+    match rpc::recorder() {
+        Some(recorder) => record_to(recorder, req, next).await,
+        None => next.run(req).await,
+    }
+}
+
+async fn record_to(recorder: &Recorder, req: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let at_ms = now_ms();
+    let first_seq = recorder.next_seq.load(Ordering::Relaxed);
+
+    let (parts, body) = req.into_parts();
+    let credentials = credential_route(parts.uri.path());
+    let (body, request_body) = if !credentials && recordable(&parts.headers) {
+        match to_bytes(body, MAX_RECORDED_BODY).await {
+            Ok(bytes) => {
+                let text = recorded_body(&bytes);
+                (Body::from(bytes), text)
+            }
+            Err(_) => {
+                return (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "Request body is too large to record",
+                )
+                    .into_response()
+            }
+        }
+    } else {
+        (body, None)
+    };
+    let method = parts.method.to_string();
+    let uri = parts.uri.to_string();
+    let request = RecordedMessage {
+        headers: recorded_headers(&parts.headers),
+        body: request_body,
+    };
+
+    let res = next.run(Request::from_parts(parts, body)).await;
+
+    let (parts, body) = res.into_parts();
+    let (body, response_body) = if bufferable(&parts.headers, &body) {
+        let Ok(bytes) = to_bytes(body, MAX_RECORDED_BODY).await else {
+            return internal_error("Failed to read response body for recording");
+        };
+        let text = (!credentials).then(|| recorded_body(&bytes)).flatten();
+        (Body::from(bytes), text)
+    } else {
+        (body, None)
+    };
+    let response = RecordedMessage {
+        headers: recorded_headers(&parts.headers),
+        body: response_body,
+    };
+    let exchange = Exchange {
+        id: recorder.next_exchange.fetch_add(1, Ordering::Relaxed),
+        at_ms,
+        duration_ms: started.elapsed().as_millis() as u64,
+        method,
+        uri,
+        request,
+        status: parts.status.as_u16(),
+        response,
+        rpc: recorder.rpc_after(first_seq),
+    };
+    append_line(&recorder.http_log, &exchange);
+
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn recorded_calls_replay_in_order() {
+        let dir = std::env::temp_dir().join(format!("recording-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let recorder = Recorder::open(&dir).unwrap();
+        let params = json!(["wallet"]);
+        recorder.record_rpc("getBalance".into(), params.clone(), &Ok(json!(1)));
+        recorder.record_rpc("getBalance".into(), params.clone(), &Ok(json!(2)));
+        recorder.record_rpc(
+            "getSlot".into(),
+            json!([]),
+            &Err(ClientErrorKind::Custom("node down".into()).into()),
+        );
+        assert_eq!(recorder.rpc_after(2).len(), 2);
+
+        let replayer = Replayer::load(&dir).unwrap();
+        assert_eq!(replayer.len(), 3);
+        assert_eq!(replayer.answer("getBalance", &params), Ok(json!(1)));
+        assert_eq!(replayer.answer("getBalance", &params), Ok(json!(2)));
+        // The last answer repeats.
+        assert_eq!(replayer.answer("getBalance", &params), Ok(json!(2)));
+        assert_eq!(
+            replayer.answer("getSlot", &json!([])),
+            Err("Custom: node down".to_string())
+        );
+        assert_eq!(
+            replayer.answer("getBalance", &json!(["other"])),
+            Err("no recorded response for getBalance [\"other\"]".to_string())
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn headers_are_redacted_and_joined() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+        headers.append("x-tag", "a".parse().unwrap());
+        headers.append("x-tag", "b".parse().unwrap());
+        assert_eq!(
+            recorded_headers(&headers),
+            BTreeMap::from([
+                ("authorization".to_string(), "<redacted>".to_string()),
+                ("x-tag".to_string(), "a, b".to_string()),
+            ])
+        );
+
+        assert!(recordable(&headers));
+        headers.insert(header::CONTENT_LENGTH, (MAX_RECORDED_BODY + 1).into());
+        assert!(!recordable(&headers));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            "multipart/form-data; boundary=x".parse().unwrap(),
+        );
+        assert!(!recordable(&headers));
+    }
+
+    #[test]
+    fn credentials_are_kept_out_of_bodies() {
+        let issued = json!({ "id": "k1", "api_key": "sk_live", "nested": [{ "token": "t" }] });
+        let recorded = recorded_body(issued.to_string().as_bytes()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&recorded).unwrap(),
+            json!({ "id": "k1", "api_key": "<redacted>", "nested": [{ "token": "<redacted>" }] })
+        );
+        assert_eq!(recorded_body(b"plain").as_deref(), Some("plain"));
+        assert_eq!(recorded_body(&[0xff]), None);
+
+        assert!(credential_route("/auth/siws/verify"));
+        assert!(credential_route("/admin/keys/k1/rotate"));
+        assert!(credential_route("/admin/orgs/acme/keys"));
+        assert!(!credential_route("/admin/orgs/acme"));
+        assert!(!credential_route("/get_airdrop"));
+    }
+
+    #[test]
+    fn streams_are_passed_through() {
+        let mut headers = HeaderMap::new();
+        assert!(bufferable(&headers, &Body::from("{}")));
+        let stream =
+            || Body::from_stream(futures_util::stream::iter([Ok::<_, std::io::Error>("[")]));
+        assert!(!bufferable(&headers, &stream()));
+        assert!(!bufferable(
+            &headers,
+            &Body::from(vec![0; MAX_RECORDED_BODY + 1])
+        ));
+        headers.insert(header::CONTENT_TYPE, "text/event-stream".parse().unwrap());
+        assert!(!bufferable(&headers, &Body::from("data: 1\n\n")));
+    }
+
+    // Served through the middleware: issued keys come back to the caller
+    // but not into http.jsonl, and a stream reaches the caller chunk by
+    // chunk rather than once it ends.
+    #[tokio::test]
+    async fn served_requests_are_recorded_redacted_and_streams_flow() {
+        use axum::{middleware, routing::post, Json, Router};
+        use tokio::sync::mpsc;
+
+        let dir = std::env::temp_dir().join(format!("recording-serve-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let recorder = Arc::new(Recorder::open(&dir).unwrap());
+        let (chunks, rx) = mpsc::unbounded_channel::<&'static str>();
+        let rx = Arc::new(Mutex::new(Some(rx)));
+        let app = Router::new()
+            .route(
+                "/admin/keys",
+                post(|| async { Json(json!({ "id": "k1", "api_key": "sk_live" })) }),
+            )
+            .route(
+                "/echo",
+                post(|Json(body): Json<serde_json::Value>| async move { Json(body) }),
+            )
+            .route(
+                "/stream",
+                post(move || async move {
+                    let rx = rx.lock().unwrap().take().unwrap();
+                    let lines = futures_util::stream::unfold(rx, |mut rx| async move {
+                        let line = rx.recv().await?;
+                        Some((Ok::<_, std::io::Error>(line), rx))
+                    });
+                    (
+                        [(header::CONTENT_TYPE, "application/x-ndjson")],
+                        Body::from_stream(lines),
+                    )
+                }),
+            )
+            .layer(middleware::from_fn(move |req: Request, next: Next| {
+                let recorder = recorder.clone();
+                async move { record_to(&recorder, req, next).await }
+            }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let client = reqwest::Client::new();
+        let url = |path: &str| format!("http://{}{}", addr, path);
+
+        let issued: serde_json::Value = client
+            .post(url("/admin/keys"))
+            .bearer_auth("admin")
+            .json(&json!({ "label": "ci" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(issued, json!({ "id": "k1", "api_key": "sk_live" }));
+
+        let echoed: serde_json::Value = client
+            .post(url("/echo"))
+            .json(&json!({ "wallet": "w", "secret": "hunter2" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(echoed, json!({ "wallet": "w", "secret": "hunter2" }));
+
+        // The second line is only sent once the first has arrived.
+        let mut streamed = client.post(url("/stream")).send().await.unwrap();
+        chunks.send("{\"n\":1}\n").unwrap();
+        let first = streamed.chunk().await.unwrap().unwrap();
+        assert_eq!(&first[..], b"{\"n\":1}\n");
+        chunks.send("{\"n\":2}\n").unwrap();
+        drop(chunks);
+        let mut rest = Vec::new();
+        while let Some(chunk) = streamed.chunk().await.unwrap() {
+            rest.extend_from_slice(&chunk);
+        }
+        assert_eq!(rest, b"{\"n\":2}\n");
+
+        let log = fs::read_to_string(dir.join(HTTP_LOG)).unwrap();
+        let exchanges: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let recorded: Vec<_> = exchanges
+            .iter()
+            .map(|e| {
+                (
+                    e["uri"].as_str().unwrap(),
+                    e["status"].as_u64().unwrap(),
+                    &e["request"]["body"],
+                    &e["response"]["body"],
+                )
+            })
+            .collect();
+        let echoed = json!({ "secret": "<redacted>", "wallet": "w" }).to_string();
+        assert_eq!(
+            recorded,
+            [
+                ("/admin/keys", 200, &json!(null), &json!(null)),
+                ("/echo", 200, &json!(echoed), &json!(echoed)),
+                ("/stream", 200, &json!(""), &json!(null)),
+            ]
+        );
+        assert_eq!(
+            exchanges[0]["request"]["headers"]["authorization"],
+            "<redacted>"
+        );
+        assert!(!log.contains("sk_live") && !log.contains("hunter2"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
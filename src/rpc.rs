@@ -0,0 +1,494 @@
+use async_trait::async_trait;
+use solana_client::{
+    client_error::{ClientErrorKind, Result as ClientResult},
+    rpc_client::{RpcClient, RpcClientConfig},
+    rpc_request::{RpcError, RpcRequest},
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::{Backend, Config},
+    logging,
+    mock::{MockChain, MockSender},
+    pool::{self, PooledSender},
+    recording::{Recorder, RecordingSender, ReplaySender, Replayer},
+    service::{self, ServiceError},
+};
+
+enum Transport {
+    Mock(Arc<MockChain>),
+    Record(Arc<Recorder>),
+    Replay(Arc<Replayer>),
+}
+
+// Chosen once at startup; the backend and the recording and replay
+// directories can't be changed by a reload.
+static TRANSPORT: OnceLock<Transport> = OnceLock::new();
+
+// getMultipleAccounts takes at most this many addresses per call.
+pub const MAX_ACCOUNTS_PER_CALL: usize = 100;
+
+// Recent RPC call durations across every client, for load shedding.
+static LATENCY: Mutex<VecDeque<(Instant, Duration)>> = Mutex::new(VecDeque::new());
+const MAX_LATENCY_SAMPLES: usize = 1_000;
+
+// Counter for the RPC calls made on behalf of the current HTTP request.
+// Blocking tasks started with `spawn_blocking` below carry it over in a
+// thread local.
+tokio::task_local! {
+    static REQUEST_CALLS: Arc<AtomicU64>;
+}
+thread_local! {
+    static BLOCKING_CALLS: RefCell<Option<Arc<AtomicU64>>> = const { RefCell::new(None) };
+}
+
+// Likewise the lowest `context.slot` among the RPC answers for the current
+// read request, u64::MAX until one carries a context.
+tokio::task_local! {
+    static REQUEST_SLOT: Arc<AtomicU64>;
+}
+thread_local! {
+    static BLOCKING_SLOT: RefCell<Option<Arc<AtomicU64>>> = const { RefCell::new(None) };
+}
+
+// For `consistent=true` reads: the slot every sub-read must have been
+// answered at or after.
+tokio::task_local! {
+    static MIN_SLOT: u64;
+}
+thread_local! {
+    static BLOCKING_MIN_SLOT: RefCell<Option<u64>> = const { RefCell::new(None) };
+}
+
+// Methods that take `minContextSlot`, with the number of positional
+// parameters before their config object.
+const MIN_SLOT_METHODS: [(&str, usize); 8] = [
+    ("getBalance", 1),
+    ("getAccountInfo", 1),
+    ("getMultipleAccounts", 1),
+    ("getProgramAccounts", 1),
+    ("getTokenAccountsByOwner", 2),
+    ("getTokenAccountBalance", 1),
+    ("getSignaturesForAddress", 1),
+    ("getSlot", 0),
+];
+// Load-balanced endpoints can route a retry to a node that has caught up.
+const MIN_SLOT_ATTEMPTS: usize = 5;
+const MIN_SLOT_RETRY_DELAY: Duration = Duration::from_millis(200);
+// "Minimum context slot has not been reached"
+const MIN_SLOT_NOT_REACHED: i64 = -32016;
+
+// `treasury` is the server keypair's address, funded when the mock backend
+// is in use.
+pub fn init(config: &Config, treasury: Option<Pubkey>) -> Result<(), String> {
+    pool::init(config)?;
+    let transport = match (config.backend, &config.record_dir, &config.replay_dir) {
+        (Backend::Mock, _, _) => {
+            logging::info("mock backend: RPC calls are answered by an in-memory ledger");
+            Transport::Mock(Arc::new(MockChain::new(treasury)))
+        }
+        (_, Some(dir), _) => {
+            logging::info(format_args!(
+                "recording requests and RPC calls to {}",
+                dir.display()
+            ));
+            Transport::Record(Arc::new(Recorder::open(dir)?))
+        }
+        (_, _, Some(dir)) => {
+            let replayer = Replayer::load(dir)?;
+            logging::info(format_args!(
+                "replay mode: answering RPC calls from {} ({} recorded calls)",
+                dir.display(),
+                replayer.len()
+            ));
+            Transport::Replay(Arc::new(replayer))
+        }
+        _ => return Ok(()),
+    };
+    TRANSPORT
+        .set(transport)
+        .map_err(|_| "RPC transport already initialised".to_string())
+}
+
+// Every RpcClient in the server is built here so the mock backend,
+// recording, replay and latency tracking apply to all of them.
+pub fn client(rpc_url: &str) -> RpcClient {
+    match TRANSPORT.get() {
+        None => timed(PooledSender::new(rpc_url)),
+        Some(Transport::Mock(chain)) => timed(MockSender::new(chain.clone())),
+        Some(Transport::Record(recorder)) => timed(RecordingSender::new(
+            PooledSender::new(rpc_url),
+            recorder.clone(),
+        )),
+        Some(Transport::Replay(replayer)) => timed(ReplaySender::new(rpc_url, replayer.clone())),
+    }
+}
+
+fn timed(sender: impl RpcSender + Send + Sync + 'static) -> RpcClient {
+    let calls = REQUEST_CALLS
+        .try_with(|calls| calls.clone())
+        .ok()
+        .or_else(|| BLOCKING_CALLS.with(|calls| calls.borrow().clone()));
+    let slot = REQUEST_SLOT
+        .try_with(|slot| slot.clone())
+        .ok()
+        .or_else(|| BLOCKING_SLOT.with(|slot| slot.borrow().clone()));
+    let min_slot = MIN_SLOT
+        .try_with(|slot| *slot)
+        .ok()
+        .or_else(|| BLOCKING_MIN_SLOT.with(|slot| *slot.borrow()));
+    RpcClient::new_sender(
+        TimedSender {
+            inner: sender,
+            calls,
+            slot,
+            min_slot,
+        },
+        RpcClientConfig::default(),
+    )
+}
+
+struct TimedSender<S> {
+    inner: S,
+    calls: Option<Arc<AtomicU64>>,
+    slot: Option<Arc<AtomicU64>>,
+    min_slot: Option<u64>,
+}
+
+// Adds `minContextSlot` to the call's config object, creating it when the
+// call has none. False for methods that don't take it.
+fn with_min_slot(request: RpcRequest, params: &mut serde_json::Value, min_slot: u64) -> bool {
+    let method = request.to_string();
+    let Some((_, positional)) = MIN_SLOT_METHODS.iter().find(|(m, _)| *m == method) else {
+        return false;
+    };
+    let Some(params) = params.as_array_mut() else {
+        return false;
+    };
+    if params.len() == *positional {
+        params.push(serde_json::json!({}));
+    }
+    match params.get_mut(*positional).and_then(|c| c.as_object_mut()) {
+        Some(config) => {
+            config.insert("minContextSlot".to_string(), min_slot.into());
+            true
+        }
+        None => false,
+    }
+}
+
+fn min_slot_not_reached(outcome: &ClientResult<serde_json::Value>, min_slot: u64) -> bool {
+    match outcome {
+        Ok(value) => value
+            .pointer("/context/slot")
+            .and_then(|s| s.as_u64())
+            .is_some_and(|slot| slot < min_slot),
+        Err(e) => matches!(
+            e.kind(),
+            ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. })
+                if *code == MIN_SLOT_NOT_REACHED
+        ),
+    }
+}
+
+#[async_trait]
+impl<S: RpcSender + Send + Sync> RpcSender for TimedSender<S> {
+    // With a minimum slot pinned, calls that take one are sent with it and
+    // re-sent while the answer comes from an older slot.
+    async fn send(
+        &self,
+        request: RpcRequest,
+        mut params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        let Some(min_slot) = self
+            .min_slot
+            .filter(|slot| with_min_slot(request, &mut params, *slot))
+        else {
+            return self.send_once(request, params).await;
+        };
+        let mut attempts = 1;
+        loop {
+            let outcome = self.send_once(request, params.clone()).await;
+            if !min_slot_not_reached(&outcome, min_slot) {
+                return outcome;
+            }
+            if attempts == MIN_SLOT_ATTEMPTS {
+                return Err(ClientErrorKind::Custom(format!(
+                    "RPC node didn't reach slot {} after {} attempts",
+                    min_slot, attempts
+                ))
+                .into());
+            }
+            attempts += 1;
+            tokio::time::sleep(MIN_SLOT_RETRY_DELAY).await;
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+impl<S: RpcSender + Send + Sync> TimedSender<S> {
+    async fn send_once(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        if let Some(calls) = &self.calls {
+            calls.fetch_add(1, Ordering::Relaxed);
+        }
+        let started = Instant::now();
+        let outcome = self.inner.send(request, params).await;
+        let mut latency = LATENCY.lock().unwrap();
+        if latency.len() == MAX_LATENCY_SAMPLES {
+            latency.pop_front();
+        }
+        latency.push_back((started, started.elapsed()));
+        drop(latency);
+        if let (Some(slot), Ok(value)) = (&self.slot, &outcome) {
+            if let Some(observed) = value.pointer("/context/slot").and_then(|s| s.as_u64()) {
+                slot.fetch_min(observed, Ordering::Relaxed);
+            }
+        }
+        outcome
+    }
+}
+
+// p95 duration of the RPC calls started within `window`, with the number of
+// calls it covers. None when there were none.
+pub fn p95_latency(window: Duration) -> Option<(Duration, usize)> {
+    let mut latency = LATENCY.lock().unwrap();
+    while latency.front().is_some_and(|(at, _)| at.elapsed() > window) {
+        latency.pop_front();
+    }
+    if latency.is_empty() {
+        return None;
+    }
+    let mut durations: Vec<Duration> = latency.iter().map(|(_, d)| *d).collect();
+    durations.sort_unstable();
+    Some((
+        durations[(durations.len() * 95).div_ceil(100) - 1],
+        durations.len(),
+    ))
+}
+
+// Runs `f` (a request handler) counting the RPC calls it makes in `calls`.
+pub async fn counting<F: Future>(calls: Arc<AtomicU64>, f: F) -> F::Output {
+    REQUEST_CALLS.scope(calls, f).await
+}
+
+// Runs `f` (a read handler) recording in `slot` the lowest context slot of
+// the RPC answers it gets.
+pub async fn observing_slot<F: Future>(slot: Arc<AtomicU64>, f: F) -> F::Output {
+    REQUEST_SLOT.scope(slot, f).await
+}
+
+// Runs `f` (a read handler) with every sub-read that takes a minimum
+// context slot pinned to `min_slot`, when set.
+pub async fn pinned<F: Future>(min_slot: Option<u64>, f: F) -> F::Output {
+    match min_slot {
+        Some(slot) => MIN_SLOT.scope(slot, f).await,
+        None => f.await,
+    }
+}
+
+// The slot to pin a consistent read to: the node's current one at the
+// client's commitment.
+pub fn current_slot(rpc_url: &str) -> Result<u64, ServiceError> {
+    client(rpc_url)
+        .get_slot()
+        .map_err(service::rpc_error("Failed to get slot"))
+}
+
+struct BlockingCallsGuard;
+
+impl Drop for BlockingCallsGuard {
+    fn drop(&mut self) {
+        BLOCKING_CALLS.with(|calls| calls.borrow_mut().take());
+        BLOCKING_SLOT.with(|slot| slot.borrow_mut().take());
+        BLOCKING_MIN_SLOT.with(|slot| slot.borrow_mut().take());
+    }
+}
+
+// tokio::task::spawn_blocking, keeping RPC calls made by `f` attributed to
+// the request that spawned it, and its spans under the request's.
+pub fn spawn_blocking<F, R>(f: F) -> tokio::task::JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let calls = REQUEST_CALLS.try_with(|calls| calls.clone()).ok();
+    let observed = REQUEST_SLOT.try_with(|slot| slot.clone()).ok();
+    let min_slot = MIN_SLOT.try_with(|slot| *slot).ok();
+    let span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || {
+        BLOCKING_MIN_SLOT.with(|slot| *slot.borrow_mut() = min_slot);
+        BLOCKING_CALLS.with(|slot| *slot.borrow_mut() = calls);
+        BLOCKING_SLOT.with(|slot| *slot.borrow_mut() = observed);
+        let _guard = BlockingCallsGuard;
+        span.in_scope(f)
+    })
+}
+
+// `spawn_blocking`, with the sub-reads of `f` pinned to `min_slot` when set.
+pub fn spawn_blocking_pinned<F, R>(min_slot: Option<u64>, f: F) -> tokio::task::JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    spawn_blocking(move || {
+        if min_slot.is_some() {
+            BLOCKING_MIN_SLOT.with(|slot| *slot.borrow_mut() = min_slot);
+        }
+        f()
+    })
+}
+
+pub fn recorder() -> Option<&'static Arc<Recorder>> {
+    match TRANSPORT.get() {
+        Some(Transport::Record(recorder)) => Some(recorder),
+        _ => None,
+    }
+}
+
+pub fn mock() -> Option<&'static Arc<MockChain>> {
+    match TRANSPORT.get() {
+        Some(Transport::Mock(chain)) => Some(chain),
+        _ => None,
+    }
+}
+
+// Tests share one mock chain for the whole binary; they use fresh
+// addresses rather than resetting it, since other tests run alongside.
+#[cfg(test)]
+pub fn mock_for_tests() -> Arc<MockChain> {
+    match TRANSPORT.get_or_init(|| Transport::Mock(Arc::new(MockChain::new(None)))) {
+        Transport::Mock(chain) => chain.clone(),
+        _ => panic!("tests need the mock transport"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn min_slot_goes_into_the_config_object() {
+        let mut params = json!(["wallet"]);
+        assert!(with_min_slot(RpcRequest::GetBalance, &mut params, 7));
+        assert_eq!(params, json!(["wallet", { "minContextSlot": 7 }]));
+
+        let mut params = json!(["owner", { "mint": "m" }, { "commitment": "confirmed" }]);
+        assert!(with_min_slot(
+            RpcRequest::GetTokenAccountsByOwner,
+            &mut params,
+            9
+        ));
+        assert_eq!(
+            params[2],
+            json!({ "commitment": "confirmed", "minContextSlot": 9 })
+        );
+
+        let mut params = json!([]);
+        assert!(with_min_slot(RpcRequest::GetSlot, &mut params, 3));
+        assert_eq!(params, json!([{ "minContextSlot": 3 }]));
+
+        let mut params = json!(["sig"]);
+        assert!(!with_min_slot(RpcRequest::GetTransaction, &mut params, 3));
+        assert_eq!(params, json!(["sig"]));
+    }
+
+    #[test]
+    fn stale_answers_are_told_apart() {
+        let answer = |slot: u64| json!({ "context": { "slot": slot }, "value": 1 });
+        assert!(min_slot_not_reached(&Ok(answer(9)), 10));
+        assert!(!min_slot_not_reached(&Ok(answer(10)), 10));
+        assert!(!min_slot_not_reached(&Ok(json!(5)), 10));
+        let behind: ClientResult<serde_json::Value> =
+            Err(ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                code: MIN_SLOT_NOT_REACHED,
+                message: "Minimum context slot has not been reached".into(),
+                data: solana_client::rpc_request::RpcResponseErrorData::Empty,
+            })
+            .into());
+        assert!(min_slot_not_reached(&behind, 10));
+        let other: ClientResult<serde_json::Value> =
+            Err(ClientErrorKind::Custom("timed out".into()).into());
+        assert!(!min_slot_not_reached(&other, 10));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn blocking_calls_count_towards_the_request() {
+        mock_for_tests();
+        let wallet = Pubkey::new_unique();
+        let (calls, observed) = (
+            Arc::new(AtomicU64::new(0)),
+            Arc::new(AtomicU64::new(u64::MAX)),
+        );
+        let balance = counting(
+            calls.clone(),
+            observing_slot(observed.clone(), async move {
+                spawn_blocking(move || {
+                    let client = client("http://mock");
+                    client.get_balance(&wallet).unwrap();
+                    client.get_balance(&wallet).unwrap()
+                })
+                .await
+                .unwrap()
+            }),
+        )
+        .await;
+        assert_eq!(balance, 0);
+        // The client looks up the node's version once, before its first call.
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+        assert_ne!(observed.load(Ordering::Relaxed), u64::MAX);
+        assert!(p95_latency(Duration::from_secs(60)).is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pinned_reads_give_up_on_a_node_that_lags() {
+        mock_for_tests();
+        let wallet = Pubkey::new_unique();
+        let current = current_slot("http://mock").unwrap();
+        let ahead = current + 1_000_000;
+        let result = spawn_blocking_pinned(Some(ahead), move || {
+            client("http://mock")
+                .get_balance(&wallet)
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            result.unwrap_err(),
+            format!(
+                "Custom: RPC node didn't reach slot {} after {} attempts",
+                ahead, MIN_SLOT_ATTEMPTS
+            )
+        );
+        let result = spawn_blocking_pinned(Some(current), move || {
+            client("http://mock")
+                .get_balance(&wallet)
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .unwrap();
+        assert_eq!(result.unwrap(), 0);
+    }
+}
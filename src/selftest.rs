@@ -0,0 +1,228 @@
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, signature::Signer};
+use std::{
+    fs::{self, OpenOptions},
+    path::Path,
+};
+
+use crate::{
+    amount, compat,
+    config::{Config, StoreBackend},
+    freshness, rpc, service,
+    state::AppState,
+    store,
+};
+
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+fn check(name: &'static str, result: Result<String, String>) -> Check {
+    match result {
+        Ok(detail) => Check {
+            name,
+            passed: true,
+            detail,
+        },
+        Err(detail) => Check {
+            name,
+            passed: false,
+            detail,
+        },
+    }
+}
+
+// Stores are plain files, so "connecting" is making sure they can be
+// written without touching what is there: an existing file is opened for
+// appending, a missing one needs a writable directory.
+fn check_writable(path: &Path) -> Result<String, String> {
+    if path.exists() {
+        OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{} is not writable: {}", path.display(), e))?;
+        return Ok(format!("{} is writable", path.display()));
+    }
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let probe = dir.join(format!(".self-test-{:016x}", rand::random::<u64>()));
+    fs::write(&probe, b"")
+        .and_then(|()| fs::remove_file(&probe))
+        .map_err(|e| format!("can't create files in {}: {}", dir.display(), e))?;
+    Ok(format!("{} will be created", path.display()))
+}
+
+// Reachable, and within rpc_min_version / rpc_max_version and
+// rpc_max_slot_lag.
+fn check_rpc(config: &Config) -> Result<String, String> {
+    let status = compat::check(&config.rpc_url)
+        .map_err(|e| format!("{} is unreachable: {}", config.rpc_url, e))?;
+    let slot_lag = freshness::check(&config.rpc_url, config.rpc_reference_url.as_deref())
+        .ok()
+        .and_then(|s| s.lag_slots);
+    let version = status.version.unwrap_or_default();
+    let problems = compat::problems(config, &version, slot_lag);
+    if !problems.is_empty() {
+        return Err(problems.join("; "));
+    }
+    Ok(format!("{} runs solana-core {}", config.rpc_url, version))
+}
+
+fn check_treasury(rpc_url: &str, wallet: &str, min_sol: Option<f64>) -> Result<String, String> {
+    let balance = service::get_balance(rpc_url, wallet).map_err(|e| e.to_string())?;
+    let sol = amount::lamports_to_sol_decimal(balance);
+    match min_sol {
+        Some(min_sol) if balance < (min_sol * LAMPORTS_PER_SOL as f64) as u64 => Err(format!(
+            "{} holds {} SOL, below the {} SOL minimum",
+            wallet, sol, min_sol
+        )),
+        _ => Ok(format!("{} holds {} SOL", wallet, sol)),
+    }
+}
+
+// Runs every check, even after one fails, so the report is complete. The
+// config was validated when it was loaded.
+pub async fn run(state: &AppState) -> Vec<Check> {
+    let config = state.config();
+    let mut checks = vec![Check {
+        name: "config",
+        passed: true,
+        detail: "valid".to_string(),
+    }];
+
+    let rpc_config = config.clone();
+    let result = rpc::spawn_blocking(move || check_rpc(&rpc_config))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+    checks.push(check("rpc", result));
+
+    if let Some(keypair) = state.keypair.clone() {
+        let message = b"self-test";
+        let signature = keypair.sign_message(message);
+        let result = if signature.verify(keypair.pubkey().as_ref(), message) {
+            Ok(format!("{} can sign", keypair.pubkey()))
+        } else {
+            Err(format!(
+                "{} produced an invalid signature",
+                keypair.pubkey()
+            ))
+        };
+        checks.push(check("keypair", result));
+
+        let rpc_url = config.rpc_url.clone();
+        let wallet = keypair.pubkey().to_string();
+        let min_sol = config
+            .self_test_min_treasury_sol
+            .or(config.treasury_low_watermark_sol);
+        let result = rpc::spawn_blocking(move || check_treasury(&rpc_url, &wallet, min_sol))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+        checks.push(check("treasury", result));
+    }
+
+    match config.store_backend {
+        StoreBackend::File => {
+            for (name, path) in store::file_paths(&config) {
+                checks.push(check(name, check_writable(path)));
+            }
+        }
+        StoreBackend::Memory => {}
+        StoreBackend::Sqlite | StoreBackend::Postgres => {
+            let result = store::open(&config).map(|_| "connected".to_string());
+            checks.push(check("store", result));
+        }
+    }
+    checks
+}
+
+// Prints the report and fails when any check did.
+pub fn report(checks: &[Check]) -> Result<(), String> {
+    println!("self-test:");
+    for check in checks {
+        println!(
+            "  {:<4} {:<11} {}",
+            if check.passed { "ok" } else { "FAIL" },
+            check.name,
+            check.detail
+        );
+    }
+    let failed = checks.iter().filter(|c| !c.passed).count();
+    if failed > 0 {
+        return Err(format!(
+            "self-test failed: {} of {} checks failed",
+            failed,
+            checks.len()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn stores_must_be_writable() {
+        let dir = std::env::temp_dir();
+        let existing = dir.join(format!("self-test-existing-{}", std::process::id()));
+        fs::write(&existing, "keep me").unwrap();
+        assert!(check_writable(&existing).unwrap().ends_with("is writable"));
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "keep me");
+        fs::remove_file(&existing).unwrap();
+
+        let missing = dir.join(format!("self-test-missing-{}", std::process::id()));
+        assert!(check_writable(&missing)
+            .unwrap()
+            .ends_with("will be created"));
+        assert!(!missing.exists());
+
+        let nowhere = Path::new("/definitely/not/here/store.json");
+        assert!(check_writable(nowhere)
+            .unwrap_err()
+            .starts_with("can't create files in /definitely/not/here"));
+    }
+
+    #[test]
+    fn treasury_needs_the_minimum() {
+        let chain = rpc::mock_for_tests();
+        let wallet = Pubkey::new_unique();
+        chain.set_balance(wallet, 1_500_000_000);
+        let wallet = wallet.to_string();
+
+        assert_eq!(
+            check_treasury("http://mock", &wallet, Some(1.0)).unwrap(),
+            format!("{} holds 1.5 SOL", wallet)
+        );
+        assert_eq!(
+            check_treasury("http://mock", &wallet, Some(2.0)).unwrap_err(),
+            format!("{} holds 1.5 SOL, below the 2 SOL minimum", wallet)
+        );
+        assert!(check_treasury("http://mock", &wallet, None).is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn run_checks_config_and_rpc() {
+        let state = AppState::for_tests(Config::default());
+        let checks = run(&state).await;
+        let names: Vec<&str> = checks.iter().map(|c| c.name).collect();
+        assert_eq!(names, ["config", "rpc"]);
+        assert!(checks.iter().all(|c| c.passed), "{}", checks[1].detail);
+        assert!(report(&checks).is_ok());
+    }
+
+    #[test]
+    fn report_fails_when_any_check_did() {
+        let checks = [
+            check("config", Ok("valid".into())),
+            check("rpc", Err("unreachable".into())),
+        ];
+        assert_eq!(
+            report(&checks).unwrap_err(),
+            "self-test failed: 1 of 2 checks failed"
+        );
+    }
+}
@@ -0,0 +1,554 @@
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::{AccountMeta, Instruction},
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+use std::{fmt, str::FromStr};
+
+use crate::{amount, confirm, decoders::MEMO_PROGRAM_ID, rpc};
+
+// Shared by the HTTP handlers and the CLI so both paths validate and talk to
+// the RPC node the same way.
+#[derive(Debug)]
+pub enum ServiceError {
+    InvalidWallet,
+    Invalid(String),
+    HistoryUnavailable(String),
+    AirdropTooSmall {
+        min_lamports: u64,
+    },
+    AirdropTooLarge {
+        max_lamports: u64,
+    },
+    Denylisted(Pubkey),
+    NotAllowlisted(Pubkey),
+    SignInRequired,
+    SessionWalletMismatch(Pubkey),
+    QuotaExceeded {
+        remaining_lamports: u64,
+    },
+    RpcBudgetExceeded {
+        budget: u64,
+    },
+    TreasuryLow,
+    FeatureDisabled(String),
+    RpcIncompatible(String),
+    DeprecatedFields(String),
+    // `airdrop_maintenance` is set.
+    Maintenance {
+        message: String,
+        retry_after_secs: Option<u64>,
+    },
+    // The RPC node refused an airdrop: too many recently, or none left.
+    FaucetRateLimited(String),
+    FaucetExhausted(String),
+    Rpc {
+        action: &'static str,
+        message: String,
+    },
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::InvalidWallet => write!(f, "Invalid wallet address"),
+            ServiceError::Invalid(message) => write!(f, "{}", message),
+            ServiceError::HistoryUnavailable(message) => write!(f, "{}", message),
+            ServiceError::AirdropTooSmall { min_lamports } => {
+                write!(
+                    f,
+                    "Airdrop amount too small (min {} lamports)",
+                    min_lamports
+                )
+            }
+            ServiceError::AirdropTooLarge { max_lamports } => write!(
+                f,
+                "Airdrop amount too large (max {} SOL)",
+                amount::lamports_to_sol_decimal(*max_lamports)
+            ),
+            ServiceError::Denylisted(wallet) => {
+                write!(f, "Wallet {} is not allowed to use this service", wallet)
+            }
+            ServiceError::NotAllowlisted(wallet) => {
+                write!(f, "Wallet {} is not registered with this faucet", wallet)
+            }
+            ServiceError::SignInRequired => {
+                write!(
+                    f,
+                    "Sign in with the wallet (SIWS) before requesting airdrops"
+                )
+            }
+            ServiceError::SessionWalletMismatch(wallet) => {
+                write!(f, "This session is only valid for wallet {}", wallet)
+            }
+            ServiceError::QuotaExceeded { remaining_lamports } => write!(
+                f,
+                "Daily airdrop quota exceeded ({} SOL remaining today)",
+                amount::lamports_to_sol_decimal(*remaining_lamports)
+            ),
+            ServiceError::RpcBudgetExceeded { budget } => write!(
+                f,
+                "Daily RPC budget of {} calls used up for this credential",
+                budget
+            ),
+            ServiceError::TreasuryLow => write!(
+                f,
+                "The treasury is below its low watermark; writes are paused until it is refilled"
+            ),
+            ServiceError::FeatureDisabled(route) => {
+                write!(f, "{} is disabled on this server", route)
+            }
+            ServiceError::RpcIncompatible(problems) => {
+                write!(f, "Writes are paused: {}", problems)
+            }
+            ServiceError::DeprecatedFields(fields) => {
+                write!(f, "Deprecated fields are no longer accepted: {}", fields)
+            }
+            ServiceError::Maintenance { message, .. } => write!(f, "{}", message),
+            ServiceError::FaucetRateLimited(message) => {
+                write!(f, "The faucet is rate limiting airdrops: {}", message)
+            }
+            ServiceError::FaucetExhausted(message) => {
+                write!(f, "The faucet has run out of SOL: {}", message)
+            }
+            ServiceError::Rpc { action, message } => write!(f, "{}: {}", action, message),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+pub fn rpc_error(
+    action: &'static str,
+) -> impl Fn(solana_client::client_error::ClientError) -> ServiceError {
+    move |e| ServiceError::Rpc {
+        action,
+        message: e.to_string(),
+    }
+}
+
+// Airdrop failures the RPC node's faucet explains, told apart from other
+// RPC errors so callers know whether retrying can help.
+fn faucet_error(e: solana_client::client_error::ClientError) -> ServiceError {
+    let message = e.to_string();
+    let lower = message.to_ascii_lowercase();
+    if lower.contains("429") || lower.contains("rate limit") || lower.contains("too many requests")
+    {
+        ServiceError::FaucetRateLimited(message)
+    } else if lower.contains("run dry") || lower.contains("insufficient") {
+        ServiceError::FaucetExhausted(message)
+    } else {
+        ServiceError::Rpc {
+            action: "Airdrop failed",
+            message,
+        }
+    }
+}
+
+pub fn parse_wallet(wallet: &str) -> Result<Pubkey, ServiceError> {
+    Pubkey::from_str(wallet).map_err(|_| ServiceError::InvalidWallet)
+}
+
+pub fn get_balance(rpc_url: &str, wallet: &str) -> Result<u64, ServiceError> {
+    let pubkey = parse_wallet(wallet)?;
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.get_balance", %rpc_url).entered();
+    client
+        .get_balance(&pubkey)
+        .map_err(rpc_error("Failed to get balance"))
+}
+
+pub struct BalanceProjection {
+    // "processed", "confirmed" or "finalized"; "pending" before the node
+    // has seen the transaction.
+    pub commitment: String,
+    pub confirmed_balance: u64,
+    // The confirmed balance with the credit added until the transaction is
+    // confirmed (and included in it); the confirmed balance once it failed.
+    pub projected_balance: u64,
+}
+
+// Blocking. Where `wallet` stands while a transaction crediting it with
+// `lamports` is on its way, without waiting for it to finalize.
+pub fn project_balance(
+    rpc_url: &str,
+    wallet: &Pubkey,
+    signature: &Signature,
+    lamports: u64,
+) -> Result<BalanceProjection, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.project_balance", %rpc_url).entered();
+    let status = client
+        .get_signature_statuses(&[*signature])
+        .map_err(rpc_error("Failed to get the transaction status"))?
+        .value
+        .into_iter()
+        .next()
+        .flatten();
+    let confirmed_balance = client
+        .get_balance_with_commitment(wallet, CommitmentConfig::confirmed())
+        .map_err(rpc_error("Failed to get balance"))?
+        .value;
+    let (commitment, projected_balance) = match status {
+        None => (
+            "pending".to_string(),
+            confirmed_balance.saturating_add(lamports),
+        ),
+        Some(status) if status.err.is_some() => (commitment_name(&status), confirmed_balance),
+        Some(status) if status.satisfies_commitment(CommitmentConfig::confirmed()) => {
+            (commitment_name(&status), confirmed_balance)
+        }
+        Some(status) => (
+            commitment_name(&status),
+            confirmed_balance.saturating_add(lamports),
+        ),
+    };
+    Ok(BalanceProjection {
+        commitment,
+        confirmed_balance,
+        projected_balance,
+    })
+}
+
+pub struct TransactionProgress {
+    // "pending", "processed", "confirmed" or "finalized", as for
+    // BalanceProjection.
+    pub commitment: String,
+    pub slot: Option<u64>,
+    // Set once the transaction failed.
+    pub error: Option<String>,
+}
+
+// Blocking. How far the cluster has got with `signature`.
+pub fn transaction_progress(
+    rpc_url: &str,
+    signature: &Signature,
+) -> Result<TransactionProgress, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.transaction_progress", %rpc_url).entered();
+    let status = client
+        .get_signature_statuses(&[*signature])
+        .map_err(rpc_error("Failed to get the transaction status"))?
+        .value
+        .into_iter()
+        .next()
+        .flatten();
+    Ok(match status {
+        None => TransactionProgress {
+            commitment: "pending".to_string(),
+            slot: None,
+            error: None,
+        },
+        Some(status) => TransactionProgress {
+            commitment: commitment_name(&status),
+            slot: Some(status.slot),
+            error: status.err.map(|e| e.to_string()),
+        },
+    })
+}
+
+fn commitment_name(status: &TransactionStatus) -> String {
+    match status.confirmation_status() {
+        TransactionConfirmationStatus::Processed => "processed",
+        TransactionConfirmationStatus::Confirmed => "confirmed",
+        TransactionConfirmationStatus::Finalized => "finalized",
+    }
+    .to_string()
+}
+
+// Bounds every airdrop is checked against, whichever path requested it.
+#[derive(Clone, Copy)]
+pub struct AirdropLimits {
+    pub min_lamports: u64,
+    pub max_lamports: u64,
+}
+
+pub fn sol_to_lamports(sol: u64) -> Result<u64, ServiceError> {
+    sol.checked_mul(LAMPORTS_PER_SOL)
+        .ok_or_else(|| ServiceError::Invalid(format!("{} SOL is out of range", sol)))
+}
+
+// Requests name the amount in either SOL or lamports, never both.
+pub fn airdrop_lamports(sol: Option<u64>, lamports: Option<u64>) -> Result<u64, ServiceError> {
+    match (sol, lamports) {
+        (Some(sol), None) => sol_to_lamports(sol),
+        (None, Some(lamports)) => Ok(lamports),
+        (Some(_), Some(_)) => Err(ServiceError::Invalid(
+            "Specify either sol or lamports, not both".to_string(),
+        )),
+        (None, None) => Err(ServiceError::Invalid(
+            "Specify the airdrop amount in sol or lamports".to_string(),
+        )),
+    }
+}
+
+pub fn check_airdrop_amount(lamports: u64, limits: AirdropLimits) -> Result<(), ServiceError> {
+    if lamports < limits.min_lamports {
+        return Err(ServiceError::AirdropTooSmall {
+            min_lamports: limits.min_lamports,
+        });
+    }
+    if lamports > limits.max_lamports {
+        return Err(ServiceError::AirdropTooLarge {
+            max_lamports: limits.max_lamports,
+        });
+    }
+    Ok(())
+}
+
+pub fn request_airdrop(
+    rpc_url: &str,
+    wallet: &str,
+    lamports: u64,
+    limits: AirdropLimits,
+) -> Result<Signature, ServiceError> {
+    let pubkey = parse_wallet(wallet)?;
+    check_airdrop_amount(lamports, limits)?;
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.request_airdrop", %rpc_url, lamports).entered();
+    client
+        .request_airdrop(&pubkey, lamports)
+        .map_err(faucet_error)
+}
+
+// Pays from the server keypair; used where the faucet should not depend on
+// the RPC node's airdrop limits. Returns once the transfer is confirmed,
+// or, without `confirm`, once the node accepted it.
+pub fn transfer(
+    rpc_url: &str,
+    payer: &Keypair,
+    to: &Pubkey,
+    lamports: u64,
+    confirm: bool,
+) -> Result<Signature, ServiceError> {
+    send_transfer(rpc_url, payer, to, lamports, None, confirm)
+}
+
+// A transfer with a memo instruction signed by the payer, so the memo is
+// attributable to it.
+pub fn transfer_with_memo(
+    rpc_url: &str,
+    payer: &Keypair,
+    to: &Pubkey,
+    lamports: u64,
+    memo: &str,
+    confirm: bool,
+) -> Result<Signature, ServiceError> {
+    send_transfer(rpc_url, payer, to, lamports, Some(memo), confirm)
+}
+
+fn send_transfer(
+    rpc_url: &str,
+    payer: &Keypair,
+    to: &Pubkey,
+    lamports: u64,
+    memo: Option<&str>,
+    confirm: bool,
+) -> Result<Signature, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.transfer", %rpc_url, lamports).entered();
+    let tx = transfer_transaction(&client, payer, to, lamports, memo)?;
+    if !confirm {
+        return client
+            .send_transaction(&tx)
+            .map_err(rpc_error("Transfer failed"));
+    }
+    confirm::send_and_confirm(&client, &tx, "Transfer failed")
+}
+
+pub struct SimulatedTransfer {
+    pub fee_lamports: u64,
+    pub error: Option<String>,
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+}
+
+// Blocking. Simulates the transaction `transfer` (or `transfer_with_memo`)
+// would send, without sending it.
+pub fn simulate_transfer(
+    rpc_url: &str,
+    payer: &Keypair,
+    to: &Pubkey,
+    lamports: u64,
+    memo: Option<&str>,
+) -> Result<SimulatedTransfer, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.simulate_transfer", %rpc_url, lamports).entered();
+    let tx = transfer_transaction(&client, payer, to, lamports, memo)?;
+    let fee_lamports = client
+        .get_fee_for_message(&tx.message)
+        .map_err(rpc_error("Failed to estimate the fee"))?;
+    let result = client
+        .simulate_transaction(&tx)
+        .map_err(rpc_error("Failed to simulate the transfer"))?
+        .value;
+    Ok(SimulatedTransfer {
+        fee_lamports,
+        error: result.err.map(|e| e.to_string()),
+        units_consumed: result.units_consumed,
+        logs: result.logs.unwrap_or_default(),
+    })
+}
+
+fn transfer_transaction(
+    client: &solana_client::rpc_client::RpcClient,
+    payer: &Keypair,
+    to: &Pubkey,
+    lamports: u64,
+    memo: Option<&str>,
+) -> Result<Transaction, ServiceError> {
+    let blockhash = client
+        .get_latest_blockhash()
+        .map_err(rpc_error("Transfer failed"))?;
+    let mut instructions = vec![system_instruction::transfer(&payer.pubkey(), to, lamports)];
+    if let Some(memo) = memo {
+        instructions.push(Instruction::new_with_bytes(
+            MEMO_PROGRAM_ID,
+            memo.as_bytes(),
+            vec![AccountMeta::new_readonly(payer.pubkey(), true)],
+        ));
+    }
+    Ok(Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &[payer],
+        blockhash,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::client_error::{ClientError, ClientErrorKind};
+
+    const LIMITS: AirdropLimits = AirdropLimits {
+        min_lamports: 1_000,
+        max_lamports: 2 * LAMPORTS_PER_SOL,
+    };
+
+    #[test]
+    fn amounts_come_in_sol_or_lamports() {
+        assert_eq!(
+            airdrop_lamports(Some(2), None).unwrap(),
+            2 * LAMPORTS_PER_SOL
+        );
+        assert_eq!(airdrop_lamports(None, Some(5)).unwrap(), 5);
+        assert!(matches!(
+            airdrop_lamports(Some(1), Some(1)),
+            Err(ServiceError::Invalid(_))
+        ));
+        assert!(matches!(
+            airdrop_lamports(None, None),
+            Err(ServiceError::Invalid(_))
+        ));
+        assert_eq!(
+            sol_to_lamports(u64::MAX).unwrap_err().to_string(),
+            format!("{} SOL is out of range", u64::MAX)
+        );
+    }
+
+    #[test]
+    fn airdrops_are_bounded() {
+        assert!(check_airdrop_amount(1_000, LIMITS).is_ok());
+        assert!(check_airdrop_amount(2 * LAMPORTS_PER_SOL, LIMITS).is_ok());
+        assert_eq!(
+            check_airdrop_amount(999, LIMITS).unwrap_err().to_string(),
+            "Airdrop amount too small (min 1000 lamports)"
+        );
+        assert_eq!(
+            check_airdrop_amount(2 * LAMPORTS_PER_SOL + 1, LIMITS)
+                .unwrap_err()
+                .to_string(),
+            "Airdrop amount too large (max 2 SOL)"
+        );
+    }
+
+    #[test]
+    fn faucet_refusals_are_told_apart() {
+        let refused = |message: &str| {
+            faucet_error(ClientError::from(ClientErrorKind::Custom(message.into())))
+        };
+        assert!(matches!(
+            refused("HTTP status client error (429 Too Many Requests)"),
+            ServiceError::FaucetRateLimited(_)
+        ));
+        assert!(matches!(
+            refused("airdrop request failed. This can happen when the rate limit is reached."),
+            ServiceError::FaucetRateLimited(_)
+        ));
+        assert!(matches!(
+            refused("the faucet has run dry"),
+            ServiceError::FaucetExhausted(_)
+        ));
+        assert!(matches!(
+            refused("connection refused"),
+            ServiceError::Rpc {
+                action: "Airdrop failed",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn airdrops_land_on_the_mock_chain() {
+        rpc::mock_for_tests();
+        let wallet = Pubkey::new_unique();
+        assert!(matches!(
+            get_balance("http://mock", "not-a-wallet"),
+            Err(ServiceError::InvalidWallet)
+        ));
+        assert_eq!(get_balance("http://mock", &wallet.to_string()).unwrap(), 0);
+
+        let signature = request_airdrop("http://mock", &wallet.to_string(), 5_000, LIMITS).unwrap();
+        assert_eq!(
+            get_balance("http://mock", &wallet.to_string()).unwrap(),
+            5_000
+        );
+        let progress = transaction_progress("http://mock", &signature).unwrap();
+        assert_eq!(progress.commitment, "finalized");
+        assert!(progress.slot.is_some() && progress.error.is_none());
+
+        // Confirmed already, so the credit is in the confirmed balance.
+        let projection = project_balance("http://mock", &wallet, &signature, 5_000).unwrap();
+        assert_eq!(
+            (projection.confirmed_balance, projection.projected_balance),
+            (5_000, 5_000)
+        );
+        let pending = project_balance("http://mock", &wallet, &Signature::new_unique(), 7).unwrap();
+        assert_eq!(
+            (pending.commitment.as_str(), pending.projected_balance),
+            ("pending", 5_007)
+        );
+    }
+
+    #[test]
+    fn transfers_move_lamports_from_the_payer() {
+        let chain = rpc::mock_for_tests();
+        let payer = Keypair::new();
+        let to = Pubkey::new_unique();
+        chain.set_balance(payer.pubkey(), LAMPORTS_PER_SOL);
+
+        let simulated = simulate_transfer("http://mock", &payer, &to, 1_000, Some("hi")).unwrap();
+        assert!(simulated.error.is_none());
+        assert!(simulated.fee_lamports > 0);
+        assert_eq!(get_balance("http://mock", &to.to_string()).unwrap(), 0);
+
+        transfer_with_memo("http://mock", &payer, &to, 1_000, "hi", false).unwrap();
+        assert_eq!(get_balance("http://mock", &to.to_string()).unwrap(), 1_000);
+        assert_eq!(
+            get_balance("http://mock", &payer.pubkey().to_string()).unwrap(),
+            LAMPORTS_PER_SOL - 1_000 - simulated.fee_lamports
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    auth::{self, Priority},
+    config::Config,
+    handlers::error_response,
+    rpc,
+    state::AppState,
+};
+
+pub use crate::api::LoadReport;
+
+// Fewer RPC calls than this in the window aren't enough to judge latency.
+const MIN_RPC_SAMPLES: usize = 5;
+// The balance long-poll parks callers without using the node; they are
+// capped by `balance_wait_max_waiters` and not counted as in flight.
+const PARKED_ROUTES: [&str; 1] = ["/wallet/{pubkey}/balance"];
+
+// Rejects low-priority requests while the RPC node is slow or too many
+// requests are in flight, so health checks and airdrops keep being served
+// during devnet incidents instead of everything timing out.
+#[derive(Default)]
+pub struct LoadShedder {
+    in_flight: AtomicUsize,
+    shed: AtomicU64,
+}
+
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl LoadShedder {
+    pub fn report(&self, config: &Config) -> LoadReport {
+        let latency = rpc::p95_latency(Duration::from_secs(config.shed_window_secs));
+        LoadReport {
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            rpc_p95_latency_ms: latency.map(|(p95, _)| p95.as_millis() as u64),
+            rpc_calls: latency.map(|(_, calls)| calls).unwrap_or(0),
+            shedding: self.overload(config).map(str::to_string),
+            shed_requests: self.shed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn overload(&self, config: &Config) -> Option<&'static str> {
+        if config
+            .shed_max_in_flight
+            .is_some_and(|max| self.in_flight.load(Ordering::Relaxed) > max)
+        {
+            return Some("in_flight");
+        }
+        let threshold = config.shed_rpc_p95_latency_ms?;
+        let (p95, calls) = rpc::p95_latency(Duration::from_secs(config.shed_window_secs))?;
+        (calls >= MIN_RPC_SAMPLES && p95.as_millis() as u64 > threshold).then_some("rpc_latency")
+    }
+}
+
+// Counts every routed request, whatever its priority, other than parked
+// long-polls.
+pub async fn track(
+    State(state): State<AppState>,
+    matched: Option<MatchedPath>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if matched.is_some_and(|path| PARKED_ROUTES.contains(&path.as_str())) {
+        return next.run(req).await;
+    }
+    state.shedder.in_flight.fetch_add(1, Ordering::Relaxed);
+    let _guard = InFlight(&state.shedder.in_flight);
+    next.run(req).await
+}
+
+// Layered on the low-priority routes, inside their auth guard. High tier
+// credentials are never shed.
+pub async fn shed(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let config = state.config();
+    if auth::request_priority(&req) == Priority::High || state.shedder.overload(&config).is_none() {
+        return next.run(req).await;
+    }
+    state.shedder.shed.fetch_add(1, Ordering::Relaxed);
+    overloaded(&config, "Server is overloaded, retry later")
+}
+
+pub fn overloaded(config: &Config, message: impl Into<String>) -> Response {
+    let mut res = error_response(StatusCode::SERVICE_UNAVAILABLE, message).into_response();
+    res.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(config.shed_retry_after_secs),
+    );
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn too_many_requests_in_flight_is_an_overload() {
+        let config = Config {
+            shed_max_in_flight: Some(1),
+            shed_rpc_p95_latency_ms: None,
+            ..Config::default()
+        };
+        let shedder = LoadShedder::default();
+        shedder.in_flight.fetch_add(1, Ordering::Relaxed);
+        let first = InFlight(&shedder.in_flight);
+        assert_eq!(shedder.overload(&config), None);
+
+        shedder.in_flight.fetch_add(1, Ordering::Relaxed);
+        let second = InFlight(&shedder.in_flight);
+        assert_eq!(shedder.overload(&config), Some("in_flight"));
+        assert_eq!(
+            shedder.report(&config).shedding.as_deref(),
+            Some("in_flight")
+        );
+        assert_eq!(shedder.report(&config).in_flight, 2);
+
+        drop(second);
+        drop(first);
+        assert_eq!(shedder.report(&config).in_flight, 0);
+        assert_eq!(shedder.overload(&config), None);
+    }
+
+    #[test]
+    fn overloaded_sets_retry_after() {
+        let config = Config {
+            shed_retry_after_secs: 7,
+            ..Config::default()
+        };
+        let res = overloaded(&config, "busy");
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.headers()[header::RETRY_AFTER], "7");
+    }
+
+    #[tokio::test]
+    async fn low_priority_requests_are_shed() {
+        use axum::{middleware, routing::get, Router};
+
+        let state = AppState::for_tests(Config {
+            shed_max_in_flight: Some(0),
+            ..Config::default()
+        });
+        let app = Router::new()
+            .route("/summary", get(|| async { "ok" }))
+            .route_layer(middleware::from_fn_with_state(state.clone(), shed))
+            .route("/health", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(state.clone(), track))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let res = reqwest::get(format!("http://{}/summary", addr))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 503);
+        let res = reqwest::get(format!("http://{}/health", addr))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let report = state.shedder.report(&state.config());
+        assert_eq!((report.shed_requests, report.in_flight), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn parked_long_polls_are_not_in_flight() {
+        use axum::{middleware, routing::get, Router};
+        use std::sync::Arc;
+        use tokio::sync::Notify;
+
+        let state = AppState::for_tests(Config {
+            shed_max_in_flight: Some(1),
+            ..Config::default()
+        });
+        let release = Arc::new(Notify::new());
+        let parked = release.clone();
+        let app = Router::new()
+            .route("/summary", get(|| async { "ok" }))
+            .route_layer(middleware::from_fn_with_state(state.clone(), shed))
+            .route(
+                "/wallet/{pubkey}/balance",
+                get(move || async move {
+                    parked.notified().await;
+                    "changed"
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(state.clone(), track))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let waiters: Vec<_> = (0..3)
+            .map(|_| tokio::spawn(reqwest::get(format!("http://{}/wallet/w/balance", addr))))
+            .collect();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let res = reqwest::get(format!("http://{}/summary", addr))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.text().await.unwrap(), "ok");
+        assert_eq!(state.shedder.report(&state.config()).shed_requests, 0);
+
+        release.notify_waiters();
+        for waiter in waiters {
+            let res = waiter.await.unwrap().unwrap();
+            assert_eq!(res.text().await.unwrap(), "changed");
+        }
+    }
+}
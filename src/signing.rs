@@ -0,0 +1,338 @@
+use base64::Engine;
+use solana_sdk::{
+    bs58,
+    message::VersionedMessage,
+    offchain_message::{MessageFormat, OffchainMessage},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::service::ServiceError;
+
+pub use crate::api::{MessageEncoding, ParsedOffchainMessage, SignedFormat};
+
+// Name of the configured server keypair among the dev signing keys. It is
+// the treasury, so it only signs offchain envelopes, which can't be
+// mistaken for a transaction.
+pub const SERVER_KEY: &str = "server";
+// The dev key used when a request names none.
+pub const DEFAULT_KEY: &str = "dev";
+const MAX_DEV_KEYS: usize = 100;
+const MAX_KEY_NAME_LENGTH: usize = 32;
+
+pub struct Verification {
+    pub valid: bool,
+    // "raw" or "offchain": what the signature was checked against. For a
+    // failed `auto` check, the most specific format tried.
+    pub format: &'static str,
+    pub offchain: Option<ParsedOffchainMessage>,
+}
+
+pub fn decode_message(message: &str, encoding: MessageEncoding) -> Result<Vec<u8>, ServiceError> {
+    match encoding {
+        MessageEncoding::Utf8 => Ok(message.as_bytes().to_vec()),
+        MessageEncoding::Base64 => base64::engine::general_purpose::STANDARD
+            .decode(message)
+            .map_err(|e| ServiceError::Invalid(format!("Invalid base64 message: {}", e))),
+        MessageEncoding::Base58 => bs58::decode(message)
+            .into_vec()
+            .map_err(|e| ServiceError::Invalid(format!("Invalid base58 message: {}", e))),
+    }
+}
+
+// Base58, as wallets and the CLI print them, or base64.
+pub fn parse_signature(signature: &str) -> Result<Signature, ServiceError> {
+    let bytes = bs58::decode(signature)
+        .into_vec()
+        .ok()
+        .filter(|b| b.len() == 64)
+        .or_else(|| {
+            base64::engine::general_purpose::STANDARD
+                .decode(signature)
+                .ok()
+        })
+        .ok_or_else(|| ServiceError::Invalid("Invalid signature encoding".to_string()))?;
+    Signature::try_from(bytes.as_slice())
+        .map_err(|_| ServiceError::Invalid("Signature must be 64 bytes".to_string()))
+}
+
+fn parsed(message: &OffchainMessage) -> ParsedOffchainMessage {
+    let body = message.get_message();
+    ParsedOffchainMessage {
+        version: message.get_version(),
+        format: match message.get_format() {
+            MessageFormat::RestrictedAscii => "restricted_ascii",
+            MessageFormat::LimitedUtf8 => "limited_utf8",
+            MessageFormat::ExtendedUtf8 => "extended_utf8",
+        }
+        .to_string(),
+        message: String::from_utf8(body.clone())
+            .unwrap_or_else(|_| base64::engine::general_purpose::STANDARD.encode(body)),
+        length: body.len(),
+    }
+}
+
+// Wraps `message` in a version 0 envelope, unless it already is one.
+pub fn offchain_message(message: &[u8]) -> Result<OffchainMessage, ServiceError> {
+    let result = if message.starts_with(OffchainMessage::SIGNING_DOMAIN) {
+        OffchainMessage::deserialize(message)
+    } else {
+        OffchainMessage::new(0, message)
+    };
+    result.map_err(|e| ServiceError::Invalid(format!("Invalid offchain message: {}", e)))
+}
+
+fn verify_offchain(
+    pubkey: &Pubkey,
+    signature: &Signature,
+    message: OffchainMessage,
+) -> Result<Verification, ServiceError> {
+    let valid = message
+        .verify(pubkey, signature)
+        .map_err(|e| ServiceError::Invalid(format!("Invalid offchain message: {}", e)))?;
+    Ok(Verification {
+        valid,
+        format: "offchain",
+        offchain: Some(parsed(&message)),
+    })
+}
+
+pub fn verify(
+    pubkey: &Pubkey,
+    signature: &Signature,
+    message: &[u8],
+    format: SignedFormat,
+) -> Result<Verification, ServiceError> {
+    let raw = || Verification {
+        valid: signature.verify(pubkey.as_ref(), message),
+        format: "raw",
+        offchain: None,
+    };
+    match format {
+        SignedFormat::Raw => Ok(raw()),
+        SignedFormat::Offchain => verify_offchain(pubkey, signature, offchain_message(message)?),
+        SignedFormat::Auto if message.starts_with(OffchainMessage::SIGNING_DOMAIN) => {
+            verify_offchain(pubkey, signature, offchain_message(message)?)
+        }
+        SignedFormat::Auto => {
+            let raw = raw();
+            if raw.valid {
+                return Ok(raw);
+            }
+            // Too long or not printable for an envelope: only raw applies.
+            match OffchainMessage::new(0, message) {
+                Ok(envelope) => verify_offchain(pubkey, signature, envelope),
+                Err(_) => Ok(raw),
+            }
+        }
+    }
+}
+
+// Named throwaway keypairs for /dev/sign_message, generated on first use
+// and kept in memory only, so tests get stable signers for one run.
+#[derive(Default)]
+pub struct DevKeys {
+    keys: Mutex<HashMap<String, Arc<Keypair>>>,
+}
+
+impl DevKeys {
+    pub fn get_or_create(&self, name: &str) -> Result<Arc<Keypair>, ServiceError> {
+        if name.is_empty()
+            || name.len() > MAX_KEY_NAME_LENGTH
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(ServiceError::Invalid(format!(
+                "Key names must be 1-{} letters, digits, '-' or '_'",
+                MAX_KEY_NAME_LENGTH
+            )));
+        }
+        let mut keys = self.keys.lock().unwrap();
+        if let Some(key) = keys.get(name) {
+            return Ok(key.clone());
+        }
+        if keys.len() >= MAX_DEV_KEYS {
+            return Err(ServiceError::Invalid(format!(
+                "At most {} dev keys can be created",
+                MAX_DEV_KEYS
+            )));
+        }
+        let key = Arc::new(Keypair::new());
+        keys.insert(name.to_string(), key.clone());
+        Ok(key)
+    }
+}
+
+// Signs `message` as given, or wrapped in an offchain message envelope.
+// `auto` is treated as `offchain`, the format the CLI signs. Raw bytes
+// that read as a transaction message are refused: signing them would
+// authorize the transaction.
+// Returns the signature and the exact bytes signed.
+pub fn sign(
+    signer: &Keypair,
+    message: &[u8],
+    format: SignedFormat,
+) -> Result<(Signature, Vec<u8>), ServiceError> {
+    let signed = match format {
+        SignedFormat::Raw if bincode::deserialize::<VersionedMessage>(message).is_ok() => {
+            return Err(ServiceError::Invalid(
+                "Raw messages that read as a transaction are not signed".to_string(),
+            ))
+        }
+        SignedFormat::Raw => message.to_vec(),
+        SignedFormat::Offchain | SignedFormat::Auto => offchain_message(message)?
+            .serialize()
+            .map_err(|e| ServiceError::Invalid(format!("Invalid offchain message: {}", e)))?,
+    };
+    Ok((signer.sign_message(&signed), signed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid<T>(result: Result<T, ServiceError>) -> String {
+        match result {
+            Err(ServiceError::Invalid(message)) => message,
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn messages_decode_from_each_encoding() {
+        assert_eq!(decode_message("hi", MessageEncoding::Utf8).unwrap(), b"hi");
+        assert_eq!(
+            decode_message("aGk=", MessageEncoding::Base64).unwrap(),
+            b"hi"
+        );
+        assert_eq!(
+            decode_message("8wr", MessageEncoding::Base58).unwrap(),
+            b"hi"
+        );
+        assert!(invalid(decode_message("0OIl", MessageEncoding::Base58))
+            .starts_with("Invalid base58 message"));
+        assert!(invalid(decode_message("***", MessageEncoding::Base64))
+            .starts_with("Invalid base64 message"));
+    }
+
+    #[test]
+    fn signatures_parse_from_base58_or_base64() {
+        let signature = Keypair::new().sign_message(b"hi");
+        assert_eq!(parse_signature(&signature.to_string()).unwrap(), signature);
+        let base64 = base64::engine::general_purpose::STANDARD.encode(signature.as_ref());
+        assert_eq!(parse_signature(&base64).unwrap(), signature);
+        assert_eq!(invalid(parse_signature("!!")), "Invalid signature encoding");
+        assert_eq!(
+            invalid(parse_signature("aGk=")),
+            "Signature must be 64 bytes"
+        );
+    }
+
+    #[test]
+    fn offchain_envelope_layout() {
+        let mut expected = b"\xffsolana offchain".to_vec();
+        expected.extend_from_slice(&[0, 0, 2, 0]); // version, restricted ascii, length
+        expected.extend_from_slice(b"hi");
+
+        let (_, signed) = sign(&Keypair::new(), b"hi", SignedFormat::Auto).unwrap();
+        assert_eq!(signed, expected);
+        // An envelope is signed as given, not wrapped again.
+        let (_, again) = sign(&Keypair::new(), &signed, SignedFormat::Offchain).unwrap();
+        assert_eq!(again, expected);
+
+        let parsed = parsed(&offchain_message("héllo".as_bytes()).unwrap());
+        assert_eq!(
+            (
+                parsed.format.as_str(),
+                parsed.message.as_str(),
+                parsed.length
+            ),
+            ("limited_utf8", "héllo", 6)
+        );
+    }
+
+    #[test]
+    fn transactions_are_never_signed_raw() {
+        use solana_sdk::{message::Message, system_instruction};
+
+        let key = Keypair::new();
+        let drain = system_instruction::transfer(&key.pubkey(), &Pubkey::new_unique(), 1);
+        let legacy = Message::new(&[drain], Some(&key.pubkey()));
+        let bytes = legacy.serialize();
+        assert_eq!(
+            invalid(sign(&key, &bytes, SignedFormat::Raw)),
+            "Raw messages that read as a transaction are not signed"
+        );
+        let versioned = VersionedMessage::Legacy(legacy).serialize();
+        assert!(sign(&key, &versioned, SignedFormat::Raw).is_err());
+        // Binary, so there is no envelope for them either.
+        assert!(sign(&key, &bytes, SignedFormat::Offchain).is_err());
+        assert!(sign(&key, b"hello", SignedFormat::Raw).is_ok());
+    }
+
+    #[test]
+    fn verify_auto_detects_the_format() {
+        let key = Keypair::new();
+        let pubkey = key.pubkey();
+
+        let (raw, _) = sign(&key, b"hello", SignedFormat::Raw).unwrap();
+        let check = verify(&pubkey, &raw, b"hello", SignedFormat::Auto).unwrap();
+        assert!(check.valid && check.format == "raw" && check.offchain.is_none());
+
+        let (offchain, signed) = sign(&key, b"hello", SignedFormat::Offchain).unwrap();
+        let check = verify(&pubkey, &offchain, b"hello", SignedFormat::Auto).unwrap();
+        assert!(check.valid && check.format == "offchain");
+        assert_eq!(check.offchain.unwrap().message, "hello");
+        assert!(
+            verify(&pubkey, &offchain, &signed, SignedFormat::Auto)
+                .unwrap()
+                .valid
+        );
+        assert!(
+            !verify(&pubkey, &offchain, b"hello", SignedFormat::Raw)
+                .unwrap()
+                .valid
+        );
+
+        let other = Keypair::new().pubkey();
+        let check = verify(&other, &offchain, b"hello", SignedFormat::Auto).unwrap();
+        assert!(!check.valid && check.format == "offchain");
+        // Not UTF-8, so no envelope is tried.
+        let check = verify(&other, &raw, &[0xc3, 0x28], SignedFormat::Auto).unwrap();
+        assert!(!check.valid && check.format == "raw");
+    }
+
+    #[test]
+    fn dev_keys_are_stable_per_name() {
+        let keys = DevKeys::default();
+        let alice = keys.get_or_create("alice").unwrap();
+        assert_eq!(
+            keys.get_or_create("alice").unwrap().pubkey(),
+            alice.pubkey()
+        );
+        assert_ne!(
+            keys.get_or_create("bob-2").unwrap().pubkey(),
+            alice.pubkey()
+        );
+        assert_eq!(
+            invalid(keys.get_or_create("no spaces")),
+            "Key names must be 1-32 letters, digits, '-' or '_'"
+        );
+        assert!(keys.get_or_create("").is_err());
+        assert!(keys.get_or_create(&"k".repeat(33)).is_err());
+
+        for i in 0..98 {
+            keys.get_or_create(&format!("key{}", i)).unwrap();
+        }
+        assert_eq!(
+            invalid(keys.get_or_create("one-too-many")),
+            "At most 100 dev keys can be created"
+        );
+        assert!(keys.get_or_create("alice").is_ok());
+    }
+}
@@ -0,0 +1,270 @@
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::{collections::HashMap, str::FromStr, sync::Mutex, time::Duration};
+
+use crate::{
+    auth::{Principal, Role},
+    clock::{now_ms, random_hex, rfc3339},
+    service::{self, ServiceError},
+};
+
+const CHALLENGE_TTL: Duration = Duration::from_secs(300);
+// Unanswered challenges are capped so anonymous callers can't grow memory.
+const MAX_PENDING_CHALLENGES: usize = 10_000;
+pub const SESSION_TOKEN_PREFIX: &str = "siws_";
+
+pub struct Challenge {
+    pub nonce: String,
+    pub message: String,
+    pub expires_at_ms: u64,
+}
+
+pub struct Session {
+    pub token: String,
+    pub wallet: Pubkey,
+    pub expires_at_ms: u64,
+}
+
+struct Pending {
+    wallet: Pubkey,
+    message: String,
+    expires_at_ms: u64,
+}
+
+// Nonce challenges and the sessions issued for them. Both live in memory,
+// so a restart signs everyone out.
+#[derive(Default)]
+pub struct SessionStore {
+    challenges: Mutex<HashMap<String, Pending>>,
+    sessions: Mutex<HashMap<String, (Pubkey, u64)>>,
+}
+
+impl SessionStore {
+    pub fn challenge(&self, domain: &str, wallet: Pubkey) -> Result<Challenge, ServiceError> {
+        let now = now_ms();
+        let expires_at_ms = now + CHALLENGE_TTL.as_millis() as u64;
+        let nonce = random_hex(16);
+        let message = format!(
+            "{domain} wants you to sign in with your Solana account:\n\
+             {wallet}\n\n\
+             Sign in to request airdrops for this wallet.\n\n\
+             URI: {domain}\n\
+             Nonce: {nonce}\n\
+             Issued At: {issued}\n\
+             Expiration Time: {expires}",
+            issued = rfc3339(now),
+            expires = rfc3339(expires_at_ms),
+        );
+
+        let mut challenges = self.challenges.lock().unwrap();
+        challenges.retain(|_, c| c.expires_at_ms > now);
+        if challenges.len() >= MAX_PENDING_CHALLENGES {
+            return Err(ServiceError::Invalid(
+                "Too many pending sign-in challenges, try again shortly".to_string(),
+            ));
+        }
+        challenges.insert(
+            nonce.clone(),
+            Pending {
+                wallet,
+                message: message.clone(),
+                expires_at_ms,
+            },
+        );
+
+        Ok(Challenge {
+            nonce,
+            message,
+            expires_at_ms,
+        })
+    }
+
+    // Consumes the nonce whether or not the signature checks out, so each
+    // challenge can be answered at most once.
+    pub fn verify(
+        &self,
+        wallet: Pubkey,
+        nonce: &str,
+        signature: &str,
+        session_ttl: Duration,
+    ) -> Result<Session, ServiceError> {
+        let pending = self
+            .challenges
+            .lock()
+            .unwrap()
+            .remove(nonce)
+            .filter(|p| p.expires_at_ms > now_ms())
+            .ok_or_else(|| ServiceError::Invalid("Unknown or expired sign-in nonce".to_string()))?;
+        if pending.wallet != wallet {
+            return Err(ServiceError::Invalid(
+                "Nonce was issued for a different wallet".to_string(),
+            ));
+        }
+
+        let signature = Signature::from_str(signature)
+            .map_err(|_| ServiceError::Invalid("Invalid signature encoding".to_string()))?;
+        if !signature.verify(wallet.as_ref(), pending.message.as_bytes()) {
+            return Err(ServiceError::Invalid(
+                "Signature does not match the wallet".to_string(),
+            ));
+        }
+
+        let now = now_ms();
+        let expires_at_ms = now + session_ttl.as_millis() as u64;
+        let token = format!("{}{}", SESSION_TOKEN_PREFIX, random_hex(32));
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, (_, expires)| *expires > now);
+        sessions.insert(token.clone(), (wallet, expires_at_ms));
+
+        Ok(Session {
+            token,
+            wallet,
+            expires_at_ms,
+        })
+    }
+
+    pub fn wallet(&self, token: &str) -> Option<Pubkey> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .get(token)
+            .filter(|(_, expires)| *expires > now_ms())
+            .map(|(wallet, _)| *wallet)
+    }
+}
+
+// Airdrops made with a SIWS session may only target the signed-in wallet.
+// With `required`, callers without a session (other than admins) are refused.
+pub fn check_wallet(
+    principal: Option<&Principal>,
+    wallet: &str,
+    required: bool,
+) -> Result<(), ServiceError> {
+    let pubkey = service::parse_wallet(wallet)?;
+    match principal {
+        Some(Principal {
+            wallet: Some(signed_in),
+            ..
+        }) if *signed_in != pubkey => Err(ServiceError::SessionWalletMismatch(*signed_in)),
+        Some(Principal {
+            wallet: Some(_), ..
+        }) => Ok(()),
+        Some(Principal {
+            role: Some(Role::Admin),
+            ..
+        }) => Ok(()),
+        _ if required => Err(ServiceError::SignInRequired),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Priority;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn principal(wallet: Option<Pubkey>, role: Option<Role>) -> Principal {
+        Principal {
+            subject: None,
+            role,
+            wallet,
+            org: None,
+            priority: Priority::Normal,
+        }
+    }
+
+    #[test]
+    fn signed_challenges_open_a_session_once() {
+        let store = SessionStore::default();
+        let keypair = Keypair::new();
+        let wallet = keypair.pubkey();
+        let challenge = store.challenge("faucet.example", wallet).unwrap();
+        assert!(challenge.message.starts_with(&format!(
+            "faucet.example wants you to sign in with your Solana account:\n{}\n",
+            wallet
+        )));
+        assert!(challenge
+            .message
+            .contains(&format!("\nNonce: {}\n", challenge.nonce)));
+
+        let signature = keypair
+            .sign_message(challenge.message.as_bytes())
+            .to_string();
+        let session = store
+            .verify(wallet, &challenge.nonce, &signature, TTL)
+            .unwrap();
+        assert!(session.token.starts_with(SESSION_TOKEN_PREFIX));
+        assert_eq!(store.wallet(&session.token), Some(wallet));
+        assert_eq!(store.wallet("siws_unknown"), None);
+
+        let replayed = store.verify(wallet, &challenge.nonce, &signature, TTL);
+        assert_eq!(
+            replayed.err().unwrap().to_string(),
+            "Unknown or expired sign-in nonce"
+        );
+    }
+
+    #[test]
+    fn challenges_are_bound_to_wallet_and_signer() {
+        let store = SessionStore::default();
+        let (keypair, other) = (Keypair::new(), Keypair::new());
+        let verify = |signer: &Keypair, wallet: Pubkey| {
+            let challenge = store.challenge("faucet.example", keypair.pubkey()).unwrap();
+            let signature = signer
+                .sign_message(challenge.message.as_bytes())
+                .to_string();
+            store
+                .verify(wallet, &challenge.nonce, &signature, TTL)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            verify(&other, other.pubkey()),
+            "Nonce was issued for a different wallet"
+        );
+        assert_eq!(
+            verify(&other, keypair.pubkey()),
+            "Signature does not match the wallet"
+        );
+        // Expired sessions are never handed out.
+        let challenge = store.challenge("faucet.example", keypair.pubkey()).unwrap();
+        let signature = keypair
+            .sign_message(challenge.message.as_bytes())
+            .to_string();
+        let session = store
+            .verify(
+                keypair.pubkey(),
+                &challenge.nonce,
+                &signature,
+                Duration::ZERO,
+            )
+            .unwrap();
+        assert_eq!(store.wallet(&session.token), None);
+    }
+
+    #[test]
+    fn sessions_may_only_fund_their_own_wallet() {
+        let (signed_in, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let session = principal(Some(signed_in), Some(Role::Funder));
+        assert!(check_wallet(Some(&session), &signed_in.to_string(), true).is_ok());
+        assert!(matches!(
+            check_wallet(Some(&session), &other.to_string(), false),
+            Err(ServiceError::SessionWalletMismatch(wallet)) if wallet == signed_in
+        ));
+
+        let admin = principal(None, Some(Role::Admin));
+        assert!(check_wallet(Some(&admin), &other.to_string(), true).is_ok());
+        let funder = principal(None, Some(Role::Funder));
+        assert!(matches!(
+            check_wallet(Some(&funder), &other.to_string(), true),
+            Err(ServiceError::SignInRequired)
+        ));
+        assert!(check_wallet(None, &other.to_string(), false).is_ok());
+        assert!(matches!(
+            check_wallet(None, &other.to_string(), true),
+            Err(ServiceError::SignInRequired)
+        ));
+    }
+}
@@ -0,0 +1,259 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{config::Config, state::AppState};
+
+pub use crate::api::RouteReport;
+
+// Bounds memory for very hot routes; the window is usually the tighter limit.
+const MAX_SAMPLES_PER_ROUTE: usize = 10_000;
+// Long-poll and streaming routes: they hold the connection for as long as
+// the caller asks, so they aren't tracked at all.
+const LONG_LIVED_ROUTES: [&str; 4] = [
+    "/wallet/{pubkey}/balance",
+    "/stream/events",
+    "/ws/events/{program_id}",
+    "/tx/{signature}/stream",
+];
+// Routes that wait for their transaction to confirm. How long that takes is
+// up to the cluster, so only their success rate counts.
+const CONFIRMING_ROUTES: [&str; 9] = [
+    "/get_airdrop",
+    "/admin/approvals/{id}/approve",
+    "/cnft/{asset_id}/transfer",
+    "/swap/build",
+    "/admin/treasury/sweep",
+    "/admin/treasury/rebalance",
+    "/dev/nft/mint",
+    "/dev/stake-pools/{pool}/deposit",
+    "/dev/stake-pools/{pool}/withdraw",
+];
+
+struct Sample {
+    at: Instant,
+    // None for confirming routes.
+    latency: Option<Duration>,
+    success: bool,
+}
+
+#[derive(Default)]
+pub struct SloTracker {
+    routes: Mutex<HashMap<String, VecDeque<Sample>>>,
+}
+
+impl SloTracker {
+    pub fn record(&self, route: &str, latency: Option<Duration>, success: bool) {
+        let mut routes = self.routes.lock().unwrap();
+        let samples = routes.entry(route.to_string()).or_default();
+        if samples.len() == MAX_SAMPLES_PER_ROUTE {
+            samples.pop_front();
+        }
+        samples.push_back(Sample {
+            at: Instant::now(),
+            latency,
+            success,
+        });
+    }
+
+    pub fn report(&self, config: &Config) -> Vec<RouteReport> {
+        let window = Duration::from_secs(config.slo_window_secs);
+        let mut routes = self.routes.lock().unwrap();
+
+        let mut reports: Vec<RouteReport> = routes
+            .iter_mut()
+            .filter_map(|(route, samples)| {
+                while samples.front().is_some_and(|s| s.at.elapsed() > window) {
+                    samples.pop_front();
+                }
+                if samples.is_empty() {
+                    return None;
+                }
+
+                let requests = samples.len();
+                let successes = samples.iter().filter(|s| s.success).count();
+                let success_rate = successes as f64 / requests as f64;
+
+                let mut latencies: Vec<Duration> =
+                    samples.iter().filter_map(|s| s.latency).collect();
+                latencies.sort_unstable();
+                let p95_latency_ms = match latencies.len() {
+                    0 => 0,
+                    timed => latencies[(timed * 95).div_ceil(100) - 1].as_millis() as u64,
+                };
+
+                // Too few requests to judge; don't let a single slow call flip health.
+                let slo_met = requests < config.slo_min_requests
+                    || (success_rate >= config.slo_min_success_rate
+                        && p95_latency_ms <= config.slo_p95_latency_ms);
+
+                Some(RouteReport {
+                    route: route.clone(),
+                    requests,
+                    success_rate,
+                    p95_latency_ms,
+                    slo_met,
+                })
+            })
+            .collect();
+
+        reports.sort_by(|a, b| a.route.cmp(&b.route));
+        reports
+    }
+}
+
+pub async fn track(
+    State(state): State<AppState>,
+    matched: Option<MatchedPath>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let route = match matched {
+        Some(path)
+            if !path.as_str().starts_with("/health")
+                && !LONG_LIVED_ROUTES.contains(&path.as_str()) =>
+        {
+            path.as_str().to_string()
+        }
+        _ => return next.run(req).await,
+    };
+
+    let started = Instant::now();
+    let res = next.run(req).await;
+    let latency = (!CONFIRMING_ROUTES.contains(&route.as_str())).then(|| started.elapsed());
+    state
+        .slo
+        .record(&route, latency, !res.status().is_server_error());
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            slo_min_requests: 20,
+            slo_min_success_rate: 0.9,
+            slo_p95_latency_ms: 100,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn p95_is_the_nearest_rank() {
+        let slo = SloTracker::default();
+        for ms in 1..=100 {
+            slo.record("/balance", Some(Duration::from_millis(ms)), true);
+        }
+        let report = &slo.report(&config())[0];
+        assert_eq!((report.requests, report.p95_latency_ms), (100, 95));
+        assert!(report.slo_met);
+
+        slo.record("/balance", Some(Duration::from_millis(500)), true);
+        // ceil(101 * 0.95) = 96th of 101.
+        assert_eq!(slo.report(&config())[0].p95_latency_ms, 96);
+    }
+
+    #[test]
+    fn routes_miss_their_slo_on_errors_or_latency() {
+        let slo = SloTracker::default();
+        for i in 0..20 {
+            slo.record("/airdrop", Some(Duration::from_millis(10)), i % 5 != 0);
+            slo.record("/slow", Some(Duration::from_millis(150)), true);
+        }
+        slo.record("/quiet", Some(Duration::from_secs(10)), false);
+
+        let reports = slo.report(&config());
+        let routes: Vec<_> = reports
+            .iter()
+            .map(|r| (r.route.as_str(), r.slo_met))
+            .collect();
+        assert_eq!(
+            routes,
+            [("/airdrop", false), ("/quiet", true), ("/slow", false)]
+        );
+        assert_eq!(reports[0].success_rate, 0.8);
+    }
+
+    #[test]
+    fn samples_age_out_of_the_window() {
+        let slo = SloTracker::default();
+        slo.record("/balance", Some(Duration::from_millis(1)), true);
+        let config = Config {
+            slo_window_secs: 0,
+            ..config()
+        };
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(slo.report(&config).is_empty());
+    }
+
+    // A caller parked on the balance long-poll for longer than the target,
+    // and slow confirmations, leave the routes healthy.
+    #[tokio::test]
+    async fn waiting_routes_are_not_judged_on_latency() {
+        use axum::{middleware, routing::get, Router};
+
+        let state = AppState::for_tests(Config {
+            slo_min_requests: 1,
+            slo_p95_latency_ms: 10,
+            ..Config::default()
+        });
+        let wait = || async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            "ok"
+        };
+        let app = Router::new()
+            .route("/wallet/{pubkey}/balance", get(wait))
+            .route("/get_airdrop", get(wait))
+            .route("/summary", get(wait))
+            .route_layer(middleware::from_fn_with_state(state.clone(), track))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        for path in ["/wallet/w/balance", "/get_airdrop", "/summary"] {
+            let res = reqwest::get(format!("http://{}{}", addr, path))
+                .await
+                .unwrap();
+            assert_eq!(res.text().await.unwrap(), "ok");
+        }
+
+        let reports: Vec<_> = state
+            .slo
+            .report(&state.config())
+            .into_iter()
+            .map(|r| (r.route, r.requests, r.slo_met))
+            .collect();
+        assert_eq!(
+            reports,
+            [
+                ("/get_airdrop".to_string(), 1, true),
+                ("/summary".to_string(), 1, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn untimed_routes_are_registered() {
+        let config = Config {
+            dev_mode: true,
+            ..Config::default()
+        };
+        let index = crate::catalog::index(&config, &crate::features::Features::default());
+        for route in LONG_LIVED_ROUTES.iter().chain(&CONFIRMING_ROUTES) {
+            assert!(
+                index.endpoints.iter().any(|e| e.path == *route),
+                "{} isn't a route",
+                route
+            );
+        }
+    }
+}
@@ -0,0 +1,86 @@
+// The store's SQLite connection, through rusqlite with SQLite bundled.
+use rusqlite::{params_from_iter, OpenFlags};
+use std::{path::Path, time::Duration};
+
+use crate::store::Connection;
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Sqlite {
+    db: rusqlite::Connection,
+}
+
+impl Sqlite {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let db = rusqlite::Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        )
+        .and_then(|db| db.busy_timeout(BUSY_TIMEOUT).map(|_| db))
+        .map_err(|e| format!("failed to open sqlite database '{}': {}", path.display(), e))?;
+        Ok(Sqlite { db })
+    }
+}
+
+impl Connection for Sqlite {
+    const ID_COLUMN: &'static str = "INTEGER PRIMARY KEY AUTOINCREMENT";
+
+    fn query(&mut self, sql: &str, params: &[&str]) -> Result<Vec<String>, String> {
+        let mut stmt = self.db.prepare(sql).map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params_from_iter(params), |row| {
+                row.get::<_, Option<String>>(0)
+            })
+            .map_err(|e| e.to_string())?;
+        // NULL columns come back empty.
+        rows.map(|row| row.map(Option::unwrap_or_default))
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameters_are_bound_not_spliced() {
+        let mut db = Sqlite::open(Path::new(":memory:")).unwrap();
+        db.query("CREATE TABLE t (v TEXT)", &[]).unwrap();
+        let tricky = "it's'); DROP TABLE t; -- ünïcode";
+        db.query("INSERT INTO t (v) VALUES ($1)", &[tricky])
+            .unwrap();
+        db.query("INSERT INTO t (v) VALUES ($1)", &[""]).unwrap();
+        assert_eq!(
+            db.query("SELECT v FROM t ORDER BY rowid", &[]).unwrap(),
+            [tricky, ""]
+        );
+        assert_eq!(
+            db.query("SELECT v FROM t WHERE v = $1", &["missing"])
+                .unwrap(),
+            Vec::<String>::new()
+        );
+        assert_eq!(db.query("SELECT NULL", &[]).unwrap(), [""]);
+    }
+
+    #[test]
+    fn errors_carry_sqlite_messages() {
+        let mut db = Sqlite::open(Path::new(":memory:")).unwrap();
+        let err = db.query("SELECT v FROM missing", &[]).unwrap_err();
+        assert!(err.starts_with("no such table: missing"), "{}", err);
+        db.query("CREATE TABLE t (v TEXT PRIMARY KEY)", &[])
+            .unwrap();
+        db.query("INSERT INTO t VALUES ($1)", &["a"]).unwrap();
+        let err = db.query("INSERT INTO t VALUES ($1)", &["a"]).unwrap_err();
+        assert!(err.starts_with("UNIQUE constraint failed"), "{}", err);
+
+        let err = Sqlite::open(Path::new("/definitely/not/here/store.db"))
+            .err()
+            .unwrap();
+        assert!(
+            err.starts_with("failed to open sqlite database '/definitely/not/here/store.db': "),
+            "{}",
+            err
+        );
+    }
+}
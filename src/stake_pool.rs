@@ -0,0 +1,564 @@
+use base64::Engine;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    stake, system_program, sysvar,
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+
+use crate::{
+    confirm,
+    layout::Reader,
+    rpc,
+    service::{rpc_error, ServiceError},
+};
+
+// AccountType tags.
+const ACCOUNT_TYPE_STAKE_POOL: u8 = 1;
+const ACCOUNT_TYPE_VALIDATOR_LIST: u8 = 2;
+
+// StakePoolInstruction variant indexes.
+const DEPOSIT_SOL: u8 = 14;
+const WITHDRAW_SOL: u8 = 16;
+
+#[derive(Clone, Copy)]
+pub struct Fee {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+pub struct StakePool {
+    // Whichever program owns the pool account, so pools run by other
+    // deployments of the stake pool program work too.
+    pub program_id: Pubkey,
+    pub manager: Pubkey,
+    pub staker: Pubkey,
+    pub validator_list: Pubkey,
+    pub reserve_stake: Pubkey,
+    pub pool_mint: Pubkey,
+    pub manager_fee_account: Pubkey,
+    pub token_program_id: Pubkey,
+    pub total_lamports: u64,
+    pub pool_token_supply: u64,
+    pub last_update_epoch: u64,
+    pub epoch_fee: Fee,
+    pub stake_deposit_fee: Fee,
+    pub stake_withdrawal_fee: Fee,
+    pub sol_deposit_authority: Option<Pubkey>,
+    pub sol_deposit_fee: Fee,
+    pub sol_withdraw_authority: Option<Pubkey>,
+    pub sol_withdrawal_fee: Fee,
+}
+
+pub struct ValidatorStake {
+    pub vote_account: Pubkey,
+    pub active_stake_lamports: u64,
+    pub transient_stake_lamports: u64,
+    pub last_update_epoch: u64,
+    pub status: &'static str,
+}
+
+pub struct ValidatorList {
+    pub max_validators: u32,
+    pub validators: Vec<ValidatorStake>,
+}
+
+// Stored as denominator, then numerator.
+fn fee(r: &mut Reader) -> Result<Fee, String> {
+    let denominator = r.u64()?;
+    let numerator = r.u64()?;
+    Ok(Fee {
+        numerator,
+        denominator,
+    })
+}
+
+// FutureEpoch<Fee>: None, One(fee) or Two(fee).
+fn future_fee(r: &mut Reader) -> Result<(), String> {
+    if r.u8()? != 0 {
+        fee(r)?;
+    }
+    Ok(())
+}
+
+fn parse_stake_pool(program_id: Pubkey, data: &[u8]) -> Result<StakePool, String> {
+    let mut r = Reader::new(data);
+    if r.u8()? != ACCOUNT_TYPE_STAKE_POOL {
+        return Err("not a stake pool account".to_string());
+    }
+    let manager = r.pubkey()?;
+    let staker = r.pubkey()?;
+    let _stake_deposit_authority = r.pubkey()?;
+    let _stake_withdraw_bump_seed = r.u8()?;
+    let validator_list = r.pubkey()?;
+    let reserve_stake = r.pubkey()?;
+    let pool_mint = r.pubkey()?;
+    let manager_fee_account = r.pubkey()?;
+    let token_program_id = r.pubkey()?;
+    let total_lamports = r.u64()?;
+    let pool_token_supply = r.u64()?;
+    let last_update_epoch = r.u64()?;
+    // Lockup: unix_timestamp, epoch, custodian.
+    r.take(8 + 8 + 32)?;
+    let epoch_fee = fee(&mut r)?;
+    future_fee(&mut r)?;
+    let _preferred_deposit_validator = r.option(Reader::pubkey)?;
+    let _preferred_withdraw_validator = r.option(Reader::pubkey)?;
+    let stake_deposit_fee = fee(&mut r)?;
+    let stake_withdrawal_fee = fee(&mut r)?;
+    future_fee(&mut r)?;
+    let _stake_referral_fee = r.u8()?;
+    let sol_deposit_authority = r.option(Reader::pubkey)?;
+    let sol_deposit_fee = fee(&mut r)?;
+    let _sol_referral_fee = r.u8()?;
+    let sol_withdraw_authority = r.option(Reader::pubkey)?;
+    let sol_withdrawal_fee = fee(&mut r)?;
+
+    Ok(StakePool {
+        program_id,
+        manager,
+        staker,
+        validator_list,
+        reserve_stake,
+        pool_mint,
+        manager_fee_account,
+        token_program_id,
+        total_lamports,
+        pool_token_supply,
+        last_update_epoch,
+        epoch_fee,
+        stake_deposit_fee,
+        stake_withdrawal_fee,
+        sol_deposit_authority,
+        sol_deposit_fee,
+        sol_withdraw_authority,
+        sol_withdrawal_fee,
+    })
+}
+
+fn validator_status(tag: u8) -> &'static str {
+    match tag {
+        0 => "active",
+        1 => "deactivating_transient",
+        2 => "ready_for_removal",
+        3 => "deactivating_validator",
+        4 => "deactivating_all",
+        _ => "unknown",
+    }
+}
+
+fn parse_validator_list(data: &[u8]) -> Result<ValidatorList, String> {
+    let mut r = Reader::new(data);
+    if r.u8()? != ACCOUNT_TYPE_VALIDATOR_LIST {
+        return Err("not a validator list account".to_string());
+    }
+    let max_validators = r.u32()?;
+    let len = r.u32()?;
+    let mut validators = Vec::new();
+    for _ in 0..len.min(max_validators) {
+        let active_stake_lamports = r.u64()?;
+        let transient_stake_lamports = r.u64()?;
+        let last_update_epoch = r.u64()?;
+        // transient_seed_suffix, unused, validator_seed_suffix.
+        r.take(8 + 4 + 4)?;
+        let status = validator_status(r.u8()?);
+        let vote_account = r.pubkey()?;
+        validators.push(ValidatorStake {
+            vote_account,
+            active_stake_lamports,
+            transient_stake_lamports,
+            last_update_epoch,
+            status,
+        });
+    }
+    Ok(ValidatorList {
+        max_validators,
+        validators,
+    })
+}
+
+fn load_pool(client: &RpcClient, pool: &Pubkey) -> Result<StakePool, ServiceError> {
+    let account = client
+        .get_account(pool)
+        .map_err(rpc_error("Failed to load stake pool"))?;
+    parse_stake_pool(account.owner, &account.data)
+        .map_err(|e| ServiceError::Invalid(format!("{} is not a stake pool: {}", pool, e)))
+}
+
+// The pool and the current epoch, to tell whether it still needs its
+// per-epoch update (deposits and withdrawals fail until it has one).
+pub fn stake_pool(rpc_url: &str, pool: &Pubkey) -> Result<(StakePool, u64), ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.stake_pool", %rpc_url, %pool).entered();
+    let stake_pool = load_pool(&client, pool)?;
+    let epoch = client
+        .get_epoch_info()
+        .map_err(rpc_error("Failed to get epoch"))?
+        .epoch;
+    Ok((stake_pool, epoch))
+}
+
+pub fn validator_list(rpc_url: &str, pool: &Pubkey) -> Result<ValidatorList, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.stake_pool_validators", %rpc_url, %pool).entered();
+    let stake_pool = load_pool(&client, pool)?;
+    let data = client
+        .get_account_data(&stake_pool.validator_list)
+        .map_err(rpc_error("Failed to load validator list"))?;
+    parse_validator_list(&data).map_err(|e| ServiceError::Rpc {
+        action: "Failed to read validator list",
+        message: e,
+    })
+}
+
+fn withdraw_authority(program_id: &Pubkey, pool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[pool.as_ref(), b"withdraw"], program_id).0
+}
+
+// Pools that restrict SOL deposits or withdrawals to an authority can't be
+// used by arbitrary wallets.
+fn check_authority(authority: Option<Pubkey>, action: &str) -> Result<(), ServiceError> {
+    match authority {
+        Some(authority) => Err(ServiceError::Invalid(format!(
+            "This pool only allows SOL {} signed by {}",
+            action, authority
+        ))),
+        None => Ok(()),
+    }
+}
+
+// DepositSol from `wallet`, creating its pool token account if needed.
+fn deposit_sol(
+    pool: &Pubkey,
+    stake_pool: &StakePool,
+    wallet: &Pubkey,
+    lamports: u64,
+) -> Vec<Instruction> {
+    let pool_tokens = get_associated_token_address_with_program_id(
+        wallet,
+        &stake_pool.pool_mint,
+        &stake_pool.token_program_id,
+    );
+    let mut data = vec![DEPOSIT_SOL];
+    data.extend_from_slice(&lamports.to_le_bytes());
+    vec![
+        create_associated_token_account_idempotent(
+            wallet,
+            wallet,
+            &stake_pool.pool_mint,
+            &stake_pool.token_program_id,
+        ),
+        Instruction {
+            program_id: stake_pool.program_id,
+            accounts: vec![
+                AccountMeta::new(*pool, false),
+                AccountMeta::new_readonly(withdraw_authority(&stake_pool.program_id, pool), false),
+                AccountMeta::new(stake_pool.reserve_stake, false),
+                AccountMeta::new(*wallet, true),
+                AccountMeta::new(pool_tokens, false),
+                AccountMeta::new(stake_pool.manager_fee_account, false),
+                // Referrer: the depositor's own account, so no fee leaves it.
+                AccountMeta::new(pool_tokens, false),
+                AccountMeta::new(stake_pool.pool_mint, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(stake_pool.token_program_id, false),
+            ],
+            data,
+        },
+    ]
+}
+
+// WithdrawSol of `pool_tokens` (raw units) from `wallet`'s token account.
+fn withdraw_sol(
+    pool: &Pubkey,
+    stake_pool: &StakePool,
+    wallet: &Pubkey,
+    pool_tokens: u64,
+) -> Vec<Instruction> {
+    let token_account = get_associated_token_address_with_program_id(
+        wallet,
+        &stake_pool.pool_mint,
+        &stake_pool.token_program_id,
+    );
+    let mut data = vec![WITHDRAW_SOL];
+    data.extend_from_slice(&pool_tokens.to_le_bytes());
+    vec![Instruction {
+        program_id: stake_pool.program_id,
+        accounts: vec![
+            AccountMeta::new(*pool, false),
+            AccountMeta::new_readonly(withdraw_authority(&stake_pool.program_id, pool), false),
+            AccountMeta::new_readonly(*wallet, true),
+            AccountMeta::new(token_account, false),
+            AccountMeta::new(stake_pool.reserve_stake, false),
+            AccountMeta::new(*wallet, false),
+            AccountMeta::new(stake_pool.manager_fee_account, false),
+            AccountMeta::new(stake_pool.pool_mint, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+            AccountMeta::new_readonly(stake::program::id(), false),
+            AccountMeta::new_readonly(stake_pool.token_program_id, false),
+        ],
+        data,
+    }]
+}
+
+pub enum PoolAction {
+    DepositSol { lamports: u64 },
+    WithdrawSol { pool_tokens: u64 },
+}
+
+pub enum PoolTransaction {
+    Unsigned(String),
+    Submitted(Signature),
+}
+
+// Builds the action for `wallet`, then either returns it unsigned (base64)
+// or, when `signer` is given, signs and sends it.
+pub fn run(
+    rpc_url: &str,
+    pool: &Pubkey,
+    wallet: &Pubkey,
+    action: PoolAction,
+    signer: Option<&Keypair>,
+) -> Result<PoolTransaction, ServiceError> {
+    let client = rpc::client(rpc_url);
+
+    let _span = tracing::info_span!("rpc.stake_pool_transaction", %rpc_url, %pool).entered();
+    let stake_pool = load_pool(&client, pool)?;
+    let instructions = match action {
+        PoolAction::DepositSol { lamports } => {
+            check_authority(stake_pool.sol_deposit_authority, "deposits")?;
+            deposit_sol(pool, &stake_pool, wallet, lamports)
+        }
+        PoolAction::WithdrawSol { pool_tokens } => {
+            check_authority(stake_pool.sol_withdraw_authority, "withdrawals")?;
+            withdraw_sol(pool, &stake_pool, wallet, pool_tokens)
+        }
+    };
+
+    let blockhash = client
+        .get_latest_blockhash()
+        .map_err(rpc_error("Failed to build stake pool transaction"))?;
+    match signer {
+        Some(signer) => {
+            let tx = Transaction::new_signed_with_payer(
+                &instructions,
+                Some(&signer.pubkey()),
+                &[signer],
+                blockhash,
+            );
+            let signature =
+                confirm::send_and_confirm(&client, &tx, "Stake pool transaction failed")?;
+            Ok(PoolTransaction::Submitted(signature))
+        }
+        None => {
+            let mut tx = Transaction::new_with_payer(&instructions, Some(wallet));
+            tx.message.recent_blockhash = blockhash;
+            let bytes =
+                bincode::serialize(&tx).map_err(|e| ServiceError::Invalid(e.to_string()))?;
+            Ok(PoolTransaction::Unsigned(
+                base64::engine::general_purpose::STANDARD.encode(bytes),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::pubkey;
+
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        fn u8(&mut self, v: u8) -> &mut Self {
+            self.0.push(v);
+            self
+        }
+        fn u32(&mut self, v: u32) -> &mut Self {
+            self.0.extend_from_slice(&v.to_le_bytes());
+            self
+        }
+        fn u64(&mut self, v: u64) -> &mut Self {
+            self.0.extend_from_slice(&v.to_le_bytes());
+            self
+        }
+        fn key(&mut self, key: &Pubkey) -> &mut Self {
+            self.0.extend_from_slice(key.as_ref());
+            self
+        }
+        fn fee(&mut self, numerator: u64, denominator: u64) -> &mut Self {
+            self.u64(denominator).u64(numerator)
+        }
+    }
+
+    fn pool_data(keys: &[Pubkey; 8], sol_deposit_authority: Option<&Pubkey>) -> Vec<u8> {
+        let mut w = Writer(Vec::new());
+        w.u8(ACCOUNT_TYPE_STAKE_POOL)
+            .key(&keys[0]) // manager
+            .key(&keys[1]) // staker
+            .key(&keys[2]) // stake_deposit_authority
+            .u8(255)
+            .key(&keys[3]) // validator_list
+            .key(&keys[4]) // reserve_stake
+            .key(&keys[5]) // pool_mint
+            .key(&keys[6]) // manager_fee_account
+            .key(&keys[7]) // token_program_id
+            .u64(1_000_000_000_000)
+            .u64(950_000_000_000)
+            .u64(612);
+        w.0.extend_from_slice(&[0; 48]); // lockup
+        w.fee(4, 100) // epoch_fee
+            .u8(2) // next_epoch_fee: Two
+            .fee(5, 100)
+            .u8(0) // preferred_deposit_validator
+            .u8(1) // preferred_withdraw_validator
+            .key(&keys[0])
+            .fee(0, 0) // stake_deposit_fee
+            .fee(1, 1000) // stake_withdrawal_fee
+            .u8(0) // next_stake_withdrawal_fee: None
+            .u8(0); // stake_referral_fee
+        match sol_deposit_authority {
+            Some(authority) => w.u8(1).key(authority),
+            None => w.u8(0),
+        };
+        w.fee(1, 10_000) // sol_deposit_fee
+            .u8(50) // sol_referral_fee
+            .u8(0) // sol_withdraw_authority
+            .fee(3, 1000); // sol_withdrawal_fee
+                           // The rest of the account is the next fee fields and padding.
+        w.0.extend_from_slice(&[0; 64]);
+        w.0
+    }
+
+    #[test]
+    fn parses_a_stake_pool() {
+        let keys = [(); 8].map(|_| Pubkey::new_unique());
+        let authority = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let pool = parse_stake_pool(program_id, &pool_data(&keys, Some(&authority))).unwrap();
+
+        assert_eq!(pool.program_id, program_id);
+        assert_eq!(pool.manager, keys[0]);
+        assert_eq!(pool.validator_list, keys[3]);
+        assert_eq!(pool.pool_mint, keys[5]);
+        assert_eq!(pool.token_program_id, keys[7]);
+        assert_eq!(pool.total_lamports, 1_000_000_000_000);
+        assert_eq!(pool.pool_token_supply, 950_000_000_000);
+        assert_eq!(pool.last_update_epoch, 612);
+        assert_eq!(
+            (pool.epoch_fee.numerator, pool.epoch_fee.denominator),
+            (4, 100)
+        );
+        assert_eq!(
+            (
+                pool.stake_withdrawal_fee.numerator,
+                pool.stake_withdrawal_fee.denominator
+            ),
+            (1, 1000)
+        );
+        assert_eq!(pool.sol_deposit_authority, Some(authority));
+        assert_eq!(pool.sol_deposit_fee.denominator, 10_000);
+        assert_eq!(pool.sol_withdraw_authority, None);
+        assert_eq!(
+            (
+                pool.sol_withdrawal_fee.numerator,
+                pool.sol_withdrawal_fee.denominator
+            ),
+            (3, 1000)
+        );
+    }
+
+    #[test]
+    fn rejects_other_accounts() {
+        let keys = [(); 8].map(|_| Pubkey::new_unique());
+        let mut data = pool_data(&keys, None);
+        assert_eq!(
+            parse_stake_pool(Pubkey::new_unique(), &data[..100])
+                .err()
+                .unwrap(),
+            "account data is truncated"
+        );
+        data[0] = ACCOUNT_TYPE_VALIDATOR_LIST;
+        assert_eq!(
+            parse_stake_pool(Pubkey::new_unique(), &data).err().unwrap(),
+            "not a stake pool account"
+        );
+    }
+
+    #[test]
+    fn parses_a_validator_list() {
+        let votes = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut w = Writer(Vec::new());
+        w.u8(ACCOUNT_TYPE_VALIDATOR_LIST).u32(2).u32(3);
+        for (i, vote) in votes.iter().enumerate() {
+            w.u64(100 + i as u64)
+                .u64(7)
+                .u64(612)
+                .u64(0)
+                .u32(0)
+                .u32(0)
+                .u8(i as u8 * 2)
+                .key(vote);
+        }
+
+        // len is clamped to max_validators.
+        let list = parse_validator_list(&w.0).unwrap();
+        assert_eq!(list.max_validators, 2);
+        assert_eq!(list.validators.len(), 2);
+        assert_eq!(list.validators[1].vote_account, votes[1]);
+        assert_eq!(list.validators[1].active_stake_lamports, 101);
+        assert_eq!(list.validators[0].status, "active");
+        assert_eq!(list.validators[1].status, "ready_for_removal");
+        assert_eq!(validator_status(9), "unknown");
+    }
+
+    #[test]
+    fn withdraw_authority_matches_jitosol() {
+        assert_eq!(
+            withdraw_authority(
+                &pubkey!("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy"),
+                &pubkey!("Jito4APyf642JPZPx3hGc6WWJ8zPKtRbRs4P815Awbb"),
+            ),
+            pubkey!("6iQKfEyhr3bZMotVkW6beNZz5CPAkiwvgV2CTje9pVSS")
+        );
+    }
+
+    #[test]
+    fn sol_instructions() {
+        let keys = [(); 8].map(|_| Pubkey::new_unique());
+        let program_id = Pubkey::new_unique();
+        let stake_pool = parse_stake_pool(program_id, &pool_data(&keys, None)).unwrap();
+        let (pool, wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let deposit = deposit_sol(&pool, &stake_pool, &wallet, 1_500_000_000);
+        assert_eq!(deposit.len(), 2);
+        assert_eq!(deposit[1].program_id, program_id);
+        assert_eq!(deposit[1].data, [14, 0, 0x2f, 0x68, 0x59, 0, 0, 0, 0]);
+        assert_eq!(deposit[1].accounts[3], AccountMeta::new(wallet, true));
+        assert_eq!(deposit[1].accounts[4].pubkey, deposit[1].accounts[6].pubkey);
+
+        let withdraw = withdraw_sol(&pool, &stake_pool, &wallet, 7);
+        assert_eq!(withdraw[0].data, [16, 7, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            withdraw[0].accounts[1].pubkey,
+            withdraw_authority(&program_id, &pool)
+        );
+
+        let authority = Pubkey::new_unique();
+        assert!(matches!(
+            check_authority(Some(authority), "deposits"),
+            Err(ServiceError::Invalid(message))
+                if message == format!("This pool only allows SOL deposits signed by {}", authority)
+        ));
+        assert!(check_authority(None, "deposits").is_ok());
+    }
+}
@@ -0,0 +1,371 @@
+use serde_json::Value;
+use solana_sdk::signature::{read_keypair_file, Keypair};
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    alerts::AlertStore,
+    allowlist::Allowlist,
+    approvals::ApprovalStore,
+    audit::{self, Actor, AuditLog, AuditRecord},
+    auth::{JwksCache, Principal},
+    chaos::Chaos,
+    compat::RpcCompat,
+    config::Config,
+    decoders::Registry,
+    denylist::Denylist,
+    events::EventBus,
+    export::Exporter,
+    features::Features,
+    freshness::SlotTracker,
+    hot::HotCache,
+    idempotency::IdempotencyStore,
+    idl::{self, IdlStore},
+    index::AccountIndex,
+    jobs::JobStore,
+    keys::KeyStore,
+    labels::LabelStore,
+    logging,
+    notify::Notifier,
+    orgs::OrgStore,
+    programs::ProgramRegistry,
+    queue::RequestQueue,
+    ratelimit::RateLimiter,
+    service::ServiceError,
+    shed::LoadShedder,
+    signing::DevKeys,
+    siws::{self, SessionStore},
+    slo::SloTracker,
+    store,
+    summary::SummaryCache,
+    swap::Jupiter,
+    tasks::Supervisor,
+    topups::TopupStore,
+    treasury::Treasury,
+    tx_history::TxHistory,
+    usage::UsageTracker,
+    validator::ValidatorManager,
+    watchlists::WatchlistStore,
+};
+
+pub use crate::api::ReloadReport;
+
+#[derive(Clone)]
+pub struct AppState {
+    config: Arc<RwLock<Arc<Config>>>,
+    pub slo: Arc<SloTracker>,
+    pub shedder: Arc<LoadShedder>,
+    pub queue: Arc<RequestQueue>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub audit: Arc<AuditLog>,
+    pub keypair: Option<Arc<Keypair>>,
+    pub jobs: Arc<JobStore>,
+    pub idempotency: Arc<IdempotencyStore>,
+    pub summaries: Arc<SummaryCache>,
+    pub denylist: Arc<Denylist>,
+    pub allowlist: Arc<Allowlist>,
+    pub jwks: Arc<JwksCache>,
+    pub sessions: Arc<SessionStore>,
+    pub notifier: Arc<Notifier>,
+    pub topups: Arc<TopupStore>,
+    pub watchlists: Arc<WatchlistStore>,
+    pub tx_history: Arc<TxHistory>,
+    pub alerts: Arc<AlertStore>,
+    pub orgs: Arc<OrgStore>,
+    pub keys: Arc<KeyStore>,
+    pub approvals: Arc<ApprovalStore>,
+    pub labels: Arc<LabelStore>,
+    pub programs: Arc<ProgramRegistry>,
+    pub treasury: Arc<Treasury>,
+    pub usage: Arc<UsageTracker>,
+    pub validator: Arc<ValidatorManager>,
+    pub chaos: Arc<Chaos>,
+    pub rpc_compat: Arc<RpcCompat>,
+    pub slots: Arc<SlotTracker>,
+    pub features: Arc<Features>,
+    pub jupiter: Arc<Jupiter>,
+    pub decoders: Arc<Registry>,
+    pub idls: Arc<IdlStore>,
+    pub dev_keys: Arc<DevKeys>,
+    pub tasks: Arc<Supervisor>,
+    pub events: Arc<EventBus>,
+    pub index: Arc<AccountIndex>,
+    pub exporter: Arc<Exporter>,
+    pub hot: Arc<HotCache>,
+}
+
+impl AppState {
+    pub fn new(config: Config) -> Result<Self, String> {
+        let keypair = match &config.keypair_path {
+            Some(path) => Some(Arc::new(read_keypair_file(path).map_err(|e| {
+                format!("failed to read keypair '{}': {}", path.display(), e)
+            })?)),
+            None => None,
+        };
+
+        let store = store::open(&config)?;
+        let allowlist = Allowlist::load(store.clone())?;
+        let topups = TopupStore::load(store.clone())?;
+        let watchlists = WatchlistStore::load(store.clone())?;
+        let tx_history = TxHistory::load(store.clone())?;
+        let alerts = AlertStore::load(store.clone())?;
+        let orgs = OrgStore::load(store.clone())?;
+        let keys = KeyStore::load(store.clone())?;
+        let approvals = ApprovalStore::load(store.clone())?;
+        let labels = LabelStore::load(store.clone())?;
+        let programs = ProgramRegistry::load(store.clone())?;
+        let jobs = JobStore::load(store.clone())?;
+        let usage = UsageTracker::load(store.clone())?;
+        let idempotency = IdempotencyStore::load(store.clone())?;
+        let treasury = Treasury::load(&config.treasury_aux_keypairs)?;
+        let decoders = Registry::builtin();
+        if let Some(dir) = &config.idl_dir {
+            for (program_id, idl) in idl::load_dir(dir)? {
+                decoders.register(program_id, Arc::new(idl));
+            }
+        }
+        // Uploaded IDLs win over the directory's.
+        let (idls, uploaded) = IdlStore::load(store.clone())?;
+        for (program_id, idl) in uploaded {
+            decoders.register(program_id, Arc::new(idl));
+        }
+
+        Ok(AppState {
+            audit: Arc::new(AuditLog::new(store)),
+            config: Arc::new(RwLock::new(Arc::new(config))),
+            slo: Arc::new(SloTracker::default()),
+            shedder: Arc::new(LoadShedder::default()),
+            queue: Arc::new(RequestQueue::default()),
+            rate_limiter: Arc::new(RateLimiter::default()),
+            keypair,
+            jobs: Arc::new(jobs),
+            idempotency: Arc::new(idempotency),
+            summaries: Arc::new(SummaryCache::default()),
+            denylist: Arc::new(Denylist::default()),
+            allowlist: Arc::new(allowlist),
+            jwks: Arc::new(JwksCache::default()),
+            sessions: Arc::new(SessionStore::default()),
+            notifier: Arc::new(Notifier::default()),
+            topups: Arc::new(topups),
+            watchlists: Arc::new(watchlists),
+            tx_history: Arc::new(tx_history),
+            alerts: Arc::new(alerts),
+            orgs: Arc::new(orgs),
+            keys: Arc::new(keys),
+            approvals: Arc::new(approvals),
+            labels: Arc::new(labels),
+            programs: Arc::new(programs),
+            treasury: Arc::new(treasury),
+            usage: Arc::new(usage),
+            validator: Arc::new(ValidatorManager::default()),
+            chaos: Arc::new(Chaos::default()),
+            rpc_compat: Arc::new(RpcCompat::default()),
+            slots: Arc::new(SlotTracker::default()),
+            features: Arc::new(Features::default()),
+            jupiter: Arc::new(Jupiter::default()),
+            decoders: Arc::new(decoders),
+            idls: Arc::new(idls),
+            dev_keys: Arc::new(DevKeys::default()),
+            tasks: Arc::new(Supervisor::default()),
+            events: Arc::new(EventBus::default()),
+            index: Arc::new(AccountIndex::default()),
+            exporter: Arc::new(Exporter::default()),
+            hot: Arc::new(HotCache::default()),
+        })
+    }
+
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap().clone()
+    }
+
+    // Points RPC at another node without touching the configuration source;
+    // used while a local validator is running.
+    pub fn set_rpc_url(&self, rpc_url: String) {
+        let mut config = self.config.write().unwrap();
+        let mut updated = (**config).clone();
+        updated.rpc_url = rpc_url;
+        *config = Arc::new(updated);
+    }
+
+    // Policy checks shared by every airdrop entry point (HTTP, chat bots),
+    // run before the RPC node is asked for funds.
+    pub fn check_airdrop(
+        &self,
+        principal: Option<&Principal>,
+        wallet: &str,
+    ) -> Result<(), ServiceError> {
+        let config = self.config();
+        if let Some(message) = &config.airdrop_maintenance {
+            return Err(ServiceError::Maintenance {
+                message: message.clone(),
+                retry_after_secs: config.airdrop_maintenance_retry_after_secs,
+            });
+        }
+        siws::check_wallet(principal, wallet, config.siws_required)?;
+        self.check_recipient(wallet)
+    }
+
+    // Screening of a wallet the server pays or builds a transfer to: the
+    // denylist, and the allowlist in allowlist-only mode. Airdrops, bulk
+    // airdrops, top-ups, cNFT transfers and swaps all go through here.
+    pub fn check_recipient(&self, wallet: &str) -> Result<(), ServiceError> {
+        self.denylist.check(wallet)?;
+        self.allowlist.check(wallet, self.config().allowlist_only)
+    }
+
+    // Screening of a wallet the server is asked to keep an eye on
+    // (watchlists, balance alerts): it doesn't watch denylisted ones.
+    pub fn check_watched(&self, wallet: &str) -> Result<(), ServiceError> {
+        self.denylist.check(wallet)
+    }
+
+    // Re-reads the configuration and swaps in every field that can change at
+    // runtime. Fields bound at startup keep their running value and are only
+    // reported back so the operator knows a restart is needed.
+    pub fn reload_config(&self, actor: Actor) -> Result<ReloadReport, String> {
+        let record = audit::record("config_reload", actor, serde_json::Value::Null);
+        let result = self.apply_reload();
+
+        match &result {
+            Ok(report) => self.audit.record(AuditRecord {
+                params: serde_json::json!(report),
+                ..record
+            }),
+            Err(e) => self.audit.record(record.failed(e)),
+        }
+        result
+    }
+
+    fn apply_reload(&self) -> Result<ReloadReport, String> {
+        let mut new = Config::load()?;
+        self.validator.pin_rpc_url(&mut new.rpc_url);
+        let mut current = self.config.write().unwrap();
+
+        let mut report = ReloadReport {
+            applied: Vec::new(),
+            restart_required: Vec::new(),
+        };
+
+        // Fields bound at startup: a changed value is reported and the running
+        // one kept. Everything else that differs is swapped in and listed as
+        // applied, so a new field can't change silently.
+        macro_rules! keep_running {
+            ($($field:ident),* $(,)?) => {$(
+                if new.$field != current.$field {
+                    report.restart_required.push(stringify!($field).to_string());
+                    new.$field = current.$field.clone();
+                }
+            )*};
+        }
+        keep_running!(
+            port,
+            store_backend,
+            store_url,
+            audit_log_path,
+            keypair_path,
+            max_program_size,
+            telegram_bot_token,
+            telegram_api_url,
+            sentry_dsn,
+            sentry_environment,
+            sentry_min_level,
+            rpc_pool_max_connections,
+            rpc_pool_idle_per_host,
+            rpc_pool_idle_timeout_secs,
+            rpc_http2,
+            backend,
+            record_dir,
+            replay_dir,
+            topups_path,
+            watchlists_path,
+            orgs_path,
+            api_keys_path,
+            labels_path,
+            programs_path,
+            idls_path,
+            balance_alerts_path,
+            tx_history_path,
+            labels_import_path,
+            allowlist_path,
+            idl_dir,
+            approvals_path,
+            jobs_path,
+            quotas_path,
+            idempotency_keys_path,
+            treasury_aux_keypairs,
+        );
+        report.applied = changed_fields(&current, &new);
+
+        logging::set_level(new.log_level);
+        *current = Arc::new(new);
+        Ok(report)
+    }
+}
+
+// Names of the top-level fields whose values differ, in name order.
+fn changed_fields(old: &Config, new: &Config) -> Vec<String> {
+    let (Ok(Value::Object(old)), Ok(Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return Vec::new();
+    };
+    new.into_iter()
+        .filter(|(name, value)| old.get(name) != Some(value))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+// State for tests: every store in memory and RPC on the shared mock chain.
+#[cfg(test)]
+impl AppState {
+    pub fn for_tests(config: Config) -> Self {
+        crate::rpc::mock_for_tests();
+        AppState::new(Config {
+            store_backend: crate::config::StoreBackend::Memory,
+            ..config
+        })
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn changed_fields_lists_every_differing_field() {
+        let old = Config::default();
+        assert!(changed_fields(&old, &old.clone()).is_empty());
+
+        let mut new = old.clone();
+        new.max_airdrop_sol += 1;
+        new.cors_origins.push("https://example.com".into());
+        new.rpc_fanout_concurrency += 1;
+        assert_eq!(
+            changed_fields(&old, &new),
+            ["cors_origins", "max_airdrop_sol", "rpc_fanout_concurrency"]
+        );
+    }
+
+    #[test]
+    fn recipients_are_screened_by_both_lists() {
+        let state = AppState::for_tests(Config {
+            allowlist_only: true,
+            ..Config::default()
+        });
+        let (listed, unlisted) = (Pubkey::new_unique(), Pubkey::new_unique());
+        state.allowlist.add(&[listed]).unwrap();
+
+        assert!(state.check_recipient(&listed.to_string()).is_ok());
+        assert!(matches!(
+            state.check_recipient(&unlisted.to_string()),
+            Err(ServiceError::NotAllowlisted(wallet)) if wallet == unlisted
+        ));
+        // Watching isn't receiving: allowlist-only mode doesn't apply.
+        assert!(state.check_watched(&unlisted.to_string()).is_ok());
+        assert!(matches!(
+            state.check_watched("nope"),
+            Err(ServiceError::InvalidWallet)
+        ));
+    }
+}
@@ -0,0 +1,507 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use crate::config::{Config, StoreBackend};
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+use tokio::runtime::RuntimeFlavor;
+
+// Lines the memory backend keeps per log; older ones are dropped.
+const MAX_MEMORY_LOG_LINES: usize = 10_000;
+
+// Where the persistent stores keep their state. Each store saves itself as
+// one document under its name ("watchlists", "api_keys", ...); the audit
+// log and transaction history append a line per record instead.
+pub trait Store: Send + Sync {
+    // Whether anything saved under `name` is kept.
+    fn persists(&self, name: &str) -> bool;
+    // The saved document, or None when nothing has been saved yet.
+    fn read(&self, name: &str) -> Result<Option<String>, String>;
+    fn write(&self, name: &str, contents: &str) -> Result<(), String>;
+    fn append(&self, name: &str, line: &str) -> Result<(), String>;
+    // Where `name` is kept, for error messages.
+    fn location(&self, name: &str) -> String;
+}
+
+// The configured file of each store; stores without one aren't persisted.
+pub fn file_paths(config: &Config) -> Vec<(&'static str, &PathBuf)> {
+    [
+        ("audit_log", &config.audit_log_path),
+        ("allowlist", &config.allowlist_path),
+        ("topups", &config.topups_path),
+        ("watchlists", &config.watchlists_path),
+        ("orgs", &config.orgs_path),
+        ("api_keys", &config.api_keys_path),
+        ("approvals", &config.approvals_path),
+        ("labels", &config.labels_path),
+        ("programs", &config.programs_path),
+        ("idls", &config.idls_path),
+        ("tx_history", &config.tx_history_path),
+        ("balance_alerts", &config.balance_alerts_path),
+        ("jobs", &config.jobs_path),
+        ("quotas", &config.quotas_path),
+        ("idempotency_keys", &config.idempotency_keys_path),
+    ]
+    .into_iter()
+    .filter_map(|(name, path)| path.as_ref().map(|path| (name, path)))
+    .collect()
+}
+
+pub fn open(config: &Config) -> Result<Arc<dyn Store>, String> {
+    Ok(match config.store_backend {
+        StoreBackend::File => Arc::new(FileStore::new(config)),
+        StoreBackend::Memory => Arc::new(MemoryStore::default()),
+        #[cfg(feature = "sqlite")]
+        StoreBackend::Sqlite => {
+            let path = config.store_url.as_deref().unwrap_or_default();
+            Arc::new(SqlStore::open(
+                crate::sqlite::Sqlite::open(std::path::Path::new(path))?,
+                format!("sqlite '{}'", path),
+            )?)
+        }
+        #[cfg(feature = "postgres")]
+        StoreBackend::Postgres => {
+            let url = config.store_url.as_deref().unwrap_or_default();
+            // Key=value settings may carry a password; URLs are redacted.
+            let location = match reqwest::Url::parse(url) {
+                Ok(_) => format!("postgres {}", crate::pool::redact(url)),
+                Err(_) => "postgres".to_string(),
+            };
+            Arc::new(SqlStore::open(
+                crate::postgres::Postgres::connect(url)?,
+                location,
+            )?)
+        }
+        #[cfg(not(all(feature = "sqlite", feature = "postgres")))]
+        backend => {
+            return Err(format!(
+                "store backend '{}' needs a build with the `{}` feature",
+                backend, backend
+            ))
+        }
+    })
+}
+
+// Each store in the file at its `*_path`, as before there was a choice.
+pub struct FileStore {
+    paths: HashMap<&'static str, PathBuf>,
+    lock: Mutex<()>,
+}
+
+impl FileStore {
+    pub fn new(config: &Config) -> Self {
+        FileStore {
+            paths: file_paths(config)
+                .into_iter()
+                .map(|(name, path)| (name, path.clone()))
+                .collect(),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+impl Store for FileStore {
+    fn persists(&self, name: &str) -> bool {
+        self.paths.contains_key(name)
+    }
+
+    fn read(&self, name: &str) -> Result<Option<String>, String> {
+        let Some(path) = self.paths.get(name) else {
+            return Ok(None);
+        };
+        let _guard = self.lock.lock().unwrap();
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Some(text)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("failed to read '{}': {}", path.display(), e)),
+        }
+    }
+
+    fn write(&self, name: &str, contents: &str) -> Result<(), String> {
+        let Some(path) = self.paths.get(name) else {
+            return Ok(());
+        };
+        let _guard = self.lock.lock().unwrap();
+        fs::write(path, contents)
+            .map_err(|e| format!("failed to write '{}': {}", path.display(), e))
+    }
+
+    fn append(&self, name: &str, line: &str) -> Result<(), String> {
+        let Some(path) = self.paths.get(name) else {
+            return Ok(());
+        };
+        let _guard = self.lock.lock().unwrap();
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(|e| format!("failed to write '{}': {}", path.display(), e))
+    }
+
+    fn location(&self, name: &str) -> String {
+        match self.paths.get(name) {
+            Some(path) => format!("'{}'", path.display()),
+            None => name.to_string(),
+        }
+    }
+}
+
+// Everything in process, gone on restart. Every store is kept, paths or
+// not, which suits tests and throwaway deployments.
+#[derive(Default)]
+pub struct MemoryStore {
+    documents: Mutex<HashMap<String, String>>,
+    logs: Mutex<HashMap<String, VecDeque<String>>>,
+}
+
+impl Store for MemoryStore {
+    fn persists(&self, _name: &str) -> bool {
+        true
+    }
+
+    fn read(&self, name: &str) -> Result<Option<String>, String> {
+        if let Some(lines) = self.logs.lock().unwrap().get(name) {
+            return Ok(Some(lines.iter().map(|l| format!("{}\n", l)).collect()));
+        }
+        Ok(self.documents.lock().unwrap().get(name).cloned())
+    }
+
+    fn write(&self, name: &str, contents: &str) -> Result<(), String> {
+        self.documents
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), contents.to_string());
+        Ok(())
+    }
+
+    fn append(&self, name: &str, line: &str) -> Result<(), String> {
+        let mut logs = self.logs.lock().unwrap();
+        let lines = logs.entry(name.to_string()).or_default();
+        if lines.len() >= MAX_MEMORY_LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line.to_string());
+        Ok(())
+    }
+
+    fn location(&self, name: &str) -> String {
+        format!("memory store '{}'", name)
+    }
+}
+
+// A memory store whose writes fail while `failing` is set, for testing
+// that stores roll back what they couldn't save.
+#[cfg(test)]
+#[derive(Default)]
+pub struct FlakyStore {
+    inner: MemoryStore,
+    failing: std::sync::atomic::AtomicBool,
+}
+
+#[cfg(test)]
+impl FlakyStore {
+    fn check(&self) -> Result<(), String> {
+        match self.failing.load(std::sync::atomic::Ordering::Relaxed) {
+            true => Err("disk full".to_string()),
+            false => Ok(()),
+        }
+    }
+
+    pub fn fail(&self, failing: bool) {
+        self.failing
+            .store(failing, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+impl Store for FlakyStore {
+    fn persists(&self, name: &str) -> bool {
+        self.inner.persists(name)
+    }
+
+    fn read(&self, name: &str) -> Result<Option<String>, String> {
+        self.inner.read(name)
+    }
+
+    fn write(&self, name: &str, contents: &str) -> Result<(), String> {
+        self.check()?;
+        self.inner.write(name, contents)
+    }
+
+    fn append(&self, name: &str, line: &str) -> Result<(), String> {
+        self.check()?;
+        self.inner.append(name, line)
+    }
+
+    fn location(&self, name: &str) -> String {
+        self.inner.location(name)
+    }
+}
+
+// A connection to one of the SQL backends. Runs `sql` with `params` bound
+// as text to $1, $2, ... and returns the first column of each row.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub trait Connection: Send {
+    // Column type of the log lines' self-numbering id.
+    const ID_COLUMN: &'static str;
+
+    fn query(&mut self, sql: &str, params: &[&str]) -> Result<Vec<String>, String>;
+}
+
+// Documents in one table keyed by store name, log lines in another; the
+// same SQL runs on SQLite and Postgres. Every store is kept, paths or not.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub struct SqlStore<C> {
+    conn: Mutex<C>,
+    location: String,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+impl<C: Connection> SqlStore<C> {
+    pub fn open(mut conn: C, location: String) -> Result<Self, String> {
+        let schema = [
+            "CREATE TABLE IF NOT EXISTS store_documents \
+             (name TEXT PRIMARY KEY, contents TEXT NOT NULL)"
+                .to_string(),
+            format!(
+                "CREATE TABLE IF NOT EXISTS store_log_lines \
+                 (id {}, name TEXT NOT NULL, line TEXT NOT NULL)",
+                C::ID_COLUMN
+            ),
+            "CREATE INDEX IF NOT EXISTS store_log_lines_name ON store_log_lines (name, id)"
+                .to_string(),
+        ];
+        for sql in &schema {
+            conn.query(sql, &[])
+                .map_err(|e| format!("failed to set up {}: {}", location, e))?;
+        }
+        Ok(SqlStore {
+            conn: Mutex::new(conn),
+            location,
+        })
+    }
+
+    // Stores are called from async handlers, so on the multi-threaded
+    // runtime the round trip runs in `block_in_place`: the worker's other
+    // tasks move to another thread until it's done, as they do while
+    // `rpc::spawn_blocking` waits on the RPC node.
+    fn query(&self, name: &str, sql: &str, params: &[&str]) -> Result<Vec<String>, String> {
+        let run = || {
+            self.conn
+                .lock()
+                .unwrap()
+                .query(sql, params)
+                .map_err(|e| format!("{}: {}", self.location(name), e))
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(run)
+            }
+            _ => run(),
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+impl<C: Connection> Store for SqlStore<C> {
+    fn persists(&self, _name: &str) -> bool {
+        true
+    }
+
+    fn read(&self, name: &str) -> Result<Option<String>, String> {
+        let lines = self.query(
+            name,
+            "SELECT line FROM store_log_lines WHERE name = $1 ORDER BY id",
+            &[name],
+        )?;
+        if !lines.is_empty() {
+            return Ok(Some(lines.iter().map(|l| format!("{}\n", l)).collect()));
+        }
+        let documents = self.query(
+            name,
+            "SELECT contents FROM store_documents WHERE name = $1",
+            &[name],
+        )?;
+        Ok(documents.into_iter().next())
+    }
+
+    fn write(&self, name: &str, contents: &str) -> Result<(), String> {
+        self.query(
+            name,
+            "INSERT INTO store_documents (name, contents) VALUES ($1, $2) \
+             ON CONFLICT (name) DO UPDATE SET contents = excluded.contents",
+            &[name, contents],
+        )
+        .map(|_| ())
+    }
+
+    fn append(&self, name: &str, line: &str) -> Result<(), String> {
+        self.query(
+            name,
+            "INSERT INTO store_log_lines (name, line) VALUES ($1, $2)",
+            &[name, line],
+        )
+        .map(|_| ())
+    }
+
+    fn location(&self, name: &str) -> String {
+        format!("{} ({})", self.location, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(store: &dyn Store) {
+        assert_eq!(store.read("orgs").unwrap(), None);
+        store.write("orgs", "[1]").unwrap();
+        store.write("orgs", "[1,2]").unwrap();
+        assert_eq!(store.read("orgs").unwrap().as_deref(), Some("[1,2]"));
+        store.append("audit_log", "{\"a\":1}").unwrap();
+        store.append("audit_log", "{\"a\":2}").unwrap();
+        assert_eq!(
+            store.read("audit_log").unwrap().as_deref(),
+            Some("{\"a\":1}\n{\"a\":2}\n")
+        );
+    }
+
+    #[test]
+    fn memory_store_round_trips() {
+        round_trip(&MemoryStore::default());
+    }
+
+    #[test]
+    fn memory_logs_drop_the_oldest_lines() {
+        let store = MemoryStore::default();
+        for i in 0..MAX_MEMORY_LOG_LINES + 2 {
+            store.append("audit_log", &i.to_string()).unwrap();
+        }
+        let log = store.read("audit_log").unwrap().unwrap();
+        assert_eq!(log.lines().count(), MAX_MEMORY_LOG_LINES);
+        assert_eq!(log.lines().next(), Some("2"));
+        assert_eq!(
+            log.lines().last(),
+            Some((MAX_MEMORY_LOG_LINES + 1).to_string().as_str())
+        );
+    }
+
+    #[test]
+    fn file_store_keeps_only_configured_stores() {
+        let dir = std::env::temp_dir().join(format!("file-store-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let store = FileStore::new(&Config {
+            orgs_path: Some(dir.join("orgs.json")),
+            audit_log_path: Some(dir.join("audit.log")),
+            ..Config::default()
+        });
+        round_trip(&store);
+        assert_eq!(fs::read_to_string(dir.join("orgs.json")).unwrap(), "[1,2]");
+        assert_eq!(
+            store.location("orgs"),
+            format!("'{}'", dir.join("orgs.json").display())
+        );
+
+        // Stores without a path are accepted and dropped.
+        assert!(!store.persists("labels"));
+        store.write("labels", "{}").unwrap();
+        store.append("labels", "{}").unwrap();
+        assert_eq!(store.read("labels").unwrap(), None);
+        assert_eq!(store.location("labels"), "labels");
+        fs::remove_dir_all(&dir).unwrap();
+
+        // A path that can't be written is reported with the path.
+        let err = store.write("orgs", "[]").unwrap_err();
+        assert!(err.starts_with("failed to write '"), "{}", err);
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    #[test]
+    fn backends_need_their_feature() {
+        let err = open(&Config {
+            store_backend: StoreBackend::Sqlite,
+            ..Config::default()
+        })
+        .err()
+        .unwrap();
+        assert_eq!(
+            err,
+            "store backend 'sqlite' needs a build with the `sqlite` feature"
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_store_round_trips_and_keeps_data() {
+        let path = std::env::temp_dir().join(format!("store-test-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let open = || {
+            SqlStore::open(
+                crate::sqlite::Sqlite::open(&path).unwrap(),
+                "sqlite test".to_string(),
+            )
+            .unwrap()
+        };
+        round_trip(&open());
+        assert_eq!(open().read("orgs").unwrap().as_deref(), Some("[1,2]"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgres"))]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn slow_queries_leave_the_worker_free() {
+        struct Slow;
+        impl Connection for Slow {
+            const ID_COLUMN: &'static str = "INTEGER PRIMARY KEY";
+
+            fn query(&mut self, _sql: &str, _params: &[&str]) -> Result<Vec<String>, String> {
+                std::thread::sleep(std::time::Duration::from_millis(300));
+                Ok(Vec::new())
+            }
+        }
+
+        // Opening runs the schema statements before the runtime matters.
+        let store = tokio::task::spawn_blocking(|| SqlStore::open(Slow, "slow".to_string()))
+            .await
+            .unwrap()
+            .unwrap();
+        let ticked = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let ticker = tokio::spawn({
+            let ticked = ticked.clone();
+            async move {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                ticked.store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+        });
+        // Handlers call the store straight from their task on a worker, as
+        // here. With the only worker stuck in the query the ticker couldn't
+        // run meanwhile.
+        let handler = tokio::spawn(async move {
+            tokio::task::yield_now().await;
+            store.write("orgs", "[]").unwrap();
+            ticked.load(std::sync::atomic::Ordering::SeqCst)
+        });
+        assert!(handler.await.unwrap());
+        ticker.await.unwrap();
+    }
+
+    // Needs a scratch database, e.g.
+    // TEST_POSTGRES_URL=postgres://postgres@127.0.0.1/postgres.
+    // Async, since that's where the stores are used from.
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    async fn postgres_store_round_trips() {
+        let Ok(url) = std::env::var("TEST_POSTGRES_URL") else {
+            return;
+        };
+        let mut conn = crate::postgres::Postgres::connect(&url).unwrap();
+        conn.query("DROP TABLE IF EXISTS store_documents, store_log_lines", &[])
+            .unwrap();
+        round_trip(&SqlStore::open(conn, "postgres test".to_string()).unwrap());
+    }
+}
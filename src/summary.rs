@@ -0,0 +1,316 @@
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::now_secs,
+    history, rpc,
+    service::{self, rpc_error, ServiceError},
+};
+
+const SIGNATURE_PAGE_SIZE: usize = 1_000;
+const TOP_COUNTERPARTIES: usize = 5;
+
+#[derive(Clone, Default)]
+pub struct Counterparty {
+    pub address: String,
+    pub transactions: u32,
+    pub sent_to_lamports: u64,
+    pub received_from_lamports: u64,
+}
+
+#[derive(Clone, Default)]
+pub struct TokenActivity {
+    pub mint: String,
+    pub decimals: u8,
+    pub received_raw: u128,
+    pub sent_raw: u128,
+    pub transactions: u32,
+}
+
+#[derive(Clone)]
+pub struct WalletSummary {
+    pub days: u32,
+    pub transaction_count: u32,
+    pub failed_transaction_count: u32,
+    pub received_lamports: u64,
+    pub sent_lamports: u64,
+    pub fees_lamports: u64,
+    pub top_counterparties: Vec<Counterparty>,
+    pub token_activity: Vec<TokenActivity>,
+    // The transaction cap was hit before the start of the window.
+    pub truncated: bool,
+    pub computed_at_ms: u64,
+}
+
+// Summaries cost one RPC call per transaction, so results are kept for a
+// while keyed by (wallet, days).
+#[derive(Default)]
+pub struct SummaryCache {
+    entries: Mutex<HashMap<(Pubkey, u32), (Instant, WalletSummary)>>,
+}
+
+impl SummaryCache {
+    pub fn get(&self, wallet: &Pubkey, days: u32, ttl: Duration) -> Option<WalletSummary> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (at, _)| at.elapsed() < ttl);
+        entries.get(&(*wallet, days)).map(|(_, s)| s.clone())
+    }
+
+    pub fn insert(&self, wallet: Pubkey, days: u32, summary: WalletSummary) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert((wallet, days), (Instant::now(), summary));
+    }
+}
+
+struct Accumulator {
+    summary: WalletSummary,
+    counterparties: HashMap<Pubkey, Counterparty>,
+    tokens: HashMap<String, TokenActivity>,
+}
+
+impl Accumulator {
+    fn add(&mut self, wallet: &Pubkey, tx: &EncodedConfirmedTransactionWithStatusMeta) {
+        let Some(meta) = tx.transaction.meta.as_ref() else {
+            return;
+        };
+        let keys = history::account_keys(tx);
+        let Some(index) = keys.iter().position(|k| k == wallet) else {
+            return;
+        };
+
+        self.summary.transaction_count += 1;
+        let fee_payer = index == 0;
+        if fee_payer {
+            self.summary.fees_lamports += meta.fee;
+        }
+        if meta.err.is_some() {
+            self.summary.failed_transaction_count += 1;
+            return;
+        }
+
+        let delta = |i: usize| -> i128 {
+            let pre = meta.pre_balances.get(i).copied().unwrap_or(0) as i128;
+            let post = meta.post_balances.get(i).copied().unwrap_or(0) as i128;
+            // The fee payer's change includes the fee; keep transfers separate.
+            post - pre + if i == 0 { meta.fee as i128 } else { 0 }
+        };
+
+        let own = delta(index);
+        if own > 0 {
+            self.summary.received_lamports += own as u64;
+        } else if own < 0 {
+            self.summary.sent_lamports += (-own) as u64;
+        }
+
+        if own != 0 {
+            for (i, key) in keys.iter().enumerate() {
+                let theirs = delta(i);
+                if i == index || theirs == 0 || theirs.signum() == own.signum() {
+                    continue;
+                }
+                let amount = theirs.abs().min(own.abs()) as u64;
+                let entry = self
+                    .counterparties
+                    .entry(*key)
+                    .or_insert_with(|| Counterparty {
+                        address: key.to_string(),
+                        ..Default::default()
+                    });
+                entry.transactions += 1;
+                if own > 0 {
+                    entry.received_from_lamports += amount;
+                } else {
+                    entry.sent_to_lamports += amount;
+                }
+            }
+        }
+
+        let owner = wallet.to_string();
+        let pre = history::owned_token_amounts(meta.pre_token_balances.as_ref().into(), &owner);
+        let post = history::owned_token_amounts(meta.post_token_balances.as_ref().into(), &owner);
+        let mut mints: Vec<&String> = pre.keys().chain(post.keys()).collect();
+        mints.sort();
+        mints.dedup();
+        for mint in mints {
+            let (decimals, before) = pre.get(mint).copied().unwrap_or_default();
+            let (post_decimals, after) = post.get(mint).copied().unwrap_or((decimals, 0));
+            if before == after {
+                continue;
+            }
+            let entry = self
+                .tokens
+                .entry(mint.clone())
+                .or_insert_with(|| TokenActivity {
+                    mint: mint.clone(),
+                    decimals: post_decimals,
+                    ..Default::default()
+                });
+            entry.transactions += 1;
+            if after > before {
+                entry.received_raw += after - before;
+            } else {
+                entry.sent_raw += before - after;
+            }
+        }
+    }
+
+    fn finish(mut self) -> WalletSummary {
+        let mut counterparties: Vec<Counterparty> = self.counterparties.into_values().collect();
+        counterparties.sort_by_key(|c| {
+            std::cmp::Reverse(c.sent_to_lamports as u128 + c.received_from_lamports as u128)
+        });
+        counterparties.truncate(TOP_COUNTERPARTIES);
+
+        let mut tokens: Vec<TokenActivity> = self.tokens.into_values().collect();
+        tokens.sort_by(|a, b| {
+            b.transactions
+                .cmp(&a.transactions)
+                .then(a.mint.cmp(&b.mint))
+        });
+
+        self.summary.top_counterparties = counterparties;
+        self.summary.token_activity = tokens;
+        self.summary
+    }
+}
+
+pub fn summarize(
+    rpc_url: &str,
+    wallet: &str,
+    days: u32,
+    max_transactions: usize,
+) -> Result<WalletSummary, ServiceError> {
+    let pubkey = service::parse_wallet(wallet)?;
+    let client = rpc::client(rpc_url);
+    let cutoff = now_secs().saturating_sub(days as u64 * 86_400) as i64;
+
+    let mut acc = Accumulator {
+        summary: WalletSummary {
+            days,
+            transaction_count: 0,
+            failed_transaction_count: 0,
+            received_lamports: 0,
+            sent_lamports: 0,
+            fees_lamports: 0,
+            top_counterparties: Vec::new(),
+            token_activity: Vec::new(),
+            truncated: false,
+            computed_at_ms: now_secs() * 1_000,
+        },
+        counterparties: HashMap::new(),
+        tokens: HashMap::new(),
+    };
+
+    let mut before = None;
+    let mut fetched = 0;
+    'pages: loop {
+        let page = client
+            .get_signatures_for_address_with_config(
+                &pubkey,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(SIGNATURE_PAGE_SIZE),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .map_err(rpc_error("Failed to get signatures"))?;
+
+        for sig in &page {
+            if sig.block_time.is_some_and(|t| t < cutoff) {
+                break 'pages;
+            }
+            if fetched == max_transactions {
+                acc.summary.truncated = true;
+                break 'pages;
+            }
+            let tx = history::fetch_transaction(&client, &sig.signature)?;
+            acc.add(&pubkey, &tx);
+            fetched += 1;
+        }
+
+        if page.len() < SIGNATURE_PAGE_SIZE {
+            break;
+        }
+        before = page
+            .last()
+            .and_then(|last| Signature::from_str(&last.signature).ok());
+    }
+
+    Ok(acc.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    #[test]
+    fn transfers_are_split_into_flows_fees_and_counterparties() {
+        let chain = rpc::mock_for_tests();
+        let (alice, bob) = (Keypair::new(), Keypair::new());
+        chain.set_balance(alice.pubkey(), 1_000_000);
+        service::transfer("http://mock", &alice, &bob.pubkey(), 100_000, false).unwrap();
+        service::transfer("http://mock", &bob, &alice.pubkey(), 30_000, false).unwrap();
+        let fee = service::simulate_transfer("http://mock", &bob, &alice.pubkey(), 1, None)
+            .unwrap()
+            .fee_lamports;
+
+        let summary = summarize("http://mock", &bob.pubkey().to_string(), 1, 100).unwrap();
+        assert_eq!(summary.transaction_count, 2);
+        assert_eq!(summary.failed_transaction_count, 0);
+        assert_eq!(summary.received_lamports, 100_000);
+        // The fee is counted apart from what was sent.
+        assert_eq!(summary.sent_lamports, 30_000);
+        assert_eq!(summary.fees_lamports, fee);
+        assert!(!summary.truncated);
+        assert_eq!(summary.top_counterparties.len(), 1);
+        let alice_side = &summary.top_counterparties[0];
+        assert_eq!(alice_side.address, alice.pubkey().to_string());
+        assert_eq!(
+            (
+                alice_side.transactions,
+                alice_side.received_from_lamports,
+                alice_side.sent_to_lamports
+            ),
+            (2, 100_000, 30_000)
+        );
+
+        let capped = summarize("http://mock", &bob.pubkey().to_string(), 1, 1).unwrap();
+        assert_eq!(capped.transaction_count, 1);
+        assert!(capped.truncated);
+    }
+
+    #[test]
+    fn cached_summaries_expire() {
+        let cache = SummaryCache::default();
+        let wallet = Pubkey::new_unique();
+        let summary = WalletSummary {
+            days: 7,
+            transaction_count: 3,
+            failed_transaction_count: 0,
+            received_lamports: 0,
+            sent_lamports: 0,
+            fees_lamports: 0,
+            top_counterparties: Vec::new(),
+            token_activity: Vec::new(),
+            truncated: false,
+            computed_at_ms: 0,
+        };
+        cache.insert(wallet, 7, summary);
+        let ttl = Duration::from_secs(60);
+        assert_eq!(cache.get(&wallet, 7, ttl).unwrap().transaction_count, 3);
+        assert!(cache.get(&wallet, 30, ttl).is_none());
+        assert!(cache.get(&wallet, 7, Duration::ZERO).is_none());
+        assert!(cache.get(&wallet, 7, ttl).is_none());
+    }
+}
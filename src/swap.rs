@@ -0,0 +1,345 @@
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::VersionedTransaction,
+};
+use std::time::Duration;
+
+use crate::{config::Config, confirm, rpc, service::ServiceError};
+
+pub use crate::api::QuoteQuery;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const DEFAULT_SLIPPAGE_BPS: u16 = 50;
+const MAX_SLIPPAGE_BPS: u16 = 10_000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SwapResponse {
+    swap_transaction: String,
+    last_valid_block_height: u64,
+}
+
+pub struct BuiltSwap {
+    // Base64 versioned transaction, unsigned.
+    pub transaction: String,
+    pub last_valid_block_height: u64,
+}
+
+// Thin client for Jupiter's quote and swap APIs. Quotes are passed through
+// as Jupiter returns them, since /swap needs the quote back verbatim.
+pub struct Jupiter {
+    client: reqwest::Client,
+}
+
+impl Default for Jupiter {
+    fn default() -> Self {
+        Jupiter {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// Jupiter answers 4xx with {"error": ...} for requests it can't route, which
+// are the caller's to fix; anything else is reported as an upstream failure.
+async fn read(res: Result<reqwest::Response, reqwest::Error>) -> Result<Value, ServiceError> {
+    let upstream = |e: reqwest::Error| ServiceError::Rpc {
+        action: "Jupiter request failed",
+        message: e.to_string(),
+    };
+    let res = res.map_err(upstream)?;
+    let status = res.status();
+    let body: Value = res.json().await.map_err(upstream)?;
+    if status.is_client_error() {
+        let message = body
+            .get("error")
+            .and_then(Value::as_str)
+            .unwrap_or("request rejected");
+        return Err(ServiceError::Invalid(format!("Jupiter: {}", message)));
+    }
+    if !status.is_success() {
+        return Err(ServiceError::Rpc {
+            action: "Jupiter request failed",
+            message: format!("HTTP {}: {}", status, body),
+        });
+    }
+    Ok(body)
+}
+
+fn parse_mint(name: &str, mint: &str) -> Result<(), ServiceError> {
+    mint.parse::<Pubkey>()
+        .map(|_| ())
+        .map_err(|_| ServiceError::Invalid(format!("Invalid {} '{}'", name, mint)))
+}
+
+impl Jupiter {
+    pub async fn quote(&self, config: &Config, query: &QuoteQuery) -> Result<Value, ServiceError> {
+        parse_mint("input_mint", &query.input_mint)?;
+        parse_mint("output_mint", &query.output_mint)?;
+        if query.amount == 0 {
+            return Err(ServiceError::Invalid(
+                "'amount' must be greater than 0".to_string(),
+            ));
+        }
+        let slippage_bps = query.slippage_bps.unwrap_or(DEFAULT_SLIPPAGE_BPS);
+        if slippage_bps > MAX_SLIPPAGE_BPS {
+            return Err(ServiceError::Invalid(format!(
+                "'slippage_bps' must be at most {}",
+                MAX_SLIPPAGE_BPS
+            )));
+        }
+
+        let res = self
+            .client
+            .get(format!(
+                "{}/quote",
+                config.jupiter_api_url.trim_end_matches('/')
+            ))
+            .query(&[
+                ("inputMint", query.input_mint.clone()),
+                ("outputMint", query.output_mint.clone()),
+                ("amount", query.amount.to_string()),
+                ("slippageBps", slippage_bps.to_string()),
+            ])
+            .send()
+            .await;
+        read(res).await
+    }
+
+    // Builds the swap for `user` to sign, who also pays its fees.
+    pub async fn build(
+        &self,
+        config: &Config,
+        quote: Value,
+        user: &Pubkey,
+    ) -> Result<BuiltSwap, ServiceError> {
+        let res = self
+            .client
+            .post(format!(
+                "{}/swap",
+                config.jupiter_api_url.trim_end_matches('/')
+            ))
+            .json(&json!({
+                "quoteResponse": quote,
+                "userPublicKey": user.to_string(),
+                "wrapAndUnwrapSol": true,
+                "dynamicComputeUnitLimit": true,
+            }))
+            .send()
+            .await;
+        let swap: SwapResponse =
+            serde_json::from_value(read(res).await?).map_err(|e| ServiceError::Rpc {
+                action: "Unexpected Jupiter response",
+                message: e.to_string(),
+            })?;
+        Ok(BuiltSwap {
+            transaction: swap.swap_transaction,
+            last_valid_block_height: swap.last_valid_block_height,
+        })
+    }
+}
+
+// Signs a built swap with `signer`, which must be its only required signer,
+// and sends it.
+pub fn submit(
+    rpc_url: &str,
+    signer: &Keypair,
+    transaction: &str,
+) -> Result<Signature, ServiceError> {
+    let unexpected = |message: String| ServiceError::Rpc {
+        action: "Unexpected Jupiter response",
+        message,
+    };
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(transaction)
+        .map_err(|e| unexpected(e.to_string()))?;
+    let unsigned: VersionedTransaction =
+        bincode::deserialize(&bytes).map_err(|e| unexpected(e.to_string()))?;
+    let tx = VersionedTransaction::try_new(unsigned.message, &[signer])
+        .map_err(|e| unexpected(format!("failed to sign swap: {}", e)))?;
+
+    let client = rpc::client(rpc_url);
+    let _span = tracing::info_span!("rpc.swap", %rpc_url, signer = %signer.pubkey()).entered();
+    confirm::send_and_confirm(&client, &tx, "Swap failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        extract::Query,
+        http::StatusCode,
+        routing::{get, post},
+        Json, Router,
+    };
+    use solana_sdk::{
+        hash::Hash,
+        message::{Message, VersionedMessage},
+        system_instruction,
+    };
+    use std::collections::HashMap;
+
+    const SOL: &str = "So11111111111111111111111111111111111111112";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn query(amount: u64, slippage_bps: Option<u16>) -> QuoteQuery {
+        QuoteQuery {
+            input_mint: SOL.to_string(),
+            output_mint: USDC.to_string(),
+            amount,
+            slippage_bps,
+        }
+    }
+
+    async fn jupiter(swap: Value) -> Config {
+        let app = Router::new()
+            .route(
+                "/quote",
+                get(|Query(params): Query<HashMap<String, String>>| async move {
+                    if params["inputMint"] == params["outputMint"] {
+                        return (
+                            StatusCode::BAD_REQUEST,
+                            Json(json!({ "error": "Could not find any route" })),
+                        );
+                    }
+                    (StatusCode::OK, Json(json!(params)))
+                }),
+            )
+            .route("/swap", post(move || async move { Json(swap) }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Config {
+            jupiter_api_url: format!("http://{}/", addr),
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn quote_passes_the_query_through() {
+        let config = jupiter(json!({})).await;
+        let jupiter = Jupiter::default();
+
+        let quote = jupiter.quote(&config, &query(1_000, None)).await.unwrap();
+        assert_eq!(
+            quote,
+            json!({
+                "inputMint": SOL,
+                "outputMint": USDC,
+                "amount": "1000",
+                "slippageBps": "50",
+            })
+        );
+
+        let same = QuoteQuery {
+            output_mint: SOL.to_string(),
+            ..query(1_000, Some(100))
+        };
+        assert!(matches!(
+            jupiter.quote(&config, &same).await,
+            Err(ServiceError::Invalid(message)) if message == "Jupiter: Could not find any route"
+        ));
+    }
+
+    #[tokio::test]
+    async fn quote_rejects_bad_input_before_calling_out() {
+        let config = Config {
+            jupiter_api_url: "http://127.0.0.1:1".to_string(),
+            ..Config::default()
+        };
+        let jupiter = Jupiter::default();
+        let invalid = |result: Result<Value, ServiceError>| match result {
+            Err(ServiceError::Invalid(message)) => message,
+            _ => panic!("expected a validation error"),
+        };
+        assert_eq!(
+            invalid(jupiter.quote(&config, &query(0, None)).await),
+            "'amount' must be greater than 0"
+        );
+        assert_eq!(
+            invalid(jupiter.quote(&config, &query(1, Some(10_001))).await),
+            "'slippage_bps' must be at most 10000"
+        );
+        let bad_mint = QuoteQuery {
+            input_mint: "sol".to_string(),
+            ..query(1, None)
+        };
+        assert_eq!(
+            invalid(jupiter.quote(&config, &bad_mint).await),
+            "Invalid input_mint 'sol'"
+        );
+        assert!(matches!(
+            jupiter.quote(&config, &query(1, None)).await,
+            Err(ServiceError::Rpc {
+                action: "Jupiter request failed",
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn build_reads_the_swap_transaction() {
+        let config = jupiter(json!({
+            "swapTransaction": "AQID",
+            "lastValidBlockHeight": 42,
+            "prioritizationFeeLamports": 0,
+        }))
+        .await;
+        let built = Jupiter::default()
+            .build(&config, json!({}), &Pubkey::new_unique())
+            .await
+            .unwrap();
+        assert_eq!(built.transaction, "AQID");
+        assert_eq!(built.last_valid_block_height, 42);
+
+        let config = jupiter(json!({ "swapTransaction": "AQID" })).await;
+        assert!(matches!(
+            Jupiter::default()
+                .build(&config, json!({}), &Pubkey::new_unique())
+                .await,
+            Err(ServiceError::Rpc {
+                action: "Unexpected Jupiter response",
+                ..
+            })
+        ));
+    }
+
+    fn unsigned(payer: &Pubkey) -> String {
+        let transfer = system_instruction::transfer(payer, &Pubkey::new_unique(), 1_000);
+        let message = Message::new_with_blockhash(&[transfer], Some(payer), &Hash::default());
+        let tx = VersionedTransaction {
+            signatures: vec![Default::default()],
+            message: VersionedMessage::Legacy(message),
+        };
+        base64::engine::general_purpose::STANDARD.encode(bincode::serialize(&tx).unwrap())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn submit_signs_with_the_user() {
+        let chain = rpc::mock_for_tests();
+        let user = Keypair::new();
+        chain.set_balance(user.pubkey(), 1_000_000);
+        let transaction = unsigned(&user.pubkey());
+
+        let signature =
+            tokio::task::spawn_blocking(move || submit("http://mock", &user, &transaction))
+                .await
+                .unwrap()
+                .unwrap();
+        assert_ne!(signature, Signature::default());
+
+        let other = Keypair::new();
+        let transaction = unsigned(&Pubkey::new_unique());
+        assert!(matches!(
+            submit("http://mock", &other, &transaction),
+            Err(ServiceError::Rpc { message, .. }) if message.starts_with("failed to sign swap")
+        ));
+        assert!(submit("http://mock", &other, "not base64!").is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{clock::now_ms, logging, state::AppState};
+#[cfg(feature = "sentry")]
+use crate::{config::SentryLevel, sentry};
+
+pub use crate::api::{TaskReport, TaskState};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+// A task that ran this long before panicking starts over from the minimum
+// backoff, so one panic a day doesn't keep it waiting minutes.
+const STABLE_AFTER: Duration = Duration::from_secs(600);
+
+tokio::task_local! {
+    static CURRENT: (Arc<Supervisor>, &'static str);
+}
+
+// Runs the long-lived background tasks (watchers, schedulers), restarting
+// any that panic with exponential backoff.
+#[derive(Default)]
+pub struct Supervisor {
+    tasks: Mutex<BTreeMap<&'static str, TaskReport>>,
+}
+
+pub fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+impl Supervisor {
+    pub fn spawn<F, Fut>(self: &Arc<Self>, name: &'static str, state: &AppState, task: F)
+    where
+        F: Fn(AppState) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.lock().unwrap().insert(
+            name,
+            TaskReport {
+                name: name.to_string(),
+                state: TaskState::Running,
+                restarts: 0,
+                started_at_ms: now_ms(),
+                last_success_ms: None,
+                last_panic: None,
+                last_panic_at_ms: None,
+            },
+        );
+        let supervisor = self.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            let mut backoff = MIN_BACKOFF;
+            loop {
+                let started = Instant::now();
+                let run = CURRENT.scope((supervisor.clone(), name), task(state.clone()));
+                let result = tokio::spawn(run).await;
+                let payload = match result {
+                    Ok(()) => {
+                        supervisor.update(name, |t| t.state = TaskState::Finished);
+                        return;
+                    }
+                    // The runtime is shutting down.
+                    Err(e) if e.is_cancelled() => return,
+                    Err(e) => e.into_panic(),
+                };
+                if started.elapsed() >= STABLE_AFTER {
+                    backoff = MIN_BACKOFF;
+                }
+                let message = panic_message(payload);
+                logging::error(format_args!(
+                    "Background task {} panicked, restarting in {}s: {}",
+                    name,
+                    backoff.as_secs(),
+                    message
+                ));
+                #[cfg(feature = "sentry")]
+                sentry::capture(sentry::Event {
+                    level: SentryLevel::Fatal,
+                    logger: "tasks",
+                    message: format!("Background task {} panicked: {}", name, message),
+                    tags: vec![("task", name.to_string())],
+                    request: None,
+                });
+                supervisor.update(name, |t| {
+                    t.state = TaskState::Restarting;
+                    t.last_panic = Some(message);
+                    t.last_panic_at_ms = Some(now_ms());
+                });
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                supervisor.update(name, |t| {
+                    t.state = TaskState::Running;
+                    t.restarts += 1;
+                    t.started_at_ms = now_ms();
+                });
+            }
+        });
+    }
+
+    pub fn report(&self) -> Vec<TaskReport> {
+        self.tasks.lock().unwrap().values().cloned().collect()
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut TaskReport)) {
+        if let Some(task) = self.tasks.lock().unwrap().get_mut(name) {
+            f(task);
+        }
+    }
+}
+
+// Called by a supervised task when it finishes a round of work; a no-op
+// outside one.
+pub fn succeeded() {
+    let _ = CURRENT.try_with(|(supervisor, name)| {
+        supervisor.update(name, |t| t.last_success_ms = Some(now_ms()))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn panic_messages() {
+        assert_eq!(panic_message(Box::new("static")), "static");
+        assert_eq!(panic_message(Box::new(format!("owned {}", 1))), "owned 1");
+        assert_eq!(panic_message(Box::new(7)), "unknown panic");
+    }
+
+    async fn wait_for(supervisor: &Supervisor, done: impl Fn(&TaskReport) -> bool) -> TaskReport {
+        for _ in 0..300 {
+            if let Some(task) = supervisor.report().into_iter().find(|t| done(t)) {
+                return task;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("task never got there: {:?}", supervisor.report());
+    }
+
+    #[tokio::test]
+    async fn panicking_tasks_restart() {
+        let state = AppState::for_tests(Config::default());
+        let supervisor = Arc::new(Supervisor::default());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        supervisor.spawn("flaky", &state, move |_| {
+            let run = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                succeeded();
+                if run == 0 {
+                    panic!("boom");
+                }
+            }
+        });
+
+        let task = wait_for(&supervisor, |t| t.restarts == 0 && t.last_panic.is_some()).await;
+        assert!(matches!(task.state, TaskState::Restarting));
+        assert_eq!(task.last_panic.as_deref(), Some("boom"));
+        assert!(task.last_success_ms.is_some());
+
+        let task = wait_for(&supervisor, |t| matches!(t.state, TaskState::Finished)).await;
+        assert_eq!(task.name, "flaky");
+        assert_eq!(task.restarts, 1);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn succeeded_outside_a_task_does_nothing() {
+        succeeded();
+    }
+}
@@ -0,0 +1,402 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::{
+    amount,
+    audit::{self, Actor, AuditRecord},
+    events, links, logging, receipts, rpc,
+    service::{self, ServiceError},
+    state::AppState,
+};
+
+// Seconds Telegram holds a getUpdates request open when there is nothing new.
+const LONG_POLL_SECS: u64 = 30;
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+const HELP: &str = "Commands:\n\
+    /balance <wallet> - show a wallet's SOL balance\n\
+    /airdrop <wallet> <sol> - request an airdrop";
+
+#[derive(Deserialize)]
+struct Updates {
+    ok: bool,
+    #[serde(default)]
+    result: Vec<Update>,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    chat: Chat,
+    from: Option<User>,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: i64,
+    username: Option<String>,
+}
+
+struct Bot {
+    client: reqwest::Client,
+    base: String,
+}
+
+impl Bot {
+    async fn updates(&self, offset: i64) -> Result<Vec<Update>, String> {
+        let updates: Updates = self
+            .client
+            .get(format!("{}/getUpdates", self.base))
+            .query(&[
+                ("offset", offset.to_string()),
+                ("timeout", LONG_POLL_SECS.to_string()),
+                ("allowed_updates", "[\"message\"]".to_string()),
+            ])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !updates.ok {
+            return Err(updates
+                .description
+                .unwrap_or_else(|| "getUpdates failed".to_string()));
+        }
+        Ok(updates.result)
+    }
+
+    async fn reply(&self, chat_id: i64, text: &str) {
+        let result = self
+            .client
+            .post(format!("{}/sendMessage", self.base))
+            .json(&serde_json::json!({ "chat_id": chat_id, "text": text }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            logging::warn(format_args!("Telegram sendMessage failed: {}", e));
+        }
+    }
+}
+
+// Long-polls for commands until the process exits. Balance and airdrop
+// requests go through the same service functions, policy checks and audit
+// log as the HTTP API.
+pub async fn run(state: AppState) {
+    let (token, api_url) = {
+        let config = state.config();
+        match &config.telegram_bot_token {
+            Some(token) => (token.clone(), config.telegram_api_url.clone()),
+            None => return,
+        }
+    };
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(LONG_POLL_SECS + 10))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            logging::warn(format_args!("Failed to start Telegram bot: {}", e));
+            return;
+        }
+    };
+    let bot = Bot {
+        client,
+        base: format!("{}/bot{}", api_url.trim_end_matches('/'), token),
+    };
+    logging::info("telegram bot: polling for commands");
+
+    let mut offset = 0;
+    loop {
+        let updates = match bot.updates(offset).await {
+            Ok(updates) => updates,
+            Err(e) => {
+                logging::warn(format_args!("Telegram getUpdates failed: {}", e));
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        };
+
+        for update in updates {
+            offset = offset.max(update.update_id + 1);
+            let Some(message) = update.message else {
+                continue;
+            };
+            let Some(text) = message.text else {
+                continue;
+            };
+            let reply = handle(&state, message.from.as_ref(), &text).await;
+            bot.reply(message.chat.id, &reply).await;
+        }
+    }
+}
+
+async fn handle(state: &AppState, from: Option<&User>, text: &str) -> String {
+    let mut args = text.split_whitespace();
+    // Commands may be addressed as /balance@SomeBot in group chats.
+    let command = args
+        .next()
+        .unwrap_or_default()
+        .split('@')
+        .next()
+        .unwrap_or_default();
+    let args: Vec<&str> = args.collect();
+
+    // Commands reach the chain like API calls do, so they share its rate
+    // limit, keyed by the sender's id: usernames can be changed at will.
+    if matches!(command, "/balance" | "/airdrop") {
+        if let Err(retry_after) = state.rate_limiter.check(&state.config(), &subject(from)) {
+            return format!("Rate limit exceeded, retry in {} seconds", retry_after);
+        }
+    }
+
+    match (command, args.as_slice()) {
+        ("/balance", [wallet]) => balance(state, wallet).await,
+        ("/airdrop", [wallet, sol]) => match sol.parse::<u64>() {
+            Ok(sol) => airdrop(state, from, wallet, sol).await,
+            Err(_) => "Amount must be a whole number of SOL".to_string(),
+        },
+        ("/balance", _) => "Usage: /balance <wallet>".to_string(),
+        ("/airdrop", _) => "Usage: /airdrop <wallet> <sol>".to_string(),
+        _ => HELP.to_string(),
+    }
+}
+
+fn subject(from: Option<&User>) -> String {
+    match from {
+        Some(user) => format!("telegram:{}", user.id),
+        None => "telegram".to_string(),
+    }
+}
+
+async fn balance(state: &AppState, wallet: &str) -> String {
+    let rpc_url = state.config().rpc_url.clone();
+    let lookup_wallet = wallet.to_string();
+    let result = rpc::spawn_blocking(move || service::get_balance(&rpc_url, &lookup_wallet)).await;
+    match result {
+        Ok(Ok(lamports)) => format!(
+            "{} has {} SOL",
+            wallet,
+            amount::lamports_to_sol_decimal(lamports)
+        ),
+        Ok(Err(e)) => e.to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+async fn airdrop(state: &AppState, from: Option<&User>, wallet: &str, sol: u64) -> String {
+    let actor = Actor {
+        subject: from.map(|_| subject(from)),
+        ..Actor::system("telegram")
+    };
+    let subject = actor.subject.clone();
+    let record = audit::record(
+        "airdrop",
+        actor,
+        serde_json::json!({
+            "wallet": wallet,
+            "sol": sol,
+            "username": from.and_then(|u| u.username.as_deref()),
+        }),
+    );
+
+    let config = state.config();
+    let limits = config.airdrop_limits();
+    let checks = service::sol_to_lamports(sol).and_then(|lamports| {
+        service::check_airdrop_amount(lamports, limits)?;
+        state.check_airdrop(None, wallet)?;
+        state.usage.reserve_airdrop(
+            subject.as_deref(),
+            lamports,
+            config.airdrop_daily_quota_sol,
+        )?;
+        Ok(lamports)
+    });
+    let threshold = config.airdrop_approval_threshold_lamports;
+    let result = match checks {
+        Ok(lamports) if threshold.is_some_and(|t| lamports > t) => {
+            match state.approvals.create(
+                wallet,
+                lamports,
+                record.actor.clone(),
+                None,
+                &config.approval_callback_hosts,
+            ) {
+                Ok(approval) => {
+                    let reply = format!(
+                        "Airdrops above {} SOL need an admin's approval; request {} is pending.",
+                        amount::lamports_to_sol_decimal(threshold.unwrap_or_default()),
+                        approval.id
+                    );
+                    state.audit.record(AuditRecord {
+                        outcome: "pending".to_string(),
+                        ..record
+                    });
+                    return reply;
+                }
+                Err(e) => {
+                    state.usage.release_airdrop(subject.as_deref(), lamports);
+                    Err(e)
+                }
+            }
+        }
+        Ok(lamports) => {
+            let (fund_config, keypair) = (config.clone(), state.keypair.clone());
+            let fund_wallet = wallet.to_string();
+            rpc::spawn_blocking(move || {
+                receipts::fund_airdrop(
+                    &fund_config,
+                    keypair.as_deref(),
+                    &fund_wallet,
+                    lamports,
+                    true,
+                )
+            })
+            .await
+            .unwrap_or_else(|e| {
+                Err(ServiceError::Rpc {
+                    action: "Airdrop failed",
+                    message: e.to_string(),
+                })
+            })
+            .map(|funded| (funded, lamports))
+            .inspect_err(|_| state.usage.release_airdrop(subject.as_deref(), lamports))
+        }
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok((funded, lamports)) => {
+            let sig = funded.signature;
+            state
+                .audit
+                .record(record.signature(sig).receipt(funded.receipt));
+            events::confirm_faucet(state, sig, wallet.to_string(), lamports, funded.source);
+            format!(
+                "Airdrop of {} SOL requested for {}\n{}",
+                sol,
+                wallet,
+                links::transaction(&config, &sig).solana_explorer
+            )
+        }
+        Err(e) => {
+            state.audit.record(record.failed(&e));
+            e.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+
+    const USER: User = User {
+        id: 42,
+        username: None,
+    };
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn commands_check_balances_and_airdrop() {
+        let state = AppState::for_tests(Config::default());
+        let wallet = Pubkey::new_unique().to_string();
+
+        assert_eq!(
+            handle(
+                &state,
+                Some(&USER),
+                &format!("/balance@FaucetBot {}", wallet)
+            )
+            .await,
+            format!("{} has 0 SOL", wallet)
+        );
+        let reply = handle(&state, Some(&USER), &format!("/airdrop {} 1", wallet)).await;
+        assert!(reply.starts_with(&format!("Airdrop of 1 SOL requested for {}\n", wallet)));
+        assert_eq!(
+            handle(&state, None, &format!("/balance {}", wallet)).await,
+            format!("{} has 1 SOL", wallet)
+        );
+
+        let records = state.audit.query(Some("airdrop"), None, None, 10).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].actor.source, "telegram");
+        assert_eq!(records[0].actor.subject.as_deref(), Some("telegram:42"));
+        assert!(records[0].signature.is_some());
+    }
+
+    #[tokio::test]
+    async fn bad_commands_get_usage() {
+        let state = AppState::for_tests(Config::default());
+        let wallet = Pubkey::new_unique().to_string();
+        assert_eq!(
+            handle(&state, None, "/balance").await,
+            "Usage: /balance <wallet>"
+        );
+        assert_eq!(
+            handle(&state, None, &format!("/airdrop {}", wallet)).await,
+            "Usage: /airdrop <wallet> <sol>"
+        );
+        assert_eq!(
+            handle(&state, None, &format!("/airdrop {} 0.5", wallet)).await,
+            "Amount must be a whole number of SOL"
+        );
+        assert_eq!(handle(&state, None, "hello").await, HELP);
+        assert_eq!(
+            handle(&state, None, &format!("/airdrop {} 1000", wallet)).await,
+            "Airdrop amount too large (max 2 SOL)"
+        );
+    }
+
+    #[tokio::test]
+    async fn large_airdrops_wait_for_approval() {
+        let state = AppState::for_tests(Config {
+            airdrop_approval_threshold_lamports: Some(LAMPORTS_PER_SOL),
+            ..Config::default()
+        });
+        let wallet = Pubkey::new_unique().to_string();
+        let reply = handle(&state, Some(&USER), &format!("/airdrop {} 2", wallet)).await;
+        assert!(reply.starts_with("Airdrops above 1 SOL need an admin's approval; request "));
+        let records = state.audit.query(Some("airdrop"), None, None, 10).unwrap();
+        assert_eq!(records[0].outcome, "pending");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn commands_share_the_api_rate_limit() {
+        let state = AppState::for_tests(Config {
+            rate_limit_per_minute: Some(1),
+            ..Config::default()
+        });
+        let wallet = Pubkey::new_unique().to_string();
+        let renamed = User {
+            username: Some("someone_else".to_string()),
+            ..USER
+        };
+
+        let reply = handle(&state, Some(&USER), &format!("/airdrop {} 1", wallet)).await;
+        assert!(reply.starts_with("Airdrop of 1 SOL requested"));
+        assert_eq!(
+            handle(&state, Some(&renamed), &format!("/balance {}", wallet)).await,
+            "Rate limit exceeded, retry in 60 seconds"
+        );
+        assert_eq!(
+            handle(&state, None, &format!("/balance {}", wallet)).await,
+            format!("{} has 1 SOL", wallet)
+        );
+        // Help doesn't touch the chain.
+        assert_eq!(handle(&state, Some(&USER), "/help").await, HELP);
+    }
+}
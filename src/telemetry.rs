@@ -0,0 +1,169 @@
+// Built without the `otel` feature only `traced` is here, and it leaves
+// requests as they are.
+#[cfg(feature = "otel")]
+use axum::http::HeaderMap;
+#[cfg(feature = "otel")]
+use opentelemetry::{
+    global,
+    propagation::{Extractor, Injector},
+    trace::TracerProvider as _,
+    Context,
+};
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+#[cfg(feature = "otel")]
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+#[cfg(feature = "otel")]
+const SERVICE_NAME: &str = "solana-axum-server";
+
+// Installs a tracing subscriber that exports spans over OTLP/gRPC. The
+// collector endpoint comes from the standard OTEL_EXPORTER_OTLP_ENDPOINT
+// variable (default http://localhost:4317).
+#[cfg(feature = "otel")]
+pub fn init() -> Result<SdkTracerProvider, Box<dyn std::error::Error>> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .build()?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+        .build();
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)))
+        .try_init()?;
+
+    Ok(provider)
+}
+
+#[cfg(feature = "otel")]
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+#[cfg(feature = "otel")]
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+// Continues the caller's trace when the request carries a `traceparent` header.
+#[cfg(feature = "otel")]
+pub fn parent_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+// Outbound calls are made with reqwest, on the older `http` crate.
+#[cfg(feature = "otel")]
+struct HeaderInjector<'a>(&'a mut reqwest::header::HeaderMap);
+
+#[cfg(feature = "otel")]
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            reqwest::header::HeaderName::from_bytes(key.as_bytes()),
+            reqwest::header::HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+fn inject(context: &Context, headers: &mut reqwest::header::HeaderMap) {
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(context, &mut HeaderInjector(headers))
+    });
+}
+
+// Carries the current span's trace on to an outbound call (webhooks, event
+// sinks, approval callbacks) as a `traceparent` header.
+pub fn traced(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    #[cfg(feature = "otel")]
+    {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+        let mut headers = reqwest::header::HeaderMap::new();
+        inject(&tracing::Span::current().context(), &mut headers);
+        request.headers(headers)
+    }
+    #[cfg(not(feature = "otel"))]
+    request
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{TraceContextExt, TraceId};
+
+    #[test]
+    fn traceparent_continues_the_callers_trace() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        let context = parent_context(&headers);
+        let span = context.span();
+        let parent = span.span_context();
+        assert!(parent.is_remote() && parent.is_sampled());
+        assert_eq!(
+            parent.trace_id(),
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+        );
+
+        let context = parent_context(&HeaderMap::new());
+        assert!(!context.span().span_context().is_valid());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn blocking_work_and_outbound_calls_stay_in_the_request_trace() {
+        use tracing::Instrument;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = SdkTracerProvider::builder().build();
+        let _ = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)))
+            .try_init();
+
+        let mut incoming = HeaderMap::new();
+        incoming.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        let span = tracing::info_span!("http.request");
+        let _ = span.set_parent(parent_context(&incoming));
+        let outbound = async {
+            crate::rpc::spawn_blocking(|| {
+                let _span = tracing::info_span!("rpc.get_balance").entered();
+                traced(reqwest::Client::new().post("http://hooks.example.com/"))
+                    .build()
+                    .unwrap()
+            })
+            .await
+            .unwrap()
+        }
+        .instrument(span)
+        .await;
+
+        // The webhook hears the caller's trace, from the RPC span's id.
+        let traceparent = outbound.headers()["traceparent"].to_str().unwrap();
+        let parts: Vec<&str> = traceparent.split('-').collect();
+        assert_eq!(parts[1], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_ne!(parts[2], "00f067aa0ba902b7");
+        assert_eq!(parts[3], "01");
+    }
+}
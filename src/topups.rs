@@ -0,0 +1,326 @@
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    audit::{self, Actor},
+    clock::now_ms,
+    events::{self, EventKind},
+    logging, receipts,
+    service::{self, ServiceError},
+    state::AppState,
+    store::Store,
+    tasks,
+};
+
+pub use crate::api::{Topup, TopupOutcome, TopupRun};
+
+const STORE_NAME: &str = "topups";
+
+// CI wallets kept above a minimum balance. Saved as JSON under "topups" in
+// the store (`topups_path` for files), including each wallet's last run.
+pub struct TopupStore {
+    store: Arc<dyn Store>,
+    entries: Mutex<BTreeMap<Pubkey, Topup>>,
+}
+
+impl TopupStore {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let mut entries = BTreeMap::new();
+        if let Some(text) = store.read(STORE_NAME)? {
+            let topups: Vec<Topup> = serde_json::from_str(&text)
+                .map_err(|e| format!("invalid topups in {}: {}", store.location(STORE_NAME), e))?;
+            for topup in topups {
+                let wallet = service::parse_wallet(&topup.wallet).map_err(|_| {
+                    format!(
+                        "invalid wallet '{}' in {}",
+                        topup.wallet,
+                        store.location(STORE_NAME)
+                    )
+                })?;
+                entries.insert(wallet, topup);
+            }
+        }
+        Ok(TopupStore {
+            store,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    pub fn register(&self, wallet: Pubkey, topup: Topup) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap();
+        let previous = entries.insert(wallet, topup);
+        if let Err(e) = self.save(&entries) {
+            match previous {
+                Some(previous) => entries.insert(wallet, previous),
+                None => entries.remove(&wallet),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, wallet: &Pubkey) -> Result<bool, String> {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(previous) = entries.remove(wallet) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.save(&entries) {
+            entries.insert(*wallet, previous);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    pub fn get(&self, wallet: &Pubkey) -> Option<Topup> {
+        self.entries.lock().unwrap().get(wallet).cloned()
+    }
+
+    pub fn wallets(&self) -> Vec<Pubkey> {
+        self.entries.lock().unwrap().keys().copied().collect()
+    }
+
+    fn record_run(&self, wallet: &Pubkey, run: TopupRun) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(topup) = entries.get_mut(wallet) {
+            topup.last_run = Some(run);
+            if let Err(e) = self.save(&entries) {
+                logging::warn(format_args!("{}", e));
+            }
+        }
+    }
+
+    fn save(&self, entries: &BTreeMap<Pubkey, Topup>) -> Result<(), String> {
+        if !self.store.persists(STORE_NAME) {
+            return Ok(());
+        }
+        let topups: Vec<&Topup> = entries.values().collect();
+        let text = serde_json::to_string_pretty(&topups).map_err(|e| e.to_string())?;
+        self.store.write(STORE_NAME, &text)
+    }
+}
+
+// Checks one wallet and tops it up to its target when it is below the
+// minimum. Pays from the server keypair when there is one, otherwise asks
+// the RPC node for an airdrop of the deficit (limited to max_airdrop_sol per
+// run).
+pub async fn run_one(state: &AppState, wallet: Pubkey) {
+    let Some(topup) = state.topups.get(&wallet) else {
+        return;
+    };
+    let config = state.config();
+    let keypair = state.keypair.clone();
+    let wallet_str = topup.wallet.clone();
+
+    let denied = state.check_airdrop(None, &wallet_str).err();
+    let result = tokio::task::spawn_blocking(move || {
+        let balance = service::get_balance(&config.rpc_url, &wallet_str)?;
+        if balance >= topup.min_lamports {
+            return Ok((balance, None));
+        }
+        if let Some(e) = denied {
+            return Err(e);
+        }
+
+        let deficit = topup.target_lamports - balance;
+        let funded = match &keypair {
+            Some(payer) => {
+                let (sig, receipt) = receipts::pay(&config, payer, &wallet, deficit, true)?;
+                (deficit, "treasury", sig, receipt)
+            }
+            None => {
+                let limits = config.airdrop_limits();
+                let lamports = deficit.min(limits.max_lamports).max(limits.min_lamports);
+                let sig = service::request_airdrop(&config.rpc_url, &wallet_str, lamports, limits)?;
+                (lamports, "airdrop", sig, None)
+            }
+        };
+        Ok((balance, Some(funded)))
+    })
+    .await
+    .unwrap_or_else(|e| {
+        Err(ServiceError::Rpc {
+            action: "Top-up failed",
+            message: e.to_string(),
+        })
+    });
+
+    let checked_at_ms = now_ms();
+    if let Ok((balance, _)) = &result {
+        state.events.observe_balance(&wallet, *balance);
+    }
+    let run = match result {
+        Ok((balance, None)) => TopupRun {
+            checked_at_ms,
+            outcome: TopupOutcome::Skipped,
+            balance_lamports: Some(balance),
+            amount_lamports: None,
+            source: None,
+            signature: None,
+            error: None,
+        },
+        Ok((balance, Some((amount, source, sig, receipt)))) => {
+            state.audit.record(
+                audit::record(
+                    "topup",
+                    Actor::system("topup"),
+                    serde_json::json!({ "wallet": wallet.to_string(), "lamports": amount, "source": source }),
+                )
+                .signature(sig)
+                .receipt(receipt),
+            );
+            // Treasury transfers are confirmed by the time they return.
+            if source == "treasury" {
+                state.events.publish(
+                    EventKind::FaucetConfirmed,
+                    serde_json::json!({
+                        "wallet": wallet.to_string(),
+                        "lamports": amount,
+                        "source": source,
+                        "signature": sig.to_string(),
+                    }),
+                );
+            } else {
+                events::confirm_faucet(state, sig, wallet.to_string(), amount, source);
+            }
+            TopupRun {
+                checked_at_ms,
+                outcome: TopupOutcome::ToppedUp,
+                balance_lamports: Some(balance),
+                amount_lamports: Some(amount),
+                source: Some(source.to_string()),
+                signature: Some(sig.to_string()),
+                error: None,
+            }
+        }
+        Err(e) => {
+            state.audit.record(
+                audit::record(
+                    "topup",
+                    Actor::system("topup"),
+                    serde_json::json!({ "wallet": wallet.to_string() }),
+                )
+                .failed(&e),
+            );
+            TopupRun {
+                checked_at_ms,
+                outcome: TopupOutcome::Failed,
+                balance_lamports: None,
+                amount_lamports: None,
+                source: None,
+                signature: None,
+                error: Some(e.to_string()),
+            }
+        }
+    };
+    state.topups.record_run(&wallet, run);
+}
+
+pub async fn scheduler(state: AppState) {
+    loop {
+        let interval = state.config().topup_interval_secs;
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        for wallet in state.topups.wallets() {
+            run_one(&state, wallet).await;
+        }
+        tasks::succeeded();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, store::MemoryStore};
+    use solana_sdk::signature::{Keypair, Signer};
+
+    fn topup(wallet: Pubkey, min_lamports: u64, target_lamports: u64) -> Topup {
+        Topup {
+            wallet: wallet.to_string(),
+            min_lamports,
+            target_lamports,
+            registered_by: None,
+            registered_at_ms: 0,
+            last_run: None,
+        }
+    }
+
+    #[test]
+    fn registrations_survive_a_reload() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        let topups = TopupStore::load(store.clone()).unwrap();
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        topups.register(a, topup(a, 1, 2)).unwrap();
+        topups.register(b, topup(b, 1, 2)).unwrap();
+        topups.register(a, topup(a, 5, 10)).unwrap();
+        assert_eq!(topups.remove(&b), Ok(true));
+        assert_eq!(topups.remove(&b), Ok(false));
+
+        let reloaded = TopupStore::load(store).unwrap();
+        assert_eq!(reloaded.wallets(), [a]);
+        assert_eq!(reloaded.get(&a).unwrap().target_lamports, 10);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn wallets_below_their_minimum_are_airdropped_the_deficit() {
+        let state = AppState::for_tests(Config::default());
+        let chain = crate::rpc::mock_for_tests();
+        let (low, funded) = (Pubkey::new_unique(), Pubkey::new_unique());
+        chain.set_balance(low, 400);
+        chain.set_balance(funded, 5_000);
+        state
+            .topups
+            .register(low, topup(low, 1_000, 3_000))
+            .unwrap();
+        state
+            .topups
+            .register(funded, topup(funded, 1_000, 3_000))
+            .unwrap();
+
+        run_one(&state, low).await;
+        run_one(&state, funded).await;
+
+        let run = state.topups.get(&low).unwrap().last_run.unwrap();
+        assert!(matches!(run.outcome, TopupOutcome::ToppedUp));
+        assert_eq!(
+            (
+                run.balance_lamports,
+                run.amount_lamports,
+                run.source.as_deref()
+            ),
+            (Some(400), Some(2_600), Some("airdrop"))
+        );
+        assert_eq!(
+            service::get_balance("http://mock", &low.to_string()).unwrap(),
+            3_000
+        );
+        let run = state.topups.get(&funded).unwrap().last_run.unwrap();
+        assert!(matches!(run.outcome, TopupOutcome::Skipped));
+        assert_eq!(run.amount_lamports, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn the_treasury_pays_when_there_is_a_keypair() {
+        let mut state = AppState::for_tests(Config::default());
+        let treasury = Keypair::new();
+        crate::rpc::mock_for_tests().set_balance(treasury.pubkey(), 1_000_000);
+        state.keypair = Some(Arc::new(treasury));
+        let wallet = Pubkey::new_unique();
+        state
+            .topups
+            .register(wallet, topup(wallet, 1_000, 2_000))
+            .unwrap();
+
+        run_one(&state, wallet).await;
+        let run = state.topups.get(&wallet).unwrap().last_run.unwrap();
+        assert_eq!(
+            (run.amount_lamports, run.source.as_deref()),
+            (Some(2_000), Some("treasury"))
+        );
+        let records = state.audit.query(Some("topup"), None, None, 10).unwrap();
+        assert_eq!(records[0].signature, run.signature);
+    }
+}
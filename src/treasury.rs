@@ -0,0 +1,407 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use solana_client::{rpc_client::GetConfirmedSignaturesForAddress2Config, rpc_client::RpcClient};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    message::Message,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    amount,
+    auth::{Principal, Role},
+    config::Config,
+    confirm,
+    handlers::service_error,
+    history, links, notify, rpc,
+    service::{self, rpc_error, ServiceError},
+    state::AppState,
+    tasks,
+};
+
+pub use crate::api::{AuxBalance, Movement, Outflow};
+
+// The auxiliary keypairs the treasury sweeps, and whether the last
+// watermark check found the treasury low.
+#[derive(Default)]
+pub struct Treasury {
+    pub aux: Vec<Arc<Keypair>>,
+    low: AtomicBool,
+}
+
+impl Treasury {
+    pub fn load(paths: &[PathBuf]) -> Result<Self, String> {
+        let aux = paths
+            .iter()
+            .map(|path| {
+                read_keypair_file(path)
+                    .map(Arc::new)
+                    .map_err(|e| format!("failed to read keypair '{}': {}", path.display(), e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Treasury {
+            aux,
+            ..Default::default()
+        })
+    }
+
+    // Set while the treasury is below `treasury_low_watermark_sol`.
+    pub fn is_low(&self) -> bool {
+        self.low.load(Ordering::Relaxed)
+    }
+
+    // Returns whether the treasury just went below (Some(true)) or back
+    // above (Some(false)) the watermark.
+    fn record(&self, balance: u64, watermark: Option<u64>) -> Option<bool> {
+        let low = watermark.is_some_and(|w| balance < w);
+        let was_low = self.low.swap(low, Ordering::Relaxed);
+        (low != was_low).then_some(low)
+    }
+}
+
+pub fn watermark_lamports(sol: Option<f64>) -> Option<u64> {
+    sol.map(|sol| (sol * LAMPORTS_PER_SOL as f64) as u64)
+}
+
+pub fn recent_outflows(
+    config: &Config,
+    treasury: &Pubkey,
+    limit: usize,
+) -> Result<Vec<Outflow>, ServiceError> {
+    let rpc_url = &config.rpc_url;
+    let client = rpc::client(rpc_url);
+    let _span = tracing::info_span!("rpc.treasury_outflows", %rpc_url, limit).entered();
+    let signatures = client
+        .get_signatures_for_address_with_config(
+            treasury,
+            GetConfirmedSignaturesForAddress2Config {
+                limit: Some(limit),
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..Default::default()
+            },
+        )
+        .map_err(rpc_error("Failed to get signatures"))?;
+
+    let mut outflows = Vec::new();
+    for sig in signatures {
+        let tx = history::fetch_transaction(&client, &sig.signature)?;
+        let Some((pre, post)) = history::balance_change(&tx, treasury) else {
+            continue;
+        };
+        if post < pre {
+            outflows.push(Outflow {
+                explorer_urls: links::transaction(config, &sig.signature),
+                signature: sig.signature,
+                slot: sig.slot,
+                block_time: sig.block_time,
+                lamports: pre - post,
+            });
+        }
+    }
+    Ok(outflows)
+}
+
+fn send(
+    client: &RpcClient,
+    from: &Keypair,
+    to: &Pubkey,
+    lamports: u64,
+) -> Result<String, ServiceError> {
+    let blockhash = client
+        .get_latest_blockhash()
+        .map_err(rpc_error("Transfer failed"))?;
+    let tx = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(&from.pubkey(), to, lamports)],
+        Some(&from.pubkey()),
+        &[from],
+        blockhash,
+    );
+    confirm::send_and_confirm(client, &tx, "Transfer failed").map(|sig| sig.to_string())
+}
+
+// Brings one auxiliary keypair to `target` lamports: the treasury pays a
+// shortfall, and a surplus goes back to the treasury less the fee, which
+// the keypair pays.
+fn rebalance_one(
+    client: &RpcClient,
+    treasury: &Keypair,
+    aux: &Keypair,
+    target: u64,
+) -> Result<(u64, i128, Option<String>), ServiceError> {
+    let balance = client
+        .get_balance(&aux.pubkey())
+        .map_err(rpc_error("Failed to get balance"))?;
+    if balance < target {
+        let amount = target - balance;
+        let sig = send(client, treasury, &aux.pubkey(), amount)?;
+        return Ok((balance, amount as i128, Some(sig)));
+    }
+
+    let message = Message::new(
+        &[system_instruction::transfer(
+            &aux.pubkey(),
+            &treasury.pubkey(),
+            balance - target,
+        )],
+        Some(&aux.pubkey()),
+    );
+    let fee = client
+        .get_fee_for_message(&message)
+        .map_err(rpc_error("Failed to get fee"))?;
+    // Dust that doesn't cover the fee stays where it is.
+    let Some(amount) = (balance - target).checked_sub(fee).filter(|a| *a > 0) else {
+        return Ok((balance, 0, None));
+    };
+    let sig = send(client, aux, &treasury.pubkey(), amount)?;
+    Ok((balance, -(amount as i128), Some(sig)))
+}
+
+// Sweeping is rebalancing to zero.
+pub fn rebalance(
+    config: &Config,
+    treasury: &Keypair,
+    aux: &[Arc<Keypair>],
+    target: u64,
+) -> Vec<Movement> {
+    let rpc_url = &config.rpc_url;
+    let client = rpc::client(rpc_url);
+    let _span = tracing::info_span!("rpc.treasury_rebalance", %rpc_url, target).entered();
+    aux.iter()
+        .map(
+            |keypair| match rebalance_one(&client, treasury, keypair, target) {
+                Ok((balance, moved, signature)) => Movement {
+                    address: keypair.pubkey().to_string(),
+                    balance_lamports: balance,
+                    moved_lamports: moved,
+                    explorer_urls: signature.as_ref().map(|s| links::transaction(config, s)),
+                    signature,
+                    error: None,
+                },
+                Err(e) => Movement {
+                    address: keypair.pubkey().to_string(),
+                    balance_lamports: 0,
+                    moved_lamports: 0,
+                    signature: None,
+                    explorer_urls: None,
+                    error: Some(e.to_string()),
+                },
+            },
+        )
+        .collect()
+}
+
+pub fn aux_balances(rpc_url: &str, aux: &[Arc<Keypair>]) -> Vec<AuxBalance> {
+    let client = rpc::client(rpc_url);
+    aux.iter()
+        .map(|keypair| {
+            let result = client
+                .get_balance(&keypair.pubkey())
+                .map_err(rpc_error("Failed to get balance"));
+            AuxBalance {
+                address: keypair.pubkey().to_string(),
+                balance_lamports: result.as_ref().ok().copied(),
+                error: result.err().map(|e| e.to_string()),
+            }
+        })
+        .collect()
+}
+
+// Checks the treasury against the low watermark every
+// `treasury_check_interval_secs`, alerting when it crosses it either way.
+pub async fn watch(state: AppState) {
+    let Some(keypair) = state.keypair.clone() else {
+        return;
+    };
+    loop {
+        let config = state.config();
+        let rpc_url = config.rpc_url.clone();
+        let treasury = keypair.pubkey();
+        let wallet = treasury.to_string();
+        let balance = rpc::spawn_blocking(move || service::get_balance(&rpc_url, &wallet)).await;
+        if let Ok(Ok(balance)) = balance {
+            tasks::succeeded();
+            state.events.observe_balance(&treasury, balance);
+            let watermark = watermark_lamports(config.treasury_low_watermark_sol);
+            match state.treasury.record(balance, watermark) {
+                Some(true) => {
+                    let text = format!(
+                        "Treasury {} is down to {} SOL, below the {} SOL watermark; \
+                         airdrops and other writes are paused for non-admins",
+                        treasury,
+                        amount::lamports_to_sol_decimal(balance),
+                        config.treasury_low_watermark_sol.unwrap_or_default()
+                    );
+                    state
+                        .notifier
+                        .notify(&config, notify::Event::TreasuryLow, &text)
+                        .await;
+                }
+                Some(false) => {
+                    let text = format!(
+                        "Treasury {} is back to {} SOL; writes have resumed",
+                        treasury,
+                        amount::lamports_to_sol_decimal(balance)
+                    );
+                    state
+                        .notifier
+                        .notify(&config, notify::Event::TreasuryRecovered, &text)
+                        .await;
+                }
+                None => {}
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(config.treasury_check_interval_secs)).await;
+    }
+}
+
+// Layered on the write groups, inside their auth guard: while the treasury
+// is below its watermark only admins get through.
+pub async fn guard(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let admin = req
+        .extensions()
+        .get::<Principal>()
+        .is_some_and(|p| p.role == Some(Role::Admin));
+    if !state.treasury.is_low() || admin {
+        return next.run(req).await;
+    }
+    service_error(ServiceError::TreasuryLow).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::write_keypair_file;
+
+    #[test]
+    fn watermark_crossings_are_reported_once() {
+        let treasury = Treasury::default();
+        let watermark = watermark_lamports(Some(1.5));
+        assert_eq!(watermark, Some(1_500_000_000));
+
+        assert_eq!(treasury.record(2_000_000_000, watermark), None);
+        assert_eq!(treasury.record(1_499_999_999, watermark), Some(true));
+        assert!(treasury.is_low());
+        assert_eq!(treasury.record(1_000_000_000, watermark), None);
+        assert_eq!(treasury.record(1_500_000_000, watermark), Some(false));
+        assert!(!treasury.is_low());
+        // Without a watermark the treasury is never low.
+        assert_eq!(treasury.record(0, None), None);
+    }
+
+    #[test]
+    fn load_reads_every_aux_keypair() {
+        let keypair = Keypair::new();
+        let path = std::env::temp_dir().join(format!("treasury-aux-{}.json", std::process::id()));
+        write_keypair_file(&keypair, &path).unwrap();
+
+        let treasury = Treasury::load(std::slice::from_ref(&path)).unwrap();
+        assert_eq!(treasury.aux[0].pubkey(), keypair.pubkey());
+        std::fs::remove_file(&path).unwrap();
+        assert!(Treasury::load(&[path])
+            .err()
+            .unwrap()
+            .starts_with("failed to read keypair"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rebalance_tops_up_and_sweeps_back() {
+        let chain = rpc::mock_for_tests();
+        let treasury = Keypair::new();
+        let aux = [(); 3].map(|_| Arc::new(Keypair::new()));
+        chain.set_balance(treasury.pubkey(), 1_000_000);
+        chain.set_balance(aux[0].pubkey(), 400);
+        chain.set_balance(aux[1].pubkey(), 10_000);
+        // 3 lamports over target doesn't cover the 5000 lamport fee.
+        chain.set_balance(aux[2].pubkey(), 3_003);
+
+        let config = Config::default();
+        let (movements, balances, outflows) = tokio::task::spawn_blocking(move || {
+            let movements = rebalance(&config, &treasury, &aux, 3_000);
+            let balances = aux_balances(&config.rpc_url, &aux);
+            let outflows = recent_outflows(&config, &treasury.pubkey(), 10);
+            (movements, balances, outflows)
+        })
+        .await
+        .unwrap();
+
+        let moved: Vec<_> = movements.iter().map(|m| m.moved_lamports).collect();
+        assert_eq!(moved, [2_600, -2_000, 0]);
+        assert!(movements[0].signature.is_some() && movements[2].signature.is_none());
+        assert_eq!(movements[1].balance_lamports, 10_000);
+
+        let balances: Vec<_> = balances.iter().map(|b| b.balance_lamports).collect();
+        assert_eq!(balances, [Some(3_000), Some(3_000), Some(3_003)]);
+
+        // The top-up and its fee left the treasury; the sweep came in.
+        let outflows = outflows.unwrap();
+        assert_eq!(outflows.len(), 1);
+        assert_eq!(outflows[0].lamports, 2_600 + 5_000);
+    }
+
+    #[tokio::test]
+    async fn only_admins_write_while_low() {
+        use axum::{middleware, routing::post, Router};
+
+        let state = AppState::for_tests(Config::default());
+        // Callers sending x-admin get an admin principal, as the auth
+        // guard would give them.
+        let app = Router::new()
+            .route("/write", post(|| async { "ok" }))
+            .route_layer(middleware::from_fn_with_state(state.clone(), guard))
+            .route_layer(middleware::from_fn(
+                |mut req: Request, next: Next| async move {
+                    if req.headers().contains_key("x-admin") {
+                        req.extensions_mut().insert(Principal {
+                            subject: Some("admin_token".into()),
+                            role: Some(Role::Admin),
+                            wallet: None,
+                            org: None,
+                            priority: crate::auth::Priority::High,
+                        });
+                    }
+                    next.run(req).await
+                },
+            ))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/write", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let client = reqwest::Client::new();
+
+        let res = client.post(&url).send().await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.text().await.unwrap(), "ok");
+        state.treasury.record(0, Some(1));
+        let res = client.post(&url).send().await.unwrap();
+        assert_eq!(res.status(), 503);
+        let body: serde_json::Value = res.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "treasury_low");
+        assert_eq!(
+            body["error"]["message"],
+            ServiceError::TreasuryLow.to_string()
+        );
+        let admin = client
+            .post(&url)
+            .header("x-admin", "1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(admin.status(), 200);
+        assert_eq!(admin.text().await.unwrap(), "ok");
+    }
+}
@@ -0,0 +1,620 @@
+use serde::{Deserialize, Serialize};
+use solana_client::{
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, pubkey, pubkey::Pubkey, signature::Signature,
+};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, UiTransactionStatusMeta,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use crate::{
+    decoders::MEMO_PROGRAM_ID,
+    history, logging, rpc,
+    service::{rpc_error, ServiceError},
+    state::AppState,
+    store::Store,
+    tasks,
+};
+
+pub use crate::api::{IndexedTx, MemoMatch, TxKind};
+
+const STORE_NAME: &str = "tx_history";
+const SIGNATURE_PAGE_SIZE: usize = 1_000;
+// Caps how far a poll looks for new transactions of a very busy wallet.
+const MAX_SIGNATURE_PAGES: usize = 10;
+const MEMO_V1_PROGRAM_ID: Pubkey = pubkey!("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo");
+
+// One line of the store's log.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum Line {
+    Transaction(IndexedTx),
+    // The wallet's history is indexed back to its first transaction, or
+    // to `tx_history_max_backfill`.
+    BackfillComplete { wallet: String },
+}
+
+#[derive(Default)]
+struct WalletHistory {
+    // By (slot, signature), so oldest first.
+    transactions: BTreeMap<(u64, String), IndexedTx>,
+    backfill_complete: bool,
+}
+
+impl WalletHistory {
+    fn newest(&self) -> Option<Signature> {
+        let (_, signature) = self.transactions.keys().next_back()?;
+        Signature::from_str(signature).ok()
+    }
+
+    fn oldest(&self) -> Option<Signature> {
+        let (_, signature) = self.transactions.keys().next()?;
+        Signature::from_str(signature).ok()
+    }
+}
+
+pub struct Filter {
+    pub kind: Option<TxKind>,
+    // Compared with the size of the SOL change, either way.
+    pub min_lamports: Option<u64>,
+    // Block times, in Unix seconds, both inclusive.
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    // Continues a listing after this signature.
+    pub before: Option<String>,
+    pub limit: usize,
+}
+
+impl Filter {
+    fn matches(&self, tx: &IndexedTx) -> bool {
+        self.kind.is_none_or(|kind| tx.kind == kind)
+            && self
+                .min_lamports
+                .is_none_or(|min| tx.change_lamports.unsigned_abs() >= min)
+            && self
+                .since
+                .is_none_or(|since| tx.block_time.is_some_and(|t| t >= since))
+            && self
+                .until
+                .is_none_or(|until| tx.block_time.is_some_and(|t| t <= until))
+    }
+}
+
+pub struct WalletTransactions {
+    // Newest first.
+    pub transactions: Vec<IndexedTx>,
+    pub indexed: usize,
+    pub backfill_complete: bool,
+    // Pass as `before` for the next page; None on the last.
+    pub next_before: Option<String>,
+}
+
+// The transaction history of watchlist wallets, indexed locally so it can
+// be filtered without walking the wallet's signatures on every request.
+// Appended as JSON lines under "tx_history" in the store
+// (`tx_history_path` for files).
+pub struct TxHistory {
+    store: Arc<dyn Store>,
+    wallets: RwLock<HashMap<Pubkey, WalletHistory>>,
+}
+
+impl TxHistory {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let mut wallets: HashMap<Pubkey, WalletHistory> = HashMap::new();
+        let text = store.read(STORE_NAME)?.unwrap_or_default();
+        for line in text.lines() {
+            let Ok(line) = serde_json::from_str::<Line>(line) else {
+                continue;
+            };
+            match line {
+                Line::Transaction(tx) => {
+                    if let Ok(wallet) = Pubkey::from_str(&tx.wallet) {
+                        let history = wallets.entry(wallet).or_default();
+                        history
+                            .transactions
+                            .insert((tx.slot, tx.signature.clone()), tx);
+                    }
+                }
+                Line::BackfillComplete { wallet } => {
+                    if let Ok(wallet) = Pubkey::from_str(&wallet) {
+                        wallets.entry(wallet).or_default().backfill_complete = true;
+                    }
+                }
+            }
+        }
+        Ok(TxHistory {
+            store,
+            wallets: RwLock::new(wallets),
+        })
+    }
+
+    // (newest, oldest, backfill complete, transactions indexed)
+    fn cursor(&self, wallet: &Pubkey) -> (Option<Signature>, Option<Signature>, bool, usize) {
+        let wallets = self.wallets.read().unwrap();
+        match wallets.get(wallet) {
+            Some(history) => (
+                history.newest(),
+                history.oldest(),
+                history.backfill_complete,
+                history.transactions.len(),
+            ),
+            None => (None, None, false, 0),
+        }
+    }
+
+    fn record(&self, wallet: Pubkey, transactions: Vec<IndexedTx>, backfill_complete: bool) {
+        let mut lines = Vec::with_capacity(transactions.len() + 1);
+        let mut wallets = self.wallets.write().unwrap();
+        let history = wallets.entry(wallet).or_default();
+        for tx in transactions {
+            let key = (tx.slot, tx.signature.clone());
+            if history.transactions.contains_key(&key) {
+                continue;
+            }
+            lines.push(Line::Transaction(tx.clone()));
+            history.transactions.insert(key, tx);
+        }
+        if backfill_complete && !history.backfill_complete {
+            history.backfill_complete = true;
+            lines.push(Line::BackfillComplete {
+                wallet: wallet.to_string(),
+            });
+        }
+        drop(wallets);
+
+        for line in lines {
+            let result = serde_json::to_string(&line)
+                .map_err(|e| e.to_string())
+                .and_then(|line| self.store.append(STORE_NAME, &line));
+            if let Err(e) = result {
+                logging::warn(format_args!("Failed to save transaction history: {}", e));
+            }
+        }
+    }
+
+    // None when the wallet hasn't been indexed.
+    pub fn query(&self, wallet: &Pubkey, filter: &Filter) -> Option<WalletTransactions> {
+        let wallets = self.wallets.read().unwrap();
+        let history = wallets.get(wallet)?;
+        let mut newest_first = history.transactions.values().rev().peekable();
+        if let Some(before) = &filter.before {
+            while newest_first.next_if(|tx| &tx.signature != before).is_some() {}
+            newest_first.next();
+        }
+        let mut matching = newest_first.filter(|tx| filter.matches(tx));
+        let transactions: Vec<IndexedTx> = matching.by_ref().take(filter.limit).cloned().collect();
+        let next_before = match matching.next() {
+            Some(_) => transactions.last().map(|tx| tx.signature.clone()),
+            None => None,
+        };
+        Some(WalletTransactions {
+            transactions,
+            indexed: history.transactions.len(),
+            backfill_complete: history.backfill_complete,
+            next_before,
+        })
+    }
+
+    // Indexed transactions whose memos hold every word of `query` (case
+    // insensitive), newest first.
+    pub fn search_memos(&self, query: &str, limit: usize) -> Vec<MemoMatch> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        let wallets = self.wallets.read().unwrap();
+        let mut matches: HashMap<&str, MemoMatch> = HashMap::new();
+        for tx in wallets.values().flat_map(|h| h.transactions.values()) {
+            if tx.memos.is_empty() {
+                continue;
+            }
+            if let Some(found) = matches.get_mut(tx.signature.as_str()) {
+                found.wallets.push(tx.wallet.clone());
+                continue;
+            }
+            let text = tx.memos.join("\n").to_lowercase();
+            if terms.iter().all(|term| text.contains(term)) {
+                matches.insert(
+                    &tx.signature,
+                    MemoMatch {
+                        signature: tx.signature.clone(),
+                        wallets: vec![tx.wallet.clone()],
+                        slot: tx.slot,
+                        block_time: tx.block_time,
+                        memos: tx.memos.clone(),
+                    },
+                );
+            }
+        }
+        let mut matches: Vec<MemoMatch> = matches.into_values().collect();
+        matches.sort_by(|a, b| b.slot.cmp(&a.slot).then(a.signature.cmp(&b.signature)));
+        matches.truncate(limit);
+        for found in &mut matches {
+            found.wallets.sort();
+        }
+        matches
+    }
+}
+
+// The text of each top-level memo instruction, in order.
+fn memos(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Vec<String> {
+    let Some(decoded) = tx.transaction.transaction.decode() else {
+        return Vec::new();
+    };
+    let keys = decoded.message.static_account_keys();
+    decoded
+        .message
+        .instructions()
+        .iter()
+        .filter(|ix| {
+            keys.get(ix.program_id_index as usize)
+                .is_some_and(|p| *p == MEMO_PROGRAM_ID || *p == MEMO_V1_PROGRAM_ID)
+        })
+        .map(|ix| String::from_utf8_lossy(&ix.data).into_owned())
+        .collect()
+}
+
+fn delta(meta: &UiTransactionStatusMeta, i: usize) -> i128 {
+    let pre = meta.pre_balances.get(i).copied().unwrap_or(0) as i128;
+    let post = meta.post_balances.get(i).copied().unwrap_or(0) as i128;
+    // The fee payer's change includes the fee; keep transfers separate.
+    post - pre + if i == 0 { meta.fee as i128 } else { 0 }
+}
+
+fn classify(
+    wallet: &Pubkey,
+    status: RpcConfirmedTransactionStatusWithSignature,
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+) -> IndexedTx {
+    let mut indexed = IndexedTx {
+        wallet: wallet.to_string(),
+        signature: status.signature,
+        slot: status.slot,
+        block_time: status.block_time,
+        succeeded: status.err.is_none(),
+        kind: TxKind::Other,
+        change_lamports: 0,
+        fee_lamports: 0,
+        counterparty: None,
+        mints: Vec::new(),
+        memos: memos(tx),
+    };
+    let Some(meta) = tx.transaction.meta.as_ref() else {
+        return indexed;
+    };
+    let keys = history::account_keys(tx);
+    let Some(index) = keys.iter().position(|k| k == wallet) else {
+        return indexed;
+    };
+    if index == 0 {
+        indexed.fee_lamports = meta.fee;
+    }
+    if meta.err.is_some() {
+        return indexed;
+    }
+
+    let own = delta(meta, index);
+    indexed.change_lamports = own.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+    if own != 0 {
+        indexed.counterparty = keys
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != index)
+            .map(|(i, key)| (delta(meta, i), key))
+            .filter(|(theirs, _)| *theirs != 0 && theirs.signum() != own.signum())
+            .max_by_key(|(theirs, _)| theirs.abs())
+            .map(|(_, key)| key.to_string());
+    }
+
+    let owner = wallet.to_string();
+    let pre = history::owned_token_amounts(meta.pre_token_balances.as_ref().into(), &owner);
+    let post = history::owned_token_amounts(meta.post_token_balances.as_ref().into(), &owner);
+    let mut mints: Vec<String> = pre
+        .keys()
+        .chain(post.keys())
+        .filter(|mint| {
+            pre.get(*mint).map(|(_, a)| *a).unwrap_or(0)
+                != post.get(*mint).map(|(_, a)| *a).unwrap_or(0)
+        })
+        .cloned()
+        .collect();
+    mints.sort();
+    mints.dedup();
+    indexed.mints = mints;
+
+    indexed.kind = if !indexed.mints.is_empty() {
+        TxKind::Token
+    } else if own != 0 {
+        TxKind::Transfer
+    } else {
+        TxKind::Other
+    };
+    indexed
+}
+
+// Blocking: up to `batch` transactions to index for `wallet`, oldest of
+// the new ones first, then older history, and whether the backfill is
+// done. `indexed` counts what the wallet already has.
+fn sync_wallet(
+    rpc_url: &str,
+    wallet: &Pubkey,
+    (newest, oldest, complete, indexed): (Option<Signature>, Option<Signature>, bool, usize),
+    batch: usize,
+    max_backfill: usize,
+) -> Result<(Vec<IndexedTx>, bool), ServiceError> {
+    let client = rpc::client(rpc_url);
+    let _span = tracing::info_span!("rpc.tx_history", %rpc_url, %wallet).entered();
+    let signatures = |before: Option<Signature>, until: Option<Signature>, limit: usize| {
+        client
+            .get_signatures_for_address_with_config(
+                wallet,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit: Some(limit),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .map_err(rpc_error("Failed to get signatures"))
+    };
+
+    // Newer than the newest indexed (everything, for a wallet whose
+    // backfill found none), newest first. Only the oldest `batch` are
+    // taken, so the next poll carries on where this one stops.
+    let mut pending = Vec::new();
+    if newest.is_some() || complete {
+        let mut before = None;
+        for _ in 0..MAX_SIGNATURE_PAGES {
+            let page = signatures(before, newest, SIGNATURE_PAGE_SIZE)?;
+            let full = page.len() == SIGNATURE_PAGE_SIZE;
+            before = page
+                .last()
+                .and_then(|s| Signature::from_str(&s.signature).ok());
+            pending.extend(page);
+            if !full {
+                break;
+            }
+        }
+        let skip = pending.len().saturating_sub(batch);
+        pending.drain(..skip);
+    }
+
+    let mut complete = complete || indexed >= max_backfill;
+    let room = batch
+        .saturating_sub(pending.len())
+        .min(max_backfill.saturating_sub(indexed));
+    if !complete && room > 0 {
+        let page = signatures(oldest, None, room)?;
+        complete = page.len() < room || indexed + page.len() >= max_backfill;
+        pending.extend(page);
+    }
+
+    let mut transactions = Vec::with_capacity(pending.len());
+    for status in pending {
+        let tx = history::fetch_transaction(&client, &status.signature)?;
+        transactions.push(classify(wallet, status, &tx));
+    }
+    Ok((transactions, complete))
+}
+
+// Indexes the history of every watchlist wallet, every
+// `tx_history_poll_secs` while `tx_history` is on.
+pub async fn run(state: AppState) {
+    loop {
+        let config = state.config();
+        if config.tx_history {
+            for wallet in state.watchlists.wallets() {
+                let rpc_url = config.rpc_url.clone();
+                let cursor = state.tx_history.cursor(&wallet);
+                let (batch, max_backfill) =
+                    (config.tx_history_batch, config.tx_history_max_backfill);
+                let synced = rpc::spawn_blocking(move || {
+                    sync_wallet(&rpc_url, &wallet, cursor, batch, max_backfill)
+                })
+                .await;
+                match synced {
+                    Ok(Ok((transactions, complete))) => {
+                        state.tx_history.record(wallet, transactions, complete)
+                    }
+                    Ok(Err(e)) => {
+                        logging::warn(format_args!("Failed to index history of {}: {}", wallet, e))
+                    }
+                    Err(e) => {
+                        logging::warn(format_args!("Failed to index history of {}: {}", wallet, e))
+                    }
+                }
+            }
+        }
+        tasks::succeeded();
+        tokio::time::sleep(Duration::from_secs(config.tx_history_poll_secs)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{service, store::MemoryStore};
+    use solana_sdk::signature::{Keypair, Signer};
+
+    fn tx(wallet: &Pubkey, slot: u64, kind: TxKind, change: i64, memos: &[&str]) -> IndexedTx {
+        IndexedTx {
+            wallet: wallet.to_string(),
+            signature: Signature::new_unique().to_string(),
+            slot,
+            block_time: Some(slot as i64 * 10),
+            succeeded: true,
+            kind,
+            change_lamports: change,
+            fee_lamports: 0,
+            counterparty: None,
+            mints: Vec::new(),
+            memos: memos.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    fn filter(limit: usize) -> Filter {
+        Filter {
+            kind: None,
+            min_lamports: None,
+            since: None,
+            until: None,
+            before: None,
+            limit,
+        }
+    }
+
+    fn slots(page: &WalletTransactions) -> Vec<u64> {
+        page.transactions.iter().map(|t| t.slot).collect()
+    }
+
+    #[test]
+    fn history_is_saved_and_filtered() {
+        let store = Arc::new(MemoryStore::default());
+        let history = TxHistory::load(store.clone()).unwrap();
+        let wallet = Pubkey::new_unique();
+        let txs = vec![
+            tx(&wallet, 1, TxKind::Transfer, -500, &[]),
+            tx(&wallet, 2, TxKind::Token, 0, &[]),
+            tx(&wallet, 3, TxKind::Transfer, 2_000, &[]),
+            tx(&wallet, 4, TxKind::Other, 0, &[]),
+        ];
+        history.record(wallet, txs.clone(), false);
+        // Already indexed transactions aren't saved twice.
+        history.record(wallet, txs[..1].to_vec(), true);
+        assert_eq!(store.read(STORE_NAME).unwrap().unwrap().lines().count(), 5);
+        assert!(history.query(&Pubkey::new_unique(), &filter(10)).is_none());
+
+        let history = TxHistory::load(store).unwrap();
+        let all = history.query(&wallet, &filter(10)).unwrap();
+        assert_eq!(slots(&all), [4, 3, 2, 1]);
+        assert_eq!((all.indexed, all.backfill_complete), (4, true));
+        assert_eq!(all.next_before, None);
+
+        let transfers = Filter {
+            kind: Some(TxKind::Transfer),
+            ..filter(10)
+        };
+        assert_eq!(slots(&history.query(&wallet, &transfers).unwrap()), [3, 1]);
+        let large = Filter {
+            min_lamports: Some(500),
+            ..filter(10)
+        };
+        assert_eq!(slots(&history.query(&wallet, &large).unwrap()), [3, 1]);
+        let window = Filter {
+            since: Some(20),
+            until: Some(30),
+            ..filter(10)
+        };
+        assert_eq!(slots(&history.query(&wallet, &window).unwrap()), [3, 2]);
+
+        let first = history.query(&wallet, &filter(3)).unwrap();
+        assert_eq!(slots(&first), [4, 3, 2]);
+        assert_eq!(first.next_before, Some(txs[1].signature.clone()));
+        let rest = Filter {
+            before: first.next_before,
+            ..filter(3)
+        };
+        let rest = history.query(&wallet, &rest).unwrap();
+        assert_eq!((slots(&rest), rest.next_before), (vec![1], None));
+    }
+
+    #[test]
+    fn memo_search_needs_every_word() {
+        let history = TxHistory::load(Arc::new(MemoryStore::default())).unwrap();
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let shared = tx(&a, 5, TxKind::Transfer, 1, &["Invoice 42", "paid"]);
+        let mut seen_by_b = shared.clone();
+        seen_by_b.wallet = b.to_string();
+        history.record(
+            a,
+            vec![shared.clone(), tx(&a, 9, TxKind::Other, 0, &["invoice 43"])],
+            false,
+        );
+        history.record(b, vec![seen_by_b], false);
+
+        let found = history.search_memos("INVOICE", 10);
+        let found_slots: Vec<u64> = found.iter().map(|m| m.slot).collect();
+        assert_eq!(found_slots, [9, 5]);
+        let mut wallets = vec![a.to_string(), b.to_string()];
+        wallets.sort();
+        assert_eq!(found[1].wallets, wallets);
+
+        let found = history.search_memos("42 paid", 10);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].signature, shared.signature);
+        assert!(history.search_memos("invoice 44", 10).is_empty());
+        assert!(history.search_memos("  ", 10).is_empty());
+        assert_eq!(history.search_memos("invoice", 1).len(), 1);
+    }
+
+    #[test]
+    fn wallets_sync_new_then_older_history() {
+        let chain = rpc::mock_for_tests();
+        let payer = Keypair::new();
+        let wallet = Pubkey::new_unique();
+        chain.set_balance(payer.pubkey(), 10_000_000_000);
+        let send = |lamports, memo: Option<&str>| {
+            match memo {
+                Some(memo) => service::transfer_with_memo(
+                    "http://mock",
+                    &payer,
+                    &wallet,
+                    lamports,
+                    memo,
+                    true,
+                ),
+                None => service::transfer("http://mock", &payer, &wallet, lamports, true),
+            }
+            .unwrap()
+        };
+        send(1_000, None);
+        send(2_000, Some("rent for May"));
+        send(3_000, None);
+
+        let history = TxHistory::load(Arc::new(MemoryStore::default())).unwrap();
+        let sync = |batch| {
+            let (txs, complete) =
+                sync_wallet("http://mock", &wallet, history.cursor(&wallet), batch, 10).unwrap();
+            let amounts: Vec<i64> = txs.iter().map(|t| t.change_lamports).collect();
+            history.record(wallet, txs, complete);
+            (amounts, complete)
+        };
+
+        // The backfill starts from the newest and walks back.
+        assert_eq!(sync(2), (vec![3_000, 2_000], false));
+        assert_eq!(sync(2), (vec![1_000], true));
+        assert_eq!(sync(2), (vec![], true));
+        send(4_000, None);
+        assert_eq!(sync(2), (vec![4_000], true));
+
+        let all = history.query(&wallet, &filter(10)).unwrap();
+        let received = &all.transactions[2];
+        assert_eq!(received.kind, TxKind::Transfer);
+        assert_eq!(received.counterparty, Some(payer.pubkey().to_string()));
+        assert_eq!(received.fee_lamports, 0);
+        assert_eq!(received.memos, ["rent for May"]);
+        assert_eq!(history.search_memos("may", 10).len(), 1);
+
+        // The payer's view: the amount sent, the fee apart.
+        let (txs, _) = sync_wallet(
+            "http://mock",
+            &payer.pubkey(),
+            (None, None, false, 0),
+            1,
+            10,
+        )
+        .unwrap();
+        assert_eq!(txs[0].change_lamports, -4_000);
+        assert_eq!(txs[0].fee_lamports, 5_000);
+        assert_eq!(txs[0].counterparty, Some(wallet.to_string()));
+    }
+}
@@ -0,0 +1,492 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{
+    auth::Principal,
+    clock::now_ms,
+    config::Config,
+    handlers::service_error,
+    logging,
+    orgs::{self, Org},
+    rpc,
+    service::ServiceError,
+    state::AppState,
+    store::Store,
+    tasks,
+};
+
+pub use crate::api::{QuotaReport, RouteRpcReport, RpcBudgetReport, UsageReport};
+
+const DAY_MS: u64 = 86_400_000;
+// Anonymous callers get a subject per IP, so the table is capped and the
+// least recently seen subject is dropped first.
+const MAX_TRACKED_SUBJECTS: usize = 10_000;
+const STORE_NAME: &str = "quotas";
+// Request and RPC counters are saved this often; airdrops, which count
+// towards quotas, are saved as they happen.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+// Who an airdrop's quota is drawn from: the caller's credential or, for
+// anonymous callers, their IP, as `ratelimit` keys them. Leaving a key off
+// doesn't escape its quota, only trades it for the IP's.
+pub fn quota_subject(subject: Option<&str>, ip: Option<&str>) -> String {
+    match subject {
+        Some(subject) => subject.to_string(),
+        None => format!("ip:{}", ip.unwrap_or("unknown")),
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Usage {
+    requests: u64,
+    errors: u64,
+    airdrops: u64,
+    airdropped_lamports: u64,
+    // UTC day number that `day_airdropped_lamports` counts towards.
+    day: u64,
+    day_airdropped_lamports: u64,
+    rpc_calls: u64,
+    day_rpc_calls: u64,
+    last_seen_ms: u64,
+}
+
+#[derive(Default)]
+struct RouteUsage {
+    requests: u64,
+    rpc_calls: u64,
+}
+
+// RPC calls made while handling the current request, shared between the
+// app-level counter and the per-group `track` layer.
+#[derive(Clone)]
+struct RequestRpcCalls(Arc<AtomicU64>);
+
+// Per-credential consumption, keyed by the authenticated principal's
+// subject (JWT `sub`, org API key or `admin_token`), plus one total per
+// org. Anonymous requests are not tracked, but anonymous airdrops count
+// towards a quota per client IP (see `quota_subject`). Saved as JSON under
+// "quotas" in the store (`quotas_path` for the file backend), so daily
+// quotas and RPC budgets hold across restarts; per-route counts are not.
+pub struct UsageTracker {
+    store: Arc<dyn Store>,
+    subjects: Mutex<HashMap<String, Usage>>,
+    routes: Mutex<HashMap<String, RouteUsage>>,
+    // Counters changed since the last save.
+    dirty: AtomicBool,
+}
+
+impl UsageTracker {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let subjects = match store.read(STORE_NAME)? {
+            Some(text) => serde_json::from_str(&text)
+                .map_err(|e| format!("invalid quotas in {}: {}", store.location(STORE_NAME), e))?,
+            None => HashMap::new(),
+        };
+        Ok(UsageTracker {
+            store,
+            subjects: Mutex::new(subjects),
+            routes: Mutex::new(HashMap::new()),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if !self.store.persists(STORE_NAME) || !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let text =
+            serde_json::to_string(&*self.subjects.lock().unwrap()).map_err(|e| e.to_string())?;
+        self.store.write(STORE_NAME, &text).inspect_err(|_| {
+            self.dirty.store(true, Ordering::Relaxed);
+        })
+    }
+
+    fn save_now(&self) {
+        if let Err(e) = self.save() {
+            logging::warn(format_args!("Failed to save quotas: {}", e));
+        }
+    }
+
+    fn with_usage<T>(&self, subject: &str, f: impl FnOnce(&mut Usage, u64) -> T) -> T {
+        let now = now_ms();
+        let mut subjects = self.subjects.lock().unwrap();
+        if !subjects.contains_key(subject) && subjects.len() >= MAX_TRACKED_SUBJECTS {
+            let oldest = subjects
+                .iter()
+                .min_by_key(|(_, u)| u.last_seen_ms)
+                .map(|(s, _)| s.clone());
+            if let Some(oldest) = oldest {
+                subjects.remove(&oldest);
+            }
+        }
+        let usage = subjects.entry(subject.to_string()).or_default();
+        let today = now / DAY_MS;
+        if usage.day != today {
+            usage.day = today;
+            usage.day_airdropped_lamports = 0;
+            usage.day_rpc_calls = 0;
+        }
+        usage.last_seen_ms = now;
+        self.dirty.store(true, Ordering::Relaxed);
+        f(usage, today)
+    }
+
+    pub fn record_request(&self, subject: &str, error: bool) {
+        self.with_usage(subject, |usage, _| {
+            usage.requests += 1;
+            if error {
+                usage.errors += 1;
+            }
+        });
+    }
+
+    // Counts `lamports` against today's quota up front so concurrent
+    // requests can't overshoot it. Call `release_airdrop` if the airdrop
+    // then fails.
+    pub fn reserve_airdrop(
+        &self,
+        subject: Option<&str>,
+        lamports: u64,
+        daily_quota_sol: Option<u64>,
+    ) -> Result<(), ServiceError> {
+        let Some(subject) = subject else {
+            return Ok(());
+        };
+        self.with_usage(subject, |usage, _| {
+            if let Some(quota) = daily_quota_sol {
+                let remaining = quota
+                    .saturating_mul(LAMPORTS_PER_SOL)
+                    .saturating_sub(usage.day_airdropped_lamports);
+                if lamports > remaining {
+                    return Err(ServiceError::QuotaExceeded {
+                        remaining_lamports: remaining,
+                    });
+                }
+            }
+            usage.airdrops += 1;
+            usage.airdropped_lamports = usage.airdropped_lamports.saturating_add(lamports);
+            usage.day_airdropped_lamports = usage.day_airdropped_lamports.saturating_add(lamports);
+            Ok(())
+        })?;
+        self.save_now();
+        Ok(())
+    }
+
+    pub fn release_airdrop(&self, subject: Option<&str>, lamports: u64) {
+        let Some(subject) = subject else {
+            return;
+        };
+        self.with_usage(subject, |usage, _| {
+            usage.airdrops = usage.airdrops.saturating_sub(1);
+            usage.airdropped_lamports = usage.airdropped_lamports.saturating_sub(lamports);
+            usage.day_airdropped_lamports = usage.day_airdropped_lamports.saturating_sub(lamports);
+        });
+        self.save_now();
+    }
+
+    // Refuses the request once today's RPC budget is used up. The calls a
+    // request makes are only known afterwards, so the last request let
+    // through can overshoot the budget.
+    fn check_rpc_budget(&self, subject: &str, budget: Option<u64>) -> Result<(), ServiceError> {
+        let Some(budget) = budget else {
+            return Ok(());
+        };
+        self.with_usage(subject, |usage, _| {
+            if usage.day_rpc_calls >= budget {
+                return Err(ServiceError::RpcBudgetExceeded { budget });
+            }
+            Ok(())
+        })
+    }
+
+    // Returns the calls made today.
+    fn record_rpc_calls(&self, subject: &str, calls: u64) -> u64 {
+        self.with_usage(subject, |usage, _| {
+            usage.rpc_calls += calls;
+            usage.day_rpc_calls += calls;
+            usage.day_rpc_calls
+        })
+    }
+
+    fn record_route(&self, route: &str, calls: u64) {
+        let mut routes = self.routes.lock().unwrap();
+        let usage = routes.entry(route.to_string()).or_default();
+        usage.requests += 1;
+        usage.rpc_calls += calls;
+    }
+
+    pub fn report(&self, subject: &str, config: &Config) -> UsageReport {
+        self.with_usage(subject, |usage, today| {
+            usage_report(
+                subject,
+                usage,
+                today,
+                config.airdrop_daily_quota_sol,
+                config,
+            )
+        })
+    }
+
+    // Totals of all an org's keys, against the org's own airdrop quota.
+    pub fn org_report(&self, org: &Org, config: &Config) -> UsageReport {
+        let subject = orgs::usage_subject(&org.id);
+        self.with_usage(&subject, |usage, today| {
+            usage_report(&subject, usage, today, org.airdrop_daily_quota_sol, config)
+        })
+    }
+
+    pub fn report_all(&self, config: &Config) -> Vec<UsageReport> {
+        let today = now_ms() / DAY_MS;
+        let subjects = self.subjects.lock().unwrap();
+        let mut reports: Vec<UsageReport> = subjects
+            .iter()
+            // Org totals have their own quota and are under /admin/orgs.
+            .filter(|(subject, _)| !orgs::is_usage_subject(subject))
+            .map(|(subject, usage)| {
+                usage_report(
+                    subject,
+                    usage,
+                    today,
+                    config.airdrop_daily_quota_sol,
+                    config,
+                )
+            })
+            .collect();
+        reports.sort_by(|a, b| a.subject.cmp(&b.subject));
+        reports
+    }
+
+    pub fn route_report(&self) -> Vec<RouteRpcReport> {
+        let routes = self.routes.lock().unwrap();
+        let mut reports: Vec<RouteRpcReport> = routes
+            .iter()
+            .map(|(route, usage)| RouteRpcReport {
+                route: route.clone(),
+                requests: usage.requests,
+                rpc_calls: usage.rpc_calls,
+                rpc_calls_per_request: usage.rpc_calls as f64 / usage.requests.max(1) as f64,
+            })
+            .collect();
+        reports.sort_by(|a, b| a.route.cmp(&b.route));
+        reports
+    }
+}
+
+fn usage_report(
+    subject: &str,
+    usage: &Usage,
+    today: u64,
+    daily_quota_sol: Option<u64>,
+    config: &Config,
+) -> UsageReport {
+    let (used_today_lamports, rpc_calls_today) = if usage.day == today {
+        (usage.day_airdropped_lamports, usage.day_rpc_calls)
+    } else {
+        (0, 0)
+    };
+    let remaining_today_lamports = daily_quota_sol.map(|q| {
+        q.saturating_mul(LAMPORTS_PER_SOL)
+            .saturating_sub(used_today_lamports)
+    });
+    let rpc_budget = if orgs::is_usage_subject(subject) {
+        config.org_rpc_budget(subject)
+    } else {
+        config.rpc_budget(subject)
+    };
+    UsageReport {
+        subject: subject.to_string(),
+        requests: usage.requests,
+        errors: usage.errors,
+        error_rate: if usage.requests == 0 {
+            0.0
+        } else {
+            usage.errors as f64 / usage.requests as f64
+        },
+        airdrops: usage.airdrops,
+        airdropped_sol: usage.airdropped_lamports / LAMPORTS_PER_SOL,
+        airdropped_lamports: usage.airdropped_lamports,
+        last_seen_ms: usage.last_seen_ms,
+        quota: QuotaReport {
+            daily_airdrop_sol: daily_quota_sol,
+            used_today_sol: used_today_lamports / LAMPORTS_PER_SOL,
+            used_today_lamports,
+            remaining_today_sol: remaining_today_lamports.map(|r| r / LAMPORTS_PER_SOL),
+            remaining_today_lamports,
+            resets_at_ms: (today + 1) * DAY_MS,
+        },
+        rpc_calls: usage.rpc_calls,
+        rpc_budget: RpcBudgetReport {
+            daily_calls: rpc_budget,
+            used_today: rpc_calls_today,
+            remaining_today: rpc_budget.map(|b| b.saturating_sub(rpc_calls_today)),
+        },
+    }
+}
+
+// Counts the RPC calls every routed request makes, per route, and reports
+// them in an `x-rpc-calls` response header.
+pub async fn count_rpc(
+    State(state): State<AppState>,
+    matched: Option<MatchedPath>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let calls = Arc::new(AtomicU64::new(0));
+    req.extensions_mut().insert(RequestRpcCalls(calls.clone()));
+    let mut res = rpc::counting(calls.clone(), next.run(req)).await;
+
+    let calls = calls.load(Ordering::Relaxed);
+    if let Some(route) = matched {
+        state.usage.record_route(route.as_str(), calls);
+    }
+    res.headers_mut()
+        .insert("x-rpc-calls", HeaderValue::from(calls));
+    res
+}
+
+// Layered inside each route group's auth guard so the Principal it
+// inserted is visible here. Requests made with an org's API key count
+// towards the key and towards the org.
+pub async fn track(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let principal = req.extensions().get::<Principal>();
+    let subject = principal.and_then(|p| p.subject.clone());
+    let org = principal
+        .and_then(|p| p.org.as_deref())
+        .map(orgs::usage_subject);
+    let calls = req.extensions().get::<RequestRpcCalls>().cloned();
+    let Some(subject) = subject else {
+        return next.run(req).await;
+    };
+
+    let mut res = next.run(req).await;
+    let status = res.status();
+    let error = status.is_client_error() || status.is_server_error();
+    state.usage.record_request(&subject, error);
+    if let Some(org) = &org {
+        state.usage.record_request(org, error);
+    }
+    if let Some(RequestRpcCalls(calls)) = calls {
+        let calls = calls.load(Ordering::Relaxed);
+        let today = state.usage.record_rpc_calls(&subject, calls);
+        if let Some(org) = &org {
+            state.usage.record_rpc_calls(org, calls);
+        }
+        let headers = res.headers_mut();
+        headers.insert("x-rpc-calls-today", HeaderValue::from(today));
+        if let Some(budget) = state.config().rpc_budget(&subject) {
+            headers.insert(
+                "x-rpc-budget-remaining",
+                HeaderValue::from(budget.saturating_sub(today)),
+            );
+        }
+    }
+    res
+}
+
+// Layered on the consumer-facing groups (reads and airdrops), inside their
+// auth guard and the `track` layer; admin routes stay reachable.
+pub async fn enforce_rpc_budget(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(principal) = req.extensions().get::<Principal>() else {
+        return next.run(req).await;
+    };
+    let config = state.config();
+    let mut checks = Vec::new();
+    if let Some(subject) = &principal.subject {
+        checks.push((subject.clone(), config.rpc_budget(subject)));
+    }
+    if let Some(org) = &principal.org {
+        let subject = orgs::usage_subject(org);
+        let budget = config.org_rpc_budget(&subject);
+        checks.push((subject, budget));
+    }
+    let result = checks
+        .iter()
+        .try_for_each(|(subject, budget)| state.usage.check_rpc_budget(subject, *budget));
+    match result {
+        Ok(()) => next.run(req).await,
+        Err(e) => service_error(e).into_response(),
+    }
+}
+
+// Saves request and RPC counters changed since the last save.
+pub async fn persist(state: AppState) {
+    loop {
+        tokio::time::sleep(SAVE_INTERVAL).await;
+        match state.usage.save() {
+            Ok(()) => tasks::succeeded(),
+            Err(e) => logging::warn(format_args!("Failed to save quotas: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    #[test]
+    fn quotas_survive_a_reload() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        let usage = UsageTracker::load(store.clone()).unwrap();
+        usage
+            .reserve_airdrop(Some("alice"), 2 * LAMPORTS_PER_SOL, Some(3))
+            .unwrap();
+        usage.record_rpc_calls("alice", 5);
+        usage.save().unwrap();
+
+        let reloaded = UsageTracker::load(store).unwrap();
+        let err = reloaded
+            .reserve_airdrop(Some("alice"), 2 * LAMPORTS_PER_SOL, Some(3))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ServiceError::QuotaExceeded { remaining_lamports } if remaining_lamports == LAMPORTS_PER_SOL
+        ));
+        assert_eq!(reloaded.record_rpc_calls("alice", 1), 6);
+        // Route counts are not saved.
+        usage.record_route("/balance/{wallet}", 1);
+        assert!(reloaded.routes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn anonymous_airdrops_share_a_quota_per_ip() {
+        let usage = UsageTracker::load(Arc::new(MemoryStore::default())).unwrap();
+        let anonymous = quota_subject(None, Some("203.0.113.7"));
+        assert_eq!(anonymous, "ip:203.0.113.7");
+        assert_eq!(quota_subject(Some("key:1"), Some("203.0.113.7")), "key:1");
+        usage
+            .reserve_airdrop(Some(&anonymous), LAMPORTS_PER_SOL, Some(1))
+            .unwrap();
+        assert!(matches!(
+            usage.reserve_airdrop(Some(&anonymous), 1, Some(1)),
+            Err(ServiceError::QuotaExceeded {
+                remaining_lamports: 0
+            })
+        ));
+        let other = quota_subject(None, Some("198.51.100.1"));
+        usage
+            .reserve_airdrop(Some(&other), LAMPORTS_PER_SOL, Some(1))
+            .unwrap();
+        usage.release_airdrop(Some(&anonymous), LAMPORTS_PER_SOL);
+        usage.reserve_airdrop(Some(&anonymous), 1, Some(1)).unwrap();
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    path::PathBuf,
+    process::Stdio,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::process::{Child, Command};
+
+use crate::{
+    clock::now_ms,
+    config::{Backend, Config},
+    logging, rpc,
+    state::AppState,
+};
+
+pub use crate::api::ValidatorStatus;
+
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+struct Running {
+    child: Child,
+    rpc_url: String,
+    ledger_dir: PathBuf,
+    started_at_ms: u64,
+}
+
+// A `solana-test-validator` child process for local development. While it
+// runs, the server's rpc_url points at it; stopping it restores the
+// configured URL. The child is killed when the manager is dropped, but not
+// if the server process itself is killed.
+#[derive(Default)]
+pub struct ValidatorManager {
+    running: tokio::sync::Mutex<Option<Running>>,
+    // (validator URL, configured URL to restore on stop)
+    pinned: Mutex<Option<(String, String)>>,
+}
+
+fn status(running: Option<&Running>) -> ValidatorStatus {
+    ValidatorStatus {
+        running: running.is_some(),
+        pid: running.and_then(|r| r.child.id()),
+        rpc_url: running.map(|r| r.rpc_url.clone()),
+        ledger_dir: running.map(|r| r.ledger_dir.display().to_string()),
+        started_at_ms: running.map(|r| r.started_at_ms),
+    }
+}
+
+fn command(config: &Config, reset: bool) -> Command {
+    let mut cmd = Command::new(&config.validator_bin);
+    cmd.arg("--ledger")
+        .arg(&config.validator_ledger_dir)
+        .arg("--rpc-port")
+        .arg(config.validator_rpc_port.to_string())
+        .arg("--quiet");
+    if reset {
+        cmd.arg("--reset");
+    }
+    for program in &config.validator_programs {
+        cmd.arg("--bpf-program")
+            .arg(&program.address)
+            .arg(&program.path);
+    }
+    for account in &config.validator_accounts {
+        cmd.arg("--account")
+            .arg(&account.address)
+            .arg(&account.path);
+    }
+    if let Some(dir) = config
+        .validator_fixtures_dir
+        .as_ref()
+        .filter(|d| d.is_dir())
+    {
+        cmd.arg("--account-dir").arg(dir);
+    }
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    cmd
+}
+
+async fn wait_until_healthy(
+    child: &mut Child,
+    rpc_url: &str,
+    timeout: Duration,
+) -> Result<(), String> {
+    let started = Instant::now();
+    loop {
+        if let Some(exit) = child.try_wait().map_err(|e| e.to_string())? {
+            return Err(format!("validator exited during startup ({})", exit));
+        }
+        let url = rpc_url.to_string();
+        let healthy = tokio::task::spawn_blocking(move || rpc::client(&url).get_health().is_ok())
+            .await
+            .unwrap_or(false);
+        if healthy {
+            return Ok(());
+        }
+        if started.elapsed() > timeout {
+            return Err(format!(
+                "validator did not become healthy within {}s",
+                timeout.as_secs()
+            ));
+        }
+        tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+    }
+}
+
+impl ValidatorManager {
+    pub async fn status(&self) -> ValidatorStatus {
+        status(self.running.lock().await.as_ref())
+    }
+
+    // `reset` wipes the ledger so the validator starts from genesis with
+    // only the configured programs and accounts.
+    pub async fn start(&self, state: &AppState, reset: bool) -> Result<ValidatorStatus, String> {
+        let config = state.config();
+        if config.backend == Backend::Mock || config.replay_dir.is_some() {
+            return Err(
+                "a local validator can't be used with the mock backend or replay".to_string(),
+            );
+        }
+
+        let mut running = self.running.lock().await;
+        if running.is_some() {
+            return Err("validator is already running".to_string());
+        }
+
+        let mut child = command(&config, reset)
+            .spawn()
+            .map_err(|e| format!("failed to start '{}': {}", config.validator_bin, e))?;
+        let rpc_url = format!("http://127.0.0.1:{}", config.validator_rpc_port);
+        let timeout = Duration::from_secs(config.validator_startup_timeout_secs);
+        if let Err(e) = wait_until_healthy(&mut child, &rpc_url, timeout).await {
+            let _ = child.kill().await;
+            return Err(e);
+        }
+
+        *self.pinned.lock().unwrap() = Some((rpc_url.clone(), config.rpc_url.clone()));
+        state.set_rpc_url(rpc_url.clone());
+        logging::info(format_args!("local validator started at {}", rpc_url));
+
+        *running = Some(Running {
+            child,
+            rpc_url,
+            ledger_dir: config.validator_ledger_dir.clone(),
+            started_at_ms: now_ms(),
+        });
+        Ok(status(running.as_ref()))
+    }
+
+    pub async fn stop(&self, state: &AppState) -> Result<ValidatorStatus, String> {
+        let mut running = self.running.lock().await;
+        let Some(mut validator) = running.take() else {
+            return Err("validator is not running".to_string());
+        };
+        validator
+            .child
+            .kill()
+            .await
+            .map_err(|e| format!("failed to stop validator: {}", e))?;
+
+        if let Some((_, configured)) = self.pinned.lock().unwrap().take() {
+            state.set_rpc_url(configured);
+        }
+        logging::info("local validator stopped");
+        Ok(status(None))
+    }
+
+    pub async fn reset(&self, state: &AppState) -> Result<ValidatorStatus, String> {
+        if self.running.lock().await.is_some() {
+            self.stop(state).await?;
+        }
+        self.start(state, true).await
+    }
+
+    // While the validator runs it owns rpc_url. A reloaded value is kept
+    // and restored when the validator stops.
+    pub fn pin_rpc_url(&self, rpc_url: &mut String) {
+        if let Some((validator, configured)) = self.pinned.lock().unwrap().as_mut() {
+            *configured = std::mem::replace(rpc_url, validator.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ValidatorPreload;
+
+    fn args(cmd: &Command) -> Vec<String> {
+        cmd.as_std()
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn command_loads_programs_and_accounts() {
+        let config = Config {
+            validator_ledger_dir: PathBuf::from("/tmp/ledger"),
+            validator_rpc_port: 9899,
+            validator_programs: vec![ValidatorPreload {
+                address: "Prog111".into(),
+                path: PathBuf::from("prog.so"),
+            }],
+            validator_accounts: vec![ValidatorPreload {
+                address: "Acct111".into(),
+                path: PathBuf::from("acct.json"),
+            }],
+            validator_fixtures_dir: Some(PathBuf::from("/definitely/not/here")),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            args(&command(&config, false)),
+            [
+                "--ledger",
+                "/tmp/ledger",
+                "--rpc-port",
+                "9899",
+                "--quiet",
+                "--bpf-program",
+                "Prog111",
+                "prog.so",
+                "--account",
+                "Acct111",
+                "acct.json",
+            ]
+        );
+        assert!(args(&command(&config, true)).contains(&"--reset".to_string()));
+    }
+
+    #[test]
+    fn command_only_passes_an_existing_fixtures_dir() {
+        let dir = std::env::temp_dir();
+        let config = Config {
+            validator_fixtures_dir: Some(dir.clone()),
+            ..Config::default()
+        };
+        let args = args(&command(&config, false));
+        assert_eq!(
+            args[args.len() - 2..],
+            ["--account-dir".to_string(), dir.display().to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn start_is_refused_on_the_mock_backend() {
+        let state = AppState::for_tests(Config {
+            backend: Backend::Mock,
+            ..Config::default()
+        });
+        let manager = ValidatorManager::default();
+        let err = manager.start(&state, false).await.unwrap_err();
+        assert!(err.contains("mock backend"), "{}", err);
+        assert!(!manager.status().await.running);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn start_pins_rpc_url_until_stop() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // The mock transport answers getHealth, so any long-lived child
+        // passes the startup check.
+        let bin = std::env::temp_dir().join(format!("fake-validator-{}", std::process::id()));
+        std::fs::write(&bin, "#!/bin/sh\nexec sleep 30\n").unwrap();
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let configured = Config::default().rpc_url;
+        let state = AppState::for_tests(Config {
+            validator_bin: bin.display().to_string(),
+            validator_rpc_port: 9899,
+            ..Config::default()
+        });
+        let manager = ValidatorManager::default();
+
+        let status = manager.start(&state, true).await.unwrap();
+        assert!(status.running && status.pid.is_some());
+        assert_eq!(status.rpc_url.as_deref(), Some("http://127.0.0.1:9899"));
+        assert_eq!(state.config().rpc_url, "http://127.0.0.1:9899");
+        assert_eq!(
+            manager.start(&state, false).await.unwrap_err(),
+            "validator is already running"
+        );
+
+        assert!(!manager.stop(&state).await.unwrap().running);
+        assert_eq!(state.config().rpc_url, configured);
+        std::fs::remove_file(&bin).unwrap();
+    }
+
+    #[tokio::test]
+    async fn start_reports_a_missing_binary() {
+        let state = AppState::for_tests(Config {
+            validator_bin: "/definitely/not/a/validator".into(),
+            ..Config::default()
+        });
+        let manager = ValidatorManager::default();
+        let err = manager.start(&state, false).await.unwrap_err();
+        assert!(err.starts_with("failed to start"), "{}", err);
+        assert_eq!(state.config().rpc_url, Config::default().rpc_url);
+    }
+
+    #[tokio::test]
+    async fn stop_requires_a_running_validator() {
+        let state = AppState::for_tests(Config::default());
+        let manager = ValidatorManager::default();
+        assert_eq!(
+            manager.stop(&state).await.unwrap_err(),
+            "validator is not running"
+        );
+    }
+
+    #[test]
+    fn pinned_rpc_url_survives_a_reload() {
+        let manager = ValidatorManager::default();
+        let mut url = "https://reloaded".to_string();
+        manager.pin_rpc_url(&mut url);
+        assert_eq!(url, "https://reloaded");
+
+        *manager.pinned.lock().unwrap() =
+            Some(("http://127.0.0.1:8899".into(), "https://configured".into()));
+        manager.pin_rpc_url(&mut url);
+        assert_eq!(url, "http://127.0.0.1:8899");
+        assert_eq!(
+            manager.pinned.lock().unwrap().as_ref().unwrap().1,
+            "https://reloaded"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    clock,
+    config::{Backend, Config},
+    frontend, pool,
+};
+
+pub use crate::api::BuildInfo;
+
+// What `--version` prints; `-V` prints the version alone.
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (commit ",
+    env!("BUILD_GIT_COMMIT"),
+    ", solana-sdk ",
+    env!("BUILD_SOLANA_SDK_VERSION"),
+    ")"
+);
+
+pub fn info() -> BuildInfo {
+    let built_at = env!("BUILD_TIMESTAMP").parse::<u64>().unwrap_or_default();
+    BuildInfo {
+        service: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_commit: env!("BUILD_GIT_COMMIT").to_string(),
+        git_dirty: env!("BUILD_GIT_DIRTY") == "true",
+        built_at: clock::rfc3339(built_at * 1_000),
+        features: env!("BUILD_FEATURES")
+            .split(',')
+            .filter(|f| !f.is_empty())
+            .map(str::to_string)
+            .collect(),
+        solana_sdk: env!("BUILD_SOLANA_SDK_VERSION").to_string(),
+        rustc: env!("BUILD_RUSTC_VERSION").to_string(),
+    }
+}
+
+// Printed when the server starts, so logs show what ran against what.
+pub fn banner(config: &Config) -> String {
+    let info = info();
+    let commit: String = info.git_commit.chars().take(12).collect();
+    let features = if info.features.is_empty() {
+        "none".to_string()
+    } else {
+        info.features.join(", ")
+    };
+    let cluster = match config.backend {
+        Backend::Mock => frontend::cluster_name(config),
+        _ => format!(
+            "{} via {}",
+            frontend::cluster_name(config),
+            pool::redact(&config.rpc_url)
+        ),
+    };
+    format!(
+        "{} {} (commit {}{}, built {})\n  \
+         solana-sdk {}, {}, features: {}\n  \
+         cluster: {}",
+        info.service,
+        info.version,
+        commit,
+        if info.git_dirty { "-dirty" } else { "" },
+        info.built_at,
+        info.solana_sdk,
+        info.rustc,
+        features,
+        cluster,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_matches_the_build() {
+        let info = info();
+        assert_eq!(info.service, "solana-axum-server");
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_commit.is_empty());
+        assert!(info.built_at.ends_with('Z'), "{}", info.built_at);
+        assert!(info.rustc.starts_with("rustc ") || info.rustc == "unknown");
+        // From the solana-sdk entry in Cargo.lock.
+        assert_eq!(info.solana_sdk, "1.18.26");
+        assert!(LONG_VERSION.starts_with(&format!("{} (commit ", info.version)));
+        assert!(LONG_VERSION.ends_with(", solana-sdk 1.18.26)"));
+    }
+
+    #[test]
+    fn features_are_the_enabled_ones() {
+        let features = info().features;
+        for (feature, enabled) in [
+            ("sqlite", cfg!(feature = "sqlite")),
+            ("postgres", cfg!(feature = "postgres")),
+            ("profiling", cfg!(feature = "profiling")),
+            ("sentry", cfg!(feature = "sentry")),
+            ("otel", cfg!(feature = "otel")),
+            ("telegram", cfg!(feature = "telegram")),
+        ] {
+            assert_eq!(
+                features.iter().any(|f| f == feature),
+                enabled,
+                "{}",
+                feature
+            );
+        }
+        let mut sorted = features.clone();
+        sorted.sort();
+        assert_eq!(features, sorted);
+    }
+
+    #[test]
+    fn banner_names_the_cluster() {
+        let rpc = banner(&Config {
+            backend: Backend::Rpc,
+            rpc_url: "https://api.devnet.solana.com/?api-key=secret".to_string(),
+            ..Config::default()
+        });
+        let lines: Vec<&str> = rpc.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with(&format!(
+            "solana-axum-server {} (commit ",
+            env!("CARGO_PKG_VERSION")
+        )));
+        assert!(lines[1].starts_with("  solana-sdk 1.18.26, "));
+        assert_eq!(
+            lines[2],
+            "  cluster: devnet via https://api.devnet.solana.com"
+        );
+
+        let mock = banner(&Config {
+            backend: Backend::Mock,
+            ..Config::default()
+        });
+        assert!(mock.ends_with("\n  cluster: mock"), "{}", mock);
+    }
+}
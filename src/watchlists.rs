@@ -0,0 +1,388 @@
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{
+    amount,
+    clock::now_ms,
+    config::Config,
+    diff::{self, TokenHolding},
+    fanout::{self, Limits},
+    links, rpc,
+    service::{self, rpc_error, ServiceError},
+    store::Store,
+};
+
+pub use crate::api::{Activity, Member, Overview, TokenTotal, Watchlist};
+
+const STORE_NAME: &str = "watchlists";
+const MAX_WALLETS: usize = 100;
+const MAX_WATCHLISTS_PER_OWNER: usize = 20;
+const MAX_NAME_LENGTH: usize = 64;
+const ACTIVITY_PER_WALLET: usize = 10;
+const RECENT_ACTIVITY: usize = 20;
+
+// Named wallet groups, saved as JSON under "watchlists" in the store
+// (`watchlists_path` for files).
+pub struct WatchlistStore {
+    store: Arc<dyn Store>,
+    // Ids aren't reused after a delete, so links to a deleted list 404.
+    next_id: AtomicU64,
+    entries: Mutex<BTreeMap<u64, Watchlist>>,
+}
+
+impl WatchlistStore {
+    pub fn load(store: Arc<dyn Store>) -> Result<Self, String> {
+        let mut entries = BTreeMap::new();
+        if let Some(text) = store.read(STORE_NAME)? {
+            let watchlists: Vec<Watchlist> = serde_json::from_str(&text).map_err(|e| {
+                format!(
+                    "invalid watchlists in {}: {}",
+                    store.location(STORE_NAME),
+                    e
+                )
+            })?;
+            for watchlist in watchlists {
+                entries.insert(watchlist.id, watchlist);
+            }
+        }
+        Ok(WatchlistStore {
+            store,
+            next_id: AtomicU64::new(entries.keys().next_back().copied().unwrap_or_default()),
+            entries: Mutex::new(entries),
+        })
+    }
+
+    // Validates the name and wallets, dropping duplicate wallets.
+    pub fn create(
+        &self,
+        owner: &str,
+        name: &str,
+        wallets: &[String],
+    ) -> Result<Watchlist, ServiceError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            return Err(ServiceError::Invalid(format!(
+                "Watchlist names must be 1-{} characters",
+                MAX_NAME_LENGTH
+            )));
+        }
+        let mut members: Vec<String> = Vec::new();
+        for wallet in wallets {
+            let wallet = service::parse_wallet(wallet)?.to_string();
+            if !members.contains(&wallet) {
+                members.push(wallet);
+            }
+        }
+        if members.is_empty() || members.len() > MAX_WALLETS {
+            return Err(ServiceError::Invalid(format!(
+                "A watchlist holds 1-{} wallets",
+                MAX_WALLETS
+            )));
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.values().filter(|w| w.owner == owner).count() >= MAX_WATCHLISTS_PER_OWNER {
+            return Err(ServiceError::Invalid(format!(
+                "At most {} watchlists per credential",
+                MAX_WATCHLISTS_PER_OWNER
+            )));
+        }
+        let watchlist = Watchlist {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+            name: name.to_string(),
+            wallets: members,
+            owner: owner.to_string(),
+            created_at_ms: now_ms(),
+        };
+        entries.insert(watchlist.id, watchlist.clone());
+        if let Err(e) = self.save(&entries) {
+            entries.remove(&watchlist.id);
+            return Err(ServiceError::Rpc {
+                action: "Failed to save watchlist",
+                message: e,
+            });
+        }
+        Ok(watchlist)
+    }
+
+    pub fn list(&self, owner: &str) -> Vec<Watchlist> {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|w| w.owner == owner)
+            .cloned()
+            .collect()
+    }
+
+    // Members of every owner's lists.
+    pub fn wallets(&self) -> BTreeSet<Pubkey> {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .flat_map(|w| &w.wallets)
+            .filter_map(|w| service::parse_wallet(w).ok())
+            .collect()
+    }
+
+    // None for lists that don't exist or belong to someone else.
+    pub fn get(&self, owner: &str, id: u64) -> Option<Watchlist> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&id)
+            .filter(|w| w.owner == owner)
+            .cloned()
+    }
+
+    pub fn remove(&self, owner: &str, id: u64) -> Result<bool, String> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.get(&id).is_none_or(|w| w.owner != owner) {
+            return Ok(false);
+        }
+        let Some(previous) = entries.remove(&id) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.save(&entries) {
+            entries.insert(id, previous);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn save(&self, entries: &BTreeMap<u64, Watchlist>) -> Result<(), String> {
+        if !self.store.persists(STORE_NAME) {
+            return Ok(());
+        }
+        let watchlists: Vec<&Watchlist> = entries.values().collect();
+        let text = serde_json::to_string_pretty(&watchlists).map_err(|e| e.to_string())?;
+        self.store.write(STORE_NAME, &text)
+    }
+}
+
+struct Holdings {
+    lamports: u64,
+    tokens: BTreeMap<String, TokenHolding>,
+    activity: Vec<Activity>,
+}
+
+fn load_wallet(config: &Config, wallet: &str) -> Result<Holdings, ServiceError> {
+    let rpc_url = &config.rpc_url;
+    let pubkey = service::parse_wallet(wallet)?;
+    let lamports = service::get_balance(rpc_url, wallet)?;
+
+    let client = rpc::client(rpc_url);
+    let _span = tracing::info_span!("rpc.watchlist_wallet", %rpc_url, %wallet).entered();
+    let tokens = diff::token_holdings(&client, wallet)?;
+    let activity = client
+        .get_signatures_for_address_with_config(
+            &pubkey,
+            GetConfirmedSignaturesForAddress2Config {
+                limit: Some(ACTIVITY_PER_WALLET),
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..Default::default()
+            },
+        )
+        .map_err(rpc_error("Failed to get signatures"))?
+        .into_iter()
+        .map(|sig| Activity {
+            explorer_urls: links::transaction(config, &sig.signature),
+            wallet: wallet.to_string(),
+            signature: sig.signature,
+            slot: sig.slot,
+            block_time: sig.block_time,
+            succeeded: sig.err.is_none(),
+            memo: sig.memo,
+        })
+        .collect();
+    Ok(Holdings {
+        lamports,
+        tokens,
+        activity,
+    })
+}
+
+// Loads every member, fanned out within `limits`, and sums their
+// holdings. A member that fails is reported but doesn't fail the overview.
+pub async fn overview(config: Arc<Config>, limits: Limits, watchlist: Watchlist) -> Overview {
+    let results = fanout::run(limits, watchlist.wallets.clone(), move |wallet| {
+        load_wallet(&config, &wallet)
+    })
+    .await;
+    let failures = fanout::failures(&results);
+
+    let mut total_lamports: u64 = 0;
+    let mut tokens: BTreeMap<String, (u8, u128, usize)> = BTreeMap::new();
+    let mut recent_activity = Vec::new();
+    let mut members = Vec::new();
+    for (wallet, result) in watchlist.wallets.into_iter().zip(results) {
+        match result {
+            Ok(holdings) => {
+                total_lamports = total_lamports.saturating_add(holdings.lamports);
+                for (mint, holding) in holdings.tokens {
+                    let total = tokens.entry(mint).or_insert((holding.decimals, 0, 0));
+                    total.1 = total.1.saturating_add(holding.amount);
+                    total.2 += 1;
+                }
+                recent_activity.extend(holdings.activity);
+                members.push(Member {
+                    wallet,
+                    balance_lamports: Some(holdings.lamports),
+                    error: None,
+                });
+            }
+            Err(e) => members.push(Member {
+                wallet,
+                balance_lamports: None,
+                error: Some(e.to_string()),
+            }),
+        }
+    }
+    recent_activity.sort_by_key(|a| std::cmp::Reverse(a.slot));
+    recent_activity.truncate(RECENT_ACTIVITY);
+
+    Overview {
+        id: watchlist.id,
+        name: watchlist.name,
+        total_lamports,
+        total_sol_decimal: amount::lamports_to_sol_decimal(total_lamports),
+        tokens: tokens
+            .into_iter()
+            .map(|(mint, (decimals, amount, holders))| TokenTotal {
+                mint,
+                decimals,
+                amount: amount.to_string(),
+                holders,
+            })
+            .collect(),
+        recent_activity,
+        members,
+        failures,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{FlakyStore, MemoryStore};
+
+    fn wallets(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|_| Pubkey::new_unique().to_string())
+            .collect()
+    }
+
+    fn invalid(result: Result<Watchlist, ServiceError>) -> String {
+        match result {
+            Err(ServiceError::Invalid(message)) => message,
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn create_validates_and_dedupes() {
+        let store = WatchlistStore::load(Arc::new(MemoryStore::default())).unwrap();
+        let members = wallets(2);
+        let doubled = [members.clone(), members.clone()].concat();
+        let watchlist = store.create("alice", "  Treasury  ", &doubled).unwrap();
+        assert_eq!((watchlist.id, watchlist.name.as_str()), (1, "Treasury"));
+        assert_eq!(watchlist.wallets, members);
+
+        assert_eq!(
+            invalid(store.create("alice", " ", &members)),
+            "Watchlist names must be 1-64 characters"
+        );
+        assert_eq!(
+            invalid(store.create("alice", "Big", &wallets(101))),
+            "A watchlist holds 1-100 wallets"
+        );
+        assert_eq!(
+            invalid(store.create("alice", "Empty", &[])),
+            "A watchlist holds 1-100 wallets"
+        );
+        assert!(store.create("alice", "Bad", &["nope".to_string()]).is_err());
+    }
+
+    #[test]
+    fn lists_are_private_to_their_owner() {
+        let store = WatchlistStore::load(Arc::new(MemoryStore::default())).unwrap();
+        let mine = store.create("alice", "Mine", &wallets(1)).unwrap();
+        let theirs = store.create("bob", "Theirs", &wallets(2)).unwrap();
+
+        assert_eq!(store.list("alice").len(), 1);
+        assert!(store.get("alice", theirs.id).is_none());
+        assert_eq!(store.remove("alice", theirs.id), Ok(false));
+        assert_eq!(store.wallets().len(), 3);
+        assert_eq!(store.remove("alice", mine.id), Ok(true));
+        assert!(store.list("alice").is_empty());
+
+        for i in 0..MAX_WATCHLISTS_PER_OWNER {
+            store
+                .create("carol", &format!("List {}", i), &wallets(1))
+                .unwrap();
+        }
+        assert_eq!(
+            invalid(store.create("carol", "One more", &wallets(1))),
+            "At most 20 watchlists per credential"
+        );
+    }
+
+    #[test]
+    fn ids_survive_a_reload_and_failed_saves_roll_back() {
+        let backing = Arc::new(FlakyStore::default());
+        let store = WatchlistStore::load(backing.clone()).unwrap();
+        store.create("alice", "One", &wallets(1)).unwrap();
+        let two = store.create("alice", "Two", &wallets(1)).unwrap();
+
+        backing.fail(true);
+        assert!(matches!(
+            store.create("alice", "Three", &wallets(1)),
+            Err(ServiceError::Rpc {
+                action: "Failed to save watchlist",
+                ..
+            })
+        ));
+        assert_eq!(store.remove("alice", two.id), Err("disk full".to_string()));
+        assert_eq!(store.list("alice").len(), 2);
+        backing.fail(false);
+
+        let reloaded = WatchlistStore::load(backing).unwrap();
+        assert_eq!(reloaded.get("alice", two.id).unwrap().name, "Two");
+        // The failed create used id 3; after a reload ids continue from
+        // the highest saved one.
+        assert_eq!(reloaded.create("alice", "Four", &wallets(1)).unwrap().id, 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn overview_sums_members_and_reports_failures() {
+        let chain = rpc::mock_for_tests();
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        chain.set_balance(a, 1_500_000_000);
+        chain.set_balance(b, 500_000_000);
+        let config = Arc::new(Config::default());
+        let watchlist = Watchlist {
+            id: 7,
+            name: "Team".to_string(),
+            wallets: vec![a.to_string(), "not-a-wallet".to_string(), b.to_string()],
+            owner: "alice".to_string(),
+            created_at_ms: 0,
+        };
+
+        let overview = overview(config.clone(), Limits::new(&config), watchlist).await;
+        assert_eq!(overview.total_lamports, 2_000_000_000);
+        assert_eq!(overview.total_sol_decimal, "2");
+        assert_eq!(overview.members[0].balance_lamports, Some(1_500_000_000));
+        assert!(overview.members[1].error.is_some());
+        assert_eq!(overview.members[2].wallet, b.to_string());
+        assert_eq!((overview.failures.calls, overview.failures.failed), (3, 1));
+        assert!(overview.tokens.is_empty());
+    }
+}